@@ -9,6 +9,12 @@ const COMPLETION_DIR: &str = "../completions";
 const APP_NAME: &str = "swww";
 
 fn main() -> Result<(), Error> {
+    #[cfg(feature = "heif")]
+    pkg_config::Config::new()
+        .atleast_version("1.16")
+        .probe("libheif")
+        .unwrap();
+
     let outdir = completion_dir()?;
     let mut app = Swww::command();
 
@@ -0,0 +1,187 @@
+# English messages of the swww client. This is also the template for translations:
+# copy it to <language>.ftl (e.g. pt_BR.ftl) and translate the text after each `=`,
+# leaving the ids and the { $placeholders } as they are.
+
+## swww
+providers-disabled = cannot use '@{ $provider }': swww was compiled without the `providers` feature
+avif-disabled = cannot decode AVIF images: swww was compiled without the `avif` feature
+svg-disabled = cannot display SVG images: swww was compiled without the `svg` feature
+heif-disabled = cannot decode HEIF images: swww was compiled without the `heif` feature
+zstd-disabled = cannot use --cache-compress: swww was compiled without the `zstd` feature
+error = Error: { $error }
+clean-cache-failed = failed to clean the cache: { $error }
+unexpected-answer = Daemon did not return { $expected }, as expected
+dynamic-completions-unsupported = dynamic completions are only available for bash, zsh and fish, not { $shell }
+start-daemon-failed = failed to start { $daemon }: { $error }
+daemon-exited-early = swww-daemon exited early ({ $status })
+daemon-start-timeout = timed out waiting for swww-daemon to start
+invalid-transition-preset = invalid transition preset: { $error }
+bad-output-transition = --output-transition must be <outputs>:<flags>, e.g. 'DP-1:--transition-type wipe'
+output-transition-not-transition = --output-transition only takes transition flags, not { $flag }
+request-built = request built in { $elapsed }
+daemon-answered = daemon answered in { $elapsed }
+transition-clamped = WARNING: the daemon changed the transition { $name } of { $outputs } from { $requested } to { $used }
+transition-used = { $outputs }: { $transition_type } transition of { $duration }s at { $fps } fps
+socket-not-deleted = Could not confirm socket deletion at: { $path }
+var-not-set = variable { $key } is not set
+daemon-refused = swww-daemon refused the request: { $error }
+daemon-lacks-feature = swww-daemon was built without the `{ $feature }` feature, which the transition plugin { $path } needs
+validation-failed = failed to run validation command: { $error }
+rejected = image { $path } was rejected by '{ $cmd }' ({ $status })
+rejected-keep = WARNING: image { $path } was rejected by '{ $cmd }' ({ $status }). Keeping the current wallpaper
+rejected-fallback = WARNING: image { $path } was rejected by '{ $cmd }' ({ $status }). Displaying the fallback image instead
+image-decoded = decoded image in { $elapsed }
+banding = WARNING: { $path } has smooth gradients that will show banding at 8 bits per channel. Use `--dither auto` to hide them
+canonicalize-failed = failed to canonicalize image path: { $error }
+cache-load-failed = Error loading cache for { $path }: { $error }
+invalid-cutout = invalid cutout '{ $value }': expected '<x>,<y>,<width>,<height>[,letterbox|mirror]'
+processed = { $outputs } at { $width }x{ $height }: { $size }, processed in { $elapsed }
+processed-animation = { $outputs } at { $width }x{ $height }: { $size }, animation of { $frames } frames, processed in { $elapsed }
+processed-cached-animation = { $outputs } at { $width }x{ $height }: { $size }, animation of { $frames } frames (from the cache), processed in { $elapsed }
+request-size = request size: { $size }
+no-valid-outputs = none of the requested outputs are valid
+previous-image-failed = failed to get previous image path: { $error }
+restore-failed = WARNING: failed to load cache for output { $output }: { $error }
+
+## connecting to the daemon
+no-socket = Socket file not found. Are you sure swww-daemon is running?
+connect-failed = failed to connect to socket
+daemon-timeout = timed out waiting for an answer. Is swww-daemon stuck?
+
+## image decoding
+read-stdin-failed = failed to read standard input: { $error }
+read-file-failed = failed to read file: { $error }
+detect-format-failed = failed to detect the image's format: { $error }
+decode-format-failed = failed to decode { $format } image: { $error }
+detect-apng-failed = failed to detect if PNG is animated: { $error }
+unknown-format = Unknown image format
+decode-failed = failed to decode image: { $error }
+read-dir-failed = failed to read directory { $path }: { $error }
+empty-sequence = { $path } has no PNG or JPEG frames
+stack-weights-mismatch = got { $weights } stack weights for { $images } images
+stack-color = only images can be stacked, not colors
+stack-too-small = a { $width }x{ $height } output is too small to stack these images
+decode-mask-failed = failed to decode the transition mask { $path }: { $error }
+decode-animation-failed = failed to decode { $format } during animation: { $error }
+no-decoder = requested format has no decoder: { $format }
+avif-failed = failed to decode AVIF image: { $error }
+svg-failed = failed to render SVG image: { $error }
+heif-failed = failed to decode HEIF image: { $error }
+tiff-failed = failed to decode TIFF page: { $error }
+tiff-unsupported = TIFF pages with { $color } colors are not supported
+tiff-truncated = a TIFF page has less data than its size needs
+page-missing = there is no page { $page }, the image has { $pages }
+time-of-day-invalid = the image's time of day metadata is invalid: { $error }
+unexpected-date-output = could not read the time of day from date: '{ $output }'
+icc-invalid = not an ICC profile
+icc-not-rgb = only RGB profiles are supported
+icc-not-matrix = only matrix/TRC profiles are supported
+icc-ignored = WARNING: ignoring the image's color profile: { $error }
+read-color-profile-failed = failed to read color profile { $path }: { $error }
+no-quantization = image has { $bits } bits per channel, no quantization needed
+quantization-dithering = image has { $bits } bits per channel, with { $gradients }% smooth gradients (banding-prone above { $threshold }%): dithering
+quantization-not-dithering = image has { $bits } bits per channel, with { $gradients }% smooth gradients (banding-prone above { $threshold }%): not dithering
+position-out-of-range = Warning: { $axis } value not in range [0,1] position might be set outside screen: { $value }
+
+## swww cache
+read-cache-dir-failed = failed to read the cache directory: { $error }
+nothing-to-export = there are no cached animation frames for { $image }. Display it with `swww img` first
+copy-failed = failed to copy { $path }: { $error }
+write-manifest-failed = failed to write manifest: { $error }
+exported = exported { $name }
+read-manifest-failed = failed to read the bundle's manifest: { $error }
+not-a-bundle = not a swww bundle
+bundle-version-mismatch = bundle was made by swww { $version }, but this is swww { $ours }. Both machines must run the same version
+bundle-image-mismatch = bundle was made from a different image than { $image }
+invalid-animation-name = invalid animation name in manifest: { $name }
+animation-corrupted = animation { $name } is corrupted
+invalid-manifest-line = invalid line in manifest: { $line }
+manifest-missing-line = the bundle's manifest has no { $kind } line
+manifest-repeated-line = the bundle's manifest has more than one { $kind } line
+cache-dir-failed = failed to get the cache directory: { $error }
+import-failed = failed to import { $name }: { $error }
+imported = imported { $name }
+run-failed = failed to run { $program }: { $error }
+tar-failed = tar failed: { $error }
+read-failed = failed to read { $path }: { $error }
+temp-dir-failed = failed to create temporary directory: { $error }
+
+## swww now-playing
+album-art-failed = WARNING: failed to display album art: { $error }
+open-album-art-failed = failed to open album art: { $error }
+save-album-art-failed = failed to save blurred album art: { $error }
+
+## swww picture-frame
+picture-frame-failed = WARNING: picture frame: { $error }
+picture-frame-no-pictures = none of the given paths contain pictures
+
+## swww screencast-watch
+screencast-nothing-to-do = nothing to do: pass --pause and/or --presentation
+read-dbus-monitor-failed = failed to read dbus-monitor output: { $error }
+screencast-started = screencast started
+screencast-stopped = screencast stopped
+dbus-monitor-exited = dbus-monitor exited unexpectedly
+restore-wallpaper-failed = WARNING: failed to restore wallpaper of output { $output }: { $error }
+
+## swww workspace-tint
+workspace-tint-nothing-to-do = nothing to do: pass --tint and/or --filter
+unknown-compositor = could not detect the compositor. Pass --compositor hyprland or --compositor sway
+no-hyprland-signature = $HYPRLAND_INSTANCE_SIGNATURE is not set. Is Hyprland running?
+command-failed = { $program } failed ({ $status })
+connect-compositor-failed = failed to connect to { $path }: { $error }
+read-compositor-events-failed = failed to read the compositor's events: { $error }
+compositor-events-ended = the compositor stopped sending events
+workspace-tint-failed = WARNING: failed to tint output { $output }: { $error }
+
+## swww stream
+stream-failed = failed to share the stream with the daemon: { $error }
+stream-stopped = the daemon stopped showing the stream
+
+## swww shader
+shader-unknown-language = { $path } is neither WGSL (.wgsl) nor GLSL (.glsl)
+
+## transition presets
+unknown-preset = unknown transition preset '{ $name }'. The builtin ones are: { $builtin }
+presets-file-invalid = invalid presets file { $path }: { $error }
+preset-line = line { $line }: { $error }
+preset-bad-line = expected `[<preset name>]` or `<key> = <value>` inside a preset
+preset-unknown-key = unknown key '{ $key }'. Keys are the ones of `swww transition-defaults set`
+
+## swww preview-transition
+preview-simple = Warning: random and plugin transitions are previewed as 'simple', since only the daemon can run them
+preview-first-frame = Warning: only the first frame of animated images is used in previews
+preview-zero-fps = --transition-fps must be greater than 0
+preview-create-failed = failed to create { $path }: { $error }
+preview-save-failed = failed to save the preview: { $error }
+preview-saved = saved { $frames } frames to { $path }
+
+## swww batch
+batch-line = line { $line }: { $error }
+cannot-batch = `{ $command }` cannot be batched. Only `img`, `clear`, `set-var`, `color-filter` and `dim` can
+batch-if-usage = expected `if <condition> then <command> [else <command>]`
+batch-nested-if = `if` cannot be nested
+batch-bad-condition = unknown condition `{ $condition }`. Expected `output exists <output>`, `current-image [on <output>] <==|!=> <path>` or `var <name> == <value>`
+batch-bad-operator = unknown operator `{ $operator }`. Expected `==` or `!=`
+
+## swww fifo
+fifo-listening = reading commands from { $path }
+fifo-command-failed = { $command }: { $error }
+fifo-create-failed = failed to create { $path }: { $error }
+fifo-nested = cannot run `swww fifo` from a fifo
+not-a-fifo = { $path } exists, but is not a named pipe
+no-runtime-dir = $XDG_RUNTIME_DIR is not set. Pass --path instead
+unclosed-quote = unclosed quote
+
+## wallpaper providers
+unknown-provider = unknown provider '@{ $name }'. Available providers are: { $providers }
+bing-no-url = could not find the image url in bing's response
+wikimedia-no-potd = could not find the picture of the day in wikimedia's response
+read-rss-failed = failed to read rss feed { $feed }: { $error }
+rss-no-image = could not find any image in rss feed { $feed }
+providers-cache-dir-failed = failed to create providers cache directory: { $error }
+fetch-failed-warning = WARNING: failed to fetch image from '@{ $name }': { $error }
+fetch-fallback = falling back to { $path }
+fetch-failed = failed to fetch image from '@{ $name }': { $error }
+invalid-utf8 = response is not valid utf8: { $error }
+refusing-download = refusing to download from { $url }
+store-download-failed = failed to store downloaded image: { $error }
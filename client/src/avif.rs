@@ -0,0 +1,127 @@
+//! AVIF decoding, through libavif (the `avif` feature).
+//!
+//! `image` can only decode still AVIFs, and only with dav1d, so we go to libavif ourselves for
+//! both still and animated ones. The frames are converted to 8 bit RGBA, like `image` gives us
+//! for every other format, so they go through the same resizing and compression.
+use std::{ffi::CStr, marker::PhantomData, time::Duration};
+
+use image::{
+    error::{DecodingError, ImageFormatHint},
+    Delay, DynamicImage, Frame, ImageError, ImageFormat, ImageResult, RgbaImage,
+};
+use libavif_sys as sys;
+
+use crate::messages::msg;
+
+/// Decodes the frames of an AVIF, in order
+pub struct Decoder<'a> {
+    decoder: *mut sys::avifDecoder,
+    /// libavif reads straight from the bytes we were given
+    bytes: PhantomData<&'a [u8]>,
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(bytes: &'a [u8]) -> Result<Self, String> {
+        // SAFETY: the decoder is destroyed when we are dropped, and `bytes` outlive it
+        unsafe {
+            let decoder = sys::avifDecoderCreate();
+            if decoder.is_null() {
+                return Err(msg!("avif-failed", error = "out of memory"));
+            }
+            let decoder = Self {
+                decoder,
+                bytes: PhantomData,
+            };
+            check(sys::avifDecoderSetIOMemory(
+                decoder.decoder,
+                bytes.as_ptr(),
+                bytes.len(),
+            ))?;
+            check(sys::avifDecoderParse(decoder.decoder))?;
+            Ok(decoder)
+        }
+    }
+
+    pub fn is_animated(&self) -> bool {
+        // SAFETY: the decoder was parsed successfully in `new`
+        unsafe { (*self.decoder).imageCount > 1 }
+    }
+
+    /// The first frame, for still images
+    pub fn into_image(mut self) -> Result<DynamicImage, String> {
+        match self.next_frame()? {
+            Some(frame) => Ok(DynamicImage::ImageRgba8(frame.into_buffer())),
+            None => Err(msg!("avif-failed", error = "the file has no images")),
+        }
+    }
+
+    fn next_frame(&mut self) -> Result<Option<Frame>, String> {
+        // SAFETY: `image` points to the frame we just decoded, which stays valid until the next
+        // call to `avifDecoderNextImage`. The rgb pixels are allocated for its dimensions
+        unsafe {
+            match sys::avifDecoderNextImage(self.decoder) {
+                sys::AVIF_RESULT_NO_IMAGES_REMAINING => return Ok(None),
+                result => check(result)?,
+            }
+            let image = (*self.decoder).image;
+            let mut rgb: sys::avifRGBImage = std::mem::zeroed();
+            sys::avifRGBImageSetDefaults(&mut rgb, image);
+            rgb.format = sys::AVIF_RGB_FORMAT_RGBA;
+            rgb.depth = 8;
+            check(sys::avifRGBImageAllocatePixels(&mut rgb))?;
+            let converted = check(sys::avifImageYUVToRGB(image, &mut rgb));
+            let pixels = converted.map(|()| {
+                let row = rgb.width as usize * 4;
+                let mut pixels = Vec::with_capacity(row * rgb.height as usize);
+                for y in 0..rgb.height as usize {
+                    let start = rgb.pixels.add(y * rgb.rowBytes as usize);
+                    pixels.extend_from_slice(std::slice::from_raw_parts(start, row));
+                }
+                pixels
+            });
+            let (width, height) = (rgb.width, rgb.height);
+            sys::avifRGBImageFreePixels(&mut rgb);
+
+            let buffer = RgbaImage::from_raw(width, height, pixels?)
+                .expect("we copied exactly width * height pixels");
+            let duration = Duration::from_secs_f64((*self.decoder).imageTiming.duration.max(0.0));
+            Ok(Some(Frame::from_parts(
+                buffer,
+                0,
+                0,
+                Delay::from_saturating_duration(duration),
+            )))
+        }
+    }
+}
+
+impl Iterator for Decoder<'_> {
+    type Item = ImageResult<Frame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_frame()
+            .map_err(|e| {
+                ImageError::Decoding(DecodingError::new(
+                    ImageFormatHint::Exact(ImageFormat::Avif),
+                    e,
+                ))
+            })
+            .transpose()
+    }
+}
+
+impl Drop for Decoder<'_> {
+    fn drop(&mut self) {
+        // SAFETY: we created it in `new`, and nothing else holds it
+        unsafe { sys::avifDecoderDestroy(self.decoder) }
+    }
+}
+
+fn check(result: sys::avifResult) -> Result<(), String> {
+    if result == sys::AVIF_RESULT_OK {
+        return Ok(());
+    }
+    // SAFETY: libavif returns static strings for every result
+    let error = unsafe { CStr::from_ptr(sys::avifResultToString(result)) };
+    Err(msg!("avif-failed", error = error.to_string_lossy()))
+}
@@ -0,0 +1,169 @@
+//! Implements `swww batch`.
+//!
+//! We build the request of every command just like `swww` would on its own, and then pack all of
+//! them into a single one (see `RequestSend::batch`). Nothing is sent unless every command is
+//! valid.
+//!
+//! Lines starting with `if` hold a condition, which the daemon checks when it gets to them (see
+//! `RequestSend::condition`), so scripts do not race with other clients between checking and
+//! acting.
+use std::{ffi::OsString, io::Read, path::Path};
+
+use clap::Parser;
+use common::ipc::{Condition, RequestSend};
+
+use crate::cli::{Batch, Swww};
+use crate::error::{Error, ErrorKind};
+use crate::fifo::split_words;
+use crate::messages::msg;
+
+pub fn make_request(args: &Batch) -> Result<RequestSend, Error> {
+    let script = match args.file.as_deref() {
+        Some(path) if path != Path::new("-") => std::fs::read_to_string(path)
+            .map_err(|e| msg!("read-failed", path = path.display(), error = e))?,
+        _ => {
+            let mut script = String::new();
+            std::io::stdin()
+                .read_to_string(&mut script)
+                .map_err(|e| msg!("read-stdin-failed", error = e))?;
+            script
+        }
+    };
+
+    let mut requests = Vec::new();
+    for (i, line) in script.lines().enumerate() {
+        let context = |e: Error| Error::new(e.kind(), msg!("batch-line", line = i + 1, error = e));
+        let args = split_words(line).map_err(|e| context(e.into()))?;
+        // blank lines and comments
+        if args.is_empty() {
+            continue;
+        }
+        let request = if args[0] == "if" {
+            condition_request(&args[1..]).map(Some)
+        } else {
+            command_request(args)
+        };
+        requests.extend(request.map_err(context)?);
+    }
+    Ok(RequestSend::batch(requests))
+}
+
+/// `args` is `<condition> then <command> [else <command>]`
+fn condition_request(args: &[String]) -> Result<RequestSend, Error> {
+    let usage = || Error::new(ErrorKind::Usage, msg!("batch-if-usage"));
+    let then = args
+        .iter()
+        .position(|arg| arg == "then")
+        .ok_or_else(usage)?;
+    let condition = parse_condition(&args[..then])?;
+    let (then, otherwise) = match args[then + 1..].iter().position(|arg| arg == "else") {
+        Some(i) => (&args[then + 1..then + 1 + i], Some(&args[then + 2 + i..])),
+        None => (&args[then + 1..], None),
+    };
+    let branch = |args: &[String]| -> Result<Vec<RequestSend>, Error> {
+        match args.first().map(String::as_str) {
+            None => Err(usage()),
+            Some("if") => Err(Error::new(ErrorKind::Usage, msg!("batch-nested-if"))),
+            Some(_) => Ok(command_request(args.to_vec())?.into_iter().collect()),
+        }
+    };
+    let then = branch(then)?;
+    let otherwise = match otherwise {
+        Some(otherwise) => branch(otherwise)?,
+        None => Vec::new(),
+    };
+    Ok(RequestSend::condition(&condition, then, otherwise))
+}
+
+fn parse_condition(args: &[String]) -> Result<Condition, Error> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let (output, operator, path) = match args[..] {
+        ["output", "exists", output] => return Ok(Condition::OutputExists(output.to_string())),
+        ["var", name, "==", value] => {
+            return Ok(Condition::Var(name.to_string(), value.to_string()))
+        }
+        ["current-image", operator, path] => (None, operator, path),
+        ["current-image", "on", output, operator, path] => (Some(output), operator, path),
+        _ => {
+            return Err(Error::new(
+                ErrorKind::Usage,
+                msg!("batch-bad-condition", condition = args.join(" ")),
+            ))
+        }
+    };
+    let equal = match operator {
+        "==" => true,
+        "!=" => false,
+        _ => {
+            return Err(Error::new(
+                ErrorKind::Usage,
+                msg!("batch-bad-operator", operator = operator),
+            ))
+        }
+    };
+    // the daemon knows images by their canonical path
+    let path = Path::new(path)
+        .canonicalize()
+        .map_or_else(|_| path.to_string(), |path| path.display().to_string());
+    Ok(Condition::CurrentImage {
+        output: output.map(str::to_string),
+        path,
+        equal,
+    })
+}
+
+/// `None` if there is nothing to do, e.g. when `--validate` rejected the image of an `img`
+fn command_request(args: Vec<String>) -> Result<Option<RequestSend>, Error> {
+    let args: Vec<OsString> = args.into_iter().map(OsString::from).collect();
+    let swww = Swww::try_parse_from(std::iter::once(OsString::from("swww")).chain(args.clone()))
+        .map_err(|e| Error::new(ErrorKind::Usage, e.to_string()))?;
+    let swww = match swww {
+        Swww::Img(_) => Swww::Img(crate::with_transition_defaults(args)?),
+        Swww::Clear(_)
+        | Swww::SetVar(_)
+        | Swww::ColorFilter(_)
+        | Swww::Dim(_)
+        | Swww::Pause
+        | Swww::Resume => swww,
+        _ => {
+            let command = args[0].to_string_lossy();
+            return Err(Error::new(
+                ErrorKind::Usage,
+                msg!("cannot-batch", command = command),
+            ));
+        }
+    };
+    crate::make_request(&swww)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(line: &str) -> Vec<String> {
+        split_words(line).unwrap()
+    }
+
+    #[test]
+    fn conditions_should_parse() {
+        assert_eq!(
+            parse_condition(&words("output exists DP-2")).unwrap(),
+            Condition::OutputExists("DP-2".to_string())
+        );
+        assert_eq!(
+            parse_condition(&words("current-image on DP-1 != /does/not/exist.png")).unwrap(),
+            Condition::CurrentImage {
+                output: Some("DP-1".to_string()),
+                path: "/does/not/exist.png".to_string(),
+                equal: false,
+            }
+        );
+        assert_eq!(
+            parse_condition(&words("var mode == 'focus time'")).unwrap(),
+            Condition::Var("mode".to_string(), "focus time".to_string())
+        );
+        assert!(parse_condition(&words("var mode focus")).is_err());
+        assert!(parse_condition(&words("current-image = a.png")).is_err());
+        assert!(parse_condition(&words("output DP-2")).is_err());
+    }
+}
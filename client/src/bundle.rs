@@ -0,0 +1,296 @@
+//! Implements `swww cache export` and `swww cache import`.
+//!
+//! A bundle is a tarball with the cached animation frames of one image, plus a `manifest` file
+//! describing them. Like our other external integrations, we do not link against any archive
+//! library: bundles are created and extracted by `tar`, which picks the compression from the
+//! bundle's extension (so `.tar.zst` gets zstd).
+//!
+//! The manifest is a plain text file, one entry per line:
+//!
+//! ```text
+//! swww-bundle 1
+//! version <swww version>
+//! image <size in bytes> <hash>
+//! frames <name> <size in bytes> <hash>
+//! ```
+//!
+//! There is one `frames` line per cached animation, where the name encodes the dimensions, pixel
+//! format and settings the frames were made with (see `cache::cached_animations`).
+use std::{
+    os::unix::fs::DirBuilderExt,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use common::cache;
+
+use crate::messages::msg;
+
+const MAGIC: &str = "swww-bundle 1";
+const MANIFEST: &str = "manifest";
+
+pub fn export(image: &Path, bundle: &Path) -> Result<(), String> {
+    let image = image
+        .canonicalize()
+        .map_err(|e| msg!("canonicalize-failed", error = e))?;
+    let animations =
+        cache::cached_animations(&image).map_err(|e| msg!("read-cache-dir-failed", error = e))?;
+    if animations.is_empty() {
+        return Err(msg!("nothing-to-export", image = image.display()));
+    }
+
+    let mut manifest = format!("{MAGIC}\nversion {}\n", env!("CARGO_PKG_VERSION"));
+    let (size, hash) = checksum(&image)?;
+    manifest.push_str(&format!("image {size} {hash:016x}\n"));
+
+    let dir = TempDir::new()?;
+    for (name, path) in &animations {
+        let (size, hash) = checksum(path)?;
+        manifest.push_str(&format!("frames {name} {size} {hash:016x}\n"));
+        std::fs::copy(path, dir.0.join(name))
+            .map_err(|e| msg!("copy-failed", path = path.display(), error = e))?;
+    }
+    std::fs::write(dir.0.join(MANIFEST), manifest)
+        .map_err(|e| msg!("write-manifest-failed", error = e))?;
+
+    tar(Command::new("tar")
+        .arg("--auto-compress")
+        .arg("--create")
+        .arg("--file")
+        .arg(bundle)
+        .arg("--directory")
+        .arg(&dir.0)
+        .arg(MANIFEST)
+        .args(animations.iter().map(|(name, _)| name)))?;
+
+    for (name, _) in &animations {
+        println!("{}", msg!("exported", name = name));
+    }
+    Ok(())
+}
+
+pub fn import(bundle: &Path, image: &Path) -> Result<(), String> {
+    let image = image
+        .canonicalize()
+        .map_err(|e| msg!("canonicalize-failed", error = e))?;
+
+    let dir = TempDir::new()?;
+    tar(Command::new("tar")
+        .arg("--extract")
+        .arg("--file")
+        .arg(bundle)
+        .arg("--directory")
+        .arg(&dir.0))?;
+
+    let manifest = std::fs::read_to_string(dir.0.join(MANIFEST))
+        .map_err(|e| msg!("read-manifest-failed", error = e))?;
+    let manifest = Manifest::parse(&manifest)?;
+
+    if manifest.version != env!("CARGO_PKG_VERSION") {
+        return Err(msg!(
+            "bundle-version-mismatch",
+            version = manifest.version,
+            ours = env!("CARGO_PKG_VERSION")
+        ));
+    }
+    let (size, hash) = manifest.image;
+    let (actual_size, actual_hash) = checksum(&image)?;
+    if size.parse() != Ok(actual_size) || hash != format!("{actual_hash:016x}") {
+        return Err(msg!("bundle-image-mismatch", image = image.display()));
+    }
+
+    let mut frames = Vec::new();
+    for (name, size, hash) in manifest.frames {
+        let path = dir.0.join(name);
+        let (actual_size, actual_hash) = checksum(&path)?;
+        if size.parse() != Ok(actual_size) || hash != format!("{actual_hash:016x}") {
+            return Err(msg!("animation-corrupted", name = name));
+        }
+        frames.push((name, path));
+    }
+
+    for (name, path) in frames {
+        let dest = cache::cached_animation_path(&image, name)
+            .map_err(|e| msg!("cache-dir-failed", error = e))?;
+        std::fs::copy(&path, &dest).map_err(|e| msg!("import-failed", name = name, error = e))?;
+        println!("{}", msg!("imported", name = name));
+    }
+    Ok(())
+}
+
+/// The entries of a bundle's manifest, still unchecked against the image and frames
+struct Manifest<'a> {
+    version: &'a str,
+    /// size and hash
+    image: (&'a str, &'a str),
+    /// name, size and hash
+    frames: Vec<(&'a str, &'a str, &'a str)>,
+}
+
+impl<'a> Manifest<'a> {
+    /// Parses the manifest, making sure it has exactly one `version` and one `image` line, so we
+    /// never copy any frames from a bundle we could not check
+    fn parse(manifest: &'a str) -> Result<Self, String> {
+        let mut lines = manifest.lines();
+        if lines.next() != Some(MAGIC) {
+            return Err(msg!("not-a-bundle"));
+        }
+
+        let (mut version, mut image, mut frames) = (None, None, Vec::new());
+        for line in lines {
+            let fields: Vec<&str> = line.split(' ').collect();
+            match fields.as_slice() {
+                ["version", v] => {
+                    if version.replace(*v).is_some() {
+                        return Err(msg!("manifest-repeated-line", kind = "version"));
+                    }
+                }
+                ["image", size, hash] => {
+                    if image.replace((*size, *hash)).is_some() {
+                        return Err(msg!("manifest-repeated-line", kind = "image"));
+                    }
+                }
+                ["frames", name, size, hash] => {
+                    if !is_valid_name(name) {
+                        return Err(msg!("invalid-animation-name", name = name));
+                    }
+                    frames.push((*name, *size, *hash));
+                }
+                _ => return Err(msg!("invalid-manifest-line", line = line)),
+            }
+        }
+
+        Ok(Self {
+            version: version.ok_or_else(|| msg!("manifest-missing-line", kind = "version"))?,
+            image: image.ok_or_else(|| msg!("manifest-missing-line", kind = "image"))?,
+            frames,
+        })
+    }
+}
+
+/// checks `name` looks like `<width>x<height>_<pixel format>[_<settings>]`, which also makes
+/// sure it cannot escape the cache directory
+fn is_valid_name(name: &str) -> bool {
+    let mut parts = name.split('_');
+    let dims = parts.next().and_then(|dims| dims.split_once('x'));
+    let format = parts.next();
+    dims.is_some_and(|(w, h)| w.parse::<u32>().is_ok() && h.parse::<u32>().is_ok())
+        && matches!(format, Some("Bgr" | "Rgb" | "Xbgr" | "Xrgb"))
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn tar(cmd: &mut Command) -> Result<(), String> {
+    let output = cmd
+        .output()
+        .map_err(|e| msg!("run-failed", program = "tar", error = e))?;
+    if !output.status.success() {
+        let error = String::from_utf8_lossy(&output.stderr);
+        return Err(msg!("tar-failed", error = error.trim()));
+    }
+    Ok(())
+}
+
+/// size and FNV-1a hash of a file. We do not need anything cryptographic here, just something
+/// stable across machines and rust versions to detect corruption and mismatched images
+fn checksum(path: &Path) -> Result<(usize, u64), String> {
+    let bytes =
+        std::fs::read(path).map_err(|e| msg!("read-failed", path = path.display(), error = e))?;
+    let hash = bytes.iter().fold(0xcbf29ce484222325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    });
+    Ok((bytes.len(), hash))
+}
+
+/// A temporary directory, removed when dropped
+struct TempDir(PathBuf);
+
+impl TempDir {
+    /// how many random names we try before giving up, if somebody else keeps taking them
+    const ATTEMPTS: usize = 16;
+
+    /// Creates a new directory with a random name, that only we can read, like `mkdtemp`. We never
+    /// reuse one that already exists, since anybody could have put it there for us to fill
+    fn new() -> Result<Self, String> {
+        let mut builder = std::fs::DirBuilder::new();
+        builder.mode(0o700);
+        let mut error = std::io::ErrorKind::AlreadyExists.into();
+        for _ in 0..Self::ATTEMPTS {
+            let name: String = (0..12).map(|_| fastrand::alphanumeric()).collect();
+            let path = std::env::temp_dir().join(format!("swww-bundle-{name}"));
+            match builder.create(&path) {
+                Ok(()) => return Ok(Self(path)),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => error = e,
+                Err(e) => {
+                    error = e;
+                    break;
+                }
+            }
+        }
+        Err(msg!("temp-dir-failed", error = error))
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::*;
+
+    #[test]
+    fn temp_dirs_should_be_new_and_private() {
+        let (a, b) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+        assert_ne!(a.0, b.0);
+        let mode = std::fs::metadata(&a.0).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+
+        let path = a.0.clone();
+        drop(a);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn names_should_not_escape_the_cache_dir() {
+        assert!(is_valid_name("1920x1080_Xrgb"));
+        assert!(is_valid_name("1920x1080_Bgr_fit"));
+        assert!(!is_valid_name("../x"));
+        assert!(!is_valid_name("1920x1080_Xrgb/../../x"));
+        assert!(!is_valid_name(""));
+        assert!(!is_valid_name("1920x1080_Argb"));
+        assert!(!is_valid_name("1920x_Xrgb"));
+    }
+
+    #[test]
+    fn manifests_need_one_version_and_one_image() {
+        let frames = "frames 1920x1080_Xrgb 10 0000000000000000";
+        let version = "version 1.0.0";
+        let image = "image 10 0000000000000000";
+        let manifest = |lines: &[&str]| format!("{MAGIC}\n{}\n", lines.join("\n"));
+
+        let ok = manifest(&[version, image, frames]);
+        let parsed = Manifest::parse(&ok).unwrap();
+        assert_eq!(parsed.version, "1.0.0");
+        assert_eq!(parsed.image, ("10", "0000000000000000"));
+        assert_eq!(
+            parsed.frames,
+            [("1920x1080_Xrgb", "10", "0000000000000000")]
+        );
+
+        for lines in [
+            &[image, frames][..],
+            &[version, frames],
+            &[frames],
+            &[version, version, image, frames],
+            &[version, image, image, frames],
+        ] {
+            assert!(Manifest::parse(&manifest(lines)).is_err(), "{lines:?}");
+        }
+        assert!(Manifest::parse(&format!("{version}\n{image}\n")).is_err());
+        assert!(Manifest::parse(&manifest(&[version, image, "frames ../x 10 0"])).is_err());
+    }
+}
@@ -1,6 +1,6 @@
 /// Note: this file only has basic declarations and some definitions in order to be possible to
 /// import it in the build script, to automate shell completion
-use clap::{Parser, ValueEnum};
+use clap::{Parser, Subcommand, ValueEnum};
 use std::path::PathBuf;
 
 fn from_hex(hex: &str) -> Result<[u8; 3], String> {
@@ -48,7 +48,7 @@ pub enum PixelFormat {
     Xrgb,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub enum Filter {
     Nearest,
     Bilinear,
@@ -74,7 +74,7 @@ impl std::str::FromStr for Filter {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 pub enum TransitionType {
     None,
     Simple,
@@ -90,6 +90,23 @@ pub enum TransitionType {
     Wipe,
     Wave,
     Grow,
+    Ripple,
+    Pixelate,
+    Blinds,
+    Dissolve,
+    Clock,
+    Cube,
+    Shatter,
+    Spiral,
+    Checkerboard,
+    Push,
+    ZoomBlur,
+    PixelSort,
+    Halftone,
+    DoubleExposure,
+    Sketch,
+    Luma,
+    Plugin,
 }
 
 impl std::str::FromStr for TransitionType {
@@ -111,8 +128,27 @@ impl std::str::FromStr for TransitionType {
             "wave" => Ok(Self::Wave),
             "random" => Ok(Self::Random),
             "fade" => Ok(Self::Fade),
+            "ripple" => Ok(Self::Ripple),
+            "pixelate" => Ok(Self::Pixelate),
+            "blinds" => Ok(Self::Blinds),
+            "dissolve" => Ok(Self::Dissolve),
+            "clock" => Ok(Self::Clock),
+            "cube" => Ok(Self::Cube),
+            "shatter" => Ok(Self::Shatter),
+            "spiral" => Ok(Self::Spiral),
+            "checkerboard" => Ok(Self::Checkerboard),
+            "push" => Ok(Self::Push),
+            "zoomblur" => Ok(Self::ZoomBlur),
+            "pixelsort" => Ok(Self::PixelSort),
+            "halftone" => Ok(Self::Halftone),
+            "doubleexposure" => Ok(Self::DoubleExposure),
+            "sketch" => Ok(Self::Sketch),
+            "luma" => Ok(Self::Luma),
+            "plugin" => Ok(Self::Plugin),
             _ => Err("unrecognized transition type.\nValid transitions are:\n\
-                     \tsimple | fade | left | right | top | bottom | wipe | grow | center | outer | random | wave\n\
+                     \tsimple | fade | left | right | top | bottom | wipe | grow | center | outer | random | wave | ripple |\n\
+                     \tpixelate | blinds | dissolve | clock | cube | shatter | spiral | checkerboard | push | zoomblur |\n\
+                     \tpixelsort | halftone | doubleexposure | sketch | luma | plugin\n\
                      see swww img --help for more details"),
         }
     }
@@ -137,11 +173,36 @@ impl CliPosition {
     }
 }
 
+/// Where the circles of the 'grow' transition start from
+#[derive(Clone)]
+pub enum GrowOrigins {
+    Points(Box<[CliPosition]>),
+    /// this many random points
+    Random(u8),
+}
+
+/// How many times an animation plays before stopping on its last frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Loops {
+    Infinite,
+    Times(u16),
+}
+
+/// Which page of multi-page TIFFs to show
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Page {
+    /// every page, played as an animation
+    All,
+    Number(std::num::NonZeroU16),
+}
+
 #[derive(Clone)]
 pub enum CliImage {
     Path(PathBuf),
     /// Single rgb color
     Color([u8; 3]),
+    /// Wallpaper provider (e.g. `@bing-potd`), without the `@`
+    Provider(String),
 }
 
 #[derive(Parser)]
@@ -169,19 +230,795 @@ pub enum Swww {
     ///as the animation frames of every gif ever set for a given version of `swww`.
     ClearCache,
 
+    ///Shares cached animation frames between machines.
+    ///
+    ///Processing big animations can take a long time on weak machines. With this, a more
+    ///powerful one can do it instead: display the animation there, export the bundle, and import
+    ///it on the weak machine. Both must use the same version of swww, and the animation will
+    ///only be used on outputs with the same resolution (and pixel format) it was processed for.
+    Cache(Cache),
+
     /// Sends an image (or animated gif) for the daemon to display.
     ///
     /// Use `-` to read from stdin
     Img(Img),
 
+    ///Starts the daemon if it is not running yet, then displays an image, like `swww img`.
+    ///
+    ///This lets you autostart swww with a single command. The daemon keeps running in the
+    ///background after this exits, and takes no options unless you give them with
+    ///`--daemon-arg`.
+    Run(Run),
+
     ///Kills the daemon
-    Kill,
+    Kill(Kill),
 
     ///Asks the daemon to print output information (names and dimensions).
     ///
     ///You may use this to find out valid values for the <swww-img --outputs> option. If you want
     ///more detailed information about your outputs, I would recommend trying wlr-randr.
     Query,
+
+    ///Pauses every animation, which holds its current frame until `swww resume`.
+    Pause,
+
+    ///Resumes the animations paused with `swww pause`.
+    Resume,
+
+    ///Asks the daemon what it supports.
+    ///
+    ///Prints the optional features the daemon was built with (like `wasm` and `gpu`, which
+    ///transition plugins need), and the wayland protocols it is using, with their versions.
+    Capabilities,
+
+    ///Prints the daemon's internal state, for bug reports.
+    ///
+    ///`swww debug surfaces` prints, for every output, whether the daemon may draw its next frame,
+    ///the frame callback it is waiting on, and which of its buffers the compositor still holds.
+    ///This tells animations stuck waiting on the compositor apart from ones stuck in the daemon.
+    Debug(Debug),
+
+    ///Stores a variable in the daemon.
+    ///
+    ///Variables are kept in memory for as long as the daemon runs. External scripts can use them
+    ///to feed state (e.g. cpu load, the currently playing track) into dynamic wallpapers.
+    SetVar(SetVar),
+
+    ///Prints the value of a variable previously stored with `swww set-var`.
+    ///
+    ///Fails if the variable was never set.
+    GetVar(GetVar),
+
+    ///Displays the album art of the currently playing track.
+    ///
+    ///Watches an MPRIS media player (through `playerctl`) and sets the album art of whatever is
+    ///playing as the wallpaper. This command runs until it is interrupted.
+    NowPlaying(NowPlaying),
+
+    ///Turns the screen into a digital picture frame.
+    ///
+    ///Cycles through a (by default shuffled) playlist of images, showing a plain color during
+    ///quiet hours. Meant to run unattended, e.g. on a Raspberry Pi: if the daemon goes away, the
+    ///frame waits for it to come back (or restarts it) and displays the current image again. This
+    ///command runs until it is interrupted.
+    PictureFrame(PictureFrame),
+
+    ///Applies an accessibility filter to the wallpaper of the specified outputs.
+    ///
+    ///Filters are applied by the daemon to everything it draws, including transitions and
+    ///animations, until they are changed again (use `none` to remove them).
+    ColorFilter(ColorFilter),
+
+    ///Dims the wallpaper of the specified outputs, blending it towards black (or another color).
+    ///
+    ///The dim is applied by the daemon after everything else, including filters, transitions and
+    ///animations, until it is changed again. This can even out monitors of different
+    ///brightness.
+    Dim(Dim),
+
+    ///Reacts to screen sharing sessions.
+    ///
+    ///Watches xdg-desktop-portal (through `dbus-monitor`) for screencasts. While one is active,
+    ///animations can be paused and/or a presentation wallpaper displayed instead of the current
+    ///one, which is restored once the screencast ends. This command runs until it is
+    ///interrupted.
+    ScreencastWatch(ScreencastWatch),
+
+    ///Tints the wallpaper according to the workspace displayed on each output.
+    ///
+    ///Watches the workspaces of Hyprland or Sway through their IPC, and applies the dim (see
+    ///`swww dim`) and/or color filter (see `swww color-filter`) configured for the workspace
+    ///each output is showing. This gives every workspace its own look with the same wallpaper,
+    ///without sending any new images. This command runs until it is interrupted.
+    WorkspaceTint(WorkspaceTint),
+
+    ///Displays raw video frames read from standard input.
+    ///
+    ///Frames are packed 8 bit RGB (e.g. what `ffmpeg -f rawvideo -pix_fmt rgb24` writes), of
+    ///the size given with `--size`, one right after the other. They are shared with the daemon,
+    ///which displays the newest one as they come, at most at its maximum frame rate. This command
+    ///runs until standard input ends, leaving the last frame on screen, or until the outputs get
+    ///another image.
+    Stream(Stream),
+
+    ///Displays a fragment shader as an animated wallpaper.
+    ///
+    ///The shader is drawn again every frame, on the gpu if the daemon was built with the `gpu`
+    ///feature. Otherwise, WGSL shaders are interpreted on the cpu, at a lower resolution. See
+    ///swww-shader(1) for what the shader must define. The shader runs until the outputs get
+    ///another image.
+    Shader(Shader),
+
+    ///Runs several commands at once.
+    ///
+    ///Reads commands from a file (or standard input), one per line, with the same grammar as the
+    ///command line and shell-like quoting, and sends all of them to the daemon in a single
+    ///request. The daemon runs them in order, without any other command in between. If any of them
+    ///is invalid, none are run.
+    Batch(Batch),
+
+    ///Runs the commands written to a named pipe.
+    ///
+    ///Every line written to the pipe is run as if it were the arguments to `swww` (e.g. `img
+    ///~/wall.png --outputs DP-1`), with shell-like quoting. This makes it easy to control swww from
+    ///anything that can write to a file. This command runs until it is interrupted.
+    Fifo(Fifo),
+
+    ///Changes the default transition parameters kept in the daemon.
+    ///
+    ///`swww img` uses these for every transition flag that was neither passed in the command line
+    ///nor set through an environment variable. This lets you try out different transitions
+    ///without touching your configuration.
+    TransitionDefaults(TransitionDefaults),
+
+    ///Saves the frames of a transition to a file, without displaying it.
+    ///
+    ///The transition runs in the same way it does in the daemon, from OLD to the image given
+    ///after it, with all the `swww img` transition flags. This lets you tune them without
+    ///flashing your desktop, and does not need the daemon to be running.
+    PreviewTransition(PreviewTransition),
+
+    ///Prints a shell completion script.
+    ///
+    ///These are the same scripts as in the `completions` directory, for the swww you are running.
+    ///With `--dynamic`, completing `--outputs` asks the daemon for the names of its outputs, so
+    ///it offers the ones of the machine you are on.
+    Completions(Completions),
+
+    ///Prints the names of the daemon's outputs, one per line, for dynamic completions.
+    #[command(name = "__complete-outputs", hide = true)]
+    CompleteOutputs,
+}
+
+#[derive(Parser)]
+pub struct Cache {
+    #[command(subcommand)]
+    pub action: CacheAction,
+}
+
+#[derive(Subcommand)]
+pub enum CacheAction {
+    /// Exports the cached animation frames of an image into a bundle
+    Export {
+        /// Image whose frames we should export
+        image: PathBuf,
+        /// Where to write the bundle. Its extension decides the compression (e.g. `.tar.zst`)
+        bundle: PathBuf,
+    },
+    /// Imports a bundle made by `swww cache export`
+    Import {
+        /// The bundle to import
+        bundle: PathBuf,
+        /// Where the bundle's image is in this machine
+        image: PathBuf,
+    },
+}
+
+#[derive(Parser)]
+pub struct Debug {
+    #[command(subcommand)]
+    pub action: DebugAction,
+}
+
+#[derive(Subcommand)]
+pub enum DebugAction {
+    /// Prints the state of every output's surface
+    Surfaces,
+}
+
+#[derive(Parser)]
+pub struct Completions {
+    /// The shell to complete in
+    pub shell: clap_complete::Shell,
+
+    /// Complete output names by asking the running daemon. Only for bash, zsh and fish
+    #[arg(long)]
+    pub dynamic: bool,
+}
+
+#[derive(Parser)]
+pub struct TransitionDefaults {
+    #[command(subcommand)]
+    pub action: TransitionDefaultsAction,
+}
+
+#[derive(Subcommand)]
+pub enum TransitionDefaultsAction {
+    /// Sets the given defaults, keeping the others as they are
+    Set(Box<TransitionDefaultsSet>),
+    /// Prints the current defaults
+    Show,
+    /// Forgets every default, going back to the builtin ones
+    Reset,
+}
+
+/// The transition defaults, as (name, `swww img` argument id, `swww img` flag). They are stored
+/// in the daemon as the `transition.<name>` variables
+pub const TRANSITION_DEFAULTS: [(&str, &str, &str); 40] = [
+    ("type", "transition_type", "--transition-type"),
+    ("step", "transition_step", "--transition-step"),
+    ("duration", "transition_duration", "--transition-duration"),
+    ("fps", "transition_fps", "--transition-fps"),
+    ("angle", "transition_angle", "--transition-angle"),
+    ("pos", "transition_pos", "--transition-pos"),
+    ("origins", "transition_origins", "--transition-origins"),
+    ("bezier", "transition_bezier", "--transition-bezier"),
+    ("spring", "transition_spring", "--transition-spring"),
+    ("curve", "transition_curve", "--transition-curve"),
+    ("wave", "transition_wave", "--transition-wave"),
+    ("feather", "transition_feather", "--transition-feather"),
+    (
+        "colorspace",
+        "transition_colorspace",
+        "--transition-colorspace",
+    ),
+    ("ripple", "transition_ripple", "--transition-ripple"),
+    (
+        "block-size",
+        "transition_block_size",
+        "--transition-block-size",
+    ),
+    ("slats", "transition_slats", "--transition-slats"),
+    (
+        "slat-direction",
+        "transition_slat_direction",
+        "--transition-slat-direction",
+    ),
+    ("seed", "transition_seed", "--transition-seed"),
+    (
+        "clock-direction",
+        "transition_clock_direction",
+        "--transition-clock-direction",
+    ),
+    (
+        "soft-edge",
+        "transition_soft_edge",
+        "--transition-soft-edge",
+    ),
+    ("axis", "transition_axis", "--transition-axis"),
+    ("fov", "transition_fov", "--transition-fov"),
+    ("shards", "transition_shards", "--transition-shards"),
+    ("gravity", "transition_gravity", "--transition-gravity"),
+    ("turns", "transition_turns", "--transition-turns"),
+    (
+        "thickness",
+        "transition_thickness",
+        "--transition-thickness",
+    ),
+    (
+        "tile-size",
+        "transition_tile_size",
+        "--transition-tile-size",
+    ),
+    ("mask", "transition_mask", "--transition-mask"),
+    ("softness", "transition_softness", "--transition-softness"),
+    (
+        "sort-threshold",
+        "transition_sort_threshold",
+        "--transition-sort-threshold",
+    ),
+    (
+        "sort-direction",
+        "transition_sort_direction",
+        "--transition-sort-direction",
+    ),
+    ("matrix", "transition_matrix", "--transition-matrix"),
+    (
+        "matrix-size",
+        "transition_matrix_size",
+        "--transition-matrix-size",
+    ),
+    ("blend", "transition_blend", "--transition-blend"),
+    ("overlay", "transition_overlay", "--transition-overlay"),
+    (
+        "edge-threshold",
+        "transition_edge_threshold",
+        "--transition-edge-threshold",
+    ),
+    ("plugin", "transition_plugin", "--transition-plugin"),
+    ("random", "transition_random", "--transition-random"),
+    (
+        "random-exclude",
+        "transition_random_exclude",
+        "--transition-random-exclude",
+    ),
+    ("invert-y", "invert_y", "--invert-y"),
+];
+
+/// See `swww img --help` for what each of these do
+#[derive(Parser)]
+pub struct TransitionDefaultsSet {
+    #[arg(long = "type", value_parser = validated(|s| s.parse::<TransitionType>()))]
+    pub transition_type: Option<String>,
+
+    #[arg(long = "step", value_parser = validated(|s| s.parse::<std::num::NonZeroU8>()))]
+    pub transition_step: Option<String>,
+
+    #[arg(long = "duration", value_parser = validated(|s| s.parse::<f32>()))]
+    pub transition_duration: Option<String>,
+
+    #[arg(long = "fps", value_parser = validated(|s| s.parse::<u16>()))]
+    pub transition_fps: Option<String>,
+
+    #[arg(long = "angle", value_parser = validated(|s| s.parse::<f64>()))]
+    pub transition_angle: Option<String>,
+
+    #[arg(long = "pos", value_parser = validated(parse_coords))]
+    pub transition_pos: Option<String>,
+
+    #[arg(long = "origins", value_parser = validated(parse_origins))]
+    pub transition_origins: Option<String>,
+
+    #[arg(long = "bezier", value_parser = validated(parse_bezier))]
+    pub transition_bezier: Option<String>,
+
+    #[arg(long = "spring", value_parser = validated(parse_spring))]
+    pub transition_spring: Option<String>,
+
+    #[arg(long = "curve", value_parser = validated(parse_curve))]
+    pub transition_curve: Option<String>,
+
+    #[arg(long = "wave", value_parser = validated(parse_wave))]
+    pub transition_wave: Option<String>,
+
+    #[arg(long = "feather", value_parser = validated(parse_feather))]
+    pub transition_feather: Option<String>,
+
+    #[arg(long = "colorspace", value_parser = validated(|s| TransitionColorspace::from_str(s, true)))]
+    pub transition_colorspace: Option<String>,
+
+    #[arg(long = "ripple", value_parser = validated(parse_ripple))]
+    pub transition_ripple: Option<String>,
+
+    #[arg(long = "block-size", value_parser = validated(|s| s.parse::<std::num::NonZeroU16>()))]
+    pub transition_block_size: Option<String>,
+
+    #[arg(long = "slats", value_parser = validated(|s| s.parse::<std::num::NonZeroU16>()))]
+    pub transition_slats: Option<String>,
+
+    #[arg(long = "slat-direction", value_parser = validated(|s| SlatDirection::from_str(s, true)))]
+    pub transition_slat_direction: Option<String>,
+
+    #[arg(long = "seed", value_parser = validated(|s| s.parse::<u32>()))]
+    pub transition_seed: Option<String>,
+
+    #[arg(long = "clock-direction", value_parser = validated(|s| ClockDirection::from_str(s, true)))]
+    pub transition_clock_direction: Option<String>,
+
+    #[arg(long = "soft-edge", value_parser = validated(parse_soft_edge))]
+    pub transition_soft_edge: Option<String>,
+
+    #[arg(long = "axis", value_parser = validated(|s| CubeAxis::from_str(s, true)))]
+    pub transition_axis: Option<String>,
+
+    #[arg(long = "fov", value_parser = validated(parse_fov))]
+    pub transition_fov: Option<String>,
+
+    #[arg(long = "shards", value_parser = validated(|s| s.parse::<std::num::NonZeroU16>()))]
+    pub transition_shards: Option<String>,
+
+    #[arg(long = "gravity", value_parser = validated(|s| s.parse::<f32>()))]
+    pub transition_gravity: Option<String>,
+
+    #[arg(long = "turns", value_parser = validated(parse_turns))]
+    pub transition_turns: Option<String>,
+
+    #[arg(long = "thickness", value_parser = validated(parse_thickness))]
+    pub transition_thickness: Option<String>,
+
+    #[arg(long = "tile-size", value_parser = validated(|s| s.parse::<std::num::NonZeroU16>()))]
+    pub transition_tile_size: Option<String>,
+
+    /// must be absolute, so that `show` names the same file from any directory
+    #[arg(long = "mask", value_parser = validated(parse_mask_default))]
+    pub transition_mask: Option<String>,
+
+    #[arg(long = "softness", value_parser = validated(parse_softness))]
+    pub transition_softness: Option<String>,
+
+    #[arg(long = "sort-threshold", value_parser = validated(parse_sort_threshold))]
+    pub transition_sort_threshold: Option<String>,
+
+    #[arg(long = "sort-direction", value_parser = validated(|s| SortDirection::from_str(s, true)))]
+    pub transition_sort_direction: Option<String>,
+
+    #[arg(long = "matrix", value_parser = validated(|s| HalftoneMatrix::from_str(s, true)))]
+    pub transition_matrix: Option<String>,
+
+    #[arg(long = "matrix-size", value_parser = validated(parse_matrix_size))]
+    pub transition_matrix_size: Option<String>,
+
+    #[arg(long = "blend", value_parser = validated(|s| TransitionBlend::from_str(s, true)))]
+    pub transition_blend: Option<String>,
+
+    #[arg(long = "overlay", value_parser = validated(parse_overlay))]
+    pub transition_overlay: Option<String>,
+
+    #[arg(long = "edge-threshold", value_parser = validated(parse_edge_threshold))]
+    pub transition_edge_threshold: Option<String>,
+
+    #[arg(long = "plugin")]
+    pub transition_plugin: Option<String>,
+
+    #[arg(long = "random", value_parser = validated(parse_random_weights))]
+    pub transition_random: Option<String>,
+
+    #[arg(long = "random-exclude", value_parser = validated(parse_random_exclude))]
+    pub transition_random_exclude: Option<String>,
+
+    #[arg(long = "invert-y", value_parser = validated(|s| s.parse::<bool>()))]
+    pub invert_y: Option<String>,
+}
+
+impl TransitionDefaultsSet {
+    /// the values that were set, in the same order as `TRANSITION_DEFAULTS`
+    pub fn values(&self) -> [Option<&str>; 40] {
+        [
+            self.transition_type.as_deref(),
+            self.transition_step.as_deref(),
+            self.transition_duration.as_deref(),
+            self.transition_fps.as_deref(),
+            self.transition_angle.as_deref(),
+            self.transition_pos.as_deref(),
+            self.transition_origins.as_deref(),
+            self.transition_bezier.as_deref(),
+            self.transition_spring.as_deref(),
+            self.transition_curve.as_deref(),
+            self.transition_wave.as_deref(),
+            self.transition_feather.as_deref(),
+            self.transition_colorspace.as_deref(),
+            self.transition_ripple.as_deref(),
+            self.transition_block_size.as_deref(),
+            self.transition_slats.as_deref(),
+            self.transition_slat_direction.as_deref(),
+            self.transition_seed.as_deref(),
+            self.transition_clock_direction.as_deref(),
+            self.transition_soft_edge.as_deref(),
+            self.transition_axis.as_deref(),
+            self.transition_fov.as_deref(),
+            self.transition_shards.as_deref(),
+            self.transition_gravity.as_deref(),
+            self.transition_turns.as_deref(),
+            self.transition_thickness.as_deref(),
+            self.transition_tile_size.as_deref(),
+            self.transition_mask.as_deref(),
+            self.transition_softness.as_deref(),
+            self.transition_sort_threshold.as_deref(),
+            self.transition_sort_direction.as_deref(),
+            self.transition_matrix.as_deref(),
+            self.transition_matrix_size.as_deref(),
+            self.transition_blend.as_deref(),
+            self.transition_overlay.as_deref(),
+            self.transition_edge_threshold.as_deref(),
+            self.transition_plugin.as_deref(),
+            self.transition_random.as_deref(),
+            self.transition_random_exclude.as_deref(),
+            self.invert_y.as_deref(),
+        ]
+    }
+}
+
+/// checks the value can be parsed, but keeps it as a string, so we can forward it to the daemon
+fn validated<T, E: std::fmt::Display>(
+    parse: impl Fn(&str) -> Result<T, E> + Clone + Send + Sync + 'static,
+) -> impl Fn(&str) -> Result<String, String> + Clone + Send + Sync + 'static {
+    move |raw| {
+        parse(raw)
+            .map(|_| raw.to_string())
+            .map_err(|e| e.to_string())
+    }
+}
+
+#[derive(Parser)]
+pub struct ScreencastWatch {
+    /// Pause animations while a screencast is active
+    #[arg(long)]
+    pub pause: bool,
+
+    /// Image (or hexcode starting with 0x) to display while a screencast is active
+    #[arg(long, value_parser = parse_image)]
+    pub presentation: Option<CliImage>,
+
+    /// Comma separated list of outputs to display the presentation wallpaper at.
+    ///
+    /// If it isn't set, it is displayed on all outputs.
+    #[arg(short, long, default_value = "")]
+    pub outputs: String,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Compositor {
+    /// Detect it from the environment
+    Auto,
+    Hyprland,
+    Sway,
+}
+
+#[derive(Parser)]
+pub struct WorkspaceTint {
+    /// Tint of a workspace, as <WORKSPACE>=<rrggbb>[:<AMOUNT>]. Can be given multiple times.
+    ///
+    /// The workspace is given by name. The amount goes from 0 to 1, like in `swww dim`, and
+    /// defaults to 0.2. Outputs showing workspaces without a tint are not dimmed.
+    #[arg(long, value_name = "WORKSPACE=COLOR[:AMOUNT]", value_parser = parse_workspace_tint)]
+    pub tint: Vec<(String, [u8; 3], f32)>,
+
+    /// Color filter of a workspace, as <WORKSPACE>=<FILTER>. Can be given multiple times.
+    ///
+    /// The filters are the same as in `swww color-filter`. Outputs showing workspaces without a
+    /// filter have none.
+    #[arg(long, value_name = "WORKSPACE=FILTER", value_parser = parse_workspace_filter)]
+    pub filter: Vec<(String, ColorFilterType)>,
+
+    /// The compositor whose workspaces we watch
+    #[arg(long, default_value = "auto")]
+    pub compositor: Compositor,
+}
+
+#[derive(Parser)]
+pub struct Batch {
+    /// File to read the commands from.
+    ///
+    /// Reads them from standard input if this is `-` or is not given. Only `img`, `clear`,
+    /// `set-var`, `color-filter` and `dim` can be batched.
+    pub file: Option<PathBuf>,
+}
+
+#[derive(Parser)]
+pub struct Fifo {
+    /// Path of the named pipe, which is created if it does not exist yet.
+    ///
+    /// Defaults to `$XDG_RUNTIME_DIR/swww.fifo`.
+    #[arg(long)]
+    pub path: Option<PathBuf>,
+}
+
+#[derive(Parser)]
+pub struct ColorFilter {
+    /// Filter to apply
+    pub filter: ColorFilterType,
+
+    /// Comma separated list of outputs to apply the filter to.
+    ///
+    /// If it isn't set, the filter is applied to all outputs, replacing any per-output filters.
+    #[arg(short, long, default_value = "")]
+    pub outputs: String,
+}
+
+#[derive(Parser)]
+pub struct Dim {
+    /// Comma separated list of outputs to dim, or `all`.
+    ///
+    /// `all` dims all outputs, replacing any per-output dims.
+    pub outputs: String,
+
+    /// How much to dim, from 0 (not at all) to 1 (entirely the dim's color)
+    #[arg(value_parser = parse_dim)]
+    pub amount: f32,
+
+    /// Color to dim towards.
+    ///
+    /// Must be given in rrggbb format (note there is no prepended '#').
+    #[arg(short, long, value_parser = from_hex, default_value = "000000")]
+    pub color: [u8; 3],
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ColorFilterType {
+    /// Removes the filter
+    None,
+    /// Inverts every color
+    Invert,
+    /// Increases the contrast
+    HighContrast,
+    /// Simulates how people with protanopia (red blindness) see the wallpaper
+    ProtanopiaSimulation,
+    /// Simulates how people with deuteranopia (green blindness) see the wallpaper
+    DeuteranopiaSimulation,
+    /// Simulates how people with tritanopia (blue blindness) see the wallpaper
+    TritanopiaSimulation,
+    /// Shifts colors hard to distinguish with protanopia into ones that are easier to see
+    ProtanopiaCompensation,
+    /// Shifts colors hard to distinguish with deuteranopia into ones that are easier to see
+    DeuteranopiaCompensation,
+    /// Shifts colors hard to distinguish with tritanopia into ones that are easier to see
+    TritanopiaCompensation,
+}
+
+#[derive(Parser)]
+pub struct NowPlaying {
+    /// Comma separated list of outputs to display the album art at.
+    ///
+    /// If it isn't set, the album art is displayed on all outputs.
+    #[arg(short, long, default_value = "")]
+    pub outputs: String,
+
+    /// Image (or hexcode starting with 0x) to display when nothing is playing, or when the
+    /// playing track has no local album art
+    ///
+    /// If it isn't set, the last album art is kept on screen.
+    #[arg(long, value_parser = parse_image)]
+    pub fallback: Option<CliImage>,
+
+    /// Name of the player to watch, as understood by `playerctl --player`
+    ///
+    /// If it isn't set, `playerctl` picks the first player it finds.
+    #[arg(short, long)]
+    pub player: Option<String>,
+
+    /// How often to ask the player what it is playing, in milliseconds
+    #[arg(long, env = "SWWW_NOW_PLAYING_INTERVAL", default_value = "1000")]
+    pub interval: u64,
+
+    /// How long a track must keep playing before its album art is displayed, in milliseconds
+    ///
+    /// This avoids flickering through every album when skipping tracks quickly.
+    #[arg(long, env = "SWWW_NOW_PLAYING_DEBOUNCE", default_value = "2000")]
+    pub debounce: u64,
+
+    /// Blurs the album art with the given standard deviation. 0 disables the blur
+    #[arg(long, default_value = "0")]
+    pub blur: f32,
+
+    /// Whether to resize the album art and the method by which to resize it
+    ///
+    /// The default, 'crop', expands the album art to cover the whole screen.
+    #[arg(long, default_value = "crop")]
+    pub resize: ResizeStrategy,
+}
+
+#[derive(Parser)]
+pub struct SetVar {
+    /// Name of the variable
+    #[arg(value_parser = parse_var_key)]
+    pub key: String,
+
+    /// Value to store
+    pub value: String,
+}
+
+#[derive(Parser)]
+pub struct GetVar {
+    /// Name of the variable
+    #[arg(value_parser = parse_var_key)]
+    pub key: String,
+}
+
+fn parse_var_key(raw: &str) -> Result<String, String> {
+    if raw.is_empty() {
+        return Err("variable name cannot be empty".to_string());
+    }
+    if let Some(c) = raw.chars().find(|c| c.is_whitespace() || *c == '=') {
+        return Err(format!("variable name cannot contain {c:?}"));
+    }
+    Ok(raw.to_string())
+}
+
+#[derive(Parser)]
+pub struct Stream {
+    /// Comma separated list of outputs to display the stream at.
+    ///
+    /// If it isn't set, the stream is displayed on all outputs.
+    #[arg(short, long, default_value = "")]
+    pub outputs: String,
+
+    /// Size of the frames, as <width>x<height>
+    ///
+    /// It must not be larger than any of the outputs. If it isn't set, the frames are the size of
+    /// the first output. Smaller frames are upscaled by the compositor.
+    #[arg(long, value_parser = parse_resolution)]
+    pub size: Option<(u32, u32)>,
+
+    /// How many frames the daemon can hold at once
+    ///
+    /// The daemon only keeps the newest frame, so more slots only help when it cannot show them
+    /// for a while (e.g. when animations are paused), at the cost of memory.
+    #[arg(long, default_value = "3", value_parser = clap::value_parser!(u32).range(1..=16))]
+    pub slots: u32,
+}
+
+#[derive(Parser)]
+pub struct Shader {
+    /// The shader's source: WGSL if its name ends in `.wgsl`, GLSL in the style of shadertoy if
+    /// it ends in `.glsl`
+    pub file: PathBuf,
+
+    /// Comma separated list of outputs to display the shader at.
+    ///
+    /// If it isn't set, the shader is displayed on all outputs.
+    #[arg(short, long, default_value = "")]
+    pub outputs: String,
+
+    /// How many frames to draw per second, at most
+    ///
+    /// The daemon's maximum frame rate still applies, and complex shaders may not keep up.
+    #[arg(long, default_value = "30", value_parser = clap::value_parser!(u16).range(1..))]
+    pub fps: u16,
+
+    /// Draw at 1/<DOWNSCALE> of the outputs' resolution, and let the compositor upscale it
+    ///
+    /// If it isn't set, the shader is drawn at full resolution on the gpu, and at a quarter of it
+    /// on the cpu.
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..=16))]
+    pub downscale: Option<u8>,
+}
+
+#[derive(Parser)]
+pub struct PictureFrame {
+    /// Images to show, or directories containing them.
+    ///
+    /// Directories are read again every time the playlist starts over, so new pictures are picked
+    /// up without restarting the frame. Subdirectories are not searched.
+    #[arg(required = true)]
+    pub paths: Vec<PathBuf>,
+
+    /// Comma separated list of outputs to display the pictures at.
+    ///
+    /// If it isn't set, the pictures are displayed on all outputs.
+    #[arg(short, long, default_value = "")]
+    pub outputs: String,
+
+    /// How long each picture stays on screen, in seconds
+    #[arg(long, env = "SWWW_PICTURE_FRAME_INTERVAL", default_value = "300")]
+    pub interval: u64,
+
+    /// Show the pictures in the order they were given (and directories in alphabetical order),
+    /// instead of shuffling them every time the playlist starts over
+    #[arg(long)]
+    pub no_shuffle: bool,
+
+    /// Time of day during which no pictures are shown, as HH:MM-HH:MM in local time
+    ///
+    /// The end may come before the start, for quiet hours over midnight (e.g. 22:30-07:00).
+    #[arg(long, value_parser = parse_quiet_hours)]
+    pub quiet_hours: Option<(u16, u16)>,
+
+    /// Color to fill the screen with during quiet hours, in rrggbb format
+    #[arg(long, value_parser = from_hex, default_value = "000000")]
+    pub quiet_color: [u8; 3],
+
+    /// Shell command to run when quiet hours start, e.g. to turn off the backlight
+    #[arg(long)]
+    pub quiet_command: Option<String>,
+
+    /// Shell command to run when quiet hours end, e.g. to turn the backlight back on
+    #[arg(long)]
+    pub wake_command: Option<String>,
+
+    /// Start `swww-daemon` whenever it is not running, instead of waiting for it to come back
+    #[arg(long)]
+    pub restart_daemon: bool,
+
+    /// Argument for `swww-daemon` when it is started by `--restart-daemon`. Can be given several
+    /// times
+    #[arg(long = "daemon-arg", value_name = "ARG", allow_hyphen_values = true)]
+    pub daemon_args: Vec<String>,
+
+    /// Whether to resize the pictures and the method by which to resize them
+    ///
+    /// The default, 'crop', expands the pictures to cover the whole screen.
+    #[arg(long, default_value = "crop")]
+    pub resize: ResizeStrategy,
 }
 
 #[derive(Parser)]
@@ -197,6 +1034,128 @@ pub struct Clear {
     /// If it isn't set, the image is displayed on all outputs.
     #[clap(short, long, default_value = "")]
     pub outputs: String,
+
+    /// Animate the wallpapers into the color, instead of changing them right away.
+    ///
+    /// Useful to soften theme switches in scripts.
+    #[arg(long, value_enum, default_value = "none")]
+    pub outro: Outro,
+
+    /// How long the outro takes, in seconds
+    #[arg(long, default_value = "1")]
+    pub outro_duration: f32,
+}
+
+#[derive(Parser)]
+pub struct Kill {
+    /// Animate the wallpapers into `--outro-color` before the daemon exits.
+    ///
+    /// `swww kill` waits for the outro to finish.
+    #[arg(long, value_enum, default_value = "none")]
+    pub outro: Outro,
+
+    /// How long the outro takes, in seconds
+    #[arg(long, default_value = "1")]
+    pub outro_duration: f32,
+
+    /// Color the outro ends in, in rrggbb format
+    #[arg(long, value_parser = from_hex, default_value = "000000")]
+    pub outro_color: [u8; 3],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Outro {
+    /// change right away
+    None,
+    /// fade into the color
+    Fade,
+    /// wipe the color in, the other way around from the default wipe transition
+    Wipe,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PrintRequest {
+    /// Print the request without sending it
+    Only,
+    /// Print the request and send it
+    Send,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SlatDirection {
+    Horizontal,
+    Vertical,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SortDirection {
+    /// sort along rows
+    Horizontal,
+    /// sort along columns
+    Vertical,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum HalftoneMatrix {
+    /// an ordered dithering matrix, drawing regular crosshatch patterns
+    Bayer,
+    /// blue noise, drawing an even grain
+    BlueNoise,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum StackDirection {
+    /// top to bottom
+    Vertical,
+    /// left to right
+    Horizontal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ClockDirection {
+    Clockwise,
+    CounterClockwise,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TransitionColorspace {
+    Srgb,
+    Linear,
+    Oklab,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TransitionBlend {
+    Screen,
+    Multiply,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CubeAxis {
+    Vertical,
+    Horizontal,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Dither {
+    /// Dither images with more than 8 bits per channel that have smooth gradients
+    Auto,
+    /// Dither every image with more than 8 bits per channel
+    #[default]
+    Always,
+    /// Never dither, warning about images that will show banding
+    Never,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ToneMap {
+    /// Compress the highlights smoothly, keeping the hue of bright colors
+    #[default]
+    Reinhard,
+    /// The filmic curve of the Academy Color Encoding System, with more contrast than 'reinhard'
+    Aces,
+    /// Cut off everything brighter than white
+    Clip,
 }
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, ValueEnum)]
@@ -223,9 +1182,47 @@ pub struct Restore {
     pub outputs: String,
 }
 
+#[derive(Parser)]
+pub struct Run {
+    ///An argument to pass to `swww-daemon` when starting it, e.g., `--daemon-arg=--no-cache`.
+    ///Can be given multiple times. Ignored if the daemon is already running
+    #[arg(long = "daemon-arg", value_name = "ARG", allow_hyphen_values = true)]
+    pub daemon_args: Vec<String>,
+
+    #[command(flatten)]
+    pub img: Img,
+}
+
+#[derive(Parser)]
+pub struct PreviewTransition {
+    /// The image the transition starts from
+    pub old: PathBuf,
+
+    /// Where to save the preview: an animated gif if it ends in `.gif`, otherwise a directory
+    /// where every frame is saved as a numbered png
+    #[arg(long, value_name = "FILE")]
+    pub out: PathBuf,
+
+    /// Size of the preview, as <WIDTH>x<HEIGHT>. Defaults to the size of OLD
+    #[arg(long, value_parser = parse_resolution)]
+    pub size: Option<(u32, u32)>,
+
+    #[command(flatten)]
+    pub img: Img,
+}
+
 #[derive(Parser)]
 pub struct Img {
-    /// Path of image or hexcode (starting with 0x) to display
+    /// Path of image, hexcode (starting with 0x) or wallpaper provider (starting with @) to display
+    ///
+    /// The path can also be a directory of numbered frames, played as an animation at `--fps`.
+    ///
+    /// Available providers are:
+    ///
+    /// bing-potd | wikimedia-potd | rss:<url or path>
+    ///
+    /// Providers download their image of the day (through curl) and cache it, falling back to
+    /// the last image they gave us when offline.
     #[arg(value_parser = parse_image)]
     pub image: CliImage,
 
@@ -256,6 +1253,26 @@ pub struct Img {
     #[arg(value_parser = from_hex, long, default_value = "000000")]
     pub fill_color: [u8; 3],
 
+    /// Another image to stack after this one, so they fill each output together. Can be repeated
+    ///
+    /// This is meant for very tall or wide outputs, like stacked virtual monitors. Each image gets
+    /// a strip of the output, and is resized into it according to `--resize`. Stacked images are
+    /// always still: animations only show their first frame. Note restoring from the cache only
+    /// brings back the first image
+    #[arg(long = "stack", value_name = "IMAGE")]
+    pub stack: Vec<PathBuf>,
+
+    /// Whether stacked images go top to bottom (vertical) or left to right (horizontal)
+    #[arg(long, default_value = "vertical")]
+    pub stack_direction: StackDirection,
+
+    /// Comma separated relative sizes of the stacked images' strips, starting with this image's
+    ///
+    /// For example, '2,1' gives two thirds of the output to the first image. By default, the
+    /// strips have the same size
+    #[arg(long, value_delimiter = ',', value_name = "WEIGHTS")]
+    pub stack_weights: Vec<std::num::NonZeroU32>,
+
     ///Filter to use when scaling images (run swww img --help to see options).
     ///
     ///Available options are:
@@ -278,7 +1295,9 @@ pub struct Img {
     ///Possible transitions are:
     ///
     ///none | simple | fade | left | right | top | bottom | wipe | wave | grow | center | any |
-    /// outer | random
+    /// outer | ripple | pixelate | blinds | dissolve | clock | cube | shatter | spiral |
+    /// checkerboard | push | zoomblur | pixelsort | halftone | doubleexposure | sketch | luma |
+    /// plugin | random
     ///
     ///The 'left', 'right', 'top' and 'bottom' options make the transition happen from that
     ///position to its opposite in the screen.
@@ -303,77 +1322,593 @@ pub struct Img {
     ///
     ///'outer' is the same as grow but the circle shrinks instead of growing.
     ///
-    ///Finally, 'random' will select a transition effect at random
+    ///'ripple' is like grow, but the circle's edge is made of concentric waves, controlled with
+    /// the `--transition-ripple` flag.
+    ///
+    ///'pixelate' turns the old image into a mosaic, whose blocks shrink as it turns into the new
+    /// image. The starting block size is controlled with the `--transition-block-size` flag, and
+    /// how fast they shrink with `--transition-bezier`.
+    ///
+    ///'blinds' reveals the new image through slats that open like venetian blinds. Their number
+    /// and direction are controlled with the `--transition-slats` and
+    /// `--transition-slat-direction` flags.
+    ///
+    ///'dissolve' switches the pixels to the new image one by one, in a pseudo-random order given
+    /// by the `--transition-seed` flag, like a film dissolve.
+    ///
+    ///'clock' sweeps a hand around the `--transition-pos` position, starting at 12 o'clock, with
+    /// the new image behind it. Control it with the `--transition-clock-direction` and
+    /// `--transition-soft-edge` flags.
+    ///
+    ///'cube' turns the old image away in perspective, like a face of a cube, bringing the new one
+    /// in on the next face. Control it with the `--transition-axis` and `--transition-fov` flags.
+    ///
+    ///'shatter' breaks the old image into triangular shards that fly away from the
+    /// `--transition-pos` position and fall, revealing the new image. Control it with the
+    /// `--transition-shards`, `--transition-gravity` and `--transition-seed` flags.
+    ///
+    ///'spiral' reveals the new image along a thick line spiraling out of the `--transition-pos`
+    /// position, starting at 12 o'clock and going clockwise. Control it with the
+    /// `--transition-turns` and `--transition-thickness` flags.
+    ///
+    ///'checkerboard' flips square tiles to the new image in a wave crossing the screen, first
+    /// every other tile and then the rest. Their size is controlled with the
+    /// `--transition-tile-size` flag, and the wave's direction with `--transition-angle`.
+    ///
+    ///'push' slides the new image in, pushing the old one out of the screen ahead of it, in the
+    /// direction of the `--transition-angle` flag.
+    ///
+    ///'zoomblur' zooms the old image in from the position of the `--transition-pos` flag,
+    /// blurring it along the rays coming out of that point, while the new image fades in.
+    ///
+    ///'pixelsort' sorts the bright runs of pixels of the old image by brightness, like glitch
+    /// art, one row after the other, before they snap to the new image. How bright the runs are
+    /// is controlled with the `--transition-sort-threshold` flag, and whether it sorts rows or
+    /// columns with `--transition-sort-direction`.
+    ///
+    ///'halftone' switches the pixels to the new image in the order of a threshold matrix tiled
+    /// over the screen, drawing halftone patterns on the way. The matrix is chosen with the
+    /// `--transition-matrix` flag, and its size with `--transition-matrix-size`.
+    ///
+    ///'doubleexposure' fades to the new image, overlaying both images on the way, like a double
+    /// exposure. How they are overlaid is controlled with the `--transition-blend` flag, and how
+    /// strong the overlay gets and for how long with `--transition-overlay`.
+    ///
+    ///'sketch' draws the new image's edges first, like a pencil sketch, and then fills in the rest
+    /// of it away from them. Which edges are drawn first is controlled with
+    /// `--transition-edge-threshold`.
+    ///
+    ///'luma' reveals the new image in the order of the brightness of a grayscale image, darkest
+    /// first, like a luma wipe. That is the image given with the `--transition-mask` flag or,
+    /// without it, the new image itself. How gradual the edge is can be controlled with
+    /// `--transition-softness`.
+    ///
+    ///'plugin' runs a transition plugin from the daemon's `$XDG_CONFIG_HOME/swww/transitions/`,
+    /// chosen with the `--transition-plugin` flag. See swww-daemon(1) for how to write one.
+    ///
+    ///Finally, 'random' makes the daemon pick one of the `--transition-random` transitions at
+    /// random, going in a random direction from a random position
     #[arg(short, long, env = "SWWW_TRANSITION", default_value = "simple")]
     pub transition_type: TransitionType,
 
-    ///How fast the transition approaches the new image.
+    ///Name of a set of transition flags to use, for every one not passed explicitly.
+    ///
+    ///The builtin presets are 'smooth' (a slow fade), 'snappy' (a quick wipe) and 'dramatic' (a
+    /// long grow from the center). You can define your own in `$XDG_CONFIG_HOME/swww/presets`, see
+    /// swww-img(1).
+    #[arg(long, env = "SWWW_TRANSITION_PRESET")]
+    pub transition_preset: Option<String>,
+
+    ///Gives some outputs a different transition, as <outputs>:<flags>. Can be given multiple times.
+    ///
+    ///The outputs are comma separated, and the flags are transition flags, quoted like in a
+    /// shell, e.g. 'HDMI-A-1:--transition-type fade --transition-duration 2'. Any other flag is an
+    /// error. Any transition flag they do not set is the same as for the other outputs.
+    #[arg(long, value_name = "OUTPUTS:FLAGS")]
+    pub output_transition: Vec<String>,
+
+    /// the outputs of each `--output-transition`, with the arguments they transition with
+    #[arg(skip)]
+    pub transition_groups: Vec<(Box<[String]>, Box<Img>)>,
+
+    /// the transition defaults the daemon fills in, since they were not given (see
+    /// `common::ipc::Transition::defaulted`)
+    #[arg(skip)]
+    pub transition_defaulted: u64,
+
+    ///How fast the transition approaches the new image.
+    ///
+    ///The transition logic works by adding or subtracting from the current rgb values until the
+    ///old image transforms in the new one. This controls by how much we add or subtract.
+    ///
+    ///Larger values will make the transition faster, but more abrupt. A value of 255 will always
+    ///switch to the new image immediately.
+    ///
+    /// This defaults to 2 when transition-type is 'simple', and 90 otherwise
+    #[arg(
+        long,
+        env = "SWWW_TRANSITION_STEP",
+        default_value = "90",
+        default_value_if("transition_type", "simple", "2")
+    )]
+    pub transition_step: std::num::NonZeroU8,
+
+    ///How long the transition takes to complete in seconds. The daemon clamps it to at most 60.
+    ///
+    ///Note that this doesn't work with the 'simple' transition
+    #[arg(long, env = "SWWW_TRANSITION_DURATION", default_value = "3")]
+    pub transition_duration: f32,
+
+    ///Frame rate for the transition effect. The daemon clamps it to at most 240, or to its --limits.
+    ///
+    ///Note there is no point in setting this to a value smaller than what your monitor supports.
+    ///
+    ///Also note this is **different** from the transition-step. That one controls by how much we
+    ///approach the new image every frame.
+    #[arg(long, env = "SWWW_TRANSITION_FPS", default_value = "30")]
+    pub transition_fps: u16,
+
+    ///This is used for the 'wipe', 'wave', 'checkerboard' and 'push' transitions. It controls the
+    /// angle of the wipe
+    ///
+    ///Note that the angle is in degrees, where '0' is right to left and '90' is top to bottom,
+    /// and '270' bottom to top
+    #[arg(long, env = "SWWW_TRANSITION_ANGLE", default_value = "45")]
+    pub transition_angle: f64,
+
+    ///This is only used for the 'grow', 'outer', 'ripple', 'clock', 'shatter', 'spiral' and 'zoomblur' transitions. It
+    /// controls the center of circle
+    /// (default is 'center').
+    ///
+    ///Position values can be given in both percentage values and pixel values:
+    ///  float values are interpreted as percentages and integer values as pixel values
+    ///  eg: 0.5,0.5 means 50% of the screen width and 50% of the screen height
+    ///      200,400 means 200 pixels from the left and 400 pixels from the bottom
+    ///
+    ///the value can also be an alias which will set the position accordingly):
+    /// 'center' | 'top' | 'left' | 'right' | 'bottom' | 'top-left' | 'top-right' | 'bottom-left' |
+    /// 'bottom-right'
+    #[arg(long, env = "SWWW_TRANSITION_POS", default_value = "center", value_parser=parse_coords)]
+    pub transition_pos: CliPosition,
+
+    ///only used for the 'grow' transition, to grow circles from several points at once instead of
+    /// from --transition-pos. The circles merge as they meet
+    ///
+    ///Takes positions like the ones of --transition-pos, separated by spaces, 'corners' for the
+    /// four corners of the screen, or 'random=<N>' for N random points, up to 64
+    ///
+    ///eg: "top-left bottom-right", "corners center" or "random=5"
+    #[arg(long, env = "SWWW_TRANSITION_ORIGINS", value_parser = parse_origins)]
+    pub transition_origins: Option<GrowOrigins>,
+
+    /// inverts the y position sent in 'transition_pos' flag
+    #[arg(long, env = "INVERT_Y", default_value = "false")]
+    pub invert_y: bool,
+
+    ///run the transition across all the outputs at once, as if they were a single screen, using
+    /// where they are in the compositor's layout. A wipe then flows from one output onto the next,
+    /// instead of crossing each of them on its own
+    ///
+    ///Only wipe, grow, outer, ripple, clock and spiral go across outputs. The other transitions
+    /// still run on each output on their own
+    #[arg(long, env = "SWWW_TRANSITION_SPAN", default_value = "false")]
+    pub transition_span: bool,
+
+    ///bezier curve to use for the transition
+    ///https://cubic-bezier.com is a good website to get these values from
+    ///
+    ///eg: 0.0,0.0,1.0,1.0 for linear animation
+    #[arg(long, env = "SWWW_TRANSITION_BEZIER", default_value = ".54,0,.34,.99", value_parser = parse_bezier)]
+    pub transition_bezier: (f32, f32, f32, f32),
+
+    ///moves the transition like a spring, instead of along the bezier curve, so it can overshoot
+    ///and settle back. Takes the spring's stiffness, damping and mass
+    ///
+    ///eg: 170,12,1 for a bouncy spring, 170,26,1 for one that barely overshoots. If the spring has
+    ///not settled by the end of --transition-duration, the transition snaps to the end
+    #[arg(long, env = "SWWW_TRANSITION_SPRING", value_parser = parse_spring)]
+    pub transition_spring: Option<(f32, f32, f32)>,
+
+    ///moves the transition through a series of keyframes, instead of along the bezier curve or
+    ///the spring. Each keyframe is <value>@<time>, where both go from 0 to 1 over the
+    ///transition, optionally followed by :<easing> for how it gets there from the previous one.
+    ///The easing is linear, ease, ease-in, ease-out, ease-in-out, or a bezier curve like in
+    ///--transition-bezier, and defaults to linear. The curve starts at 0@0, and ends at 1@1 if its
+    ///last keyframe is not at time 1
+    ///
+    ///eg: "0.1@0.3:ease-in 0.9@0.6 1.1@0.8:ease-out 1@1:ease-in-out" for slow-fast-slow-bounce
+    #[arg(long, env = "SWWW_TRANSITION_CURVE", value_parser = parse_curve)]
+    pub transition_curve: Option<Box<[CurveKeyframe]>>,
+
+    ///currently only used for 'wave' transition to control the width and height of each wave
+    #[arg(long, env = "SWWW_TRANSITION_WAVE", default_value = "20,20", value_parser = parse_wave)]
+    pub transition_wave: (f32, f32),
+
+    ///only used for the 'wipe', 'grow' and 'outer' transitions (and the directions that are
+    /// aliases of 'wipe'), to blend their moving edge over this many pixels behind it. 0 makes it
+    /// sharp. Wider edges avoid shimmering on high-DPI displays during slow transitions
+    #[arg(long, env = "SWWW_TRANSITION_FEATHER", default_value = "0", value_parser = parse_feather)]
+    pub transition_feather: f32,
+
+    ///only used for the 'fade' transition, to control the color space colors are blended in.
+    /// 'srgb' blends the bytes themselves, which darkens and desaturates the colors in between.
+    /// 'linear' keeps them as bright as the images, and 'oklab' also keeps them as saturated
+    #[arg(
+        long,
+        env = "SWWW_TRANSITION_COLORSPACE",
+        default_value = "srgb",
+        value_enum
+    )]
+    pub transition_colorspace: TransitionColorspace,
+
+    ///only used for the 'ripple' transition, to control the amplitude and wavelength of the
+    /// ripples, in pixels
+    #[arg(long, env = "SWWW_TRANSITION_RIPPLE", default_value = "20,60", value_parser = parse_ripple)]
+    pub transition_ripple: (f32, f32),
+
+    ///only used for the 'pixelate' transition, to control the size of the mosaic's blocks at the
+    /// beginning of the transition, in pixels
+    #[arg(long, env = "SWWW_TRANSITION_BLOCK_SIZE", default_value = "64")]
+    pub transition_block_size: std::num::NonZeroU16,
+
+    ///only used for the 'blinds' transition, to control how many slats there are
+    #[arg(long, env = "SWWW_TRANSITION_SLATS", default_value = "10")]
+    pub transition_slats: std::num::NonZeroU16,
+
+    ///only used for the 'blinds' transition, to control whether the slats are horizontal (opening
+    /// from top to bottom) or vertical (opening from left to right)
+    #[arg(
+        long,
+        env = "SWWW_TRANSITION_SLAT_DIRECTION",
+        default_value = "horizontal",
+        value_enum
+    )]
+    pub transition_slat_direction: SlatDirection,
+
+    ///only used for the 'dissolve' and 'shatter' transitions, to control the order the pixels
+    /// switch in, or the shards' shapes and flight. The same seed always gives the same result.
+    /// Default is a different one every time
+    #[arg(long, env = "SWWW_TRANSITION_SEED")]
+    pub transition_seed: Option<u32>,
+
+    ///only used for the 'clock' transition, to control which way its hand goes
+    #[arg(
+        long,
+        env = "SWWW_TRANSITION_CLOCK_DIRECTION",
+        default_value = "clockwise",
+        value_enum
+    )]
+    pub transition_clock_direction: ClockDirection,
+
+    ///only used for the 'clock' transition, to control the width of the blurry edge behind its
+    /// hand, in degrees. 0 makes it sharp
+    #[arg(long, env = "SWWW_TRANSITION_SOFT_EDGE", default_value = "10", value_parser = parse_soft_edge)]
+    pub transition_soft_edge: f32,
+
+    ///only used for the 'cube' transition, to control the axis it turns around. A vertical axis
+    /// turns it from left to right, a horizontal one from top to bottom
+    #[arg(
+        long,
+        env = "SWWW_TRANSITION_AXIS",
+        default_value = "vertical",
+        value_enum
+    )]
+    pub transition_axis: CubeAxis,
+
+    ///only used for the 'cube' transition, to control the camera's field of view, in degrees.
+    /// Wider fields of view make the perspective stronger
+    #[arg(long, env = "SWWW_TRANSITION_FOV", default_value = "60", value_parser = parse_fov)]
+    pub transition_fov: f32,
+
+    ///only used for the 'shatter' transition, to control roughly how many shards the old image
+    /// breaks into
+    #[arg(long, env = "SWWW_TRANSITION_SHARDS", default_value = "40")]
+    pub transition_shards: std::num::NonZeroU16,
+
+    ///only used for the 'shatter' transition, to control how fast the shards fall, in pixels per
+    /// second squared. Negative values make them fall up
+    #[arg(
+        long,
+        env = "SWWW_TRANSITION_GRAVITY",
+        default_value = "2000",
+        allow_negative_numbers = true
+    )]
+    pub transition_gravity: f32,
+
+    ///only used for the 'spiral' transition, to control how many times its line goes around
+    /// before reaching the farthest corner of the screen
+    #[arg(long, env = "SWWW_TRANSITION_TURNS", default_value = "5", value_parser = parse_turns)]
+    pub transition_turns: f32,
+
+    ///only used for the 'spiral' transition, to control how thick its line is, in pixels. Lines
+    /// thinner than the distance between two turns leave gaps, which close as the next turn goes
+    /// by
+    #[arg(long, env = "SWWW_TRANSITION_THICKNESS", default_value = "30", value_parser = parse_thickness)]
+    pub transition_thickness: f32,
+
+    ///only used for the 'checkerboard' transition, to control the size of its tiles, in pixels
+    #[arg(long, env = "SWWW_TRANSITION_TILE_SIZE", default_value = "64")]
+    pub transition_tile_size: std::num::NonZeroU16,
+
+    ///the grayscale image the 'luma' transition follows, instead of the brightness of the new
+    /// image. It is scaled and cropped to each output like `--resize crop` would, and converted to
+    /// grayscale if it has colors
+    #[arg(long, env = "SWWW_TRANSITION_MASK")]
+    pub transition_mask: Option<PathBuf>,
+
+    ///only used for the 'luma' transition, to control how gradual its edge is, from 0 (pixels
+    /// switch as soon as the threshold reaches their brightness) to 1 (they blend in while the
+    /// threshold goes over the mask's whole brightness range)
+    #[arg(long, env = "SWWW_TRANSITION_SOFTNESS", default_value = "0.1", value_parser = parse_softness)]
+    pub transition_softness: f32,
+
+    ///only used for the 'pixelsort' transition: how bright pixels must be, from 0 to 1, for it to
+    /// sort them. Lower thresholds sort longer runs, and 0 sorts whole lines
+    #[arg(long, env = "SWWW_TRANSITION_SORT_THRESHOLD", default_value = "0.25", value_parser = parse_sort_threshold)]
+    pub transition_sort_threshold: f32,
+
+    ///only used for the 'pixelsort' transition, to control whether it sorts rows (horizontal) or
+    /// columns (vertical)
+    #[arg(
+        long,
+        env = "SWWW_TRANSITION_SORT_DIRECTION",
+        default_value = "horizontal",
+        value_enum
+    )]
+    pub transition_sort_direction: SortDirection,
+
+    ///only used for the 'halftone' transition, to control the threshold matrix the pixels switch
+    /// in the order of
+    #[arg(
+        long,
+        env = "SWWW_TRANSITION_MATRIX",
+        default_value = "bayer",
+        value_enum
+    )]
+    pub transition_matrix: HalftoneMatrix,
+
+    ///only used for the 'halftone' transition, to control the size of its matrix, in pixels. It
+    /// must be a power of two, from 2 to 64
+    #[arg(long, env = "SWWW_TRANSITION_MATRIX_SIZE", default_value = "8", value_parser = parse_matrix_size)]
+    pub transition_matrix_size: u8,
+
+    ///only used for the 'doubleexposure' transition, to control how it overlays the images.
+    /// 'screen' lightens them, like two exposures of a film, and 'multiply' darkens them, like two
+    /// stacked slides
+    #[arg(
+        long,
+        env = "SWWW_TRANSITION_BLEND",
+        default_value = "screen",
+        value_enum
+    )]
+    pub transition_blend: TransitionBlend,
+
+    ///only used for the 'doubleexposure' transition, as <STRENGTH>,<LENGTH>: how much of the
+    /// overlay shows halfway through, and for how much of the transition it shows, both from 0 to
+    /// 1. The overlay eases in and out of its strength
+    #[arg(long, env = "SWWW_TRANSITION_OVERLAY", default_value = "1,0.6", value_parser = parse_overlay)]
+    pub transition_overlay: (f32, f32),
+
+    ///only used for the 'sketch' transition: how strong, from 0 to 1, an edge must be, compared to
+    /// the strongest one in the image, to be drawn first. Lower values draw more of the image as
+    /// edges
+    #[arg(long, env = "SWWW_TRANSITION_EDGE_THRESHOLD", default_value = "0.2", value_parser = parse_edge_threshold)]
+    pub transition_edge_threshold: f32,
+
+    ///the transition plugin the 'plugin' transition runs, required by it. This is the name of its
+    /// file in the daemon's `$XDG_CONFIG_HOME/swww/transitions/`, without the `.wasm`, `.wgsl`,
+    /// `.glsl` or `.so`
+    #[arg(
+        long,
+        env = "SWWW_TRANSITION_PLUGIN",
+        required_if_eq("transition_type", "plugin")
+    )]
+    pub transition_plugin: Option<String>,
+
+    ///only used for the 'random' transition: the transitions it picks from, with their weights,
+    /// as <transition>[=<weight>],... Transitions without a weight have a weight of 1
+    ///
+    ///It can pick simple, fade, wipe, wave, grow, outer, ripple, pixelate, blinds, dissolve,
+    /// clock, cube, shatter, spiral, checkerboard, push, zoomblur, pixelsort, halftone,
+    /// doubleexposure and sketch
+    ///
+    ///eg: grow=3,wipe,fade=2 picks 'grow' half of the time
+    #[arg(
+        long,
+        env = "SWWW_TRANSITION_RANDOM",
+        default_value = "simple,wipe,outer,grow",
+        value_parser = parse_random_weights
+    )]
+    pub transition_random: Box<[(TransitionType, u8)]>,
+
+    ///only used for the 'random' transition: comma separated transitions it must never pick, even
+    /// if they are in --transition-random. If nothing is left to pick from, it uses 'simple'
+    #[arg(long, env = "SWWW_TRANSITION_RANDOM_EXCLUDE", value_parser = parse_random_exclude)]
+    pub transition_random_exclude: Option<Box<[TransitionType]>>,
+
+    ///Frames of animated images whose delay is at or below this many milliseconds are displayed
+    ///for 100ms instead
+    ///
+    ///Many GIFs have frames with a delay of 0 or 10ms, which browsers display for 100ms. Without
+    ///this, such GIFs would play absurdly fast and keep a CPU core busy. Frames with no delay
+    ///at all are always displayed for 100ms, even when this is 0.
+    #[arg(long, env = "SWWW_FRAME_DELAY_CLAMP", default_value = "10")]
+    pub frame_delay_clamp: u64,
+
+    ///Smooths animated images up to this frame rate, by blending each frame into the next
+    ///
+    ///This is meant for low frame rate GIFs (10fps or so). Frames displayed for a quarter of a
+    ///second or longer are left as they are. Interpolated animations are cached like any other,
+    ///so only the first time takes longer (and more memory)
+    #[arg(long, env = "SWWW_INTERPOLATE", value_parser = clap::value_parser!(u16).range(1..))]
+    pub interpolate: Option<u16>,
+
+    ///How many times animated images play before stopping on their last frame: 'infinite',
+    ///'once', or a number of times
+    ///
+    ///By default, GIFs play as many times as their loop count says. Like in browsers, GIFs
+    ///without one play once. Other animations play forever.
+    #[arg(long = "loop", env = "SWWW_LOOP", value_parser = parse_loops)]
+    pub loops: Option<Loops>,
+
+    ///Page of multi-page TIFFs to show, from 1, or 'all' to play every page as an animation at
+    ///`--fps`
+    ///
+    ///Other images only have a first page.
+    #[arg(long, env = "SWWW_PAGE", default_value = "1", value_parser = parse_page)]
+    pub page: Page,
+
+    ///Frame rate of image sequences, given as a directory of numbered frames, and of the pages
+    ///of multi-page TIFFs played with `--page all`
+    ///
+    ///The PNG and JPEG files in the directory are played in the order of the last number in
+    ///their names (so `frame_9.png` comes before `frame_10.png`). `--frame-delay-clamp` only
+    ///applies to the delays stored in animated images, so high frame rates play as given
+    #[arg(
+        long,
+        env = "SWWW_FPS",
+        default_value = "24",
+        value_parser = clap::value_parser!(u16).range(1..)
+    )]
+    pub fps: u16,
+
+    ///Maximum resolution animated images are rendered at, as <WIDTH>x<HEIGHT>
+    ///
+    ///On outputs bigger than this, animations are decoded, stored and drawn at a lower
+    ///resolution, and the compositor upscales them to fill the output. This trades sharpness for
+    ///much lower CPU and memory usage. For example, '1920x1080' renders animations at half
+    ///resolution on a 4k output. Static images are not affected
+    #[arg(long, env = "SWWW_MAX_ANIM_RESOLUTION", value_parser = parse_resolution)]
+    pub max_anim_resolution: Option<(u32, u32)>,
+
+    ///Where you are, as <LATITUDE>,<LONGITUDE> in degrees (north and east are positive)
+    ///
+    ///Time of day wallpapers that follow the sun (dynamic HEIC wallpapers made for macOS) show
+    ///the image taken with the sun where it is in your sky. Without this, your longitude is
+    ///guessed from your time zone, at a latitude of 45 degrees north
+    #[arg(long, env = "SWWW_LOCATION", value_parser = parse_location)]
+    pub location: Option<(f64, f64)>,
+
+    ///Whether to dither images with more than 8 bits per channel
+    ///
+    ///All pixel formats we can draw in have 8 bits per channel, so smooth gradients from 16 bit
+    ///images (common with renders and HDR photos) get reduced into visible bands. Dithering
+    ///hides those bands behind a fine (mostly imperceptible) noise
+    #[arg(long, env = "SWWW_DITHER", default_value = "always")]
+    pub dither: Dither,
+
+    ///How to bring the colors of HDR images (OpenEXR and Radiance HDR) into the range of the
+    ///outputs
+    ///
+    ///These images store light as it was rendered or photographed, often far brighter than
+    ///white. All pixel formats we can draw in have 8 bits per channel, so their highlights have
+    ///to be compressed (or clipped) into it. Other images are not affected
+    #[arg(long, env = "SWWW_TONE_MAP", default_value = "reinhard")]
+    pub tone_map: ToneMap,
+
+    ///ICC profile of the outputs, which the colors of images with an embedded ICC profile are
+    ///converted to
+    ///
+    ///Wide gamut photos (like the Display P3 ones phones take) look oversaturated unless their
+    ///colors are converted. Without this, the outputs are assumed to be sRGB
+    #[arg(long, env = "SWWW_COLOR_PROFILE")]
+    pub color_profile: Option<PathBuf>,
+
+    ///Compress the animation frames cached on disk with zstd
     ///
-    ///The transition logic works by adding or subtracting from the current rgb values until the
-    ///old image transforms in the new one. This controls by how much we add or subtract.
+    ///The cached frames of big animations can take hundreds of megabytes. Compressing them
+    ///usually makes them several times smaller, for a bit of cpu when they are cached and loaded.
+    ///Animations that are already cached are kept as they are. Needs the `zstd` feature
+    #[arg(long, env = "SWWW_CACHE_COMPRESS", default_value = "false")]
+    pub cache_compress: bool,
+
+    ///Print what decisions were made while processing the image, and how long it took
     ///
-    ///Larger values will make the transition faster, but more abrupt. A value of 255 will always
-    ///switch to the new image immediately.
+    ///This includes how long decoding and processing the image for each output took, how big
+    ///the request sent to the daemon is, and how long the daemon took to answer it
+    #[arg(short, long)]
+    pub verbose: bool,
+
+    ///Print the request as json, after applying defaults and aliases
     ///
-    /// This defaults to 2 when transition-type is 'simple', and 90 otherwise
+    ///This shows exactly what would be sent to the daemon: the transition after the environment
+    ///variables and `swww transition-defaults` were applied, the outputs the image goes to and
+    ///their dimensions, and so on. By itself, this flag does not send the request; use
+    ///`--print-request=send` to also display the image
     #[arg(
         long,
-        env = "SWWW_TRANSITION_STEP",
-        default_value = "90",
-        default_value_if("transition_type", "simple", "2")
+        value_enum,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "only"
     )]
-    pub transition_step: std::num::NonZeroU8,
+    pub print_request: Option<PrintRequest>,
 
-    ///How long the transition takes to complete in seconds.
+    ///Command that must approve images fetched from a provider before they are displayed
     ///
-    ///Note that this doesn't work with the 'simple' transition
-    #[arg(long, env = "SWWW_TRANSITION_DURATION", default_value = "3")]
-    pub transition_duration: f32,
+    ///The command is run through `sh -c`, with the image's path appended as its last argument.
+    ///The image is only displayed if the command exits successfully. Use this to, for example,
+    ///check the image's aspect ratio or run it through a content classifier.
+    #[arg(long, env = "SWWW_VALIDATE")]
+    pub validate: Option<String>,
 
-    ///Frame rate for the transition effect.
+    ///What to do when the --validate command rejects an image
     ///
-    ///Note there is no point in setting this to a value smaller than what your monitor supports.
+    ///Possible values are:
     ///
-    ///Also note this is **different** from the transition-step. That one controls by how much we
-    ///approach the new image every frame.
-    #[arg(long, env = "SWWW_TRANSITION_FPS", default_value = "30")]
-    pub transition_fps: u16,
-
-    ///This is used for the 'wipe' and 'wave' transitions. It controls the angle of the wipe
+    ///keep | error | <path/to/img or hexcode>
     ///
-    ///Note that the angle is in degrees, where '0' is right to left and '90' is top to bottom,
-    /// and '270' bottom to top
-    #[arg(long, env = "SWWW_TRANSITION_ANGLE", default_value = "45")]
-    pub transition_angle: f64,
+    ///'keep' leaves the current wallpaper untouched, 'error' also exits with an error, and
+    ///anything else is taken as an image (or hexcode starting with 0x) to display instead.
+    #[arg(
+        long,
+        env = "SWWW_VALIDATE_FALLBACK",
+        default_value = "keep",
+        value_parser = parse_validate_fallback
+    )]
+    pub validate_fallback: ValidateFallback,
+}
 
-    ///This is only used for the 'grow','outer' transitions. It controls the center of circle
-    /// (default is 'center').
-    ///
-    ///Position values can be given in both percentage values and pixel values:
-    ///  float values are interpreted as percentages and integer values as pixel values
-    ///  eg: 0.5,0.5 means 50% of the screen width and 50% of the screen height
-    ///      200,400 means 200 pixels from the left and 400 pixels from the bottom
-    ///
-    ///the value can also be an alias which will set the position accordingly):
-    /// 'center' | 'top' | 'left' | 'right' | 'bottom' | 'top-left' | 'top-right' | 'bottom-left' |
-    /// 'bottom-right'
-    #[arg(long, env = "SWWW_TRANSITION_POS", default_value = "center", value_parser=parse_coords)]
-    pub transition_pos: CliPosition,
+#[derive(Clone)]
+pub enum ValidateFallback {
+    Keep,
+    Error,
+    Image(CliImage),
+}
 
-    /// inverts the y position sent in 'transition_pos' flag
-    #[arg(long, env = "INVERT_Y", default_value = "false")]
-    pub invert_y: bool,
+fn parse_validate_fallback(raw: &str) -> Result<ValidateFallback, String> {
+    match raw {
+        "keep" => Ok(ValidateFallback::Keep),
+        "error" => Ok(ValidateFallback::Error),
+        _ => match parse_image(raw)? {
+            CliImage::Provider(_) => {
+                Err("the validation fallback cannot be another provider".to_string())
+            }
+            image => Ok(ValidateFallback::Image(image)),
+        },
+    }
+}
 
-    ///bezier curve to use for the transition
-    ///https://cubic-bezier.com is a good website to get these values from
-    ///
-    ///eg: 0.0,0.0,1.0,1.0 for linear animation
-    #[arg(long, env = "SWWW_TRANSITION_BEZIER", default_value = ".54,0,.34,.99", value_parser = parse_bezier)]
-    pub transition_bezier: (f32, f32, f32, f32),
+fn parse_resolution(raw: &str) -> Result<(u32, u32), String> {
+    let err = || format!("resolution must be <WIDTH>x<HEIGHT>, got '{raw}'");
+    let (width, height) = raw.split_once('x').ok_or_else(err)?;
+    match (width.trim().parse(), height.trim().parse()) {
+        (Ok(width), Ok(height)) if width > 0 && height > 0 => Ok((width, height)),
+        _ => Err(err()),
+    }
+}
 
-    ///currently only used for 'wave' transition to control the width and height of each wave
-    #[arg(long, env = "SWWW_TRANSITION_WAVE", default_value = "20,20", value_parser = parse_wave)]
-    pub transition_wave: (f32, f32),
+fn parse_location(raw: &str) -> Result<(f64, f64), String> {
+    let err = || format!("location must be <LATITUDE>,<LONGITUDE> in degrees, got '{raw}'");
+    let (latitude, longitude) = raw.split_once(',').ok_or_else(err)?;
+    match (latitude.trim().parse(), longitude.trim().parse()) {
+        (Ok(latitude), Ok(longitude))
+            if (-90.0..=90.0).contains(&latitude) && (-180.0..=180.0).contains(&longitude) =>
+        {
+            Ok((latitude, longitude))
+        }
+        _ => Err(err()),
+    }
 }
 
 fn parse_wave(raw: &str) -> Result<(f32, f32), String> {
@@ -388,6 +1923,174 @@ fn parse_wave(raw: &str) -> Result<(f32, f32), String> {
     Ok(parsed)
 }
 
+fn parse_ripple(raw: &str) -> Result<(f32, f32), String> {
+    let (amplitude, wavelength) = parse_wave(raw)?;
+    if !(amplitude >= 0.0 && amplitude.is_finite()) {
+        return Err(format!(
+            "Invalid ripple amplitude: {amplitude} (must be 0 or more)"
+        ));
+    }
+    if !(wavelength > 0.0 && wavelength.is_finite()) {
+        return Err(format!(
+            "Invalid ripple wavelength: {wavelength} (must be more than 0)"
+        ));
+    }
+    Ok((amplitude, wavelength))
+}
+
+fn parse_soft_edge(raw: &str) -> Result<f32, String> {
+    let soft_edge = raw.parse::<f32>().map_err(|e| e.to_string())?;
+    if !(0.0..=360.0).contains(&soft_edge) {
+        return Err(format!(
+            "Invalid soft edge: {soft_edge} (must be between 0 and 360 degrees)"
+        ));
+    }
+    Ok(soft_edge)
+}
+
+fn parse_fov(raw: &str) -> Result<f32, String> {
+    let fov = raw.parse::<f32>().map_err(|e| e.to_string())?;
+    if !(1.0..=179.0).contains(&fov) {
+        return Err(format!(
+            "Invalid field of view: {fov} (must be between 1 and 179 degrees)"
+        ));
+    }
+    Ok(fov)
+}
+
+fn parse_turns(raw: &str) -> Result<f32, String> {
+    let turns = raw.parse::<f32>().map_err(|e| e.to_string())?;
+    if !(turns > 0.0 && turns.is_finite()) {
+        return Err(format!("Invalid turns: {turns} (must be greater than 0)"));
+    }
+    Ok(turns)
+}
+
+fn parse_feather(raw: &str) -> Result<f32, String> {
+    let feather = raw.parse::<f32>().map_err(|e| e.to_string())?;
+    if !(feather >= 0.0 && feather.is_finite()) {
+        return Err(format!(
+            "Invalid feather: {feather} (must be 0 or more pixels)"
+        ));
+    }
+    Ok(feather)
+}
+
+fn parse_thickness(raw: &str) -> Result<f32, String> {
+    let thickness = raw.parse::<f32>().map_err(|e| e.to_string())?;
+    if !(thickness >= 0.0 && thickness.is_finite()) {
+        return Err(format!(
+            "Invalid thickness: {thickness} (must be 0 or more pixels)"
+        ));
+    }
+    Ok(thickness)
+}
+
+fn parse_dim(raw: &str) -> Result<f32, String> {
+    let amount = raw.parse::<f32>().map_err(|e| e.to_string())?;
+    if !(0.0..=1.0).contains(&amount) {
+        return Err(format!("Invalid dim: {amount} (must be between 0 and 1)"));
+    }
+    Ok(amount)
+}
+
+fn parse_workspace_tint(raw: &str) -> Result<(String, [u8; 3], f32), String> {
+    let (workspace, tint) = raw
+        .split_once('=')
+        .ok_or_else(|| format!("expected <WORKSPACE>=<rrggbb>[:<AMOUNT>], got '{raw}'"))?;
+    let (color, amount) = match tint.split_once(':') {
+        Some((color, amount)) => (color, parse_dim(amount)?),
+        None => (tint, 0.2),
+    };
+    Ok((workspace.to_string(), from_hex(color)?, amount))
+}
+
+fn parse_workspace_filter(raw: &str) -> Result<(String, ColorFilterType), String> {
+    let (workspace, filter) = raw
+        .split_once('=')
+        .ok_or_else(|| format!("expected <WORKSPACE>=<FILTER>, got '{raw}'"))?;
+    Ok((
+        workspace.to_string(),
+        ColorFilterType::from_str(filter, true)?,
+    ))
+}
+
+/// Parses `HH:MM-HH:MM` into the minutes of the day at which the quiet hours start and end
+fn parse_quiet_hours(raw: &str) -> Result<(u16, u16), String> {
+    let parse_time = |time: &str| {
+        let (hours, minutes) = time
+            .split_once(':')
+            .and_then(|(h, m)| Some((h.parse::<u16>().ok()?, m.parse::<u16>().ok()?)))
+            .filter(|&(h, m)| h < 24 && m < 60)
+            .ok_or_else(|| format!("Invalid time: '{time}' (expected HH:MM)"))?;
+        Ok::<_, String>(hours * 60 + minutes)
+    };
+    let (start, end) = raw
+        .split_once('-')
+        .ok_or_else(|| format!("expected <HH:MM>-<HH:MM>, got '{raw}'"))?;
+    Ok((parse_time(start)?, parse_time(end)?))
+}
+
+fn parse_softness(raw: &str) -> Result<f32, String> {
+    let softness = raw.parse::<f32>().map_err(|e| e.to_string())?;
+    if !(0.0..=1.0).contains(&softness) {
+        return Err(format!(
+            "Invalid softness: {softness} (must be between 0 and 1)"
+        ));
+    }
+    Ok(softness)
+}
+
+fn parse_sort_threshold(raw: &str) -> Result<f32, String> {
+    let threshold = raw.parse::<f32>().map_err(|e| e.to_string())?;
+    if !(0.0..=1.0).contains(&threshold) {
+        return Err(format!(
+            "Invalid sort threshold: {threshold} (must be between 0 and 1)"
+        ));
+    }
+    Ok(threshold)
+}
+
+fn parse_matrix_size(raw: &str) -> Result<u8, String> {
+    let size = raw.parse::<u8>().map_err(|e| e.to_string())?;
+    if !(2..=64).contains(&size) || !size.is_power_of_two() {
+        return Err(format!(
+            "Invalid matrix size: {size} (must be a power of two, from 2 to 64)"
+        ));
+    }
+    Ok(size)
+}
+
+fn parse_overlay(raw: &str) -> Result<(f32, f32), String> {
+    let (strength, length) = parse_wave(raw)?;
+    for (name, value) in [("strength", strength), ("length", length)] {
+        if !(0.0..=1.0).contains(&value) {
+            return Err(format!(
+                "Invalid overlay {name}: {value} (must be between 0 and 1)"
+            ));
+        }
+    }
+    Ok((strength, length))
+}
+
+fn parse_edge_threshold(raw: &str) -> Result<f32, String> {
+    let threshold = raw.parse::<f32>().map_err(|e| e.to_string())?;
+    if !(0.0..=1.0).contains(&threshold) {
+        return Err(format!(
+            "Invalid edge threshold: {threshold} (must be between 0 and 1)"
+        ));
+    }
+    Ok(threshold)
+}
+
+fn parse_mask_default(raw: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(raw);
+    if !path.is_absolute() {
+        return Err(format!("Invalid mask: {raw} (must be an absolute path)"));
+    }
+    Ok(path)
+}
+
 fn parse_bezier(raw: &str) -> Result<(f32, f32, f32, f32), String> {
     let mut iter = raw.split(',');
     let mut parse = || {
@@ -403,11 +2106,149 @@ fn parse_bezier(raw: &str) -> Result<(f32, f32, f32, f32), String> {
     Ok(parsed)
 }
 
+/// The transitions 'random' can pick, which are the ones that are neither aliases of others (like
+/// 'left') nor need the client to prepare something for them (like the mask of 'luma')
+const RANDOM_TRANSITIONS: [&str; 21] = [
+    "simple",
+    "fade",
+    "wipe",
+    "wave",
+    "grow",
+    "outer",
+    "ripple",
+    "pixelate",
+    "blinds",
+    "dissolve",
+    "clock",
+    "cube",
+    "shatter",
+    "spiral",
+    "checkerboard",
+    "push",
+    "zoomblur",
+    "pixelsort",
+    "halftone",
+    "doubleexposure",
+    "sketch",
+];
+
+fn parse_random_transition(raw: &str) -> Result<TransitionType, String> {
+    if !RANDOM_TRANSITIONS.contains(&raw) {
+        return Err(format!(
+            "'random' cannot pick {raw}. It can pick: {}",
+            RANDOM_TRANSITIONS.join(", ")
+        ));
+    }
+    raw.parse().map_err(str::to_string)
+}
+
+fn parse_random_weights(raw: &str) -> Result<Box<[(TransitionType, u8)]>, String> {
+    raw.split(',')
+        .map(|candidate| {
+            let (name, weight) = match candidate.split_once('=') {
+                Some((name, weight)) => (
+                    name,
+                    weight
+                        .parse::<u8>()
+                        .map_err(|e| format!("Invalid weight for {name}: {e}"))?,
+                ),
+                None => (candidate, 1),
+            };
+            Ok((parse_random_transition(name)?, weight))
+        })
+        .collect()
+}
+
+fn parse_random_exclude(raw: &str) -> Result<Box<[TransitionType]>, String> {
+    raw.split(',').map(parse_random_transition).collect()
+}
+
+const LINEAR: (f32, f32, f32, f32) = (0.0, 0.0, 1.0, 1.0);
+
+/// `common::ipc::CurveKeyframe`, which the build script, including this file, cannot see: a
+/// keyframe as (time, value, bezier), where the bezier eases from it to the next one
+pub type CurveKeyframe = (f32, f32, (f32, f32, f32, f32));
+
+fn parse_curve(raw: &str) -> Result<Box<[CurveKeyframe]>, String> {
+    let mut keyframes = vec![(0.0, 0.0, LINEAR)];
+    for keyframe in raw.split_whitespace() {
+        let (keyframe, easing) = match keyframe.split_once(':') {
+            Some((keyframe, easing)) => (keyframe, parse_easing(easing)?),
+            None => (keyframe, LINEAR),
+        };
+        let (value, time) = keyframe
+            .split_once('@')
+            .ok_or_else(|| format!("Invalid keyframe: {keyframe} (expected <value>@<time>)"))?;
+        let value = value.parse::<f32>().map_err(|e| e.to_string())?;
+        let time = time.parse::<f32>().map_err(|e| e.to_string())?;
+        if !value.is_finite() {
+            return Err(format!("Invalid keyframe value: {value}"));
+        }
+
+        let previous = keyframes.last_mut().unwrap();
+        if !(time > previous.0 && time <= 1.0) {
+            return Err(format!(
+                "Invalid keyframe time: {time} (must be after the previous keyframe's, and at most 1)"
+            ));
+        }
+        previous.2 = easing;
+        keyframes.push((time, value, LINEAR));
+    }
+
+    if keyframes.len() == 1 {
+        return Err("Empty curve".to_string());
+    }
+    if keyframes[keyframes.len() - 1].0 < 1.0 {
+        keyframes.push((1.0, 1.0, LINEAR));
+    }
+    Ok(keyframes.into_boxed_slice())
+}
+
+fn parse_easing(raw: &str) -> Result<(f32, f32, f32, f32), String> {
+    // the same curves as in css
+    match raw {
+        "linear" => Ok(LINEAR),
+        "ease" => Ok((0.25, 0.1, 0.25, 1.0)),
+        "ease-in" => Ok((0.42, 0.0, 1.0, 1.0)),
+        "ease-out" => Ok((0.0, 0.0, 0.58, 1.0)),
+        "ease-in-out" => Ok((0.42, 0.0, 0.58, 1.0)),
+        _ => parse_bezier(raw),
+    }
+}
+
+fn parse_spring(raw: &str) -> Result<(f32, f32, f32), String> {
+    let mut iter = raw.split(',');
+    let mut parse = || {
+        iter.next()
+            .ok_or_else(|| "Not enough values".to_string())
+            .and_then(|s| s.parse::<f32>().map_err(|e| e.to_string()))
+    };
+
+    let (stiffness, damping, mass) = (parse()?, parse()?, parse()?);
+    if !(stiffness > 0.0 && stiffness.is_finite()) {
+        return Err(format!(
+            "Invalid spring stiffness: {stiffness} (must be more than 0)"
+        ));
+    }
+    if !(damping >= 0.0 && damping.is_finite()) {
+        return Err(format!(
+            "Invalid spring damping: {damping} (must be 0 or more)"
+        ));
+    }
+    if !(mass > 0.0 && mass.is_finite()) {
+        return Err(format!("Invalid spring mass: {mass} (must be more than 0)"));
+    }
+    Ok((stiffness, damping, mass))
+}
+
 pub fn parse_image(raw: &str) -> Result<CliImage, String> {
     let path = PathBuf::from(raw);
     if path.exists() {
         return Ok(CliImage::Path(path));
     }
+    if let Some(provider) = raw.strip_prefix('@') {
+        return Ok(CliImage::Provider(provider.to_string()));
+    }
     if let Some(color) = raw.strip_prefix("0x") {
         if let Ok(color) = from_hex(color) {
             return Ok(CliImage::Color(color));
@@ -501,6 +2342,72 @@ fn parse_coords(raw: &str) -> Result<CliPosition, String> {
     Ok(CliPosition::new(parsed_x, parsed_y))
 }
 
+/// the most points the 'grow' transition's circles can start from
+const MAX_ORIGINS: usize = 64;
+
+// parses "random=<N>", or positions separated by spaces, where "corners" stands for the four
+// corners
+fn parse_origins(raw: &str) -> Result<GrowOrigins, String> {
+    if let Some(count) = raw.trim().strip_prefix("random=") {
+        return match count.parse::<u8>() {
+            Ok(count) if (1..=MAX_ORIGINS).contains(&(count as usize)) => {
+                Ok(GrowOrigins::Random(count))
+            }
+            _ => Err(format!(
+                "Invalid number of random origins: {count} (must be from 1 to {MAX_ORIGINS})"
+            )),
+        };
+    }
+
+    let mut points = Vec::new();
+    for origin in raw.split_whitespace() {
+        if origin == "corners" {
+            for corner in ["top-left", "top-right", "bottom-left", "bottom-right"] {
+                points.push(parse_coords(corner)?);
+            }
+        } else {
+            points.push(parse_coords(origin)?);
+        }
+    }
+    if points.is_empty() {
+        return Err("No origins given".to_string());
+    }
+    if points.len() > MAX_ORIGINS {
+        return Err(format!(
+            "Too many origins: {} (at most {MAX_ORIGINS})",
+            points.len()
+        ));
+    }
+    Ok(GrowOrigins::Points(points.into_boxed_slice()))
+}
+
+fn parse_loops(raw: &str) -> Result<Loops, String> {
+    match raw {
+        "infinite" => Ok(Loops::Infinite),
+        "once" => Ok(Loops::Times(1)),
+        _ => match raw.parse::<u16>() {
+            Ok(times) if times > 0 => Ok(Loops::Times(times)),
+            _ => Err(format!(
+                "Invalid loop count: {raw} (must be 'infinite', 'once' or a number of times, from 1 \
+                 to {})",
+                u16::MAX
+            )),
+        },
+    }
+}
+
+fn parse_page(raw: &str) -> Result<Page, String> {
+    match raw {
+        "all" => Ok(Page::All),
+        _ => raw.parse().map(Page::Number).map_err(|_| {
+            format!(
+                "Invalid page: {raw} (must be 'all' or a page number, from 1 to {})",
+                u16::MAX
+            )
+        }),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -532,4 +2439,225 @@ mod tests {
         let color = from_hex("000000").unwrap();
         assert_eq!(color, [0, 0, 0]);
     }
+
+    #[test]
+    fn should_reject_bad_var_keys() {
+        assert!(parse_var_key("").is_err());
+        assert!(parse_var_key("cpu load").is_err());
+        assert!(parse_var_key("cpu=load").is_err());
+        assert_eq!(parse_var_key("cpu_load").unwrap(), "cpu_load");
+    }
+
+    #[test]
+    fn should_parse_resolutions() {
+        assert_eq!(parse_resolution("1920x1080").unwrap(), (1920, 1080));
+        assert!(parse_resolution("1920").is_err());
+        assert!(parse_resolution("0x1080").is_err());
+        assert!(parse_resolution("axb").is_err());
+    }
+
+    #[test]
+    fn should_parse_locations() {
+        assert_eq!(parse_location("52.5, 13.4").unwrap(), (52.5, 13.4));
+        assert_eq!(parse_location("-33.9,-70.6").unwrap(), (-33.9, -70.6));
+        assert!(parse_location("52.5").is_err());
+        assert!(parse_location("91,0").is_err());
+        assert!(parse_location("0,181").is_err());
+    }
+
+    #[test]
+    fn should_parse_providers() {
+        match parse_image("@bing-potd") {
+            Ok(CliImage::Provider(provider)) => assert_eq!(provider, "bing-potd"),
+            _ => panic!("'@bing-potd' should be parsed as a provider"),
+        }
+    }
+
+    #[test]
+    fn run_should_take_daemon_args_and_img_args() {
+        let swww = Swww::try_parse_from([
+            "swww",
+            "run",
+            "--daemon-arg=--no-cache",
+            "--daemon-arg",
+            "--quiet",
+            "--transition-type=fade",
+            "0x000000",
+        ])
+        .unwrap();
+        let Swww::Run(run) = swww else {
+            panic!("expected the run subcommand");
+        };
+        assert_eq!(run.daemon_args, ["--no-cache", "--quiet"]);
+        assert!(matches!(run.img.transition_type, TransitionType::Fade));
+        assert!(matches!(run.img.image, CliImage::Color([0, 0, 0])));
+    }
+
+    #[test]
+    fn preview_transition_should_take_old_then_new() {
+        let swww = Swww::try_parse_from([
+            "swww",
+            "preview-transition",
+            "old.png",
+            "0x102030",
+            "--out",
+            "preview.gif",
+            "--transition-type=wipe",
+        ])
+        .unwrap();
+        let Swww::PreviewTransition(preview) = swww else {
+            panic!("expected the preview-transition subcommand");
+        };
+        assert_eq!(preview.old, PathBuf::from("old.png"));
+        assert_eq!(preview.out, PathBuf::from("preview.gif"));
+        assert!(preview.size.is_none());
+        assert!(matches!(preview.img.transition_type, TransitionType::Wipe));
+        // a color, since image paths must exist
+        assert!(matches!(
+            preview.img.image,
+            CliImage::Color([0x10, 0x20, 0x30])
+        ));
+        assert!(
+            Swww::try_parse_from(["swww", "preview-transition", "old.png", "0x102030"]).is_err()
+        );
+    }
+
+    #[test]
+    fn workspace_tints_should_default_to_a_fifth() {
+        assert_eq!(
+            parse_workspace_tint("2=ff0000").unwrap(),
+            ("2".to_string(), [255, 0, 0], 0.2)
+        );
+        assert_eq!(
+            parse_workspace_tint("web=0000ff:0.5").unwrap(),
+            ("web".to_string(), [0, 0, 255], 0.5)
+        );
+        assert!(parse_workspace_tint("2").is_err());
+        assert!(parse_workspace_tint("2=ff0000:2").is_err());
+        assert!(matches!(
+            parse_workspace_filter("3=invert").unwrap(),
+            (ref workspace, ColorFilterType::Invert) if workspace == "3"
+        ));
+        assert!(parse_workspace_filter("3=sepia").is_err());
+    }
+
+    #[test]
+    fn should_parse_quiet_hours() {
+        assert_eq!(parse_quiet_hours("22:30-07:00").unwrap(), (1350, 420));
+        assert_eq!(parse_quiet_hours("0:00-23:59").unwrap(), (0, 1439));
+        assert!(parse_quiet_hours("22:30").is_err());
+        assert!(parse_quiet_hours("24:00-07:00").is_err());
+        assert!(parse_quiet_hours("22:60-07:00").is_err());
+    }
+
+    #[test]
+    fn dim_should_take_outputs_then_amount() {
+        let swww = Swww::try_parse_from(["swww", "dim", "DP-1", "0.3"]).unwrap();
+        let Swww::Dim(dim) = swww else {
+            panic!("expected the dim subcommand");
+        };
+        assert_eq!(dim.outputs, "DP-1");
+        assert_eq!(dim.amount, 0.3);
+        assert_eq!(dim.color, [0, 0, 0]);
+        assert!(Swww::try_parse_from(["swww", "dim", "DP-1", "1.5"]).is_err());
+    }
+
+    #[test]
+    fn curves_should_start_at_0_and_end_at_1() {
+        let curve = parse_curve("0.5@0.2:ease-in 1.1@0.8").unwrap();
+        assert_eq!(
+            &*curve,
+            [
+                (0.0, 0.0, (0.42, 0.0, 1.0, 1.0)),
+                (0.2, 0.5, LINEAR),
+                (0.8, 1.1, LINEAR),
+                (1.0, 1.0, LINEAR),
+            ]
+        );
+        let curve = parse_curve("0.5@0.5:0,0,.5,1  1.2@1").unwrap();
+        assert_eq!(
+            &*curve,
+            [
+                (0.0, 0.0, (0.0, 0.0, 0.5, 1.0)),
+                (0.5, 0.5, LINEAR),
+                (1.0, 1.2, LINEAR)
+            ]
+        );
+        for raw in ["", "0.5", "0.5@0", "0.5@0.6 0.7@0.4", "1@1.5", "1@1:bounce"] {
+            assert!(parse_curve(raw).is_err(), "{raw:?}");
+        }
+    }
+
+    #[test]
+    fn random_weights_should_default_to_1() {
+        let weights = parse_random_weights("grow=3,wipe,fade=0").unwrap();
+        assert!(matches!(
+            &*weights,
+            [
+                (TransitionType::Grow, 3),
+                (TransitionType::Wipe, 1),
+                (TransitionType::Fade, 0)
+            ]
+        ));
+        for raw in ["", "grow=", "grow=300", "left", "luma", "random"] {
+            assert!(parse_random_weights(raw).is_err(), "{raw:?}");
+        }
+    }
+
+    #[test]
+    fn origins_should_expand_corners() {
+        match parse_origins("corners 0.5,0.5") {
+            Ok(GrowOrigins::Points(points)) => assert_eq!(points.len(), 5),
+            _ => panic!("'corners 0.5,0.5' should be parsed as points"),
+        }
+        assert!(matches!(
+            parse_origins("random=3"),
+            Ok(GrowOrigins::Random(3))
+        ));
+        assert!(parse_origins("random=0").is_err());
+        assert!(parse_origins("random=65").is_err());
+        assert!(parse_origins("").is_err());
+        assert!(parse_origins("top-left nowhere").is_err());
+    }
+
+    #[test]
+    fn loops_should_be_a_name_or_a_count() {
+        assert_eq!(parse_loops("infinite"), Ok(Loops::Infinite));
+        assert_eq!(parse_loops("once"), Ok(Loops::Times(1)));
+        assert_eq!(parse_loops("3"), Ok(Loops::Times(3)));
+        assert!(parse_loops("0").is_err());
+        assert!(parse_loops("forever").is_err());
+    }
+
+    #[test]
+    fn pages_should_be_all_or_a_number() {
+        assert_eq!(parse_page("all"), Ok(Page::All));
+        assert_eq!(
+            parse_page("2"),
+            Ok(Page::Number(std::num::NonZeroU16::new(2).unwrap()))
+        );
+        assert!(parse_page("0").is_err());
+        assert!(parse_page("first").is_err());
+    }
+
+    #[test]
+    fn transition_defaults_should_match_img_args() {
+        use clap::CommandFactory;
+        let img = Img::command();
+        for (name, id, flag) in TRANSITION_DEFAULTS {
+            let arg = img
+                .get_arguments()
+                .find(|arg| arg.get_id() == id)
+                .unwrap_or_else(|| panic!("img has no argument {id}, for default {name}"));
+            assert_eq!(
+                arg.get_long().map(|l| format!("--{l}")).as_deref(),
+                Some(flag)
+            );
+            assert_ne!(
+                common::ipc::transition_default_bit(name),
+                0,
+                "the daemon does not know the default {name}"
+            );
+        }
+    }
 }
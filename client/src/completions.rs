@@ -0,0 +1,101 @@
+//! `swww completions`: shell completion scripts.
+//!
+//! The static scripts are the ones the build script writes to the `completions` directory. With
+//! `--dynamic`, we add a bit of shell around them that completes `--outputs` with the names the
+//! daemon gives through the hidden `swww __complete-outputs`, and leaves everything else to the
+//! static completion.
+use clap::CommandFactory;
+use clap_complete::Shell;
+use common::ipc::{Answer, IpcSocket, RequestSend};
+
+use crate::{
+    cli::{Completions, Swww},
+    error::{Error, ErrorKind},
+    messages::msg,
+};
+
+const BASH: &str = r#"
+# completes the names of the daemon's outputs, leaving everything else to _swww
+_swww_dynamic() {
+    local cur="${COMP_WORDS[COMP_CWORD]}" prev="${COMP_WORDS[COMP_CWORD-1]}"
+    if [[ "$prev" == "-o" || "$prev" == "--outputs" ]]; then
+        # outputs are separated by commas, so we only complete the last one
+        local prefix=""
+        [[ "$cur" == *,* ]] && prefix="${cur%,*},"
+        COMPREPLY=($(compgen -P "$prefix" -W "$(swww __complete-outputs 2>/dev/null)" -- "${cur##*,}"))
+        return 0
+    fi
+    _swww "$@"
+}
+complete -F _swww_dynamic -o nosort -o bashdefault -o default swww
+"#;
+
+const ZSH: &str = r#"
+# completes the names of the daemon's outputs, leaving everything else to _swww
+_swww_dynamic() {
+    if [[ ${words[CURRENT-1]} == (-o|--outputs) ]]; then
+        local -a outputs
+        outputs=(${(f)"$(swww __complete-outputs 2>/dev/null)"})
+        _values -s , output $outputs
+    else
+        _swww "$@"
+    fi
+}
+"#;
+
+const FISH: &str = r#"
+# the names of the daemon's outputs, separated by commas
+complete -c swww -s o -l outputs -x -a "(__fish_complete_list , 'swww __complete-outputs 2>/dev/null')"
+"#;
+
+pub fn print(completions: &Completions) -> Result<(), Error> {
+    let mut script = Vec::new();
+    clap_complete::generate(completions.shell, &mut Swww::command(), "swww", &mut script);
+    let mut script = String::from_utf8_lossy(&script).into_owned();
+
+    if completions.dynamic {
+        match completions.shell {
+            Shell::Bash => script.push_str(BASH),
+            Shell::Zsh => {
+                // the script ends by completing (when autoloaded) or registering `_swww`, which
+                // must be `_swww_dynamic` instead
+                let end = script
+                    .rfind("if [ \"$funcstack[1]\"")
+                    .unwrap_or(script.len());
+                let tail = script[end..]
+                    .replace("    _swww \"$@\"", "    _swww_dynamic \"$@\"")
+                    .replace("compdef _swww swww", "compdef _swww_dynamic swww");
+                script.truncate(end);
+                script.push_str(ZSH.trim_start());
+                script.push('\n');
+                script.push_str(&tail);
+            }
+            Shell::Fish => script.push_str(FISH),
+            shell => {
+                return Err(Error::new(
+                    ErrorKind::Usage,
+                    msg!("dynamic-completions-unsupported", shell = shell),
+                ))
+            }
+        }
+    }
+
+    print!("{script}");
+    Ok(())
+}
+
+/// Prints the names of the daemon's outputs, one per line. Shells run this while completing, so
+/// it must be quick: unlike other commands, it does not wait for the daemon to be configured
+pub fn outputs() -> Result<(), Error> {
+    let socket = IpcSocket::connect()?;
+    RequestSend::Query.send(&socket)?;
+    match Answer::receive(socket.recv()?) {
+        Answer::Info(infos) => {
+            for info in infos.iter() {
+                println!("{}", info.name);
+            }
+            Ok(())
+        }
+        _ => Err(msg!("unexpected-answer", expected = "Answer::Info").into()),
+    }
+}
@@ -0,0 +1,209 @@
+//! Keeps images out from under notches and camera cutouts, set through the `cutout.*` variables.
+//!
+//! `swww set-var cutout.<output> "<x>,<y>,<width>,<height>[,letterbox|mirror]"` marks a rectangle
+//! of the output, in pixels from its top left corner, as hidden. Images for that output are then
+//! resized into the largest part of the output beside the rectangle (the "safe area"), and the
+//! rest is filled with `--fill-color` (letterbox, the default) or with the image reflected across
+//! the safe area's edge (mirror). An empty value removes the cutout.
+use crate::messages::msg;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    Letterbox,
+    Mirror,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cutout {
+    /// x, y, width and height, in pixels
+    rect: (u32, u32, u32, u32),
+    mode: Mode,
+}
+
+impl Cutout {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let mut parts = value.trim().split(',').map(str::trim);
+        let mut rect = [0; 4];
+        for n in &mut rect {
+            *n = parts
+                .next()
+                .and_then(|n| n.parse().ok())
+                .ok_or_else(|| msg!("invalid-cutout", value = value))?;
+        }
+        let mode = match parts.next() {
+            None | Some("letterbox") => Mode::Letterbox,
+            Some("mirror") => Mode::Mirror,
+            Some(_) => return Err(msg!("invalid-cutout", value = value)),
+        };
+        if parts.next().is_some() {
+            return Err(msg!("invalid-cutout", value = value));
+        }
+        let [x, y, width, height] = rect;
+        Ok(Self {
+            rect: (x, y, width, height),
+            mode,
+        })
+    }
+
+    /// The cutouts of every output, from the daemon's `cutout.*` variables. Invalid ones are
+    /// reported and ignored
+    pub fn from_vars(vars: &[(String, String)]) -> Vec<(String, Self)> {
+        vars.iter()
+            .filter(|(_, value)| !value.is_empty())
+            .filter_map(|(key, value)| {
+                let output = key.strip_prefix("cutout.")?;
+                match Self::parse(value) {
+                    Ok(cutout) => Some((output.to_string(), cutout)),
+                    Err(e) => {
+                        eprintln!("{e}");
+                        None
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// A string that tells cutouts apart, for caching animations made for them
+    pub fn key(&self) -> String {
+        let (x, y, width, height) = self.rect;
+        let mode = match self.mode {
+            Mode::Letterbox => "letterbox",
+            Mode::Mirror => "mirror",
+        };
+        format!("cutout{x}-{y}-{width}-{height}-{mode}")
+    }
+
+    /// The cutout of an output of `from` pixels, for images made at `to` instead (as with
+    /// `--max-anim-resolution`). It is rounded outwards, so it still covers what it did
+    pub fn scale(self, from: (u32, u32), to: (u32, u32)) -> Self {
+        if from == to {
+            return self;
+        }
+        let (x, y, w, h) = self.rect;
+        let scale = |v: u32, from: u32, to: u32| v as u64 * to as u64 / from.max(1) as u64;
+        let (x2, y2) = (
+            (x as u64 + w as u64) * to.0 as u64,
+            (y as u64 + h as u64) * to.1 as u64,
+        );
+        let (x, y) = (scale(x, from.0, to.0), scale(y, from.1, to.1));
+        let right = x2.div_ceil(from.0.max(1) as u64);
+        let bottom = y2.div_ceil(from.1.max(1) as u64);
+        Self {
+            rect: (
+                x as u32,
+                y as u32,
+                (right - x).min(u32::MAX as u64) as u32,
+                (bottom - y).min(u32::MAX as u64) as u32,
+            ),
+            ..self
+        }
+    }
+
+    /// The largest rectangle of an output of `dim` that does not overlap the cutout, as
+    /// (x, y, width, height). It is the whole output if the cutout is outside of it
+    pub fn safe_area(&self, dim: (u32, u32)) -> (u32, u32, u32, u32) {
+        let (width, height) = dim;
+        let (x, y, w, h) = self.rect;
+        if x >= width || y >= height || w == 0 || h == 0 {
+            return (0, 0, width, height);
+        }
+        let right = x.saturating_add(w).min(width);
+        let bottom = y.saturating_add(h).min(height);
+        [
+            (0, 0, width, y),
+            (0, bottom, width, height - bottom),
+            (0, 0, x, height),
+            (right, 0, width - right, height),
+        ]
+        .into_iter()
+        .max_by_key(|&(_, _, w, h)| w as u64 * h as u64)
+        .filter(|&(_, _, w, h)| w > 0 && h > 0)
+        // the cutout covers the whole output, so there is nowhere better to put the image
+        .unwrap_or((0, 0, width, height))
+    }
+
+    /// Places `img`, made for the safe area, in a canvas of `dim`, filling the rest of it
+    /// according to the mode. `color` has the canvas' channels, in its order
+    pub fn compose(&self, img: &[u8], dim: (u32, u32), channels: usize, color: &[u8]) -> Box<[u8]> {
+        let (width, height) = (dim.0 as usize, dim.1 as usize);
+        let (x, y, w, h) = self.safe_area(dim);
+        let (x, y, w, h) = (x as usize, y as usize, w as usize, h as usize);
+        debug_assert_eq!(img.len(), w * h * channels);
+
+        // reflects `c` into `start..start + len`, across the edge it is past
+        let reflect = |c: usize, start: usize, len: usize| {
+            let offset = if c < start {
+                start - 1 - c
+            } else if c >= start + len {
+                (len - 1).saturating_sub(c - start - len)
+            } else {
+                c - start
+            };
+            offset.min(len - 1)
+        };
+
+        let mut canvas = Vec::with_capacity(width * height * channels);
+        for row in 0..height {
+            let inside = (y..y + h).contains(&row);
+            if self.mode == Mode::Letterbox && !inside {
+                for _ in 0..width {
+                    canvas.extend_from_slice(color);
+                }
+                continue;
+            }
+            let src_row = &img[reflect(row, y, h) * w * channels..][..w * channels];
+            for col in 0..width {
+                if self.mode == Mode::Letterbox && !(x..x + w).contains(&col) {
+                    canvas.extend_from_slice(color);
+                } else {
+                    let src = reflect(col, x, w) * channels;
+                    canvas.extend_from_slice(&src_row[src..src + channels]);
+                }
+            }
+        }
+        canvas.into_boxed_slice()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cutouts_should_leave_the_largest_safe_area() {
+        let notch = Cutout::parse("800,0,320,40").unwrap();
+        assert_eq!(notch.mode, Mode::Letterbox);
+        assert_eq!(notch.safe_area((1920, 1200)), (0, 40, 1920, 1160));
+
+        let camera = Cutout::parse("0,500,30,80, mirror").unwrap();
+        assert_eq!(camera.mode, Mode::Mirror);
+        assert_eq!(camera.safe_area((1080, 2400)), (30, 0, 1050, 2400));
+
+        assert_eq!(
+            Cutout::parse("5000,0,10,10").unwrap().safe_area((100, 100)),
+            (0, 0, 100, 100)
+        );
+        let half = notch.scale((1920, 1200), (960, 600));
+        assert_eq!(half.safe_area((960, 600)), (0, 20, 960, 580));
+
+        assert!(Cutout::parse("1,2,3").is_err());
+        assert!(Cutout::parse("1,2,3,4,sideways").is_err());
+    }
+
+    #[test]
+    fn compose_should_letterbox_or_mirror_the_hidden_rows() {
+        // a 1 channel 2x4 output with the top row hidden
+        let img = [1, 2, 3, 4, 5, 6];
+        let letterbox = Cutout::parse("0,0,2,1").unwrap();
+        assert_eq!(
+            &*letterbox.compose(&img, (2, 4), 1, &[0]),
+            [0, 0, 1, 2, 3, 4, 5, 6]
+        );
+
+        let mirror = Cutout::parse("0,0,2,1,mirror").unwrap();
+        assert_eq!(
+            &*mirror.compose(&img, (2, 4), 1, &[0]),
+            [1, 2, 1, 2, 3, 4, 5, 6]
+        );
+    }
+}
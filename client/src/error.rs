@@ -0,0 +1,119 @@
+//! The errors `swww` exits with.
+//!
+//! Every kind of error has its own exit code, so that scripts can tell them apart. These codes
+//! are part of our interface: never change or reuse them, only add new ones.
+use std::fmt;
+
+use common::ipc::{IpcError, IpcErrorKind};
+
+use crate::messages::msg;
+
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Anything that does not fit the others
+    Other = 1,
+    /// We could not connect to `swww-daemon`
+    DaemonNotRunning = 2,
+    /// None of the outputs we were asked to use exist
+    NoSuchOutput = 3,
+    /// The image could not be read or decoded
+    Decode = 4,
+    /// The daemon took too long to answer
+    Timeout = 5,
+    /// The command line arguments were invalid
+    Usage = 6,
+    /// `swww get-var` was asked for a variable that was never set
+    VarNotSet = 7,
+    /// The daemon refused the request, because of the limits its administrator set
+    Refused = 8,
+    /// The daemon was built without a feature the request needs
+    Unsupported = 9,
+}
+
+#[derive(Debug)]
+pub struct Error {
+    kind: ErrorKind,
+    msg: String,
+}
+
+impl Error {
+    pub fn new(kind: ErrorKind, msg: impl Into<String>) -> Self {
+        Self {
+            kind,
+            msg: msg.into(),
+        }
+    }
+
+    pub fn decode(msg: String) -> Self {
+        Self::new(ErrorKind::Decode, msg)
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    pub fn exit_code(&self) -> u8 {
+        self.kind as u8
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.msg)
+    }
+}
+
+impl From<String> for Error {
+    fn from(msg: String) -> Self {
+        Self::new(ErrorKind::Other, msg)
+    }
+}
+
+impl From<&str> for Error {
+    fn from(msg: &str) -> Self {
+        Self::new(ErrorKind::Other, msg)
+    }
+}
+
+impl From<IpcError> for Error {
+    fn from(err: IpcError) -> Self {
+        match err.kind() {
+            IpcErrorKind::NoSocketFile => Self::new(ErrorKind::DaemonNotRunning, msg!("no-socket")),
+            IpcErrorKind::Connect => Self::new(ErrorKind::DaemonNotRunning, msg!("connect-failed")),
+            IpcErrorKind::Timeout => Self::new(ErrorKind::Timeout, msg!("daemon-timeout")),
+            _ => Self::new(ErrorKind::Other, err.to_string()),
+        }
+    }
+}
+
+/// for the parts of the client that only deal in messages, like `swww now-playing`
+impl From<Error> for String {
+    fn from(err: Error) -> Self {
+        err.msg
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exit_codes_should_be_stable() {
+        let codes = [
+            (ErrorKind::Other, 1),
+            (ErrorKind::DaemonNotRunning, 2),
+            (ErrorKind::NoSuchOutput, 3),
+            (ErrorKind::Decode, 4),
+            (ErrorKind::Timeout, 5),
+            (ErrorKind::Usage, 6),
+            (ErrorKind::VarNotSet, 7),
+            (ErrorKind::Refused, 8),
+            (ErrorKind::Unsupported, 9),
+        ];
+        for (kind, code) in codes {
+            assert_eq!(Error::new(kind, "").exit_code(), code, "{kind:?}");
+        }
+        assert_eq!(Error::from("oops".to_string()).kind, ErrorKind::Other);
+    }
+}
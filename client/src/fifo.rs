@@ -0,0 +1,161 @@
+//! Implements `swww fifo`.
+//!
+//! Every line written to the pipe is split like a shell would, and then run like the arguments of
+//! a separate `swww` invocation. A failed command is reported, but does not stop us from reading
+//! the next one.
+use std::{
+    ffi::OsString,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader},
+    os::unix::fs::FileTypeExt,
+    path::{Path, PathBuf},
+};
+
+use clap::Parser;
+
+use crate::cli::{Fifo, Swww};
+use crate::error::{Error, ErrorKind};
+use crate::messages::msg;
+
+pub fn listen(args: &Fifo) -> Result<(), String> {
+    let path = match &args.path {
+        Some(path) => path.clone(),
+        None => default_path()?,
+    };
+    let fifo = open(&path)?;
+    eprintln!("{}", msg!("fifo-listening", path = path.display()));
+
+    for line in BufReader::new(fifo).lines() {
+        let line = line.map_err(|e| msg!("read-failed", path = path.display(), error = e))?;
+        if let Err(e) = run_line(&line) {
+            eprintln!(
+                "{}",
+                msg!("fifo-command-failed", command = line.trim(), error = e)
+            );
+        }
+    }
+    Ok(())
+}
+
+fn default_path() -> Result<PathBuf, String> {
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) if !dir.is_empty() => Ok(PathBuf::from(dir).join("swww.fifo")),
+        _ => Err(msg!("no-runtime-dir")),
+    }
+}
+
+/// Opens the pipe at `path`, creating it first if needed
+fn open(path: &Path) -> Result<File, String> {
+    use rustix::fs::{FileType, Mode, CWD};
+
+    match std::fs::metadata(path) {
+        Ok(metadata) if metadata.file_type().is_fifo() => (),
+        Ok(_) => return Err(msg!("not-a-fifo", path = path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            rustix::fs::mknodat(CWD, path, FileType::Fifo, Mode::RUSR | Mode::WUSR, 0)
+                .map_err(|e| msg!("fifo-create-failed", path = path.display(), error = e))?;
+        }
+        Err(e) => return Err(msg!("read-failed", path = path.display(), error = e)),
+    }
+
+    // opening it for writing as well means there is always a writer, so we never reach its end
+    // when the programs writing to it close it
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .map_err(|e| msg!("read-failed", path = path.display(), error = e))
+}
+
+fn run_line(line: &str) -> Result<(), Error> {
+    let args: Vec<OsString> = split_words(line)?.into_iter().map(OsString::from).collect();
+    // blank lines and comments
+    if args.is_empty() {
+        return Ok(());
+    }
+
+    let swww = Swww::try_parse_from(std::iter::once(OsString::from("swww")).chain(args.clone()))
+        .map_err(|e| Error::new(ErrorKind::Usage, e.to_string()))?;
+    if let Swww::Fifo(_) = swww {
+        return Err(Error::new(ErrorKind::Usage, msg!("fifo-nested")));
+    }
+    crate::run(swww, args)
+}
+
+/// Splits `line` into words like a shell would, with single and double quotes and backslash
+/// escapes, but without any expansions. Everything after a `#` starting a word is a comment
+pub(crate) fn split_words(line: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '#' if word.is_none() => break,
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(msg!("unclosed-quote")),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err(msg!("unclosed-quote")),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err(msg!("unclosed-quote")),
+                    }
+                }
+            }
+            '\\' => {
+                let word = word.get_or_insert_with(String::new);
+                word.extend(chars.next());
+            }
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_words_should_handle_quotes_and_comments() {
+        assert_eq!(
+            split_words("img  ~/wall.png --outputs DP-1").unwrap(),
+            ["img", "~/wall.png", "--outputs", "DP-1"]
+        );
+        assert_eq!(
+            split_words(r#"img "my wall.png" -o 'DP-1,HDMI-A-1' a\ b"#).unwrap(),
+            ["img", "my wall.png", "-o", "DP-1,HDMI-A-1", "a b"]
+        );
+        assert_eq!(
+            split_words(r#"img "say \"hi\"" ''"#).unwrap(),
+            ["img", "say \"hi\"", ""]
+        );
+        assert_eq!(
+            split_words("clear 000000 # back to black").unwrap(),
+            ["clear", "000000"]
+        );
+        assert_eq!(split_words("img a#b").unwrap(), ["img", "a#b"]);
+        assert!(split_words("   # only a comment").unwrap().is_empty());
+        assert!(split_words("img 'unclosed").is_err());
+        assert!(split_words("img \"unclosed").is_err());
+    }
+}
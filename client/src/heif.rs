@@ -0,0 +1,293 @@
+//! HEIF images, through libheif (the `heif` feature).
+//!
+//! Besides photos from phones, this is the format of the dynamic wallpapers made for macOS: one
+//! file with an image for each part of the day, and XMP metadata on the primary image telling
+//! which is which (see `time_of_day`). Images are converted to 8 bit RGBA, like `image` gives us
+//! for every other format, so they go through the same resizing and compression.
+use std::{
+    ffi::{c_char, c_int, c_void, CStr},
+    marker::PhantomData,
+    ptr,
+};
+
+use image::{DynamicImage, RgbaImage};
+
+use crate::messages::msg;
+
+#[repr(C)]
+struct HeifError {
+    code: c_int,
+    subcode: c_int,
+    message: *const c_char,
+}
+
+#[repr(C)]
+struct Context {
+    _private: [u8; 0],
+}
+
+#[repr(C)]
+struct Handle {
+    _private: [u8; 0],
+}
+
+#[repr(C)]
+struct RawImage {
+    _private: [u8; 0],
+}
+
+type ItemId = u32;
+
+const COLORSPACE_RGB: c_int = 1;
+const CHROMA_INTERLEAVED_RGBA: c_int = 11;
+const CHANNEL_INTERLEAVED: c_int = 10;
+
+extern "C" {
+    fn heif_context_alloc() -> *mut Context;
+
+    fn heif_context_free(ctx: *mut Context);
+
+    /// # Safety
+    ///
+    /// `mem` must outlive the context.
+    fn heif_context_read_from_memory_without_copy(
+        ctx: *mut Context,
+        mem: *const c_void,
+        size: usize,
+        options: *const c_void,
+    ) -> HeifError;
+
+    fn heif_context_get_number_of_top_level_images(ctx: *mut Context) -> c_int;
+
+    fn heif_context_get_list_of_top_level_image_IDs(
+        ctx: *mut Context,
+        ids: *mut ItemId,
+        count: c_int,
+    ) -> c_int;
+
+    fn heif_context_get_primary_image_ID(ctx: *mut Context, id: *mut ItemId) -> HeifError;
+
+    fn heif_context_get_image_handle(
+        ctx: *mut Context,
+        id: ItemId,
+        handle: *mut *mut Handle,
+    ) -> HeifError;
+
+    fn heif_image_handle_release(handle: *const Handle);
+
+    fn heif_image_handle_get_number_of_metadata_blocks(
+        handle: *const Handle,
+        type_filter: *const c_char,
+    ) -> c_int;
+
+    fn heif_image_handle_get_list_of_metadata_block_IDs(
+        handle: *const Handle,
+        type_filter: *const c_char,
+        ids: *mut ItemId,
+        count: c_int,
+    ) -> c_int;
+
+    fn heif_image_handle_get_metadata_size(handle: *const Handle, id: ItemId) -> usize;
+
+    /// # Safety
+    ///
+    /// `out` must have room for `heif_image_handle_get_metadata_size` bytes.
+    fn heif_image_handle_get_metadata(
+        handle: *const Handle,
+        id: ItemId,
+        out: *mut c_void,
+    ) -> HeifError;
+
+    fn heif_decode_image(
+        handle: *const Handle,
+        out: *mut *mut RawImage,
+        colorspace: c_int,
+        chroma: c_int,
+        options: *const c_void,
+    ) -> HeifError;
+
+    fn heif_image_get_width(img: *const RawImage, channel: c_int) -> c_int;
+
+    fn heif_image_get_height(img: *const RawImage, channel: c_int) -> c_int;
+
+    fn heif_image_get_plane_readonly(
+        img: *const RawImage,
+        channel: c_int,
+        stride: *mut c_int,
+    ) -> *const u8;
+
+    fn heif_image_release(img: *const RawImage);
+}
+
+/// A parsed HEIF file, whose images we decode on demand
+pub struct Heif<'a> {
+    ctx: *mut Context,
+    /// the top level images, in the order of the file
+    ids: Box<[ItemId]>,
+    primary: ItemId,
+    /// libheif reads straight from the bytes we were given
+    bytes: PhantomData<&'a [u8]>,
+}
+
+impl<'a> Heif<'a> {
+    pub fn new(bytes: &'a [u8]) -> Result<Self, String> {
+        // SAFETY: the context is freed when we are dropped, and `bytes` outlive it. The id list
+        // has room for as many ids as we tell libheif
+        unsafe {
+            let ctx = heif_context_alloc();
+            if ctx.is_null() {
+                return Err(msg!("heif-failed", error = "out of memory"));
+            }
+            let mut heif = Self {
+                ctx,
+                ids: Box::default(),
+                primary: 0,
+                bytes: PhantomData,
+            };
+            check(heif_context_read_from_memory_without_copy(
+                ctx,
+                bytes.as_ptr().cast(),
+                bytes.len(),
+                ptr::null(),
+            ))?;
+            let count = heif_context_get_number_of_top_level_images(ctx).max(0);
+            let mut ids = vec![0; count as usize];
+            let count = heif_context_get_list_of_top_level_image_IDs(ctx, ids.as_mut_ptr(), count);
+            ids.truncate(count.max(0) as usize);
+            if ids.is_empty() {
+                return Err(msg!("heif-failed", error = "the file has no images"));
+            }
+            heif.ids = ids.into_boxed_slice();
+            check(heif_context_get_primary_image_ID(ctx, &mut heif.primary))?;
+            Ok(heif)
+        }
+    }
+
+    /// How many top level images the file has
+    pub fn image_count(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// The image to show when the file is just a picture
+    pub fn primary(&self) -> Result<DynamicImage, String> {
+        self.decode(self.primary)
+    }
+
+    /// The `index`th top level image, in the order of the file (which is how time of day
+    /// metadata refers to them)
+    pub fn image(&self, index: usize) -> Result<DynamicImage, String> {
+        match self.ids.get(index) {
+            Some(&id) => self.decode(id),
+            None => Err(msg!(
+                "heif-failed",
+                error = format!("there is no image {index}")
+            )),
+        }
+    }
+
+    /// The XMP metadata blocks of the primary image
+    pub fn xmp(&self) -> Result<Vec<String>, String> {
+        let handle = self.handle(self.primary)?;
+        let filter = b"mime\0".as_ptr().cast();
+        // SAFETY: the id list has room for as many ids as we tell libheif, and each block gets
+        // as many bytes as libheif says it has
+        unsafe {
+            let count = heif_image_handle_get_number_of_metadata_blocks(handle.0, filter).max(0);
+            let mut ids = vec![0; count as usize];
+            let count = heif_image_handle_get_list_of_metadata_block_IDs(
+                handle.0,
+                filter,
+                ids.as_mut_ptr(),
+                count,
+            );
+            ids.truncate(count.max(0) as usize);
+
+            let mut blocks = Vec::with_capacity(ids.len());
+            for id in ids {
+                let mut block = vec![0u8; heif_image_handle_get_metadata_size(handle.0, id)];
+                check(heif_image_handle_get_metadata(
+                    handle.0,
+                    id,
+                    block.as_mut_ptr().cast(),
+                ))?;
+                blocks.push(String::from_utf8_lossy(&block).into_owned());
+            }
+            Ok(blocks)
+        }
+    }
+
+    fn handle(&self, id: ItemId) -> Result<HandleGuard, String> {
+        let mut handle = ptr::null_mut();
+        // SAFETY: the context lives as long as we do
+        check(unsafe { heif_context_get_image_handle(self.ctx, id, &mut handle) })?;
+        Ok(HandleGuard(handle))
+    }
+
+    fn decode(&self, id: ItemId) -> Result<DynamicImage, String> {
+        let handle = self.handle(id)?;
+        // SAFETY: the plane has `height` rows of `stride` bytes, each starting with `width` RGBA
+        // pixels, and it lives until we release the image
+        unsafe {
+            let mut img = ptr::null_mut();
+            check(heif_decode_image(
+                handle.0,
+                &mut img,
+                COLORSPACE_RGB,
+                CHROMA_INTERLEAVED_RGBA,
+                ptr::null(),
+            ))?;
+            let width = heif_image_get_width(img, CHANNEL_INTERLEAVED);
+            let height = heif_image_get_height(img, CHANNEL_INTERLEAVED);
+            let mut stride = 0;
+            let plane = heif_image_get_plane_readonly(img, CHANNEL_INTERLEAVED, &mut stride);
+            if plane.is_null() || width <= 0 || height <= 0 {
+                heif_image_release(img);
+                return Err(msg!("heif-failed", error = "the image has no pixels"));
+            }
+
+            let row = width as usize * 4;
+            let mut pixels = Vec::with_capacity(row * height as usize);
+            for y in 0..height as usize {
+                let start = plane.add(y * stride as usize);
+                pixels.extend_from_slice(std::slice::from_raw_parts(start, row));
+            }
+            heif_image_release(img);
+
+            let buffer = RgbaImage::from_raw(width as u32, height as u32, pixels)
+                .expect("we copied exactly width * height pixels");
+            Ok(DynamicImage::ImageRgba8(buffer))
+        }
+    }
+}
+
+impl Drop for Heif<'_> {
+    fn drop(&mut self) {
+        // SAFETY: we created it in `new`, and nothing else holds it
+        unsafe { heif_context_free(self.ctx) }
+    }
+}
+
+/// Releases an image handle when dropped
+struct HandleGuard(*mut Handle);
+
+impl Drop for HandleGuard {
+    fn drop(&mut self) {
+        // SAFETY: libheif gave it to us, and we only release it once
+        unsafe { heif_image_handle_release(self.0) }
+    }
+}
+
+fn check(error: HeifError) -> Result<(), String> {
+    if error.code == 0 {
+        return Ok(());
+    }
+    // SAFETY: libheif's messages are static strings
+    let message = if error.message.is_null() {
+        format!("error {}.{}", error.code, error.subcode)
+    } else {
+        unsafe { CStr::from_ptr(error.message) }
+            .to_string_lossy()
+            .into_owned()
+    };
+    Err(msg!("heif-failed", error = message))
+}
@@ -0,0 +1,512 @@
+//! Color management for images with embedded ICC profiles.
+//!
+//! Wide gamut photos (Display P3, Adobe RGB, ProPhoto and the like) store their colors relative
+//! to the primaries of their profile, and look oversaturated when shown as if they were sRGB,
+//! which is what we assume the outputs are. We convert them to sRGB instead, or to the profile
+//! given with `--color-profile`.
+//!
+//! We only understand matrix/TRC profiles: a tone curve for each channel, and the XYZ of each
+//! primary. That is how camera, editing and display profiles are made. Other profiles (lookup
+//! table based, grayscale or CMYK ones) are ignored. Colors outside of the destination's gamut
+//! are clipped, as with the relative colorimetric intent.
+use std::path::Path;
+
+use crate::messages::msg;
+
+/// How many entries the tables that turn linear light back into the destination's values have.
+/// They are indexed by the square root of the light, so dark colors get more of them
+const ENCODE_LEN: usize = 4096;
+
+/// A matrix/TRC RGB profile
+#[derive(Clone)]
+pub struct Profile {
+    /// from linear RGB to XYZ (D50): its columns are the XYZ of the red, green and blue primaries
+    to_xyz: [[f32; 3]; 3],
+    /// the tone curve of each channel, from the values stored in the image to linear light
+    curves: [Curve; 3],
+    /// checksum of the file the profile was loaded from, if it was (see `Profile::id`)
+    id: Option<u64>,
+}
+
+impl Profile {
+    /// The sRGB profile, as it is in the ICC's own sRGB profile
+    pub fn srgb() -> Self {
+        let curve = Curve::Parametric {
+            g: 2.4,
+            a: 1.0 / 1.055,
+            b: 0.055 / 1.055,
+            c: 1.0 / 12.92,
+            d: 0.04045,
+            e: 0.0,
+            f: 0.0,
+        };
+        Self {
+            to_xyz: [
+                [0.436_074_7, 0.385_064_9, 0.143_080_4],
+                [0.222_504_5, 0.716_878_6, 0.060_616_9],
+                [0.013_932_2, 0.097_104_5, 0.714_173_3],
+            ],
+            curves: [curve.clone(), curve.clone(), curve],
+            id: None,
+        }
+    }
+
+    /// Reads the profile at `path`, like the ones `--color-profile` takes
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let bytes = std::fs::read(path).map_err(|e| {
+            msg!(
+                "read-color-profile-failed",
+                path = path.display(),
+                error = e
+            )
+        })?;
+        let mut profile = Self::parse(&bytes).map_err(|e| {
+            msg!(
+                "read-color-profile-failed",
+                path = path.display(),
+                error = e
+            )
+        })?;
+        // FNV-1a, which unlike `DefaultHasher` is stable across rust versions
+        profile.id = Some(bytes.iter().fold(0xcbf29ce484222325, |hash, &b| {
+            (hash ^ b as u64).wrapping_mul(0x100000001b3)
+        }));
+        Ok(profile)
+    }
+
+    /// Identifies profiles loaded from a file, like the ones `--color-profile` takes, so the
+    /// animations converted to them are cached apart from each other. `None` for the builtin sRGB
+    /// and for profiles embedded in images
+    pub fn id(&self) -> Option<u64> {
+        self.id
+    }
+
+    pub fn parse(icc: &[u8]) -> Result<Self, String> {
+        if icc.len() < 132 || &icc[36..40] != b"acsp" {
+            return Err(msg!("icc-invalid"));
+        }
+        if &icc[16..20] != b"RGB " {
+            return Err(msg!("icc-not-rgb"));
+        }
+
+        let tags = u32_at(icc, 128).unwrap_or_default() as usize;
+        let tag = |signature: &[u8; 4]| {
+            (0..tags).find_map(|k| {
+                let entry = icc.get(132 + k * 12..144 + k * 12)?;
+                if &entry[..4] != signature {
+                    return None;
+                }
+                let offset = u32_at(entry, 4)? as usize;
+                let size = u32_at(entry, 8)? as usize;
+                icc.get(offset..offset.checked_add(size)?)
+            })
+        };
+        let primary = |signature| {
+            let tag = tag(signature)?;
+            if tag.get(..4)? != b"XYZ " {
+                return None;
+            }
+            Some([
+                s15_fixed16_at(tag, 8)?,
+                s15_fixed16_at(tag, 12)?,
+                s15_fixed16_at(tag, 16)?,
+            ])
+        };
+        let curve = |signature| tag(signature).and_then(Curve::parse);
+
+        let (Some(r), Some(g), Some(b), Some(r_curve), Some(g_curve), Some(b_curve)) = (
+            primary(b"rXYZ"),
+            primary(b"gXYZ"),
+            primary(b"bXYZ"),
+            curve(b"rTRC"),
+            curve(b"gTRC"),
+            curve(b"bTRC"),
+        ) else {
+            return Err(msg!("icc-not-matrix"));
+        };
+        Ok(Self {
+            to_xyz: [[r[0], g[0], b[0]], [r[1], g[1], b[1]], [r[2], g[2], b[2]]],
+            curves: [r_curve, g_curve, b_curve],
+            id: None,
+        })
+    }
+
+    /// Whether colors look the same in both profiles, like with the many slightly different
+    /// versions of sRGB
+    fn same_as(&self, other: &Self) -> bool {
+        let same_matrix = self
+            .to_xyz
+            .iter()
+            .flatten()
+            .zip(other.to_xyz.iter().flatten())
+            .all(|(a, b)| (a - b).abs() < 2e-3);
+        let same_curves = self.curves.iter().zip(&other.curves).all(|(a, b)| {
+            (0..=32).all(|k| {
+                let x = k as f32 / 32.0;
+                (a.eval(x) - b.eval(x)).abs() < 1.0 / 512.0
+            })
+        });
+        same_matrix && same_curves
+    }
+}
+
+/// A tone curve, from the values stored in an image to linear light, both from 0 to 1
+#[derive(Clone)]
+enum Curve {
+    /// sampled uniformly from 0 to 1
+    Table(Box<[f32]>),
+    /// the most general of the ICC's parametric curves: `(a * x + b)^g + e` from `d` on, and
+    /// `c * x + f` below it. The simpler ones are special cases of it
+    Parametric {
+        g: f32,
+        a: f32,
+        b: f32,
+        c: f32,
+        d: f32,
+        e: f32,
+        f: f32,
+    },
+}
+
+impl Curve {
+    fn gamma(g: f32) -> Self {
+        Self::Parametric {
+            g,
+            a: 1.0,
+            b: 0.0,
+            c: 0.0,
+            d: 0.0,
+            e: 0.0,
+            f: 0.0,
+        }
+    }
+
+    /// Parses a `curv` or `para` tag
+    fn parse(tag: &[u8]) -> Option<Self> {
+        match tag.get(..4)? {
+            b"curv" => {
+                let count = u32_at(tag, 8)? as usize;
+                let entries = tag.get(12..12 + count.checked_mul(2)?)?;
+                let entry = |e: &[u8]| u16::from_be_bytes([e[0], e[1]]);
+                Some(match count {
+                    0 => Self::gamma(1.0),
+                    // an 8.8 fixed point gamma
+                    1 => Self::gamma(entry(entries) as f32 / 256.0),
+                    _ => Self::Table(
+                        entries
+                            .chunks_exact(2)
+                            .map(|e| entry(e) as f32 / u16::MAX as f32)
+                            .collect(),
+                    ),
+                })
+            }
+            b"para" => {
+                let kind = u16::from_be_bytes([*tag.get(8)?, *tag.get(9)?]);
+                let params = *[1, 3, 4, 5, 7].get(kind as usize)?;
+                let p = (0..params)
+                    .map(|k| s15_fixed16_at(tag, 12 + k * 4))
+                    .collect::<Option<Vec<_>>>()?;
+                if kind == 0 {
+                    return Some(Self::gamma(p[0]));
+                }
+                let (g, a, b) = (p[0], p[1], p[2]);
+                // below `-b / a`, the first curves are constant
+                if (kind == 1 || kind == 2) && a == 0.0 {
+                    return None;
+                }
+                Some(match kind {
+                    1 => Self::Parametric {
+                        g,
+                        a,
+                        b,
+                        c: 0.0,
+                        d: -b / a,
+                        e: 0.0,
+                        f: 0.0,
+                    },
+                    2 => Self::Parametric {
+                        g,
+                        a,
+                        b,
+                        c: 0.0,
+                        d: -b / a,
+                        e: p[3],
+                        f: p[3],
+                    },
+                    3 => Self::Parametric {
+                        g,
+                        a,
+                        b,
+                        c: p[3],
+                        d: p[4],
+                        e: 0.0,
+                        f: 0.0,
+                    },
+                    _ => Self::Parametric {
+                        g,
+                        a,
+                        b,
+                        c: p[3],
+                        d: p[4],
+                        e: p[5],
+                        f: p[6],
+                    },
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn eval(&self, x: f32) -> f32 {
+        match *self {
+            Self::Table(ref table) => {
+                let pos = x.clamp(0.0, 1.0) * (table.len() - 1) as f32;
+                let i = (pos as usize).min(table.len() - 2);
+                let t = pos - i as f32;
+                table[i] * (1.0 - t) + table[i + 1] * t
+            }
+            Self::Parametric {
+                g,
+                a,
+                b,
+                c,
+                d,
+                e,
+                f,
+            } => {
+                if x >= d {
+                    (a * x + b).max(0.0).powf(g) + e
+                } else {
+                    c * x + f
+                }
+            }
+        }
+    }
+
+    /// The table `encode` looks values up in, turning linear light back into this curve's values
+    fn inverse(&self) -> Box<[f32]> {
+        (0..ENCODE_LEN)
+            .map(|k| {
+                let light = (k as f32 / (ENCODE_LEN - 1) as f32).powi(2);
+                // tone curves go up, so we can look for the value giving `light` by bisection
+                let (mut low, mut high) = (0.0f32, 1.0f32);
+                for _ in 0..24 {
+                    let mid = (low + high) / 2.0;
+                    if self.eval(mid) < light {
+                        low = mid;
+                    } else {
+                        high = mid;
+                    }
+                }
+                (low + high) / 2.0
+            })
+            .collect()
+    }
+}
+
+/// Looks `light` up in a table made by `Curve::inverse`
+fn encode(table: &[f32], light: f32) -> f32 {
+    let pos = light.clamp(0.0, 1.0).sqrt() * (table.len() - 1) as f32;
+    let i = (pos as usize).min(table.len() - 2);
+    let t = pos - i as f32;
+    table[i] * (1.0 - t) + table[i + 1] * t
+}
+
+/// Converts colors from one profile to another
+#[derive(Clone)]
+pub struct Transform {
+    /// from the source's linear RGB to the destination's
+    matrix: [[f32; 3]; 3],
+    /// the source's tone curves
+    curves: [Curve; 3],
+    /// the destination's tone curves, inverted (see `Curve::inverse`)
+    encode: [Box<[f32]>; 3],
+}
+
+impl Transform {
+    /// `None` when there is nothing to convert, because colors look the same in both profiles
+    pub fn new(from: &Profile, to: &Profile) -> Option<Self> {
+        if from.same_as(to) {
+            return None;
+        }
+        let from_xyz = invert(&to.to_xyz)?;
+        let matrix = std::array::from_fn(|row| {
+            std::array::from_fn(|col| (0..3).map(|k| from_xyz[row][k] * from.to_xyz[k][col]).sum())
+        });
+        Some(Self {
+            matrix,
+            curves: from.curves.clone(),
+            encode: std::array::from_fn(|c| to.curves[c].inverse()),
+        })
+    }
+
+    /// Converts 8 bit `pixels`, whose first 3 channels out of `channels` are red, green and blue
+    pub fn convert_u8(&self, pixels: &mut [u8], channels: usize) {
+        self.convert(pixels, channels, u8::MAX.into(), |value| value as u8);
+    }
+
+    /// Converts 16 bit `pixels`, whose first 3 channels out of `channels` are red, green and blue
+    pub fn convert_u16(&self, pixels: &mut [u16], channels: usize) {
+        self.convert(pixels, channels, u16::MAX.into(), |value| value as u16);
+    }
+
+    /// `max` is the value of a full channel, and `from_f32` saturates values outside of 0..=max
+    fn convert<T: Copy + Into<u32>>(
+        &self,
+        pixels: &mut [T],
+        channels: usize,
+        max: u32,
+        from_f32: impl Fn(f32) -> T,
+    ) {
+        // every value a channel can have, in linear light
+        let decode: [Box<[f32]>; 3] = std::array::from_fn(|c| {
+            (0..=max)
+                .map(|value| self.curves[c].eval(value as f32 / max as f32))
+                .collect()
+        });
+        for pixel in pixels.chunks_exact_mut(channels) {
+            let rgb: [f32; 3] = std::array::from_fn(|c| decode[c][pixel[c].into() as usize]);
+            for (c, row) in self.matrix.iter().enumerate() {
+                let light = row[0] * rgb[0] + row[1] * rgb[1] + row[2] * rgb[2];
+                pixel[c] = from_f32(encode(&self.encode[c], light) * max as f32 + 0.5);
+            }
+        }
+    }
+}
+
+fn u32_at(bytes: &[u8], i: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(bytes.get(i..i + 4)?.try_into().ok()?))
+}
+
+fn s15_fixed16_at(bytes: &[u8], i: usize) -> Option<f32> {
+    Some(u32_at(bytes, i)? as i32 as f32 / 65536.0)
+}
+
+fn invert(m: &[[f32; 3]; 3]) -> Option<[[f32; 3]; 3]> {
+    let cofactor = |r: usize, c: usize| {
+        let (r0, r1) = ((r + 1) % 3, (r + 2) % 3);
+        let (c0, c1) = ((c + 1) % 3, (c + 2) % 3);
+        m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]
+    };
+    let det = (0..3).map(|c| m[0][c] * cofactor(0, c)).sum::<f32>();
+    if det.abs() < 1e-6 {
+        return None;
+    }
+    Some(std::array::from_fn(|r| {
+        std::array::from_fn(|c| cofactor(c, r) / det)
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A matrix/TRC profile with the given primaries, as the columns of `to_xyz`, and sRGB's tone
+    /// curve
+    fn profile(to_xyz: [[f32; 3]; 3]) -> Vec<u8> {
+        let mut icc = vec![0u8; 128];
+        icc[16..20].copy_from_slice(b"RGB ");
+        icc[20..24].copy_from_slice(b"XYZ ");
+        icc[36..40].copy_from_slice(b"acsp");
+        let fixed = |x: f32| ((x * 65536.0).round() as i32).to_be_bytes();
+
+        let mut tags = Vec::new();
+        for (c, signature) in [b"rXYZ", b"gXYZ", b"bXYZ"].into_iter().enumerate() {
+            let mut tag = b"XYZ \0\0\0\0".to_vec();
+            for row in to_xyz {
+                tag.extend(fixed(row[c]));
+            }
+            tags.push((signature, tag));
+        }
+        let mut para = b"para\0\0\0\0\0\x03\0\0".to_vec();
+        for p in [2.4, 1.0 / 1.055, 0.055 / 1.055, 1.0 / 12.92, 0.04045] {
+            para.extend(fixed(p));
+        }
+        for signature in [b"rTRC", b"gTRC", b"bTRC"] {
+            tags.push((signature, para.clone()));
+        }
+
+        icc.extend((tags.len() as u32).to_be_bytes());
+        let mut offset = 132 + tags.len() * 12;
+        for (signature, tag) in &tags {
+            icc.extend(*signature);
+            icc.extend((offset as u32).to_be_bytes());
+            icc.extend((tag.len() as u32).to_be_bytes());
+            offset += tag.len();
+        }
+        for (_, tag) in tags {
+            icc.extend(tag);
+        }
+        icc
+    }
+
+    /// Display P3, adapted to D50
+    const P3: [[f32; 3]; 3] = [
+        [0.5151, 0.2920, 0.1571],
+        [0.2412, 0.6922, 0.0666],
+        [-0.0011, 0.0419, 0.7841],
+    ];
+
+    #[test]
+    fn srgb_images_should_not_be_converted() {
+        let srgb = Profile::parse(&profile(Profile::srgb().to_xyz)).unwrap();
+        assert!(Transform::new(&srgb, &Profile::srgb()).is_none());
+    }
+
+    #[test]
+    fn wide_gamut_colors_should_get_more_saturated_in_srgb() {
+        let p3 = Profile::parse(&profile(P3)).unwrap();
+        let transform = Transform::new(&p3, &Profile::srgb()).unwrap();
+
+        let mut pixels = [255, 255, 255, 128, 128, 128, 220, 120, 60];
+        transform.convert_u8(&mut pixels, 3);
+        assert_eq!(pixels[..3], [255, 255, 255]);
+        assert!(
+            pixels[3..6].iter().all(|&c| c.abs_diff(128) <= 1),
+            "{pixels:?}"
+        );
+        let [r, g, b] = [pixels[6], pixels[7], pixels[8]];
+        assert!(r > 220 && g < 120 && b < 60, "{pixels:?}");
+
+        // and back, they are the same, with a bit of rounding
+        let back = Transform::new(&Profile::srgb(), &p3).unwrap();
+        let mut alpha = [r as u16 * 257, g as u16 * 257, b as u16 * 257, 1234];
+        back.convert_u16(&mut alpha, 4);
+        for (value, expected) in alpha.iter().zip([220u16, 120, 60]) {
+            assert!((value / 257).abs_diff(expected) <= 1, "{alpha:?}");
+        }
+        assert_eq!(alpha[3], 1234);
+    }
+
+    #[test]
+    fn should_reject_profiles_we_do_not_understand() {
+        let mut icc = profile(P3);
+        assert!(Profile::parse(&icc[..100]).is_err());
+        icc[16..20].copy_from_slice(b"GRAY");
+        assert!(Profile::parse(&icc).is_err());
+        // without tone curves, like profiles made of lookup tables
+        let mut icc = profile(P3);
+        icc[128..132].copy_from_slice(&3u32.to_be_bytes());
+        assert!(Profile::parse(&icc).is_err());
+    }
+
+    #[test]
+    fn loaded_profiles_should_be_told_apart() {
+        let dir = std::env::temp_dir().join(format!("swww-icc-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut other = P3;
+        other[0][0] += 0.01;
+        let (a, b) = (dir.join("a.icc"), dir.join("b.icc"));
+        std::fs::write(&a, profile(P3)).unwrap();
+        std::fs::write(&b, profile(other)).unwrap();
+        let (a, a_again, b) = (Profile::load(&a), Profile::load(&a), Profile::load(&b));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let (a, a_again, b) = (a.unwrap().id(), a_again.unwrap().id(), b.unwrap().id());
+        assert!(a.is_some());
+        assert_eq!(a, a_again);
+        assert_ne!(a, b);
+        assert_eq!(Profile::srgb().id(), None);
+        assert_eq!(Profile::parse(&profile(P3)).unwrap().id(), None);
+    }
+}
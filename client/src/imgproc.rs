@@ -1,11 +1,12 @@
 use fast_image_resize::{FilterType, PixelType, ResizeAlg, ResizeOptions, Resizer};
 use image::{
     codecs::{gif::GifDecoder, png::PngDecoder, webp::WebPDecoder},
-    AnimationDecoder, DynamicImage, Frames, GenericImageView, ImageFormat,
+    AnimationDecoder, Delay, DynamicImage, Frame, Frames, GenericImageView, ImageDecoder,
+    ImageFormat,
 };
 use std::{
     io::{stdin, Cursor, Read},
-    path::Path,
+    path::{Path, PathBuf},
     time::Duration,
 };
 
@@ -15,122 +16,705 @@ use common::{
 };
 
 use crate::cli::ResizeStrategy;
+use crate::cutout::Cutout;
+use crate::icc;
+use crate::messages::msg;
 
 use super::cli;
 
+/// What an `ImgBuf` holds
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(not(all(feature = "svg", feature = "heif")), allow(dead_code))]
+enum Format {
+    /// anything `image` decodes, and AVIFs
+    Image(ImageFormat),
+    /// rasterized at the size of each output, instead of decoded once (see `ImgBuf::rasterize`)
+    Svg,
+    /// HEIF images, which may be time of day wallpapers (see `ImgBuf::follow_time_of_day`)
+    Heif,
+}
+
+/// The parts of the day of a time of day wallpaper
+#[cfg_attr(not(feature = "heif"), allow(dead_code))]
+struct Day {
+    /// when the first part starts, in seconds after local midnight
+    start: u32,
+    /// the image shown in each part, and for how long
+    slots: Box<[(usize, Duration)]>,
+}
+
 pub struct ImgBuf {
     bytes: Box<[u8]>,
-    format: ImageFormat,
+    format: Format,
     is_animated: bool,
+    /// the frames, in order, when we were given a directory of frames. `bytes` is the first one
+    sequence: Option<Box<[PathBuf]>>,
+    /// for time of day wallpapers, the image shown in each part of the day
+    day: Option<Day>,
+    /// from the colors of the image's embedded ICC profile to the outputs' (see `convert_colors`)
+    colors: Option<icc::Transform>,
+    /// the page of multi-page TIFFs `decode` decodes, from 0 (see `select_page`)
+    page: usize,
 }
 
 impl ImgBuf {
-    /// Create a new ImgBuf from a given path. Use - for Stdin
+    /// Create a new ImgBuf from a given path. Use - for Stdin, and a directory for a sequence of
+    /// numbered frames
     pub fn new(path: &Path) -> Result<Self, String> {
+        if path.is_dir() {
+            let frames = sequence_frames(path)?;
+            let mut imgbuf = Self::new(&frames[0])?;
+            imgbuf.is_animated = frames.len() > 1;
+            imgbuf.sequence = Some(frames);
+            return Ok(imgbuf);
+        }
+
         let bytes = if let Some("-") = path.to_str() {
             let mut bytes = Vec::new();
             stdin()
                 .read_to_end(&mut bytes)
-                .map_err(|e| format!("failed to read standard input: {e}"))?;
+                .map_err(|e| msg!("read-stdin-failed", error = e))?;
             bytes
         } else {
-            std::fs::read(path).map_err(|e| format!("failed to read file: {e}"))?
+            std::fs::read(path).map_err(|e| msg!("read-file-failed", error = e))?
         };
 
+        if is_svg(&bytes) {
+            #[cfg(not(feature = "svg"))]
+            return Err(msg!("svg-disabled"));
+            #[cfg(feature = "svg")]
+            {
+                // so invalid SVGs fail here, like other images do
+                crate::svg::Svg::parse(&bytes)?;
+                return Ok(Self {
+                    bytes: bytes.into_boxed_slice(),
+                    format: Format::Svg,
+                    is_animated: false,
+                    sequence: None,
+                    day: None,
+                    colors: None,
+                    page: 0,
+                });
+            }
+        }
+
+        if is_heif(&bytes) {
+            #[cfg(not(feature = "heif"))]
+            return Err(msg!("heif-disabled"));
+            #[cfg(feature = "heif")]
+            {
+                crate::heif::Heif::new(&bytes)?;
+                return Ok(Self {
+                    bytes: bytes.into_boxed_slice(),
+                    format: Format::Heif,
+                    is_animated: false,
+                    sequence: None,
+                    day: None,
+                    colors: None,
+                    page: 0,
+                });
+            }
+        }
+
         let reader = image::io::Reader::new(Cursor::new(&bytes))
             .with_guessed_format()
-            .map_err(|e| format!("failed to detect the image's format: {e}"))?;
+            .map_err(|e| msg!("detect-format-failed", error = e))?;
 
-        let format = reader.format();
+        let format = if is_avif(&bytes) {
+            ImageFormat::Avif
+        } else {
+            reader.format().ok_or_else(|| msg!("unknown-format"))?
+        };
         let is_animated = match format {
-            Some(ImageFormat::Gif) => true,
-            Some(ImageFormat::WebP) => WebPDecoder::new(Cursor::new(&bytes))
-                .map_err(|e| format!("failed to decode Webp Image: {e}"))?
+            ImageFormat::Gif => true,
+            #[cfg(feature = "avif")]
+            ImageFormat::Avif => crate::avif::Decoder::new(&bytes)?.is_animated(),
+            #[cfg(not(feature = "avif"))]
+            ImageFormat::Avif => return Err(msg!("avif-disabled")),
+            ImageFormat::WebP => WebPDecoder::new(Cursor::new(&bytes))
+                .map_err(|e| msg!("decode-format-failed", format = "WebP", error = e))?
                 .has_animation(),
-            Some(ImageFormat::Png) => PngDecoder::new(Cursor::new(&bytes))
-                .map_err(|e| format!("failed to decode Png Image: {e}"))?
+            ImageFormat::Png => PngDecoder::new(Cursor::new(&bytes))
+                .map_err(|e| msg!("decode-format-failed", format = "PNG", error = e))?
                 .is_apng()
-                .map_err(|e| format!("failed to detect if Png is animated: {e}"))?,
-            None => return Err("Unknown image format".to_string()),
+                .map_err(|e| msg!("detect-apng-failed", error = e))?,
             _ => false,
         };
 
         Ok(Self {
-            format: format.unwrap(), // this is ok because we return err earlier if it is None
+            format: Format::Image(format),
             bytes: bytes.into_boxed_slice(),
             is_animated,
+            sequence: None,
+            day: None,
+            colors: None,
+            page: 0,
         })
     }
 
+    /// Makes images with an embedded ICC profile convert their colors to `profile`, the outputs'
+    /// one, when they are decoded. Profiles we do not understand are ignored, with a warning
+    pub fn convert_colors(&mut self, profile: &icc::Profile) {
+        // AVIF, HEIF and SVG images are assumed to be sRGB
+        let Format::Image(format) = self.format else {
+            return;
+        };
+        if format == ImageFormat::Avif {
+            return;
+        }
+        let mut reader = image::io::Reader::new(Cursor::new(&self.bytes));
+        reader.set_format(format);
+        // images we cannot decode fail later, when we decode them
+        let Ok(Some(embedded)) = reader
+            .into_decoder()
+            .and_then(|mut decoder| decoder.icc_profile())
+        else {
+            return;
+        };
+        match icc::Profile::parse(&embedded) {
+            Ok(embedded) => self.colors = icc::Transform::new(&embedded, profile),
+            Err(e) => eprintln!("{}", msg!("icc-ignored", error = e)),
+        }
+    }
+
+    /// Picks the page of multi-page TIFFs `decode` decodes, or with `Page::All`, makes them play
+    /// their pages as an animation. Other images only have a first page
+    pub fn select_page(&mut self, page: cli::Page) -> Result<(), String> {
+        let is_tiff = self.is_tiff();
+        match page {
+            cli::Page::All if is_tiff => {
+                self.is_animated = crate::tiff::page_count(&self.bytes)? > 1;
+            }
+            cli::Page::All => (),
+            cli::Page::Number(number) => {
+                let pages = if is_tiff && number.get() > 1 {
+                    crate::tiff::page_count(&self.bytes)?
+                } else {
+                    1
+                };
+                let index = usize::from(number.get() - 1);
+                if index >= pages {
+                    return Err(msg!("page-missing", page = number, pages = pages));
+                }
+                self.page = index;
+            }
+        }
+        Ok(())
+    }
+
+    /// Makes time of day wallpapers follow the time of day at `location` (see `time_of_day`).
+    /// Their frames then start with the image for now, and `day_start` tells when it started
+    pub fn follow_time_of_day(&mut self, location: Option<(f64, f64)>) -> Result<(), String> {
+        #[cfg(feature = "heif")]
+        if self.format == Format::Heif {
+            let heif = crate::heif::Heif::new(&self.bytes)?;
+            for xmp in heif.xmp()? {
+                if let Some(schedule) =
+                    crate::time_of_day::Schedule::from_xmp(&xmp, heif.image_count(), location)?
+                {
+                    self.day = Some(Day {
+                        start: schedule.start,
+                        slots: schedule.slots.into_boxed_slice(),
+                    });
+                    break;
+                }
+            }
+        }
+        let _ = location;
+        Ok(())
+    }
+
+    /// For time of day wallpapers, when their first frame starts, in seconds after local midnight
+    pub fn day_start(&self) -> Option<u32> {
+        self.day.as_ref().map(|day| day.start)
+    }
+
     pub fn is_animated(&self) -> bool {
         self.is_animated
     }
 
-    /// Decode the ImgBuf into am RgbImage
-    pub fn decode(&self, format: PixelFormat) -> Result<Image, String> {
-        let mut reader = image::io::Reader::new(Cursor::new(&self.bytes));
-        reader.set_format(self.format);
-        let dynimage = reader
-            .decode()
-            .map_err(|e| format!("failed to decode image: {e}"))?;
+    /// How many times the image says its animation plays, or `None` for forever. Only GIFs say
+    /// it, so other animations play forever
+    pub fn plays(&self) -> Option<u16> {
+        match self.format {
+            Format::Image(ImageFormat::Gif) => match gif_loop_count(&self.bytes) {
+                // like browsers do, GIFs without a loop count play once
+                None => Some(1),
+                Some(0) => None,
+                // the count is of the times it plays again
+                Some(count) => Some(count.saturating_add(1)),
+            },
+            _ => None,
+        }
+    }
 
-        let width = dynimage.width();
-        let height = dynimage.height();
+    /// whether this is a directory of frames, or a TIFF playing its pages. Neither have timing of
+    /// their own, so they are played at `--fps`
+    pub fn is_sequence(&self) -> bool {
+        self.sequence.is_some() || (self.is_tiff() && self.is_animated)
+    }
 
-        let bytes = {
-            let mut img = if format.channels() == 3 {
-                dynimage.into_rgb8().into_raw().into_boxed_slice()
-            } else {
-                dynimage.into_rgba8().into_raw().into_boxed_slice()
-            };
+    fn is_tiff(&self) -> bool {
+        self.format == Format::Image(ImageFormat::Tiff)
+    }
 
-            if format.must_swap_r_and_b_channels() {
-                for pixel in img.chunks_exact_mut(format.channels() as usize) {
-                    pixel.swap(0, 2);
+    /// Decode the ImgBuf into am RgbImage, tone mapping HDR images with `tone_map`
+    pub fn decode(
+        &self,
+        format: PixelFormat,
+        dither: cli::Dither,
+        tone_map: cli::ToneMap,
+    ) -> Result<(Image, Quantization), String> {
+        let dynimage = match self.format {
+            #[cfg(feature = "svg")]
+            Format::Svg => {
+                let svg = crate::svg::Svg::parse(&self.bytes)?;
+                svg.render(svg.size(), ResizeStrategy::No, &[0, 0, 0])?
+            }
+            #[cfg(feature = "heif")]
+            Format::Heif => {
+                let heif = crate::heif::Heif::new(&self.bytes)?;
+                match &self.day {
+                    // the image for now, which the frames start with
+                    Some(day) => heif.image(day.slots[0].0)?,
+                    None => heif.primary()?,
                 }
             }
-            img
+            #[cfg(feature = "avif")]
+            Format::Image(ImageFormat::Avif) => {
+                crate::avif::Decoder::new(&self.bytes)?.into_image()?
+            }
+            Format::Image(ImageFormat::Tiff) if self.page > 0 => {
+                crate::tiff::page(&self.bytes, self.page)?
+            }
+            Format::Image(image_format) => {
+                let mut reader = image::io::Reader::new(Cursor::new(&self.bytes));
+                reader.set_format(image_format);
+                reader
+                    .decode()
+                    .map_err(|e| msg!("decode-failed", error = e))?
+            }
+            #[allow(unreachable_patterns)]
+            _ => unreachable!("formats are only detected with their features"),
         };
 
-        Ok(Image {
-            width,
-            height,
-            bytes,
-            format,
-        })
+        let dynimage = match &self.colors {
+            Some(colors) => convert_colors(dynimage, colors),
+            None => dynimage,
+        };
+        let dynimage = hdr_tone_map(dynimage, tone_map);
+        let quantization = Quantization::new(&dynimage, dither);
+        Ok((
+            Image::new(dynimage, format, quantization.dithered),
+            quantization,
+        ))
     }
 
-    /// Convert this ImgBuf into Frames
-    pub fn as_frames(&self) -> Result<Frames, String> {
-        match self.format {
-            ImageFormat::Gif => Ok(GifDecoder::new(Cursor::new(&self.bytes))
-                .map_err(|e| format!("failed to decode gif during animation: {e}"))?
-                .into_frames()),
-            ImageFormat::WebP => Ok(WebPDecoder::new(Cursor::new(&self.bytes))
-                .map_err(|e| format!("failed to decode webp during animation: {e}"))?
-                .into_frames()),
-            ImageFormat::Png => Ok(PngDecoder::new(Cursor::new(&self.bytes))
-                .map_err(|e| format!("failed to decode png during animation: {e}"))?
+    /// SVGs, rasterized straight at `dim` instead of decoded once and resized for each output.
+    /// `None` for every other image
+    pub fn rasterize(
+        &self,
+        dim: (u32, u32),
+        resize: ResizeStrategy,
+        color: &[u8; 3],
+        format: PixelFormat,
+    ) -> Result<Option<Image>, String> {
+        #[cfg(feature = "svg")]
+        if self.format == Format::Svg {
+            let img = crate::svg::Svg::parse(&self.bytes)?.render(dim, resize, color)?;
+            return Ok(Some(Image::new(img, format, false)));
+        }
+        let _ = (dim, resize, color, format);
+        Ok(None)
+    }
+
+    /// Convert this ImgBuf into Frames. Sequences and TIFF pages are played at
+    /// `timing.sequence_fps`, and time of day wallpapers have a frame for each part of the day
+    pub fn as_frames(&self, timing: &FrameTiming) -> Result<Frames<'_>, String> {
+        let frames = self.frames(timing)?;
+        let Some(colors) = &self.colors else {
+            return Ok(frames);
+        };
+        Ok(Frames::new(Box::new(frames.map(move |frame| {
+            let frame = frame?;
+            let (left, top, delay) = (frame.left(), frame.top(), frame.delay());
+            let mut buffer = frame.into_buffer();
+            colors.convert_u8(&mut buffer, 4);
+            Ok(Frame::from_parts(buffer, left, top, delay))
+        }))))
+    }
+
+    fn frames(&self, timing: &FrameTiming) -> Result<Frames<'_>, String> {
+        if let Some(frames) = &self.sequence {
+            let delay = timing.sequence_delay();
+            return Ok(Frames::new(Box::new(frames.iter().map(move |path| {
+                let img = image::open(path)?.into_rgba8();
+                Ok(Frame::from_parts(img, 0, 0, delay))
+            }))));
+        }
+        #[cfg(feature = "heif")]
+        if let Some(day) = &self.day {
+            let heif = crate::heif::Heif::new(&self.bytes)?;
+            return Ok(Frames::new(Box::new(day.slots.iter().map(
+                move |&(index, duration)| {
+                    let img = heif.image(index).map_err(|e| {
+                        image::ImageError::Decoding(image::error::DecodingError::new(
+                            image::error::ImageFormatHint::Name("HEIF".to_string()),
+                            e,
+                        ))
+                    })?;
+                    let delay = Delay::from_saturating_duration(duration);
+                    Ok(Frame::from_parts(img.into_rgba8(), 0, 0, delay))
+                },
+            ))));
+        }
+        let Format::Image(format) = self.format else {
+            unreachable!("only images `image` knows about are animated")
+        };
+        let frames = match format {
+            ImageFormat::Gif => GifDecoder::new(Cursor::new(&self.bytes))
+                .map_err(|e| msg!("decode-animation-failed", format = "GIF", error = e))?
+                .into_frames(),
+            ImageFormat::WebP => WebPDecoder::new(Cursor::new(&self.bytes))
+                .map_err(|e| msg!("decode-animation-failed", format = "WebP", error = e))?
+                .into_frames(),
+            ImageFormat::Png => PngDecoder::new(Cursor::new(&self.bytes))
+                .map_err(|e| msg!("decode-animation-failed", format = "PNG", error = e))?
                 .apng()
                 .unwrap() // we detected this earlier
-                .into_frames()),
-            _ => Err(format!(
-                "requested format has no decoder: {:#?}",
-                self.format
-            )),
+                .into_frames(),
+            ImageFormat::Tiff => {
+                let delay = timing.sequence_delay();
+                return Ok(Frames::new(Box::new(crate::tiff::pages(&self.bytes)?.map(
+                    move |page| {
+                        let img = page.map_err(|e| {
+                            image::ImageError::Decoding(image::error::DecodingError::new(
+                                image::error::ImageFormatHint::Exact(ImageFormat::Tiff),
+                                e,
+                            ))
+                        })?;
+                        Ok(Frame::from_parts(img.into_rgba8(), 0, 0, delay))
+                    },
+                ))));
+            }
+            #[cfg(feature = "avif")]
+            ImageFormat::Avif => Frames::new(Box::new(crate::avif::Decoder::new(&self.bytes)?)),
+            _ => return Err(msg!("no-decoder", format = format!("{format:?}"))),
+        };
+        Ok(clamp_delays(frames, timing.delay_clamp))
+    }
+}
+
+/// What happened when reducing an image to 8 bits per channel
+pub struct Quantization {
+    /// bits per channel of the original image
+    pub bits: u16,
+    /// fraction of neighboring pixels that differ by less than one 8 bit step, meaning they are
+    /// part of a gradient that will band
+    pub gradients: f32,
+    pub dithered: bool,
+}
+
+impl Quantization {
+    /// Above this fraction of gradients, images are considered banding-prone
+    const BANDING_THRESHOLD: f32 = 0.1;
+
+    fn new(img: &DynamicImage, dither: cli::Dither) -> Self {
+        let color = img.color();
+        let bits = color.bits_per_pixel() / color.channel_count() as u16;
+        if bits <= 8 {
+            return Self {
+                bits,
+                gradients: 0.0,
+                dithered: false,
+            };
+        }
+
+        let gradients = match img {
+            DynamicImage::ImageLuma16(img) => fine_gradients(img),
+            DynamicImage::ImageLumaA16(img) => fine_gradients(img),
+            DynamicImage::ImageRgb16(img) => fine_gradients(img),
+            DynamicImage::ImageRgba16(img) => fine_gradients(img),
+            // floating point images were tone mapped into 16 bits already
+            img => fine_gradients(&img.to_rgb16()),
+        };
+        let dithered = match dither {
+            cli::Dither::Auto => gradients > Self::BANDING_THRESHOLD,
+            cli::Dither::Always => true,
+            cli::Dither::Never => false,
+        };
+        Self {
+            bits,
+            gradients,
+            dithered,
+        }
+    }
+
+    pub fn is_banding_prone(&self) -> bool {
+        self.gradients > Self::BANDING_THRESHOLD
+    }
+}
+
+impl std::fmt::Display for Quantization {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.bits <= 8 {
+            return f.write_str(&msg!("no-quantization", bits = self.bits));
         }
+        let gradients = format!("{:.1}", self.gradients * 100.0);
+        let threshold = format!("{:.0}", Self::BANDING_THRESHOLD * 100.0);
+        let msg = if self.dithered {
+            msg!(
+                "quantization-dithering",
+                bits = self.bits,
+                gradients = gradients,
+                threshold = threshold
+            )
+        } else {
+            msg!(
+                "quantization-not-dithering",
+                bits = self.bits,
+                gradients = gradients,
+                threshold = threshold
+            )
+        };
+        f.write_str(&msg)
     }
 }
 
+/// How many pixels along each side `fine_gradients` looks at, at most. Gradients that band are
+/// wide, so a grid of this many pixels finds them as well as the whole image would
+const GRADIENT_SAMPLES: u32 = 512;
+
+/// the fraction of neighboring pixels that differ, but by less than one 8 bit step, measured on a
+/// grid of pixels spread over the image and their neighbors
+fn fine_gradients<P: image::Pixel<Subpixel = u16>>(img: &image::ImageBuffer<P, Vec<u16>>) -> f32 {
+    // leaving alpha out: gray images with alpha have 2 channels, and color ones 4
+    let colors = match P::CHANNEL_COUNT {
+        2 => 1,
+        channels => channels.min(3) as usize,
+    };
+    let is_fine = |a: &P, b: &P| {
+        let diff = a.channels()[..colors]
+            .iter()
+            .zip(&b.channels()[..colors])
+            .map(|(&a, &b)| a.abs_diff(b))
+            .max()
+            .unwrap_or(0);
+        diff > 0 && diff < 257
+    };
+
+    let (width, height) = img.dimensions();
+    let step_x = (width / GRADIENT_SAMPLES).max(1) as usize;
+    let step_y = (height / GRADIENT_SAMPLES).max(1) as usize;
+    let mut fine = 0usize;
+    let mut total = 0usize;
+    for y in (0..height).step_by(step_y) {
+        for x in (0..width).step_by(step_x) {
+            let pixel = img.get_pixel(x, y);
+            if x + 1 < width {
+                fine += is_fine(pixel, img.get_pixel(x + 1, y)) as usize;
+                total += 1;
+            }
+            if y + 1 < height {
+                fine += is_fine(pixel, img.get_pixel(x, y + 1)) as usize;
+                total += 1;
+            }
+        }
+    }
+    if total == 0 {
+        0.0
+    } else {
+        fine as f32 / total as f32
+    }
+}
+
+/// Converts the colors of RGB images with `colors`. Other images do not come with the RGB profiles
+/// it converts from
+fn convert_colors(mut img: DynamicImage, colors: &icc::Transform) -> DynamicImage {
+    match &mut img {
+        DynamicImage::ImageRgb8(img) => colors.convert_u8(img, 3),
+        DynamicImage::ImageRgba8(img) => colors.convert_u8(img, 4),
+        DynamicImage::ImageRgb16(img) => colors.convert_u16(img, 3),
+        DynamicImage::ImageRgba16(img) => colors.convert_u16(img, 4),
+        _ => (),
+    }
+    img
+}
+
+/// Brings floating point images (OpenEXR and Radiance HDR), whose colors are linear and may be
+/// far brighter than white, into sRGB with `tone_map`. They come out with 16 bits per channel, so
+/// they can still be dithered. Other images are returned as they are
+fn hdr_tone_map(img: DynamicImage, tone_map: cli::ToneMap) -> DynamicImage {
+    let img = match img {
+        DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_) => img.into_rgba32f(),
+        img => return img,
+    };
+    let to_u16 = |c: f32| (c.clamp(0.0, 1.0) * 65535.0).round() as u16;
+    let mut out = image::ImageBuffer::<image::Rgba<u16>, _>::new(img.width(), img.height());
+    for (src, dst) in img.pixels().zip(out.pixels_mut()) {
+        let [r, g, b, a] = src.0;
+        let [r, g, b] = tone_map_pixel([r, g, b], tone_map).map(linear_to_srgb);
+        dst.0 = [to_u16(r), to_u16(g), to_u16(b), to_u16(a)];
+    }
+    DynamicImage::ImageRgba16(out)
+}
+
+/// Maps linear light of any brightness into the 0 to 1 range
+fn tone_map_pixel(rgb: [f32; 3], tone_map: cli::ToneMap) -> [f32; 3] {
+    // renderers sometimes leave negative values and NaNs behind. Infinities are capped at the
+    // largest half float, which is what most EXRs store
+    let rgb = rgb.map(|c| if c > 0.0 { c.min(65504.0) } else { 0.0 });
+    match tone_map {
+        cli::ToneMap::Clip => rgb.map(|c| c.min(1.0)),
+        cli::ToneMap::Reinhard => {
+            // on the luminance, instead of each channel, so bright colors keep their hue
+            let luminance = 0.2126 * rgb[0] + 0.7152 * rgb[1] + 0.0722 * rgb[2];
+            let scale = 1.0 / (1.0 + luminance);
+            rgb.map(|c| (c * scale).min(1.0))
+        }
+        // Krzysztof Narkowicz's fit of the ACES curve
+        cli::ToneMap::Aces => rgb.map(|c| {
+            let c = c * 0.6;
+            ((c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14)).clamp(0.0, 1.0)
+        }),
+    }
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Reduces `pixels`, with 16 bits per channel in native endianness, to 8 bits per channel with a
+/// 4x4 Bayer matrix. The matrix is laid over the pixels as they will be shown, so this must be
+/// done after resizing them
+fn ordered_dither(pixels: &[u8], width: u32, channels: usize) -> Box<[u8]> {
+    #[rustfmt::skip]
+    const BAYER: [[f32; 4]; 4] = [
+        [ 0.0,  8.0,  2.0, 10.0],
+        [12.0,  4.0, 14.0,  6.0],
+        [ 3.0, 11.0,  1.0,  9.0],
+        [15.0,  7.0, 13.0,  5.0],
+    ];
+
+    let channel = |pixel: &[u8], i: usize| u16::from_ne_bytes([pixel[2 * i], pixel[2 * i + 1]]);
+    let mut bytes = Vec::with_capacity(pixels.len() / 2);
+    for (i, pixel) in pixels.chunks_exact(channels * 2).enumerate() {
+        let (x, y) = (i % width as usize, i / width as usize);
+        let threshold = (BAYER[y % 4][x % 4] + 0.5) / 16.0 - 0.5;
+        for c in 0..3 {
+            let value = channel(pixel, c) as f32 / 257.0 + threshold;
+            bytes.push(value.round().clamp(0.0, 255.0) as u8);
+        }
+        if channels == 4 {
+            bytes.push((channel(pixel, 3) >> 8) as u8);
+        }
+    }
+    bytes.into_boxed_slice()
+}
+
 /// Created by decoding an ImgBuf
 pub struct Image {
     width: u32,
     height: u32,
     format: PixelFormat,
     bytes: Box<[u8]>,
+    /// the pixels with 16 bits per channel, in native endianness, for images we dither. `bytes`
+    /// has them dithered at this size, but resizing starts from these, and dithers the result
+    deep: Option<Box<[u8]>>,
 }
 
 impl Image {
+    /// Converts `dynimage` to `format`, dithering it down to 8 bits per channel if `dither`
+    fn new(dynimage: DynamicImage, format: PixelFormat, dither: bool) -> Self {
+        let width = dynimage.width();
+        let height = dynimage.height();
+        let channels = format.channels() as usize;
+
+        let deep = dither.then(|| {
+            let mut deep = if channels == 3 {
+                dynimage.to_rgb16().into_raw()
+            } else {
+                dynimage.to_rgba16().into_raw()
+            };
+            if format.must_swap_r_and_b_channels() {
+                for pixel in deep.chunks_exact_mut(channels) {
+                    pixel.swap(0, 2);
+                }
+            }
+            deep.iter()
+                .flat_map(|c| c.to_ne_bytes())
+                .collect::<Box<[u8]>>()
+        });
+
+        let bytes = match &deep {
+            Some(deep) => ordered_dither(deep, width, channels),
+            None => {
+                let mut bytes = if channels == 3 {
+                    dynimage.into_rgb8().into_raw().into_boxed_slice()
+                } else {
+                    dynimage.into_rgba8().into_raw().into_boxed_slice()
+                };
+                if format.must_swap_r_and_b_channels() {
+                    for pixel in bytes.chunks_exact_mut(channels) {
+                        pixel.swap(0, 2);
+                    }
+                }
+                bytes
+            }
+        };
+
+        Self {
+            width,
+            height,
+            bytes,
+            format,
+            deep,
+        }
+    }
+
+    /// Resizes the image to `dim` with `options`, dithering the result if we keep its 16 bit
+    /// pixels
+    fn resize(&self, dim: (u32, u32), options: &ResizeOptions) -> Result<Box<[u8]>, String> {
+        let channels = self.format.channels() as usize;
+        let (pixels, pixel_type) = match (&self.deep, channels) {
+            (Some(deep), 3) => (deep, PixelType::U16x3),
+            (Some(deep), _) => (deep, PixelType::U16x4),
+            (None, 3) => (&self.bytes, PixelType::U8x3),
+            (None, _) => (&self.bytes, PixelType::U8x4),
+        };
+        let src = fast_image_resize::images::ImageRef::new(
+            self.width,
+            self.height,
+            pixels.as_ref(),
+            pixel_type,
+        )
+        .map_err(|e| e.to_string())?;
+
+        let mut dst = fast_image_resize::images::Image::new(dim.0, dim.1, pixel_type);
+        Resizer::new()
+            .resize(&src, &mut dst, Some(options))
+            .map_err(|e| e.to_string())?;
+
+        Ok(match self.deep {
+            Some(_) => ordered_dither(dst.buffer(), dim.0, channels),
+            None => dst.into_vec().into_boxed_slice(),
+        })
+    }
+
+    /// The pixels, in the image's format
+    pub fn into_bytes(self) -> Box<[u8]> {
+        self.bytes
+    }
+
+    #[must_use]
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
     #[must_use]
     fn crop(&self, x: u32, y: u32, width: u32, height: u32) -> Self {
         // make sure we don't crop a region larger than the image
@@ -155,6 +739,7 @@ impl Image {
             height: height as u32,
             bytes: bytes.into_boxed_slice(),
             format: self.format,
+            deep: None,
         }
     }
 
@@ -181,73 +766,341 @@ impl Image {
             height,
             format,
             bytes,
+            deep: None,
+        }
+    }
+}
+
+/// How the frames of an animation should be timed
+pub struct FrameTiming {
+    /// delays stored in animated images at or below this become 100ms. Sequences, TIFF pages and
+    /// time of day wallpapers are timed by us, and never clamped
+    pub delay_clamp: Duration,
+    /// frame rate to interpolate the animation up to, if any
+    pub interpolate: Option<u16>,
+    /// frame rate of directories of frames and of TIFF pages, which have no timing of their own.
+    /// `None` for everything else
+    pub sequence_fps: Option<u16>,
+    /// the `--color-profile` the frames were converted to, if any (see `icc::Profile::id`). It
+    /// does not change the timing, but the frames have to be cached apart all the same
+    pub color_profile: Option<u64>,
+}
+
+impl FrameTiming {
+    /// Frames are only blended into each other if they are displayed for less than this. Longer
+    /// delays are pauses in the animation, and should stay that way
+    const MAX_INTERPOLATED_DELAY: Duration = Duration::from_millis(250);
+    const DEFAULT_SEQUENCE_FPS: u16 = 24;
+
+    /// distinguishes animations compressed with these settings in the cache
+    pub fn cache_key(&self) -> String {
+        let mut key = String::new();
+        if self.delay_clamp != Duration::from_millis(10) {
+            key.push_str(&format!("clamp{}", self.delay_clamp.as_millis()));
+        }
+        if let Some(fps) = self.interpolate {
+            key.push_str(&format!("interp{fps}"));
+        }
+        if let Some(fps) = self.sequence_fps {
+            key.push_str(&format!("fps{fps}"));
+        }
+        if let Some(id) = self.color_profile {
+            key.push_str(&format!("icc{id:016x}"));
+        }
+        key
+    }
+
+    /// how long each frame of a sequence is displayed
+    fn sequence_delay(&self) -> Delay {
+        let fps = self.sequence_fps.unwrap_or(Self::DEFAULT_SEQUENCE_FPS);
+        Delay::from_numer_denom_ms(1000, fps.into())
+    }
+
+    /// in how many steps a frame displayed for `delay` is split when interpolating
+    fn steps(&self, delay: Duration) -> u32 {
+        match self.interpolate {
+            Some(fps) if fps > 0 && delay < Self::MAX_INTERPOLATED_DELAY => {
+                (delay.as_secs_f64() * fps as f64).round().max(1.0) as u32
+            }
+            _ => 1,
         }
     }
 }
 
+/// Accumulates the frames of an animation, compressing each one against the previous
+struct FrameSink {
+    compressor: Compressor,
+    compressed_frames: Vec<(BitPack, Duration)>,
+    first: Box<[u8]>,
+    first_duration: Duration,
+    canvas: Option<Box<[u8]>>,
+    format: PixelFormat,
+}
+
+impl FrameSink {
+    fn new(first: Box<[u8]>, first_duration: Duration, format: PixelFormat) -> Self {
+        Self {
+            compressor: Compressor::new(),
+            compressed_frames: Vec::new(),
+            first,
+            first_duration,
+            canvas: None,
+            format,
+        }
+    }
+
+    fn push(&mut self, img: Box<[u8]>, duration: Duration) {
+        let prev = self.canvas.as_ref().unwrap_or(&self.first);
+        match self.compressor.compress(prev, &img, self.format) {
+            Some(bytes) => self.compressed_frames.push((bytes, duration)),
+            None => match self.compressed_frames.last_mut() {
+                Some(last) => last.1 += duration,
+                None => self.first_duration += duration,
+            },
+        }
+        self.canvas = Some(img);
+    }
+
+    /// Pushes the frames in between the last one and `to`, when interpolating. `delay` is how
+    /// long the last frame should have been displayed for
+    fn push_interpolated(&mut self, to: &[u8], delay: Duration, timing: &FrameTiming) {
+        let steps = timing.steps(delay);
+        if steps <= 1 {
+            return;
+        }
+        let from = self.canvas.as_ref().unwrap_or(&self.first).clone();
+        for step in 1..steps {
+            let blended = from
+                .iter()
+                .zip(to)
+                .map(|(&a, &b)| ((a as u32 * (steps - step) + b as u32 * step) / steps) as u8)
+                .collect();
+            self.push(blended, delay / steps);
+        }
+    }
+
+    fn finish(mut self) -> Vec<(BitPack, Duration)> {
+        //Add the first frame we got earlier:
+        if let Some(canvas) = self.canvas.as_ref() {
+            match self.compressor.compress(canvas, &self.first, self.format) {
+                Some(bytes) => self.compressed_frames.push((bytes, self.first_duration)),
+                None => match self.compressed_frames.last_mut() {
+                    Some(last) => last.1 += self.first_duration,
+                    None => self.first_duration += self.first_duration,
+                },
+            }
+        }
+        self.compressed_frames
+    }
+}
+
+/// How the frames of an animation are fit to the output
+pub struct FrameFit<'a> {
+    pub resize: ResizeStrategy,
+    pub filter: FilterType,
+    /// fills what the frames don't cover
+    pub color: &'a [u8; 3],
+    /// the part of the output the frames must stay out of, if any
+    pub cutout: Option<Cutout>,
+}
+
 pub fn compress_frames(
     mut frames: Frames,
     dim: (u32, u32),
     format: PixelFormat,
-    filter: FilterType,
-    resize: ResizeStrategy,
-    color: &[u8; 3],
-) -> Result<Vec<(BitPack, Duration)>, String> {
-    let mut compressor = Compressor::new();
-    let mut compressed_frames = Vec::new();
+    fit: &FrameFit,
+    timing: &FrameTiming,
+) -> Result<ipc::Animation, String> {
+    let safe_dim = fit.cutout.map_or(dim, |cutout| {
+        let (_, _, width, height) = cutout.safe_area(dim);
+        (width, height)
+    });
+    let resize_frame = |frame: image::Frame| -> Result<Box<[u8]>, String> {
+        let img = Image::from_frame(frame, format);
+        let resized = match fit.resize {
+            ResizeStrategy::No => img_pad(&img, safe_dim, fit.color),
+            ResizeStrategy::Crop => img_resize_crop(&img, safe_dim, fit.filter),
+            ResizeStrategy::Fit => img_resize_fit(&img, safe_dim, fit.filter, fit.color),
+        }?;
+        // frames always have 3 channels, whatever the output's format
+        Ok(match fit.cutout {
+            Some(cutout) => {
+                let fill = format_color(fit.color, img.format);
+                cutout.compose(&resized, dim, img.format.channels() as usize, &fill)
+            }
+            None => resized,
+        })
+    };
 
     // The first frame should always exist
     let first = frames.next().unwrap().unwrap();
-    let first_duration = first.delay().numer_denom_ms();
-    let mut first_duration = Duration::from_millis((first_duration.0 / first_duration.1).into());
-    let first_img = Image::from_frame(first, format);
-    let first_img = match resize {
-        ResizeStrategy::No => img_pad(&first_img, dim, color)?,
-        ResizeStrategy::Crop => img_resize_crop(&first_img, dim, filter)?,
-        ResizeStrategy::Fit => img_resize_fit(&first_img, dim, filter, color)?,
-    };
+    let first_delay = Duration::from(first.delay());
+    let mut sink = FrameSink::new(
+        resize_frame(first)?,
+        first_delay / timing.steps(first_delay),
+        format,
+    );
 
-    let mut canvas: Option<Box<[u8]>> = None;
+    let mut prev_delay = first_delay;
     while let Some(Ok(frame)) = frames.next() {
-        let (dur_num, dur_div) = frame.delay().numer_denom_ms();
-        let duration = Duration::from_millis((dur_num / dur_div).into());
+        let delay = Duration::from(frame.delay());
+        let img = resize_frame(frame)?;
 
-        let img = Image::from_frame(frame, format);
-        let img = match resize {
-            ResizeStrategy::No => img_pad(&img, dim, color)?,
-            ResizeStrategy::Crop => img_resize_crop(&img, dim, filter)?,
-            ResizeStrategy::Fit => img_resize_fit(&img, dim, filter, color)?,
-        };
+        sink.push_interpolated(&img, prev_delay, timing);
+        sink.push(img, delay / timing.steps(delay));
+        prev_delay = delay;
+    }
 
-        if let Some(canvas) = canvas.as_ref() {
-            match compressor.compress(canvas, &img, format) {
-                Some(bytes) => compressed_frames.push((bytes, duration)),
-                None => match compressed_frames.last_mut() {
-                    Some(last) => last.1 += duration,
-                    None => first_duration += duration,
-                },
+    // the frames from here on go back to the first one
+    let wrap_start = sink.compressed_frames.len();
+    if sink.canvas.is_some() {
+        let first = sink.first.clone();
+        sink.push_interpolated(&first, prev_delay, timing);
+    }
+
+    let animation = sink.finish();
+    Ok(ipc::Animation {
+        wrap_len: (animation.len() - wrap_start) as u32,
+        animation: animation.into_boxed_slice(),
+        day_start: None,
+        plays: None,
+    })
+}
+
+/// Whether `bytes` are an SVG: an xml document whose root is an `<svg>` element
+fn is_svg(bytes: &[u8]) -> bool {
+    let head = String::from_utf8_lossy(&bytes[..bytes.len().min(4096)]);
+    let head = head.trim_start_matches('\u{feff}').trim_start();
+    (head.starts_with("<?xml") || head.starts_with("<!") || head.starts_with("<svg"))
+        && head.contains("<svg")
+}
+
+/// Whether `bytes` are a HEIF image: an ISO media file with a HEIF brand. `mif1` and `msf1` are
+/// generic, and shared with AVIFs, so they only count along with a HEVC one
+fn is_heif(bytes: &[u8]) -> bool {
+    const HEVC: [&[u8]; 6] = [b"heic", b"heix", b"heim", b"heis", b"hevc", b"hevx"];
+    if bytes.get(4..8) != Some(b"ftyp") {
+        return false;
+    }
+    let size = u32::from_be_bytes(bytes[0..4].try_into().unwrap()) as usize;
+    let Some(brands) = bytes.get(8..size.min(bytes.len())) else {
+        return false;
+    };
+    // the major brand, then the minor version and the compatible brands
+    let major = &brands[..brands.len().min(4)];
+    HEVC.contains(&major)
+        || (matches!(major, b"mif1" | b"msf1")
+            && brands
+                .get(8..)
+                .is_some_and(|compatible| compatible.chunks_exact(4).any(|b| HEVC.contains(&b))))
+}
+
+/// Whether `bytes` are an AVIF. `image` does not recognize animated ones, whose brand is `avis`
+fn is_avif(bytes: &[u8]) -> bool {
+    bytes
+        .get(4..12)
+        .is_some_and(|brand| brand == b"ftypavif" || brand == b"ftypavis")
+}
+
+/// The PNG and JPEG files in `dir`, ordered by the last number in their names (so `frame_9.png`
+/// comes before `frame_10.png`), and then by name
+fn sequence_frames(dir: &Path) -> Result<Box<[PathBuf]>, String> {
+    let mut frames: Vec<(Option<u64>, PathBuf)> = std::fs::read_dir(dir)
+        .map_err(|e| msg!("read-dir-failed", path = dir.display(), error = e))?
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| {
+            let extension = path
+                .extension()
+                .and_then(|e| e.to_str())
+                .unwrap_or_default();
+            ["png", "jpg", "jpeg"]
+                .iter()
+                .any(|e| extension.eq_ignore_ascii_case(e))
+                && path.is_file()
+        })
+        .map(|path| (frame_number(&path), path))
+        .collect();
+    if frames.is_empty() {
+        return Err(msg!("empty-sequence", path = dir.display()));
+    }
+    frames.sort_unstable();
+    Ok(frames.into_iter().map(|(_, path)| path).collect())
+}
+
+/// the last run of digits in the file's name, without its extension
+fn frame_number(path: &Path) -> Option<u64> {
+    let stem = path.file_stem()?.to_str()?;
+    let end = stem.rfind(|c: char| c.is_ascii_digit())? + 1;
+    let start = stem[..end]
+        .rfind(|c: char| !c.is_ascii_digit())
+        .map_or(0, |i| i + 1);
+    stem[start..end].parse().ok()
+}
+
+/// The loop count of a GIF's NETSCAPE2.0 (or ANIMEXTS1.0) application extension, if it has one
+fn gif_loop_count(bytes: &[u8]) -> Option<u16> {
+    // the offset past the sub-blocks starting at `i`
+    let skip_sub_blocks = |mut i: usize| {
+        while let Some(&len) = bytes.get(i) {
+            i += 1;
+            if len == 0 {
+                return Some(i);
             }
+            i += len as usize;
+        }
+        None
+    };
+    // the length of the color table `flags` say follows them, if any
+    let color_table_len = |flags: u8| {
+        if flags & 0x80 != 0 {
+            3 << ((flags & 0x07) + 1)
         } else {
-            match compressor.compress(&first_img, &img, format) {
-                Some(bytes) => compressed_frames.push((bytes, duration)),
-                None => first_duration += duration,
-            }
+            0
         }
-        canvas = Some(img);
-    }
+    };
 
-    //Add the first frame we got earlier:
-    if let Some(canvas) = canvas.as_ref() {
-        match compressor.compress(canvas, &first_img, format) {
-            Some(bytes) => compressed_frames.push((bytes, first_duration)),
-            None => match compressed_frames.last_mut() {
-                Some(last) => last.1 += first_duration,
-                None => first_duration += first_duration,
-            },
+    // header and logical screen descriptor
+    let mut i = 13 + color_table_len(*bytes.get(10)?);
+    loop {
+        match *bytes.get(i)? {
+            // extension
+            0x21 => {
+                let label = *bytes.get(i + 1)?;
+                let data = bytes.get(i + 2..)?;
+                if label == 0xFF
+                    && data.len() >= 17
+                    && (&data[..12] == b"\x0BNETSCAPE2.0" || &data[..12] == b"\x0BANIMEXTS1.0")
+                    && data[12] == 3
+                    && data[13] == 1
+                {
+                    return Some(u16::from_le_bytes([data[14], data[15]]));
+                }
+                i = skip_sub_blocks(i + 2)?;
+            }
+            // image descriptor, followed by the image's color table and LZW data
+            0x2C => {
+                let flags = *bytes.get(i + 9)?;
+                i = skip_sub_blocks(i + 11 + color_table_len(flags))?;
+            }
+            // trailer
+            _ => return None,
         }
     }
+}
 
-    Ok(compressed_frames)
+/// Normalizes the delays stored in an animated image the way browsers do it: missing delays, and
+/// those at or below `clamp`, become 100ms
+fn clamp_delays(frames: Frames<'_>, clamp: Duration) -> Frames<'_> {
+    Frames::new(Box::new(frames.map(move |frame| {
+        let frame = frame?;
+        let delay = if Duration::from(frame.delay()) <= clamp {
+            Delay::from_numer_denom_ms(100, 1)
+        } else {
+            frame.delay()
+        };
+        let (left, top) = (frame.left(), frame.top());
+        Ok(Frame::from_parts(frame.into_buffer(), left, top, delay))
+    })))
 }
 
 pub fn make_filter(filter: &cli::Filter) -> fast_image_resize::FilterType {
@@ -260,20 +1113,21 @@ pub fn make_filter(filter: &cli::Filter) -> fast_image_resize::FilterType {
     }
 }
 
+/// `color` as a pixel of `format`
+pub fn format_color(color: &[u8; 3], format: PixelFormat) -> Vec<u8> {
+    let mut pixel = color.to_vec();
+    if format.channels() == 4 {
+        pixel.push(255);
+    }
+    if format.must_swap_r_and_b_channels() {
+        pixel.swap(0, 2);
+    }
+    pixel
+}
+
 pub fn img_pad(img: &Image, dimensions: (u32, u32), color: &[u8; 3]) -> Result<Box<[u8]>, String> {
     let channels = img.format.channels() as usize;
-
-    let mut color3 = color.to_owned();
-    let mut color4 = [color[0], color[1], color[2], 255];
-    let color: &mut [u8] = if channels == 3 {
-        &mut color3
-    } else {
-        &mut color4
-    };
-
-    if img.format.must_swap_r_and_b_channels() {
-        color.swap(0, 2);
-    }
+    let color = &format_color(color, img.format)[..];
     let (padded_w, padded_h) = dimensions;
     let (padded_w, padded_h) = (padded_w as usize, padded_h as usize);
     let mut padded = Vec::with_capacity(padded_h * padded_w * channels);
@@ -347,34 +1201,13 @@ pub fn img_resize_fit(
             (width, (img.height as f32 * scale) as u32)
         };
 
-        let pixel_type = if img.format.channels() == 3 {
-            PixelType::U8x3
-        } else {
-            PixelType::U8x4
-        };
-        let src = match fast_image_resize::images::ImageRef::new(
-            img.width,
-            img.height,
-            img.bytes.as_ref(),
-            pixel_type,
-        ) {
-            Ok(i) => i,
-            Err(e) => return Err(e.to_string()),
-        };
-
-        let mut dst = fast_image_resize::images::Image::new(trg_w, trg_h, pixel_type);
-        let mut resizer = Resizer::new();
         let options = ResizeOptions::new().resize_alg(ResizeAlg::Convolution(filter));
-
-        if let Err(e) = resizer.resize(&src, &mut dst, Some(&options)) {
-            return Err(e.to_string());
-        }
-
         let img = Image {
             width: trg_w,
             height: trg_h,
             format: img.format,
-            bytes: dst.into_vec().into_boxed_slice(),
+            bytes: img.resize((trg_w, trg_h), &options)?,
+            deep: None,
         };
         img_pad(&img, dimensions, padding_color)
     } else {
@@ -389,32 +1222,10 @@ pub fn img_resize_crop(
 ) -> Result<Box<[u8]>, String> {
     let (width, height) = dimensions;
     let resized_img = if (img.width, img.height) != (width, height) {
-        let pixel_type = if img.format.channels() == 3 {
-            PixelType::U8x3
-        } else {
-            PixelType::U8x4
-        };
-        let src = match fast_image_resize::images::ImageRef::new(
-            img.width,
-            img.height,
-            img.bytes.as_ref(),
-            pixel_type,
-        ) {
-            Ok(i) => i,
-            Err(e) => return Err(e.to_string()),
-        };
-
-        let mut dst = fast_image_resize::images::Image::new(width, height, pixel_type);
-        let mut resizer = Resizer::new();
         let options = ResizeOptions::new()
             .resize_alg(ResizeAlg::Convolution(filter))
             .fit_into_destination(Some((0.5, 0.5)));
-
-        if let Err(e) = resizer.resize(&src, &mut dst, Some(&options)) {
-            return Err(e.to_string());
-        }
-
-        dst.into_vec().into_boxed_slice()
+        img.resize(dimensions, &options)?
     } else {
         img.bytes.clone()
     };
@@ -422,44 +1233,114 @@ pub fn img_resize_crop(
     Ok(resized_img)
 }
 
-pub fn make_transition(img: &cli::Img) -> ipc::Transition {
-    let mut angle = img.transition_angle;
-    let step = img.transition_step;
-
-    let x = match img.transition_pos.x {
-        cli::CliCoord::Percent(x) => {
-            if !(0.0..=1.0).contains(&x) {
-                println!(
-                    "Warning: x value not in range [0,1] position might be set outside screen: {x}"
-                );
-            }
-            Coord::Percent(x)
-        }
-        cli::CliCoord::Pixel(x) => Coord::Pixel(x),
+/// Splits `dim` into a strip per weight along `direction`, in order, giving the rounding
+/// leftovers to the strips they come from
+pub fn stack_strips(
+    dim: (u32, u32),
+    direction: cli::StackDirection,
+    weights: &[u32],
+) -> Result<Vec<(u32, u32)>, String> {
+    let length = match direction {
+        cli::StackDirection::Vertical => dim.1,
+        cli::StackDirection::Horizontal => dim.0,
     };
+    let total: u64 = weights.iter().map(|&w| w as u64).sum();
+    let mut done = 0;
+    let mut end = 0;
+    let mut strips = Vec::with_capacity(weights.len());
+    for &weight in weights {
+        done += weight as u64;
+        let start = end;
+        end = (length as u64 * done / total) as u32;
+        if end == start {
+            return Err(msg!("stack-too-small", width = dim.0, height = dim.1));
+        }
+        strips.push(match direction {
+            cli::StackDirection::Vertical => (dim.0, end - start),
+            cli::StackDirection::Horizontal => (end - start, dim.1),
+        });
+    }
+    Ok(strips)
+}
 
-    let y = match img.transition_pos.y {
-        cli::CliCoord::Percent(y) => {
-            if !(0.0..=1.0).contains(&y) {
-                println!(
-                    "Warning: y value not in range [0,1] position might be set outside screen: {y}"
-                );
+/// Joins images the size of `strips` (see `stack_strips`) back into one
+pub fn stack_join(
+    images: &[Box<[u8]>],
+    strips: &[(u32, u32)],
+    direction: cli::StackDirection,
+    channels: usize,
+) -> Box<[u8]> {
+    match direction {
+        // the rows of each strip simply follow each other
+        cli::StackDirection::Vertical => images.concat().into_boxed_slice(),
+        cli::StackDirection::Horizontal => {
+            let height = strips.first().map_or(0, |strip| strip.1 as usize);
+            let mut joined = Vec::with_capacity(images.iter().map(|i| i.len()).sum());
+            for row in 0..height {
+                for (image, &(width, _)) in images.iter().zip(strips) {
+                    let stride = width as usize * channels;
+                    joined.extend_from_slice(&image[row * stride..(row + 1) * stride]);
+                }
             }
-            Coord::Percent(y)
+            joined.into_boxed_slice()
         }
-        cli::CliCoord::Pixel(y) => Coord::Pixel(y),
-    };
+    }
+}
+
+/// Decodes the 'luma' transition's mask, as grayscale
+pub fn load_mask(path: &Path) -> Result<image::GrayImage, String> {
+    image::open(path)
+        .map(DynamicImage::into_luma8)
+        .map_err(|e| msg!("decode-mask-failed", path = path.display(), error = e))
+}
+
+/// Scales and crops the mask to `dimensions` the way `img_resize_crop` does images, so that the
+/// two line up
+pub fn mask_resize(
+    mask: &image::GrayImage,
+    dimensions: (u32, u32),
+    filter: FilterType,
+) -> Result<Box<[u8]>, String> {
+    let (width, height) = dimensions;
+    if mask.dimensions() == dimensions {
+        return Ok(mask.as_raw().clone().into_boxed_slice());
+    }
+    let src = fast_image_resize::images::ImageRef::new(
+        mask.width(),
+        mask.height(),
+        mask.as_raw(),
+        PixelType::U8,
+    )
+    .map_err(|e| e.to_string())?;
+
+    let mut dst = fast_image_resize::images::Image::new(width, height, PixelType::U8);
+    let options = ResizeOptions::new()
+        .resize_alg(ResizeAlg::Convolution(filter))
+        .fit_into_destination(Some((0.5, 0.5)));
+    Resizer::new()
+        .resize(&src, &mut dst, Some(&options))
+        .map_err(|e| e.to_string())?;
+
+    Ok(dst.into_vec().into_boxed_slice())
+}
+
+/// The mask the 'luma' transition follows when it is not given one: the brightness of the new
+/// image itself, which then appears from its shadows up
+pub fn luma_mask(img: &[u8], format: PixelFormat) -> Box<[u8]> {
+    swww_render::compose::luma_mask(
+        img,
+        format.channels() as usize,
+        format.must_swap_r_and_b_channels(),
+    )
+}
+
+pub fn make_transition(img: &cli::Img) -> ipc::Transition {
+    let mut angle = img.transition_angle;
+    let step = img.transition_step;
 
-    let mut pos = Position::new(x, y);
+    let mut pos = position(&img.transition_pos);
 
     let transition_type = match img.transition_type {
-        cli::TransitionType::None => ipc::TransitionType::None,
-        cli::TransitionType::Simple => ipc::TransitionType::Simple,
-        cli::TransitionType::Fade => ipc::TransitionType::Fade,
-        cli::TransitionType::Wipe => ipc::TransitionType::Wipe,
-        cli::TransitionType::Outer => ipc::TransitionType::Outer,
-        cli::TransitionType::Grow => ipc::TransitionType::Grow,
-        cli::TransitionType::Wave => ipc::TransitionType::Wave,
         cli::TransitionType::Right => {
             angle = 0.0;
             ipc::TransitionType::Wipe
@@ -491,20 +1372,48 @@ pub fn make_transition(img: &cli::Img) -> ipc::Transition {
                 ipc::TransitionType::Outer
             }
         }
-        cli::TransitionType::Random => {
-            pos = Position::new(
-                Coord::Percent(fastrand::f32()),
-                Coord::Percent(fastrand::f32()),
-            );
-            angle = fastrand::f64();
-            match fastrand::u8(0..4) {
-                0 => ipc::TransitionType::Simple,
-                1 => ipc::TransitionType::Wipe,
-                2 => ipc::TransitionType::Outer,
-                3 => ipc::TransitionType::Grow,
-                _ => unreachable!(),
-            }
+        // the daemon picks one of `random`
+        cli::TransitionType::Random => ipc::TransitionType::Random,
+        ref transition_type => {
+            base_transition_type(transition_type).expect("aliases are handled above")
+        }
+    };
+    let origins: Box<[Position]> = match (&img.transition_type, &img.transition_origins) {
+        // 'center' and 'any' grow from their own single point
+        (cli::TransitionType::Center | cli::TransitionType::Any, _) | (_, None) => Box::new([]),
+        (_, Some(cli::GrowOrigins::Points(points))) => points.iter().map(position).collect(),
+        (_, Some(cli::GrowOrigins::Random(count))) => (0..*count)
+            .map(|_| {
+                Position::new(
+                    Coord::Percent(fastrand::f32()),
+                    Coord::Percent(fastrand::f32()),
+                )
+            })
+            .collect(),
+    };
+    let random = img
+        .transition_random
+        .iter()
+        .filter_map(|(transition_type, weight)| {
+            Some((base_transition_type(transition_type)?, *weight))
+        })
+        .collect();
+    let random_exclude = img
+        .transition_random_exclude
+        .iter()
+        .flatten()
+        .filter_map(base_transition_type)
+        .collect();
+    // the daemon must not replace what the aliases set
+    let aliased = match img.transition_type {
+        cli::TransitionType::Left
+        | cli::TransitionType::Right
+        | cli::TransitionType::Top
+        | cli::TransitionType::Bottom => ipc::transition_default_bit("angle"),
+        cli::TransitionType::Center | cli::TransitionType::Any => {
+            ipc::transition_default_bit("pos") | ipc::transition_default_bit("origins")
         }
+        _ => 0,
     };
 
     ipc::Transition {
@@ -512,10 +1421,320 @@ pub fn make_transition(img: &cli::Img) -> ipc::Transition {
         step,
         fps: img.transition_fps,
         bezier: img.transition_bezier,
+        spring: img.transition_spring,
+        curve: img.transition_curve.clone().unwrap_or_default(),
+        random,
         angle,
         pos,
         transition_type,
         wave: img.transition_wave,
+        ripple: img.transition_ripple,
+        block_size: img.transition_block_size.get(),
+        slats: (
+            img.transition_slats.get(),
+            img.transition_slat_direction == cli::SlatDirection::Vertical,
+        ),
+        seed: img.transition_seed.unwrap_or_else(|| fastrand::u32(..)),
+        clock: (
+            img.transition_clock_direction == cli::ClockDirection::Clockwise,
+            img.transition_soft_edge,
+        ),
+        cube: (
+            img.transition_axis == cli::CubeAxis::Vertical,
+            img.transition_fov,
+        ),
+        shatter: (img.transition_shards.get(), img.transition_gravity),
+        spiral: (img.transition_turns, img.transition_thickness),
+        tile_size: img.transition_tile_size.get(),
+        softness: img.transition_softness,
+        feather: img.transition_feather,
+        colorspace: match img.transition_colorspace {
+            cli::TransitionColorspace::Srgb => ipc::Colorspace::Srgb,
+            cli::TransitionColorspace::Linear => ipc::Colorspace::Linear,
+            cli::TransitionColorspace::Oklab => ipc::Colorspace::Oklab,
+        },
+        pixel_sort: (
+            img.transition_sort_threshold,
+            img.transition_sort_direction == cli::SortDirection::Vertical,
+        ),
+        halftone: (
+            img.transition_matrix_size,
+            img.transition_matrix == cli::HalftoneMatrix::BlueNoise,
+        ),
+        exposure: (
+            match img.transition_blend {
+                cli::TransitionBlend::Screen => ipc::BlendMode::Screen,
+                cli::TransitionBlend::Multiply => ipc::BlendMode::Multiply,
+            },
+            img.transition_overlay.0,
+            img.transition_overlay.1,
+        ),
+        origins,
+        edge_threshold: img.transition_edge_threshold,
         invert_y: img.invert_y,
+        span: img.transition_span,
+        plugin: img.transition_plugin.clone().unwrap_or_default(),
+        random_exclude,
+        defaulted: img.transition_defaulted & !aliased,
+    }
+}
+
+/// The daemon's version of a `--transition-pos` position, warning about percentages outside of the
+/// screen
+fn position(pos: &cli::CliPosition) -> Position {
+    let x = match pos.x {
+        cli::CliCoord::Percent(x) => {
+            if !(0.0..=1.0).contains(&x) {
+                eprintln!("{}", msg!("position-out-of-range", axis = "x", value = x));
+            }
+            Coord::Percent(x)
+        }
+        cli::CliCoord::Pixel(x) => Coord::Pixel(x),
+    };
+
+    let y = match pos.y {
+        cli::CliCoord::Percent(y) => {
+            if !(0.0..=1.0).contains(&y) {
+                eprintln!("{}", msg!("position-out-of-range", axis = "y", value = y));
+            }
+            Coord::Percent(y)
+        }
+        cli::CliCoord::Pixel(y) => Coord::Pixel(y),
+    };
+
+    Position::new(x, y)
+}
+
+/// The daemon's version of `transition_type`, unless it is an alias of another transition (like
+/// 'left'), or 'random'
+fn base_transition_type(transition_type: &cli::TransitionType) -> Option<ipc::TransitionType> {
+    Some(match transition_type {
+        cli::TransitionType::None => ipc::TransitionType::None,
+        cli::TransitionType::Simple => ipc::TransitionType::Simple,
+        cli::TransitionType::Fade => ipc::TransitionType::Fade,
+        cli::TransitionType::Wipe => ipc::TransitionType::Wipe,
+        cli::TransitionType::Outer => ipc::TransitionType::Outer,
+        cli::TransitionType::Grow => ipc::TransitionType::Grow,
+        cli::TransitionType::Wave => ipc::TransitionType::Wave,
+        cli::TransitionType::Ripple => ipc::TransitionType::Ripple,
+        cli::TransitionType::Pixelate => ipc::TransitionType::Pixelate,
+        cli::TransitionType::Blinds => ipc::TransitionType::Blinds,
+        cli::TransitionType::Dissolve => ipc::TransitionType::Dissolve,
+        cli::TransitionType::Clock => ipc::TransitionType::Clock,
+        cli::TransitionType::Cube => ipc::TransitionType::Cube,
+        cli::TransitionType::Shatter => ipc::TransitionType::Shatter,
+        cli::TransitionType::Spiral => ipc::TransitionType::Spiral,
+        cli::TransitionType::Checkerboard => ipc::TransitionType::Checkerboard,
+        cli::TransitionType::Push => ipc::TransitionType::Push,
+        cli::TransitionType::ZoomBlur => ipc::TransitionType::ZoomBlur,
+        cli::TransitionType::PixelSort => ipc::TransitionType::PixelSort,
+        cli::TransitionType::Halftone => ipc::TransitionType::Halftone,
+        cli::TransitionType::DoubleExposure => ipc::TransitionType::DoubleExposure,
+        cli::TransitionType::Sketch => ipc::TransitionType::Sketch,
+        cli::TransitionType::Luma => ipc::TransitionType::Luma,
+        cli::TransitionType::Plugin => ipc::TransitionType::Plugin,
+        cli::TransitionType::Left
+        | cli::TransitionType::Right
+        | cli::TransitionType::Top
+        | cli::TransitionType::Bottom
+        | cli::TransitionType::Center
+        | cli::TransitionType::Any
+        | cli::TransitionType::Random => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_recognize_still_and_animated_avifs() {
+        assert!(is_avif(b"\0\0\0\x1cftypavif\0\0\0\0"));
+        assert!(is_avif(b"\0\0\0\x20ftypavis\0\0\0\0"));
+        assert!(!is_avif(b"\0\0\0\x18ftypheic"));
+        assert!(!is_avif(b"GIF89a"));
+    }
+
+    #[test]
+    fn should_recognize_heifs() {
+        assert!(is_heif(b"\0\0\0\x18ftypheic\0\0\0\0mif1heic"));
+        assert!(is_heif(b"\0\0\0\x18ftypmif1\0\0\0\0mif1heic"));
+        assert!(!is_heif(b"\0\0\0\x18ftypmif1\0\0\0\0mif1avif"));
+        assert!(!is_heif(b"\0\0\0\x1cftypavif\0\0\0\0"));
+        assert!(!is_heif(b"GIF89a"));
+    }
+
+    #[test]
+    fn luma_masks_should_weigh_green_the_most() {
+        let rgb = [255, 0, 0, 0, 255, 0, 0, 0, 255, 255, 255, 255];
+        assert_eq!(*luma_mask(&rgb, PixelFormat::Bgr), [53, 182, 18, 255]);
+        let bgrx = [0, 0, 255, 255, 0, 255, 0, 255];
+        assert_eq!(*luma_mask(&bgrx, PixelFormat::Xrgb), [53, 182]);
+    }
+
+    #[test]
+    fn images_should_be_dithered_at_the_size_they_are_shown() {
+        // halfway between two 8 bit steps, which only dithering can show
+        let flat = image::ImageBuffer::from_pixel(16, 16, image::Rgb([128 * 257 + 128u16; 3]));
+        let img = Image::new(DynamicImage::ImageRgb16(flat), PixelFormat::Bgr, true);
+        let resized = img_resize_crop(&img, (4, 4), FilterType::Bilinear).unwrap();
+        let high = resized.iter().filter(|&&c| c == 129).count();
+        assert!(resized.iter().all(|&c| c == 128 || c == 129));
+        assert_eq!(high, resized.len() / 2);
+
+        let gradient = image::ImageBuffer::from_fn(600, 600, |x, _| image::Rgb([x as u16 * 4; 3]));
+        assert!(fine_gradients(&gradient) > 0.4);
+    }
+
+    #[test]
+    fn tone_mapping_should_keep_highlights_below_white() {
+        let bright = [3.0, 2.0, 0.5];
+        assert_eq!(tone_map_pixel(bright, cli::ToneMap::Clip), [1.0, 1.0, 0.5]);
+        for tone_map in [cli::ToneMap::Reinhard, cli::ToneMap::Aces] {
+            let [r, g, b] = tone_map_pixel(bright, tone_map);
+            assert!(r < 1.0 && g < r && b < g, "{tone_map:?}: {r} {g} {b}");
+            let dim = tone_map_pixel([0.1; 3], tone_map);
+            assert!(dim[0] > 0.0 && dim[0] < 0.1, "{tone_map:?}: {dim:?}");
+        }
+        assert_eq!(
+            tone_map_pixel([f32::NAN, -1.0, 0.0], cli::ToneMap::Aces),
+            [0.0; 3]
+        );
+    }
+
+    #[test]
+    fn should_read_the_loop_count_of_gifs() {
+        // a 1x1 GIF with a 2 color global table, a comment, an image and an optional loop count
+        let gif = |netscape: &[u8]| {
+            let mut gif = b"GIF89a\x01\0\x01\0\x80\0\0\0\0\0\xff\xff\xff".to_vec();
+            gif.extend_from_slice(b"\x21\xfe\x02hi\0");
+            gif.extend_from_slice(b"\x2c\0\0\0\0\x01\0\x01\0\0\x02\x02\x44\x01\0");
+            gif.extend_from_slice(netscape);
+            gif.push(0x3b);
+            gif
+        };
+        let netscape = b"\x21\xff\x0bNETSCAPE2.0\x03\x01\x05\x01\0";
+        assert_eq!(gif_loop_count(&gif(netscape)), Some(261));
+        assert_eq!(gif_loop_count(&gif(b"")), None);
+        assert_eq!(gif_loop_count(b"GIF89a"), None);
+    }
+
+    #[test]
+    fn should_recognize_svgs() {
+        assert!(is_svg(b"<svg xmlns=\"http://www.w3.org/2000/svg\"/>"));
+        assert!(is_svg(
+            b"\xef\xbb\xbf<?xml version=\"1.0\"?>\n<!-- made by hand -->\n<svg/>"
+        ));
+        assert!(!is_svg(b"<?xml version=\"1.0\"?><rss/>"));
+        assert!(!is_svg(b"\x89PNG\r\n\x1a\n"));
+    }
+
+    #[test]
+    fn stacked_strips_should_fill_the_output() {
+        use cli::StackDirection::{Horizontal, Vertical};
+        assert_eq!(
+            stack_strips((10, 100), Vertical, &[1, 1, 1]).unwrap(),
+            [(10, 33), (10, 33), (10, 34)]
+        );
+        assert_eq!(
+            stack_strips((90, 7), Horizontal, &[2, 1]).unwrap(),
+            [(60, 7), (30, 7)]
+        );
+        assert!(stack_strips((10, 2), Vertical, &[1, 1, 1]).is_err());
+
+        // two 1 pixel wide strips, 2 pixels high
+        let images = [vec![1, 2].into_boxed_slice(), vec![3, 4].into_boxed_slice()];
+        assert_eq!(
+            *stack_join(&images, &[(1, 2), (1, 2)], Horizontal, 1),
+            [1, 3, 2, 4]
+        );
+        assert_eq!(
+            *stack_join(&images, &[(1, 2), (1, 2)], Vertical, 1),
+            [1, 2, 3, 4]
+        );
+    }
+
+    #[test]
+    fn sequences_should_be_in_frame_number_order() {
+        let number = |name: &str| frame_number(Path::new(name));
+        assert_eq!(number("frames/frame_0010.png"), Some(10));
+        assert_eq!(number("shot2_take3.jpg"), Some(3));
+        assert_eq!(number("12.png"), Some(12));
+        assert_eq!(number("cover.png"), None);
+
+        let dir = std::env::temp_dir().join(format!("swww-sequence-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["f10.png", "f9.PNG", "f1.jpg", "notes.txt"] {
+            std::fs::write(dir.join(name), []).unwrap();
+        }
+        let frames = sequence_frames(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+        let names: Vec<_> = frames
+            .unwrap()
+            .iter()
+            .map(|p| p.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(names, ["f1.jpg", "f9.PNG", "f10.png"]);
+    }
+
+    #[test]
+    fn cache_keys_should_tell_color_profiles_apart() {
+        let timing = |color_profile| FrameTiming {
+            delay_clamp: Duration::from_millis(10),
+            interpolate: None,
+            sequence_fps: None,
+            color_profile,
+        };
+        assert_eq!(timing(None).cache_key(), "");
+        assert_eq!(timing(Some(0xabc)).cache_key(), "icc0000000000000abc");
+        assert_ne!(timing(Some(1)).cache_key(), timing(Some(2)).cache_key());
+    }
+
+    fn frames(colors: &[u8], delay: Delay) -> Frames<'static> {
+        let frames: Vec<_> = colors
+            .iter()
+            .map(|&c| {
+                let img = image::RgbaImage::from_pixel(2, 2, image::Rgba([c, c, c, 255]));
+                Ok(Frame::from_parts(img, 0, 0, delay))
+            })
+            .collect();
+        Frames::new(Box::new(frames.into_iter()))
+    }
+
+    #[test]
+    fn only_stored_delays_should_be_clamped() {
+        let delays = |frames: Frames| -> Vec<Duration> {
+            frames.map(|f| Duration::from(f.unwrap().delay())).collect()
+        };
+        let clamp = Duration::from_millis(10);
+        let fast = Delay::from_numer_denom_ms(25, 3);
+        assert_eq!(
+            delays(clamp_delays(frames(&[0], fast), clamp)),
+            [Duration::from_millis(100)]
+        );
+        let slow = Delay::from_numer_denom_ms(125, 3);
+        assert_eq!(
+            delays(clamp_delays(frames(&[0], slow), clamp)),
+            [Duration::from_nanos(41_666_666)]
+        );
+
+        // a sequence at 120fps is neither clamped nor rounded to whole milliseconds
+        let timing = FrameTiming {
+            delay_clamp: clamp,
+            interpolate: None,
+            sequence_fps: Some(120),
+            color_profile: None,
+        };
+        let fit = FrameFit {
+            resize: ResizeStrategy::No,
+            filter: FilterType::Bilinear,
+            color: &[0; 3],
+            cutout: None,
+        };
+        let sequence = frames(&[0, 100, 200], timing.sequence_delay());
+        let animation =
+            compress_frames(sequence, (2, 2), PixelFormat::Xrgb, &fit, &timing).unwrap();
+        let durations: Vec<_> = animation.animation.iter().map(|(_, d)| *d).collect();
+        assert_eq!(durations, [Duration::from_nanos(8_333_333); 3]);
     }
 }
@@ -1,6 +1,10 @@
-use std::{path::Path, time::Duration};
+use std::{
+    path::{Path, PathBuf},
+    process::{Command, ExitCode, Stdio},
+    time::{Duration, Instant},
+};
 
-use clap::Parser;
+use clap::{FromArgMatches, Parser};
 use common::cache;
 use common::ipc::{self, Answer, Client, IpcSocket, RequestSend};
 use common::mmap::Mmap;
@@ -8,51 +12,466 @@ use common::mmap::Mmap;
 mod imgproc;
 use imgproc::*;
 
+#[cfg(feature = "avif")]
+mod avif;
+mod batch;
+mod bundle;
 mod cli;
+mod completions;
+mod cutout;
+mod error;
+mod fifo;
+#[cfg(feature = "heif")]
+mod heif;
+mod icc;
+mod messages;
+mod now_playing;
+mod picture_frame;
+mod presets;
+mod preview;
+mod print_request;
+#[cfg(feature = "providers")]
+mod providers;
+mod screencast;
+mod stream;
+#[cfg(feature = "svg")]
+mod svg;
+mod tiff;
+#[cfg(feature = "heif")]
+mod time_of_day;
+mod workspace_tint;
+#[cfg(not(feature = "providers"))]
+mod providers {
+    pub fn fetch(provider: &str) -> Result<std::path::PathBuf, String> {
+        Err(crate::messages::msg!(
+            "providers-disabled",
+            provider = provider
+        ))
+    }
+}
 use cli::{CliImage, ResizeStrategy, Swww};
+use cutout::Cutout;
+use error::{Error, ErrorKind};
+use messages::msg;
 
-fn main() -> Result<(), String> {
-    let swww = Swww::parse();
+fn main() -> ExitCode {
+    let swww = match Swww::try_parse() {
+        Ok(swww) => swww,
+        // this also covers `--help` and `--version`, which are not errors
+        Err(e) => {
+            let _ = e.print();
+            return match e.use_stderr() {
+                true => ExitCode::from(ErrorKind::Usage as u8),
+                false => ExitCode::SUCCESS,
+            };
+        }
+    };
+
+    match run(swww, std::env::args_os().skip(1).collect()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", msg!("error", error = e));
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
 
+/// `args` are the ones `swww` was parsed from, without the program name
+fn run(swww: Swww, args: Vec<std::ffi::OsString>) -> Result<(), Error> {
     if let Swww::ClearCache = &swww {
-        return cache::clean().map_err(|e| format!("failed to clean the cache: {e}"));
+        return Ok(cache::clean().map_err(|e| msg!("clean-cache-failed", error = e))?);
+    }
+
+    if let Swww::Cache(cache) = &swww {
+        return Ok(match &cache.action {
+            cli::CacheAction::Export { image, bundle } => bundle::export(image, bundle),
+            cli::CacheAction::Import { bundle, image } => bundle::import(bundle, image),
+        }?);
     }
 
-    let socket = IpcSocket::connect().map_err(|err| err.to_string())?;
+    if let Swww::Completions(completions) = &swww {
+        return completions::print(completions);
+    }
+
+    if let Swww::CompleteOutputs = &swww {
+        return completions::outputs();
+    }
+
+    if let Swww::Fifo(fifo) = &swww {
+        return Ok(fifo::listen(fifo)?);
+    }
+
+    if let Swww::PreviewTransition(preview) = &swww {
+        return preview::preview(preview);
+    }
+
+    // the frame waits for the daemon itself, and survives it going away
+    if let Swww::PictureFrame(frame) = &swww {
+        return Ok(picture_frame::watch(frame)?);
+    }
+
+    if let Swww::Run(run) = &swww {
+        start_daemon(&run.daemon_args)?;
+    }
+
+    let socket = IpcSocket::connect()?;
     loop {
         RequestSend::Ping.send(&socket)?;
-        let bytes = socket.recv().map_err(|err| err.to_string())?;
+        let bytes = socket.recv()?;
         let answer = Answer::receive(bytes);
         if let Answer::Ping(configured) = answer {
-            if configured {
+            // outputs that never get configured are one of the things to debug
+            if configured || matches!(swww, Swww::Debug(_)) {
                 break;
             }
         } else {
-            return Err("Daemon did not return Answer::Ping, as expected".to_string());
+            return Err(msg!("unexpected-answer", expected = "Answer::Ping").into());
         }
         std::thread::sleep(Duration::from_millis(1));
     }
 
+    let swww = match swww {
+        Swww::Img(_) => Swww::Img(with_transition_defaults(args)?),
+        Swww::Run(_) => {
+            let run: cli::Run = with_transition_defaults(args)?;
+            Swww::Img(run.img)
+        }
+        swww => swww,
+    };
     process_swww_args(&swww)
 }
 
-fn process_swww_args(args: &Swww) -> Result<(), String> {
+/// Starts `swww-daemon` with `args` in the background, unless it is already running, and waits
+/// until we can connect to it
+fn start_daemon(args: &[String]) -> Result<(), Error> {
+    use std::os::unix::process::CommandExt;
+
+    if IpcSocket::connect().is_ok() {
+        return Ok(());
+    }
+
+    // prefer the daemon that was installed alongside us, so their versions match
+    let daemon = std::env::current_exe()
+        .map(|exe| exe.with_file_name("swww-daemon"))
+        .ok()
+        .filter(|daemon| daemon.is_file())
+        .unwrap_or_else(|| "swww-daemon".into());
+    let mut child = Command::new(&daemon)
+        .args(args)
+        .stdin(Stdio::null())
+        // so that it survives a ctrl-c in the terminal we were started from
+        .process_group(0)
+        .spawn()
+        .map_err(|e| msg!("start-daemon-failed", daemon = daemon.display(), error = e))?;
+
+    // every failed connection attempt takes half a second
+    for _ in 0..20 {
+        if let Ok(Some(status)) = child.try_wait() {
+            return Err(Error::new(
+                ErrorKind::DaemonNotRunning,
+                msg!("daemon-exited-early", status = status),
+            ));
+        }
+        if IpcSocket::connect().is_ok() {
+            return Ok(());
+        }
+    }
+    Err(Error::new(ErrorKind::Timeout, msg!("daemon-start-timeout")))
+}
+
+/// Parses the arguments of `swww img` (or `swww run`), marking the transition flags given neither
+/// in `args`, the environment nor `--transition-preset` in `cli::Img::transition_defaulted`, so
+/// that the daemon fills in its defaults for them (see `swww transition-defaults`). The flags of
+/// `--transition-preset` come after `args` and the environment.
+///
+/// It also parses the transition of each `--output-transition`, into `cli::Img::transition_groups`,
+/// failing if one of them sets anything other than a transition flag.
+///
+/// `args` must start with the subcommand name, i.e., `img`
+fn with_transition_defaults<P, I, T>(args: I) -> Result<P, Error>
+where
+    P: clap::Parser + ImgArgs,
+    I: IntoIterator<Item = T>,
+    T: Into<std::ffi::OsString>,
+{
+    use clap::parser::ValueSource;
+
+    let mut args: Vec<std::ffi::OsString> = args.into_iter().map(Into::into).collect();
+    let matches = P::command()
+        .try_get_matches_from(&args)
+        .map_err(|e| Error::new(ErrorKind::Usage, e.to_string()))?;
+
+    if let Ok(Some(name)) = matches.try_get_one::<String>("transition_preset") {
+        for (key, value) in presets::find(name)? {
+            let (_, id, flag) = cli::TRANSITION_DEFAULTS
+                .iter()
+                .find(|(name, _, _)| *name == key)
+                .expect("presets only have known keys");
+            if matches!(
+                matches.value_source(id),
+                None | Some(ValueSource::DefaultValue)
+            ) {
+                args.extend(transition_flag(id, flag, &value));
+            }
+        }
+    }
+    let matches = P::command()
+        .try_get_matches_from(&args)
+        .map_err(|e| msg!("invalid-transition-preset", error = e))?;
+    let usage = |e: clap::Error| Error::new(ErrorKind::Usage, e.to_string());
+    let mut parsed = P::from_arg_matches(&matches).map_err(usage)?;
+    parsed.img_mut().transition_defaulted = defaulted(&matches);
+
+    // everything past the last index of `args` comes from the words of a group
+    let command = P::command();
+    let last_index = command
+        .get_arguments()
+        .map(|arg| arg.get_id().as_str())
+        .filter(|id| matches.value_source(id) == Some(ValueSource::CommandLine))
+        .filter_map(|id| matches.indices_of(id))
+        .flatten()
+        .max()
+        .unwrap_or(0);
+
+    for group in parsed.img_mut().output_transition.clone() {
+        let (outputs, flags) = group
+            .split_once(':')
+            .ok_or_else(|| Error::new(ErrorKind::Usage, msg!("bad-output-transition")))?;
+        let mut group_args = args.clone();
+        group_args.extend(fifo::split_words(flags)?.into_iter().map(Into::into));
+        // so that the group's flags replace the ones we already have
+        let matches = P::command()
+            .args_override_self(true)
+            .try_get_matches_from(&group_args)
+            .map_err(usage)?;
+        let not_transition = command.get_arguments().find(|arg| {
+            let id = arg.get_id().as_str();
+            !cli::TRANSITION_DEFAULTS
+                .iter()
+                .any(|(_, known, _)| *known == id)
+                && matches.value_source(id) == Some(ValueSource::CommandLine)
+                && matches
+                    .indices_of(id)
+                    .is_some_and(|mut indices| indices.any(|i| i > last_index))
+        });
+        if let Some(arg) = not_transition {
+            let flag = match arg.get_long() {
+                Some(long) => format!("--{long}"),
+                None => arg.get_id().to_string(),
+            };
+            return Err(Error::new(
+                ErrorKind::Usage,
+                msg!("output-transition-not-transition", flag = flag),
+            ));
+        }
+        let mut img = cli::Img::from_arg_matches(&matches).map_err(usage)?;
+        img.transition_defaulted = defaulted(&matches);
+        parsed
+            .img_mut()
+            .transition_groups
+            .push((split_cmdline_outputs(outputs), Box::new(img)));
+    }
+    Ok(parsed)
+}
+
+/// The transition defaults whose flags were not given in `matches`, as bits of
+/// `ipc::Transition::defaulted`
+fn defaulted(matches: &clap::ArgMatches) -> u64 {
+    use clap::parser::ValueSource;
+
+    cli::TRANSITION_DEFAULTS
+        .iter()
+        .filter(|(_, id, _)| {
+            matches!(
+                matches.value_source(id),
+                None | Some(ValueSource::DefaultValue)
+            )
+        })
+        .fold(0, |bits, (name, _, _)| {
+            bits | ipc::transition_default_bit(name)
+        })
+}
+
+/// The `swww img` arguments of the commands that take them
+trait ImgArgs {
+    fn img_mut(&mut self) -> &mut cli::Img;
+}
+
+impl ImgArgs for cli::Img {
+    fn img_mut(&mut self) -> &mut cli::Img {
+        self
+    }
+}
+
+impl ImgArgs for cli::Run {
+    fn img_mut(&mut self) -> &mut cli::Img {
+        &mut self.img
+    }
+}
+
+/// `flag` set to `value`. `--invert-y` takes no value, so it is only passed when `value` is "true"
+fn transition_flag(id: &str, flag: &str, value: &str) -> Option<std::ffi::OsString> {
+    if id == "invert_y" {
+        (value == "true").then(|| flag.into())
+    } else {
+        Some(format!("{flag}={value}").into())
+    }
+}
+
+fn list_vars(prefix: &str) -> Result<Box<[(String, String)]>, Error> {
+    let list = ipc::ListVarsSend {
+        prefix: prefix.to_string(),
+    };
+    let socket = IpcSocket::connect()?;
+    RequestSend::ListVars(list.create_request()).send(&socket)?;
+    let bytes = socket.recv()?;
+    match Answer::receive(bytes) {
+        Answer::Vars(vars) => Ok(vars),
+        _ => Err(msg!("unexpected-answer", expected = "Answer::Vars").into()),
+    }
+}
+
+/// What the daemon was built with, or `None` if it is too old to tell us
+fn daemon_capabilities() -> Option<ipc::Capabilities> {
+    let socket = IpcSocket::connect().ok()?;
+    RequestSend::Capabilities.send(&socket).ok()?;
+    match Answer::receive(socket.recv().ok()?) {
+        Answer::Capabilities(capabilities) => Some(capabilities),
+        _ => None,
+    }
+}
+
+/// Fails if the transition plugin `name` needs a feature the daemon was built without, which the
+/// daemon would otherwise only log once the transition starts
+fn check_plugin_support(name: &str) -> Result<(), Error> {
+    let dir = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => match std::env::var_os("HOME") {
+            Some(home) => PathBuf::from(home).join(".config"),
+            None => return Ok(()),
+        },
+    };
+    // the daemon looks for them in this order
+    let needs = [("wasm", "wasm"), ("wgsl", "gpu"), ("glsl", "gpu")]
+        .into_iter()
+        .map(|(extension, feature)| {
+            (
+                dir.join(format!("swww/transitions/{name}.{extension}")),
+                feature,
+            )
+        })
+        .find(|(path, _)| path.exists());
+    let Some((path, feature)) = needs else {
+        return Ok(());
+    };
+    match daemon_capabilities() {
+        Some(capabilities) if !capabilities.has_feature(feature) => Err(Error::new(
+            ErrorKind::Unsupported,
+            msg!(
+                "daemon-lacks-feature",
+                feature = feature,
+                path = path.display()
+            ),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// The request changing the transition defaults to `values`, in the order of
+/// `cli::TRANSITION_DEFAULTS`. Empty values forget the default. The aliases of `--type` are set as
+/// what they stand for, since the daemon fills in the defaults after we handled the aliases
+fn transition_defaults_request(values: [Option<&str>; 40]) -> Result<RequestSend, Error> {
+    let mut values: Vec<(&str, &str)> = cli::TRANSITION_DEFAULTS
+        .iter()
+        .zip(values)
+        .filter_map(|((name, _, _), value)| Some((*name, value?)))
+        .collect();
+    let transition_type = values
+        .iter()
+        .find(|(name, _)| *name == "type")
+        .and_then(|(_, value)| value.parse().ok());
+    let aliased: &[(&str, &str)] = match transition_type {
+        Some(cli::TransitionType::Right) => &[("type", "wipe"), ("angle", "0")],
+        Some(cli::TransitionType::Top) => &[("type", "wipe"), ("angle", "90")],
+        Some(cli::TransitionType::Left) => &[("type", "wipe"), ("angle", "180")],
+        Some(cli::TransitionType::Bottom) => &[("type", "wipe"), ("angle", "270")],
+        Some(cli::TransitionType::Center) => {
+            &[("type", "grow"), ("pos", "center"), ("origins", "")]
+        }
+        Some(cli::TransitionType::Any) => &[
+            ("type", "random"),
+            ("random", "grow,outer"),
+            ("origins", ""),
+        ],
+        _ => &[],
+    };
+    for &(name, value) in aliased {
+        values.retain(|(other, _)| *other != name);
+        values.push((name, value));
+    }
+
+    let mut args: Vec<std::ffi::OsString> = vec!["img".into(), "0x000000".into()];
+    for (name, value) in values.iter().filter(|(_, value)| !value.is_empty()) {
+        let (_, id, flag) = cli::TRANSITION_DEFAULTS
+            .iter()
+            .find(|(other, _, _)| other == name)
+            .expect("we only set known defaults");
+        args.extend(transition_flag(id, flag, value));
+    }
+    let img =
+        cli::Img::try_parse_from(args).map_err(|e| Error::new(ErrorKind::Usage, e.to_string()))?;
+    let mask = match &img.transition_mask {
+        Some(path) => {
+            let mask = load_mask(path).map_err(Error::decode)?;
+            Some((mask.dimensions(), mask.into_raw().into_boxed_slice()))
+        }
+        None => None,
+    };
+
+    let defaults = ipc::TransitionDefaultsSend {
+        values: values
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect(),
+        transition: make_transition(&img),
+        mask,
+    };
+    Ok(RequestSend::TransitionDefaults(defaults.create_request()))
+}
+
+fn process_swww_args(args: &Swww) -> Result<(), Error> {
+    let verbose = matches!(args, Swww::Img(img) if img.verbose);
+    let start = Instant::now();
     let request = match make_request(args)? {
         Some(request) => request,
         None => return Ok(()),
     };
-    let socket = IpcSocket::connect().map_err(|err| err.to_string())?;
+    if verbose {
+        let elapsed = format!("{:.1?}", start.elapsed());
+        eprintln!("{}", msg!("request-built", elapsed = elapsed));
+    }
+    let socket = IpcSocket::connect()?;
+    let start = Instant::now();
     request.send(&socket)?;
-    let bytes = socket.recv().map_err(|err| err.to_string())?;
+    let bytes = socket.recv()?;
+    if verbose {
+        let elapsed = format!("{:.1?}", start.elapsed());
+        eprintln!("{}", msg!("daemon-answered", elapsed = elapsed));
+    }
     drop(socket);
     match Answer::receive(bytes) {
         Answer::Info(info) => info.iter().for_each(|i| println!("{}", i)),
         Answer::Ok => {
-            if let Swww::Kill = args {
+            if let Swww::Kill(kill) = args {
                 #[cfg(debug_assertions)]
                 let tries = 20;
                 #[cfg(not(debug_assertions))]
                 let tries = 10;
+                // the daemon only exits once the outro is over
+                let tries = match kill.outro {
+                    cli::Outro::None => tries,
+                    _ => tries + (kill.outro_duration.max(0.0) * 10.0).ceil() as u32,
+                };
                 let path = IpcSocket::<Client>::path();
                 let path = Path::new(path);
                 for _ in 0..tries {
@@ -61,159 +480,722 @@ fn process_swww_args(args: &Swww) -> Result<(), String> {
                     }
                     std::thread::sleep(Duration::from_millis(100));
                 }
-                return Err(format!("Could not confirm socket deletion at: {path:?}"));
+                return Err(Error::new(
+                    ErrorKind::Timeout,
+                    msg!("socket-not-deleted", path = path.display()),
+                ));
             }
         }
         Answer::Ping(_) => {
             return Ok(());
         }
+        Answer::Var(var) => match var {
+            Some(value) => println!("{value}"),
+            None => {
+                if let Swww::GetVar(get) = args {
+                    return Err(Error::new(
+                        ErrorKind::VarNotSet,
+                        msg!("var-not-set", key = get.key),
+                    ));
+                }
+            }
+        },
+        Answer::Transitions(transitions) => {
+            for transition in transitions.iter() {
+                let outputs = transition.outputs.join(",");
+                for (name, requested, used) in transition.clamped.iter() {
+                    eprintln!(
+                        "{}",
+                        msg!(
+                            "transition-clamped",
+                            outputs = outputs,
+                            name = name,
+                            requested = requested,
+                            used = used
+                        )
+                    );
+                }
+                if verbose {
+                    eprintln!(
+                        "{}",
+                        msg!(
+                            "transition-used",
+                            outputs = outputs,
+                            transition_type = print_request::type_name(transition.transition_type),
+                            duration = transition.duration,
+                            fps = transition.fps
+                        )
+                    );
+                }
+            }
+        }
+        Answer::Error(error) => {
+            return Err(Error::new(
+                ErrorKind::Refused,
+                msg!("daemon-refused", error = error),
+            ));
+        }
+        Answer::Capabilities(capabilities) => {
+            println!("features: {}", capabilities.features.join(", "));
+            for (protocol, version) in capabilities.protocols.iter() {
+                println!("{protocol}: {version}");
+            }
+        }
+        Answer::Surfaces(surfaces) => surfaces.iter().for_each(|s| println!("{s}")),
+        Answer::Vars(vars) => {
+            for (key, value) in vars.iter().filter(|(_, value)| !value.is_empty()) {
+                match key.strip_prefix("transition.") {
+                    Some(name) if matches!(args, Swww::TransitionDefaults(_)) => {
+                        println!("{name}: {value}")
+                    }
+                    _ => println!("{key}: {value}"),
+                }
+            }
+        }
     }
     Ok(())
 }
 
-fn make_request(args: &Swww) -> Result<Option<RequestSend>, String> {
+fn clear_request(
+    mut color: [u8; 3],
+    outputs: &str,
+    outro: cli::Outro,
+    outro_duration: f32,
+) -> Result<RequestSend, Error> {
+    let (format, _, _) = get_format_dims_and_outputs(&[])?;
+    if format.must_swap_r_and_b_channels() {
+        color.swap(0, 2);
+    }
+    let clear = ipc::ClearSend {
+        color,
+        outputs: split_cmdline_outputs(outputs),
+        outro: match outro {
+            cli::Outro::None => ipc::Outro::None,
+            cli::Outro::Fade => ipc::Outro::Fade,
+            cli::Outro::Wipe => ipc::Outro::Wipe,
+        },
+        outro_duration,
+    };
+    Ok(RequestSend::Clear(clear.create_request()))
+}
+
+fn make_request(args: &Swww) -> Result<Option<RequestSend>, Error> {
     match args {
-        Swww::Clear(c) => {
-            let (format, _, _) = get_format_dims_and_outputs(&[])?;
-            let mut color = c.color;
-            if format.must_swap_r_and_b_channels() {
-                color.swap(0, 2);
-            }
-            let clear = ipc::ClearSend {
-                color,
-                outputs: split_cmdline_outputs(&c.outputs),
-            };
-            Ok(Some(RequestSend::Clear(clear.create_request())))
-        }
+        Swww::Clear(c) => Ok(Some(clear_request(
+            c.color,
+            &c.outputs,
+            c.outro,
+            c.outro_duration,
+        )?)),
         Swww::Restore(restore) => {
             let requested_outputs = split_cmdline_outputs(&restore.outputs);
             restore_from_cache(&requested_outputs)?;
             Ok(None)
         }
         Swww::ClearCache => unreachable!("there is no request for clear-cache"),
+        Swww::Cache(_) => unreachable!("there is no request for cache"),
+        Swww::Run(_) => unreachable!("run is turned into img before making the request"),
+        Swww::Fifo(_) => unreachable!("there is no request for fifo"),
+        Swww::PreviewTransition(_) => unreachable!("there is no request for preview-transition"),
+        Swww::PictureFrame(_) => unreachable!("there is no request for picture-frame"),
+        Swww::Completions(_) => unreachable!("there is no request for completions"),
+        Swww::CompleteOutputs => unreachable!("__complete-outputs makes its own request"),
+        Swww::Batch(batch) => Ok(Some(batch::make_request(batch)?)),
         Swww::Img(img) => {
+            let image = match &img.image {
+                CliImage::Provider(provider) => match fetch_from_provider(img, provider)? {
+                    Some(image) => image,
+                    None => return Ok(None),
+                },
+                image => image.clone(),
+            };
+
             let requested_outputs = split_cmdline_outputs(&img.outputs);
             let (format, dims, outputs) = get_format_dims_and_outputs(&requested_outputs)?;
-            // let imgbuf = ImgBuf::new(&img.path)?;
+            let cutouts = Cutout::from_vars(&list_vars("cutout.")?);
+            let (dims, outputs, groups, cutouts) =
+                split_transition_groups(img, dims, outputs, &cutouts);
+            let transitions: Vec<ipc::Transition> =
+                groups.iter().map(|group| make_transition(group)).collect();
+            for transition in &transitions {
+                if let ipc::TransitionType::Plugin = transition.transition_type {
+                    check_plugin_support(&transition.plugin)?;
+                }
+            }
 
-            let img_request = make_img_request(img, &dims, format, &outputs)?;
+            if let Some(print) = img.print_request {
+                println!(
+                    "{}",
+                    print_request::img_request(
+                        img,
+                        &image,
+                        format,
+                        &dims,
+                        &outputs,
+                        &groups,
+                        &transitions
+                    )
+                );
+                if print == cli::PrintRequest::Only {
+                    return Ok(None);
+                }
+            }
+
+            let img_request = make_img_request(
+                img,
+                &groups,
+                transitions,
+                &image,
+                &dims,
+                format,
+                &outputs,
+                &cutouts,
+            )?;
 
             Ok(Some(RequestSend::Img(img_request)))
         }
-        Swww::Kill => Ok(Some(RequestSend::Kill)),
+        Swww::Kill(kill) => match kill.outro {
+            cli::Outro::None => Ok(Some(RequestSend::Kill)),
+            outro => {
+                let clear = clear_request(kill.outro_color, "", outro, kill.outro_duration)?;
+                Ok(Some(RequestSend::batch(vec![clear, RequestSend::Kill])))
+            }
+        },
         Swww::Query => Ok(Some(RequestSend::Query)),
+        Swww::Pause => Ok(Some(RequestSend::Pause(true))),
+        Swww::Resume => Ok(Some(RequestSend::Pause(false))),
+        Swww::Capabilities => Ok(Some(RequestSend::Capabilities)),
+        Swww::Debug(debug) => match debug.action {
+            cli::DebugAction::Surfaces => Ok(Some(RequestSend::DebugSurfaces)),
+        },
+        Swww::SetVar(set) => {
+            let set = ipc::SetVarSend {
+                key: set.key.clone(),
+                value: set.value.clone(),
+            };
+            Ok(Some(RequestSend::SetVar(set.create_request())))
+        }
+        Swww::GetVar(get) => {
+            let get = ipc::GetVarSend {
+                key: get.key.clone(),
+            };
+            Ok(Some(RequestSend::GetVar(get.create_request())))
+        }
+        Swww::ColorFilter(color_filter) => {
+            let filter = ipc::FilterSend {
+                filter: match color_filter.filter {
+                    cli::ColorFilterType::None => ipc::ColorFilter::None,
+                    cli::ColorFilterType::Invert => ipc::ColorFilter::Invert,
+                    cli::ColorFilterType::HighContrast => ipc::ColorFilter::HighContrast,
+                    cli::ColorFilterType::ProtanopiaSimulation => {
+                        ipc::ColorFilter::ProtanopiaSimulation
+                    }
+                    cli::ColorFilterType::DeuteranopiaSimulation => {
+                        ipc::ColorFilter::DeuteranopiaSimulation
+                    }
+                    cli::ColorFilterType::TritanopiaSimulation => {
+                        ipc::ColorFilter::TritanopiaSimulation
+                    }
+                    cli::ColorFilterType::ProtanopiaCompensation => {
+                        ipc::ColorFilter::ProtanopiaCompensation
+                    }
+                    cli::ColorFilterType::DeuteranopiaCompensation => {
+                        ipc::ColorFilter::DeuteranopiaCompensation
+                    }
+                    cli::ColorFilterType::TritanopiaCompensation => {
+                        ipc::ColorFilter::TritanopiaCompensation
+                    }
+                },
+                outputs: split_cmdline_outputs(&color_filter.outputs),
+            };
+            Ok(Some(RequestSend::Filter(filter.create_request())))
+        }
+        Swww::Dim(dim) => {
+            let outputs = if dim.outputs == "all" {
+                Box::default()
+            } else {
+                split_cmdline_outputs(&dim.outputs)
+            };
+            let dim = ipc::DimSend {
+                color: dim.color,
+                strength: (dim.amount * 255.0).round() as u8,
+                outputs,
+            };
+            Ok(Some(RequestSend::Dim(dim.create_request())))
+        }
+        Swww::ScreencastWatch(screencast) => {
+            screencast::watch(screencast)?;
+            Ok(None)
+        }
+        Swww::WorkspaceTint(workspace_tint) => {
+            workspace_tint::watch(workspace_tint)?;
+            Ok(None)
+        }
+        Swww::NowPlaying(now_playing) => {
+            now_playing::watch(now_playing)?;
+            Ok(None)
+        }
+        Swww::Stream(stream) => {
+            stream::run(stream)?;
+            Ok(None)
+        }
+        Swww::Shader(shader) => {
+            let path = &shader.file;
+            let language = match path.extension().and_then(|e| e.to_str()) {
+                Some("wgsl") => ipc::ShaderLanguage::Wgsl,
+                Some("glsl") => ipc::ShaderLanguage::Glsl,
+                _ => {
+                    return Err(Error::new(
+                        ErrorKind::Usage,
+                        msg!("shader-unknown-language", path = path.display()),
+                    ))
+                }
+            };
+            let source = std::fs::read_to_string(path)
+                .map_err(|e| msg!("read-failed", path = path.display(), error = e))?;
+            let shader = ipc::ShaderSend {
+                source,
+                language,
+                fps: shader.fps,
+                downscale: shader.downscale.unwrap_or(0),
+                outputs: split_cmdline_outputs(&shader.outputs),
+            };
+            Ok(Some(RequestSend::Shader(shader.create_request())))
+        }
+        Swww::TransitionDefaults(defaults) => match &defaults.action {
+            cli::TransitionDefaultsAction::Set(set) => {
+                transition_defaults_request(set.values()).map(Some)
+            }
+            cli::TransitionDefaultsAction::Show => {
+                let list = ipc::ListVarsSend {
+                    prefix: "transition.".to_string(),
+                };
+                Ok(Some(RequestSend::ListVars(list.create_request())))
+            }
+            cli::TransitionDefaultsAction::Reset => {
+                transition_defaults_request([Some(""); 40]).map(Some)
+            }
+        },
+    }
+}
+
+/// Fetches the image from `provider`, running it through the `--validate` command if there is
+/// one. Returns `None` if the image was rejected and we should keep the current wallpaper
+fn fetch_from_provider(img: &cli::Img, provider: &str) -> Result<Option<CliImage>, Error> {
+    let path = providers::fetch(provider)?;
+    let cmd = match &img.validate {
+        Some(cmd) => cmd,
+        None => return Ok(Some(CliImage::Path(path))),
+    };
+
+    let status = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(format!("{cmd} \"$1\""))
+        .arg("sh")
+        .arg(&path)
+        .status()
+        .map_err(|e| msg!("validation-failed", error = e))?;
+    if status.success() {
+        return Ok(Some(CliImage::Path(path)));
+    }
+
+    let path = path.display();
+    match &img.validate_fallback {
+        cli::ValidateFallback::Keep => {
+            let msg = msg!("rejected-keep", path = path, cmd = cmd, status = status);
+            eprintln!("{msg}");
+            Ok(None)
+        }
+        cli::ValidateFallback::Error => Err(Error::decode(msg!(
+            "rejected",
+            path = path,
+            cmd = cmd,
+            status = status
+        ))),
+        cli::ValidateFallback::Image(image) => {
+            let msg = msg!("rejected-fallback", path = path, cmd = cmd, status = status);
+            eprintln!("{msg}");
+            Ok(Some(image.clone()))
+        }
+    }
+}
+
+/// The size we should render an animation at on an output with dimensions `dim`, so that it fits
+/// into `max` while keeping its aspect ratio
+fn anim_dim(dim: (u32, u32), max: (u32, u32)) -> (u32, u32) {
+    if dim.0 <= max.0 && dim.1 <= max.1 {
+        return dim;
+    }
+    let factor = f64::min(max.0 as f64 / dim.0 as f64, max.1 as f64 / dim.1 as f64);
+    (
+        ((dim.0 as f64 * factor).round() as u32).max(1),
+        ((dim.1 as f64 * factor).round() as u32).max(1),
+    )
+}
+
+/// Splits each group of `outputs` into the ones that share a transition and a cutout, returning
+/// the `swww img` arguments each new group transitions with (either `img` or those of one of its
+/// `--output-transition`s), and the group's cutout
+#[allow(clippy::type_complexity)]
+fn split_transition_groups<'a>(
+    img: &'a cli::Img,
+    dims: Vec<(u32, u32)>,
+    outputs: Vec<Vec<String>>,
+    cutouts: &[(String, Cutout)],
+) -> (
+    Vec<(u32, u32)>,
+    Vec<Vec<String>>,
+    Vec<&'a cli::Img>,
+    Vec<Option<Cutout>>,
+) {
+    let mut split = (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+    for (dim, names) in dims.into_iter().zip(outputs) {
+        let mut groups: Vec<((Option<usize>, Option<Cutout>), Vec<String>)> = Vec::new();
+        for name in names {
+            let group = img
+                .transition_groups
+                .iter()
+                .position(|(outputs, _)| outputs.contains(&name));
+            let cutout = cutouts
+                .iter()
+                .find(|(output, _)| *output == name)
+                .map(|&(_, cutout)| cutout);
+            match groups.iter_mut().find(|(g, _)| *g == (group, cutout)) {
+                Some((_, names)) => names.push(name),
+                None => groups.push(((group, cutout), vec![name])),
+            }
+        }
+        for ((group, cutout), names) in groups {
+            split.0.push(dim);
+            split.1.push(names);
+            split
+                .2
+                .push(group.map_or(img, |group| &*img.transition_groups[group].1));
+            split.3.push(cutout);
+        }
     }
+    split
 }
 
+/// `groups`, `transitions` and `cutouts` are the arguments, the transition and the cutout of each
+/// of the `outputs`
+#[allow(clippy::too_many_arguments)]
 fn make_img_request(
     img: &cli::Img,
+    groups: &[&cli::Img],
+    transitions: Vec<ipc::Transition>,
+    image: &CliImage,
     dims: &[(u32, u32)],
     pixel_format: ipc::PixelFormat,
     outputs: &[Vec<String>],
-) -> Result<Mmap, String> {
-    let transition = make_transition(img);
-    let mut img_req_builder = ipc::ImageRequestBuilder::new(transition);
+    cutouts: &[Option<Cutout>],
+) -> Result<Mmap, Error> {
+    let verbose = img.verbose;
+    let masks = groups
+        .iter()
+        .zip(&transitions)
+        .map(|(group, transition)| match &group.transition_mask {
+            Some(path) if matches!(transition.transition_type, ipc::TransitionType::Luma) => {
+                load_mask(path).map(Some).map_err(Error::decode)
+            }
+            _ => Ok(None),
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    let mask_for = |i: usize, dim, bytes: &[u8], format| match &masks[i] {
+        Some(mask) => mask_resize(mask, dim, make_filter(&img.filter)).map(Some),
+        None if matches!(transitions[i].transition_type, ipc::TransitionType::Luma) => {
+            Ok(Some(luma_mask(bytes, format)))
+        }
+        None => Ok(None),
+    };
+    #[cfg(not(feature = "zstd"))]
+    if img.cache_compress {
+        return Err(Error::new(ErrorKind::Usage, msg!("zstd-disabled")));
+    }
+    let mut img_req_builder = ipc::ImageRequestBuilder::new()
+        .compress_cache(img.cache_compress)
+        .cache_options(cache_options(img));
 
-    match &img.image {
+    match image {
+        CliImage::Color(_) if !img.stack.is_empty() => {
+            return Err(Error::new(ErrorKind::Usage, msg!("stack-color")));
+        }
         CliImage::Color(color) => {
-            for (&dim, outputs) in dims.iter().zip(outputs) {
+            for (i, (&dim, outputs)) in dims.iter().zip(outputs).enumerate() {
+                let img = image::RgbImage::from_pixel(dim.0, dim.1, image::Rgb(*color))
+                    .to_vec()
+                    .into_boxed_slice();
+                // plain RGB, whatever the format
+                let mask = mask_for(i, dim, &img, ipc::PixelFormat::Bgr)?;
                 img_req_builder.push(
                     ipc::ImgSend {
-                        img: image::RgbImage::from_pixel(dim.0, dim.1, image::Rgb(*color))
-                            .to_vec()
-                            .into_boxed_slice(),
+                        img,
                         path: format!("0x{:02x}{:02x}{:02x}", color[0], color[1], color[2]),
                         dim,
                         format: pixel_format,
+                        mask,
+                        reduced: false,
                     },
                     outputs,
                     None,
+                    &transitions[i],
                 );
             }
         }
         CliImage::Path(img_path) => {
-            let imgbuf = ImgBuf::new(img_path)?;
-            let img_raw = imgbuf.decode(pixel_format)?;
+            let start = Instant::now();
+            let mut imgbuf = ImgBuf::new(img_path).map_err(Error::decode)?;
+            let color_profile = match &img.color_profile {
+                Some(path) => icc::Profile::load(path).map_err(Error::decode)?,
+                None => icc::Profile::srgb(),
+            };
+            imgbuf.select_page(img.page).map_err(Error::decode)?;
+            imgbuf
+                .follow_time_of_day(img.location)
+                .map_err(Error::decode)?;
+            imgbuf.convert_colors(&color_profile);
+            let timing = FrameTiming {
+                delay_clamp: Duration::from_millis(img.frame_delay_clamp),
+                interpolate: img.interpolate,
+                sequence_fps: imgbuf.is_sequence().then_some(img.fps),
+                color_profile: color_profile.id(),
+            };
+            let (img_raw, quantization) = imgbuf
+                .decode(pixel_format, img.dither, img.tone_map)
+                .map_err(Error::decode)?;
+            if img.verbose {
+                eprintln!("{quantization}");
+                let elapsed = format!("{:.1?}", start.elapsed());
+                eprintln!("{}", msg!("image-decoded", elapsed = elapsed));
+            } else if quantization.is_banding_prone() && !quantization.dithered {
+                eprintln!("{}", msg!("banding", path = img_path.display()));
+            }
+
+            let weights: Vec<u32> = if img.stack_weights.is_empty() {
+                vec![1; img.stack.len() + 1]
+            } else if img.stack_weights.len() == img.stack.len() + 1 {
+                img.stack_weights.iter().map(|w| w.get()).collect()
+            } else {
+                return Err(Error::new(
+                    ErrorKind::Usage,
+                    msg!(
+                        "stack-weights-mismatch",
+                        weights = img.stack_weights.len(),
+                        images = img.stack.len() + 1
+                    ),
+                ));
+            };
+            let stacked = img
+                .stack
+                .iter()
+                .map(|path| {
+                    let mut imgbuf = ImgBuf::new(path)?;
+                    imgbuf.convert_colors(&color_profile);
+                    let (image, _) = imgbuf.decode(pixel_format, img.dither, img.tone_map)?;
+                    Ok(image)
+                })
+                .collect::<Result<Vec<_>, String>>()
+                .map_err(Error::decode)?;
+            let resize = |image: &Image, dim| match img.resize {
+                ResizeStrategy::No => img_pad(image, dim, &img.fill_color),
+                ResizeStrategy::Crop => img_resize_crop(image, dim, make_filter(&img.filter)),
+                ResizeStrategy::Fit => {
+                    img_resize_fit(image, dim, make_filter(&img.filter), &img.fill_color)
+                }
+            };
 
-            for (&dim, outputs) in dims.iter().zip(outputs) {
+            for (i, (&dim, outputs)) in dims.iter().zip(outputs).enumerate() {
+                let start = Instant::now();
                 let path = match img_path.canonicalize() {
                     Ok(p) => p.to_string_lossy().to_string(),
                     Err(e) => {
                         if let Some("-") = img_path.to_str() {
                             "STDIN".to_string()
                         } else {
-                            return Err(format!("failed no canonicalize image path: {e}"));
+                            return Err(msg!("canonicalize-failed", error = e).into());
                         }
                     }
                 };
 
-                let animation = if !imgbuf.is_animated() {
+                let output_dim = dim;
+                let dim = match img.max_anim_resolution {
+                    Some(max) if imgbuf.is_animated() && img.resize == ResizeStrategy::Crop => {
+                        anim_dim(dim, max)
+                    }
+                    _ => dim,
+                };
+                let cutout = cutouts[i].map(|cutout| cutout.scale(output_dim, dim));
+                let safe_dim = cutout.map_or(dim, |cutout| {
+                    let (_, _, width, height) = cutout.safe_area(dim);
+                    (width, height)
+                });
+                let cache_key = match cutout {
+                    Some(cutout) => timing.cache_key() + &cutout.key(),
+                    None => timing.cache_key(),
+                };
+
+                let fit = FrameFit {
+                    resize: img.resize,
+                    filter: make_filter(&img.filter),
+                    color: &img.fill_color,
+                    cutout,
+                };
+
+                let mut from_cache = false;
+                let animation = if let Some(day_start) =
+                    imgbuf.day_start().filter(|_| stacked.is_empty())
+                {
+                    Some(ipc::Animation {
+                        day_start: Some(day_start),
+                        ..compress_frames(
+                            imgbuf.as_frames(&timing).map_err(Error::decode)?,
+                            dim,
+                            pixel_format,
+                            &fit,
+                            &timing,
+                        )?
+                    })
+                } else if !imgbuf.is_animated() || !stacked.is_empty() {
                     None
                 } else if img.resize == ResizeStrategy::Crop {
-                    match cache::load_animation_frames(img_path, dim, pixel_format) {
-                        Ok(Some(animation)) => Some(animation),
+                    match cache::load_animation_frames(img_path, dim, pixel_format, &cache_key) {
+                        Ok(Some(animation)) => {
+                            from_cache = true;
+                            Some(animation)
+                        }
                         otherwise => {
                             if let Err(e) = otherwise {
-                                eprintln!("Error loading cache for {:?}: {e}", img_path);
+                                let path = img_path.display();
+                                eprintln!("{}", msg!("cache-load-failed", path = path, error = e));
                             }
 
-                            Some({
-                                ipc::Animation {
-                                    animation: compress_frames(
-                                        imgbuf.as_frames()?,
-                                        dim,
-                                        pixel_format,
-                                        make_filter(&img.filter),
-                                        img.resize,
-                                        &img.fill_color,
-                                    )?
-                                    .into_boxed_slice(),
-                                }
-                            })
+                            Some(compress_frames(
+                                imgbuf.as_frames(&timing).map_err(Error::decode)?,
+                                dim,
+                                pixel_format,
+                                &fit,
+                                &timing,
+                            )?)
                         }
                     }
                 } else {
                     None
                 };
+                // read every time, since the cached frames may have been sent with another `--loop`
+                let animation = animation.map(|animation| ipc::Animation {
+                    plays: match img.loops {
+                        Some(cli::Loops::Infinite) => None,
+                        Some(cli::Loops::Times(plays)) => Some(plays),
+                        None => imgbuf.plays(),
+                    },
+                    ..animation
+                });
 
-                let img = match img.resize {
-                    ResizeStrategy::No => img_pad(&img_raw, dim, &img.fill_color)?,
-                    ResizeStrategy::Crop => {
-                        img_resize_crop(&img_raw, dim, make_filter(&img.filter))?
+                let channels = pixel_format.channels() as usize;
+                let resized = if stacked.is_empty() {
+                    let color = &img.fill_color;
+                    match imgbuf.rasterize(safe_dim, img.resize, color, pixel_format)? {
+                        Some(svg) => svg.into_bytes(),
+                        None => resize(&img_raw, safe_dim)?,
                     }
-                    ResizeStrategy::Fit => {
-                        img_resize_fit(&img_raw, dim, make_filter(&img.filter), &img.fill_color)?
+                } else {
+                    let strips = stack_strips(safe_dim, img.stack_direction, &weights)?;
+                    let images = std::iter::once(&img_raw)
+                        .chain(&stacked)
+                        .zip(&strips)
+                        .map(|(image, &strip)| resize(image, strip))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    stack_join(&images, &strips, img.stack_direction, channels)
+                };
+                let img = match cutout {
+                    Some(cutout) => {
+                        let fill = format_color(&img.fill_color, pixel_format);
+                        cutout.compose(&resized, dim, channels, &fill)
                     }
+                    None => resized,
                 };
 
+                let mask = mask_for(i, dim, &img, pixel_format)?;
+                let frames = animation.as_ref().map(|a| a.animation.len());
+                let size = img_req_builder.len();
                 img_req_builder.push(
                     ipc::ImgSend {
                         img,
                         path,
                         dim,
                         format: pixel_format,
+                        mask,
+                        reduced: dim != output_dim,
                     },
                     outputs,
-                    animation,
+                    animation.map(|animation| (animation, cache_key)),
+                    &transitions[i],
                 );
+                if verbose {
+                    let outputs = outputs.join(",");
+                    let size = human_size(img_req_builder.len() - size);
+                    let elapsed = format!("{:.1?}", start.elapsed());
+                    let msg = match frames {
+                        Some(frames) if from_cache => msg!(
+                            "processed-cached-animation",
+                            outputs = outputs,
+                            width = dim.0,
+                            height = dim.1,
+                            size = size,
+                            frames = frames,
+                            elapsed = elapsed
+                        ),
+                        Some(frames) => msg!(
+                            "processed-animation",
+                            outputs = outputs,
+                            width = dim.0,
+                            height = dim.1,
+                            size = size,
+                            frames = frames,
+                            elapsed = elapsed
+                        ),
+                        None => msg!(
+                            "processed",
+                            outputs = outputs,
+                            width = dim.0,
+                            height = dim.1,
+                            size = size,
+                            elapsed = elapsed
+                        ),
+                    };
+                    eprintln!("{msg}");
+                }
             }
         }
+        CliImage::Provider(_) => unreachable!("providers are resolved before making the request"),
     }
 
+    if verbose {
+        let size = human_size(img_req_builder.len());
+        eprintln!("{}", msg!("request-size", size = size));
+    }
     Ok(img_req_builder.build())
 }
 
+fn human_size(bytes: usize) -> String {
+    match bytes {
+        0..=1023 => format!("{bytes} B"),
+        1024..=1048575 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0)),
+    }
+}
+
 #[allow(clippy::type_complexity)]
 fn get_format_dims_and_outputs(
     requested_outputs: &[String],
-) -> Result<(ipc::PixelFormat, Vec<(u32, u32)>, Vec<Vec<String>>), String> {
+) -> Result<(ipc::PixelFormat, Vec<(u32, u32)>, Vec<Vec<String>>), Error> {
     let mut outputs: Vec<Vec<String>> = Vec::new();
     let mut dims: Vec<(u32, u32)> = Vec::new();
     let mut imgs: Vec<ipc::BgImg> = Vec::new();
 
-    let socket = IpcSocket::connect().map_err(|err| err.to_string())?;
+    let socket = IpcSocket::connect()?;
     RequestSend::Query.send(&socket)?;
-    let bytes = socket.recv().map_err(|err| err.to_string())?;
+    let bytes = socket.recv()?;
     drop(socket);
     let answer = Answer::receive(bytes);
     match answer {
@@ -241,7 +1223,10 @@ fn get_format_dims_and_outputs(
                 }
             }
             if outputs.is_empty() {
-                Err("none of the requested outputs are valid".to_owned())
+                Err(Error::new(
+                    ErrorKind::NoSuchOutput,
+                    msg!("no-valid-outputs"),
+                ))
             } else {
                 Ok((format, dims, outputs))
             }
@@ -250,6 +1235,21 @@ fn get_format_dims_and_outputs(
     }
 }
 
+/// The options we need to set `img` again the way it was set, when restoring it from the cache
+fn cache_options(img: &cli::Img) -> Vec<String> {
+    let resize = match img.resize {
+        ResizeStrategy::No => "no",
+        ResizeStrategy::Crop => "crop",
+        ResizeStrategy::Fit => "fit",
+    };
+    let [r, g, b] = img.fill_color;
+    vec![
+        format!("--resize={resize}"),
+        format!("--fill-color={r:02x}{g:02x}{b:02x}"),
+        format!("--filter={:?}", img.filter),
+    ]
+}
+
 fn split_cmdline_outputs(outputs: &str) -> Box<[String]> {
     outputs
         .split(',')
@@ -258,21 +1258,33 @@ fn split_cmdline_outputs(outputs: &str) -> Box<[String]> {
         .collect()
 }
 
-fn restore_from_cache(requested_outputs: &[String]) -> Result<(), String> {
+fn restore_from_cache(requested_outputs: &[String]) -> Result<(), Error> {
     let (_, _, outputs) = get_format_dims_and_outputs(requested_outputs)?;
 
     for output in outputs.iter().flatten() {
         let img_path = common::cache::get_previous_image_path(output)
-            .map_err(|e| format!("failed to get previous image path: {e}"))?;
+            .map_err(|e| msg!("previous-image-failed", error = e))?;
+        let options = common::cache::get_previous_image_options(output)
+            .map_err(|e| msg!("previous-image-failed", error = e))?;
+        let cached = cli::Img::try_parse_from(
+            ["img", &img_path]
+                .into_iter()
+                .chain(options.iter().map(String::as_str)),
+        )
+        .map_err(|e| Error::new(ErrorKind::Usage, e.to_string()))?;
         #[allow(deprecated)]
         if let Err(e) = process_swww_args(&Swww::Img(cli::Img {
-            image: cli::parse_image(&img_path)?,
+            image: cached.image,
             outputs: output.to_string(),
             no_resize: false,
-            resize: ResizeStrategy::Crop,
-            fill_color: [0, 0, 0],
-            filter: cli::Filter::Lanczos3,
+            resize: cached.resize,
+            fill_color: cached.fill_color,
+            filter: cached.filter,
             transition_type: cli::TransitionType::None,
+            transition_preset: None,
+            output_transition: Vec::new(),
+            transition_groups: Vec::new(),
+            transition_defaulted: 0,
             transition_step: std::num::NonZeroU8::MAX,
             transition_duration: 0.0,
             transition_fps: 30,
@@ -281,13 +1293,99 @@ fn restore_from_cache(requested_outputs: &[String]) -> Result<(), String> {
                 x: cli::CliCoord::Pixel(0.0),
                 y: cli::CliCoord::Pixel(0.0),
             },
+            transition_origins: None,
             invert_y: false,
+            transition_span: false,
             transition_bezier: (0.0, 0.0, 0.0, 0.0),
+            transition_spring: None,
+            transition_curve: None,
             transition_wave: (0.0, 0.0),
+            transition_feather: 0.0,
+            transition_colorspace: cli::TransitionColorspace::Srgb,
+            transition_ripple: (0.0, 1.0),
+            transition_block_size: std::num::NonZeroU16::MIN,
+            transition_slats: std::num::NonZeroU16::MIN,
+            transition_slat_direction: cli::SlatDirection::Horizontal,
+            transition_seed: None,
+            transition_clock_direction: cli::ClockDirection::Clockwise,
+            transition_soft_edge: 0.0,
+            transition_axis: cli::CubeAxis::Vertical,
+            transition_fov: 60.0,
+            transition_shards: std::num::NonZeroU16::MIN,
+            transition_gravity: 0.0,
+            transition_turns: 1.0,
+            transition_thickness: 0.0,
+            transition_tile_size: std::num::NonZeroU16::MIN,
+            transition_mask: None,
+            transition_softness: 0.0,
+            transition_sort_threshold: 0.0,
+            transition_sort_direction: cli::SortDirection::Horizontal,
+            transition_matrix: cli::HalftoneMatrix::Bayer,
+            transition_matrix_size: 2,
+            transition_blend: cli::TransitionBlend::Screen,
+            transition_overlay: (0.0, 0.0),
+            transition_edge_threshold: 0.0,
+            transition_plugin: None,
+            transition_random: Box::default(),
+            transition_random_exclude: None,
+            stack: Vec::new(),
+            stack_direction: cli::StackDirection::Vertical,
+            stack_weights: Vec::new(),
+            frame_delay_clamp: 10,
+            interpolate: None,
+            loops: None,
+            page: cli::Page::Number(std::num::NonZeroU16::MIN),
+            fps: 24,
+            max_anim_resolution: None,
+            location: None,
+            cache_compress: false,
+            dither: cli::Dither::Always,
+            tone_map: cli::ToneMap::Reinhard,
+            color_profile: None,
+            verbose: false,
+            print_request: None,
+            validate: None,
+            validate_fallback: cli::ValidateFallback::Keep,
         })) {
-            eprintln!("WARNING: failed to load cache for output {output}: {e}");
+            eprintln!("{}", msg!("restore-failed", output = output, error = e));
         }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_transitions_should_only_take_transition_flags() {
+        let img: cli::Img = with_transition_defaults([
+            "img",
+            "0xff0000",
+            "--resize",
+            "fit",
+            "--output-transition",
+            "DP-1:--transition-type fade --transition-step=2",
+        ])
+        .unwrap();
+        let (outputs, group) = &img.transition_groups[0];
+        assert_eq!(&**outputs, ["DP-1"]);
+        assert!(matches!(group.transition_type, cli::TransitionType::Fade));
+
+        for flags in [
+            "DP-1:--resize crop",
+            "DP-1:--transition-type fade --resize=crop",
+        ] {
+            let err = with_transition_defaults::<cli::Img, _, _>([
+                "img",
+                "0xff0000",
+                "--output-transition",
+                flags,
+            ])
+            .err()
+            .expect("--resize is not a transition flag");
+            assert!(err.to_string().contains("--resize"), "{err}");
+        }
+    }
+}
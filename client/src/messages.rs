@@ -0,0 +1,189 @@
+//! Translatable messages.
+//!
+//! Everything we tell the user (errors, warnings and the like) goes through `msg!`, which looks
+//! it up by id in a message catalog. The English catalog, `locale/en.ftl`, is built into the
+//! binary. Translations are files named after the language, like `pt_BR.ftl` or `pt.ftl`, in
+//! `$SWWW_LOCALE_DIR` or else `/usr/share/swww/locale` (distributions can change that by
+//! setting `SWWW_LOCALE_DIR` at build time). The language comes from `$LC_ALL`,
+//! `$LC_MESSAGES` or `$LANG`, and messages missing from a translation fall back to English.
+//!
+//! The catalogs are a subset of fluent: one `id = text` per line, `#` comments, and
+//! `{ $name }` placeholders.
+//!
+//! The command line help comes from clap, and is not translated.
+use std::{collections::HashMap, fmt::Display, path::PathBuf, sync::OnceLock};
+
+const ENGLISH: &str = include_str!("../locale/en.ftl");
+
+/// `msg!("id", name = value, ...)` formats the message with that id, replacing its `{ $name }`
+/// placeholders with the given values
+macro_rules! msg {
+    ($id:literal $(, $name:ident = $value:expr)* $(,)?) => {
+        $crate::messages::format(
+            $id,
+            &[$((stringify!($name), &$value as &dyn std::fmt::Display)),*],
+        )
+    };
+}
+pub(crate) use msg;
+
+struct Catalog {
+    english: HashMap<&'static str, &'static str>,
+    translation: HashMap<String, String>,
+}
+
+fn catalog() -> &'static Catalog {
+    static CATALOG: OnceLock<Catalog> = OnceLock::new();
+    CATALOG.get_or_init(|| Catalog {
+        english: parse(ENGLISH).collect(),
+        translation: load_translation().unwrap_or_default(),
+    })
+}
+
+fn parse(catalog: &str) -> impl Iterator<Item = (&str, &str)> {
+    catalog
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(id, text)| (id.trim(), text.trim()))
+}
+
+/// the languages we should try, from the most to the least specific (e.g. `pt_BR`, then `pt`)
+fn languages() -> Vec<String> {
+    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .into_iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|locale| !locale.is_empty())
+        .unwrap_or_default();
+    // strip the encoding and modifier, as in `pt_BR.UTF-8@euro`
+    let locale = locale.split(['.', '@']).next().unwrap_or_default();
+    if locale.is_empty() || locale == "C" || locale == "POSIX" {
+        return Vec::new();
+    }
+
+    let mut languages = vec![locale.to_string()];
+    if let Some((language, _)) = locale.split_once('_') {
+        languages.push(language.to_string());
+    }
+    languages
+}
+
+fn load_translation() -> Option<HashMap<String, String>> {
+    let dir = std::env::var_os("SWWW_LOCALE_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| {
+            option_env!("SWWW_LOCALE_DIR")
+                .unwrap_or("/usr/share/swww/locale")
+                .into()
+        });
+    languages().into_iter().find_map(|language| {
+        let catalog = std::fs::read_to_string(dir.join(format!("{language}.ftl"))).ok()?;
+        Some(
+            parse(&catalog)
+                .map(|(id, text)| (id.to_string(), text.to_string()))
+                .collect(),
+        )
+    })
+}
+
+/// Formats the message `id`. Use `msg!` instead of calling this directly
+pub fn format(id: &str, args: &[(&str, &dyn Display)]) -> String {
+    let catalog = catalog();
+    let text = match catalog.translation.get(id) {
+        Some(text) => text.as_str(),
+        None => catalog.english.get(id).copied().unwrap_or(id),
+    };
+    substitute(text, args)
+}
+
+fn substitute(text: &str, args: &[(&str, &dyn Display)]) -> String {
+    use std::fmt::Write;
+
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find('}') else {
+            break;
+        };
+        let name = rest[1..end].trim().strip_prefix('$').map(str::trim);
+        match args.iter().find(|(arg, _)| Some(*arg) == name) {
+            Some((_, value)) => {
+                let _ = write!(out, "{value}");
+            }
+            None => out.push_str(&rest[..=end]),
+        }
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_substitute_placeholders() {
+        let path = "/tmp/a b.png";
+        assert_eq!(
+            substitute(
+                "failed to read { $path }: {$error}",
+                &[("path", &path), ("error", &42)]
+            ),
+            "failed to read /tmp/a b.png: 42"
+        );
+        // unknown placeholders and stray braces are kept as they are
+        assert_eq!(substitute("{ $nope } {} {", &[]), "{ $nope } {} {");
+    }
+
+    #[test]
+    fn every_message_should_be_in_the_english_catalog() {
+        let sources = [
+            include_str!("main.rs"),
+            include_str!("error.rs"),
+            include_str!("imgproc.rs"),
+            include_str!("batch.rs"),
+            include_str!("bundle.rs"),
+            include_str!("fifo.rs"),
+            include_str!("now_playing.rs"),
+            include_str!("screencast.rs"),
+            include_str!("providers.rs"),
+            include_str!("presets.rs"),
+            include_str!("avif.rs"),
+            include_str!("completions.rs"),
+            include_str!("cutout.rs"),
+            include_str!("heif.rs"),
+            include_str!("icc.rs"),
+            include_str!("picture_frame.rs"),
+            include_str!("preview.rs"),
+            include_str!("stream.rs"),
+            include_str!("svg.rs"),
+            include_str!("tiff.rs"),
+            include_str!("time_of_day.rs"),
+            include_str!("workspace_tint.rs"),
+        ];
+        let english: HashMap<_, _> = parse(ENGLISH).collect();
+        let mut used = Vec::new();
+        for source in sources {
+            for call in source.split("msg!(").skip(1) {
+                let id = call
+                    .trim_start()
+                    .strip_prefix('"')
+                    .and_then(|s| s.split('"').next());
+                if let Some(id) = id {
+                    assert!(english.contains_key(id), "{id} is not in locale/en.ftl");
+                    used.push(id);
+                }
+            }
+        }
+        for id in english.keys() {
+            assert!(
+                used.contains(id),
+                "{id} is in locale/en.ftl, but never used"
+            );
+        }
+    }
+}
@@ -0,0 +1,188 @@
+//! Implements `swww now-playing`.
+//!
+//! We do not talk to dbus ourselves. Instead, we periodically ask `playerctl` for the status and
+//! album art of the current track, and send a regular `swww img` request whenever that changes.
+use std::{
+    path::PathBuf,
+    process::Command,
+    time::{Duration, Instant},
+};
+
+use clap::ValueEnum;
+
+use crate::cli::{CliImage, NowPlaying, Swww};
+use crate::messages::msg;
+
+/// What should be on screen according to the player
+#[derive(Clone, PartialEq, Eq)]
+enum Track {
+    /// Nothing is playing, or the track has no album art we can read
+    Nothing,
+    /// Path to the local album art of the current track
+    Art(PathBuf),
+}
+
+pub fn watch(args: &NowPlaying) -> Result<(), String> {
+    let interval = Duration::from_millis(args.interval.max(1));
+    let debounce = Duration::from_millis(args.debounce);
+
+    let mut displayed: Option<Track> = None;
+    let mut pending: Option<(Track, Instant)> = None;
+    loop {
+        let track = query_player(args.player.as_deref())?;
+
+        if displayed.as_ref() == Some(&track) {
+            pending = None;
+        } else {
+            let since = match &pending {
+                Some((p, since)) if *p == track => *since,
+                _ => Instant::now(),
+            };
+            // the very first track is displayed right away
+            if displayed.is_none() || since.elapsed() >= debounce {
+                if let Err(e) = display(args, &track) {
+                    eprintln!("{}", msg!("album-art-failed", error = e));
+                }
+                displayed = Some(track);
+                pending = None;
+            } else {
+                pending = Some((track, since));
+            }
+        }
+
+        std::thread::sleep(interval);
+    }
+}
+
+fn query_player(player: Option<&str>) -> Result<Track, String> {
+    let mut cmd = Command::new("playerctl");
+    if let Some(player) = player {
+        cmd.arg("--player").arg(player);
+    }
+    let output = cmd
+        .args(["metadata", "--format", "{{status}} {{mpris:artUrl}}"])
+        .output()
+        .map_err(|e| msg!("run-failed", program = "playerctl", error = e))?;
+
+    // playerctl fails when there are no players around
+    if !output.status.success() {
+        return Ok(Track::Nothing);
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let (status, url) = stdout.trim().split_once(' ').unwrap_or((stdout.trim(), ""));
+    if status == "Stopped" {
+        return Ok(Track::Nothing);
+    }
+
+    match art_url_to_path(url) {
+        Some(path) if path.is_file() => Ok(Track::Art(path)),
+        _ => Ok(Track::Nothing),
+    }
+}
+
+/// Only local art is supported, since we do not want to be downloading things from the internet
+fn art_url_to_path(url: &str) -> Option<PathBuf> {
+    let path = url.strip_prefix("file://")?;
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok().map(PathBuf::from)
+}
+
+fn display(args: &NowPlaying, track: &Track) -> Result<(), String> {
+    let image = match track {
+        Track::Art(path) if args.blur > 0.0 => blur(path, args.blur, &args.outputs)?,
+        Track::Art(path) => path.to_string_lossy().to_string(),
+        Track::Nothing => match &args.fallback {
+            Some(CliImage::Path(path)) => path.to_string_lossy().to_string(),
+            Some(CliImage::Color(c)) => format!("0x{:02x}{:02x}{:02x}", c[0], c[1], c[2]),
+            Some(CliImage::Provider(provider)) => format!("@{provider}"),
+            None => return Ok(()),
+        },
+    };
+
+    let resize = args
+        .resize
+        .to_possible_value()
+        .expect("ResizeStrategy has no skipped variants");
+    // we parse the arguments instead of building the struct ourselves so that the user's
+    // SWWW_TRANSITION_* environment variables and transition defaults are respected
+    let img = crate::with_transition_defaults([
+        "img",
+        &image,
+        "--outputs",
+        &args.outputs,
+        "--resize",
+        resize.get_name(),
+    ])?;
+
+    crate::process_swww_args(&Swww::Img(img)).map_err(String::from)
+}
+
+/// Blurs the image at `path`, and stores the result in the cache directory, returning its path
+fn blur(path: &std::path::Path, sigma: f32, outputs: &str) -> Result<String, String> {
+    let img = image::open(path).map_err(|e| msg!("open-album-art-failed", error = e))?;
+    let mut out = common::cache::cache_dir().map_err(|e| msg!("cache-dir-failed", error = e))?;
+    out.push(blurred_art_name(outputs));
+    img.blur(sigma)
+        .save(&out)
+        .map_err(|e| msg!("save-album-art-failed", error = e))?;
+    Ok(out.to_string_lossy().to_string())
+}
+
+/// The file we blur the art for `outputs` into, so that instances showing the art on different
+/// outputs do not overwrite each other's. Each track replaces the previous one's, which is what
+/// the daemon restores after a restart
+fn blurred_art_name(outputs: &str) -> String {
+    if outputs.is_empty() {
+        "now-playing.png".to_string()
+    } else {
+        format!("now-playing-{}.png", outputs.replace('/', "_"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn art_url_to_path_should_only_accept_local_files() {
+        assert_eq!(
+            art_url_to_path("file:///home/me/art.png"),
+            Some(PathBuf::from("/home/me/art.png"))
+        );
+        assert_eq!(art_url_to_path("https://example.com/art.png"), None);
+        assert_eq!(art_url_to_path(""), None);
+    }
+
+    #[test]
+    fn art_url_to_path_should_percent_decode() {
+        assert_eq!(
+            art_url_to_path("file:///tmp/my%20album/%C3%A9t%C3%A9.jpg"),
+            Some(PathBuf::from("/tmp/my album/été.jpg"))
+        );
+        assert_eq!(
+            art_url_to_path("file:///tmp/100%25"),
+            Some(PathBuf::from("/tmp/100%"))
+        );
+        assert_eq!(art_url_to_path("file:///tmp/%zz.png"), None);
+    }
+
+    #[test]
+    fn blurred_art_should_be_kept_per_output() {
+        assert_eq!(blurred_art_name(""), "now-playing.png");
+        assert_ne!(blurred_art_name("DP-1"), blurred_art_name("HDMI-A-1"));
+        assert!(!blurred_art_name("a/b").contains('/'));
+    }
+}
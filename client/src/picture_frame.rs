@@ -0,0 +1,230 @@
+//! Implements `swww picture-frame`.
+//!
+//! Everything goes through regular requests, so the frame does not care if the daemon restarts:
+//! whenever we cannot reach it, we keep retrying (with a growing delay) and display what should
+//! be on screen again once it answers. The time of day for the quiet hours comes from
+//! `localtime_r`, like the daemon's time of day schedules.
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+    time::{Duration, Instant},
+};
+
+use clap::ValueEnum;
+use common::ipc::IpcSocket;
+
+use crate::cli::{self, PictureFrame, Swww};
+use crate::error::ErrorKind;
+use crate::messages::msg;
+
+/// How often we check the clock and whether the daemon is still around
+const TICK: Duration = Duration::from_secs(5);
+
+/// The longest we wait before retrying a request the daemon did not answer
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// What should be on screen
+#[derive(Clone, PartialEq, Eq)]
+enum Showing {
+    Picture(PathBuf),
+    Quiet,
+}
+
+pub fn watch(args: &PictureFrame) -> Result<(), String> {
+    let interval = Duration::from_secs(args.interval.max(1));
+    let mut playlist = Vec::new();
+    let mut picture: Option<PathBuf> = None;
+    let mut next_picture = Instant::now();
+
+    // whether we last ran the quiet or the wake command, if any
+    let mut was_quiet: Option<bool> = None;
+    // what the daemon is displaying, as far as we know
+    let mut displayed: Option<Showing> = None;
+    let mut retry_delay = TICK;
+    let mut next_try = Instant::now();
+    loop {
+        let quiet = match args.quiet_hours {
+            Some(hours) => is_quiet(hours, minute_of_day()),
+            None => false,
+        };
+        if was_quiet != Some(quiet) {
+            if quiet {
+                run_hook(args.quiet_command.as_deref());
+            } else if was_quiet.is_some() {
+                run_hook(args.wake_command.as_deref());
+            }
+            was_quiet = Some(quiet);
+        }
+
+        if !quiet && (picture.is_none() || next_picture <= Instant::now()) {
+            if playlist.is_empty() {
+                playlist = make_playlist(args, picture.as_deref())?;
+            }
+            picture = playlist.pop();
+            next_picture = Instant::now() + interval;
+        }
+        let showing = match &picture {
+            Some(path) if !quiet => Showing::Picture(path.clone()),
+            _ => Showing::Quiet,
+        };
+
+        if IpcSocket::connect().is_err() {
+            if args.restart_daemon {
+                if let Err(e) = crate::start_daemon(&args.daemon_args) {
+                    eprintln!("{}", msg!("picture-frame-failed", error = e));
+                }
+            }
+            // once it is back, it will display whatever it had cached, not what we want
+            displayed = None;
+        }
+
+        if displayed.as_ref() != Some(&showing) && next_try <= Instant::now() {
+            match display(args, &showing) {
+                Ok(()) => {
+                    displayed = Some(showing);
+                    retry_delay = TICK;
+                }
+                // there is no point in retrying broken pictures, so we move on to the next one
+                Err(e) if e.kind() == ErrorKind::Decode => {
+                    eprintln!("{}", msg!("picture-frame-failed", error = e));
+                    next_picture = Instant::now() + TICK;
+                    displayed = Some(showing);
+                }
+                Err(e) => {
+                    eprintln!("{}", msg!("picture-frame-failed", error = e));
+                    next_try = Instant::now() + retry_delay;
+                    retry_delay = (retry_delay * 2).min(MAX_RETRY_DELAY);
+                }
+            }
+        }
+
+        let wake_up = next_picture.min(Instant::now() + TICK);
+        std::thread::sleep(wake_up.saturating_duration_since(Instant::now()));
+    }
+}
+
+/// Whether `minute` (of the day) falls within `hours`, which may go over midnight
+fn is_quiet(hours: (u16, u16), minute: u16) -> bool {
+    let (start, end) = hours;
+    if start <= end {
+        (start..end).contains(&minute)
+    } else {
+        minute >= start || minute < end
+    }
+}
+
+extern "C" {
+    // POSIX, but not bound by the `libc` crate
+    fn tzset();
+}
+
+/// The local time, in minutes after midnight
+fn minute_of_day() -> u16 {
+    // SAFETY: `time` accepts a null pointer, and `localtime_r` only writes to `tm`
+    unsafe {
+        // so we follow changes to the time zone
+        tzset();
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+        if libc::localtime_r(&now, &mut tm).is_null() {
+            return (now.rem_euclid(24 * 60 * 60) / 60) as u16;
+        }
+        (tm.tm_hour * 60 + tm.tm_min) as u16
+    }
+}
+
+/// Every picture in `args.paths`, shuffled unless asked otherwise, in reverse order so they can
+/// be popped. `last` is moved away from the front, so it is not displayed twice in a row
+fn make_playlist(args: &PictureFrame, last: Option<&Path>) -> Result<Vec<PathBuf>, String> {
+    let mut playlist = Vec::new();
+    for path in &args.paths {
+        if !path.is_dir() {
+            playlist.push(path.clone());
+            continue;
+        }
+        let entries = std::fs::read_dir(path)
+            .map_err(|e| msg!("read-failed", path = path.display(), error = e))?;
+        let mut pictures: Vec<PathBuf> = entries
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| path.is_file() && image::ImageFormat::from_path(path).is_ok())
+            .collect();
+        pictures.sort();
+        playlist.extend(pictures);
+    }
+    if playlist.is_empty() {
+        return Err(msg!("picture-frame-no-pictures"));
+    }
+
+    if !args.no_shuffle {
+        fastrand::shuffle(&mut playlist);
+    }
+    playlist.reverse();
+    if playlist.len() > 1 && playlist.last().map(PathBuf::as_path) == last {
+        let end = playlist.len() - 1;
+        playlist.swap(0, end);
+    }
+    Ok(playlist)
+}
+
+/// Runs one of the user's quiet hours commands, through the shell
+fn run_hook(command: Option<&str>) {
+    let Some(command) = command else {
+        return;
+    };
+    match Command::new("sh").arg("-c").arg(command).status() {
+        Ok(status) if !status.success() => eprintln!(
+            "{}",
+            msg!("command-failed", program = command, status = status)
+        ),
+        Ok(_) => (),
+        Err(e) => eprintln!("{}", msg!("run-failed", program = command, error = e)),
+    }
+}
+
+fn display(args: &PictureFrame, showing: &Showing) -> Result<(), crate::error::Error> {
+    let swww = match showing {
+        Showing::Quiet => Swww::Clear(cli::Clear {
+            color: args.quiet_color,
+            outputs: args.outputs.clone(),
+            outro: cli::Outro::Fade,
+            outro_duration: 1.0,
+        }),
+        Showing::Picture(path) => {
+            let resize = args
+                .resize
+                .to_possible_value()
+                .expect("ResizeStrategy has no skipped variants");
+            // we parse the arguments instead of building the struct ourselves so that the
+            // user's SWWW_TRANSITION_* environment variables and transition defaults are respected
+            Swww::Img(crate::with_transition_defaults([
+                "img",
+                &path.to_string_lossy(),
+                "--outputs",
+                &args.outputs,
+                "--resize",
+                resize.get_name(),
+            ])?)
+        }
+    };
+    crate::process_swww_args(&swww)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quiet_hours_should_go_over_midnight() {
+        let night = (22 * 60 + 30, 7 * 60);
+        assert!(is_quiet(night, 23 * 60));
+        assert!(is_quiet(night, 0));
+        assert!(is_quiet(night, 6 * 60 + 59));
+        assert!(!is_quiet(night, 7 * 60));
+        assert!(!is_quiet(night, 12 * 60));
+
+        let lunch = (12 * 60, 13 * 60);
+        assert!(is_quiet(lunch, 12 * 60 + 30));
+        assert!(!is_quiet(lunch, 13 * 60));
+        assert!(!is_quiet((0, 0), 0));
+    }
+}
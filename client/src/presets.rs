@@ -0,0 +1,150 @@
+//! Named sets of transition flags, for `swww img --transition-preset`.
+//!
+//! Besides the builtin ones, users can define their own in `$XDG_CONFIG_HOME/swww/presets`, which
+//! looks like:
+//!
+//! ```text
+//! # comments start with a '#'
+//! [slow-wipe]
+//! type = wipe
+//! duration = 4
+//! angle = 45
+//! ```
+//!
+//! The keys are the same as the ones of `swww transition-defaults`. A user preset replaces the
+//! builtin one with the same name.
+use std::path::PathBuf;
+
+use crate::cli::TRANSITION_DEFAULTS;
+use crate::messages::msg;
+
+type Preset = Vec<(String, String)>;
+
+const BUILTIN: [(&str, &[(&str, &str)]); 3] = [
+    (
+        "smooth",
+        &[
+            ("type", "fade"),
+            ("duration", "1.5"),
+            ("bezier", ".42,0,.58,1"),
+        ],
+    ),
+    (
+        "snappy",
+        &[
+            ("type", "wipe"),
+            ("duration", "0.4"),
+            ("angle", "30"),
+            ("bezier", ".25,1,.5,1"),
+        ],
+    ),
+    (
+        "dramatic",
+        &[
+            ("type", "grow"),
+            ("pos", "center"),
+            ("duration", "2.5"),
+            ("bezier", ".65,0,.35,1"),
+        ],
+    ),
+];
+
+/// Finds the preset called `name`, as (`TRANSITION_DEFAULTS` name, value) pairs
+pub fn find(name: &str) -> Result<Preset, String> {
+    if let Some(path) = user_presets_path() {
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => {
+                let presets = parse(&contents)
+                    .map_err(|e| msg!("presets-file-invalid", path = path.display(), error = e))?;
+                if let Some((_, preset)) = presets.into_iter().find(|(n, _)| n == name) {
+                    return Ok(preset);
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
+            Err(e) => return Err(msg!("read-failed", path = path.display(), error = e)),
+        }
+    }
+
+    match BUILTIN.iter().find(|(n, _)| *n == name) {
+        Some((_, preset)) => Ok(preset
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()),
+        None => {
+            let builtin: Vec<&str> = BUILTIN.iter().map(|(n, _)| *n).collect();
+            Err(msg!(
+                "unknown-preset",
+                name = name,
+                builtin = builtin.join(", ")
+            ))
+        }
+    }
+}
+
+fn user_presets_path() -> Option<PathBuf> {
+    let dir = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(dir.join("swww/presets"))
+}
+
+fn parse(contents: &str) -> Result<Vec<(String, Preset)>, String> {
+    let mut presets: Vec<(String, Preset)> = Vec::new();
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let error = |error: String| msg!("preset-line", line = i + 1, error = error);
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            presets.push((name.trim().to_string(), Vec::new()));
+            continue;
+        }
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| error(msg!("preset-bad-line")))?;
+        let (key, value) = (key.trim(), value.trim());
+        if !TRANSITION_DEFAULTS.iter().any(|(name, _, _)| *name == key) {
+            return Err(error(msg!("preset-unknown-key", key = key)));
+        }
+        match presets.last_mut() {
+            Some((_, preset)) => preset.push((key.to_string(), value.to_string())),
+            None => return Err(error(msg!("preset-bad-line"))),
+        }
+    }
+    Ok(presets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_should_parse() {
+        let presets =
+            parse("# mine\n[slow-wipe]\ntype = wipe\nduration=4\n\n[ quick ]\nduration = 0.2\n")
+                .unwrap();
+        let names: Vec<&str> = presets.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["slow-wipe", "quick"]);
+        assert_eq!(
+            presets[0].1,
+            [
+                ("type".to_string(), "wipe".to_string()),
+                ("duration".to_string(), "4".to_string())
+            ]
+        );
+        assert!(parse("type = wipe").is_err());
+        assert!(parse("[a]\nspeed = 3").is_err());
+        assert!(parse("[a]\nwipe").is_err());
+    }
+
+    #[test]
+    fn builtin_presets_should_only_use_known_keys() {
+        for (_, preset) in BUILTIN {
+            for (key, _) in preset {
+                assert!(TRANSITION_DEFAULTS.iter().any(|(name, _, _)| name == key));
+            }
+        }
+    }
+}
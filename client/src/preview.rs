@@ -0,0 +1,230 @@
+//! Implements `swww preview-transition`.
+//!
+//! We run the daemon's transition engine (`swww_render::transition`) on a canvas in memory, instead of
+//! on the wallpapers. Time only moves forward when a frame is presented, by `1 / fps` seconds, so
+//! the preview has the same frames the daemon would draw on a machine fast enough to keep up,
+//! no matter how long we take to save them.
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+use common::ipc::{self, PixelFormat};
+use image::{
+    codecs::gif::{GifEncoder, Repeat},
+    Delay, DynamicImage, Frame, RgbImage,
+};
+use swww_render::transition::{Target, Transition};
+
+use crate::{
+    cli::{self, CliImage, ResizeStrategy},
+    error::{Error, ErrorKind},
+    icc,
+    imgproc::*,
+    messages::msg,
+};
+
+/// The images are decoded as rgb, which is what we save
+const FORMAT: PixelFormat = PixelFormat::Bgr;
+
+/// Trades the quality of the gif's palettes for encoding speed, from 1 to 30
+const GIF_SPEED: i32 = 10;
+
+pub fn preview(preview: &cli::PreviewTransition) -> Result<(), Error> {
+    let img = &preview.img;
+    let color_profile = match &img.color_profile {
+        Some(path) => icc::Profile::load(path).map_err(Error::decode)?,
+        None => icc::Profile::srgb(),
+    };
+    let (old, _) = ImgBuf::new(&preview.old)
+        .and_then(|mut imgbuf| {
+            imgbuf.select_page(img.page)?;
+            imgbuf.convert_colors(&color_profile);
+            imgbuf.decode(FORMAT, img.dither, img.tone_map)
+        })
+        .map_err(Error::decode)?;
+    let dim = preview.size.unwrap_or(old.dimensions());
+
+    let resize = |image: &Image| match img.resize {
+        ResizeStrategy::No => img_pad(image, dim, &img.fill_color),
+        ResizeStrategy::Crop => img_resize_crop(image, dim, make_filter(&img.filter)),
+        ResizeStrategy::Fit => {
+            img_resize_fit(image, dim, make_filter(&img.filter), &img.fill_color)
+        }
+    };
+    let new = match &img.image {
+        CliImage::Color(color) => RgbImage::from_pixel(dim.0, dim.1, image::Rgb(*color))
+            .into_raw()
+            .into_boxed_slice(),
+        CliImage::Path(path) => new_image(path, img, &color_profile, resize)?,
+        CliImage::Provider(provider) => new_image(
+            &crate::providers::fetch(provider)?,
+            img,
+            &color_profile,
+            resize,
+        )?,
+    };
+    let old = resize(&old).map_err(Error::decode)?;
+
+    let transition = make_transition(img);
+    if matches!(
+        transition.transition_type,
+        ipc::TransitionType::Random | ipc::TransitionType::Plugin
+    ) {
+        eprintln!("{}", msg!("preview-simple"));
+    }
+    let mask = match &img.transition_mask {
+        Some(path) if matches!(transition.transition_type, ipc::TransitionType::Luma) => Some(
+            mask_resize(
+                &load_mask(path).map_err(Error::decode)?,
+                dim,
+                make_filter(&img.filter),
+            )
+            .map_err(Error::decode)?,
+        ),
+        None if matches!(transition.transition_type, ipc::TransitionType::Luma) => {
+            Some(luma_mask(&new, FORMAT))
+        }
+        _ => None,
+    };
+
+    let mut recorder = Recorder::new(&preview.out, old, dim, transition.fps)?;
+    Transition::new(
+        &mut recorder,
+        dim,
+        transition.params(),
+        mask.as_deref(),
+        None,
+    )
+    .execute(&new);
+    if let Some(e) = recorder.error {
+        return Err(e.into());
+    }
+    if img.verbose {
+        let path = preview.out.display();
+        eprintln!(
+            "{}",
+            msg!("preview-saved", frames = recorder.frames, path = path)
+        );
+    }
+    Ok(())
+}
+
+fn new_image(
+    path: &Path,
+    img: &cli::Img,
+    color_profile: &icc::Profile,
+    resize: impl Fn(&Image) -> Result<Box<[u8]>, String>,
+) -> Result<Box<[u8]>, Error> {
+    let mut imgbuf = ImgBuf::new(path).map_err(Error::decode)?;
+    imgbuf.select_page(img.page).map_err(Error::decode)?;
+    imgbuf.convert_colors(color_profile);
+    if imgbuf.is_animated() {
+        eprintln!("{}", msg!("preview-first-frame"));
+    }
+    let (image, _) = imgbuf
+        .decode(FORMAT, img.dither, img.tone_map)
+        .map_err(Error::decode)?;
+    resize(&image).map_err(Error::decode)
+}
+
+enum Output {
+    Gif(GifEncoder<BufWriter<File>>),
+    /// a directory, where each frame is saved as a numbered png
+    Png(PathBuf),
+}
+
+/// Draws the transition on a single canvas, saving every frame it presents
+struct Recorder {
+    canvas: Box<[u8]>,
+    /// the canvas before the transition, kept once the transition asks for it
+    old: Option<Box<[u8]>>,
+    dim: (u32, u32),
+    fps: u16,
+    frames: u32,
+    output: Output,
+    /// the first error we got while saving the frames, after which we stop saving them
+    error: Option<String>,
+}
+
+impl Recorder {
+    fn new(out: &Path, canvas: Box<[u8]>, dim: (u32, u32), fps: u16) -> Result<Self, Error> {
+        if fps == 0 {
+            return Err(Error::new(ErrorKind::Usage, msg!("preview-zero-fps")));
+        }
+        let is_gif = out
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("gif"));
+        let output = if is_gif {
+            let file = File::create(out)
+                .map_err(|e| msg!("preview-create-failed", path = out.display(), error = e))?;
+            let mut encoder = GifEncoder::new_with_speed(BufWriter::new(file), GIF_SPEED);
+            encoder
+                .set_repeat(Repeat::Infinite)
+                .map_err(|e| msg!("preview-save-failed", error = e))?;
+            Output::Gif(encoder)
+        } else {
+            std::fs::create_dir_all(out)
+                .map_err(|e| msg!("preview-create-failed", path = out.display(), error = e))?;
+            Output::Png(out.to_path_buf())
+        };
+        Ok(Self {
+            canvas,
+            old: None,
+            dim,
+            fps,
+            frames: 0,
+            output,
+            error: None,
+        })
+    }
+
+    fn save(&mut self) -> Result<(), String> {
+        let (width, height) = self.dim;
+        let image = RgbImage::from_raw(width, height, self.canvas.to_vec())
+            .expect("the canvas has the preview's size");
+        match &mut self.output {
+            Output::Gif(encoder) => {
+                let delay = Delay::from_numer_denom_ms(1000, self.fps as u32);
+                let frame = Frame::from_parts(DynamicImage::from(image).into_rgba8(), 0, 0, delay);
+                encoder
+                    .encode_frame(frame)
+                    .map_err(|e| msg!("preview-save-failed", error = e))
+            }
+            Output::Png(dir) => image
+                .save(dir.join(format!("{:05}.png", self.frames)))
+                .map_err(|e| msg!("preview-save-failed", error = e)),
+        }
+    }
+}
+
+impl Target for Recorder {
+    fn channels(&self) -> usize {
+        FORMAT.channels() as usize
+    }
+
+    fn bgr(&self) -> bool {
+        FORMAT.must_swap_r_and_b_channels()
+    }
+
+    fn now(&self) -> f64 {
+        self.frames as f64 / self.fps as f64
+    }
+
+    fn draw(&mut self, draw: &mut dyn FnMut(&mut [u8])) {
+        draw(&mut self.canvas)
+    }
+
+    fn redraw(&mut self, draw: &mut dyn FnMut(&mut [u8], &[u8])) {
+        let old = self.old.get_or_insert_with(|| self.canvas.clone());
+        draw(&mut self.canvas, old)
+    }
+
+    fn present(&mut self) {
+        if self.error.is_none() {
+            self.error = self.save().err();
+        }
+        self.frames += 1;
+    }
+}
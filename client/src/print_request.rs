@@ -0,0 +1,511 @@
+//! Implements `swww img --print-request`.
+//!
+//! We write the json by hand: it is a handful of fields, and not worth pulling in a
+//! serialization library for.
+use std::{
+    fmt::{Display, Write},
+    path::Path,
+};
+
+use common::ipc::{self, Coord};
+
+use crate::cli::{self, CliImage, ResizeStrategy};
+
+enum Json {
+    Null,
+    Bool(bool),
+    /// already formatted
+    Number(String),
+    Str(String),
+    Array(Vec<Json>),
+    Object(Vec<(&'static str, Json)>),
+}
+
+/// non finite numbers are not valid json, so they become `null`
+fn number(n: impl Display) -> Json {
+    let s = n.to_string();
+    if s.parse::<f64>().is_ok_and(f64::is_finite) {
+        Json::Number(s)
+    } else {
+        Json::Null
+    }
+}
+
+fn string(s: impl Into<String>) -> Json {
+    Json::Str(s.into())
+}
+
+impl Json {
+    fn write(&self, out: &mut String, indent: usize) {
+        match self {
+            Self::Null => out.push_str("null"),
+            Self::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+            Self::Number(n) => out.push_str(n),
+            Self::Str(s) => {
+                out.push('"');
+                for c in s.chars() {
+                    match c {
+                        '"' => out.push_str("\\\""),
+                        '\\' => out.push_str("\\\\"),
+                        '\n' => out.push_str("\\n"),
+                        '\t' => out.push_str("\\t"),
+                        c if c.is_control() => {
+                            let _ = write!(out, "\\u{:04x}", c as u32);
+                        }
+                        c => out.push(c),
+                    }
+                }
+                out.push('"');
+            }
+            Self::Array(values) if values.is_empty() => out.push_str("[]"),
+            Self::Array(values) => {
+                out.push('[');
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(", ");
+                    }
+                    value.write(out, indent);
+                }
+                out.push(']');
+            }
+            Self::Object(fields) => {
+                out.push('{');
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    let _ = write!(out, "\n{:width$}\"{key}\": ", "", width = indent + 2);
+                    value.write(out, indent + 2);
+                }
+                let _ = write!(out, "\n{:indent$}}}", "");
+            }
+        }
+    }
+}
+
+/// The request `swww img` would send, after applying the environment, the transition defaults
+/// and the transition aliases (e.g. `--transition-type left` is a wipe at 180 degrees)
+///
+/// `groups` and `transitions` are the arguments and the transition of each of the `outputs`, which
+/// only differ from `img` with `--output-transition`
+pub fn img_request(
+    img: &cli::Img,
+    image: &CliImage,
+    pixel_format: ipc::PixelFormat,
+    dims: &[(u32, u32)],
+    outputs: &[Vec<String>],
+    groups: &[&cli::Img],
+    transitions: &[ipc::Transition],
+) -> String {
+    // the outputs may all have their own transition
+    let own_transition;
+    let transition = match groups.iter().position(|group| std::ptr::eq(*group, img)) {
+        Some(i) => &transitions[i],
+        None => {
+            own_transition = crate::make_transition(img);
+            &own_transition
+        }
+    };
+
+    let image = match image {
+        CliImage::Path(path) => match path.canonicalize() {
+            Ok(path) => ("path", string(path.to_string_lossy())),
+            Err(_) if path.to_str() == Some("-") => ("path", string("STDIN")),
+            Err(_) => ("path", string(path.to_string_lossy())),
+        },
+        CliImage::Color(c) => (
+            "color",
+            string(format!("0x{:02x}{:02x}{:02x}", c[0], c[1], c[2])),
+        ),
+        CliImage::Provider(provider) => ("provider", string(format!("@{provider}"))),
+    };
+
+    let outputs = dims
+        .iter()
+        .zip(outputs)
+        .zip(groups.iter().zip(transitions))
+        .map(|((dim, names), (group, transition))| {
+            Json::Object(vec![
+                ("names", Json::Array(names.iter().map(string).collect())),
+                ("dim", Json::Array(vec![number(dim.0), number(dim.1)])),
+                // the same as the top level one, unless changed by `--output-transition`
+                (
+                    "transition",
+                    transition_json(transition, group.transition_mask.as_deref()),
+                ),
+            ])
+        })
+        .collect();
+
+    let json = Json::Object(vec![
+        ("image", Json::Object(vec![image])),
+        ("outputs", Json::Array(outputs)),
+        (
+            "pixel_format",
+            string(format!("{pixel_format:?}").to_lowercase()),
+        ),
+        (
+            "resize",
+            string(match img.resize {
+                ResizeStrategy::No => "no",
+                ResizeStrategy::Crop => "crop",
+                ResizeStrategy::Fit => "fit",
+            }),
+        ),
+        (
+            "fill_color",
+            string(format!(
+                "{:02x}{:02x}{:02x}",
+                img.fill_color[0], img.fill_color[1], img.fill_color[2]
+            )),
+        ),
+        (
+            "filter",
+            string(match img.filter {
+                cli::Filter::Nearest => "Nearest",
+                cli::Filter::Bilinear => "Bilinear",
+                cli::Filter::CatmullRom => "CatmullRom",
+                cli::Filter::Mitchell => "Mitchell",
+                cli::Filter::Lanczos3 => "Lanczos3",
+            }),
+        ),
+        (
+            "dither",
+            string(match img.dither {
+                cli::Dither::Auto => "auto",
+                cli::Dither::Always => "always",
+                cli::Dither::Never => "never",
+            }),
+        ),
+        (
+            "tone_map",
+            string(match img.tone_map {
+                cli::ToneMap::Reinhard => "reinhard",
+                cli::ToneMap::Aces => "aces",
+                cli::ToneMap::Clip => "clip",
+            }),
+        ),
+        (
+            "stack",
+            Json::Object(vec![
+                (
+                    "images",
+                    Json::Array(
+                        img.stack
+                            .iter()
+                            .map(|path| {
+                                let path = path.canonicalize().unwrap_or_else(|_| path.clone());
+                                string(path.to_string_lossy())
+                            })
+                            .collect(),
+                    ),
+                ),
+                (
+                    "direction",
+                    string(match img.stack_direction {
+                        cli::StackDirection::Vertical => "vertical",
+                        cli::StackDirection::Horizontal => "horizontal",
+                    }),
+                ),
+                (
+                    "weights",
+                    Json::Array(img.stack_weights.iter().map(number).collect()),
+                ),
+            ]),
+        ),
+        (
+            "transition",
+            transition_json(transition, img.transition_mask.as_deref()),
+        ),
+        (
+            "animation",
+            Json::Object(vec![
+                ("frame_delay_clamp_ms", number(img.frame_delay_clamp)),
+                (
+                    "interpolate_fps",
+                    img.interpolate.map_or(Json::Null, number),
+                ),
+                (
+                    "loop",
+                    match img.loops {
+                        Some(cli::Loops::Infinite) => string("infinite"),
+                        Some(cli::Loops::Times(times)) => number(times),
+                        // as many times as the image says
+                        None => Json::Null,
+                    },
+                ),
+                (
+                    "page",
+                    match img.page {
+                        cli::Page::All => string("all"),
+                        cli::Page::Number(page) => number(page.get()),
+                    },
+                ),
+                ("sequence_fps", number(img.fps)),
+                (
+                    "max_resolution",
+                    img.max_anim_resolution
+                        .map_or(Json::Null, |(w, h)| Json::Array(vec![number(w), number(h)])),
+                ),
+                (
+                    "location",
+                    img.location.map_or(Json::Null, |(latitude, longitude)| {
+                        Json::Array(vec![number(latitude), number(longitude)])
+                    }),
+                ),
+            ]),
+        ),
+    ]);
+
+    let mut out = String::new();
+    json.write(&mut out, 0);
+    out
+}
+
+pub(crate) fn type_name(transition_type: ipc::TransitionType) -> &'static str {
+    match transition_type {
+        ipc::TransitionType::Simple => "simple",
+        ipc::TransitionType::Fade => "fade",
+        ipc::TransitionType::Outer => "outer",
+        ipc::TransitionType::Wipe => "wipe",
+        ipc::TransitionType::Grow => "grow",
+        ipc::TransitionType::Wave => "wave",
+        ipc::TransitionType::None => "none",
+        ipc::TransitionType::Ripple => "ripple",
+        ipc::TransitionType::Pixelate => "pixelate",
+        ipc::TransitionType::Blinds => "blinds",
+        ipc::TransitionType::Dissolve => "dissolve",
+        ipc::TransitionType::Clock => "clock",
+        ipc::TransitionType::Cube => "cube",
+        ipc::TransitionType::Shatter => "shatter",
+        ipc::TransitionType::Spiral => "spiral",
+        ipc::TransitionType::Checkerboard => "checkerboard",
+        ipc::TransitionType::Push => "push",
+        ipc::TransitionType::ZoomBlur => "zoomblur",
+        ipc::TransitionType::PixelSort => "pixelsort",
+        ipc::TransitionType::Halftone => "halftone",
+        ipc::TransitionType::DoubleExposure => "doubleexposure",
+        ipc::TransitionType::Sketch => "sketch",
+        ipc::TransitionType::Luma => "luma",
+        ipc::TransitionType::Plugin => "plugin",
+        ipc::TransitionType::Random => "random",
+    }
+}
+
+/// `mask` is only sent for the 'luma' transition
+fn transition_json(transition: &ipc::Transition, mask: Option<&Path>) -> Json {
+    let coord = |coord: &Coord| match coord {
+        Coord::Pixel(x) => Json::Object(vec![("pixel", number(x))]),
+        Coord::Percent(x) => Json::Object(vec![("percent", number(x))]),
+    };
+    let position =
+        |pos: &ipc::Position| Json::Object(vec![("x", coord(&pos.x)), ("y", coord(&pos.y))]);
+    let (b0, b1, b2, b3) = transition.bezier;
+    Json::Object(vec![
+        ("type", string(type_name(transition.transition_type))),
+        ("duration", number(transition.duration)),
+        ("step", number(transition.step)),
+        ("fps", number(transition.fps)),
+        ("angle", number(transition.angle)),
+        ("pos", position(&transition.pos)),
+        (
+            "origins",
+            Json::Array(transition.origins.iter().map(position).collect()),
+        ),
+        (
+            "bezier",
+            Json::Array(vec![number(b0), number(b1), number(b2), number(b3)]),
+        ),
+        (
+            "spring",
+            match transition.spring {
+                Some((stiffness, damping, mass)) => Json::Object(vec![
+                    ("stiffness", number(stiffness)),
+                    ("damping", number(damping)),
+                    ("mass", number(mass)),
+                ]),
+                None => Json::Null,
+            },
+        ),
+        (
+            "curve",
+            if transition.curve.is_empty() {
+                Json::Null
+            } else {
+                Json::Array(
+                    transition
+                        .curve
+                        .iter()
+                        .map(|&(time, value, (b0, b1, b2, b3))| {
+                            Json::Object(vec![
+                                ("time", number(time)),
+                                ("value", number(value)),
+                                (
+                                    "bezier",
+                                    Json::Array(vec![
+                                        number(b0),
+                                        number(b1),
+                                        number(b2),
+                                        number(b3),
+                                    ]),
+                                ),
+                            ])
+                        })
+                        .collect(),
+                )
+            },
+        ),
+        (
+            "wave",
+            Json::Array(vec![number(transition.wave.0), number(transition.wave.1)]),
+        ),
+        ("feather", number(transition.feather)),
+        (
+            "colorspace",
+            string(match transition.colorspace {
+                ipc::Colorspace::Srgb => "srgb",
+                ipc::Colorspace::Linear => "linear",
+                ipc::Colorspace::Oklab => "oklab",
+            }),
+        ),
+        (
+            "ripple",
+            Json::Array(vec![
+                number(transition.ripple.0),
+                number(transition.ripple.1),
+            ]),
+        ),
+        ("block_size", number(transition.block_size)),
+        ("slats", number(transition.slats.0)),
+        (
+            "slat_direction",
+            string(if transition.slats.1 {
+                "vertical"
+            } else {
+                "horizontal"
+            }),
+        ),
+        ("seed", number(transition.seed)),
+        (
+            "clock_direction",
+            string(if transition.clock.0 {
+                "clockwise"
+            } else {
+                "counter-clockwise"
+            }),
+        ),
+        ("soft_edge", number(transition.clock.1)),
+        (
+            "axis",
+            string(if transition.cube.0 {
+                "vertical"
+            } else {
+                "horizontal"
+            }),
+        ),
+        ("fov", number(transition.cube.1)),
+        ("shards", number(transition.shatter.0)),
+        ("gravity", number(transition.shatter.1)),
+        ("turns", number(transition.spiral.0)),
+        ("thickness", number(transition.spiral.1)),
+        ("tile_size", number(transition.tile_size)),
+        (
+            "mask",
+            match mask {
+                Some(mask) if matches!(transition.transition_type, ipc::TransitionType::Luma) => {
+                    let mask = mask.canonicalize().unwrap_or_else(|_| mask.to_path_buf());
+                    string(mask.to_string_lossy())
+                }
+                _ => Json::Null,
+            },
+        ),
+        ("softness", number(transition.softness)),
+        ("sort_threshold", number(transition.pixel_sort.0)),
+        (
+            "sort_direction",
+            string(if transition.pixel_sort.1 {
+                "vertical"
+            } else {
+                "horizontal"
+            }),
+        ),
+        (
+            "matrix",
+            string(if transition.halftone.1 {
+                "blue-noise"
+            } else {
+                "bayer"
+            }),
+        ),
+        ("matrix_size", number(transition.halftone.0)),
+        (
+            "blend",
+            string(match transition.exposure.0 {
+                ipc::BlendMode::Screen => "screen",
+                ipc::BlendMode::Multiply => "multiply",
+            }),
+        ),
+        (
+            "overlay",
+            Json::Array(vec![
+                number(transition.exposure.1),
+                number(transition.exposure.2),
+            ]),
+        ),
+        ("edge_threshold", number(transition.edge_threshold)),
+        (
+            "random",
+            if matches!(transition.transition_type, ipc::TransitionType::Random) {
+                Json::Array(
+                    transition
+                        .random
+                        .iter()
+                        .map(|&(transition_type, weight)| {
+                            Json::Object(vec![
+                                ("type", string(type_name(transition_type))),
+                                ("weight", number(weight)),
+                            ])
+                        })
+                        .collect(),
+                )
+            } else {
+                Json::Null
+            },
+        ),
+        (
+            "plugin",
+            if matches!(transition.transition_type, ipc::TransitionType::Plugin) {
+                string(&transition.plugin)
+            } else {
+                Json::Null
+            },
+        ),
+        ("invert_y", Json::Bool(transition.invert_y)),
+        ("span", Json::Bool(transition.span)),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_write_valid_json() {
+        let json = Json::Object(vec![
+            ("a", string("quote \" backslash \\ newline \n bell \u{7}")),
+            (
+                "b",
+                Json::Array(vec![number(1.5f32), number(f64::NAN), Json::Null]),
+            ),
+            ("c", Json::Object(vec![("d", Json::Bool(true))])),
+            ("e", Json::Array(vec![])),
+        ]);
+        let mut out = String::new();
+        json.write(&mut out, 0);
+        assert_eq!(
+            out,
+            "{\n  \"a\": \"quote \\\" backslash \\\\ newline \\n bell \\u0007\",\n  \
+             \"b\": [1.5, null, null],\n  \"c\": {\n    \"d\": true\n  },\n  \"e\": []\n}"
+        );
+    }
+}
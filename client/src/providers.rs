@@ -0,0 +1,305 @@
+//! Implements wallpaper providers, used as `swww img @<provider>`.
+//!
+//! Providers find an image somewhere on the internet (or in a local feed), download it into
+//! `$XDG_CACHE_HOME/swww/providers` and hand back its path, so the rest of `swww img` works just
+//! like it would for a regular file. Each image is only downloaded once per day, and, if we are
+//! offline, we fall back to the last image that provider gave us.
+//!
+//! We do not link against any http library; downloading is done by `curl`.
+use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::messages::msg;
+
+/// Names accepted after `@`, for error messages and documentation
+pub const PROVIDERS: &str = "bing-potd | wikimedia-potd | rss:<url or path>";
+
+enum Provider<'a> {
+    Bing,
+    Wikimedia,
+    Rss(&'a str),
+}
+
+impl<'a> Provider<'a> {
+    fn parse(name: &'a str) -> Result<Self, String> {
+        match name {
+            "bing-potd" => Ok(Self::Bing),
+            "wikimedia-potd" => Ok(Self::Wikimedia),
+            _ => match name.strip_prefix("rss:") {
+                Some(feed) if !feed.is_empty() => Ok(Self::Rss(feed)),
+                _ => Err(msg!("unknown-provider", name = name, providers = PROVIDERS)),
+            },
+        }
+    }
+
+    /// prefix of the files we store in the cache for this provider
+    fn cache_prefix(&self) -> String {
+        match self {
+            Self::Bing => "bing-potd".to_string(),
+            Self::Wikimedia => "wikimedia-potd".to_string(),
+            Self::Rss(feed) => {
+                let mut hasher = DefaultHasher::new();
+                feed.hash(&mut hasher);
+                format!("rss-{:016x}", hasher.finish())
+            }
+        }
+    }
+
+    /// whether the feed was read from a local path, the only case where we trust it to point us
+    /// at other local files with `file://`
+    fn is_local(&self) -> bool {
+        matches!(self, Self::Rss(feed) if !is_url(feed))
+    }
+
+    /// finds the url of the image we should download
+    fn image_url(&self, (year, month, day): (i64, u32, u32)) -> Result<String, String> {
+        match self {
+            Self::Bing => {
+                let json =
+                    fetch_text("https://www.bing.com/HPImageArchive.aspx?format=js&idx=0&n=1")?;
+                let path =
+                    find_between(&json, "\"url\":\"", "\"").ok_or_else(|| msg!("bing-no-url"))?;
+                Ok(format!(
+                    "https://www.bing.com{}",
+                    path.replace("\\u0026", "&")
+                ))
+            }
+            Self::Wikimedia => {
+                let json = fetch_text(&format!(
+                    "https://api.wikimedia.org/feed/v1/wikipedia/en/featured/{year}/{month:02}/{day:02}"
+                ))?;
+                // the full resolution image comes after the thumbnail
+                let url = find_between(&json, "\"image\":{\"source\":\"", "\"")
+                    .ok_or_else(|| msg!("wikimedia-no-potd"))?;
+                Ok(url.to_string())
+            }
+            Self::Rss(feed) => {
+                let xml = if is_url(feed) {
+                    fetch_text(feed)?
+                } else {
+                    std::fs::read_to_string(feed)
+                        .map_err(|e| msg!("read-rss-failed", feed = feed, error = e))?
+                };
+                ["<enclosure", "<media:content", "<img"]
+                    .iter()
+                    .find_map(|tag| {
+                        let rest = &xml[xml.find(tag)?..];
+                        let rest = &rest[..rest.find('>')?];
+                        find_between(rest, "url=\"", "\"")
+                            .or_else(|| find_between(rest, "src=\"", "\""))
+                            .map(|url| url.replace("&amp;", "&"))
+                    })
+                    .ok_or_else(|| msg!("rss-no-image", feed = feed))
+            }
+        }
+    }
+}
+
+/// Returns the path to today's image for the provider `name` (the part after the `@`)
+pub fn fetch(name: &str) -> Result<PathBuf, String> {
+    let provider = Provider::parse(name)?;
+    let mut dir = common::cache::cache_dir().map_err(|e| msg!("cache-dir-failed", error = e))?;
+    dir.push("providers");
+    if !dir.is_dir() {
+        std::fs::create_dir(&dir).map_err(|e| msg!("providers-cache-dir-failed", error = e))?;
+    }
+
+    let prefix = provider.cache_prefix();
+    let date = today();
+    let path = dir.join(format!(
+        "{prefix}_{:04}-{:02}-{:02}",
+        date.0, date.1, date.2
+    ));
+    if path.is_file() {
+        return Ok(path);
+    }
+
+    let result = provider
+        .image_url(date)
+        .and_then(|url| download(&url, &path, provider.is_local()));
+    match result {
+        Ok(()) => Ok(path),
+        Err(e) => match latest_cached(&dir, &prefix) {
+            Some(fallback) => {
+                eprintln!("{}", msg!("fetch-failed-warning", name = name, error = e));
+                eprintln!("{}", msg!("fetch-fallback", path = fallback.display()));
+                Ok(fallback)
+            }
+            None => Err(msg!("fetch-failed", name = name, error = e)),
+        },
+    }
+}
+
+fn is_url(s: &str) -> bool {
+    s.starts_with("https://") || s.starts_with("http://")
+}
+
+fn curl() -> Command {
+    let mut cmd = Command::new("curl");
+    cmd.args(["--fail", "--silent", "--show-error", "--location"])
+        .args(["--max-time", "30"]);
+    cmd
+}
+
+fn fetch_text(url: &str) -> Result<String, String> {
+    let output = curl()
+        .arg(url)
+        .output()
+        .map_err(|e| msg!("run-failed", program = "curl", error = e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    String::from_utf8(output.stdout).map_err(|e| msg!("invalid-utf8", error = e))
+}
+
+/// downloads `url` into `path`. `file://` urls are only followed when `allow_local` is set, so a
+/// feed from the internet cannot make us copy local files
+fn download(url: &str, path: &Path, allow_local: bool) -> Result<(), String> {
+    if let Some(local) = url.strip_prefix("file://") {
+        if !allow_local {
+            return Err(msg!("refusing-download", url = url));
+        }
+        return std::fs::copy(local, path)
+            .map(|_| ())
+            .map_err(|e| msg!("copy-failed", path = local, error = e));
+    }
+    if !is_url(url) {
+        return Err(msg!("refusing-download", url = url));
+    }
+
+    // download to a temporary file first, so a partial download never ends up in the cache
+    let tmp = path.with_extension("part");
+    let output = curl()
+        .arg("--output")
+        .arg(&tmp)
+        .arg(url)
+        .output()
+        .map_err(|e| msg!("run-failed", program = "curl", error = e))?;
+    if !output.status.success() {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    std::fs::rename(&tmp, path).map_err(|e| msg!("store-download-failed", error = e))
+}
+
+/// the most recent image we have for a provider. Since the dates are in ISO format, the most
+/// recent is also the biggest
+fn latest_cached(dir: &Path, prefix: &str) -> Option<PathBuf> {
+    std::fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .filter(|entry| {
+            entry.file_name().to_str().is_some_and(|name| {
+                name.strip_prefix(prefix)
+                    .is_some_and(|rest| rest.starts_with('_') && !rest.ends_with(".part"))
+            })
+        })
+        .map(|entry| entry.path())
+        .max()
+}
+
+fn find_between<'a>(haystack: &'a str, start: &str, end: &str) -> Option<&'a str> {
+    let rest = &haystack[haystack.find(start)? + start.len()..];
+    Some(&rest[..rest.find(end)?])
+}
+
+/// today's (utc) date, as (year, month, day)
+fn today() -> (i64, u32, u32) {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    civil_from_days((secs / 86400) as i64)
+}
+
+/// converts days since the unix epoch into a date. See
+/// http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn days_should_become_dates() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(civil_from_days(11016), (2000, 2, 29));
+        assert_eq!(civil_from_days(19782), (2024, 2, 29));
+        assert_eq!(civil_from_days(19783), (2024, 3, 1));
+        assert_eq!(civil_from_days(20088), (2024, 12, 31));
+    }
+
+    #[test]
+    fn should_find_text_between_delimiters() {
+        let json = r#"{"url":"/th?id=a.jpg","title":"b"}"#;
+        assert_eq!(find_between(json, "\"url\":\"", "\""), Some("/th?id=a.jpg"));
+        assert_eq!(find_between(json, "\"title\":\"", "\""), Some("b"));
+        assert_eq!(find_between(json, "\"image\":\"", "\""), None);
+        assert_eq!(find_between(json, "\"url\":\"", "#"), None);
+    }
+
+    #[test]
+    fn only_local_feeds_should_read_local_files() {
+        assert!(Provider::parse("rss:/home/me/feed.xml").unwrap().is_local());
+        assert!(!Provider::parse("rss:https://example.com/feed")
+            .unwrap()
+            .is_local());
+        assert!(!Provider::parse("rss:http://example.com/feed")
+            .unwrap()
+            .is_local());
+        assert!(!Provider::parse("bing-potd").unwrap().is_local());
+
+        let dir = std::env::temp_dir().join(format!("swww-download-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (src, dest) = (dir.join("src"), dir.join("dest"));
+        std::fs::write(&src, b"image").unwrap();
+        let url = format!("file://{}", src.display());
+
+        let refused = download(&url, &dest, false);
+        let refused_exists = dest.exists();
+        let allowed = download(&url, &dest, true);
+        let copied = std::fs::read(&dest).ok();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(refused.is_err());
+        assert!(!refused_exists);
+        assert!(allowed.is_ok());
+        assert_eq!(copied.as_deref(), Some(&b"image"[..]));
+    }
+
+    #[test]
+    fn should_fall_back_to_the_latest_complete_image() {
+        let dir = std::env::temp_dir().join(format!("swww-providers-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in [
+            "bing-potd_2024-01-30",
+            "bing-potd_2024-02-01",
+            "bing-potd_2024-02-02.part",
+            "bing-potd-other_2025-01-01",
+            "wikimedia-potd_2025-01-01",
+        ] {
+            std::fs::write(dir.join(name), b"").unwrap();
+        }
+        let latest = latest_cached(&dir, "bing-potd");
+        let none = latest_cached(&dir, "rss-0123456789abcdef");
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(latest, Some(dir.join("bing-potd_2024-02-01")));
+        assert_eq!(none, None);
+    }
+}
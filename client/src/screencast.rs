@@ -0,0 +1,184 @@
+//! Implements `swww screencast-watch`.
+//!
+//! Like `now-playing`, we do not talk to dbus ourselves: we read the output of `dbus-monitor`,
+//! watching for the calls xdg-desktop-portal makes to its backend when a screencast session
+//! starts and closes.
+use std::{
+    collections::HashSet,
+    io::{BufRead, BufReader},
+    process::{Command, Stdio},
+};
+
+use common::cache;
+use common::ipc::{Answer, BgImg, IpcSocket, RequestSend};
+
+use crate::cli::{CliImage, ScreencastWatch, Swww};
+use crate::messages::msg;
+
+const SCREENCAST_START: &str = "interface=org.freedesktop.impl.portal.ScreenCast; member=Start";
+const SESSION_CLOSE: &str = "interface=org.freedesktop.impl.portal.Session; member=Close";
+const SESSION_CLOSED: &str = "interface=org.freedesktop.impl.portal.Session; member=Closed";
+
+pub fn watch(args: &ScreencastWatch) -> Result<(), String> {
+    if !args.pause && args.presentation.is_none() {
+        return Err(msg!("screencast-nothing-to-do"));
+    }
+
+    let mut child = Command::new("dbus-monitor")
+        .arg("--session")
+        .arg("type='method_call',interface='org.freedesktop.impl.portal.ScreenCast',member='Start'")
+        .arg("type='method_call',interface='org.freedesktop.impl.portal.Session',member='Close'")
+        .arg("type='signal',interface='org.freedesktop.impl.portal.Session',member='Closed'")
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| msg!("run-failed", program = "dbus-monitor", error = e))?;
+    let stdout = child.stdout.take().expect("we just piped stdout");
+
+    let mut sessions: HashSet<String> = HashSet::new();
+    // wallpapers displayed before the screencast started
+    let mut saved: Option<Vec<Saved>> = None;
+    // set after a `Start` call, since the session handle comes in the following lines
+    let mut expecting_session = false;
+
+    for line in BufReader::new(stdout).lines() {
+        let line = line.map_err(|e| msg!("read-dbus-monitor-failed", error = e))?;
+        let was_sharing = !sessions.is_empty();
+
+        if line.contains(SCREENCAST_START) {
+            expecting_session = true;
+        } else if line.contains(SESSION_CLOSE) || line.contains(SESSION_CLOSED) {
+            if let Some(path) = header_path(&line) {
+                sessions.remove(path);
+            }
+            expecting_session = false;
+        } else if expecting_session {
+            if let Some(path) = line
+                .trim()
+                .strip_prefix("object path \"")
+                .and_then(|s| s.strip_suffix('"'))
+                .filter(|s| s.contains("/session/"))
+            {
+                sessions.insert(path.to_string());
+                expecting_session = false;
+            }
+        }
+
+        match (was_sharing, !sessions.is_empty()) {
+            (false, true) => {
+                eprintln!("{}", msg!("screencast-started"));
+                saved = on_start(args)?;
+            }
+            (true, false) => {
+                eprintln!("{}", msg!("screencast-stopped"));
+                on_stop(args, saved.take())?;
+            }
+            _ => (),
+        }
+    }
+
+    let _ = child.wait();
+    Err(msg!("dbus-monitor-exited"))
+}
+
+/// the object path in a `dbus-monitor` header line
+fn header_path(line: &str) -> Option<&str> {
+    let rest = &line[line.find(" path=")? + 6..];
+    Some(&rest[..rest.find(';')?])
+}
+
+/// A wallpaper displayed before the screencast started
+struct Saved {
+    output: String,
+    image: String,
+    /// how the image was resized and filtered, which only the cache remembers
+    options: Vec<String>,
+}
+
+impl Saved {
+    /// Must be called before the presentation wallpaper overwrites `output`'s cache
+    fn new(output: String, img: BgImg) -> Self {
+        let image = match img {
+            BgImg::Color(c) => format!("0x{:02x}{:02x}{:02x}", c[0], c[1], c[2]),
+            BgImg::Img(path) => path,
+        };
+        let options = match cache::get_previous_image_path(&output) {
+            Ok(cached) if cached == image => {
+                cache::get_previous_image_options(&output).unwrap_or_default()
+            }
+            _ => Vec::new(),
+        };
+        Self {
+            output,
+            image,
+            options,
+        }
+    }
+}
+
+fn on_start(args: &ScreencastWatch) -> Result<Option<Vec<Saved>>, String> {
+    if args.pause {
+        set_pause(true)?;
+    }
+    let presentation = match &args.presentation {
+        Some(presentation) => presentation,
+        None => return Ok(None),
+    };
+
+    let saved = query()?
+        .into_iter()
+        .filter(|(name, _)| args.outputs.is_empty() || args.outputs.split(',').any(|o| o == name))
+        .map(|(output, img)| Saved::new(output, img))
+        .collect();
+    let image = match presentation {
+        CliImage::Path(path) => path.to_string_lossy().to_string(),
+        CliImage::Color(c) => format!("0x{:02x}{:02x}{:02x}", c[0], c[1], c[2]),
+        CliImage::Provider(provider) => format!("@{provider}"),
+    };
+    set_image(&image, &args.outputs, &[])?;
+    Ok(Some(saved))
+}
+
+fn on_stop(args: &ScreencastWatch, saved: Option<Vec<Saved>>) -> Result<(), String> {
+    if args.pause {
+        set_pause(false)?;
+    }
+    for saved in saved.into_iter().flatten() {
+        if let Err(e) = set_image(&saved.image, &saved.output, &saved.options) {
+            eprintln!(
+                "{}",
+                msg!("restore-wallpaper-failed", output = saved.output, error = e)
+            );
+        }
+    }
+    Ok(())
+}
+
+fn set_pause(paused: bool) -> Result<(), String> {
+    let socket = IpcSocket::connect().map_err(|err| err.to_string())?;
+    RequestSend::Pause(paused).send(&socket)?;
+    socket.recv().map_err(|err| err.to_string())?;
+    Ok(())
+}
+
+fn query() -> Result<Vec<(String, BgImg)>, String> {
+    let socket = IpcSocket::connect().map_err(|err| err.to_string())?;
+    RequestSend::Query.send(&socket)?;
+    let bytes = socket.recv().map_err(|err| err.to_string())?;
+    match Answer::receive(bytes) {
+        Answer::Info(infos) => Ok(infos
+            .iter()
+            .map(|info| (info.name.to_string(), info.img.clone()))
+            .collect()),
+        _ => Err(msg!("unexpected-answer", expected = "Answer::Info")),
+    }
+}
+
+fn set_image(image: &str, outputs: &str, options: &[String]) -> Result<(), String> {
+    // we parse the arguments instead of building the struct ourselves so that the user's
+    // SWWW_TRANSITION_* environment variables and transition defaults are respected
+    let args = ["img", image, "--outputs", outputs]
+        .into_iter()
+        .chain(options.iter().map(String::as_str));
+    let img = crate::with_transition_defaults(args)?;
+    crate::process_swww_args(&Swww::Img(img)).map_err(String::from)
+}
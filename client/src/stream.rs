@@ -0,0 +1,86 @@
+//! Implements `swww stream`.
+//!
+//! We share a ring of frames with the daemon (see `common::ipc::StreamRing`), and fill it with the
+//! frames we read from stdin, converted to the daemon's pixel format. When the daemon falls
+//! behind, it skips frames instead of making us wait, so reading stdin never stalls a live source.
+use std::io::{self, Read};
+
+use common::ipc::{Answer, IpcSocket, PixelFormat, StreamRing};
+
+use crate::{
+    cli::Stream,
+    error::{Error, ErrorKind},
+    messages::msg,
+};
+
+pub fn run(args: &Stream) -> Result<(), Error> {
+    let outputs = crate::split_cmdline_outputs(&args.outputs);
+    let (format, dims, _) = crate::get_format_dims_and_outputs(&outputs)?;
+    let dim = args.size.unwrap_or(dims[0]);
+    let mut ring = StreamRing::new(dim, format, args.slots, &outputs)
+        .map_err(|e| msg!("stream-failed", error = e))?;
+
+    let socket = IpcSocket::connect()?;
+    ring.request()
+        .map_err(|e| msg!("stream-failed", error = e))?
+        .send(&socket)?;
+    match Answer::receive(socket.recv()?) {
+        Answer::Ok => (),
+        Answer::Error(error) => {
+            return Err(Error::new(
+                ErrorKind::Refused,
+                msg!("daemon-refused", error = error),
+            ))
+        }
+        _ => return Err(msg!("unexpected-answer", expected = "Answer::Ok").into()),
+    }
+    drop(socket);
+
+    let mut stdin = io::stdin().lock();
+    let mut rgb = vec![0; dim.0 as usize * dim.1 as usize * 3];
+    loop {
+        match stdin.read_exact(&mut rgb) {
+            Ok(()) => (),
+            // a partial frame at the end is dropped
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(msg!("read-stdin-failed", error = e).into()),
+        }
+        let Some(frame) = ring.next_frame() else {
+            eprintln!("{}", msg!("stream-stopped"));
+            return Ok(());
+        };
+        convert(&rgb, frame, format);
+        ring.publish();
+    }
+}
+
+/// Converts packed RGB pixels to `format`
+fn convert(rgb: &[u8], frame: &mut [u8], format: PixelFormat) {
+    let channels = format.channels() as usize;
+    let swap = format.must_swap_r_and_b_channels();
+    for (src, dst) in rgb.chunks_exact(3).zip(frame.chunks_exact_mut(channels)) {
+        dst[..3].copy_from_slice(src);
+        if swap {
+            dst.swap(0, 2);
+        }
+        if channels == 4 {
+            dst[3] = 255;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_should_be_converted_to_the_daemons_format() {
+        let rgb = [1, 2, 3, 4, 5, 6];
+        let mut frame = [0; 8];
+        convert(&rgb, &mut frame, PixelFormat::Xrgb);
+        assert_eq!(frame, [3, 2, 1, 255, 6, 5, 4, 255]);
+        let mut frame = [0; 6];
+        convert(&rgb, &mut frame, PixelFormat::Bgr);
+        assert_eq!(frame, rgb);
+    }
+}
@@ -0,0 +1,65 @@
+//! SVG images, rasterized through resvg (the `svg` feature).
+//!
+//! Other images are decoded once and then resized for every output. SVGs are instead rasterized
+//! straight at the size of each output, so they stay sharp on outputs of any resolution.
+use image::{DynamicImage, RgbaImage};
+use resvg::{tiny_skia, usvg};
+
+use crate::{cli::ResizeStrategy, messages::msg};
+
+pub struct Svg {
+    tree: usvg::Tree,
+}
+
+impl Svg {
+    pub fn parse(bytes: &[u8]) -> Result<Self, String> {
+        let mut options = usvg::Options::default();
+        // for `<text>` elements
+        options.fontdb_mut().load_system_fonts();
+        usvg::Tree::from_data(bytes, &options)
+            .map(|tree| Self { tree })
+            .map_err(|e| msg!("svg-failed", error = e))
+    }
+
+    /// The size the image asks to be displayed at, rounded up
+    pub fn size(&self) -> (u32, u32) {
+        let size = self.tree.size().to_int_size();
+        (size.width(), size.height())
+    }
+
+    /// Rasterizes the image into `dim`, scaled like `resize` scales other images, and centered.
+    /// What it does not cover is `color`
+    pub fn render(
+        &self,
+        dim: (u32, u32),
+        resize: ResizeStrategy,
+        color: &[u8; 3],
+    ) -> Result<DynamicImage, String> {
+        let size = self.tree.size();
+        let (width, height) = (dim.0 as f32, dim.1 as f32);
+        let scale = match resize {
+            ResizeStrategy::No => 1.0,
+            ResizeStrategy::Crop => (width / size.width()).max(height / size.height()),
+            ResizeStrategy::Fit => (width / size.width()).min(height / size.height()),
+        };
+        let mut pixmap = tiny_skia::Pixmap::new(dim.0, dim.1)
+            .ok_or_else(|| msg!("svg-failed", error = "cannot render it at 0 pixels"))?;
+        pixmap.fill(tiny_skia::Color::from_rgba8(
+            color[0], color[1], color[2], 255,
+        ));
+        let transform = tiny_skia::Transform::from_row(
+            scale,
+            0.0,
+            0.0,
+            scale,
+            (width - size.width() * scale) / 2.0,
+            (height - size.height() * scale) / 2.0,
+        );
+        resvg::render(&self.tree, transform, &mut pixmap.as_mut());
+
+        // the background is opaque, so the premultiplied pixels are the same as straight ones
+        let img = RgbaImage::from_raw(dim.0, dim.1, pixmap.take())
+            .expect("the pixmap has exactly width * height pixels");
+        Ok(DynamicImage::ImageRgba8(img))
+    }
+}
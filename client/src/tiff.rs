@@ -0,0 +1,133 @@
+//! Multi-page TIFFs, through the `tiff` crate `image` itself decodes TIFFs with.
+//!
+//! `image` only decodes the first page of a TIFF, but scanners, fax software and some animation
+//! tools write several pages to one file. We decode the page picked with `--page`, or every page
+//! to play them as an animation. Pages are converted to the `DynamicImage` `image` would have
+//! given us, so they go through the same color conversion, resizing and compression.
+use std::io::Cursor;
+
+use ::tiff::{
+    decoder::{Decoder, DecodingResult},
+    ColorType, TiffError,
+};
+use image::{DynamicImage, ImageBuffer};
+
+use crate::messages::msg;
+
+/// How many pages the TIFF in `bytes` has
+pub fn page_count(bytes: &[u8]) -> Result<usize, String> {
+    let mut decoder = decoder(bytes)?;
+    let mut count = 1;
+    while decoder.more_images() {
+        decoder.next_image().map_err(failed)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// The page at `index`, counting from 0
+pub fn page(bytes: &[u8], index: usize) -> Result<DynamicImage, String> {
+    let mut decoder = decoder(bytes)?;
+    for _ in 0..index {
+        decoder.next_image().map_err(failed)?;
+    }
+    to_image(&mut decoder)
+}
+
+/// Every page, in order. Iteration stops after the first error
+pub fn pages(
+    bytes: &[u8],
+) -> Result<impl Iterator<Item = Result<DynamicImage, String>> + '_, String> {
+    let mut decoder = Some(decoder(bytes)?);
+    let mut first = true;
+    Ok(std::iter::from_fn(move || {
+        let current = decoder.as_mut()?;
+        if !std::mem::take(&mut first) {
+            if !current.more_images() {
+                return None;
+            }
+            if let Err(e) = current.next_image() {
+                decoder = None;
+                return Some(Err(failed(e)));
+            }
+        }
+        Some(to_image(current))
+    }))
+}
+
+fn decoder(bytes: &[u8]) -> Result<Decoder<Cursor<&[u8]>>, String> {
+    Decoder::new(Cursor::new(bytes)).map_err(failed)
+}
+
+fn failed(e: TiffError) -> String {
+    msg!("tiff-failed", error = e)
+}
+
+/// Decodes the current page of `decoder`
+fn to_image(decoder: &mut Decoder<Cursor<&[u8]>>) -> Result<DynamicImage, String> {
+    let (width, height) = decoder.dimensions().map_err(failed)?;
+    let color = decoder.colortype().map_err(failed)?;
+    let image = match (color, decoder.read_image().map_err(failed)?) {
+        (ColorType::Gray(8), DecodingResult::U8(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLuma8)
+        }
+        (ColorType::GrayA(8), DecodingResult::U8(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLumaA8)
+        }
+        (ColorType::RGB(8), DecodingResult::U8(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgb8)
+        }
+        (ColorType::RGBA(8), DecodingResult::U8(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgba8)
+        }
+        (ColorType::Gray(16), DecodingResult::U16(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLuma16)
+        }
+        (ColorType::GrayA(16), DecodingResult::U16(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLumaA16)
+        }
+        (ColorType::RGB(16), DecodingResult::U16(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgb16)
+        }
+        (ColorType::RGBA(16), DecodingResult::U16(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgba16)
+        }
+        (ColorType::RGB(32), DecodingResult::F32(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgb32F)
+        }
+        (ColorType::RGBA(32), DecodingResult::F32(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgba32F)
+        }
+        (ColorType::CMYK(8), DecodingResult::U8(data)) => {
+            ImageBuffer::from_raw(width, height, cmyk_to_rgb(&data)).map(DynamicImage::ImageRgb8)
+        }
+        (color, _) => return Err(msg!("tiff-unsupported", color = format!("{color:?}"))),
+    };
+    image.ok_or_else(|| msg!("tiff-truncated"))
+}
+
+/// The same naive conversion `image` does, since TIFFs rarely say how their inks mix
+fn cmyk_to_rgb(cmyk: &[u8]) -> Vec<u8> {
+    cmyk.chunks_exact(4)
+        .flat_map(|pixel| {
+            let k = 255 - u16::from(pixel[3]);
+            let ink = move |c: u8| ((255 - u16::from(c)) * k / 255) as u8;
+            [ink(pixel[0]), ink(pixel[1]), ink(pixel[2])]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cmyk_should_convert_to_rgb() {
+        assert_eq!(cmyk_to_rgb(&[0, 0, 0, 0]), [255, 255, 255]);
+        assert_eq!(cmyk_to_rgb(&[0, 0, 0, 255]), [0, 0, 0]);
+        assert_eq!(
+            cmyk_to_rgb(&[255, 0, 255, 0, 0, 0, 0, 128]),
+            [0, 255, 0, 127, 127, 127]
+        );
+    }
+}
@@ -0,0 +1,480 @@
+//! Time of day wallpapers: the dynamic HEIC wallpapers made for macOS.
+//!
+//! The XMP metadata of their primary image has a base64 encoded binary property list, under
+//! `apple_desktop:h24` or `apple_desktop:solar`. The first gives the time of day each image starts
+//! at. The second gives where the sun is in each image, which we turn into times of day for today
+//! at `--location`. Either way, the images are sent to the daemon as an animation whose frames
+//! last as long as their part of the day, starting with the one for now, and the daemon switches
+//! between them by the wall clock (see `ipc::Animation::day_start`).
+use std::{process::Command, time::Duration};
+
+use crate::messages::msg;
+
+const DAY: u32 = 24 * 60 * 60;
+
+/// How often we look at where the sun is, for wallpapers that follow it
+const SUN_STEP: u32 = 5 * 60;
+
+/// The part of the day each image is shown in
+pub struct Schedule {
+    /// when the first part starts, in seconds after local midnight. It is the one for now
+    pub start: u32,
+    /// the index of the image shown in each part, in order, and for how long. They add up to a day
+    pub slots: Vec<(usize, Duration)>,
+}
+
+impl Schedule {
+    /// The schedule in `xmp`, if it has one. `images` is how many images the file has
+    pub fn from_xmp(
+        xmp: &str,
+        images: usize,
+        location: Option<(f64, f64)>,
+    ) -> Result<Option<Self>, String> {
+        let Some(positions) = read(xmp)? else {
+            return Ok(None);
+        };
+        let now = LocalTime::now()?;
+        let mut changes = match positions {
+            Positions::Times(times) => times
+                .into_iter()
+                .map(|(fraction, image)| ((fraction.rem_euclid(1.0) * DAY as f64) as u32, image))
+                .collect(),
+            Positions::Sun(sun) => sun_changes(&sun, &now, location),
+        };
+        changes.retain(|&(_, image)| image < images);
+        Ok(schedule(changes, now.seconds))
+    }
+}
+
+/// Where the images of a time of day wallpaper go, with their indices
+#[derive(Debug, PartialEq)]
+enum Positions {
+    /// at a fraction of the day
+    Times(Vec<(f64, usize)>),
+    /// at an altitude and azimuth of the sun, in degrees
+    Sun(Vec<(f64, f64, usize)>),
+}
+
+/// The positions in `xmp`, if it has any
+fn read(xmp: &str) -> Result<Option<Positions>, String> {
+    let invalid = |error: &str| msg!("time-of-day-invalid", error = error);
+    let plist = |encoded: &str| {
+        let bytes = base64(encoded).ok_or_else(|| invalid("it is not base64"))?;
+        Plist::parse(&bytes).ok_or_else(|| invalid("it is not a binary property list"))
+    };
+    let entries = |plist: &Plist, key: &str| match plist.get(key) {
+        Some(Plist::Array(entries)) => Ok(entries.clone()),
+        _ => Err(invalid(&format!("it has no '{key}' list"))),
+    };
+    let index = |entry: &Plist| {
+        let index = entry.get("i").and_then(Plist::as_f64)?;
+        (index >= 0.0).then_some(index as usize)
+    };
+
+    if let Some(h24) = property(xmp, "apple_desktop:h24") {
+        let times = entries(&plist(h24)?, "ti")?
+            .iter()
+            .filter_map(|entry| Some((entry.get("t")?.as_f64()?, index(entry)?)))
+            .collect();
+        Ok(Some(Positions::Times(times)))
+    } else if let Some(solar) = property(xmp, "apple_desktop:solar") {
+        let sun = entries(&plist(solar)?, "si")?
+            .iter()
+            .filter_map(|entry| {
+                let altitude = entry.get("a")?.as_f64()?;
+                let azimuth = entry.get("z")?.as_f64()?;
+                Some((altitude, azimuth, index(entry)?))
+            })
+            .collect();
+        Ok(Some(Positions::Sun(sun)))
+    } else {
+        Ok(None)
+    }
+}
+
+/// The value of an XMP property, given either as an attribute or as an element
+fn property<'a>(xmp: &'a str, name: &str) -> Option<&'a str> {
+    if let Some(i) = xmp.find(&format!("{name}=")) {
+        let rest = &xmp[i + name.len() + 1..];
+        let quote = rest.chars().next().filter(|&c| c == '"' || c == '\'')?;
+        let rest = &rest[1..];
+        return Some(&rest[..rest.find(quote)?]);
+    }
+    let open = format!("<{name}>");
+    let rest = &xmp[xmp.find(&open)? + open.len()..];
+    Some(&rest[..rest.find('<')?])
+}
+
+fn base64(encoded: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(encoded.len() * 3 / 4);
+    let (mut bits, mut len) = (0u32, 0);
+    for c in encoded.bytes().filter(|c| !c.is_ascii_whitespace()) {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            b'=' => break,
+            _ => return None,
+        };
+        bits = bits << 6 | value as u32;
+        len += 6;
+        if len >= 8 {
+            len -= 8;
+            bytes.push((bits >> len) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+/// The parts of a binary property list we care about
+#[derive(Clone, Debug, PartialEq)]
+enum Plist {
+    Int(i64),
+    Real(f64),
+    Str(String),
+    Array(Vec<Plist>),
+    Dict(Vec<(String, Plist)>),
+    Other,
+}
+
+impl Plist {
+    fn parse(bytes: &[u8]) -> Option<Self> {
+        if !bytes.starts_with(b"bplist00") || bytes.len() < 40 {
+            return None;
+        }
+        let trailer = &bytes[bytes.len() - 32..];
+        let reader = PlistReader {
+            bytes,
+            offset_size: trailer[6] as usize,
+            ref_size: trailer[7] as usize,
+            objects: be(&trailer[8..16])? as usize,
+            table: be(&trailer[24..32])? as usize,
+        };
+        reader.object(be(&trailer[16..24])? as usize, 0)
+    }
+
+    fn get(&self, key: &str) -> Option<&Self> {
+        match self {
+            Self::Dict(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Self::Int(n) => Some(n as f64),
+            Self::Real(n) => Some(n),
+            _ => None,
+        }
+    }
+}
+
+/// A big endian unsigned integer of up to 8 bytes
+fn be(bytes: &[u8]) -> Option<u64> {
+    (bytes.len() <= 8).then(|| bytes.iter().fold(0, |n, &b| n << 8 | b as u64))
+}
+
+struct PlistReader<'a> {
+    bytes: &'a [u8],
+    offset_size: usize,
+    ref_size: usize,
+    objects: usize,
+    table: usize,
+}
+
+impl PlistReader<'_> {
+    /// Property lists can refer to their own containers, so we stop this deep
+    const MAX_DEPTH: u8 = 16;
+
+    fn int(&self, at: usize, size: usize) -> Option<usize> {
+        be(self.bytes.get(at..at.checked_add(size)?)?).map(|n| n as usize)
+    }
+
+    fn object(&self, object: usize, depth: u8) -> Option<Plist> {
+        if object >= self.objects || depth > Self::MAX_DEPTH {
+            return None;
+        }
+        let entry = object
+            .checked_mul(self.offset_size)?
+            .checked_add(self.table)?;
+        let at = self.int(entry, self.offset_size)?;
+        let marker = *self.bytes.get(at)?;
+        let info = (marker & 0xf) as usize;
+        Some(match marker >> 4 {
+            0x1 => Plist::Int(self.int(at + 1, 1 << info)? as i64),
+            0x2 if info == 2 => {
+                let bytes = self.bytes.get(at + 1..at + 5)?;
+                Plist::Real(f32::from_be_bytes(bytes.try_into().unwrap()) as f64)
+            }
+            0x2 if info == 3 => {
+                let bytes = self.bytes.get(at + 1..at + 9)?;
+                Plist::Real(f64::from_be_bytes(bytes.try_into().unwrap()))
+            }
+            0x5 => {
+                let (len, start) = self.count(at, info)?;
+                let bytes = self.bytes.get(start..start.checked_add(len)?)?;
+                Plist::Str(String::from_utf8_lossy(bytes).into_owned())
+            }
+            0x6 => {
+                let (len, start) = self.count(at, info)?;
+                let bytes = self
+                    .bytes
+                    .get(start..start.checked_add(len.checked_mul(2)?)?)?;
+                let units: Vec<u16> = bytes
+                    .chunks_exact(2)
+                    .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+                    .collect();
+                Plist::Str(String::from_utf16_lossy(&units))
+            }
+            0xa => {
+                let (len, start) = self.count(at, info)?;
+                let mut items = Vec::with_capacity(len.min(self.objects));
+                for i in 0..len {
+                    let item = self.int(start + i * self.ref_size, self.ref_size)?;
+                    items.push(self.object(item, depth + 1)?);
+                }
+                Plist::Array(items)
+            }
+            0xd => {
+                let (len, start) = self.count(at, info)?;
+                let mut entries = Vec::with_capacity(len.min(self.objects));
+                for i in 0..len {
+                    let key = self.int(start + i * self.ref_size, self.ref_size)?;
+                    let value = self.int(start + (len + i) * self.ref_size, self.ref_size)?;
+                    let Plist::Str(key) = self.object(key, depth + 1)? else {
+                        return None;
+                    };
+                    entries.push((key, self.object(value, depth + 1)?));
+                }
+                Plist::Dict(entries)
+            }
+            _ => Plist::Other,
+        })
+    }
+
+    /// How many items the object at `at` has, and where they start. Counts of 15 or more follow
+    /// the marker as an integer
+    fn count(&self, at: usize, info: usize) -> Option<(usize, usize)> {
+        if info != 0xf {
+            return Some((info, at + 1));
+        }
+        let marker = *self.bytes.get(at + 1)?;
+        if marker >> 4 != 0x1 {
+            return None;
+        }
+        let size = 1 << (marker & 0xf);
+        Some((self.int(at + 2, size)?, at + 2 + size))
+    }
+}
+
+/// The local date and time, as far as we need them
+struct LocalTime {
+    /// 1 for January 1st
+    day_of_year: u32,
+    /// seconds ahead of UTC
+    utc_offset: i32,
+    /// seconds after midnight
+    seconds: u32,
+}
+
+impl LocalTime {
+    fn now() -> Result<Self, String> {
+        let output = Command::new("date")
+            .arg("+%j %z %H:%M:%S")
+            .output()
+            .map_err(|e| msg!("run-failed", program = "date", error = e))?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        Self::parse(stdout.trim())
+            .ok_or_else(|| msg!("unexpected-date-output", output = stdout.trim()))
+    }
+
+    /// Parses `date +"%j %z %H:%M:%S"`
+    fn parse(date: &str) -> Option<Self> {
+        let mut parts = date.split(' ');
+        let day_of_year = parts.next()?.parse().ok()?;
+        let offset = parts.next()?;
+        let mut time = parts.next()?.split(':').map(|n| n.parse::<u32>().ok());
+        let (hours, minutes, seconds) = (time.next()??, time.next()??, time.next()??);
+
+        let sign = match offset.get(..1)? {
+            "+" => 1,
+            "-" => -1,
+            _ => return None,
+        };
+        let offset_hours: i32 = offset.get(1..3)?.parse().ok()?;
+        let offset_minutes: i32 = offset.get(3..5)?.parse().ok()?;
+        Some(Self {
+            day_of_year,
+            utc_offset: sign * (offset_hours * 3600 + offset_minutes * 60),
+            seconds: (hours * 3600 + minutes * 60 + seconds).min(DAY - 1),
+        })
+    }
+}
+
+/// The image closest to the sun every `SUN_STEP` of today, as (seconds after midnight, image).
+/// Without a location, we guess the longitude from the time zone
+fn sun_changes(
+    sun: &[(f64, f64, usize)],
+    now: &LocalTime,
+    location: Option<(f64, f64)>,
+) -> Vec<(u32, usize)> {
+    let (latitude, longitude) = location.unwrap_or((45.0, now.utc_offset as f64 / 3600.0 * 15.0));
+    (0..DAY)
+        .step_by(SUN_STEP as usize)
+        .filter_map(|second| {
+            let utc_minutes = (second as f64 - now.utc_offset as f64) / 60.0;
+            let (altitude, azimuth) =
+                sun_position(now.day_of_year, utc_minutes, latitude, longitude);
+            let closest = sun.iter().max_by(|a, b| {
+                let a = angular_closeness((altitude, azimuth), (a.0, a.1));
+                let b = angular_closeness((altitude, azimuth), (b.0, b.1));
+                a.total_cmp(&b)
+            })?;
+            Some((second, closest.2))
+        })
+        .collect()
+}
+
+/// The cosine of the angle between two directions given as altitude and azimuth, in degrees
+fn angular_closeness(a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (alt_a, alt_b) = (a.0.to_radians(), b.0.to_radians());
+    alt_a.sin() * alt_b.sin() + alt_a.cos() * alt_b.cos() * (a.1 - b.1).to_radians().cos()
+}
+
+/// The altitude and azimuth (clockwise from north) of the sun, in degrees, `utc_minutes` after
+/// the UTC midnight of `day_of_year`, following NOAA's general solar position formulas
+fn sun_position(day_of_year: u32, utc_minutes: f64, latitude: f64, longitude: f64) -> (f64, f64) {
+    use std::f64::consts::PI;
+    let year = 2.0 * PI / 365.0 * (day_of_year as f64 - 1.0 + (utc_minutes / 60.0 - 12.0) / 24.0);
+    let equation_of_time = 229.18
+        * (0.000075 + 0.001868 * year.cos()
+            - 0.032077 * year.sin()
+            - 0.014615 * (2.0 * year).cos()
+            - 0.040849 * (2.0 * year).sin());
+    let declination = 0.006918 - 0.399912 * year.cos() + 0.070257 * year.sin()
+        - 0.006758 * (2.0 * year).cos()
+        + 0.000907 * (2.0 * year).sin()
+        - 0.002697 * (3.0 * year).cos()
+        + 0.00148 * (3.0 * year).sin();
+
+    let solar_minutes = utc_minutes + equation_of_time + 4.0 * longitude;
+    let hour_angle = (solar_minutes / 4.0 - 180.0).to_radians();
+    let latitude = latitude.to_radians();
+    let altitude = (latitude.sin() * declination.sin()
+        + latitude.cos() * declination.cos() * hour_angle.cos())
+    .clamp(-1.0, 1.0)
+    .asin();
+    let azimuth = hour_angle
+        .sin()
+        .atan2(hour_angle.cos() * latitude.sin() - declination.tan() * latitude.cos())
+        + PI;
+    (
+        altitude.to_degrees(),
+        azimuth.to_degrees().rem_euclid(360.0),
+    )
+}
+
+/// Turns the times at which each image starts, as (seconds after midnight, image), into the
+/// parts of the day they take, starting with the one `now` is in. `None` if there is only one
+fn schedule(mut changes: Vec<(u32, usize)>, now: u32) -> Option<Schedule> {
+    changes.sort_by_key(|&(second, _)| second);
+    changes.dedup_by_key(|&mut (second, _)| second);
+    // consecutive changes to the same image are one part of the day
+    changes.dedup_by(|next, previous| next.1 == previous.1);
+    if changes.len() > 1 && changes[0].1 == changes[changes.len() - 1].1 {
+        // the last part goes on past midnight
+        changes.remove(0);
+    }
+    if changes.len() < 2 {
+        return None;
+    }
+
+    let len = changes.len();
+    let parts: Vec<(u32, usize, u32)> = (0..len)
+        .map(|i| {
+            let (start, image) = changes[i];
+            let end = changes[(i + 1) % len].0;
+            (start, image, (end + DAY - start) % DAY)
+        })
+        .collect();
+    let current = parts
+        .iter()
+        .position(|&(start, _, duration)| (now + DAY - start) % DAY < duration)
+        .unwrap_or(0);
+    Some(Schedule {
+        start: parts[current].0,
+        slots: (0..len)
+            .map(|i| {
+                let (_, image, duration) = parts[(current + i) % len];
+                (image, Duration::from_secs(duration.into()))
+            })
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // made with python's plistlib
+    const H24: &str = "YnBsaXN0MDDSAQIDCFJhcFJ0adIEBQYHUWRRbBABEACiCQ3SCgsGDFFpUXQjP+gAAAAAAADSCgsHDiM/0AAAAAAAAAgNEBMYGhweICMoKiw1OgAAAAAAAAEBAAAAAAAAAA8AAAAAAAAAAAAAAAAAAABD";
+    const SOLAR: &str = "YnBsaXN0MDDRAQJSc2miAwrTBAUGBwgJUWFRaVF6I8A+gAAAAAAAEAAjQCQAAAAAAADTBAUGCwwNI0BGgAAAAAAAEAEjQGaAAAAAAAAICw4RGBocHicpMjlCRAAAAAAAAAEBAAAAAAAAAA4AAAAAAAAAAAAAAAAAAABN";
+
+    #[test]
+    fn should_read_times_and_sun_positions_from_xmp() {
+        let attribute = format!("<rdf:Description apple_desktop:h24=\"{H24}\"/>");
+        assert_eq!(
+            read(&attribute).unwrap(),
+            Some(Positions::Times(vec![(0.75, 1), (0.25, 0)]))
+        );
+        let element = format!("<apple_desktop:solar>{SOLAR}</apple_desktop:solar>");
+        assert_eq!(
+            read(&element).unwrap(),
+            Some(Positions::Sun(vec![(-30.5, 10.0, 0), (45.0, 180.0, 1)]))
+        );
+        assert_eq!(read("<rdf:Description/>").unwrap(), None);
+        assert!(read("<x apple_desktop:h24=\"bm90IGEgcGxpc3Q=\"/>").is_err());
+    }
+
+    #[test]
+    fn schedules_should_start_with_the_part_of_the_day_for_now() {
+        let hour = |h: u64| Duration::from_secs(h * 3600);
+        // day from 6:00, evening from 18:00 and night from 21:00 until 6:00
+        let changes = vec![(18 * 3600, 1), (6 * 3600, 0), (21 * 3600, 2)];
+        let at_noon = schedule(changes.clone(), 12 * 3600).unwrap();
+        assert_eq!(at_noon.start, 6 * 3600);
+        assert_eq!(at_noon.slots, [(0, hour(12)), (1, hour(3)), (2, hour(9))]);
+        let at_two = schedule(changes, 2 * 3600).unwrap();
+        assert_eq!(at_two.start, 21 * 3600);
+        assert_eq!(at_two.slots, [(2, hour(9)), (0, hour(12)), (1, hour(3))]);
+
+        // sampled every hour, with the night going over midnight
+        let sampled = (0..24).map(|h| (h * 3600, usize::from(!(6..20).contains(&h))));
+        let sampled = schedule(sampled.collect(), 12 * 3600).unwrap();
+        assert_eq!(sampled.start, 6 * 3600);
+        assert_eq!(sampled.slots, [(0, hour(14)), (1, hour(10))]);
+
+        assert!(schedule(vec![(0, 0), (3600, 0)], 0).is_none());
+    }
+
+    #[test]
+    fn the_sun_should_be_up_at_noon_and_down_at_midnight() {
+        // the March equinox, on the equator
+        let (altitude, _) = sun_position(79, 12.0 * 60.0, 0.0, 0.0);
+        assert!(altitude > 85.0, "{altitude}");
+        let (altitude, _) = sun_position(79, 0.0, 0.0, 0.0);
+        assert!(altitude < -85.0, "{altitude}");
+        // the morning sun in Berlin is in the east
+        let (altitude, azimuth) = sun_position(172, 6.0 * 60.0, 52.5, 13.4);
+        assert!(
+            altitude > 20.0 && (60.0..120.0).contains(&azimuth),
+            "{altitude} {azimuth}"
+        );
+
+        let time = LocalTime::parse("172 -0330 08:15:00").unwrap();
+        assert_eq!(time.utc_offset, -(3 * 3600 + 30 * 60));
+        assert_eq!(time.seconds, 8 * 3600 + 15 * 60);
+    }
+}
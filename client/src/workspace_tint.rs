@@ -0,0 +1,260 @@
+//! Implements `swww workspace-tint`.
+//!
+//! We only use the compositors' events to know when something changed. The workspace each output
+//! is showing is then read from the text output of `hyprctl monitors` or `swaymsg -t
+//! get_workspaces`, so we do not have to parse json. Hyprland writes its events to a socket,
+//! which we read ourselves, while for Sway we read the output of `swaymsg -t subscribe`.
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader},
+    os::unix::net::UnixStream,
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+use common::ipc::{Answer, IpcSocket, RequestSend};
+
+use crate::cli::{self, ColorFilterType, Compositor, Swww, WorkspaceTint};
+use crate::messages::msg;
+
+/// The Hyprland events after which an output may be showing another workspace
+const HYPRLAND_EVENTS: [&str; 5] = [
+    "workspace>>",
+    "focusedmon>>",
+    "moveworkspace>>",
+    "renameworkspace>>",
+    "monitoradded>>",
+];
+
+pub fn watch(args: &WorkspaceTint) -> Result<(), String> {
+    if args.tint.is_empty() && args.filter.is_empty() {
+        return Err(msg!("workspace-tint-nothing-to-do"));
+    }
+    let compositor = match args.compositor {
+        Compositor::Auto if std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some() => {
+            Compositor::Hyprland
+        }
+        Compositor::Auto if std::env::var_os("SWAYSOCK").is_some() => Compositor::Sway,
+        Compositor::Auto => return Err(msg!("unknown-compositor")),
+        compositor => compositor,
+    };
+
+    // the workspace we last tinted each output for
+    let mut tinted: HashMap<String, String> = HashMap::new();
+    let mut update = || -> Result<(), String> {
+        for (output, workspace) in workspaces(compositor)? {
+            if tinted.get(&output) == Some(&workspace) {
+                continue;
+            }
+            if let Err(e) = tint(args, &output, &workspace) {
+                eprintln!(
+                    "{}",
+                    msg!("workspace-tint-failed", output = output, error = e)
+                );
+            }
+            tinted.insert(output, workspace);
+        }
+        Ok(())
+    };
+    update()?;
+
+    match compositor {
+        Compositor::Hyprland => {
+            let path = hyprland_socket()?;
+            let socket = UnixStream::connect(&path).map_err(|e| {
+                msg!(
+                    "connect-compositor-failed",
+                    path = path.display(),
+                    error = e
+                )
+            })?;
+            for line in BufReader::new(socket).lines() {
+                let line = line.map_err(|e| msg!("read-compositor-events-failed", error = e))?;
+                if HYPRLAND_EVENTS.iter().any(|event| line.starts_with(event)) {
+                    update()?;
+                }
+            }
+        }
+        _ => {
+            let mut child = Command::new("swaymsg")
+                .args(["-t", "subscribe", "-m", r#"["workspace", "output"]"#])
+                .stdout(Stdio::piped())
+                .spawn()
+                .map_err(|e| msg!("run-failed", program = "swaymsg", error = e))?;
+            let stdout = child.stdout.take().expect("we just piped stdout");
+            for line in BufReader::new(stdout).lines() {
+                let line = line.map_err(|e| msg!("read-compositor-events-failed", error = e))?;
+                // every event is a json object, and only theirs start at the beginning of a line
+                if line.starts_with('{') {
+                    update()?;
+                }
+            }
+            let _ = child.wait();
+        }
+    }
+    Err(msg!("compositor-events-ended"))
+}
+
+/// Where Hyprland writes its events. Older versions kept it in /tmp
+fn hyprland_socket() -> Result<PathBuf, String> {
+    let signature =
+        std::env::var("HYPRLAND_INSTANCE_SIGNATURE").map_err(|_| msg!("no-hyprland-signature"))?;
+    let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .map(|dir| dir.join("hypr").join(&signature).join(".socket2.sock"));
+    Ok(runtime_dir
+        .filter(|path| path.exists())
+        .unwrap_or_else(|| PathBuf::from(format!("/tmp/hypr/{signature}/.socket2.sock"))))
+}
+
+/// The workspace each output is showing, by name
+fn workspaces(compositor: Compositor) -> Result<Vec<(String, String)>, String> {
+    let (program, args): (&str, &[&str]) = match compositor {
+        Compositor::Hyprland => ("hyprctl", &["monitors"]),
+        _ => ("swaymsg", &["-t", "get_workspaces"]),
+    };
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| msg!("run-failed", program = program, error = e))?;
+    if !output.status.success() {
+        return Err(msg!(
+            "command-failed",
+            program = program,
+            status = output.status
+        ));
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(match compositor {
+        Compositor::Hyprland => parse_hyprctl_monitors(&stdout),
+        _ => parse_sway_workspaces(&stdout),
+    })
+}
+
+/// Parses blocks like:
+///
+/// ```text
+/// Monitor DP-1 (ID 0):
+///         2560x1440@143.99800 at 0x0
+///         active workspace: 2 (web)
+/// ```
+fn parse_hyprctl_monitors(text: &str) -> Vec<(String, String)> {
+    let mut workspaces = Vec::new();
+    let mut monitor = None;
+    for line in text.lines() {
+        if let Some(rest) = line.strip_prefix("Monitor ") {
+            monitor = rest.split_whitespace().next().map(str::to_string);
+        } else if let Some(rest) = line.trim().strip_prefix("active workspace: ") {
+            let name = rest
+                .split_once(" (")
+                .and_then(|(_, name)| name.strip_suffix(')'));
+            if let (Some(monitor), Some(name)) = (monitor.take(), name) {
+                workspaces.push((monitor, name.to_string()));
+            }
+        }
+    }
+    workspaces
+}
+
+/// Parses blocks like the following, skipping the workspaces marked as off-screen:
+///
+/// ```text
+/// Workspace 2 (focused)
+///   Output: DP-1
+/// ```
+fn parse_sway_workspaces(text: &str) -> Vec<(String, String)> {
+    let mut workspaces = Vec::new();
+    let mut workspace = None;
+    for line in text.lines() {
+        if let Some(mut name) = line.strip_prefix("Workspace ") {
+            let mut visible = true;
+            for flag in [" (urgent)", " (off-screen)", " (focused)"] {
+                if let Some(rest) = name.strip_suffix(flag) {
+                    name = rest;
+                    visible &= flag != " (off-screen)";
+                }
+            }
+            workspace = visible.then(|| name.to_string());
+        } else if let Some(output) = line.trim().strip_prefix("Output: ") {
+            if let Some(name) = workspace.take() {
+                workspaces.push((output.to_string(), name));
+            }
+        }
+    }
+    workspaces
+}
+
+/// Applies the tint and filter of `workspace` to `output`, in a single request
+fn tint(args: &WorkspaceTint, output: &str, workspace: &str) -> Result<(), String> {
+    let mut commands = Vec::new();
+    if !args.tint.is_empty() {
+        let (color, amount) = args
+            .tint
+            .iter()
+            .find(|(name, _, _)| name == workspace)
+            .map_or(([0, 0, 0], 0.0), |(_, color, amount)| (*color, *amount));
+        commands.push(Swww::Dim(cli::Dim {
+            outputs: output.to_string(),
+            amount,
+            color,
+        }));
+    }
+    if !args.filter.is_empty() {
+        let filter = args
+            .filter
+            .iter()
+            .find(|(name, _)| name == workspace)
+            .map_or(ColorFilterType::None, |(_, filter)| *filter);
+        commands.push(Swww::ColorFilter(cli::ColorFilter {
+            filter,
+            outputs: output.to_string(),
+        }));
+    }
+
+    let mut requests = Vec::new();
+    for command in &commands {
+        requests.extend(crate::make_request(command).map_err(String::from)?);
+    }
+    let socket = IpcSocket::connect().map_err(|err| err.to_string())?;
+    RequestSend::batch(requests).send(&socket)?;
+    let bytes = socket.recv().map_err(|err| err.to_string())?;
+    match Answer::receive(bytes) {
+        Answer::Ok => Ok(()),
+        Answer::Error(e) => Err(e),
+        _ => Err(msg!("unexpected-answer", expected = "Answer::Ok")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_parse_hyprctl_monitors() {
+        let text =
+            "Monitor DP-1 (ID 0):\n\t2560x1440@143.99800 at 0x0\n\tactive workspace: 2 (web)\n\
+                    \tspecial workspace: 0 ()\n\nMonitor HDMI-A-1 (ID 1):\n\
+                    \tactive workspace: 5 (5)\n";
+        assert_eq!(
+            parse_hyprctl_monitors(text),
+            [
+                ("DP-1".to_string(), "web".to_string()),
+                ("HDMI-A-1".to_string(), "5".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn should_skip_off_screen_sway_workspaces() {
+        let text = "Workspace 1 (off-screen)\n  Output: DP-1\n  Layout: splith\n\n\
+                    Workspace 2 (focused)\n  Output: DP-1\n\n\
+                    Workspace my mail (urgent)\n  Output: HDMI-A-1\n";
+        assert_eq!(
+            parse_sway_workspaces(text),
+            [
+                ("DP-1".to_string(), "2".to_string()),
+                ("HDMI-A-1".to_string(), "my mail".to_string())
+            ]
+        );
+    }
+}
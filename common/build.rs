@@ -3,4 +3,9 @@ fn main() {
         .atleast_version("1.8")
         .probe("liblz4")
         .unwrap();
+    #[cfg(feature = "zstd")]
+    pkg_config::Config::new()
+        .atleast_version("1.4")
+        .probe("libzstd")
+        .unwrap();
 }
@@ -3,21 +3,53 @@
 //! The idea is:
 //!   1. the client registers the last image sent for each output in a file
 //!   2. the daemon spawns a client that reloads that image when an output is created
+//!
+//! Animation frames are cached too, so we do not have to decode and compress them again. Those
+//! files can take hundreds of megabytes, so they may be compressed again with zstd (`swww img
+//! --cache-compress`, behind the `zstd` feature). We recognize compressed files when loading them,
+//! so both kinds can live in the same cache.
 
+#[cfg(feature = "zstd")]
+use std::ffi::c_int;
 use std::{
     fs::File,
     io::{self, Read, Write},
     path::{Path, PathBuf},
 };
 
+#[cfg(feature = "zstd")]
+use crate::compression::zstd;
 use crate::ipc::Animation;
 use crate::ipc::PixelFormat;
 use crate::mmap::Mmap;
 
-pub(crate) fn store(output_name: &str, img_path: &str) -> io::Result<()> {
+/// Compressed animation files start with this. Then comes the number of blocks and the length of
+/// the whole animation, followed by a table with the compressed and decompressed length of each
+/// block, so every block can be decompressed straight into its place
+const COMPRESSED_MAGIC: &[u8; 8] = b"swwwzstd";
+
+#[cfg(not(feature = "zstd"))]
+const NO_ZSTD: &str =
+    "compressed animations need zstd, and swww was compiled without the `zstd` feature";
+
+/// How many bytes of the animation go into each compressed block
+#[cfg(feature = "zstd")]
+const BLOCK_LEN: usize = 4 << 20;
+
+#[cfg(feature = "zstd")]
+const ZSTD_LEVEL: c_int = 3;
+
+/// Remembers `output_name`'s image, followed by the `swww img` options it was set with, one per
+/// line
+pub(crate) fn store(output_name: &str, img_path: &str, options: &[String]) -> io::Result<()> {
     let mut filepath = cache_dir()?;
     filepath.push(output_name);
-    File::create(filepath)?.write_all(img_path.as_bytes())
+    let mut contents = img_path.to_string();
+    for option in options {
+        contents.push('\n');
+        contents.push_str(option);
+    }
+    File::create(filepath)?.write_all(contents.as_bytes())
 }
 
 pub(crate) fn store_animation_frames(
@@ -25,24 +57,92 @@ pub(crate) fn store_animation_frames(
     path: &Path,
     dimensions: (u32, u32),
     pixel_format: PixelFormat,
+    key: &str,
+    compress: bool,
 ) -> io::Result<()> {
-    let filename = animation_filename(path, dimensions, pixel_format);
+    let filename = animation_filename(path, dimensions, pixel_format, key);
     let mut filepath = cache_dir()?;
     filepath.push(&filename);
 
-    if !filepath.is_file() {
-        File::create(filepath)?.write_all(animation)
-    } else {
+    if filepath.is_file() {
         Ok(())
+    } else if compress {
+        File::create(filepath)?.write_all(&compress_animation(animation)?)
+    } else {
+        File::create(filepath)?.write_all(animation)
+    }
+}
+
+#[cfg(not(feature = "zstd"))]
+fn compress_animation(_: &[u8]) -> io::Result<Vec<u8>> {
+    Err(io::Error::other(NO_ZSTD))
+}
+
+#[cfg(feature = "zstd")]
+fn compress_animation(animation: &[u8]) -> io::Result<Vec<u8>> {
+    let blocks = animation.len().div_ceil(BLOCK_LEN);
+    let mut bytes = Vec::with_capacity(20 + blocks * 8);
+    bytes.extend_from_slice(COMPRESSED_MAGIC);
+    bytes.extend_from_slice(&(blocks as u32).to_ne_bytes());
+    bytes.extend_from_slice(&(animation.len() as u64).to_ne_bytes());
+    let table = bytes.len();
+    bytes.resize(table + blocks * 8, 0);
+
+    for (i, block) in animation.chunks(BLOCK_LEN).enumerate() {
+        let start = bytes.len();
+        zstd::compress(block, &mut bytes, ZSTD_LEVEL);
+        let entry = table + i * 8;
+        let compressed_len = (bytes.len() - start) as u32;
+        bytes[entry..entry + 4].copy_from_slice(&compressed_len.to_ne_bytes());
+        bytes[entry + 4..entry + 8].copy_from_slice(&(block.len() as u32).to_ne_bytes());
+    }
+    Ok(bytes)
+}
+
+#[cfg(not(feature = "zstd"))]
+fn decompress_animation(_: &[u8]) -> io::Result<Mmap> {
+    Err(io::Error::other(NO_ZSTD))
+}
+
+/// Decompresses what `compress_animation` made into a new memory map
+#[cfg(feature = "zstd")]
+fn decompress_animation(bytes: &[u8]) -> io::Result<Mmap> {
+    let invalid = || io::Error::other("the compressed animation is truncated or corrupted");
+    let header = bytes.get(..20).ok_or_else(invalid)?;
+    let blocks = u32::from_ne_bytes(header[8..12].try_into().unwrap()) as usize;
+    let len = u64::from_ne_bytes(header[12..20].try_into().unwrap()) as usize;
+    let table = bytes.get(20..20 + blocks * 8).ok_or_else(invalid)?;
+    if len == 0 || len.div_ceil(BLOCK_LEN) != blocks {
+        return Err(invalid());
+    }
+
+    let mut mmap = Mmap::try_create(len)?;
+    let (mut src, mut dst) = (20 + table.len(), 0);
+    for entry in table.chunks_exact(8) {
+        let compressed_len = u32::from_ne_bytes(entry[0..4].try_into().unwrap()) as usize;
+        let block_len = u32::from_ne_bytes(entry[4..8].try_into().unwrap()) as usize;
+        let block = bytes.get(src..src + compressed_len).ok_or_else(invalid)?;
+        let out = mmap
+            .slice_mut()
+            .get_mut(dst..dst + block_len)
+            .ok_or_else(invalid)?;
+        zstd::decompress(block, out)?;
+        src += compressed_len;
+        dst += block_len;
     }
+    if dst != len {
+        return Err(invalid());
+    }
+    Ok(mmap)
 }
 
 pub fn load_animation_frames(
     path: &Path,
     dimensions: (u32, u32),
     pixel_format: PixelFormat,
+    key: &str,
 ) -> io::Result<Option<Animation>> {
-    let filename = animation_filename(path, dimensions, pixel_format);
+    let filename = animation_filename(path, dimensions, pixel_format, key);
     let cache_dir = cache_dir()?;
     let mut filepath = cache_dir.clone();
     filepath.push(filename);
@@ -53,7 +153,17 @@ pub fn load_animation_frames(
         if entry.path() == filepath {
             let fd = File::open(&filepath)?.into();
             let len = rustix::fs::seek(&fd, rustix::fs::SeekFrom::End(0))?;
-            let mmap = Mmap::from_fd(fd, len as usize);
+            let mut mmap = Mmap::from_fd(fd, len as usize);
+            if mmap.slice().starts_with(COMPRESSED_MAGIC) {
+                match decompress_animation(mmap.slice()) {
+                    Ok(decompressed) => mmap = decompressed,
+                    Err(e) => {
+                        // so that it gets cached again
+                        let _ = std::fs::remove_file(&filepath);
+                        return Err(e);
+                    }
+                }
+            }
 
             match std::panic::catch_unwind(|| Animation::deserialize(&mmap, mmap.slice())) {
                 Ok((frames, _)) => return Ok(Some(frames)),
@@ -65,6 +175,17 @@ pub fn load_animation_frames(
 }
 
 pub fn get_previous_image_path(output_name: &str) -> io::Result<String> {
+    let cached = read_previous_image(output_name)?;
+    Ok(cached.lines().next().unwrap_or_default().to_string())
+}
+
+/// The `swww img` options `output_name`'s previous image was set with (see `store`)
+pub fn get_previous_image_options(output_name: &str) -> io::Result<Vec<String>> {
+    let cached = read_previous_image(output_name)?;
+    Ok(cached.lines().skip(1).map(str::to_string).collect())
+}
+
+fn read_previous_image(output_name: &str) -> io::Result<String> {
     let mut filepath = cache_dir()?;
     clean_previous_verions(&filepath);
 
@@ -76,12 +197,8 @@ pub fn get_previous_image_path(output_name: &str) -> io::Result<String> {
     let mut buf = Vec::with_capacity(64);
     File::open(filepath)?.read_to_end(&mut buf)?;
 
-    String::from_utf8(buf).map_err(|e| {
-        std::io::Error::new(
-            std::io::ErrorKind::Other,
-            format!("failed to decode bytes: {e}"),
-        )
-    })
+    String::from_utf8(buf)
+        .map_err(|e| std::io::Error::other(format!("failed to decode bytes: {e}")))
 }
 
 pub fn load(output_name: &str) -> io::Result<()> {
@@ -89,13 +206,13 @@ pub fn load(output_name: &str) -> io::Result<()> {
     if img_path.is_empty() {
         return Ok(());
     }
+    let options = get_previous_image_options(output_name)?;
 
     if let Ok(mut child) = std::process::Command::new("pidof").arg("swww").spawn() {
         if let Ok(status) = child.wait() {
             if status.success() {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    "there is already another swww process running".to_string(),
+                return Err(std::io::Error::other(
+                    "there is already another swww process running",
                 ));
             }
         }
@@ -108,6 +225,7 @@ pub fn load(output_name: &str) -> io::Result<()> {
             "--transition-type=none",
             &img_path,
         ])
+        .args(options)
         .spawn()?
         .wait()?;
     Ok(())
@@ -117,6 +235,34 @@ pub fn clean() -> io::Result<()> {
     std::fs::remove_dir_all(cache_dir()?)
 }
 
+/// Removes the least recently written animation frames until the whole cache takes at most
+/// `max_size` bytes. The files remembering each output's image are never removed
+pub fn trim(max_size: u64) -> io::Result<()> {
+    let mut size = 0;
+    let mut animations = Vec::new();
+    for entry in cache_dir()?.read_dir()?.flatten() {
+        let metadata = entry.metadata()?;
+        if !metadata.is_file() {
+            continue;
+        }
+        size += metadata.len();
+        let filename = entry.file_name();
+        if filename.to_str().is_some_and(|f| f.contains("__")) {
+            animations.push((metadata.modified()?, metadata.len(), entry.path()));
+        }
+    }
+
+    animations.sort_unstable();
+    for (_, len, path) in animations {
+        if size <= max_size {
+            break;
+        }
+        std::fs::remove_file(path)?;
+        size -= len;
+    }
+    Ok(())
+}
+
 fn clean_previous_verions(cache_dir: &Path) {
     let mut read_dir = match std::fs::read_dir(cache_dir) {
         Ok(read_dir) => read_dir,
@@ -160,8 +306,14 @@ fn create_dir(p: &Path) -> io::Result<()> {
     }
 }
 
-fn cache_dir() -> io::Result<PathBuf> {
-    if let Ok(path) = std::env::var("XDG_CACHE_HOME") {
+/// `$SWWW_CACHE_DIR` if it is set (`swww-daemon --greeter` sets it for its clients), otherwise
+/// `$XDG_CACHE_HOME/swww`, or `$HOME/.cache/swww`
+pub fn cache_dir() -> io::Result<PathBuf> {
+    if let Some(path) = std::env::var_os("SWWW_CACHE_DIR").filter(|p| !p.is_empty()) {
+        let path: PathBuf = path.into();
+        std::fs::create_dir_all(&path)?;
+        Ok(path)
+    } else if let Ok(path) = std::env::var("XDG_CACHE_HOME") {
         let mut path: PathBuf = path.into();
         path.push("swww");
         create_dir(&path)?;
@@ -173,22 +325,91 @@ fn cache_dir() -> io::Result<PathBuf> {
         create_dir(&path)?;
         Ok(path)
     } else {
-        Err(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            "failed to read both $XDG_CACHE_HOME and $HOME environment variables".to_string(),
+        Err(std::io::Error::other(
+            "failed to read both $XDG_CACHE_HOME and $HOME environment variables",
         ))
     }
 }
 
+/// `key` distinguishes between animations of the same image that were processed with different
+/// settings
 #[must_use]
-fn animation_filename(path: &Path, dimensions: (u32, u32), pixel_format: PixelFormat) -> PathBuf {
+fn animation_filename(
+    path: &Path,
+    dimensions: (u32, u32),
+    pixel_format: PixelFormat,
+    key: &str,
+) -> PathBuf {
     format!(
-        "{}__{}x{}_{:?}_v{}",
-        path.to_string_lossy().replace('/', "_"),
+        "{}{}x{}_{:?}{}{}_v{}",
+        animation_prefix(path),
         dimensions.0,
         dimensions.1,
         pixel_format,
+        if key.is_empty() { "" } else { "_" },
+        key,
         env!("CARGO_PKG_VERSION"),
     )
     .into()
 }
+
+#[must_use]
+fn animation_prefix(path: &Path) -> String {
+    format!("{}__", path.to_string_lossy().replace('/', "_"))
+}
+
+/// Lists the animation frames the current version of swww has cached for the image at `path`,
+/// as (name, file). The name identifies the dimensions, pixel format and settings the frames
+/// were processed with, and does not depend on where the image is
+pub fn cached_animations(path: &Path) -> io::Result<Vec<(String, PathBuf)>> {
+    let prefix = animation_prefix(path);
+    let suffix = format!("_v{}", env!("CARGO_PKG_VERSION"));
+    let mut animations = Vec::new();
+    for entry in cache_dir()?.read_dir()?.flatten() {
+        let filename = entry.file_name();
+        let name = filename
+            .to_str()
+            .and_then(|f| f.strip_prefix(&prefix))
+            .and_then(|f| f.strip_suffix(&suffix));
+        if let Some(name) = name {
+            animations.push((name.to_string(), entry.path()));
+        }
+    }
+    animations.sort_unstable();
+    Ok(animations)
+}
+
+/// Where the animation frames called `name` (see `cached_animations`) of the image at `path`
+/// should be stored
+pub fn cached_animation_path(path: &Path, name: &str) -> io::Result<PathBuf> {
+    let mut filepath = cache_dir()?;
+    filepath.push(format!(
+        "{}{name}_v{}",
+        animation_prefix(path),
+        env!("CARGO_PKG_VERSION")
+    ));
+    Ok(filepath)
+}
+
+#[cfg(all(test, feature = "zstd"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compressed_animations_should_round_trip() {
+        let mut animation = vec![0u8; BLOCK_LEN * 2 + 1000];
+        for byte in &mut animation[BLOCK_LEN..BLOCK_LEN + 5000] {
+            *byte = fastrand::u8(..);
+        }
+        let compressed = compress_animation(&animation).unwrap();
+        assert!(compressed.len() < animation.len() / 10);
+        assert_eq!(
+            decompress_animation(&compressed).unwrap().slice(),
+            animation
+        );
+
+        for len in [0, 19, 30, compressed.len() - 1] {
+            assert!(decompress_animation(&compressed[..len]).is_err());
+        }
+    }
+}
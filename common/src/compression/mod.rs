@@ -1,19 +1,19 @@
 //! # Compression utilities
 //!
-//! Our compression strategy is documented in `comp/mod.rs`
+//! Our compression strategy is documented in `swww-render`'s `comp/mod.rs`
 
-use comp::pack_bytes;
-use decomp::{unpack_bytes_3channels, unpack_bytes_4channels};
 use std::ffi::{c_char, c_int};
 
 use crate::ipc::ImageRequestBuilder;
 use crate::ipc::PixelFormat;
 use crate::mmap::Mmap;
 use crate::mmap::MmappedBytes;
+use swww_render::comp::pack_bytes;
+use swww_render::cpu;
+use swww_render::decomp::{unpack_bytes_3channels, unpack_bytes_4channels};
 
-mod comp;
-mod cpu;
-mod decomp;
+#[cfg(feature = "zstd")]
+pub(crate) mod zstd;
 
 /// extracted from lz4.h
 const LZ4_MAX_INPUT_SIZE: usize = 0x7E000000;
@@ -125,7 +125,7 @@ impl Compressor {
     /// # Panics:
     ///   * `prev.len() != cur.len()`
     ///   * the len of the diff buffer is larger than 0x7E000000. In practice, this can only
-    ///   happen for 64k monitors and beyond
+    ///     happen for 64k monitors and beyond
     #[inline]
     pub fn compress(
         &mut self,
@@ -238,15 +238,15 @@ impl Decompressor {
         self.cap = goal;
     }
 
-    ///returns whether unpacking was successful. Note it can only fail if `buf.len() !=
-    ///expected_buf_size`
+    ///returns whether unpacking was successful, and whether it changed `buf`. Note it can only
+    ///fail if `buf.len() != expected_buf_size`
     #[inline]
     pub fn decompress(
         &mut self,
         bitpack: &BitPack,
         buf: &mut [u8],
         pixel_format: PixelFormat,
-    ) -> Result<(), String> {
+    ) -> Result<bool, String> {
         if buf.len() != bitpack.expected_buf_size as usize {
             return Err(format!(
                 "buf has len {}, but expected len is {}",
@@ -255,6 +255,9 @@ impl Decompressor {
             ));
         }
 
+        if bitpack.compressed_size < 3 {
+            return Err("BitPack is malformed!".to_string());
+        }
         self.ensure_capacity(bitpack.compressed_size as usize);
 
         // SAFETY: errors will never happen because BitPacked is *always* only produced
@@ -279,13 +282,26 @@ impl Decompressor {
             std::slice::from_raw_parts_mut(self.ptr.as_ptr(), bitpack.compressed_size as usize)
         };
 
-        if pixel_format.can_copy_directly_onto_wl_buffer() {
-            unpack_bytes_3channels(buf, v);
-        } else {
-            unpack_bytes_4channels(buf, v);
+        // bitpacks may come from the cache, which could have been corrupted
+        let pixels = buf.len() / pixel_format.channels() as usize;
+        if !swww_render::decomp::is_well_formed(v, pixels) {
+            return Err("BitPack is malformed!".to_string());
+        }
+        // e.g. the frame is already on screen
+        if !swww_render::decomp::changes(buf, v, pixel_format.channels() as usize) {
+            return Ok(false);
         }
 
-        Ok(())
+        // SAFETY: we've just checked unpacking stays within buf's bounds
+        unsafe {
+            if pixel_format.can_copy_directly_onto_wl_buffer() {
+                unpack_bytes_3channels(buf, v);
+            } else {
+                unpack_bytes_4channels(buf, v);
+            }
+        }
+
+        Ok(true)
     }
 }
 
@@ -447,4 +463,102 @@ mod tests {
             }
         }
     }
+
+    mod proptests {
+        use super::*;
+        use proptest::prelude::*;
+
+        const ALL_FORMATS: [PixelFormat; 4] = [
+            PixelFormat::Bgr,
+            PixelFormat::Rgb,
+            PixelFormat::Xbgr,
+            PixelFormat::Xrgb,
+        ];
+
+        /// a few frames of the same random dimensions. Consecutive frames share most of their
+        /// pixels, like real animations do
+        fn animation() -> impl Strategy<Value = Vec<Vec<u8>>> {
+            (1usize..48, 1usize..48, 2usize..6).prop_flat_map(|(width, height, frames)| {
+                let len = width * height * 3;
+                (
+                    proptest::collection::vec(any::<u8>(), len),
+                    proptest::collection::vec(
+                        proptest::collection::vec((0..len, any::<u8>()), 0..len.min(64)),
+                        frames - 1,
+                    ),
+                )
+                    .prop_map(|(first, changes)| {
+                        let mut frames = vec![first];
+                        for changes in changes {
+                            let mut frame = frames.last().unwrap().clone();
+                            for (i, byte) in changes {
+                                frame[i] = byte;
+                            }
+                            frames.push(frame);
+                        }
+                        frames
+                    })
+            })
+        }
+
+        fn assert_frame_eq(buf: &[u8], frame: &[u8], format: PixelFormat) {
+            let channels = format.channels() as usize;
+            for (pixel, expected) in buf.chunks_exact(channels).zip(frame.chunks_exact(3)) {
+                assert_eq!(&pixel[..3], expected);
+            }
+        }
+
+        proptest! {
+            #[test]
+            fn decompress_should_invert_compress(frames in animation()) {
+                for format in ALL_FORMATS {
+                    let mut compressor = Compressor::new();
+                    let mut decompressor = Decompressor::new();
+                    let mut buf = buf_from(&frames[0], format.channels().into());
+                    for pair in frames.windows(2) {
+                        match compressor.compress(&pair[0], &pair[1], format) {
+                            Some(bitpack) => {
+                                prop_assert!(decompressor.decompress(&bitpack, &mut buf, format).unwrap());
+                                // the frame is on screen now
+                                prop_assert!(!decompressor.decompress(&bitpack, &mut buf, format).unwrap());
+                            }
+                            None => prop_assert_eq!(&pair[0], &pair[1]),
+                        }
+                        assert_frame_eq(&buf, &pair[1], format);
+                    }
+                }
+            }
+
+            #[test]
+            fn decompress_should_reject_corrupted_bitpacks(
+                frames in animation(),
+                corruption in proptest::collection::vec((any::<usize>(), any::<u8>()), 1..8),
+                size_delta in -4i32..4,
+            ) {
+                for format in ALL_FORMATS {
+                    let Some(bitpack) = Compressor::new().compress(&frames[0], &frames[1], format)
+                    else {
+                        continue;
+                    };
+                    let mut bytes = bitpack.bytes().to_vec();
+                    for &(i, byte) in &corruption {
+                        let i = i % bytes.len();
+                        bytes[i] = byte;
+                    }
+                    let corrupted = BitPack {
+                        inner: Inner::Boxed(bytes.into_boxed_slice()),
+                        expected_buf_size: bitpack.expected_buf_size,
+                        compressed_size: bitpack.compressed_size + size_delta,
+                    };
+
+                    // we only care that it does not crash, and that it never writes out of bounds
+                    const GUARD: usize = 64;
+                    let len = bitpack.expected_buf_size as usize;
+                    let mut buf = vec![0; len + GUARD];
+                    let _ = Decompressor::new().decompress(&corrupted, &mut buf[..len], format);
+                    prop_assert!(buf[len..].iter().all(|&b| b == 0));
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,80 @@
+//! Bindings to the parts of libzstd we use to compress the cache on disk
+
+use std::ffi::{c_char, c_int, c_uint, c_void, CStr};
+use std::io;
+
+extern "C" {
+    fn ZSTD_compressBound(src_size: usize) -> usize;
+
+    /// # Safety
+    ///
+    /// This is guaranteed to succeed if `dst_cap >= ZSTD_compressBound(src_size)`.
+    fn ZSTD_compress(
+        dst: *mut c_void,
+        dst_cap: usize,
+        src: *const c_void,
+        src_size: usize,
+        level: c_int,
+    ) -> usize;
+
+    /// # Safety
+    ///
+    /// Fails when src is malformed, or dst_cap is insufficient.
+    fn ZSTD_decompress(
+        dst: *mut c_void,
+        dst_cap: usize,
+        src: *const c_void,
+        compressed_size: usize,
+    ) -> usize;
+
+    fn ZSTD_isError(code: usize) -> c_uint;
+
+    fn ZSTD_getErrorName(code: usize) -> *const c_char;
+}
+
+/// Compresses `src`, appending the result to `dst`
+pub(crate) fn compress(src: &[u8], dst: &mut Vec<u8>, level: c_int) {
+    let start = dst.len();
+    // SAFETY: we reserve `ZSTD_compressBound` bytes, so compression cannot fail
+    unsafe {
+        let bound = ZSTD_compressBound(src.len());
+        dst.reserve(bound);
+        let written = ZSTD_compress(
+            dst.as_mut_ptr().add(start).cast(),
+            bound,
+            src.as_ptr().cast(),
+            src.len(),
+            level,
+        );
+        assert_eq!(ZSTD_isError(written), 0, "zstd failed with enough space");
+        dst.set_len(start + written);
+    }
+}
+
+/// Decompresses `src` into `dst`, which must be exactly as big as the original bytes
+pub(crate) fn decompress(src: &[u8], dst: &mut [u8]) -> io::Result<()> {
+    // SAFETY: zstd checks the bounds of both buffers
+    let written = unsafe {
+        ZSTD_decompress(
+            dst.as_mut_ptr().cast(),
+            dst.len(),
+            src.as_ptr().cast(),
+            src.len(),
+        )
+    };
+    // SAFETY: zstd returns static strings for every error
+    if unsafe { ZSTD_isError(written) } != 0 {
+        let name = unsafe { CStr::from_ptr(ZSTD_getErrorName(written)) };
+        return Err(io::Error::other(format!(
+            "zstd failed to decompress: {}",
+            name.to_string_lossy()
+        )));
+    }
+    if written != dst.len() {
+        return Err(io::Error::other(format!(
+            "expected {} decompressed bytes, got {written}",
+            dst.len()
+        )));
+    }
+    Ok(())
+}
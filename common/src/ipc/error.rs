@@ -14,6 +14,10 @@ impl IpcError {
     pub(crate) fn new(kind: IpcErrorKind, err: Errno) -> Self {
         Self { err, kind }
     }
+
+    pub fn kind(&self) -> &IpcErrorKind {
+        &self.kind
+    }
 }
 
 #[derive(Debug)]
@@ -36,6 +40,8 @@ pub enum IpcErrorKind {
     MalformedMsg,
     /// Reading socket failed
     Read,
+    /// The other side took too long to answer
+    Timeout,
 }
 
 impl IpcErrorKind {
@@ -50,6 +56,7 @@ impl IpcErrorKind {
             Self::BadCode => "invalid message code",
             Self::MalformedMsg => "malformed ancillary message",
             Self::Read => "failed to receive message",
+            Self::Timeout => "timed out waiting for an answer. Is swww-daemon stuck?",
         }
     }
 }
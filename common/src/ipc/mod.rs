@@ -4,6 +4,7 @@ use transmit::RawMsg;
 
 mod error;
 mod socket;
+mod stream;
 mod transmit;
 mod types;
 
@@ -11,31 +12,55 @@ use crate::cache;
 use crate::mmap::Mmap;
 pub use error::*;
 pub use socket::*;
+pub use stream::*;
 pub use types::*;
 
 pub struct ImageRequestBuilder {
     memory: Mmap,
     len: usize,
     img_count: u8,
-    img_count_index: usize,
+    /// whether the animation frames we cache are compressed (see `cache::store_animation_frames`)
+    compress_cache: bool,
+    /// the `swww img` options cached along with each output's image (see `cache::store`)
+    cache_options: Vec<String>,
+}
+
+impl Default for ImageRequestBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ImageRequestBuilder {
     #[inline]
-    pub fn new(transition: Transition) -> Self {
+    #[must_use]
+    pub fn new() -> Self {
         let memory = Mmap::create(1 << (20 + 3)); // start with 8 MB
-        let len = 0;
-        let mut builder = Self {
+                                                  // the first byte is the number of images, which we only know when building the request
+        Self {
             memory,
-            len,
+            len: 1,
             img_count: 0,
-            img_count_index: 0,
-        };
-        transition.serialize(&mut builder);
-        builder.img_count_index = builder.len;
-        builder.len += 1;
-        assert_eq!(builder.len, 52);
-        builder
+            compress_cache: false,
+            cache_options: Vec::new(),
+        }
+    }
+
+    /// Compresses the animation frames we cache with zstd, trading some cpu for disk space
+    #[inline]
+    #[must_use]
+    pub fn compress_cache(mut self, compress: bool) -> Self {
+        self.compress_cache = compress;
+        self
+    }
+
+    /// The `swww img` options the images were processed with, cached so they can be set again the
+    /// same way
+    #[inline]
+    #[must_use]
+    pub fn cache_options(mut self, options: Vec<String>) -> Self {
+        self.cache_options = options;
+        self
     }
 
     fn push_byte(&mut self, byte: u8) {
@@ -59,20 +84,38 @@ impl ImageRequestBuilder {
     }
 
     #[inline]
-    pub fn push(&mut self, img: ImgSend, outputs: &[String], animation: Option<Animation>) {
+    /// `animation` comes with the key it should be cached under (see `cache::load_animation_frames`).
+    /// `transition` is the one `outputs` transition to `img` with
+    pub fn push(
+        &mut self,
+        img: ImgSend,
+        outputs: &[String],
+        animation: Option<(Animation, String)>,
+        transition: &Transition,
+    ) {
         self.img_count += 1;
+        transition.serialize(self);
 
         let ImgSend {
             path,
             img,
             dim: dims,
             format,
+            mask,
+            reduced,
         } = &img;
         self.serialize_bytes(path.as_bytes());
         self.serialize_bytes(img);
         self.extend(&dims.0.to_ne_bytes());
         self.extend(&dims.1.to_ne_bytes());
         self.push_byte(*format as u8);
+        if let Some(mask) = mask {
+            self.push_byte(1);
+            self.serialize_bytes(mask);
+        } else {
+            self.push_byte(0);
+        }
+        self.push_byte(*reduced as u8);
 
         self.push_byte(outputs.len() as u8);
         for output in outputs.iter() {
@@ -80,7 +123,7 @@ impl ImageRequestBuilder {
         }
 
         let animation_start = self.len + 1;
-        if let Some(animation) = animation.as_ref() {
+        if let Some((animation, _)) = animation.as_ref() {
             self.push_byte(1);
             animation.serialize(self);
         } else {
@@ -89,27 +132,41 @@ impl ImageRequestBuilder {
 
         // cache the request
         for output in outputs.iter() {
-            if let Err(e) = super::cache::store(output, path) {
+            if let Err(e) = super::cache::store(output, path, &self.cache_options) {
                 eprintln!("ERROR: failed to store cache: {e}");
             }
         }
 
-        if animation.is_some() && path != "-" {
+        // time of day wallpapers are laid out for the day they were set on, so they are not cached
+        if let Some((_, key)) = animation
+            .as_ref()
+            .filter(|(animation, _)| path != "-" && animation.day_start.is_none())
+        {
             let p = PathBuf::from(&path);
             if let Err(e) = cache::store_animation_frames(
                 &self.memory.slice()[animation_start..],
                 &p,
                 *dims,
                 *format,
+                key,
+                self.compress_cache,
             ) {
                 eprintln!("Error storing cache for {}: {e}", path);
             }
         }
     }
 
+    /// bytes written to the request so far
+    #[inline]
+    #[must_use]
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
     #[inline]
     pub fn build(mut self) -> Mmap {
-        self.memory.slice_mut()[self.img_count_index] = self.img_count;
+        self.memory.slice_mut()[0] = self.img_count;
         self.memory
     }
 
@@ -125,6 +182,24 @@ pub enum RequestSend {
     Clear(Mmap),
     Img(Mmap),
     Kill,
+    SetVar(Mmap),
+    GetVar(Mmap),
+    Filter(Mmap),
+    ListVars(Mmap),
+    Dim(Mmap),
+    /// several requests, made with `RequestSend::batch`
+    Batch(Mmap),
+    /// requests to run depending on a condition, made with `RequestSend::condition`
+    If(Mmap),
+    Capabilities,
+    /// a ring of frames to show as they come, made with `StreamRing::request`. It must be sent on
+    /// its own: batching it would copy the ring, which the producer could then no longer write to
+    Stream(Mmap),
+    Shader(Mmap),
+    DebugSurfaces,
+    /// pauses every animation (`true`), or resumes them (`false`)
+    Pause(bool),
+    TransitionDefaults(Mmap),
 }
 
 pub enum RequestRecv {
@@ -133,6 +208,20 @@ pub enum RequestRecv {
     Clear(ClearReq),
     Img(ImageReq),
     Kill,
+    SetVar(SetVarReq),
+    GetVar(GetVarReq),
+    Filter(FilterReq),
+    ListVars(ListVarsReq),
+    Dim(DimReq),
+    /// requests the daemon must handle in order, before any other
+    Batch(Box<[RequestRecv]>),
+    If(IfReq),
+    Capabilities,
+    Stream(StreamReq),
+    Shader(ShaderReq),
+    DebugSurfaces,
+    Pause(bool),
+    TransitionDefaults(TransitionDefaultsReq),
 }
 
 impl RequestSend {
@@ -157,6 +246,17 @@ pub enum Answer {
     Ok,
     Ping(bool),
     Info(Box<[BgInfo]>),
+    /// value of a variable stored in the daemon, if it is set
+    Var(Option<String>),
+    /// variables stored in the daemon starting with the requested prefix, sorted by name
+    Vars(Box<[(String, String)]>),
+    /// the transitions the daemon uses for an image request, one per group of outputs
+    Transitions(Box<[TransitionUsed]>),
+    /// the daemon refused the request, and why
+    Error(String),
+    Capabilities(Capabilities),
+    /// the state of every wallpaper's surface, for `swww debug surfaces`
+    Surfaces(Box<[SurfaceInfo]>),
 }
 
 impl Answer {
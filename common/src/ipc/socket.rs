@@ -11,6 +11,10 @@ use super::ErrnoExt;
 use super::IpcError;
 use super::IpcErrorKind;
 
+/// How many clients can wait for the daemon to accept them. It accepts them in batches, so
+/// polling clients do not get refused while it is busy
+const LISTEN_BACKLOG: i32 = 64;
+
 /// Represents client in IPC communication, via typestate pattern in [`IpcSocket`]
 pub struct Client;
 /// Represents server in IPC communication, via typestate pattern in [`IpcSocket`]
@@ -127,7 +131,7 @@ impl IpcSocket<Server> {
         )
         .context(IpcErrorKind::Socket)?;
         net::bind_unix(&socket, &addr).context(IpcErrorKind::Bind)?;
-        net::listen(&socket, 0).context(IpcErrorKind::Listen)?;
+        net::listen(&socket, LISTEN_BACKLOG).context(IpcErrorKind::Listen)?;
         Ok(Self::new(socket))
     }
 }
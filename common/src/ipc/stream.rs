@@ -0,0 +1,329 @@
+//! Raw frame streams: a ring of frames in shared memory, which a producer fills and the daemon
+//! shows as they come (see `swww stream`).
+//!
+//! Unlike the memory of other requests, the ring stays shared after the request is sent: the
+//! producer keeps writing frames into it, and the daemon keeps reading them, until either of them
+//! stops. They coordinate through counters at its start:
+//!
+//! - `written`: how many frames the producer finished. Frame `n` is in slot `n % slots`
+//! - `read`: how many frames the daemon is done with. The producer must not write frame `n`
+//!   before `n < read + slots`, so that it never overwrites a frame the daemon may be reading
+//! - `closed`: set by the producer once it has no more frames
+//! - `stopped`: set by the daemon once it stops showing the stream (e.g. because the outputs got
+//!   another image), so that the producer stops as well
+//!
+//! The daemon always shows the newest frame, skipping the ones it did not get to. So the producer
+//! only waits when the daemon is not showing frames at all, like while animations are paused.
+use std::{
+    sync::atomic::{AtomicU32, Ordering},
+    thread,
+    time::Duration,
+};
+
+use super::{serialize_string, PixelFormat, RequestSend};
+use crate::mmap::{Mmap, MmappedStr};
+
+const WRITTEN: usize = 0;
+const READ: usize = 4;
+const CLOSED: usize = 8;
+const STOPPED: usize = 12;
+const WIDTH: usize = 16;
+const HEIGHT: usize = 20;
+const FORMAT: usize = 24;
+const SLOTS: usize = 28;
+/// where the first slot starts
+const FRAMES: usize = 32;
+/// the outputs come right after the fields above
+const OUTPUTS: usize = 36;
+
+/// Frames start at a multiple of this, so that copying them is fast
+const ALIGN: usize = 64;
+
+/// How often the producer checks whether the daemon freed a slot
+const ROOM_POLL: Duration = Duration::from_millis(1);
+
+pub struct StreamReq {
+    pub ring: StreamRing,
+    /// the outputs to show the stream on, or all of them if empty
+    pub outputs: Box<[MmappedStr]>,
+}
+
+pub struct StreamRing {
+    mmap: Mmap,
+    dim: (u32, u32),
+    format: PixelFormat,
+    slots: u32,
+    frames: usize,
+    /// whether we are the producer, who closes the ring when dropped (the daemon stops it)
+    producer: bool,
+    /// whether the daemon checked the ring and can write to it (see `attach`)
+    attached: bool,
+}
+
+impl StreamRing {
+    /// A ring of `slots` frames of `dim` pixels in `format`, to show on `outputs` (all of them if
+    /// empty)
+    pub fn new(
+        dim: (u32, u32),
+        format: PixelFormat,
+        slots: u32,
+        outputs: &[String],
+    ) -> std::io::Result<Self> {
+        let slots = slots.max(1);
+        let names = 1 + outputs.iter().map(|o| 4 + o.len()).sum::<usize>();
+        let frames = (OUTPUTS + names).next_multiple_of(ALIGN);
+        let frame_len = dim.0 as usize * dim.1 as usize * format.channels() as usize;
+        let mut mmap = Mmap::try_create(frames + frame_len * slots as usize)?;
+
+        let bytes = mmap.slice_mut();
+        bytes[WIDTH..WIDTH + 4].copy_from_slice(&dim.0.to_ne_bytes());
+        bytes[HEIGHT..HEIGHT + 4].copy_from_slice(&dim.1.to_ne_bytes());
+        bytes[FORMAT..FORMAT + 4].copy_from_slice(&(format as u32).to_ne_bytes());
+        bytes[SLOTS..SLOTS + 4].copy_from_slice(&slots.to_ne_bytes());
+        bytes[FRAMES..FRAMES + 4].copy_from_slice(&(frames as u32).to_ne_bytes());
+        bytes[OUTPUTS] = outputs.len() as u8;
+        let mut i = OUTPUTS + 1;
+        for output in outputs {
+            i += serialize_string(&mut bytes[i..], output);
+        }
+
+        Ok(Self {
+            mmap,
+            dim,
+            format,
+            slots,
+            frames,
+            producer: true,
+            attached: true,
+        })
+    }
+
+    /// The request that hands the ring over to the daemon. We keep our own mapping of it, to go on
+    /// writing frames. Note it cannot be part of a batch, which would copy it
+    pub fn request(&self) -> std::io::Result<RequestSend> {
+        self.mmap
+            .try_clone()
+            .map(RequestSend::Stream)
+            .map_err(Into::into)
+    }
+
+    #[must_use]
+    pub fn dim(&self) -> (u32, u32) {
+        self.dim
+    }
+
+    #[must_use]
+    pub fn format(&self) -> PixelFormat {
+        self.format
+    }
+
+    fn frame_len(&self) -> usize {
+        self.dim.0 as usize * self.dim.1 as usize * self.format.channels() as usize
+    }
+
+    fn counter(&self, offset: usize) -> &AtomicU32 {
+        // SAFETY: the counters are within the header, which every ring has, and aligned, since
+        // mappings start at a page boundary. The other side only touches them atomically as well
+        unsafe { &*self.mmap.as_ptr().add(offset).cast::<AtomicU32>() }
+    }
+
+    fn slot_offset(&self, frame: u32) -> usize {
+        self.frames + (frame % self.slots) as usize * self.frame_len()
+    }
+
+    /// Waits until the slot of the next frame is free, and returns it to be written. Returns
+    /// `None` if the daemon stopped showing the stream
+    pub fn next_frame(&mut self) -> Option<&mut [u8]> {
+        let written = self.counter(WRITTEN).load(Ordering::Relaxed);
+        while written.wrapping_sub(self.counter(READ).load(Ordering::Acquire)) >= self.slots {
+            if self.is_stopped() {
+                return None;
+            }
+            thread::sleep(ROOM_POLL);
+        }
+        if self.is_stopped() {
+            return None;
+        }
+        let (offset, len) = (self.slot_offset(written), self.frame_len());
+        // SAFETY: the slot is within the ring, and the daemon does not read it until we publish it
+        Some(unsafe { std::slice::from_raw_parts_mut(self.mmap.as_ptr().add(offset), len) })
+    }
+
+    /// Hands the frame `next_frame` returned over to the daemon
+    pub fn publish(&self) {
+        self.counter(WRITTEN).fetch_add(1, Ordering::Release);
+    }
+
+    /// Tells the daemon there are no more frames. It keeps showing the last one
+    pub fn close(&self) {
+        self.counter(CLOSED).store(1, Ordering::Release);
+    }
+
+    #[must_use]
+    pub fn is_closed(&self) -> bool {
+        self.counter(CLOSED).load(Ordering::Acquire) != 0
+    }
+
+    #[must_use]
+    pub fn is_stopped(&self) -> bool {
+        self.counter(STOPPED).load(Ordering::Acquire) != 0
+    }
+
+    pub(super) fn deserialize(mmap: Mmap) -> (Self, Box<[MmappedStr]>) {
+        let bytes = mmap.slice();
+        let field = |offset: usize| {
+            bytes
+                .get(offset..offset + 4)
+                .map_or(0, |b| u32::from_ne_bytes(b.try_into().unwrap()))
+        };
+        let dim = (field(WIDTH), field(HEIGHT));
+        let format = match field(FORMAT) {
+            0 => PixelFormat::Bgr,
+            1 => PixelFormat::Rgb,
+            2 => PixelFormat::Xbgr,
+            _ => PixelFormat::Xrgb,
+        };
+        let slots = field(SLOTS);
+        let frames = field(FRAMES) as usize;
+
+        let mut outputs = Vec::new();
+        if bytes.len() > OUTPUTS {
+            let mut i = OUTPUTS + 1;
+            for _ in 0..bytes[OUTPUTS] {
+                // a name running past the header is garbage, which `attach` rejects
+                let len = field(i) as usize;
+                if i + 4 + len > frames.min(bytes.len()) {
+                    break;
+                }
+                outputs.push(MmappedStr::new(&mmap, &bytes[i..]));
+                i += 4 + len;
+            }
+        }
+
+        let ring = Self {
+            mmap,
+            dim,
+            format,
+            slots,
+            frames,
+            producer: false,
+            attached: false,
+        };
+        (ring, outputs.into())
+    }
+
+    /// Checks the ring the daemon got is one it can show on outputs of `format`, and maps it
+    /// writable, to tell the producer which frames it is done with
+    pub fn attach(&mut self, format: PixelFormat) -> Result<(), String> {
+        if self.format != format {
+            return Err(format!(
+                "the stream's frames must be in the pixel format of the outputs, {format:?}"
+            ));
+        }
+        let (width, height) = self.dim;
+        let len = (self.frame_len() as u64).saturating_mul(self.slots as u64);
+        if width == 0
+            || height == 0
+            || self.slots == 0
+            || self.frames < OUTPUTS + 1
+            || self.frames % ALIGN != 0
+            || self.frames as u64 + len > self.mmap.len() as u64
+        {
+            return Err("the stream's ring of frames is malformed".to_string());
+        }
+        // the request came mapped read only
+        self.mmap.unmap();
+        self.mmap
+            .try_ensure_mapped()
+            .map_err(|e| format!("failed to map the stream's frames: {e}"))?;
+        self.attached = true;
+        Ok(())
+    }
+
+    /// The newest frame the daemon has not read yet, with its number
+    #[must_use]
+    pub fn newest(&self) -> Option<(u32, &[u8])> {
+        let written = self.counter(WRITTEN).load(Ordering::Acquire);
+        if written == self.counter(READ).load(Ordering::Relaxed) {
+            return None;
+        }
+        let frame = written.wrapping_sub(1);
+        let (offset, len) = (self.slot_offset(frame), self.frame_len());
+        // SAFETY: the slot is within the ring (see `attach`), and the producer does not write it
+        // again until we say we are done with it
+        Some((frame, unsafe {
+            std::slice::from_raw_parts(self.mmap.as_ptr().add(offset), len)
+        }))
+    }
+
+    /// Lets the producer reuse the slots of `frame` and every frame before it
+    pub fn done_with(&self, frame: u32) {
+        self.counter(READ)
+            .store(frame.wrapping_add(1), Ordering::Release);
+    }
+
+    /// Tells the producer we are not showing the stream anymore
+    pub fn stop(&self) {
+        self.counter(STOPPED).store(1, Ordering::Release);
+    }
+}
+
+impl Drop for StreamRing {
+    fn drop(&mut self) {
+        if self.producer {
+            self.close();
+        } else if self.attached {
+            self.stop();
+        }
+    }
+}
+
+// SAFETY: the memory is only shared through the atomic counters, and the slots they hand over
+unsafe impl Send for StreamRing {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_daemon_should_show_the_newest_frame_and_free_the_older_ones() {
+        let outputs = ["DP-1".to_string(), "HDMI-A-1".to_string()];
+        let mut producer = StreamRing::new((2, 1), PixelFormat::Bgr, 2, &outputs).unwrap();
+        let (mut daemon, names) = StreamRing::deserialize(producer.mmap.try_clone().unwrap());
+        let names: Vec<&str> = names.iter().map(MmappedStr::str).collect();
+        assert_eq!(names, outputs);
+        daemon.attach(PixelFormat::Bgr).unwrap();
+        assert!(daemon.newest().is_none());
+
+        for i in 0..2 {
+            producer.next_frame().unwrap().fill(i);
+            producer.publish();
+        }
+        let (frame, bytes) = daemon.newest().unwrap();
+        assert_eq!((frame, bytes), (1, &[1u8; 6][..]));
+        daemon.done_with(frame);
+        assert!(daemon.newest().is_none());
+
+        // both slots are free again
+        for i in 2..4 {
+            producer.next_frame().unwrap().fill(i);
+            producer.publish();
+        }
+        assert_eq!(daemon.newest().unwrap(), (3, &[3u8; 6][..]));
+
+        drop(daemon);
+        assert!(producer.next_frame().is_none());
+    }
+
+    #[test]
+    fn the_daemon_should_refuse_malformed_rings() {
+        let ring = StreamRing::new((4, 4), PixelFormat::Xrgb, 3, &[]).unwrap();
+        let (mut daemon, _) = StreamRing::deserialize(ring.mmap.try_clone().unwrap());
+        assert!(daemon.attach(PixelFormat::Bgr).is_err());
+
+        let mut mmap = Mmap::create(OUTPUTS + 1);
+        mmap.slice_mut()[SLOTS..SLOTS + 4].copy_from_slice(&1000u32.to_ne_bytes());
+        let (mut daemon, _) = StreamRing::deserialize(mmap);
+        assert!(daemon.attach(PixelFormat::Bgr).is_err());
+    }
+}
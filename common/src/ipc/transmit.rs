@@ -6,19 +6,36 @@ use rustix::io::Errno;
 use rustix::net;
 use rustix::net::RecvFlags;
 
+use super::deserialize_string;
+use super::serialize_string;
 use super::Animation;
 use super::Answer;
 use super::BgInfo;
+use super::Capabilities;
 use super::ClearReq;
+use super::Condition;
+use super::DimReq;
 use super::ErrnoExt;
+use super::FilterReq;
+use super::GetVarReq;
+use super::IfReq;
 use super::ImageReq;
 use super::ImgReq;
 use super::IpcError;
 use super::IpcErrorKind;
 use super::IpcSocket;
+use super::ListVarsReq;
+use super::Outro;
 use super::RequestRecv;
 use super::RequestSend;
+use super::SetVarReq;
+use super::ShaderReq;
+use super::StreamReq;
+use super::StreamRing;
+use super::SurfaceInfo;
 use super::Transition;
+use super::TransitionDefaultsReq;
+use super::TransitionUsed;
 use crate::mmap::Mmap;
 use crate::mmap::MmappedStr;
 
@@ -36,10 +53,35 @@ impl From<RequestSend> for RawMsg {
             RequestSend::Clear(_) => Code::ReqClear,
             RequestSend::Img(_) => Code::ReqImg,
             RequestSend::Kill => Code::ReqKill,
+            RequestSend::SetVar(_) => Code::ReqSetVar,
+            RequestSend::GetVar(_) => Code::ReqGetVar,
+            RequestSend::Filter(_) => Code::ReqFilter,
+            RequestSend::ListVars(_) => Code::ReqListVars,
+            RequestSend::Dim(_) => Code::ReqDim,
+            RequestSend::Batch(_) => Code::ReqBatch,
+            RequestSend::If(_) => Code::ReqIf,
+            RequestSend::Capabilities => Code::ReqCapabilities,
+            RequestSend::Stream(_) => Code::ReqStream,
+            RequestSend::Shader(_) => Code::ReqShader,
+            RequestSend::DebugSurfaces => Code::ReqDebugSurfaces,
+            RequestSend::Pause(true) => Code::ReqPause,
+            RequestSend::Pause(false) => Code::ReqResume,
+            RequestSend::TransitionDefaults(_) => Code::ReqTransitionDefaults,
         };
 
         let shm = match value {
-            RequestSend::Clear(mem) | RequestSend::Img(mem) => Some(mem),
+            RequestSend::Clear(mem)
+            | RequestSend::Img(mem)
+            | RequestSend::SetVar(mem)
+            | RequestSend::GetVar(mem)
+            | RequestSend::Filter(mem)
+            | RequestSend::ListVars(mem)
+            | RequestSend::Dim(mem)
+            | RequestSend::Batch(mem)
+            | RequestSend::If(mem)
+            | RequestSend::Stream(mem)
+            | RequestSend::Shader(mem)
+            | RequestSend::TransitionDefaults(mem) => Some(mem),
             _ => None,
         };
 
@@ -54,32 +96,182 @@ impl From<Answer> for RawMsg {
             Answer::Ping(true) => Code::ResConfigured,
             Answer::Ping(false) => Code::ResAwait,
             Answer::Info(_) => Code::ResInfo,
+            Answer::Var(_) => Code::ResVar,
+            Answer::Vars(_) => Code::ResVars,
+            Answer::Transitions(_) => Code::ResTransitions,
+            Answer::Error(_) => Code::ResError,
+            Answer::Capabilities(_) => Code::ResCapabilities,
+            Answer::Surfaces(_) => Code::ResSurfaces,
         };
 
-        let shm = if let Answer::Info(infos) = value {
-            let len = 1 + infos
-                .iter()
-                .map(|info| info.serialized_size())
-                .sum::<usize>();
-            let mut mmap = Mmap::create(len);
-            let bytes = mmap.slice_mut();
+        let shm = match value {
+            Answer::Info(infos) => {
+                let len = 1 + infos
+                    .iter()
+                    .map(|info| info.serialized_size())
+                    .sum::<usize>();
+                let mut mmap = Mmap::create(len);
+                let bytes = mmap.slice_mut();
+
+                bytes[0] = infos.len() as u8;
+                let mut i = 1;
 
-            bytes[0] = infos.len() as u8;
-            let mut i = 1;
+                for info in infos.iter() {
+                    i += info.serialize(&mut bytes[i..]);
+                }
 
-            for info in infos.iter() {
-                i += info.serialize(&mut bytes[i..]);
+                Some(mmap)
             }
-
-            Some(mmap)
-        } else {
-            None
+            Answer::Var(var) => {
+                // 1 - whether the variable is set
+                // 4 + value.len() - value len + bytes
+                let value = var.as_deref().unwrap_or("");
+                let mut mmap = Mmap::create(5 + value.len());
+                let bytes = mmap.slice_mut();
+                bytes[0] = var.is_some() as u8;
+                serialize_string(&mut bytes[1..], value);
+                Some(mmap)
+            }
+            Answer::Vars(vars) => {
+                // 4 - number of variables
+                // 8 + key.len() + value.len() - key and value lens + bytes
+                let len = 4 + vars
+                    .iter()
+                    .map(|(k, v)| 8 + k.len() + v.len())
+                    .sum::<usize>();
+                let mut mmap = Mmap::create(len);
+                let bytes = mmap.slice_mut();
+                bytes[0..4].copy_from_slice(&(vars.len() as u32).to_ne_bytes());
+                let mut i = 4;
+                for (key, value) in vars.iter() {
+                    i += serialize_string(&mut bytes[i..], key);
+                    i += serialize_string(&mut bytes[i..], value);
+                }
+                Some(mmap)
+            }
+            Answer::Transitions(transitions) => {
+                let len = 1 + transitions
+                    .iter()
+                    .map(TransitionUsed::serialized_size)
+                    .sum::<usize>();
+                let mut mmap = Mmap::create(len);
+                let bytes = mmap.slice_mut();
+                bytes[0] = transitions.len() as u8;
+                let mut i = 1;
+                for transition in transitions.iter() {
+                    i += transition.serialize(&mut bytes[i..]);
+                }
+                Some(mmap)
+            }
+            Answer::Error(error) => {
+                let mut mmap = Mmap::create(4 + error.len());
+                serialize_string(mmap.slice_mut(), &error);
+                Some(mmap)
+            }
+            Answer::Capabilities(capabilities) => {
+                let mut mmap = Mmap::create(capabilities.serialized_size());
+                capabilities.serialize(mmap.slice_mut());
+                Some(mmap)
+            }
+            Answer::Surfaces(surfaces) => {
+                let len = 1 + surfaces
+                    .iter()
+                    .map(|surface| surface.serialized_size())
+                    .sum::<usize>();
+                let mut mmap = Mmap::create(len);
+                let bytes = mmap.slice_mut();
+                bytes[0] = surfaces.len() as u8;
+                let mut i = 1;
+                for surface in surfaces.iter() {
+                    i += surface.serialize(&mut bytes[i..]);
+                }
+                Some(mmap)
+            }
+            _ => None,
         };
 
         Self { code, shm }
     }
 }
 
+impl RequestSend {
+    /// Packs `requests` into a single one, so that the daemon handles all of them in order, without
+    /// any other request in between
+    #[must_use]
+    pub fn batch(requests: Vec<RequestSend>) -> Self {
+        let msgs: Vec<RawMsg> = requests.into_iter().map(RawMsg::from).collect();
+        let mut mmap = Mmap::create(packed_len(&msgs));
+        pack(mmap.slice_mut(), msgs);
+        Self::Batch(mmap)
+    }
+
+    /// Makes the daemon handle `then` if `condition` holds when it gets the request, and
+    /// `otherwise` if it does not, like `batch` would
+    #[must_use]
+    pub fn condition(
+        condition: &Condition,
+        then: Vec<RequestSend>,
+        otherwise: Vec<RequestSend>,
+    ) -> Self {
+        let then: Vec<RawMsg> = then.into_iter().map(RawMsg::from).collect();
+        let otherwise: Vec<RawMsg> = otherwise.into_iter().map(RawMsg::from).collect();
+        let len = condition.serialized_size() + packed_len(&then) + packed_len(&otherwise);
+        let mut mmap = Mmap::create(len);
+        let bytes = mmap.slice_mut();
+        let mut i = condition.serialize(bytes);
+        i += pack(&mut bytes[i..], then);
+        pack(&mut bytes[i..], otherwise);
+        Self::If(mmap)
+    }
+}
+
+fn packed_len(msgs: &[RawMsg]) -> usize {
+    // 4 - number of requests
+    // 16 + shm.len() - code, shm len and shm bytes of each request
+    4 + msgs
+        .iter()
+        .map(|msg| 16 + msg.shm.as_ref().map_or(0, Mmap::len))
+        .sum::<usize>()
+}
+
+/// Writes `msgs` to the start of `bytes`, returning how many bytes that took
+fn pack(bytes: &mut [u8], msgs: Vec<RawMsg>) -> usize {
+    bytes[0..4].copy_from_slice(&(msgs.len() as u32).to_ne_bytes());
+    let mut i = 4;
+    for msg in msgs {
+        let shm = msg.shm.as_ref().map_or(&[][..], Mmap::slice);
+        bytes[i..i + 8].copy_from_slice(&msg.code.into().to_ne_bytes());
+        bytes[i + 8..i + 16].copy_from_slice(&(shm.len() as u64).to_ne_bytes());
+        bytes[i + 16..i + 16 + shm.len()].copy_from_slice(shm);
+        i += 16 + shm.len();
+    }
+    i
+}
+
+/// The inverse of `pack`
+fn unpack(bytes: &[u8]) -> (Box<[RequestRecv]>, usize) {
+    let len = u32::from_ne_bytes(bytes[0..4].try_into().unwrap()) as usize;
+    let mut requests = Vec::with_capacity(len);
+    let mut i = 4;
+    for _ in 0..len {
+        let code = u64::from_ne_bytes(bytes[i..i + 8].try_into().unwrap());
+        let shm_len = u64::from_ne_bytes(bytes[i + 8..i + 16].try_into().unwrap());
+        let shm_len = shm_len as usize;
+        i += 16;
+        // requests keep strings pointing into their memory, so each needs its own
+        let shm = (shm_len > 0).then(|| {
+            let mut shm = Mmap::create(shm_len);
+            shm.slice_mut().copy_from_slice(&bytes[i..i + shm_len]);
+            shm
+        });
+        i += shm_len;
+        if let Ok(code) = Code::try_from(code) {
+            requests.push(RawMsg { code, shm }.into());
+        }
+    }
+    (requests.into(), i)
+}
+
 // TODO: remove this ugly mess
 impl From<RawMsg> for RequestRecv {
     fn from(value: RawMsg) -> Self {
@@ -98,23 +290,31 @@ impl From<RawMsg> for RequestRecv {
                     outputs.push(output);
                 }
                 let color = [bytes[i], bytes[i + 1], bytes[i + 2]];
+                let outro = Outro::from_byte(bytes[i + 3]);
+                let outro_duration = f32::from_ne_bytes(bytes[i + 4..i + 8].try_into().unwrap());
                 Self::Clear(ClearReq {
                     color,
                     outputs: outputs.into(),
+                    outro,
+                    outro_duration,
                 })
             }
             Code::ReqImg => {
                 let mmap = value.shm.unwrap();
                 let bytes = mmap.slice();
-                let transition = Transition::deserialize(&bytes[0..]);
-                let len = bytes[51] as usize;
+                let len = bytes[0] as usize;
+                let mut i = 1;
 
+                let mut transitions = Vec::with_capacity(len);
                 let mut imgs = Vec::with_capacity(len);
                 let mut outputs = Vec::with_capacity(len);
                 let mut animations = Vec::with_capacity(len);
 
-                let mut i = 52;
                 for _ in 0..len {
+                    let (transition, offset) = Transition::deserialize(&bytes[i..]);
+                    i += offset;
+                    transitions.push(transition);
+
                     let (img, offset) = ImgReq::deserialize(&mmap, &bytes[i..]);
                     i += offset;
                     imgs.push(img);
@@ -138,7 +338,7 @@ impl From<RawMsg> for RequestRecv {
                 }
 
                 Self::Img(ImageReq {
-                    transition,
+                    transitions: transitions.into(),
                     imgs: imgs.into(),
                     outputs: outputs.into(),
                     animations: if animations.is_empty() {
@@ -149,6 +349,52 @@ impl From<RawMsg> for RequestRecv {
                 })
             }
             Code::ReqKill => Self::Kill,
+            Code::ReqSetVar => {
+                let mmap = value.shm.unwrap();
+                let bytes = mmap.slice();
+                let key = MmappedStr::new(&mmap, bytes);
+                let i = 4 + key.str().len();
+                let value = MmappedStr::new(&mmap, &bytes[i..]);
+                Self::SetVar(SetVarReq { key, value })
+            }
+            Code::ReqGetVar => {
+                let mmap = value.shm.unwrap();
+                let key = MmappedStr::new(&mmap, mmap.slice());
+                Self::GetVar(GetVarReq { key })
+            }
+            Code::ReqFilter => Self::Filter(FilterReq::deserialize(&value.shm.unwrap())),
+            Code::ReqDim => Self::Dim(DimReq::deserialize(&value.shm.unwrap())),
+            Code::ReqListVars => {
+                let mmap = value.shm.unwrap();
+                let prefix = MmappedStr::new(&mmap, mmap.slice());
+                Self::ListVars(ListVarsReq { prefix })
+            }
+            Code::ReqBatch => Self::Batch(unpack(value.shm.unwrap().slice()).0),
+            Code::ReqIf => {
+                let mmap = value.shm.unwrap();
+                let bytes = mmap.slice();
+                let (condition, mut i) = Condition::deserialize(bytes);
+                let (then, len) = unpack(&bytes[i..]);
+                i += len;
+                let (otherwise, _) = unpack(&bytes[i..]);
+                Self::If(IfReq {
+                    condition,
+                    then,
+                    otherwise,
+                })
+            }
+            Code::ReqCapabilities => Self::Capabilities,
+            Code::ReqStream => {
+                let (ring, outputs) = StreamRing::deserialize(value.shm.unwrap());
+                Self::Stream(StreamReq { ring, outputs })
+            }
+            Code::ReqShader => Self::Shader(ShaderReq::deserialize(&value.shm.unwrap())),
+            Code::ReqDebugSurfaces => Self::DebugSurfaces,
+            Code::ReqPause => Self::Pause(true),
+            Code::ReqResume => Self::Pause(false),
+            Code::ReqTransitionDefaults => {
+                Self::TransitionDefaults(TransitionDefaultsReq::deserialize(&value.shm.unwrap()))
+            }
             _ => Self::Kill,
         }
     }
@@ -175,6 +421,64 @@ impl From<RawMsg> for Answer {
 
                 Self::Info(bg_infos.into())
             }
+            Code::ResVar => {
+                let mmap = value.shm.unwrap();
+                let bytes = mmap.slice();
+                if bytes[0] == 0 {
+                    Self::Var(None)
+                } else {
+                    Self::Var(Some(deserialize_string(&bytes[1..])))
+                }
+            }
+            Code::ResVars => {
+                let mmap = value.shm.unwrap();
+                let bytes = mmap.slice();
+                let len = u32::from_ne_bytes(bytes[0..4].try_into().unwrap()) as usize;
+                let mut vars = Vec::with_capacity(len);
+                let mut i = 4;
+                for _ in 0..len {
+                    let key = deserialize_string(&bytes[i..]);
+                    i += 4 + key.len();
+                    let value = deserialize_string(&bytes[i..]);
+                    i += 4 + value.len();
+                    vars.push((key, value));
+                }
+                Self::Vars(vars.into())
+            }
+            Code::ResTransitions => {
+                let mmap = value.shm.unwrap();
+                let bytes = mmap.slice();
+                let mut i = 1;
+                let transitions = (0..bytes[0])
+                    .map(|_| {
+                        let (transition, offset) = TransitionUsed::deserialize(&bytes[i..]);
+                        i += offset;
+                        transition
+                    })
+                    .collect();
+                Self::Transitions(transitions)
+            }
+            Code::ResError => {
+                let mmap = value.shm.unwrap();
+                Self::Error(deserialize_string(mmap.slice()))
+            }
+            Code::ResCapabilities => {
+                let mmap = value.shm.unwrap();
+                Self::Capabilities(Capabilities::deserialize(mmap.slice()))
+            }
+            Code::ResSurfaces => {
+                let mmap = value.shm.unwrap();
+                let bytes = mmap.slice();
+                let mut i = 1;
+                let surfaces = (0..bytes[0])
+                    .map(|_| {
+                        let (surface, offset) = SurfaceInfo::deserialize(&bytes[i..]);
+                        i += offset;
+                        surface
+                    })
+                    .collect();
+                Self::Surfaces(surfaces)
+            }
             _ => panic!("Received malformed answer from daemon"),
         }
     }
@@ -216,6 +520,29 @@ code! {
     ResConfigured 6,
     ResAwait      7,
     ResInfo       8,
+
+    ReqSetVar     9,
+    ReqGetVar     10,
+    ResVar        11,
+
+    ReqFilter     12,
+    ReqListVars   13,
+    ResVars       14,
+
+    ReqDim        15,
+    ReqBatch      16,
+    ReqIf         17,
+    ResTransitions 18,
+    ResError      19,
+    ReqCapabilities 20,
+    ResCapabilities 21,
+    ReqStream     22,
+    ReqShader     23,
+    ReqDebugSurfaces 24,
+    ResSurfaces   25,
+    ReqPause      26,
+    ReqResume     27,
+    ReqTransitionDefaults 28,
 }
 
 impl TryFrom<u64> for Code {
@@ -258,11 +585,18 @@ impl<T> IpcSocket<T> {
 
         let mut control = net::RecvAncillaryBuffer::new(&mut ancillary_buf);
 
-        for _ in 0..5 {
+        let mut tries = 0;
+        loop {
             let iov = io::IoSliceMut::new(&mut buf);
             match net::recvmsg(self.as_fd(), &mut [iov], &mut control, RecvFlags::WAITALL) {
                 Ok(_) => break,
-                Err(Errno::WOULDBLOCK | Errno::INTR) => thread::sleep(Duration::from_millis(1)),
+                Err(err @ (Errno::WOULDBLOCK | Errno::INTR)) => {
+                    tries += 1;
+                    if tries == 5 {
+                        return Err(err).context(IpcErrorKind::Timeout);
+                    }
+                    thread::sleep(Duration::from_millis(1))
+                }
                 Err(err) => return Err(err).context(IpcErrorKind::Read),
             }
         }
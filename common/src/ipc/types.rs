@@ -10,67 +10,11 @@ use crate::mmap::MmappedBytes;
 use crate::mmap::MmappedStr;
 
 use super::ImageRequestBuilder;
+use super::RequestRecv;
 
-#[derive(Clone, PartialEq)]
-pub enum Coord {
-    Pixel(f32),
-    Percent(f32),
-}
-
-#[derive(Clone, PartialEq)]
-pub struct Position {
-    pub x: Coord,
-    pub y: Coord,
-}
-
-impl Position {
-    #[must_use]
-    pub fn new(x: Coord, y: Coord) -> Self {
-        Self { x, y }
-    }
-
-    #[must_use]
-    pub fn to_pixel(&self, dim: (u32, u32), invert_y: bool) -> (f32, f32) {
-        let x = match self.x {
-            Coord::Pixel(x) => x,
-            Coord::Percent(x) => x * dim.0 as f32,
-        };
-
-        let y = match self.y {
-            Coord::Pixel(y) => {
-                if invert_y {
-                    y
-                } else {
-                    dim.1 as f32 - y
-                }
-            }
-            Coord::Percent(y) => {
-                if invert_y {
-                    y * dim.1 as f32
-                } else {
-                    (1.0 - y) * dim.1 as f32
-                }
-            }
-        };
-
-        (x, y)
-    }
-
-    #[must_use]
-    pub fn to_percent(&self, dim: (u32, u32)) -> (f32, f32) {
-        let x = match self.x {
-            Coord::Pixel(x) => x / dim.0 as f32,
-            Coord::Percent(x) => x,
-        };
-
-        let y = match self.y {
-            Coord::Pixel(y) => y / dim.1 as f32,
-            Coord::Percent(y) => y,
-        };
+use swww_render::{transition, transitions};
 
-        (x, y)
-    }
-}
+pub use swww_render::transition::{Coord, CurveKeyframe, Position};
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum BgImg {
@@ -200,46 +144,54 @@ impl fmt::Display for Scale {
 pub struct BgInfo {
     pub name: String,
     pub dim: (u32, u32),
+    /// the output's resolution, in pixels. Images are sent at this size
+    pub native_dim: (u32, u32),
     pub scale_factor: Scale,
     pub img: BgImg,
     pub pixel_format: PixelFormat,
+    /// why the daemon cannot draw to this output, if it cannot
+    pub error: Option<String>,
 }
 
 impl BgInfo {
     #[inline]
     #[must_use]
     pub fn real_dim(&self) -> (u32, u32) {
-        let dim = self
-            .scale_factor
-            .mul_dim(self.dim.0 as i32, self.dim.1 as i32);
-        (dim.0 as u32, dim.1 as u32)
+        self.native_dim
     }
 
     pub(super) fn serialized_size(&self) -> usize {
         4 // name len
             + self.name.len()
             + 8 //dim
+            + 8 //native_dim
             + 5 //scale_factor (discriminant + value)
             + self.img.serialized_size()
             + 1 //pixel_format
+            + 4 // error len
+            + self.error.as_ref().map_or(0, String::len)
     }
 
     pub(super) fn serialize(&self, buf: &mut [u8]) -> usize {
         let Self {
             name,
             dim,
+            native_dim,
             scale_factor,
             img,
             pixel_format,
+            error,
         } = self;
 
-        let len = name.as_bytes().len();
+        let len = name.len();
         buf[0..4].copy_from_slice(&(len as u32).to_ne_bytes());
         buf[4..4 + len].copy_from_slice(name.as_bytes());
         let mut i = 4 + len;
         buf[i..i + 4].copy_from_slice(&dim.0.to_ne_bytes());
         buf[i + 4..i + 8].copy_from_slice(&dim.1.to_ne_bytes());
-        i += 8;
+        buf[i + 8..i + 12].copy_from_slice(&native_dim.0.to_ne_bytes());
+        buf[i + 12..i + 16].copy_from_slice(&native_dim.1.to_ne_bytes());
+        i += 16;
 
         match scale_factor {
             Scale::Whole(value) => {
@@ -262,7 +214,7 @@ impl BgInfo {
             BgImg::Img(path) => {
                 buf[i] = 1;
                 i += 1;
-                let len = path.as_bytes().len();
+                let len = path.len();
                 buf[i..i + 4].copy_from_slice(&(len as u32).to_ne_bytes());
                 buf[i + 4..i + 4 + len].copy_from_slice(path.as_bytes());
                 i += 4 + len;
@@ -270,20 +222,30 @@ impl BgInfo {
         }
 
         buf[i] = *pixel_format as u8;
-        i + 1
+        i += 1;
+
+        // an empty string means there is no error
+        let error = error.as_deref().unwrap_or("");
+        buf[i..i + 4].copy_from_slice(&(error.len() as u32).to_ne_bytes());
+        buf[i + 4..i + 4 + error.len()].copy_from_slice(error.as_bytes());
+        i + 4 + error.len()
     }
 
     pub(super) fn deserialize(bytes: &[u8]) -> (Self, usize) {
         let name = deserialize_string(bytes);
         let mut i = name.len() + 4;
 
-        assert!(bytes.len() > i + 17);
+        assert!(bytes.len() > i + 25);
 
         let dim = (
             u32::from_ne_bytes(bytes[i..i + 4].try_into().unwrap()),
             u32::from_ne_bytes(bytes[i + 4..i + 8].try_into().unwrap()),
         );
-        i += 8;
+        let native_dim = (
+            u32::from_ne_bytes(bytes[i + 8..i + 12].try_into().unwrap()),
+            u32::from_ne_bytes(bytes[i + 12..i + 16].try_into().unwrap()),
+        );
+        i += 16;
 
         let scale_factor = if bytes[i] == 0 {
             Scale::Whole(
@@ -318,13 +280,19 @@ impl BgInfo {
         };
         i += 1;
 
+        let error = deserialize_string(&bytes[i..]);
+        i += 4 + error.len();
+        let error = Some(error).filter(|e| !e.is_empty());
+
         (
             Self {
                 name,
                 dim,
+                native_dim,
                 scale_factor,
                 img,
                 pixel_format,
+                error,
             },
             i,
         )
@@ -337,7 +305,11 @@ impl fmt::Display for BgInfo {
             f,
             "{}: {}x{}, scale: {}, currently displaying: {}",
             self.name, self.dim.0, self.dim.1, self.scale_factor, self.img
-        )
+        )?;
+        match &self.error {
+            Some(e) => write!(f, ", error: {e}"),
+            None => Ok(()),
+        }
     }
 }
 
@@ -351,6 +323,57 @@ pub enum TransitionType {
     Grow = 4,
     Wave = 5,
     None = 6,
+    Ripple = 7,
+    Pixelate = 8,
+    Blinds = 9,
+    Dissolve = 10,
+    Clock = 11,
+    Cube = 12,
+    Shatter = 13,
+    Spiral = 14,
+    Checkerboard = 15,
+    Luma = 16,
+    Plugin = 17,
+    /// replaced by one of `Transition::random` in the daemon
+    Random = 18,
+    Push = 19,
+    ZoomBlur = 20,
+    PixelSort = 21,
+    Halftone = 22,
+    DoubleExposure = 23,
+    Sketch = 24,
+}
+
+impl TransitionType {
+    fn from_byte(byte: u8) -> Self {
+        match byte {
+            0 => Self::Simple,
+            1 => Self::Fade,
+            2 => Self::Outer,
+            3 => Self::Wipe,
+            4 => Self::Grow,
+            5 => Self::Wave,
+            7 => Self::Ripple,
+            8 => Self::Pixelate,
+            9 => Self::Blinds,
+            10 => Self::Dissolve,
+            11 => Self::Clock,
+            12 => Self::Cube,
+            13 => Self::Shatter,
+            14 => Self::Spiral,
+            15 => Self::Checkerboard,
+            16 => Self::Luma,
+            17 => Self::Plugin,
+            18 => Self::Random,
+            19 => Self::Push,
+            20 => Self::ZoomBlur,
+            21 => Self::PixelSort,
+            22 => Self::Halftone,
+            23 => Self::DoubleExposure,
+            24 => Self::Sketch,
+            _ => Self::None,
+        }
+    }
 }
 
 pub struct Transition {
@@ -361,11 +384,258 @@ pub struct Transition {
     pub angle: f64,
     pub pos: Position,
     pub bezier: (f32, f32, f32, f32),
+    /// stiffness, damping and mass of the spring that replaces the `bezier` curve, if any
+    pub spring: Option<(f32, f32, f32)>,
+    /// keyframes that replace both the `bezier` curve and the `spring` when there are any
+    pub curve: Box<[CurveKeyframe]>,
+    /// the transitions the `Random` transition picks from, with their weights
+    pub random: Box<[(TransitionType, u8)]>,
+    /// the transitions the `Random` transition never picks, even if they are in `random`
+    pub random_exclude: Box<[TransitionType]>,
     pub wave: (f32, f32),
+    /// amplitude and wavelength of the `Ripple` transition's ripples, in pixels
+    pub ripple: (f32, f32),
+    /// size of the `Pixelate` transition's blocks at its beginning, in pixels
+    pub block_size: u16,
+    /// how many slats the `Blinds` transition has, and whether they are vertical
+    pub slats: (u16, bool),
+    /// decides the order the pixels switch in during the `Dissolve` transition, and the shards'
+    /// shapes and flight during the `Shatter` one
+    pub seed: u32,
+    /// whether the `Clock` transition's hand goes clockwise, and the width of its soft edge, in
+    /// degrees
+    pub clock: (bool, f32),
+    /// whether the `Cube` transition turns around a vertical axis, and its field of view, in
+    /// degrees
+    pub cube: (bool, f32),
+    /// roughly how many shards the `Shatter` transition breaks the old image into, and how fast
+    /// they fall, in pixels per second squared
+    pub shatter: (u16, f32),
+    /// how many turns the `Spiral` transition's line makes, and how thick it is, in pixels
+    pub spiral: (f32, f32),
+    /// size of the `Checkerboard` transition's tiles, in pixels
+    pub tile_size: u16,
+    /// how far behind the `Luma` transition's threshold pixels blend in, as a fraction of the
+    /// mask's brightness range
+    pub softness: f32,
+    /// how many pixels behind their moving edge the `Wipe`, `Grow` and `Outer` transitions blend
+    /// in, instead of switching pixels right away
+    pub feather: f32,
+    /// the color space the `Fade` transition blends in
+    pub colorspace: Colorspace,
+    /// how bright, from 0 to 1, the runs of pixels the `PixelSort` transition sorts are, and
+    /// whether it sorts columns instead of rows
+    pub pixel_sort: (f32, bool),
+    /// size of the `Halftone` transition's threshold matrix, and whether it is blue noise instead
+    /// of a Bayer matrix
+    pub halftone: (u8, bool),
+    /// how the `DoubleExposure` transition overlays the images, and how strong its overlay gets
+    /// and for how much of the transition it shows, both from 0 to 1
+    pub exposure: (BlendMode, f32, f32),
+    /// where the circles of the `Grow` transition start from, when it has more than one. `pos`
+    /// is used when this is empty
+    pub origins: Box<[Position]>,
+    /// how strong, from 0 to 1, the edges the `Sketch` transition draws first are, compared to
+    /// the strongest one
+    pub edge_threshold: f32,
     pub invert_y: bool,
+    /// whether the transition flows across all the outputs it runs on, as if they were a single
+    /// screen, instead of running on each of them on its own
+    pub span: bool,
+    /// name of the daemon plugin the `Plugin` transition runs
+    pub plugin: String,
+    /// the parts of the transition the user did not set, which the daemon replaces with its
+    /// defaults (see `TRANSITION_DEFAULTS`)
+    pub defaulted: u64,
+}
+
+/// The parts of a transition `swww transition-defaults` can set. The bit of each one in
+/// `Transition::defaulted` is `1 << i`, where `i` is its index in here
+pub const TRANSITION_DEFAULTS: [&str; 40] = [
+    "type",
+    "step",
+    "duration",
+    "fps",
+    "angle",
+    "pos",
+    "origins",
+    "bezier",
+    "spring",
+    "curve",
+    "wave",
+    "feather",
+    "colorspace",
+    "ripple",
+    "block-size",
+    "slats",
+    "slat-direction",
+    "seed",
+    "clock-direction",
+    "soft-edge",
+    "axis",
+    "fov",
+    "shards",
+    "gravity",
+    "turns",
+    "thickness",
+    "tile-size",
+    "mask",
+    "softness",
+    "sort-threshold",
+    "sort-direction",
+    "matrix",
+    "matrix-size",
+    "blend",
+    "overlay",
+    "edge-threshold",
+    "plugin",
+    "random",
+    "random-exclude",
+    "invert-y",
+];
+
+/// The bit of `name` in `Transition::defaulted`, or 0 if it is not one of `TRANSITION_DEFAULTS`
+#[must_use]
+pub fn transition_default_bit(name: &str) -> u64 {
+    TRANSITION_DEFAULTS
+        .iter()
+        .position(|default| *default == name)
+        .map_or(0, |i| 1 << i)
 }
 
 impl Transition {
+    /// Replaces the parts of the transition the user did not set with the ones of `defaults`,
+    /// where they are in `set` (as bits of `defaulted`). The `mask` is not part of the transition,
+    /// so it is left to the caller
+    pub fn apply_defaults(&mut self, defaults: &Self, set: u64) {
+        for name in TRANSITION_DEFAULTS {
+            if self.defaulted & set & transition_default_bit(name) != 0 {
+                self.copy_default(defaults, name);
+            }
+        }
+    }
+
+    /// Copies the part of `from` that the default called `name` sets
+    pub fn copy_default(&mut self, from: &Self, name: &str) {
+        match name {
+            "type" => self.transition_type = from.transition_type,
+            "step" => self.step = from.step,
+            "duration" => self.duration = from.duration,
+            "fps" => self.fps = from.fps,
+            "angle" => self.angle = from.angle,
+            "pos" => self.pos = from.pos.clone(),
+            "origins" => self.origins = from.origins.clone(),
+            "bezier" => self.bezier = from.bezier,
+            "spring" => self.spring = from.spring,
+            "curve" => self.curve = from.curve.clone(),
+            "wave" => self.wave = from.wave,
+            "feather" => self.feather = from.feather,
+            "colorspace" => self.colorspace = from.colorspace,
+            "ripple" => self.ripple = from.ripple,
+            "block-size" => self.block_size = from.block_size,
+            "slats" => self.slats.0 = from.slats.0,
+            "slat-direction" => self.slats.1 = from.slats.1,
+            "seed" => self.seed = from.seed,
+            "clock-direction" => self.clock.0 = from.clock.0,
+            "soft-edge" => self.clock.1 = from.clock.1,
+            "axis" => self.cube.0 = from.cube.0,
+            "fov" => self.cube.1 = from.cube.1,
+            "shards" => self.shatter.0 = from.shatter.0,
+            "gravity" => self.shatter.1 = from.shatter.1,
+            "turns" => self.spiral.0 = from.spiral.0,
+            "thickness" => self.spiral.1 = from.spiral.1,
+            "tile-size" => self.tile_size = from.tile_size,
+            "softness" => self.softness = from.softness,
+            "sort-threshold" => self.pixel_sort.0 = from.pixel_sort.0,
+            "sort-direction" => self.pixel_sort.1 = from.pixel_sort.1,
+            "matrix" => self.halftone.1 = from.halftone.1,
+            "matrix-size" => self.halftone.0 = from.halftone.0,
+            "blend" => self.exposure.0 = from.exposure.0,
+            "overlay" => (self.exposure.1, self.exposure.2) = (from.exposure.1, from.exposure.2),
+            "edge-threshold" => self.edge_threshold = from.edge_threshold,
+            "plugin" => self.plugin = from.plugin.clone(),
+            "random" => self.random = from.random.clone(),
+            "random-exclude" => self.random_exclude = from.random_exclude.clone(),
+            "invert-y" => self.invert_y = from.invert_y,
+            _ => (),
+        }
+    }
+
+    /// What the transition engine needs to run this. Plugins are run as `simple` transitions,
+    /// since only the daemon can load them, and `random` ones must have been replaced by then
+    #[must_use]
+    pub fn params(&self) -> transition::Params {
+        use transition::Kind;
+        let kind = match self.transition_type {
+            TransitionType::None => Kind::None,
+            TransitionType::Simple | TransitionType::Plugin | TransitionType::Random => {
+                Kind::Simple
+            }
+            TransitionType::Fade => Kind::Fade,
+            TransitionType::Wipe => Kind::Wipe,
+            TransitionType::Grow => Kind::Grow,
+            TransitionType::Outer => Kind::Outer,
+            TransitionType::Wave => Kind::Wave,
+            TransitionType::Ripple => Kind::Ripple,
+            TransitionType::Pixelate => Kind::Pixelate,
+            TransitionType::Blinds => Kind::Blinds,
+            TransitionType::Dissolve => Kind::Dissolve,
+            TransitionType::Clock => Kind::Clock,
+            TransitionType::Cube => Kind::Cube,
+            TransitionType::Push => Kind::Push,
+            TransitionType::ZoomBlur => Kind::ZoomBlur,
+            TransitionType::PixelSort => Kind::PixelSort,
+            TransitionType::Halftone => Kind::Halftone,
+            TransitionType::DoubleExposure => Kind::DoubleExposure,
+            TransitionType::Sketch => Kind::Sketch,
+            TransitionType::Shatter => Kind::Shatter,
+            TransitionType::Spiral => Kind::Spiral,
+            TransitionType::Checkerboard => Kind::Checkerboard,
+            TransitionType::Luma => Kind::Luma,
+        };
+        let (mode, strength, length) = self.exposure;
+        transition::Params {
+            kind,
+            duration: self.duration,
+            step: self.step.get(),
+            angle: self.angle,
+            pos: self.pos.clone(),
+            origins: self.origins.clone(),
+            bezier: self.bezier,
+            spring: self.spring,
+            curve: self.curve.clone(),
+            wave: self.wave,
+            ripple: self.ripple,
+            block_size: self.block_size,
+            slats: self.slats,
+            seed: self.seed,
+            clock: self.clock,
+            cube: self.cube,
+            shatter: self.shatter,
+            spiral: self.spiral,
+            tile_size: self.tile_size,
+            softness: self.softness,
+            feather: self.feather,
+            colorspace: match self.colorspace {
+                Colorspace::Srgb => transitions::Colorspace::Srgb,
+                Colorspace::Linear => transitions::Colorspace::Linear,
+                Colorspace::Oklab => transitions::Colorspace::Oklab,
+            },
+            pixel_sort: self.pixel_sort,
+            halftone: self.halftone,
+            exposure: (
+                match mode {
+                    BlendMode::Screen => transitions::BlendMode::Screen,
+                    BlendMode::Multiply => transitions::BlendMode::Multiply,
+                },
+                strength,
+                length,
+            ),
+            edge_threshold: self.edge_threshold,
+            invert_y: self.invert_y,
+        }
+    }
+
     pub(super) fn serialize(&self, buf: &mut ImageRequestBuilder) {
         let Self {
             transition_type,
@@ -375,8 +645,32 @@ impl Transition {
             angle,
             pos,
             bezier,
+            spring,
+            curve,
+            random,
             wave,
+            ripple,
+            block_size,
+            slats,
+            seed,
+            clock,
+            cube,
+            shatter,
+            spiral,
+            tile_size,
+            softness,
+            feather,
+            colorspace,
+            pixel_sort,
+            halftone,
+            exposure,
+            origins,
+            edge_threshold,
             invert_y,
+            span,
+            plugin,
+            random_exclude,
+            defaulted,
         } = self;
 
         buf.push_byte(*transition_type as u8);
@@ -410,20 +704,80 @@ impl Transition {
         buf.extend(&bezier.3.to_ne_bytes());
         buf.extend(&wave.0.to_ne_bytes());
         buf.extend(&wave.1.to_ne_bytes());
+        buf.extend(&ripple.0.to_ne_bytes());
+        buf.extend(&ripple.1.to_ne_bytes());
+        buf.extend(&block_size.to_ne_bytes());
+        buf.extend(&slats.0.to_ne_bytes());
+        buf.push_byte(slats.1 as u8);
+        buf.extend(&seed.to_ne_bytes());
+        buf.push_byte(clock.0 as u8);
+        buf.extend(&clock.1.to_ne_bytes());
+        buf.push_byte(cube.0 as u8);
+        buf.extend(&cube.1.to_ne_bytes());
+        buf.extend(&shatter.0.to_ne_bytes());
+        buf.extend(&shatter.1.to_ne_bytes());
+        buf.extend(&spiral.0.to_ne_bytes());
+        buf.extend(&spiral.1.to_ne_bytes());
+        buf.extend(&tile_size.to_ne_bytes());
+        buf.extend(&softness.to_ne_bytes());
         buf.push_byte(*invert_y as u8);
+        buf.push_byte(spring.is_some() as u8);
+        let (stiffness, damping, mass) = spring.unwrap_or_default();
+        buf.extend(&stiffness.to_ne_bytes());
+        buf.extend(&damping.to_ne_bytes());
+        buf.extend(&mass.to_ne_bytes());
+        buf.extend(&(curve.len() as u32).to_ne_bytes());
+        for (time, value, bezier) in curve.iter() {
+            buf.extend(&time.to_ne_bytes());
+            buf.extend(&value.to_ne_bytes());
+            buf.extend(&bezier.0.to_ne_bytes());
+            buf.extend(&bezier.1.to_ne_bytes());
+            buf.extend(&bezier.2.to_ne_bytes());
+            buf.extend(&bezier.3.to_ne_bytes());
+        }
+        buf.push_byte(random.len() as u8);
+        for (transition_type, weight) in random.iter() {
+            buf.push_byte(*transition_type as u8);
+            buf.push_byte(*weight);
+        }
+        buf.serialize_bytes(plugin.as_bytes());
+        buf.push_byte(*span as u8);
+        buf.extend(&feather.to_ne_bytes());
+        buf.push_byte(*colorspace as u8);
+        buf.extend(&pixel_sort.0.to_ne_bytes());
+        buf.push_byte(pixel_sort.1 as u8);
+        buf.push_byte(halftone.0);
+        buf.push_byte(halftone.1 as u8);
+        buf.push_byte(exposure.0 as u8);
+        buf.extend(&exposure.1.to_ne_bytes());
+        buf.extend(&exposure.2.to_ne_bytes());
+        buf.push_byte(origins.len() as u8);
+        for origin in origins.iter() {
+            for coord in [&origin.x, &origin.y] {
+                match coord {
+                    Coord::Pixel(f) => {
+                        buf.push_byte(0);
+                        buf.extend(&f.to_ne_bytes());
+                    }
+                    Coord::Percent(f) => {
+                        buf.push_byte(1);
+                        buf.extend(&f.to_ne_bytes());
+                    }
+                }
+            }
+        }
+        buf.extend(&edge_threshold.to_ne_bytes());
+        buf.push_byte(random_exclude.len() as u8);
+        for transition_type in random_exclude.iter() {
+            buf.push_byte(*transition_type as u8);
+        }
+        buf.extend(&defaulted.to_ne_bytes());
     }
 
-    pub(super) fn deserialize(bytes: &[u8]) -> Self {
-        assert!(bytes.len() > 50);
-        let transition_type = match bytes[0] {
-            0 => TransitionType::Simple,
-            1 => TransitionType::Fade,
-            2 => TransitionType::Outer,
-            3 => TransitionType::Wipe,
-            4 => TransitionType::Grow,
-            5 => TransitionType::Wave,
-            _ => TransitionType::None,
-        };
+    /// also returns how many bytes the transition took
+    pub(super) fn deserialize(bytes: &[u8]) -> (Self, usize) {
+        assert!(bytes.len() > 119);
+        let transition_type = TransitionType::from_byte(bytes[0]);
         let duration = f32::from_ne_bytes(bytes[1..5].try_into().unwrap());
         let step = NonZeroU8::new(bytes[5]).expect("received step of 0");
         let fps = u16::from_ne_bytes(bytes[6..8].try_into().unwrap());
@@ -454,9 +808,130 @@ impl Transition {
             f32::from_ne_bytes(bytes[46..50].try_into().unwrap()),
         );
 
-        let invert_y = bytes[50] != 0;
+        let ripple = (
+            f32::from_ne_bytes(bytes[50..54].try_into().unwrap()),
+            f32::from_ne_bytes(bytes[54..58].try_into().unwrap()),
+        );
+
+        let block_size = u16::from_ne_bytes(bytes[58..60].try_into().unwrap());
 
-        Self {
+        let slats = (
+            u16::from_ne_bytes(bytes[60..62].try_into().unwrap()),
+            bytes[62] != 0,
+        );
+
+        let seed = u32::from_ne_bytes(bytes[63..67].try_into().unwrap());
+
+        let clock = (
+            bytes[67] != 0,
+            f32::from_ne_bytes(bytes[68..72].try_into().unwrap()),
+        );
+
+        let cube = (
+            bytes[72] != 0,
+            f32::from_ne_bytes(bytes[73..77].try_into().unwrap()),
+        );
+
+        let shatter = (
+            u16::from_ne_bytes(bytes[77..79].try_into().unwrap()),
+            f32::from_ne_bytes(bytes[79..83].try_into().unwrap()),
+        );
+
+        let spiral = (
+            f32::from_ne_bytes(bytes[83..87].try_into().unwrap()),
+            f32::from_ne_bytes(bytes[87..91].try_into().unwrap()),
+        );
+
+        let tile_size = u16::from_ne_bytes(bytes[91..93].try_into().unwrap());
+
+        let softness = f32::from_ne_bytes(bytes[93..97].try_into().unwrap());
+
+        let invert_y = bytes[97] != 0;
+
+        let spring = (bytes[98] != 0).then(|| {
+            (
+                f32::from_ne_bytes(bytes[99..103].try_into().unwrap()),
+                f32::from_ne_bytes(bytes[103..107].try_into().unwrap()),
+                f32::from_ne_bytes(bytes[107..111].try_into().unwrap()),
+            )
+        });
+
+        let curve_len = u32::from_ne_bytes(bytes[111..115].try_into().unwrap()) as usize;
+        let f32_at = |i: usize| f32::from_ne_bytes(bytes[i..i + 4].try_into().unwrap());
+        let curve = (0..curve_len)
+            .map(|k| {
+                let i = 115 + k * 24;
+                (
+                    f32_at(i),
+                    f32_at(i + 4),
+                    (
+                        f32_at(i + 8),
+                        f32_at(i + 12),
+                        f32_at(i + 16),
+                        f32_at(i + 20),
+                    ),
+                )
+            })
+            .collect();
+        let mut i = 115 + curve_len * 24;
+
+        let random_len = bytes[i] as usize;
+        let random = bytes[i + 1..i + 1 + random_len * 2]
+            .chunks_exact(2)
+            .map(|candidate| (TransitionType::from_byte(candidate[0]), candidate[1]))
+            .collect();
+        i += 1 + random_len * 2;
+
+        let plugin_len = u32::from_ne_bytes(bytes[i..i + 4].try_into().unwrap()) as usize;
+        let plugin = String::from_utf8_lossy(&bytes[i + 4..i + 4 + plugin_len]).into_owned();
+        i += 4 + plugin_len;
+
+        let span = bytes[i] != 0;
+        let feather = f32::from_ne_bytes(bytes[i + 1..i + 5].try_into().unwrap());
+        let colorspace = Colorspace::from_byte(bytes[i + 5]);
+        let pixel_sort = (
+            f32::from_ne_bytes(bytes[i + 6..i + 10].try_into().unwrap()),
+            bytes[i + 10] != 0,
+        );
+        let halftone = (bytes[i + 11], bytes[i + 12] != 0);
+        let exposure = (
+            BlendMode::from_byte(bytes[i + 13]),
+            f32::from_ne_bytes(bytes[i + 14..i + 18].try_into().unwrap()),
+            f32::from_ne_bytes(bytes[i + 18..i + 22].try_into().unwrap()),
+        );
+        i += 22;
+
+        let origins_len = bytes[i] as usize;
+        let coord = |tag: u8, value: &[u8]| {
+            let f = f32::from_ne_bytes(value.try_into().unwrap());
+            if tag == 0 {
+                Coord::Pixel(f)
+            } else {
+                Coord::Percent(f)
+            }
+        };
+        let origins = bytes[i + 1..i + 1 + origins_len * 10]
+            .chunks_exact(10)
+            .map(|origin| Position {
+                x: coord(origin[0], &origin[1..5]),
+                y: coord(origin[5], &origin[6..10]),
+            })
+            .collect();
+        i += 1 + origins_len * 10;
+
+        let edge_threshold = f32::from_ne_bytes(bytes[i..i + 4].try_into().unwrap());
+        i += 4;
+
+        let random_exclude_len = bytes[i] as usize;
+        let random_exclude = bytes[i + 1..i + 1 + random_exclude_len]
+            .iter()
+            .map(|byte| TransitionType::from_byte(*byte))
+            .collect();
+        i += 1 + random_exclude_len;
+
+        let defaulted = u64::from_ne_bytes(bytes[i..i + 8].try_into().unwrap());
+
+        let transition = Self {
             transition_type,
             duration,
             step,
@@ -464,8 +939,99 @@ impl Transition {
             angle,
             pos,
             bezier,
+            spring,
+            curve,
+            random,
             wave,
+            ripple,
+            block_size,
+            slats,
+            seed,
+            clock,
+            cube,
+            shatter,
+            spiral,
+            tile_size,
+            softness,
+            feather,
+            colorspace,
+            pixel_sort,
+            halftone,
+            exposure,
+            origins,
+            edge_threshold,
             invert_y,
+            span,
+            plugin,
+            random_exclude,
+            defaulted,
+        };
+        (transition, i + 8)
+    }
+}
+
+/// The color space the `Fade` transition blends colors in
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Colorspace {
+    /// the sRGB bytes themselves
+    #[default]
+    Srgb = 0,
+    /// linear light
+    Linear = 1,
+    /// OKLab, a perceptual color space
+    Oklab = 2,
+}
+
+impl Colorspace {
+    pub(super) fn from_byte(byte: u8) -> Self {
+        match byte {
+            1 => Self::Linear,
+            2 => Self::Oklab,
+            _ => Self::Srgb,
+        }
+    }
+}
+
+/// How the `DoubleExposure` transition overlays the images
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BlendMode {
+    /// lightens, like two exposures of a film
+    #[default]
+    Screen = 0,
+    /// darkens, like two stacked slides
+    Multiply = 1,
+}
+
+impl BlendMode {
+    pub(super) fn from_byte(byte: u8) -> Self {
+        match byte {
+            1 => Self::Multiply,
+            _ => Self::Screen,
+        }
+    }
+}
+
+/// How `swww clear` changes the wallpapers to its color
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Outro {
+    /// right away
+    #[default]
+    None = 0,
+    /// by fading into it
+    Fade = 1,
+    /// by wiping it in, the other way around from the default `wipe` transition
+    Wipe = 2,
+}
+
+impl Outro {
+    pub(super) fn from_byte(byte: u8) -> Self {
+        match byte {
+            1 => Self::Fade,
+            2 => Self::Wipe,
+            _ => Self::None,
         }
     }
 }
@@ -473,6 +1039,9 @@ impl Transition {
 pub struct ClearSend {
     pub color: [u8; 3],
     pub outputs: Box<[String]>,
+    pub outro: Outro,
+    /// in seconds
+    pub outro_duration: f32,
 }
 
 impl ClearSend {
@@ -480,7 +1049,9 @@ impl ClearSend {
         // 1 - output length
         // 3 - color bytes
         // 4 + output.len() - output len + bytes
-        let len = 4 + self.outputs.iter().map(|o| 4 + o.len()).sum::<usize>();
+        // 1 - outro
+        // 4 - outro duration
+        let len = 9 + self.outputs.iter().map(|o| 4 + o.len()).sum::<usize>();
         let mut mmap = Mmap::create(len);
         let bytes = mmap.slice_mut();
         bytes[0] = self.outputs.len() as u8; // we assume someone does not have more than
@@ -493,6 +1064,8 @@ impl ClearSend {
             i += 4 + len as usize;
         }
         bytes[i..i + 3].copy_from_slice(&self.color);
+        bytes[i + 3] = self.outro as u8;
+        bytes[i + 4..i + 8].copy_from_slice(&self.outro_duration.to_ne_bytes());
         mmap
     }
 }
@@ -500,6 +1073,698 @@ impl ClearSend {
 pub struct ClearReq {
     pub color: [u8; 3],
     pub outputs: Box<[MmappedStr]>,
+    pub outro: Outro,
+    pub outro_duration: f32,
+}
+
+pub struct SetVarSend {
+    pub key: String,
+    pub value: String,
+}
+
+impl SetVarSend {
+    pub fn create_request(self) -> Mmap {
+        // 4 + key.len() - key len + bytes
+        // 4 + value.len() - value len + bytes
+        let len = 8 + self.key.len() + self.value.len();
+        let mut mmap = Mmap::create(len);
+        let bytes = mmap.slice_mut();
+        let i = serialize_string(bytes, &self.key);
+        serialize_string(&mut bytes[i..], &self.value);
+        mmap
+    }
+}
+
+pub struct SetVarReq {
+    pub key: MmappedStr,
+    pub value: MmappedStr,
+}
+
+pub struct GetVarSend {
+    pub key: String,
+}
+
+impl GetVarSend {
+    pub fn create_request(self) -> Mmap {
+        let mut mmap = Mmap::create(4 + self.key.len());
+        serialize_string(mmap.slice_mut(), &self.key);
+        mmap
+    }
+}
+
+pub struct GetVarReq {
+    pub key: MmappedStr,
+}
+
+pub struct ListVarsSend {
+    pub prefix: String,
+}
+
+impl ListVarsSend {
+    pub fn create_request(self) -> Mmap {
+        let mut mmap = Mmap::create(4 + self.prefix.len());
+        serialize_string(mmap.slice_mut(), &self.prefix);
+        mmap
+    }
+}
+
+pub struct ListVarsReq {
+    pub prefix: MmappedStr,
+}
+
+/// A grayscale mask, with its dimensions
+pub type GrayMask = ((u32, u32), Box<[u8]>);
+
+/// Changes the transition defaults, which the daemon fills in the transitions of later image
+/// requests (see `Transition::defaulted`)
+pub struct TransitionDefaultsSend {
+    /// the defaults to change, as (name, value), with the names of `TRANSITION_DEFAULTS`. An
+    /// empty value forgets the default
+    pub values: Box<[(String, String)]>,
+    /// a transition with the values of the defaults that are set
+    pub transition: Transition,
+    /// the mask of the `mask` default
+    pub mask: Option<GrayMask>,
+}
+
+impl TransitionDefaultsSend {
+    pub fn create_request(self) -> Mmap {
+        // the builder's first byte, the number of images, is left unused
+        let mut buf = ImageRequestBuilder::new();
+        self.transition.serialize(&mut buf);
+        buf.push_byte(self.values.len() as u8);
+        for (name, value) in self.values.iter() {
+            buf.serialize_bytes(name.as_bytes());
+            buf.serialize_bytes(value.as_bytes());
+        }
+        match &self.mask {
+            Some((dim, mask)) => {
+                buf.push_byte(1);
+                buf.extend(&dim.0.to_ne_bytes());
+                buf.extend(&dim.1.to_ne_bytes());
+                buf.serialize_bytes(mask);
+            }
+            None => buf.push_byte(0),
+        }
+        buf.build()
+    }
+}
+
+pub struct TransitionDefaultsReq {
+    pub values: Box<[(MmappedStr, MmappedStr)]>,
+    pub transition: Box<Transition>,
+    pub mask: Option<((u32, u32), MmappedBytes)>,
+}
+
+impl TransitionDefaultsReq {
+    pub(super) fn deserialize(mmap: &Mmap) -> Self {
+        let bytes = mmap.slice();
+        let (transition, offset) = Transition::deserialize(&bytes[1..]);
+        let mut i = 1 + offset;
+
+        let len = bytes[i] as usize;
+        i += 1;
+        let mut values = Vec::with_capacity(len);
+        for _ in 0..len {
+            let name = MmappedStr::new(mmap, &bytes[i..]);
+            i += 4 + name.str().len();
+            let value = MmappedStr::new(mmap, &bytes[i..]);
+            i += 4 + value.str().len();
+            values.push((name, value));
+        }
+
+        let mask = (bytes[i] == 1).then(|| {
+            let dim = (
+                u32::from_ne_bytes(bytes[i + 1..i + 5].try_into().unwrap()),
+                u32::from_ne_bytes(bytes[i + 5..i + 9].try_into().unwrap()),
+            );
+            (dim, MmappedBytes::new(mmap, &bytes[i + 9..]))
+        });
+
+        Self {
+            values: values.into(),
+            transition: Box::new(transition),
+            mask,
+        }
+    }
+}
+
+/// Accessibility filters the daemon can apply to each output
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorFilter {
+    #[default]
+    None = 0,
+    Invert = 1,
+    HighContrast = 2,
+    ProtanopiaSimulation = 3,
+    DeuteranopiaSimulation = 4,
+    TritanopiaSimulation = 5,
+    ProtanopiaCompensation = 6,
+    DeuteranopiaCompensation = 7,
+    TritanopiaCompensation = 8,
+}
+
+impl ColorFilter {
+    fn from_u8(byte: u8) -> Self {
+        match byte {
+            1 => Self::Invert,
+            2 => Self::HighContrast,
+            3 => Self::ProtanopiaSimulation,
+            4 => Self::DeuteranopiaSimulation,
+            5 => Self::TritanopiaSimulation,
+            6 => Self::ProtanopiaCompensation,
+            7 => Self::DeuteranopiaCompensation,
+            8 => Self::TritanopiaCompensation,
+            _ => Self::None,
+        }
+    }
+}
+
+pub struct FilterSend {
+    pub filter: ColorFilter,
+    pub outputs: Box<[String]>,
+}
+
+impl FilterSend {
+    pub fn create_request(self) -> Mmap {
+        // 1 - filter
+        // 1 - output length
+        // 4 + output.len() - output len + bytes
+        let len = 2 + self.outputs.iter().map(|o| 4 + o.len()).sum::<usize>();
+        let mut mmap = Mmap::create(len);
+        let bytes = mmap.slice_mut();
+        bytes[0] = self.filter as u8;
+        bytes[1] = self.outputs.len() as u8;
+        let mut i = 2;
+        for output in self.outputs.iter() {
+            i += serialize_string(&mut bytes[i..], output);
+        }
+        mmap
+    }
+}
+
+pub struct FilterReq {
+    pub filter: ColorFilter,
+    pub outputs: Box<[MmappedStr]>,
+}
+
+impl FilterReq {
+    pub(super) fn deserialize(mmap: &Mmap) -> Self {
+        let bytes = mmap.slice();
+        let filter = ColorFilter::from_u8(bytes[0]);
+        let len = bytes[1] as usize;
+        let mut outputs = Vec::with_capacity(len);
+        let mut i = 2;
+        for _ in 0..len {
+            let output = MmappedStr::new(mmap, &bytes[i..]);
+            i += 4 + output.str().len();
+            outputs.push(output);
+        }
+        Self {
+            filter,
+            outputs: outputs.into(),
+        }
+    }
+}
+
+/// Blends the specified outputs towards a color, after every other filter
+pub struct DimSend {
+    pub color: [u8; 3],
+    /// 0 leaves the outputs alone, 255 paints them with `color`
+    pub strength: u8,
+    pub outputs: Box<[String]>,
+}
+
+impl DimSend {
+    pub fn create_request(self) -> Mmap {
+        // 3 - color
+        // 1 - strength
+        // 1 - output length
+        // 4 + output.len() - output len + bytes
+        let len = 5 + self.outputs.iter().map(|o| 4 + o.len()).sum::<usize>();
+        let mut mmap = Mmap::create(len);
+        let bytes = mmap.slice_mut();
+        bytes[0..3].copy_from_slice(&self.color);
+        bytes[3] = self.strength;
+        bytes[4] = self.outputs.len() as u8;
+        let mut i = 5;
+        for output in self.outputs.iter() {
+            i += serialize_string(&mut bytes[i..], output);
+        }
+        mmap
+    }
+}
+
+pub struct DimReq {
+    pub color: [u8; 3],
+    pub strength: u8,
+    pub outputs: Box<[MmappedStr]>,
+}
+
+impl DimReq {
+    pub(super) fn deserialize(mmap: &Mmap) -> Self {
+        let bytes = mmap.slice();
+        let color = [bytes[0], bytes[1], bytes[2]];
+        let strength = bytes[3];
+        let len = bytes[4] as usize;
+        let mut outputs = Vec::with_capacity(len);
+        let mut i = 5;
+        for _ in 0..len {
+            let output = MmappedStr::new(mmap, &bytes[i..]);
+            i += 4 + output.str().len();
+            outputs.push(output);
+        }
+        Self {
+            color,
+            strength,
+            outputs: outputs.into(),
+        }
+    }
+}
+
+/// The language a shader wallpaper is written in
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShaderLanguage {
+    Wgsl = 0,
+    Glsl = 1,
+}
+
+/// A fragment shader the daemon runs continuously as the wallpaper of the specified outputs
+pub struct ShaderSend {
+    pub source: String,
+    pub language: ShaderLanguage,
+    /// the most frames a second to render
+    pub fps: u16,
+    /// render at 1/`downscale` of the outputs' size, and let the compositor upscale it. 0 lets the
+    /// daemon choose, depending on whether it renders on the gpu
+    pub downscale: u8,
+    pub outputs: Box<[String]>,
+}
+
+impl ShaderSend {
+    pub fn create_request(self) -> Mmap {
+        // 1 - language
+        // 2 - fps
+        // 1 - downscale
+        // 1 - output length
+        // 4 + output.len() - output len + bytes
+        // 4 + source.len() - source len + bytes
+        let len =
+            5 + self.outputs.iter().map(|o| 4 + o.len()).sum::<usize>() + 4 + self.source.len();
+        let mut mmap = Mmap::create(len);
+        let bytes = mmap.slice_mut();
+        bytes[0] = self.language as u8;
+        bytes[1..3].copy_from_slice(&self.fps.to_ne_bytes());
+        bytes[3] = self.downscale;
+        bytes[4] = self.outputs.len() as u8;
+        let mut i = 5;
+        for output in self.outputs.iter() {
+            i += serialize_string(&mut bytes[i..], output);
+        }
+        serialize_string(&mut bytes[i..], &self.source);
+        mmap
+    }
+}
+
+pub struct ShaderReq {
+    pub source: MmappedStr,
+    pub language: ShaderLanguage,
+    pub fps: u16,
+    pub downscale: u8,
+    pub outputs: Box<[MmappedStr]>,
+}
+
+impl ShaderReq {
+    pub(super) fn deserialize(mmap: &Mmap) -> Self {
+        let bytes = mmap.slice();
+        let language = match bytes[0] {
+            1 => ShaderLanguage::Glsl,
+            _ => ShaderLanguage::Wgsl,
+        };
+        let fps = u16::from_ne_bytes([bytes[1], bytes[2]]);
+        let downscale = bytes[3];
+        let len = bytes[4] as usize;
+        let mut outputs = Vec::with_capacity(len);
+        let mut i = 5;
+        for _ in 0..len {
+            let output = MmappedStr::new(mmap, &bytes[i..]);
+            i += 4 + output.str().len();
+            outputs.push(output);
+        }
+        let source = MmappedStr::new(mmap, &bytes[i..]);
+        Self {
+            source,
+            language,
+            fps,
+            downscale,
+            outputs: outputs.into(),
+        }
+    }
+}
+
+/// What the daemon checks before running the requests of `swww batch`'s `if`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Condition {
+    /// an output with this name is connected
+    OutputExists(String),
+    /// every output (or only `output`, if given) displays the image at `path`. Negated by `equal`
+    /// being false
+    CurrentImage {
+        output: Option<String>,
+        path: String,
+        equal: bool,
+    },
+    /// the variable with this name (see `swww set-var`) is set to this value
+    Var(String, String),
+}
+
+impl Condition {
+    pub(super) fn serialized_size(&self) -> usize {
+        1 + match self {
+            Self::OutputExists(output) => 4 + output.len(),
+            Self::CurrentImage { output, path, .. } => {
+                4 + output.as_deref().map_or(0, str::len) + 4 + path.len()
+            }
+            Self::Var(name, value) => 4 + name.len() + 4 + value.len(),
+        }
+    }
+
+    pub(super) fn serialize(&self, buf: &mut [u8]) -> usize {
+        match self {
+            Self::OutputExists(output) => {
+                buf[0] = 0;
+                1 + serialize_string(&mut buf[1..], output)
+            }
+            Self::CurrentImage {
+                output,
+                path,
+                equal,
+            } => {
+                buf[0] = if *equal { 1 } else { 2 };
+                let mut i = 1;
+                i += serialize_string(&mut buf[i..], output.as_deref().unwrap_or_default());
+                i += serialize_string(&mut buf[i..], path);
+                i
+            }
+            Self::Var(name, value) => {
+                buf[0] = 3;
+                let mut i = 1;
+                i += serialize_string(&mut buf[i..], name);
+                i += serialize_string(&mut buf[i..], value);
+                i
+            }
+        }
+    }
+
+    pub(super) fn deserialize(bytes: &[u8]) -> (Self, usize) {
+        let first = deserialize_string(&bytes[1..]);
+        let mut i = 1 + 4 + first.len();
+        let condition = match bytes[0] {
+            0 => Self::OutputExists(first),
+            3 => {
+                let value = deserialize_string(&bytes[i..]);
+                i += 4 + value.len();
+                Self::Var(first, value)
+            }
+            kind => {
+                let path = deserialize_string(&bytes[i..]);
+                i += 4 + path.len();
+                Self::CurrentImage {
+                    output: (!first.is_empty()).then_some(first),
+                    path,
+                    equal: kind == 1,
+                }
+            }
+        };
+        (condition, i)
+    }
+}
+
+pub struct IfReq {
+    pub condition: Condition,
+    pub then: Box<[RequestRecv]>,
+    pub otherwise: Box<[RequestRecv]>,
+}
+
+/// The transition the daemon ended up using for some outputs, after picking `Random` transitions
+/// and applying its sanity limits
+pub struct TransitionUsed {
+    pub outputs: Box<[String]>,
+    pub transition_type: TransitionType,
+    pub duration: f32,
+    pub fps: u16,
+    /// the parameters the daemon had to change, as (name, requested value, used value)
+    pub clamped: Box<[(String, String, String)]>,
+}
+
+impl TransitionUsed {
+    pub(super) fn serialized_size(&self) -> usize {
+        1 // outputs len
+            + self.outputs.iter().map(|output| 4 + output.len()).sum::<usize>()
+            + 1 // transition type
+            + 4 // duration
+            + 2 // fps
+            + 1 // clamped len
+            + self
+                .clamped
+                .iter()
+                .map(|(name, requested, used)| 12 + name.len() + requested.len() + used.len())
+                .sum::<usize>()
+    }
+
+    pub(super) fn serialize(&self, buf: &mut [u8]) -> usize {
+        buf[0] = self.outputs.len() as u8;
+        let mut i = 1;
+        for output in self.outputs.iter() {
+            i += serialize_string(&mut buf[i..], output);
+        }
+        buf[i] = self.transition_type as u8;
+        buf[i + 1..i + 5].copy_from_slice(&self.duration.to_ne_bytes());
+        buf[i + 5..i + 7].copy_from_slice(&self.fps.to_ne_bytes());
+        buf[i + 7] = self.clamped.len() as u8;
+        i += 8;
+        for (name, requested, used) in self.clamped.iter() {
+            i += serialize_string(&mut buf[i..], name);
+            i += serialize_string(&mut buf[i..], requested);
+            i += serialize_string(&mut buf[i..], used);
+        }
+        i
+    }
+
+    pub(super) fn deserialize(bytes: &[u8]) -> (Self, usize) {
+        let next_string = |i: &mut usize| {
+            let s = deserialize_string(&bytes[*i..]);
+            *i += 4 + s.len();
+            s
+        };
+        let mut i = 1;
+        let outputs = (0..bytes[0]).map(|_| next_string(&mut i)).collect();
+        let transition_type = TransitionType::from_byte(bytes[i]);
+        let duration = f32::from_ne_bytes(bytes[i + 1..i + 5].try_into().unwrap());
+        let fps = u16::from_ne_bytes(bytes[i + 5..i + 7].try_into().unwrap());
+        let clamped_len = bytes[i + 7];
+        i += 8;
+        let clamped = (0..clamped_len)
+            .map(|_| {
+                let name = next_string(&mut i);
+                let requested = next_string(&mut i);
+                (name, requested, next_string(&mut i))
+            })
+            .collect();
+        let used = Self {
+            outputs,
+            transition_type,
+            duration,
+            fps,
+            clamped,
+        };
+        (used, i)
+    }
+}
+
+/// What the running daemon was built with and is using, so clients can explain why something it
+/// lacks will not work
+pub struct Capabilities {
+    /// the optional cargo features the daemon was built with, like `wasm`
+    pub features: Box<[String]>,
+    /// the wayland globals the daemon bound, with the version it bound each of them at
+    pub protocols: Box<[(String, u32)]>,
+}
+
+impl Capabilities {
+    #[must_use]
+    pub fn has_feature(&self, feature: &str) -> bool {
+        self.features.iter().any(|f| f == feature)
+    }
+
+    pub(super) fn serialized_size(&self) -> usize {
+        4 // features len
+            + self.features.iter().map(|f| 4 + f.len()).sum::<usize>()
+            + 4 // protocols len
+            + self.protocols.iter().map(|(p, _)| 8 + p.len()).sum::<usize>()
+    }
+
+    pub(super) fn serialize(&self, buf: &mut [u8]) -> usize {
+        buf[0..4].copy_from_slice(&(self.features.len() as u32).to_ne_bytes());
+        let mut i = 4;
+        for feature in self.features.iter() {
+            i += serialize_string(&mut buf[i..], feature);
+        }
+        buf[i..i + 4].copy_from_slice(&(self.protocols.len() as u32).to_ne_bytes());
+        i += 4;
+        for (protocol, version) in self.protocols.iter() {
+            i += serialize_string(&mut buf[i..], protocol);
+            buf[i..i + 4].copy_from_slice(&version.to_ne_bytes());
+            i += 4;
+        }
+        i
+    }
+
+    pub(super) fn deserialize(bytes: &[u8]) -> Self {
+        let next_u32 = |i: &mut usize| {
+            let n = u32::from_ne_bytes(bytes[*i..*i + 4].try_into().unwrap());
+            *i += 4;
+            n
+        };
+        let next_string = |i: &mut usize| {
+            let s = deserialize_string(&bytes[*i..]);
+            *i += 4 + s.len();
+            s
+        };
+        let mut i = 0;
+        let features = (0..next_u32(&mut i)).map(|_| next_string(&mut i)).collect();
+        let protocols = (0..next_u32(&mut i))
+            .map(|_| {
+                let protocol = next_string(&mut i);
+                (protocol, next_u32(&mut i))
+            })
+            .collect();
+        Self {
+            features,
+            protocols,
+        }
+    }
+}
+
+/// What a wallpaper's surface is waiting on, to diagnose animations that get stuck
+pub struct SurfaceInfo {
+    pub name: String,
+    /// whether the compositor sent the surface its first configure
+    pub configured: bool,
+    /// whether the last frame callback fired, so that we may attach the next frame
+    pub draw_ready: bool,
+    /// the object id of the last frame callback we requested
+    pub frame_callback: u32,
+    /// whether an animation or transition is drawing to the surface
+    pub animating: bool,
+    /// the object id of each of the surface's buffers, and whether the compositor released it
+    pub buffers: Box<[(u32, bool)]>,
+    /// the object id of the buffer we attach next, if we have drawn to any
+    pub committed: Option<u32>,
+    pub error: Option<String>,
+}
+
+impl SurfaceInfo {
+    pub(super) fn serialized_size(&self) -> usize {
+        4 + self.name.len()
+            + 1 // configured
+            + 1 // draw ready
+            + 4 // frame callback
+            + 1 // animating
+            + 4 // buffers len
+            + 5 * self.buffers.len()
+            + 4 // committed
+            + 4 + self.error.as_ref().map_or(0, |e| e.len())
+    }
+
+    pub(super) fn serialize(&self, buf: &mut [u8]) -> usize {
+        let mut i = serialize_string(buf, &self.name);
+        buf[i] = self.configured as u8;
+        buf[i + 1] = self.draw_ready as u8;
+        buf[i + 2..i + 6].copy_from_slice(&self.frame_callback.to_ne_bytes());
+        buf[i + 6] = self.animating as u8;
+        buf[i + 7..i + 11].copy_from_slice(&(self.buffers.len() as u32).to_ne_bytes());
+        i += 11;
+        for (buffer, released) in self.buffers.iter() {
+            buf[i..i + 4].copy_from_slice(&buffer.to_ne_bytes());
+            buf[i + 4] = *released as u8;
+            i += 5;
+        }
+        // object ids are never 0
+        buf[i..i + 4].copy_from_slice(&self.committed.unwrap_or(0).to_ne_bytes());
+        i += 4;
+        // an empty string means there is no error
+        i + serialize_string(&mut buf[i..], self.error.as_deref().unwrap_or(""))
+    }
+
+    pub(super) fn deserialize(bytes: &[u8]) -> (Self, usize) {
+        let next_u32 = |i: usize| u32::from_ne_bytes(bytes[i..i + 4].try_into().unwrap());
+        let name = deserialize_string(bytes);
+        let mut i = 4 + name.len();
+        let configured = bytes[i] != 0;
+        let draw_ready = bytes[i + 1] != 0;
+        let frame_callback = next_u32(i + 2);
+        let animating = bytes[i + 6] != 0;
+        let buffers_len = next_u32(i + 7) as usize;
+        i += 11;
+        let buffers = (0..buffers_len)
+            .map(|n| (next_u32(i + 5 * n), bytes[i + 5 * n + 4] != 0))
+            .collect();
+        i += 5 * buffers_len;
+        let committed = Some(next_u32(i)).filter(|&id| id != 0);
+        i += 4;
+        let error = deserialize_string(&bytes[i..]);
+        i += 4 + error.len();
+        let info = Self {
+            name,
+            configured,
+            draw_ready,
+            frame_callback,
+            animating,
+            buffers,
+            committed,
+            error: Some(error).filter(|e| !e.is_empty()),
+        };
+        (info, i)
+    }
+}
+
+impl fmt::Display for SurfaceInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let yes_no = |b: bool| if b { "yes" } else { "no" };
+        writeln!(f, "{}:", self.name)?;
+        writeln!(f, "  configured: {}", yes_no(self.configured))?;
+        writeln!(f, "  draw ready: {}", yes_no(self.draw_ready))?;
+        writeln!(
+            f,
+            "  frame callback: {} ({})",
+            self.frame_callback,
+            if self.draw_ready { "fired" } else { "pending" }
+        )?;
+        writeln!(f, "  animating: {}", yes_no(self.animating))?;
+        for &(buffer, released) in self.buffers.iter() {
+            let state = if released {
+                "released"
+            } else {
+                "held by the compositor"
+            };
+            let committed = if self.committed == Some(buffer) {
+                " (committed)"
+            } else {
+                ""
+            };
+            writeln!(f, "  buffer {buffer}: {state}{committed}")?;
+        }
+        if self.buffers.is_empty() {
+            writeln!(f, "  buffers: none")?;
+        }
+        match &self.error {
+            Some(e) => write!(f, "  error: {e}"),
+            None => write!(f, "  error: none"),
+        }
+    }
 }
 
 pub struct ImgSend {
@@ -507,6 +1772,11 @@ pub struct ImgSend {
     pub dim: (u32, u32),
     pub format: PixelFormat,
     pub img: Box<[u8]>,
+    /// the `Luma` transition's mask, with a byte per pixel
+    pub mask: Option<Box<[u8]>>,
+    /// whether `dim` was made smaller than the outputs' with `swww img --max-anim-resolution`.
+    /// Otherwise, it must be exactly theirs
+    pub reduced: bool,
 }
 
 pub struct ImgReq {
@@ -514,6 +1784,9 @@ pub struct ImgReq {
     pub dim: (u32, u32),
     pub format: PixelFormat,
     pub img: MmappedBytes,
+    pub mask: Option<MmappedBytes>,
+    /// see `ImgSend::reduced`
+    pub reduced: bool,
 }
 
 impl ImgReq {
@@ -539,12 +1812,26 @@ impl ImgReq {
         };
         i += 1;
 
+        let mask = if bytes[i] == 1 {
+            let mask = MmappedBytes::new(mmap, &bytes[i + 1..]);
+            i += 4 + mask.bytes().len();
+            Some(mask)
+        } else {
+            None
+        };
+        i += 1;
+
+        let reduced = bytes[i] == 1;
+        i += 1;
+
         (
             Self {
                 path,
                 dim,
                 format,
                 img,
+                mask,
+                reduced,
             },
             i,
         )
@@ -553,17 +1840,42 @@ impl ImgReq {
 
 pub struct Animation {
     pub animation: Box<[(BitPack, Duration)]>,
+    /// For time of day wallpapers: the local time, in seconds after midnight, at which the first
+    /// frame starts. The frames then follow the wall clock instead of playing one after the other
+    pub day_start: Option<u32>,
+    /// How many times the animation plays before stopping on its last frame, or `None` to play
+    /// forever
+    pub plays: Option<u16>,
+    /// How many frames, at the end of `animation`, lead from the last frame back to the first one.
+    /// They are skipped on the last play, so that it stops on the last frame
+    pub wrap_len: u32,
 }
 
 impl Animation {
     pub(crate) fn serialize(&self, buf: &mut ImageRequestBuilder) {
-        let Self { animation } = self;
+        let Self {
+            animation,
+            day_start,
+            plays,
+            wrap_len,
+        } = self;
 
         buf.extend(&(animation.len() as u32).to_ne_bytes());
         for (bitpack, duration) in animation.iter() {
             bitpack.serialize(buf);
             buf.extend(&duration.as_secs_f64().to_ne_bytes())
         }
+        match day_start {
+            Some(start) => {
+                buf.extend(&[1]);
+                buf.extend(&start.to_ne_bytes());
+            }
+            None => buf.extend(&[0]),
+        }
+        buf.extend(&[1]);
+        // 0 plays forever
+        buf.extend(&plays.unwrap_or(0).to_ne_bytes());
+        buf.extend(&wrap_len.to_ne_bytes());
     }
 
     pub(crate) fn deserialize(mmap: &Mmap, bytes: &[u8]) -> (Self, usize) {
@@ -579,10 +1891,32 @@ impl Animation {
             i += 8;
             animation.push((anim, duration));
         }
+        // animations cached before time of day wallpapers end here
+        let day_start = match bytes.get(i) {
+            Some(1) => Some(u32::from_ne_bytes(bytes[i + 1..i + 5].try_into().unwrap())),
+            _ => None,
+        };
+        i += if day_start.is_some() { 5 } else { 1 };
+        // and those cached before loop counts here
+        let (plays, wrap_len) = match bytes.get(i) {
+            Some(1) => {
+                let plays = u16::from_ne_bytes(bytes[i + 1..i + 3].try_into().unwrap());
+                let wrap_len = u32::from_ne_bytes(bytes[i + 3..i + 7].try_into().unwrap());
+                i += 7;
+                (Some(plays).filter(|&plays| plays != 0), wrap_len)
+            }
+            _ => {
+                i += 1;
+                (None, 0)
+            }
+        };
 
         (
             Self {
                 animation: animation.into(),
+                day_start,
+                plays,
+                wrap_len,
             },
             i,
         )
@@ -590,13 +1924,22 @@ impl Animation {
 }
 
 pub struct ImageReq {
-    pub transition: Transition,
+    /// the transition of each image, towards its outputs
+    pub transitions: Box<[Transition]>,
     pub imgs: Box<[ImgReq]>,
     pub outputs: Box<[Box<[MmappedStr]>]>,
     pub animations: Option<Box<[Animation]>>,
 }
 
-fn deserialize_string(bytes: &[u8]) -> String {
+/// writes the string's length followed by its bytes, returning how many bytes were written
+pub(super) fn serialize_string(buf: &mut [u8], s: &str) -> usize {
+    let len = s.len();
+    buf[0..4].copy_from_slice(&(len as u32).to_ne_bytes());
+    buf[4..4 + len].copy_from_slice(s.as_bytes());
+    4 + len
+}
+
+pub(super) fn deserialize_string(bytes: &[u8]) -> String {
     let size = u32::from_ne_bytes(bytes[0..4].try_into().unwrap()) as usize;
     std::str::from_utf8(&bytes[4..4 + size])
         .expect("received a non utf8 string from socket")
@@ -32,21 +32,27 @@ impl Mmap {
     #[inline]
     #[must_use]
     pub fn create(len: usize) -> Self {
-        let fd = Self::mmap_fd().unwrap();
-        rustix::io::retry_on_intr(|| rustix::fs::ftruncate(&fd, len as u64)).unwrap();
+        Self::try_create(len).unwrap()
+    }
+
+    /// Like `create`, but returns an error instead of panicking if we cannot get the memory
+    #[inline]
+    pub fn try_create(len: usize) -> io::Result<Self> {
+        let fd = Self::mmap_fd()?;
+        rustix::io::retry_on_intr(|| rustix::fs::ftruncate(&fd, len as u64))?;
 
         let ptr = unsafe {
-            let ptr = mmap(std::ptr::null_mut(), len, Self::PROT, Self::FLAGS, &fd, 0).unwrap();
+            let ptr = mmap(std::ptr::null_mut(), len, Self::PROT, Self::FLAGS, &fd, 0)?;
             // SAFETY: the function above will never return a null pointer if it succeeds
             // POSIX says that the implementation will never select an address at 0
             NonNull::new_unchecked(ptr)
         };
-        Self {
+        Ok(Self {
             fd,
             ptr,
             len,
             mmaped: true,
-        }
+        })
     }
 
     #[cfg(target_os = "linux")]
@@ -121,8 +127,12 @@ impl Mmap {
     ///
     /// Because `unmap`, above, is only used in the daemon, this is also only used there
     pub fn ensure_mapped(&mut self) {
+        self.try_ensure_mapped().unwrap()
+    }
+
+    #[inline]
+    pub fn try_ensure_mapped(&mut self) -> io::Result<()> {
         if !self.mmaped {
-            self.mmaped = true;
             self.ptr = unsafe {
                 let ptr = mmap(
                     std::ptr::null_mut(),
@@ -131,18 +141,27 @@ impl Mmap {
                     Self::FLAGS,
                     &self.fd,
                     0,
-                )
-                .unwrap();
+                )?;
                 // SAFETY: the function above will never return a null pointer if it succeeds
                 // POSIX says that the implementation will never select an address at 0
                 NonNull::new_unchecked(ptr)
             };
+            self.mmaped = true;
         }
+        Ok(())
     }
 
     #[inline]
     pub fn remap(&mut self, new: usize) {
-        io::retry_on_intr(|| fs::ftruncate(&self.fd, new as u64)).unwrap();
+        self.try_remap(new).unwrap()
+    }
+
+    /// Like `remap`, but returns an error instead of panicking if we cannot get the memory
+    ///
+    /// On error, the memory is left unmapped
+    #[inline]
+    pub fn try_remap(&mut self, new: usize) -> io::Result<()> {
+        io::retry_on_intr(|| fs::ftruncate(&self.fd, new as u64))?;
 
         #[cfg(target_os = "linux")]
         {
@@ -156,27 +175,15 @@ impl Mmap {
                 let ptr = unsafe { NonNull::new_unchecked(ptr) };
                 self.ptr = ptr;
                 self.len = new;
-                return;
+                return Ok(());
             }
         }
 
         self.unmap();
 
         self.len = new;
-        self.ptr = unsafe {
-            let ptr = mmap(
-                std::ptr::null_mut(),
-                self.len,
-                Self::PROT,
-                Self::FLAGS,
-                &self.fd,
-                0,
-            )
-            .unwrap();
-            // SAFETY: the function above will never return a null pointer if it succeeds
-            // POSIX says that the implementation will never select an address at 0
-            NonNull::new_unchecked(ptr)
-        };
+        self.mmaped = false;
+        self.try_ensure_mapped()
     }
 
     #[must_use]
@@ -224,9 +231,45 @@ impl Mmap {
 
     #[inline]
     #[must_use]
-    pub fn fd(&self) -> BorrowedFd {
+    pub fn fd(&self) -> BorrowedFd<'_> {
         self.fd.as_fd()
     }
+
+    /// The start of the mapping, for memory someone else writes to while we hold it (see
+    /// `ipc::StreamRing`), which we must not make a slice of
+    #[inline]
+    #[must_use]
+    pub(crate) fn as_ptr(&self) -> *mut u8 {
+        self.ptr.as_ptr().cast()
+    }
+
+    /// Another mapping of the same memory, with its own file descriptor, so that it can be sent
+    /// away while we keep this one
+    pub(crate) fn try_clone(&self) -> io::Result<Self> {
+        let fd = self.fd.try_clone().map_err(|e| {
+            e.raw_os_error()
+                .map_or(Errno::NOMEM, Errno::from_raw_os_error)
+        })?;
+        let ptr = unsafe {
+            let ptr = mmap(
+                std::ptr::null_mut(),
+                self.len,
+                Self::PROT,
+                Self::FLAGS,
+                &fd,
+                0,
+            )?;
+            // SAFETY: the function above will never return a null pointer if it succeeds
+            // POSIX says that the implementation will never select an address at 0
+            NonNull::new_unchecked(ptr)
+        };
+        Ok(Self {
+            fd,
+            ptr,
+            len: self.len,
+            mmaped: true,
+        })
+    }
 }
 
 impl Drop for Mmap {
@@ -0,0 +1,84 @@
+/*
+ * The interface of swww-daemon's transition plugins.
+ *
+ * A plugin is a shared library in $XDG_CONFIG_HOME/swww/transitions/ (or in
+ * ~/.config/swww/transitions/), called <name>.so, that exports the functions
+ * below. `swww img --transition-type plugin --transition-plugin <name>` then
+ * runs it. Build it with something like:
+ *
+ *     cc -shared -fPIC -O2 -o ~/.config/swww/transitions/name.so name.c
+ *
+ * Plugins can also be WebAssembly modules, called <name>.wasm, which run
+ * sandboxed; see daemon/src/animations/wasm.rs for their interface, or
+ * fragment shaders, called <name>.wgsl or <name>.glsl, which run on the gpu;
+ * see daemon/src/animations/shader.rs.
+ *
+ * The daemon loads the plugin again for every transition, and calls:
+ *
+ * 1. swww_transition_init once;
+ * 2. swww_transition_frame for every frame and every output the transition
+ *    runs on, from the same thread;
+ * 3. swww_transition_deinit once, after the last frame.
+ *
+ * After the last frame, the daemon finishes with a quick simple transition,
+ * so it does not matter if the canvas is not exactly the new image by then.
+ */
+#ifndef SWWW_TRANSITION_H
+#define SWWW_TRANSITION_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#define SWWW_TRANSITION_ABI_VERSION 1
+
+/* the byte order of the pixels, as in `swww-daemon --format` */
+enum swww_pixel_format {
+	SWWW_PIXEL_FORMAT_BGR = 0,
+	SWWW_PIXEL_FORMAT_RGB = 1,
+	SWWW_PIXEL_FORMAT_XBGR = 2,
+	SWWW_PIXEL_FORMAT_XRGB = 3,
+};
+
+struct swww_transition_params {
+	uint32_t width;
+	uint32_t height;
+	/* bytes per pixel: 3 or 4 */
+	uint32_t channels;
+	/* an enum swww_pixel_format */
+	uint32_t format;
+	/* --transition-duration, in seconds */
+	float duration;
+	/* --transition-step */
+	uint32_t step;
+	/* --transition-seed, random if it was not given */
+	uint32_t seed;
+	/* --transition-angle, in degrees */
+	double angle;
+	/* --transition-pos, in pixels from the top left corner */
+	float x;
+	float y;
+};
+
+/* must return SWWW_TRANSITION_ABI_VERSION */
+uint32_t swww_transition_abi_version(void);
+
+/*
+ * Starts a transition. Returns the plugin's state, given to the other
+ * functions, or NULL if it failed, in which case the daemon does a simple
+ * transition instead. `params` is only valid during the call.
+ */
+void *swww_transition_init(const struct swww_transition_params *params);
+
+/*
+ * Draws a frame. `canvas` holds what is on the screen, and should be changed in
+ * place towards `new_img`. Both are `len` bytes long: height rows of width
+ * pixels of channels bytes. `progress` goes from 0 to 1 during the transition,
+ * following --transition-bezier.
+ */
+void swww_transition_frame(void *state, uint8_t *canvas, const uint8_t *new_img,
+			   size_t len, float progress);
+
+/* Ends the transition, freeing the state */
+void swww_transition_deinit(void *state);
+
+#endif
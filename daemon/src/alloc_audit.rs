@@ -0,0 +1,99 @@
+//! Reports heap allocations in the code that runs every frame (the `alloc-audit` feature).
+//!
+//! Allocating while animating makes frame times depend on the allocator, which shows up as jitter
+//! in long running animations. With the feature on, we count every allocation each thread makes,
+//! and the animation and transition threads log the frames that made any. Without it, `Frames`
+//! does nothing.
+
+#[cfg(feature = "alloc-audit")]
+mod counting {
+    use std::{
+        alloc::{GlobalAlloc, Layout, System},
+        cell::Cell,
+    };
+
+    thread_local! {
+        static ALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+    }
+
+    /// The system allocator, counting the allocations of each thread
+    struct Counting;
+
+    #[global_allocator]
+    static GLOBAL: Counting = Counting;
+
+    fn count() {
+        // the thread local may be gone while a thread exits
+        let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+    }
+
+    unsafe impl GlobalAlloc for Counting {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            count();
+            System.alloc(layout)
+        }
+
+        unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+            count();
+            System.alloc_zeroed(layout)
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            count();
+            System.realloc(ptr, layout, new_size)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    /// How many allocations the current thread made so far
+    pub(super) fn allocations() -> u64 {
+        ALLOCATIONS.with(Cell::get)
+    }
+}
+
+/// Counts the frames of an animation or transition, logging the ones that allocated
+pub(crate) struct Frames {
+    #[cfg(feature = "alloc-audit")]
+    what: &'static str,
+    #[cfg(feature = "alloc-audit")]
+    frame: u64,
+    #[cfg(feature = "alloc-audit")]
+    allocations: u64,
+}
+
+impl Frames {
+    /// `what` names the frames in the log, like "animation"
+    #[cfg_attr(not(feature = "alloc-audit"), allow(unused_variables))]
+    pub(crate) fn new(what: &'static str) -> Self {
+        Self {
+            #[cfg(feature = "alloc-audit")]
+            what,
+            #[cfg(feature = "alloc-audit")]
+            frame: 0,
+            #[cfg(feature = "alloc-audit")]
+            allocations: counting::allocations(),
+        }
+    }
+
+    /// Marks the end of a frame. The first one is not reported, since that is when we set up
+    #[inline]
+    pub(crate) fn end_frame(&mut self) {
+        #[cfg(feature = "alloc-audit")]
+        {
+            let allocations = counting::allocations() - self.allocations;
+            if self.frame > 0 && allocations > 0 {
+                log::info!(
+                    "{} frame {} made {allocations} heap allocations",
+                    self.what,
+                    self.frame
+                );
+            }
+            self.frame += 1;
+            // so the log above does not count for the next frame
+            self.allocations = counting::allocations();
+        }
+    }
+}
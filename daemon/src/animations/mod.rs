@@ -1,29 +1,281 @@
 use log::error;
 
 use std::{
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
     thread::{self, Scope},
+    time::Duration,
 };
 
 use common::{
-    compression::Decompressor,
-    ipc::{self, Animation, Answer, BgImg, ImgReq},
+    compression::{BitPack, Decompressor},
+    ipc::{self, Animation, BgImg, ImgReq},
 };
 
 use crate::{
+    transition_defaults::{luma_mask, Mask},
     wallpaper::{AnimationToken, Wallpaper},
     wayland::globals,
 };
 
 mod anim_barrier;
+mod plugin;
+mod procedural;
+mod schedule;
+#[cfg(feature = "gpu")]
+mod shader;
+mod stream;
 mod transitions;
-use transitions::Transition;
+#[cfg(feature = "wasm")]
+mod wasm;
+mod wgsl;
 
 use self::anim_barrier::ArcAnimBarrier;
 
 ///The default thread stack size of 2MiB is way too overkill for our purposes
 const STACK_SIZE: usize = 1 << 17; //128KiB
 
+/// Whether animations should hold their current frame, set through `swww pause` and `swww resume`
+static PAUSED: AtomicBool = AtomicBool::new(false);
+
+/// How often paused animations check whether they should resume (or stop)
+const PAUSE_POLL: Duration = Duration::from_millis(100);
+
+pub(super) fn set_paused(paused: bool) {
+    PAUSED.store(paused, Ordering::Release);
+}
+
+fn is_paused() -> bool {
+    PAUSED.load(Ordering::Acquire)
+}
+
+/// How long a shader wallpaper may take to draw its first frame before we answer the client
+/// without waiting for it
+const SHADER_READY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long the first transition on an output waits for the capture of what the output showed
+/// before, before starting from black instead (see `screencopy`)
+const CAPTURE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Replaces transitions and animations for users sensitive to motion
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(super) enum ReduceMotion {
+    #[default]
+    Off,
+    /// every transition becomes a simple fade, and animations only show their first frame
+    Fade,
+    /// like `Fade`, but transitions finish instantly
+    Instant,
+}
+
+impl ReduceMotion {
+    pub(super) fn parse(s: &str) -> Option<Self> {
+        match s.trim() {
+            "" | "off" | "false" | "0" => Some(Self::Off),
+            "fade" | "on" | "true" | "1" => Some(Self::Fade),
+            "instant" => Some(Self::Instant),
+            _ => None,
+        }
+    }
+
+    /// Replaces the transitions and drops the animations, if needed
+    pub(super) fn apply(
+        self,
+        transitions: &mut [ipc::Transition],
+        animations: Option<Box<[Animation]>>,
+    ) -> Option<Box<[Animation]>> {
+        if self == Self::Off {
+            return animations;
+        }
+        for transition in transitions {
+            transition.transition_type = match transition.transition_type {
+                _ if self == Self::Instant => ipc::TransitionType::None,
+                ipc::TransitionType::None => ipc::TransitionType::None,
+                _ => ipc::TransitionType::Fade,
+            };
+        }
+        if animations.is_some() {
+            log::debug!("reduce motion is on: only showing the first frame of the animation");
+        }
+        None
+    }
+}
+
+/// Replaces a `Random` transition by one of its candidates that are not excluded, picked according
+/// to their weights, going in a random direction from a random position
+pub(super) fn pick_random(transition: &mut ipc::Transition) {
+    if !matches!(transition.transition_type, ipc::TransitionType::Random) {
+        return;
+    }
+    let candidates: Vec<_> = transition
+        .random
+        .iter()
+        .filter(|(candidate, _)| {
+            !transition
+                .random_exclude
+                .iter()
+                .any(|excluded| *excluded as u8 == *candidate as u8)
+        })
+        .copied()
+        .collect();
+    let total = candidates.iter().map(|(_, w)| *w as u32).sum::<u32>();
+    transition.transition_type = pick_weighted(&candidates, fastrand::u32(0..total.max(1)));
+    transition.pos = ipc::Position::new(
+        ipc::Coord::Percent(fastrand::f32()),
+        ipc::Coord::Percent(fastrand::f32()),
+    );
+    transition.angle = fastrand::f64() * 360.0;
+    log::debug!(
+        "random transition picked: {}",
+        transition.transition_type as u8
+    );
+}
+
+/// Longer transitions are almost certainly a mistake, like passing milliseconds
+const MAX_DURATION: f32 = 60.0;
+
+/// Clamps the parameters of `transition` to what we can sensibly run, at most `max_fps`,
+/// returning the ones we changed as (name, requested value, used value)
+pub(super) fn sanitize(
+    transition: &mut ipc::Transition,
+    max_fps: u16,
+) -> Vec<(String, String, String)> {
+    let mut clamped = Vec::new();
+    let mut clamp = |name: &str, requested: String, used: String| {
+        if requested != used {
+            clamped.push((name.to_string(), requested, used));
+        }
+    };
+
+    let fps = transition.fps.clamp(1, max_fps);
+    clamp("fps", transition.fps.to_string(), fps.to_string());
+    transition.fps = fps;
+
+    let duration = if transition.duration.is_nan() {
+        0.0
+    } else {
+        transition.duration.clamp(0.0, MAX_DURATION)
+    };
+    clamp(
+        "duration",
+        transition.duration.to_string(),
+        duration.to_string(),
+    );
+    transition.duration = duration;
+
+    // the x coordinates of a bezier curve must stay within the transition
+    let (x1, y1, x2, y2) = transition.bezier;
+    let bezier = (x1.clamp(0.0, 1.0), y1, x2.clamp(0.0, 1.0), y2);
+    let to_string = |(x1, y1, x2, y2): (f32, f32, f32, f32)| format!("{x1},{y1},{x2},{y2}");
+    clamp("bezier", to_string(transition.bezier), to_string(bezier));
+    transition.bezier = bezier;
+
+    for (name, _, used) in &clamped {
+        log::warn!("transition {name} clamped to {used}");
+    }
+    clamped
+}
+
+/// How many frames per second `swww clear --outro` runs at, unless the limits allow less
+const OUTRO_FPS: u16 = 60;
+
+/// Wallpapers with the same dimensions, which share a canvas to draw on
+type SizeGroup = ((u32, u32), Vec<Arc<Wallpaper>>);
+
+/// The transition `outro` is made of
+fn outro_transition(outro: ipc::Outro, duration: f32) -> ipc::Transition {
+    let transition_type = match outro {
+        ipc::Outro::None => ipc::TransitionType::None,
+        ipc::Outro::Fade => ipc::TransitionType::Fade,
+        ipc::Outro::Wipe => ipc::TransitionType::Wipe,
+    };
+    ipc::Transition {
+        transition_type,
+        duration,
+        step: std::num::NonZeroU8::MAX,
+        fps: OUTRO_FPS,
+        // the opposite of `swww img --transition-angle`'s default
+        angle: 225.0,
+        pos: ipc::Position::new(ipc::Coord::Percent(0.5), ipc::Coord::Percent(0.5)),
+        bezier: (0.54, 0.0, 0.34, 0.99),
+        spring: None,
+        curve: Box::default(),
+        random: Box::default(),
+        wave: (0.0, 0.0),
+        ripple: (0.0, 1.0),
+        block_size: 1,
+        slats: (1, false),
+        seed: 0,
+        clock: (false, 0.0),
+        cube: (false, 0.0),
+        shatter: (1, 0.0),
+        spiral: (0.0, 0.0),
+        tile_size: 1,
+        softness: 0.0,
+        feather: 0.0,
+        colorspace: ipc::Colorspace::Srgb,
+        pixel_sort: (0.0, false),
+        halftone: (2, false),
+        exposure: (ipc::BlendMode::Screen, 0.0, 0.0),
+        origins: Box::new([]),
+        edge_threshold: 0.0,
+        invert_y: false,
+        span: false,
+        plugin: String::new(),
+        random_exclude: Box::default(),
+        defaulted: 0,
+    }
+}
+
+/// What a transition thread draws
+struct TransitionInput<'a> {
+    img: &'a [u8],
+    /// for the 'luma' transition
+    mask: Option<&'a [u8]>,
+    path: &'a str,
+    dim: (u32, u32),
+    /// whether `dim` may be smaller than the outputs' (see `ipc::ImgSend::reduced`)
+    reduced: bool,
+    /// the rectangle the transition spans, when it spans several outputs (see `span_layout`)
+    layout: Option<(i32, i32, i32, i32)>,
+}
+
+/// The rectangle around the outputs whose transitions span them all, as (x, y, width, height) in
+/// logical pixels, if there are at least two of them
+fn span_layout(
+    transitions: &[ipc::Transition],
+    wallpapers: &[Vec<Arc<Wallpaper>>],
+) -> Option<(i32, i32, i32, i32)> {
+    let rects: Vec<_> = transitions
+        .iter()
+        .zip(wallpapers)
+        .filter(|(transition, _)| transition.span)
+        .flat_map(|(_, wallpapers)| wallpapers.iter().map(|w| w.get_layout()))
+        .collect();
+    if rects.len() < 2 {
+        return None;
+    }
+    let left = rects.iter().map(|r| r.0).min()?;
+    let top = rects.iter().map(|r| r.1).min()?;
+    let right = rects.iter().map(|r| r.0 + r.2).max()?;
+    let bottom = rects.iter().map(|r| r.1 + r.3).max()?;
+    Some((left, top, right - left, bottom - top))
+}
+
+/// `roll` must be smaller than the sum of the weights. We fall back to `Simple` if there is
+/// nothing to pick
+fn pick_weighted(candidates: &[(ipc::TransitionType, u8)], mut roll: u32) -> ipc::TransitionType {
+    for &(transition_type, weight) in candidates {
+        if roll < weight as u32 {
+            return transition_type;
+        }
+        roll -= weight as u32;
+    }
+    ipc::TransitionType::Simple
+}
+
 pub(super) struct Animator {
     anim_barrier: ArcAnimBarrier,
 }
@@ -38,13 +290,19 @@ impl Animator {
     fn spawn_transition_thread<'a, 'b>(
         scope: &'a Scope<'b, '_>,
         transition: &'b ipc::Transition,
-        img: &'b [u8],
-        path: &'b str,
-        dim: (u32, u32),
+        input: TransitionInput<'b>,
         wallpapers: &'b mut Vec<Arc<Wallpaper>>,
     ) where
         'a: 'b,
     {
+        let TransitionInput {
+            img,
+            mask,
+            path,
+            dim,
+            reduced,
+            layout,
+        } = input;
         thread::Builder::new()
             .name("transition".to_string()) //Name our threads  for better log messages
             .stack_size(STACK_SIZE) //the default of 2MB is way too overkill for this
@@ -56,47 +314,97 @@ impl Animator {
                     w.set_img_info(BgImg::Img(path.to_string()));
                 }
 
+                // images the client reduced on purpose are rendered at their size and upscaled by
+                // the compositor (see `swww img --max-anim-resolution`)
                 let expect = wallpapers[0].get_dimensions();
-                if dim != expect {
+                let fits = if reduced {
+                    dim.0 <= expect.0 && dim.1 <= expect.1
+                } else {
+                    dim == expect
+                };
+                if !fits {
                     wallpapers.clear();
                     error!("image has wrong dimensions! Expect {expect:?}, actual {dim:?}");
                     return;
                 }
+                for w in wallpapers.iter() {
+                    w.set_render_size(Some(dim));
+                }
+                let deadline = std::time::Instant::now() + CAPTURE_TIMEOUT;
+                for w in wallpapers.iter() {
+                    w.draw_capture(deadline.saturating_duration_since(std::time::Instant::now()));
+                }
 
-                Transition::new(wallpapers, dim, transition).execute(img);
+                match layout {
+                    Some(layout) => transitions::execute_spanning(
+                        wallpapers, dim, transition, mask, img, layout,
+                    ),
+                    None => transitions::execute(wallpapers, dim, transition, mask, img, None),
+                }
             })
             .unwrap(); // builder only fails if name contains null bytes
     }
 
+    /// `default_masks` are the `mask` defaults the transitions follow instead of their images'
+    /// masks (see `TransitionDefaults::apply`)
     pub(super) fn transition(
         &mut self,
-        transition: ipc::Transition,
+        transitions: Box<[ipc::Transition]>,
         imgs: Box<[ImgReq]>,
+        default_masks: Box<[Option<Arc<Mask>>]>,
         animations: Option<Box<[Animation]>>,
         mut wallpapers: Vec<Vec<Arc<Wallpaper>>>,
-    ) -> Answer {
+    ) {
         let barrier = self.anim_barrier.clone();
+        let layout = span_layout(&transitions, &wallpapers);
         thread::Builder::new()
             .stack_size(1 << 15)
             .name("animation spawner".to_string())
             .spawn(move || {
+                let masks: Vec<_> = imgs
+                    .iter()
+                    .zip(transitions.iter())
+                    .zip(default_masks.iter())
+                    .map(|((img, transition), default)| {
+                        luma_mask(img, transition, default.as_deref())
+                    })
+                    .collect();
                 thread::scope(|s| {
-                    for (ImgReq { img, path, dim, .. }, wallpapers) in
-                        imgs.iter().zip(wallpapers.iter_mut())
+                    for (
+                        (
+                            (
+                                ImgReq {
+                                    img,
+                                    path,
+                                    dim,
+                                    reduced,
+                                    ..
+                                },
+                                transition,
+                            ),
+                            mask,
+                        ),
+                        wallpapers,
+                    ) in imgs
+                        .iter()
+                        .zip(transitions.iter())
+                        .zip(masks.iter())
+                        .zip(wallpapers.iter_mut())
                     {
-                        Self::spawn_transition_thread(
-                            s,
-                            &transition,
-                            img.bytes(),
-                            path.str(),
-                            *dim,
-                            wallpapers,
-                        );
+                        let input = TransitionInput {
+                            img: img.bytes(),
+                            mask: mask.as_deref(),
+                            path: path.str(),
+                            dim: *dim,
+                            reduced: *reduced,
+                            layout: layout.filter(|_| transition.span),
+                        };
+                        Self::spawn_transition_thread(s, transition, input, wallpapers);
                     }
                 });
+                drop(masks);
                 drop(imgs);
-                #[allow(clippy::drop_non_drop)]
-                drop(transition);
+                drop(transitions);
                 if let Some(animations) = animations {
                     thread::scope(|s| {
                         for (animation, wallpapers) in animations.iter().zip(wallpapers) {
@@ -107,7 +415,133 @@ impl Animator {
                 }
             })
             .unwrap(); // builder only fails if name contains null bytes
-        Answer::Ok
+    }
+
+    /// Changes `wallpapers` to `color` with `outro`, returning the thread doing it
+    pub(super) fn outro(
+        &mut self,
+        wallpapers: Vec<Arc<Wallpaper>>,
+        color: [u8; 3],
+        outro: ipc::Outro,
+        duration: f32,
+        max_fps: u16,
+    ) -> thread::JoinHandle<()> {
+        let mut transition = outro_transition(outro, duration);
+        sanitize(&mut transition, max_fps);
+        thread::Builder::new()
+            .stack_size(1 << 15)
+            .name("outro spawner".to_string())
+            .spawn(move || {
+                crate::wallpaper::stop_animations(&wallpapers);
+                // a transition runs on wallpapers of the same size
+                let mut groups: Vec<SizeGroup> = Vec::new();
+                for wallpaper in wallpapers {
+                    wallpaper.set_img_info(BgImg::Color(color));
+                    wallpaper.set_render_size(None);
+                    let dim = wallpaper.get_dimensions();
+                    match groups.iter_mut().find(|(d, _)| *d == dim) {
+                        Some((_, group)) => group.push(wallpaper),
+                        None => groups.push((dim, vec![wallpaper])),
+                    }
+                }
+                let channels = globals::pixel_format().channels() as usize;
+                let pixel = [color[0], color[1], color[2], 0xFF];
+                thread::scope(|s| {
+                    for (dim, wallpapers) in groups.iter_mut() {
+                        let dim = *dim;
+                        let transition = &transition;
+                        thread::Builder::new()
+                            .name("outro".to_string())
+                            .stack_size(STACK_SIZE)
+                            .spawn_scoped(s, move || {
+                                let frame =
+                                    pixel[..channels].repeat(dim.0 as usize * dim.1 as usize);
+                                transitions::execute(
+                                    wallpapers, dim, transition, None, &frame, None,
+                                );
+                            })
+                            .unwrap(); // builder only fails if name contains null bytes
+                    }
+                });
+            })
+            .unwrap() // builder only fails if name contains null bytes
+    }
+
+    /// Shows the frames of `ring` on `wallpapers`, which must have stopped their animations
+    pub(super) fn stream(
+        &mut self,
+        ring: ipc::StreamRing,
+        wallpapers: Vec<Arc<Wallpaper>>,
+        max_fps: u16,
+    ) {
+        // taken now, so that a request coming before the thread starts still stops it
+        let tokens = wallpapers
+            .iter()
+            .map(|w| w.create_animation_token())
+            .collect();
+        thread::Builder::new()
+            .name("stream".to_string())
+            .stack_size(STACK_SIZE)
+            .spawn(move || {
+                for wallpaper in &wallpapers {
+                    wallpaper.set_img_info(BgImg::Img("stream".to_string()));
+                    wallpaper.set_render_size(Some(ring.dim()));
+                }
+                stream::run(ring, wallpapers, tokens, max_fps);
+            })
+            .unwrap(); // builder only fails if name contains null bytes
+    }
+
+    /// Draws the shader `source` on `wallpapers`, which must have stopped their animations, at
+    /// 1/`downscale` of their size, or a size that suits the renderer if 0. Returns once every
+    /// output drew its first frame, or failed to
+    pub(super) fn shader(
+        &mut self,
+        source: &str,
+        language: ipc::ShaderLanguage,
+        fps: u16,
+        downscale: u8,
+        wallpapers: Vec<Arc<Wallpaper>>,
+    ) -> Result<(), String> {
+        // a shader is drawn once for all the wallpapers of the same size
+        let mut groups: Vec<SizeGroup> = Vec::new();
+        for wallpaper in wallpapers {
+            let dim = wallpaper.get_dimensions();
+            match groups.iter_mut().find(|(d, _)| *d == dim) {
+                Some((_, group)) => group.push(wallpaper),
+                None => groups.push((dim, vec![wallpaper])),
+            }
+        }
+        let mut ready = Vec::with_capacity(groups.len());
+        for (_, wallpapers) in groups {
+            // taken now, so that a request coming before the thread starts still stops it
+            let tokens = wallpapers
+                .iter()
+                .map(|w| w.create_animation_token())
+                .collect();
+            let (tx, rx) = mpsc::channel();
+            let source = source.to_string();
+            // no `STACK_SIZE`: wgpu and the interpreter need more
+            thread::Builder::new()
+                .name("shader".to_string())
+                .spawn(move || {
+                    procedural::run(&source, language, fps, downscale, wallpapers, tokens, tx);
+                })
+                .unwrap(); // builder only fails if name contains null bytes
+            ready.push(rx);
+        }
+        for rx in ready {
+            match rx.recv_timeout(SHADER_READY_TIMEOUT) {
+                Ok(result) => result?,
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    log::warn!("shader is slow to draw its first frame, not waiting for it");
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    return Err("the shader's thread crashed".to_string());
+                }
+            }
+        }
+        Ok(())
     }
 
     fn spawn_animation_thread<'a, 'b>(
@@ -133,47 +567,193 @@ impl Animator {
                     .map(|w| w.create_animation_token())
                     .collect();
 
+                if let Some(start) = animation.day_start {
+                    // it does not play in step with anything, so nothing should wait for it
+                    drop(barrier);
+                    return schedule::run(animation, start, wallpapers, tokens);
+                }
+
                 let mut now = std::time::Instant::now();
 
                 let mut decompressor = Decompressor::new();
-                for (frame, duration) in animation.animation.iter().cycle() {
-                    barrier.wait(duration.div_f32(2.0));
-
-                    let mut i = 0;
-                    while i < wallpapers.len() {
-                        let token = &tokens[i];
-                        if !wallpapers[i].has_animation_id(token) {
-                            wallpapers.swap_remove(i);
-                            tokens.swap_remove(i);
-                            continue;
+                // the wallpapers the frame changed, which are the only ones we have to commit
+                let mut changed = Vec::with_capacity(wallpapers.len());
+                let mut frames = crate::alloc_audit::Frames::new("animation");
+                let len = match animation.plays {
+                    // the last play stops on the last frame, instead of going back to the first
+                    Some(plays) => (animation.animation.len() * plays as usize)
+                        .saturating_sub(animation.wrap_len as usize),
+                    None => usize::MAX,
+                };
+                for (frame, duration) in animation.animation.iter().cycle().take(len) {
+                    while is_paused() {
+                        if wallpapers
+                            .iter()
+                            .zip(&tokens)
+                            .all(|(w, token)| !w.has_animation_id(token))
+                        {
+                            return;
                         }
-
-                        let result = wallpapers[i].canvas_change(|canvas| {
-                            decompressor.decompress(frame, canvas, globals::pixel_format())
-                        });
-
-                        if let Err(e) = result {
-                            error!("failed to unpack frame: {e}");
-                            wallpapers.swap_remove(i);
-                            tokens.swap_remove(i);
-                            continue;
-                        }
-
-                        i += 1;
+                        thread::sleep(PAUSE_POLL);
                     }
+                    barrier.wait(duration.div_f32(2.0));
+
+                    changed.clear();
+                    unpack_frames(
+                        std::iter::once(frame),
+                        &mut wallpapers,
+                        &mut tokens,
+                        &mut decompressor,
+                        &mut changed,
+                    );
 
                     if wallpapers.is_empty() {
                         return;
                     }
 
-                    crate::wallpaper::attach_buffers_and_damange_surfaces(&wallpapers);
                     let timeout = duration.saturating_sub(now.elapsed());
-                    crate::spin_sleep(timeout);
-                    crate::wallpaper::commit_wallpapers(&wallpapers);
+                    if changed.is_empty() {
+                        // e.g. a frame a GIF holds for a long time. There is nothing to commit
+                        crate::sleep::spin_sleep(timeout);
+                    } else {
+                        crate::wallpaper::attach_buffers_and_damange_surfaces(&changed);
+                        crate::sleep::spin_sleep(timeout);
+                        crate::wallpaper::commit_wallpapers(&changed);
+                    }
 
                     now = std::time::Instant::now();
+                    frames.end_frame();
                 }
             })
             .unwrap(); // builder only fails if name contains null bytes
     }
 }
+
+/// Drops the wallpapers that got another image since we took `tokens`
+fn drop_replaced(wallpapers: &mut Vec<Arc<Wallpaper>>, tokens: &mut Vec<AnimationToken>) {
+    let mut i = 0;
+    while i < wallpapers.len() {
+        if wallpapers[i].has_animation_id(&tokens[i]) {
+            i += 1;
+        } else {
+            wallpapers.swap_remove(i);
+            tokens.swap_remove(i);
+        }
+    }
+}
+
+/// Unpacks `frames`, in order, onto every wallpaper still showing the animation `tokens` belong
+/// to, adding the ones they changed to `changed`. Wallpapers that got another image, or that we
+/// cannot draw to anymore, are dropped
+fn unpack_frames<'f>(
+    frames: impl Iterator<Item = &'f BitPack> + Clone,
+    wallpapers: &mut Vec<Arc<Wallpaper>>,
+    tokens: &mut Vec<AnimationToken>,
+    decompressor: &mut Decompressor,
+    changed: &mut Vec<Arc<Wallpaper>>,
+) {
+    let mut i = 0;
+    while i < wallpapers.len() {
+        let token = &tokens[i];
+        if !wallpapers[i].has_animation_id(token) {
+            wallpapers.swap_remove(i);
+            tokens.swap_remove(i);
+            continue;
+        }
+
+        // all in one buffer, so catching up on several frames does not grow the pool
+        let result = wallpapers[i].canvas_change_if_different(|canvas| {
+            let mut any = false;
+            for frame in frames.clone() {
+                any |= decompressor.decompress(frame, canvas, globals::pixel_format())?;
+            }
+            Ok(any)
+        });
+
+        match result {
+            Some(Ok(true)) => changed.push(Arc::clone(&wallpapers[i])),
+            Some(Ok(false)) => (),
+            Some(Err(e)) => {
+                error!("failed to unpack frame: {e}");
+                wallpapers.swap_remove(i);
+                tokens.swap_remove(i);
+                continue;
+            }
+            // we cannot draw to it. `canvas_change` already logged why
+            None => {
+                wallpapers.swap_remove(i);
+                tokens.swap_remove(i);
+                continue;
+            }
+        }
+
+        i += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ipc::TransitionType;
+
+    #[test]
+    fn sanitize_should_report_what_it_clamped() {
+        let mut transition = ipc::Transition {
+            transition_type: TransitionType::Simple,
+            duration: 3.0,
+            step: std::num::NonZeroU8::MAX,
+            fps: 480,
+            angle: 0.0,
+            pos: ipc::Position::new(ipc::Coord::Pixel(0.0), ipc::Coord::Pixel(0.0)),
+            bezier: (0.5, 0.0, 1.5, 1.0),
+            spring: None,
+            curve: Box::default(),
+            random: Box::default(),
+            wave: (0.0, 0.0),
+            ripple: (0.0, 1.0),
+            block_size: 1,
+            slats: (1, false),
+            seed: 0,
+            clock: (false, 0.0),
+            cube: (false, 0.0),
+            shatter: (1, 0.0),
+            spiral: (0.0, 0.0),
+            tile_size: 1,
+            softness: 0.0,
+            feather: 0.0,
+            colorspace: ipc::Colorspace::Srgb,
+            pixel_sort: (0.0, false),
+            halftone: (2, false),
+            exposure: (ipc::BlendMode::Screen, 0.0, 0.0),
+            origins: Box::new([]),
+            edge_threshold: 0.0,
+            invert_y: false,
+            span: false,
+            plugin: String::new(),
+            random_exclude: Box::default(),
+            defaulted: 0,
+        };
+        let clamped = sanitize(&mut transition, 240);
+        let names: Vec<&str> = clamped.iter().map(|(name, _, _)| name.as_str()).collect();
+        assert_eq!(names, ["fps", "bezier"]);
+        assert_eq!(clamped[0].2, "240");
+        assert_eq!(transition.fps, 240);
+        assert_eq!(transition.bezier, (0.5, 0.0, 1.0, 1.0));
+        assert_eq!(transition.duration, 3.0);
+    }
+
+    #[test]
+    fn pick_weighted_should_follow_the_weights() {
+        let candidates = [
+            (TransitionType::Grow, 3),
+            (TransitionType::Wave, 0),
+            (TransitionType::Fade, 1),
+        ];
+        let picks: Vec<u8> = (0..4)
+            .map(|roll| pick_weighted(&candidates, roll) as u8)
+            .collect();
+        let (grow, fade) = (TransitionType::Grow as u8, TransitionType::Fade as u8);
+        assert_eq!(picks, [grow, grow, grow, fade]);
+        assert!(matches!(pick_weighted(&[], 0), TransitionType::Simple));
+    }
+}
@@ -0,0 +1,262 @@
+//! Transitions loaded from shared libraries, WebAssembly modules (see `super::wasm`) or fragment
+//! shaders (see `super::shader`), so people can ship their own effects without forking the
+//! daemon. The interface shared libraries implement is described in
+//! `daemon/include/swww-transition.h`.
+//!
+//! We load the plugin again for every transition, so new or rebuilt plugins are picked up without
+//! restarting the daemon.
+
+use std::{
+    ffi::{c_char, c_void, CStr, CString},
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+};
+
+/// must match `SWWW_TRANSITION_ABI_VERSION`
+pub(super) const ABI_VERSION: u32 = 1;
+
+/// must match `struct swww_transition_params`
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(super) struct Params {
+    pub width: u32,
+    pub height: u32,
+    pub channels: u32,
+    /// the `common::ipc::PixelFormat`, as a number
+    pub format: u32,
+    pub duration: f32,
+    pub step: u32,
+    pub seed: u32,
+    pub angle: f64,
+    /// `--transition-pos`, in pixels from the top left corner
+    pub x: f32,
+    pub y: f32,
+}
+
+type AbiVersionFn = unsafe extern "C" fn() -> u32;
+type InitFn = unsafe extern "C" fn(*const Params) -> *mut c_void;
+type FrameFn = unsafe extern "C" fn(*mut c_void, *mut u8, *const u8, usize, f32);
+type DeinitFn = unsafe extern "C" fn(*mut c_void);
+
+pub(super) enum Plugin {
+    Native(Native),
+    #[cfg(feature = "wasm")]
+    Wasm(Box<super::wasm::Wasm>),
+    #[cfg(feature = "gpu")]
+    Shader(Box<super::shader::Shader>),
+}
+
+impl Plugin {
+    /// Loads the plugin called `name` from `$XDG_CONFIG_HOME/swww/transitions/`, preferring
+    /// `<name>.wasm`, then `<name>.wgsl` and `<name>.glsl`, over `<name>.so`, and starts a
+    /// transition towards `new` with it
+    pub(super) fn load(name: &str, params: &Params, new: &[u8]) -> Result<Self, String> {
+        let dir = transitions_dir()?;
+        let wasm = plugin_path(&dir, name, "wasm")?;
+        if wasm.exists() {
+            #[cfg(feature = "wasm")]
+            return super::wasm::Wasm::load(&wasm, params, new).map(|w| Self::Wasm(Box::new(w)));
+            #[cfg(not(feature = "wasm"))]
+            return Err(format!(
+                "{} needs swww-daemon to be built with the `wasm` feature",
+                wasm.display()
+            ));
+        }
+        for extension in ["wgsl", "glsl"] {
+            let shader = plugin_path(&dir, name, extension)?;
+            if shader.exists() {
+                #[cfg(feature = "gpu")]
+                return super::shader::Shader::load(&shader, params, new)
+                    .map(|s| Self::Shader(Box::new(s)));
+                #[cfg(not(feature = "gpu"))]
+                return Err(format!(
+                    "{} needs swww-daemon to be built with the `gpu` feature",
+                    shader.display()
+                ));
+            }
+        }
+        let _ = new;
+        Native::load(&plugin_path(&dir, name, "so")?, params).map(Self::Native)
+    }
+
+    /// moves `canvas` towards the new image, with `progress` going from 0 to 1 during the
+    /// transition. `old` is what the canvas was before the transition started
+    pub(super) fn frame(
+        &mut self,
+        canvas: &mut [u8],
+        old: &[u8],
+        new: &[u8],
+        progress: f32,
+    ) -> Result<(), String> {
+        // only shaders draw from the old image
+        let _ = old;
+        match self {
+            Self::Native(native) => {
+                native.frame(canvas, new, progress);
+                Ok(())
+            }
+            #[cfg(feature = "wasm")]
+            Self::Wasm(wasm) => wasm.frame(canvas, progress),
+            #[cfg(feature = "gpu")]
+            Self::Shader(shader) => shader.frame(canvas, old, progress),
+        }
+    }
+}
+
+pub(super) struct Native {
+    handle: *mut c_void,
+    state: *mut c_void,
+    frame: FrameFn,
+    deinit: DeinitFn,
+}
+
+impl Native {
+    fn load(path: &Path, params: &Params) -> Result<Self, String> {
+        let cpath = CString::new(path.as_os_str().as_bytes()).map_err(|e| e.to_string())?;
+        let handle = unsafe { libc::dlopen(cpath.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL) };
+        if handle.is_null() {
+            return Err(dlerror());
+        }
+
+        // SAFETY: if the plugin exports these symbols, the header tells it what their types are
+        let symbols = unsafe {
+            (
+                symbol::<AbiVersionFn>(handle, "swww_transition_abi_version"),
+                symbol::<InitFn>(handle, "swww_transition_init"),
+                symbol::<FrameFn>(handle, "swww_transition_frame"),
+                symbol::<DeinitFn>(handle, "swww_transition_deinit"),
+            )
+        };
+        let (abi_version, init, frame, deinit) = match symbols {
+            (Ok(a), Ok(i), Ok(f), Ok(d)) => (a, i, f, d),
+            (a, i, f, d) => {
+                let e = [a.err(), i.err(), f.err(), d.err()]
+                    .into_iter()
+                    .flatten()
+                    .next();
+                unsafe { libc::dlclose(handle) };
+                return Err(e.unwrap_or_default());
+            }
+        };
+
+        let version = unsafe { abi_version() };
+        if version != ABI_VERSION {
+            unsafe { libc::dlclose(handle) };
+            return Err(format!(
+                "{} was built for version {version} of the plugin interface, but we use version {ABI_VERSION}",
+                path.display()
+            ));
+        }
+
+        let state = unsafe { init(params) };
+        if state.is_null() {
+            unsafe { libc::dlclose(handle) };
+            return Err(format!("{} failed to initialize", path.display()));
+        }
+
+        Ok(Self {
+            handle,
+            state,
+            frame,
+            deinit,
+        })
+    }
+
+    fn frame(&self, canvas: &mut [u8], new: &[u8], progress: f32) {
+        assert_eq!(canvas.len(), new.len());
+        unsafe {
+            (self.frame)(
+                self.state,
+                canvas.as_mut_ptr(),
+                new.as_ptr(),
+                canvas.len(),
+                progress,
+            )
+        }
+    }
+}
+
+impl Drop for Native {
+    fn drop(&mut self) {
+        unsafe {
+            (self.deinit)(self.state);
+            libc::dlclose(self.handle);
+        }
+    }
+}
+
+/// # Safety
+///
+/// `T` must be the type of the symbol
+unsafe fn symbol<T: Copy>(handle: *mut c_void, name: &str) -> Result<T, String> {
+    let cname = CString::new(name).map_err(|e| e.to_string())?;
+    let ptr = unsafe { libc::dlsym(handle, cname.as_ptr()) };
+    if ptr.is_null() {
+        return Err(format!("missing symbol {name}"));
+    }
+    Ok(unsafe { std::mem::transmute_copy(&ptr) })
+}
+
+fn dlerror() -> String {
+    let e: *const c_char = unsafe { libc::dlerror() };
+    if e.is_null() {
+        "unknown error".to_string()
+    } else {
+        unsafe { CStr::from_ptr(e) }.to_string_lossy().into_owned()
+    }
+}
+
+fn transitions_dir() -> Result<PathBuf, String> {
+    let mut dir = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => match std::env::var_os("HOME") {
+            Some(home) => PathBuf::from(home).join(".config"),
+            None => return Err("neither $XDG_CONFIG_HOME nor $HOME are set".to_string()),
+        },
+    };
+    dir.push("swww/transitions");
+    Ok(dir)
+}
+
+/// plugins are named after their file, and must not point outside of `dir`
+fn plugin_path(dir: &Path, name: &str, extension: &str) -> Result<PathBuf, String> {
+    if name.is_empty() || name.starts_with('.') || name.contains('/') || name.contains('\0') {
+        return Err(format!("invalid plugin name: {name:?}"));
+    }
+    Ok(dir.join(format!("{name}.{extension}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plugin_path_should_stay_in_the_directory() {
+        let dir = Path::new("/config/swww/transitions");
+        assert_eq!(
+            plugin_path(dir, "pixel-rain", "so").unwrap(),
+            dir.join("pixel-rain.so")
+        );
+        for name in ["", ".hidden", "..", "../../evil", "a/b", "nul\0"] {
+            assert!(plugin_path(dir, name, "so").is_err(), "{name:?}");
+        }
+    }
+
+    #[test]
+    fn missing_plugins_should_not_load() {
+        let params = Params {
+            width: 1,
+            height: 1,
+            channels: 3,
+            format: 0,
+            duration: 1.0,
+            step: 90,
+            seed: 0,
+            angle: 0.0,
+            x: 0.0,
+            y: 0.0,
+        };
+        let name = "swww-test-plugin-that-does-not-exist";
+        assert!(Plugin::load(name, &params, &[0; 3]).is_err());
+    }
+}
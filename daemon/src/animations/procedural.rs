@@ -0,0 +1,206 @@
+//! Shader wallpapers (see `swww shader`): a fragment shader we draw again every frame, on the gpu
+//! when we can (see `super::shader`), and otherwise by interpreting it on the cpu (see
+//! `super::wgsl`), which only works for WGSL.
+//!
+//! Time stands still while animations are paused, so the shader picks up where it left off.
+use std::{
+    sync::{mpsc, Arc},
+    thread,
+    time::{Duration, Instant},
+};
+
+use common::ipc::{BgImg, ShaderLanguage};
+use log::{debug, error};
+
+use super::{drop_replaced, is_paused, wgsl, PAUSE_POLL};
+use crate::{
+    wallpaper::{AnimationToken, Wallpaper},
+    wayland::globals,
+};
+
+/// How much smaller than the outputs we render on the cpu, along each axis, unless asked otherwise
+const CPU_DOWNSCALE: u8 = 4;
+
+enum Renderer {
+    #[cfg(feature = "gpu")]
+    Gpu(Box<super::shader::Shader>),
+    Cpu(wgsl::Program),
+}
+
+impl Renderer {
+    /// Prepares `source` to draw on outputs of `dim`, returning the size we render it at: `dim`
+    /// divided by `downscale`, or by what suits the renderer if 0
+    fn new(
+        source: &str,
+        language: ShaderLanguage,
+        dim: (u32, u32),
+        downscale: u8,
+    ) -> Result<(Self, (u32, u32)), String> {
+        let scaled = |downscale: u8| {
+            let downscale = downscale.max(1) as u32;
+            ((dim.0 / downscale).max(1), (dim.1 / downscale).max(1))
+        };
+
+        #[cfg(feature = "gpu")]
+        {
+            let format = globals::pixel_format();
+            let size = scaled(downscale);
+            let params = super::plugin::Params {
+                width: size.0,
+                height: size.1,
+                channels: format.channels() as u32,
+                format: format as u32,
+                duration: 0.0,
+                step: 0,
+                seed: 0,
+                angle: 0.0,
+                x: 0.0,
+                y: 0.0,
+            };
+            match super::shader::Shader::wallpaper(source, language, &params) {
+                Ok(shader) => return Ok((Self::Gpu(Box::new(shader)), size)),
+                Err(e) if language == ShaderLanguage::Glsl => return Err(e),
+                Err(e) => log::warn!("interpreting the shader on the cpu: {e}"),
+            }
+        }
+
+        if language == ShaderLanguage::Glsl {
+            return Err("GLSL shaders need a gpu, and the daemon built with `gpu`".to_string());
+        }
+        let program = wgsl::Program::new(source)?;
+        let downscale = if downscale == 0 {
+            CPU_DOWNSCALE
+        } else {
+            downscale
+        };
+        Ok((Self::Cpu(program), scaled(downscale)))
+    }
+
+    fn draw(
+        &mut self,
+        canvas: &mut [u8],
+        dim: (u32, u32),
+        time: f32,
+        frame: u32,
+    ) -> Result<(), String> {
+        match self {
+            #[cfg(feature = "gpu")]
+            Self::Gpu(shader) => shader.wallpaper_frame(canvas, time, frame),
+            Self::Cpu(program) => {
+                let format = globals::pixel_format();
+                let uniforms = wgsl::Uniforms {
+                    time,
+                    ratio: dim.0 as f32 / dim.1 as f32,
+                    frame: frame as f32,
+                    resolution: [dim.0 as f32, dim.1 as f32],
+                };
+                program.render(
+                    canvas,
+                    dim.0 as usize,
+                    format.channels() as usize,
+                    format.must_swap_r_and_b_channels(),
+                    &uniforms,
+                )
+            }
+        }
+    }
+}
+
+/// Draws `source` on `wallpapers`, which all have the same size, at most `fps` times a second,
+/// until every one of them gets another image. Whether we managed to draw the first frame goes
+/// through `ready`
+pub(super) fn run(
+    source: &str,
+    language: ShaderLanguage,
+    fps: u16,
+    downscale: u8,
+    mut wallpapers: Vec<Arc<Wallpaper>>,
+    mut tokens: Vec<AnimationToken>,
+    ready: mpsc::Sender<Result<(), String>>,
+) {
+    let dim = wallpapers[0].get_dimensions();
+    let (mut renderer, dim) = match Renderer::new(source, language, dim, downscale) {
+        Ok(renderer) => renderer,
+        Err(e) => {
+            let _ = ready.send(Err(e));
+            return;
+        }
+    };
+    for wallpaper in &wallpapers {
+        wallpaper.set_img_info(BgImg::Img("shader".to_string()));
+        wallpaper.set_render_size(Some(dim));
+    }
+
+    let channels = globals::pixel_format().channels() as usize;
+    let mut canvas = vec![0; dim.0 as usize * dim.1 as usize * channels];
+    let frame_time = Duration::from_secs(1) / fps.max(1) as u32;
+    let start = Instant::now();
+    let mut paused_for = Duration::ZERO;
+    let mut ready = Some(ready);
+    let mut changed = Vec::with_capacity(wallpapers.len());
+    let mut frames = crate::alloc_audit::Frames::new("shader");
+    let mut frame = 0u32;
+    loop {
+        drop_replaced(&mut wallpapers, &mut tokens);
+        if wallpapers.is_empty() {
+            debug!("shader stopped: its outputs got another image");
+            return;
+        }
+        if is_paused() {
+            let paused = Instant::now();
+            thread::sleep(PAUSE_POLL);
+            paused_for += paused.elapsed();
+            continue;
+        }
+
+        let now = Instant::now();
+        let time = now.duration_since(start).saturating_sub(paused_for);
+        if let Err(e) = renderer.draw(&mut canvas, dim, time.as_secs_f32(), frame) {
+            match ready.take() {
+                Some(ready) => drop(ready.send(Err(e))),
+                None => error!("shader stopped: {e}"),
+            }
+            return;
+        }
+        if let Some(ready) = ready.take() {
+            let _ = ready.send(Ok(()));
+        }
+        frame = frame.wrapping_add(1);
+
+        changed.clear();
+        let mut i = 0;
+        while i < wallpapers.len() {
+            let copied = wallpapers[i].canvas_change(|target| {
+                // the canvas is the size we render at, unless the output changed since
+                let fits = target.len() == canvas.len();
+                if fits {
+                    target.copy_from_slice(&canvas);
+                }
+                fits
+            });
+            match copied {
+                Some(true) => {
+                    changed.push(Arc::clone(&wallpapers[i]));
+                    i += 1;
+                }
+                Some(false) => {
+                    error!("shader frame does not fit the output anymore");
+                    wallpapers.swap_remove(i);
+                    tokens.swap_remove(i);
+                }
+                // we cannot draw to it. `canvas_change` already logged why
+                None => {
+                    wallpapers.swap_remove(i);
+                    tokens.swap_remove(i);
+                }
+            }
+        }
+
+        if !changed.is_empty() {
+            crate::wallpaper::attach_buffers_and_damange_surfaces(&changed);
+            crate::sleep::spin_sleep(frame_time.saturating_sub(now.elapsed()));
+            crate::wallpaper::commit_wallpapers(&changed);
+            frames.end_frame();
+        }
+    }
+}
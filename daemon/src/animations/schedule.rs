@@ -0,0 +1,135 @@
+//! Time of day wallpapers: animations with a `day_start`.
+//!
+//! Their frames are not played one after the other. Each one belongs to a part of the day, and is
+//! shown during it, going by the wall clock. We look at the clock at least every minute, so
+//! suspending or changing the time gets us to the right frame soon after.
+use std::{
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use common::{compression::Decompressor, ipc::Animation};
+
+use super::{is_paused, unpack_frames};
+use crate::wallpaper::{AnimationToken, Wallpaper};
+
+const DAY: u32 = 24 * 60 * 60;
+
+/// The longest we go without looking at the clock
+const RECHECK: Duration = Duration::from_secs(60);
+
+/// How often we check whether the wallpapers got another image while we wait
+const TOKEN_POLL: Duration = Duration::from_secs(1);
+
+/// Keeps the frame for the time of day on `wallpapers`, which start on the first one, until they
+/// get another image
+pub(super) fn run(
+    animation: &Animation,
+    start: u32,
+    mut wallpapers: Vec<Arc<Wallpaper>>,
+    mut tokens: Vec<AnimationToken>,
+) {
+    let frames = &animation.animation;
+    let len = frames.len();
+    // the duration of each entry is the one of the frame it leads to
+    let durations: Vec<Duration> = (0..len).map(|i| frames[(i + len - 1) % len].1).collect();
+    let mut decompressor = Decompressor::new();
+    let mut changed = Vec::with_capacity(wallpapers.len());
+    let mut current = 0;
+    loop {
+        let (frame, next) = frame_at(&durations, start, seconds_after_midnight());
+        if frame != current && !is_paused() {
+            let steps = (frame + len - current) % len;
+            log::debug!("time of day wallpaper going from frame {current} to {frame}");
+            changed.clear();
+            unpack_frames(
+                (current..current + steps).map(|i| &frames[i % len].0),
+                &mut wallpapers,
+                &mut tokens,
+                &mut decompressor,
+                &mut changed,
+            );
+            if !changed.is_empty() {
+                crate::wallpaper::attach_buffers_and_damange_surfaces(&changed);
+                crate::wallpaper::commit_wallpapers(&changed);
+            }
+            current = frame;
+        }
+
+        let wake_up = Instant::now() + next.min(RECHECK);
+        loop {
+            if wallpapers
+                .iter()
+                .zip(&tokens)
+                .all(|(w, token)| !w.has_animation_id(token))
+            {
+                return;
+            }
+            let left = wake_up.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                break;
+            }
+            thread::sleep(left.min(TOKEN_POLL));
+        }
+    }
+}
+
+extern "C" {
+    // POSIX, but not bound by the `libc` crate
+    fn tzset();
+}
+
+/// The local time, in seconds after midnight
+fn seconds_after_midnight() -> u32 {
+    // SAFETY: `time` accepts a null pointer, and `localtime_r` only writes to `tm`
+    unsafe {
+        // so we follow changes to the time zone
+        tzset();
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+        if libc::localtime_r(&now, &mut tm).is_null() {
+            return now.rem_euclid(DAY as libc::time_t) as u32;
+        }
+        // `tm_sec` is 60 on leap seconds
+        ((tm.tm_hour * 3600 + tm.tm_min * 60 + tm.tm_sec) as u32).min(DAY - 1)
+    }
+}
+
+/// The frame to show `now` seconds after midnight, when the first one starts `start` seconds
+/// after it and each lasts its entry of `durations`, and how long until the next one
+fn frame_at(durations: &[Duration], start: u32, now: u32) -> (usize, Duration) {
+    let total: Duration = durations.iter().sum();
+    if total.is_zero() {
+        return (0, RECHECK);
+    }
+    let since_start = (now % DAY + DAY - start % DAY) % DAY;
+    // schedules that do not add up to a day start over when they end
+    let mut offset = Duration::from_secs_f64(since_start as f64 % total.as_secs_f64());
+    for (i, &duration) in durations.iter().enumerate() {
+        if offset < duration {
+            return (i, duration - offset);
+        }
+        offset -= duration;
+    }
+    // rounding put us right at the end
+    (0, durations[0])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_at_should_follow_the_wall_clock_around_midnight() {
+        let hours = |h: u64| Duration::from_secs(h * 3600);
+        // 3:00 to 9:00, 9:00 to 21:00 and 21:00 to 3:00
+        let durations = [hours(6), hours(12), hours(6)];
+        let start = 3 * 3600;
+        assert_eq!(frame_at(&durations, start, 4 * 3600), (0, hours(5)));
+        assert_eq!(frame_at(&durations, start, 9 * 3600), (1, hours(12)));
+        assert_eq!(frame_at(&durations, start, 2 * 3600), (2, hours(1)));
+        assert_eq!(frame_at(&durations, start, 23 * 3600), (2, hours(4)));
+        assert_eq!(frame_at(&[], start, 0), (0, RECHECK));
+    }
+}
@@ -0,0 +1,597 @@
+//! Transition plugins written as fragment shaders, in WGSL (`<name>.wgsl`) or GLSL
+//! (`<name>.glsl`), which we run on the gpu through wgpu. Each frame, we upload the old image,
+//! render the shader over the whole output, and copy the result back into the canvas. This makes
+//! effects like blurs and refractions affordable even at 4K.
+//!
+//! A shader only has to define the color of every pixel, given its position `uv`, which goes from
+//! (0, 0) at the top left corner to (1, 1) at the bottom right one:
+//!
+//! * in WGSL, `fn transition(uv: vec2<f32>) -> vec4<f32>`, which can use `get_from_color(uv)`,
+//!   `get_to_color(uv)`, and the fields of `swww` (see `WGSL_PRELUDE`);
+//! * in GLSL, `vec4 transition(vec2 uv)`, which can use `getFromColor(uv)`, `getToColor(uv)`,
+//!   `progress`, `ratio`, `seed`, `angle`, `resolution` and `center`, as in gl-transitions.
+//!
+//! `progress` goes from 0 to 1 during the transition, `ratio` is the output's width over its
+//! height, `seed` is `--transition-seed` in [0, 1), `angle` is `--transition-angle` in degrees,
+//! `resolution` is the output's size in pixels and `center` is `--transition-pos` in uv
+//! coordinates. The alpha channel of the result is ignored.
+//!
+//! Shader wallpapers (see `swww shader`) run the same way, continuously and without images:
+//!
+//! * in WGSL, `fn wallpaper(uv: vec2<f32>) -> vec4<f32>`, which can use `swww.time`, in seconds
+//!   since the wallpaper started, `swww.ratio`, `swww.frame`, the number of the frame, and
+//!   `swww.resolution` (see `WALLPAPER_WGSL_PRELUDE`);
+//! * in GLSL, `void mainImage(out vec4 fragColor, in vec2 fragCoord)`, as in shadertoy, which can
+//!   use `iTime`, `iFrame` and `iResolution`. `fragCoord` is in pixels, from the bottom left.
+
+use std::{path::Path, sync::mpsc};
+
+use log::error;
+
+use common::ipc::ShaderLanguage;
+
+use super::plugin::Params;
+
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+/// a triangle covering the whole output
+const VERTEX: &str = "
+@vertex
+fn main(@builtin(vertex_index) i: u32) -> @builtin(position) vec4<f32> {
+    let xy = vec2<f32>(f32((i << 1u) & 2u), f32(i & 2u));
+    return vec4<f32>(xy * 2.0 - 1.0, 0.0, 1.0);
+}
+";
+
+/// comes after the user's code, so the line numbers in their errors are right
+const WGSL_PRELUDE: &str = "
+struct Swww {
+    progress: f32,
+    ratio: f32,
+    seed: f32,
+    angle: f32,
+    resolution: vec2<f32>,
+    center: vec2<f32>,
+}
+
+@group(0) @binding(0) var<uniform> swww: Swww;
+@group(0) @binding(1) var swww_from: texture_2d<f32>;
+@group(0) @binding(2) var swww_to: texture_2d<f32>;
+@group(0) @binding(3) var swww_sampler: sampler;
+
+fn get_from_color(uv: vec2<f32>) -> vec4<f32> {
+    return textureSampleLevel(swww_from, swww_sampler, uv, 0.0);
+}
+
+fn get_to_color(uv: vec2<f32>) -> vec4<f32> {
+    return textureSampleLevel(swww_to, swww_sampler, uv, 0.0);
+}
+
+@fragment
+fn swww_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    return transition(position.xy / swww.resolution);
+}
+";
+
+const GLSL_PRELUDE: &str = "#version 450
+layout(set = 0, binding = 0) uniform Swww {
+    float progress;
+    float ratio;
+    float seed;
+    float angle;
+    vec2 resolution;
+    vec2 center;
+};
+layout(set = 0, binding = 1) uniform texture2D swww_from;
+layout(set = 0, binding = 2) uniform texture2D swww_to;
+layout(set = 0, binding = 3) uniform sampler swww_sampler;
+layout(location = 0) out vec4 swww_color;
+
+vec4 getFromColor(vec2 uv) {
+    return textureLod(sampler2D(swww_from, swww_sampler), uv, 0.0);
+}
+
+vec4 getToColor(vec2 uv) {
+    return textureLod(sampler2D(swww_to, swww_sampler), uv, 0.0);
+}
+";
+
+const GLSL_MAIN: &str = "
+void main() {
+    swww_color = transition(gl_FragCoord.xy / resolution);
+}
+";
+
+/// like `WGSL_PRELUDE`, without the images
+const WALLPAPER_WGSL_PRELUDE: &str = "
+struct Swww {
+    time: f32,
+    ratio: f32,
+    frame: f32,
+    _unused: f32,
+    resolution: vec2<f32>,
+    _center: vec2<f32>,
+}
+
+@group(0) @binding(0) var<uniform> swww: Swww;
+
+@fragment
+fn swww_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    return wallpaper(position.xy / swww.resolution);
+}
+";
+
+/// shadertoy's names for the uniforms we have
+const WALLPAPER_GLSL_PRELUDE: &str = "#version 450
+layout(set = 0, binding = 0) uniform Swww {
+    float iTime;
+    float swww_ratio;
+    float swww_frame;
+    float swww_unused;
+    vec2 swww_resolution;
+    vec2 swww_center;
+};
+layout(location = 0) out vec4 swww_color;
+
+#define iResolution vec3(swww_resolution, 1.0)
+#define iFrame int(swww_frame)
+";
+
+const WALLPAPER_GLSL_MAIN: &str = "
+void main() {
+    mainImage(swww_color, vec2(gl_FragCoord.x, swww_resolution.y - gl_FragCoord.y));
+}
+";
+
+pub(super) struct Shader {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+    uniform_buffer: wgpu::Buffer,
+    /// laid out like the shaders' `Swww` struct, starting with the progress (or the time, for
+    /// wallpapers)
+    uniforms: [f32; 8],
+    from: wgpu::Texture,
+    target: wgpu::Texture,
+    readback: wgpu::Buffer,
+    size: wgpu::Extent3d,
+    /// the rows of `readback` are padded to `wgpu::COPY_BYTES_PER_ROW_ALIGNMENT`
+    padded_row: u32,
+    channels: usize,
+    swap: bool,
+    /// where we convert the old image to rgba
+    rgba: Vec<u8>,
+}
+
+impl Shader {
+    pub(super) fn load(path: &Path, params: &Params, new: &[u8]) -> Result<Self, String> {
+        let source = std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+        let source = match path.extension().and_then(|e| e.to_str()) {
+            Some("wgsl") => wgpu::ShaderSource::Wgsl(format!("{source}\n{WGSL_PRELUDE}").into()),
+            Some("glsl") => wgpu::ShaderSource::Glsl {
+                shader: format!("{GLSL_PRELUDE}\n{source}\n{GLSL_MAIN}").into(),
+                stage: wgpu::naga::ShaderStage::Fragment,
+                defines: Default::default(),
+            },
+            _ => return Err(format!("{} is neither WGSL nor GLSL", path.display())),
+        };
+        Self::new(source, params, Some(new))
+    }
+
+    /// A shader wallpaper, of the size in `params`
+    pub(super) fn wallpaper(
+        source: &str,
+        language: ShaderLanguage,
+        params: &Params,
+    ) -> Result<Self, String> {
+        let source = match language {
+            ShaderLanguage::Wgsl => {
+                wgpu::ShaderSource::Wgsl(format!("{source}\n{WALLPAPER_WGSL_PRELUDE}").into())
+            }
+            ShaderLanguage::Glsl => wgpu::ShaderSource::Glsl {
+                shader: format!("{WALLPAPER_GLSL_PRELUDE}\n{source}\n{WALLPAPER_GLSL_MAIN}").into(),
+                stage: wgpu::naga::ShaderStage::Fragment,
+                defines: Default::default(),
+            },
+        };
+        Self::new(source, params, None)
+    }
+
+    /// `new` is the image transitioned to, which wallpapers do not have
+    fn new(
+        source: wgpu::ShaderSource,
+        params: &Params,
+        new: Option<&[u8]>,
+    ) -> Result<Self, String> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            ..Default::default()
+        }))
+        .ok_or("no usable gpu")?;
+        let (device, queue) = pollster::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some("swww shader"),
+                required_features: wgpu::Features::empty(),
+                required_limits: adapter.limits(),
+            },
+            None,
+        ))
+        .map_err(|e| e.to_string())?;
+        // by default, wgpu panics on errors nobody is waiting for, which would take the daemon
+        // down. We wait for the ones while building the pipeline, these are from the frames
+        device.on_uncaptured_error(Box::new(|e| error!("shader failed: {e}")));
+
+        let Params {
+            width,
+            height,
+            channels,
+            format,
+            seed,
+            angle,
+            x,
+            y,
+            ..
+        } = *params;
+        let max = device.limits().max_texture_dimension_2d;
+        if width > max || height > max {
+            return Err(format!(
+                "the gpu only supports textures of up to {max}x{max}, not {width}x{height}"
+            ));
+        }
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let vertex = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("swww vertex"),
+            source: wgpu::ShaderSource::Wgsl(VERTEX.into()),
+        });
+        let glsl = matches!(source, wgpu::ShaderSource::Glsl { .. });
+        let fragment = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("swww shader"),
+            source,
+        });
+
+        let texture = |label, usage| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: FORMAT,
+                usage,
+                view_formats: &[],
+            })
+        };
+        let input = wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST;
+        let from = texture("swww from", input);
+        let to = texture("swww to", input);
+        let target = texture(
+            "swww target",
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        );
+
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("swww uniforms"),
+            size: std::mem::size_of::<[f32; 8]>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let padded_row = (width * 4).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("swww readback"),
+            size: padded_row as u64 * height as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("swww sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("swww bindings"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                texture_entry(1),
+                texture_entry(2),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let from_view = from.create_view(&Default::default());
+        let to_view = to.create_view(&Default::default());
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("swww bindings"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&from_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&to_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("swww pipeline"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("swww pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &vertex,
+                entry_point: "main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &fragment,
+                entry_point: if glsl { "main" } else { "swww_main" },
+                targets: &[Some(FORMAT.into())],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+        if let Some(e) = pollster::block_on(device.pop_error_scope()) {
+            return Err(e.to_string());
+        }
+
+        let channels = channels as usize;
+        let swap = format_swaps_r_and_b(format);
+        let mut rgba = Vec::new();
+        if let Some(new) = new {
+            to_rgba(new, channels, swap, &mut rgba);
+            queue.write_texture(to.as_image_copy(), &rgba, image_layout(width * 4), size);
+        }
+
+        Ok(Self {
+            device,
+            queue,
+            pipeline,
+            bind_group,
+            uniform_buffer,
+            uniforms: [
+                0.0,
+                width as f32 / height as f32,
+                seed as f32 / (u32::MAX as f32 + 1.0),
+                angle as f32,
+                width as f32,
+                height as f32,
+                x / width as f32,
+                y / height as f32,
+            ],
+            from,
+            target,
+            readback,
+            size,
+            padded_row,
+            channels,
+            swap,
+            rgba,
+        })
+    }
+
+    /// draws the frame at `progress` from `old` into `canvas`
+    pub(super) fn frame(
+        &mut self,
+        canvas: &mut [u8],
+        old: &[u8],
+        progress: f32,
+    ) -> Result<(), String> {
+        let width = self.size.width;
+        to_rgba(old, self.channels, self.swap, &mut self.rgba);
+        self.queue.write_texture(
+            self.from.as_image_copy(),
+            &self.rgba,
+            image_layout(width * 4),
+            self.size,
+        );
+        self.uniforms[0] = progress;
+        self.render(canvas)
+    }
+
+    /// draws the wallpaper at `time` seconds, the `frame`th one we draw, into `canvas`
+    pub(super) fn wallpaper_frame(
+        &mut self,
+        canvas: &mut [u8],
+        time: f32,
+        frame: u32,
+    ) -> Result<(), String> {
+        self.uniforms[0] = time;
+        self.uniforms[2] = frame as f32;
+        self.render(canvas)
+    }
+
+    fn render(&mut self, canvas: &mut [u8]) -> Result<(), String> {
+        let width = self.size.width;
+        let uniforms: Vec<u8> = self.uniforms.iter().flat_map(|f| f.to_ne_bytes()).collect();
+        self.queue.write_buffer(&self.uniform_buffer, 0, &uniforms);
+
+        let view = self.target.create_view(&Default::default());
+        let mut encoder = self.device.create_command_encoder(&Default::default());
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("swww shader"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &self.bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+        encoder.copy_texture_to_buffer(
+            self.target.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &self.readback,
+                layout: image_layout(self.padded_row),
+            },
+            self.size,
+        );
+        self.queue.submit([encoder.finish()]);
+
+        let slice = self.readback.slice(..);
+        let (tx, rx) = mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = tx.send(result);
+        });
+        let _ = self.device.poll(wgpu::Maintain::Wait);
+        rx.recv()
+            .map_err(|_| "the gpu did not render the frame".to_string())?
+            .map_err(|e| e.to_string())?;
+        from_rgba(
+            &slice.get_mapped_range(),
+            self.padded_row as usize,
+            width as usize,
+            self.channels,
+            self.swap,
+            canvas,
+        );
+        self.readback.unmap();
+        Ok(())
+    }
+}
+
+fn image_layout(bytes_per_row: u32) -> wgpu::ImageDataLayout {
+    wgpu::ImageDataLayout {
+        offset: 0,
+        bytes_per_row: Some(bytes_per_row),
+        rows_per_image: None,
+    }
+}
+
+/// whether the pixels are in blue, green, red order in memory
+fn format_swaps_r_and_b(format: u32) -> bool {
+    use common::ipc::PixelFormat;
+    format == PixelFormat::Rgb as u32 || format == PixelFormat::Xrgb as u32
+}
+
+fn to_rgba(src: &[u8], channels: usize, swap: bool, dst: &mut Vec<u8>) {
+    dst.clear();
+    dst.reserve(src.len() / channels * 4);
+    for pixel in src.chunks_exact(channels) {
+        let (r, b) = if swap {
+            (pixel[2], pixel[0])
+        } else {
+            (pixel[0], pixel[2])
+        };
+        dst.extend_from_slice(&[r, pixel[1], b, 255]);
+    }
+}
+
+/// `src`'s rows are `padded_row` bytes long, of which only the first `width * 4` are pixels
+fn from_rgba(
+    src: &[u8],
+    padded_row: usize,
+    width: usize,
+    channels: usize,
+    swap: bool,
+    dst: &mut [u8],
+) {
+    let rows = src.chunks_exact(padded_row);
+    for (src, dst) in rows.zip(dst.chunks_exact_mut(width * channels)) {
+        for (src, dst) in src.chunks_exact(4).zip(dst.chunks_exact_mut(channels)) {
+            let (r, b) = if swap {
+                (src[2], src[0])
+            } else {
+                (src[0], src[2])
+            };
+            dst[..3].copy_from_slice(&[r, src[1], b]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rgba_conversions_should_round_trip() {
+        let (width, height) = (3, 2);
+        for channels in [3, 4] {
+            for swap in [false, true] {
+                let pixels: Vec<u8> = (0..(width * height * channels) as u8).collect();
+                let mut rgba = Vec::new();
+                to_rgba(&pixels, channels, swap, &mut rgba);
+                assert_eq!(rgba.len(), width * height * 4);
+
+                // rows padded like the readback buffer
+                let padded_row = 256;
+                let mut padded = vec![0; padded_row * height];
+                for (row, pixels) in padded.chunks_mut(padded_row).zip(rgba.chunks(width * 4)) {
+                    row[..pixels.len()].copy_from_slice(pixels);
+                }
+
+                let mut back = vec![0; pixels.len()];
+                from_rgba(&padded, padded_row, width, channels, swap, &mut back);
+                for (pixel, back) in pixels.chunks(channels).zip(back.chunks(channels)) {
+                    assert_eq!(pixel[..3], back[..3], "channels: {channels}, swap: {swap}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn only_rgb_formats_should_be_swapped() {
+        use common::ipc::PixelFormat;
+        assert!(!format_swaps_r_and_b(PixelFormat::Bgr as u32));
+        assert!(format_swaps_r_and_b(PixelFormat::Rgb as u32));
+        assert!(!format_swaps_r_and_b(PixelFormat::Xbgr as u32));
+        assert!(format_swaps_r_and_b(PixelFormat::Xrgb as u32));
+    }
+}
@@ -0,0 +1,89 @@
+//! Raw frame streams (see `common::ipc::StreamRing`).
+//!
+//! We show the newest frame the producer finished, at most `max_fps` times a second, and free
+//! its slot (and the ones of the frames we skipped) right after copying it. A producer faster than
+//! us thus never waits, and one slower than us gets every frame shown. Once the producer closes
+//! the ring, the wallpapers keep its last frame.
+use std::{
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use common::ipc::StreamRing;
+
+use super::{drop_replaced, is_paused, PAUSE_POLL};
+use crate::wallpaper::{AnimationToken, Wallpaper};
+
+/// How often we check for a new frame when there is none
+const FRAME_POLL: Duration = Duration::from_millis(2);
+
+/// Shows the frames of `ring` on `wallpapers` until the producer closes it or every wallpaper gets
+/// another image. Dropping the ring tells the producer we stopped
+pub(super) fn run(
+    ring: StreamRing,
+    mut wallpapers: Vec<Arc<Wallpaper>>,
+    mut tokens: Vec<AnimationToken>,
+    max_fps: u16,
+) {
+    let frame_time = Duration::from_secs(1) / max_fps.max(1) as u32;
+    let mut changed = Vec::with_capacity(wallpapers.len());
+    let mut frames = crate::alloc_audit::Frames::new("stream");
+    loop {
+        drop_replaced(&mut wallpapers, &mut tokens);
+        if wallpapers.is_empty() {
+            log::debug!("stream stopped: its outputs got another image");
+            return;
+        }
+        if is_paused() {
+            thread::sleep(PAUSE_POLL);
+            continue;
+        }
+        let Some((frame, bytes)) = ring.newest() else {
+            if ring.is_closed() {
+                log::debug!("stream closed by its producer");
+                return;
+            }
+            thread::sleep(FRAME_POLL);
+            continue;
+        };
+
+        let now = Instant::now();
+        changed.clear();
+        let mut i = 0;
+        while i < wallpapers.len() {
+            let copied = wallpapers[i].canvas_change(|canvas| {
+                // the canvas is the stream's size, unless the output changed since
+                let fits = canvas.len() == bytes.len();
+                if fits {
+                    canvas.copy_from_slice(bytes);
+                }
+                fits
+            });
+            match copied {
+                Some(true) => {
+                    changed.push(Arc::clone(&wallpapers[i]));
+                    i += 1;
+                }
+                Some(false) => {
+                    log::error!("stream frame does not fit the output anymore");
+                    wallpapers.swap_remove(i);
+                    tokens.swap_remove(i);
+                }
+                // we cannot draw to it. `canvas_change` already logged why
+                None => {
+                    wallpapers.swap_remove(i);
+                    tokens.swap_remove(i);
+                }
+            }
+        }
+        ring.done_with(frame);
+
+        if !changed.is_empty() {
+            crate::wallpaper::attach_buffers_and_damange_surfaces(&changed);
+            crate::sleep::spin_sleep(frame_time.saturating_sub(now.elapsed()));
+            crate::wallpaper::commit_wallpapers(&changed);
+            frames.end_frame();
+        }
+    }
+}
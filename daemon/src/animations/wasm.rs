@@ -0,0 +1,206 @@
+//! Transition plugins compiled to WebAssembly, which we run in an interpreter. Unlike shared
+//! libraries, they cannot do anything but change the frames we give them: they get no imports, a
+//! bounded memory, and a bounded number of instructions per frame. The same module also runs on
+//! every distro and architecture.
+//!
+//! A module must export:
+//!
+//! * `memory`;
+//! * `swww_transition_abi_version() -> i32`, returning 1;
+//! * `swww_transition_init(width: i32, height: i32, channels: i32, format: i32, duration: f32,
+//!   step: i32, seed: i32, angle: f64, x: f32, y: f32) -> i32`, taking the same parameters as
+//!   shared library plugins (see `daemon/include/swww-transition.h`). It returns where, in its
+//!   memory, it wants two frames of `width * height * channels` bytes: the canvas, followed by the
+//!   new image. Returning 0 means it failed;
+//! * `swww_transition_frame(progress: f32)`, which changes the canvas towards the new image, with
+//!   `progress` going from 0 to 1 during the transition.
+//!
+//! We write the new image once, after `swww_transition_init`, and copy the canvas in and out
+//! around every `swww_transition_frame`.
+
+use std::path::Path;
+
+use wasmi::{
+    core::{F32, F64},
+    Engine, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder, TypedFunc,
+};
+
+use super::plugin::{Params, ABI_VERSION};
+
+/// roughly how many instructions a module may run per byte of the frames, before we consider it
+/// stuck
+const FUEL_PER_BYTE: u64 = 256;
+/// memory a module may use on top of the frames
+const EXTRA_MEMORY: usize = 64 << 20;
+
+type InitParams = (i32, i32, i32, i32, F32, i32, i32, F64, F32, F32);
+
+pub(super) struct Wasm {
+    store: Store<StoreLimits>,
+    memory: Memory,
+    frame: TypedFunc<F32, ()>,
+    /// where the canvas starts in the module's memory
+    canvas: usize,
+    fuel: u64,
+}
+
+impl Wasm {
+    pub(super) fn load(path: &Path, params: &Params, new: &[u8]) -> Result<Self, String> {
+        let bytes =
+            std::fs::read(path).map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+        Self::new(&bytes, params, new)
+    }
+
+    fn new(bytes: &[u8], params: &Params, new: &[u8]) -> Result<Self, String> {
+        let mut config = wasmi::Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let module = Module::new(&engine, bytes).map_err(|e| e.to_string())?;
+
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(new.len() * 2 + EXTRA_MEMORY)
+            .instances(1)
+            .build();
+        let mut store = Store::new(&engine, limits);
+        store.limiter(|limits| limits);
+        let fuel = (new.len() as u64 * FUEL_PER_BYTE).max(1 << 20);
+        let _ = store.add_fuel(fuel);
+
+        // no imports: the module can only compute
+        let instance = Linker::<StoreLimits>::new(&engine)
+            .instantiate(&mut store, &module)
+            .and_then(|instance| instance.start(&mut store))
+            .map_err(|e| e.to_string())?;
+        let memory = instance
+            .get_memory(&store, "memory")
+            .ok_or("the module does not export its memory")?;
+        let abi_version = instance
+            .get_typed_func::<(), i32>(&store, "swww_transition_abi_version")
+            .map_err(|e| e.to_string())?;
+        let init = instance
+            .get_typed_func::<InitParams, i32>(&store, "swww_transition_init")
+            .map_err(|e| e.to_string())?;
+        let frame = instance
+            .get_typed_func::<F32, ()>(&store, "swww_transition_frame")
+            .map_err(|e| e.to_string())?;
+
+        let version = abi_version
+            .call(&mut store, ())
+            .map_err(|e| e.to_string())?;
+        if version as u32 != ABI_VERSION {
+            return Err(format!(
+                "the module was built for version {version} of the plugin interface, but we use version {ABI_VERSION}"
+            ));
+        }
+
+        let Params {
+            width,
+            height,
+            channels,
+            format,
+            duration,
+            step,
+            seed,
+            angle,
+            x,
+            y,
+        } = *params;
+        let args = (
+            width as i32,
+            height as i32,
+            channels as i32,
+            format as i32,
+            F32::from(duration),
+            step as i32,
+            seed as i32,
+            F64::from(angle),
+            F32::from(x),
+            F32::from(y),
+        );
+        let canvas = init.call(&mut store, args).map_err(|e| e.to_string())? as u32 as usize;
+        if canvas == 0 {
+            return Err("the module failed to initialize".to_string());
+        }
+        memory
+            .write(&mut store, canvas + new.len(), new)
+            .map_err(|_| "the module's frames are out of its memory")?;
+
+        Ok(Self {
+            store,
+            memory,
+            frame,
+            canvas,
+            fuel,
+        })
+    }
+
+    pub(super) fn frame(&mut self, canvas: &mut [u8], progress: f32) -> Result<(), String> {
+        // every frame gets the same budget, whatever the previous ones left
+        if let Ok(left) = self.store.consume_fuel(0) {
+            let _ = self.store.consume_fuel(left);
+        }
+        let _ = self.store.add_fuel(self.fuel);
+
+        self.memory
+            .write(&mut self.store, self.canvas, canvas)
+            .map_err(|e| e.to_string())?;
+        self.frame
+            .call(&mut self.store, F32::from(progress))
+            .map_err(|e| e.to_string())?;
+        self.memory
+            .read(&self.store, self.canvas, canvas)
+            .map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PARAMS: Params = Params {
+        width: 2,
+        height: 1,
+        channels: 3,
+        format: 0,
+        duration: 1.0,
+        step: 90,
+        seed: 0,
+        angle: 0.0,
+        x: 0.0,
+        y: 0.0,
+    };
+
+    fn module(frame: &str) -> Vec<u8> {
+        let wat = format!(
+            r#"(module
+                (memory (export "memory") 1)
+                (func (export "swww_transition_abi_version") (result i32) i32.const 1)
+                (func (export "swww_transition_init")
+                    (param i32 i32 i32 i32 f32 i32 i32 f64 f32 f32) (result i32)
+                    i32.const 1024)
+                (func (export "swww_transition_frame") (param f32) {frame}))"#
+        );
+        wat::parse_str(wat).unwrap()
+    }
+
+    #[test]
+    fn wasm_plugins_should_change_the_canvas() {
+        // copies the new image over the canvas
+        let copy = module("i32.const 1024 i32.const 1030 i32.const 6 memory.copy");
+        let new = [1, 2, 3, 4, 5, 6];
+        let mut plugin = Wasm::new(&copy, &PARAMS, &new).unwrap();
+        let mut canvas = [0; 6];
+        plugin.frame(&mut canvas, 0.5).unwrap();
+        assert_eq!(canvas, new);
+    }
+
+    #[test]
+    fn wasm_plugins_should_be_sandboxed() {
+        let stuck = module("(loop (br 0))");
+        let mut plugin = Wasm::new(&stuck, &PARAMS, &[0; 6]).unwrap();
+        assert!(plugin.frame(&mut [0; 6], 0.5).is_err());
+
+        let importing = wat::parse_str(r#"(module (import "env" "system" (func)))"#).unwrap();
+        assert!(Wasm::new(&importing, &PARAMS, &[0; 6]).is_err());
+    }
+}
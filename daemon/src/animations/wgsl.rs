@@ -0,0 +1,1587 @@
+//! Wallpaper shaders written in WGSL, interpreted on the cpu, for when we cannot run them on the
+//! gpu (see `super::shader`): because the daemon was built without the `gpu` feature, or because
+//! there is no usable gpu.
+//!
+//! We only understand the part of WGSL such shaders tend to need: functions, `const`, `let` and
+//! `var` declarations, assignments, `if`, `for`, `while` and `loop`, and expressions on `f32`,
+//! `bool` and vectors of up to 4 `f32`s, with most of the numeric builtin functions. Integers are
+//! `f32`s too, so `5 / 2` is 2.5. Anything else (structs, arrays, matrices, textures, `switch`)
+//! is refused when loading the shader.
+//!
+//! This is far slower than a gpu, so the caller renders at a fraction of the output's resolution
+//! and lets the compositor upscale it.
+
+use std::{collections::HashMap, thread};
+
+/// What the shader can read from `swww`
+#[derive(Clone, Copy, Default)]
+pub(super) struct Uniforms {
+    pub time: f32,
+    pub ratio: f32,
+    pub frame: f32,
+    pub resolution: [f32; 2],
+}
+
+/// How many loop iterations a pixel may take, before we consider the shader stuck
+const MAX_ITERATIONS: u32 = 1 << 16;
+/// WGSL forbids recursion, so this only keeps us from overflowing our stack
+const MAX_DEPTH: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Value {
+    Bool(bool),
+    Num(f32),
+    /// a vector of 2 to 4 components
+    Vec(u8, [f32; 4]),
+}
+
+impl Value {
+    fn num(self) -> Result<f32, String> {
+        match self {
+            Self::Num(n) => Ok(n),
+            _ => Err(format!("expected a number, not a {}", self.type_name())),
+        }
+    }
+
+    fn bool(self) -> Result<bool, String> {
+        match self {
+            Self::Bool(b) => Ok(b),
+            _ => Err(format!("expected a bool, not a {}", self.type_name())),
+        }
+    }
+
+    fn type_name(self) -> String {
+        match self {
+            Self::Bool(_) => "bool".to_string(),
+            Self::Num(_) => "f32".to_string(),
+            Self::Vec(n, _) => format!("vec{n}<f32>"),
+        }
+    }
+
+    /// How many components we have, and their values. Numbers fill every component, so that
+    /// they combine with vectors of any size
+    fn components(self) -> Result<(u8, [f32; 4]), String> {
+        match self {
+            Self::Num(n) => Ok((1, [n; 4])),
+            Self::Vec(len, c) => Ok((len, c)),
+            Self::Bool(_) => Err("expected a number or a vector, not a bool".to_string()),
+        }
+    }
+
+    fn from_components(len: u8, c: [f32; 4]) -> Self {
+        if len == 1 {
+            Self::Num(c[0])
+        } else {
+            Self::Vec(len, c)
+        }
+    }
+}
+
+fn map(v: Value, f: impl Fn(f32) -> f32) -> Result<Value, String> {
+    let (len, mut c) = v.components()?;
+    for x in &mut c[..len as usize] {
+        *x = f(*x);
+    }
+    Ok(Value::from_components(len, c))
+}
+
+/// the size of the result of combining vectors (or numbers) of these sizes
+fn combined_len(lens: &[u8]) -> Result<u8, String> {
+    let mut len = 1;
+    for &l in lens {
+        if l != 1 && len != 1 && l != len {
+            return Err(format!("cannot combine a vec{len} with a vec{l}"));
+        }
+        len = len.max(l);
+    }
+    Ok(len)
+}
+
+fn zip(a: Value, b: Value, f: impl Fn(f32, f32) -> f32) -> Result<Value, String> {
+    let ((n, a), (m, b)) = (a.components()?, b.components()?);
+    let len = combined_len(&[n, m])?;
+    let mut c = [0.0; 4];
+    for i in 0..len as usize {
+        c[i] = f(a[i], b[i]);
+    }
+    Ok(Value::from_components(len, c))
+}
+
+fn zip3(a: Value, b: Value, t: Value, f: impl Fn(f32, f32, f32) -> f32) -> Result<Value, String> {
+    let ((n, a), (m, b), (o, t)) = (a.components()?, b.components()?, t.components()?);
+    let len = combined_len(&[n, m, o])?;
+    let mut c = [0.0; 4];
+    for i in 0..len as usize {
+        c[i] = f(a[i], b[i], t[i]);
+    }
+    Ok(Value::from_components(len, c))
+}
+
+fn dot(a: Value, b: Value) -> Result<f32, String> {
+    let ((n, a), (m, b)) = (a.components()?, b.components()?);
+    if n != m {
+        return Err(format!(
+            "cannot take the dot product of a vec{n} and a vec{m}"
+        ));
+    }
+    Ok((0..n as usize).map(|i| a[i] * b[i]).sum())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Type {
+    Bool,
+    Num,
+    Vec(u8),
+}
+
+impl Type {
+    fn zero(self) -> Value {
+        match self {
+            Self::Bool => Value::Bool(false),
+            Self::Num => Value::Num(0.0),
+            Self::Vec(n) => Value::Vec(n, [0.0; 4]),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Uniform {
+    Time,
+    Ratio,
+    Frame,
+    Resolution,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Builtin {
+    Abs,
+    Sin,
+    Cos,
+    Tan,
+    Asin,
+    Acos,
+    Atan,
+    Atan2,
+    Sinh,
+    Cosh,
+    Tanh,
+    Exp,
+    Exp2,
+    Log,
+    Log2,
+    Pow,
+    Sqrt,
+    InverseSqrt,
+    Floor,
+    Ceil,
+    Round,
+    Fract,
+    Trunc,
+    Sign,
+    Saturate,
+    Radians,
+    Degrees,
+    Min,
+    Max,
+    Step,
+    Clamp,
+    Mix,
+    Smoothstep,
+    Fma,
+    Length,
+    Distance,
+    Dot,
+    Cross,
+    Normalize,
+    Reflect,
+    Select,
+}
+
+impl Builtin {
+    fn parse(name: &str) -> Option<(Self, usize)> {
+        Some(match name {
+            "abs" => (Self::Abs, 1),
+            "sin" => (Self::Sin, 1),
+            "cos" => (Self::Cos, 1),
+            "tan" => (Self::Tan, 1),
+            "asin" => (Self::Asin, 1),
+            "acos" => (Self::Acos, 1),
+            "atan" => (Self::Atan, 1),
+            "atan2" => (Self::Atan2, 2),
+            "sinh" => (Self::Sinh, 1),
+            "cosh" => (Self::Cosh, 1),
+            "tanh" => (Self::Tanh, 1),
+            "exp" => (Self::Exp, 1),
+            "exp2" => (Self::Exp2, 1),
+            "log" => (Self::Log, 1),
+            "log2" => (Self::Log2, 1),
+            "pow" => (Self::Pow, 2),
+            "sqrt" => (Self::Sqrt, 1),
+            "inverseSqrt" => (Self::InverseSqrt, 1),
+            "floor" => (Self::Floor, 1),
+            "ceil" => (Self::Ceil, 1),
+            "round" => (Self::Round, 1),
+            "fract" => (Self::Fract, 1),
+            "trunc" => (Self::Trunc, 1),
+            "sign" => (Self::Sign, 1),
+            "saturate" => (Self::Saturate, 1),
+            "radians" => (Self::Radians, 1),
+            "degrees" => (Self::Degrees, 1),
+            "min" => (Self::Min, 2),
+            "max" => (Self::Max, 2),
+            "step" => (Self::Step, 2),
+            "clamp" => (Self::Clamp, 3),
+            "mix" => (Self::Mix, 3),
+            "smoothstep" => (Self::Smoothstep, 3),
+            "fma" => (Self::Fma, 3),
+            "length" => (Self::Length, 1),
+            "distance" => (Self::Distance, 2),
+            "dot" => (Self::Dot, 2),
+            "cross" => (Self::Cross, 2),
+            "normalize" => (Self::Normalize, 1),
+            "reflect" => (Self::Reflect, 2),
+            "select" => (Self::Select, 3),
+            _ => return None,
+        })
+    }
+
+    fn call(self, args: &[Value]) -> Result<Value, String> {
+        use std::f32::consts::PI;
+        let a = args[0];
+        Ok(match self {
+            Self::Abs => map(a, f32::abs)?,
+            Self::Sin => map(a, f32::sin)?,
+            Self::Cos => map(a, f32::cos)?,
+            Self::Tan => map(a, f32::tan)?,
+            Self::Asin => map(a, f32::asin)?,
+            Self::Acos => map(a, f32::acos)?,
+            Self::Atan => map(a, f32::atan)?,
+            Self::Atan2 => zip(a, args[1], f32::atan2)?,
+            Self::Sinh => map(a, f32::sinh)?,
+            Self::Cosh => map(a, f32::cosh)?,
+            Self::Tanh => map(a, f32::tanh)?,
+            Self::Exp => map(a, f32::exp)?,
+            Self::Exp2 => map(a, f32::exp2)?,
+            Self::Log => map(a, f32::ln)?,
+            Self::Log2 => map(a, f32::log2)?,
+            Self::Pow => zip(a, args[1], f32::powf)?,
+            Self::Sqrt => map(a, f32::sqrt)?,
+            Self::InverseSqrt => map(a, |x| 1.0 / x.sqrt())?,
+            Self::Floor => map(a, f32::floor)?,
+            Self::Ceil => map(a, f32::ceil)?,
+            Self::Round => map(a, round_ties_even)?,
+            Self::Fract => map(a, |x| x - x.floor())?,
+            Self::Trunc => map(a, f32::trunc)?,
+            // unlike `f32::signum`, the sign of 0 is 0
+            Self::Sign => map(a, |x| if x == 0.0 { 0.0 } else { x.signum() })?,
+            Self::Saturate => map(a, |x| x.clamp(0.0, 1.0))?,
+            Self::Radians => map(a, |x| x * PI / 180.0)?,
+            Self::Degrees => map(a, |x| x * 180.0 / PI)?,
+            Self::Min => zip(a, args[1], f32::min)?,
+            Self::Max => zip(a, args[1], f32::max)?,
+            Self::Step => zip(a, args[1], |edge, x| if x >= edge { 1.0 } else { 0.0 })?,
+            Self::Clamp => zip3(a, args[1], args[2], |x, lo, hi| x.max(lo).min(hi))?,
+            Self::Mix => zip3(a, args[1], args[2], |a, b, t| a * (1.0 - t) + b * t)?,
+            Self::Smoothstep => zip3(a, args[1], args[2], |lo, hi, x| {
+                let t = ((x - lo) / (hi - lo)).clamp(0.0, 1.0);
+                t * t * (3.0 - 2.0 * t)
+            })?,
+            Self::Fma => zip3(a, args[1], args[2], |a, b, c| a * b + c)?,
+            Self::Length => Value::Num(dot(a, a)?.sqrt()),
+            Self::Distance => {
+                let d = zip(a, args[1], |a, b| a - b)?;
+                Value::Num(dot(d, d)?.sqrt())
+            }
+            Self::Dot => Value::Num(dot(a, args[1])?),
+            Self::Cross => match (a, args[1]) {
+                (Value::Vec(3, a), Value::Vec(3, b)) => Value::Vec(
+                    3,
+                    [
+                        a[1] * b[2] - a[2] * b[1],
+                        a[2] * b[0] - a[0] * b[2],
+                        a[0] * b[1] - a[1] * b[0],
+                        0.0,
+                    ],
+                ),
+                _ => return Err("cross only takes vec3s".to_string()),
+            },
+            Self::Normalize => {
+                let len = dot(a, a)?.sqrt();
+                map(a, |x| x / len)?
+            }
+            Self::Reflect => {
+                let d = 2.0 * dot(args[1], a)?;
+                zip(a, args[1], |i, n| i - d * n)?
+            }
+            Self::Select => {
+                if args[2].bool()? {
+                    args[1]
+                } else {
+                    a
+                }
+            }
+        })
+    }
+}
+
+/// what WGSL's `round` does, which `f32::round_ties_even` only does from rust 1.77 on
+fn round_ties_even(x: f32) -> f32 {
+    let rounded = x.round();
+    if (x - x.trunc()).abs() == 0.5 {
+        2.0 * (x / 2.0).round()
+    } else {
+        rounded
+    }
+}
+
+#[derive(Debug)]
+enum Expr {
+    Const(Value),
+    /// a parameter or variable of the current function
+    Local(usize),
+    Uniform(Uniform),
+    Neg(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+    Call(usize, Vec<Expr>),
+    Builtin(Builtin, Vec<Expr>),
+    /// `vecN(...)`, or `f32(...)` when N is 1
+    Construct(u8, Vec<Expr>),
+    /// `i32(...)` and `u32(...)`
+    Int(Box<Expr>),
+    /// `bool(...)`
+    ToBool(Box<Expr>),
+    /// up to 4 components, and how many there are
+    Swizzle(Box<Expr>, [u8; 4], u8),
+    Index(Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug)]
+enum Access {
+    Whole,
+    Swizzle([u8; 4], u8),
+    Index(Expr),
+}
+
+#[derive(Debug)]
+enum Stmt {
+    Let(usize, Expr),
+    Assign(usize, Access, Option<BinOp>, Expr),
+    If(Expr, Vec<Stmt>, Vec<Stmt>),
+    /// `for`, `while` and `loop`: runs `body` then `continuing` for as long as the condition holds
+    Loop {
+        condition: Option<Expr>,
+        body: Vec<Stmt>,
+        continuing: Vec<Stmt>,
+    },
+    Break,
+    Continue,
+    Return(Option<Expr>),
+    Expr(Expr),
+    Block(Vec<Stmt>),
+}
+
+#[derive(Debug, Default)]
+struct Function {
+    params: usize,
+    /// how many values the parameters and variables take on the stack
+    locals: usize,
+    body: Vec<Stmt>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Num(f32),
+    Punct(&'static str),
+    Eof,
+}
+
+/// longest first, so that `<=` is not read as `<` followed by `=`
+const PUNCTS: [&str; 38] = [
+    "->", "&&", "||", "==", "!=", "<=", ">=", "+=", "-=", "*=", "/=", "%=", "++", "--", "(", ")",
+    "{", "}", "[", "]", "<", ">", ",", ";", ":", ".", "=", "+", "-", "*", "/", "%", "!", "@", "&",
+    "|", "^", "~",
+];
+
+/// Splits `source` into tokens, with the line each starts on
+fn lex(source: &str) -> Result<Vec<(Token, u32)>, String> {
+    let bytes = source.as_bytes();
+    let mut tokens = Vec::new();
+    let (mut i, mut line) = (0, 1);
+    while i < bytes.len() {
+        let c = bytes[i];
+        if c == b'\n' {
+            line += 1;
+            i += 1;
+        } else if c.is_ascii_whitespace() {
+            i += 1;
+        } else if bytes[i..].starts_with(b"//") {
+            while i < bytes.len() && bytes[i] != b'\n' {
+                i += 1;
+            }
+        } else if bytes[i..].starts_with(b"/*") {
+            // block comments nest in WGSL
+            let mut depth = 0;
+            while i < bytes.len() {
+                if bytes[i..].starts_with(b"/*") {
+                    depth += 1;
+                    i += 2;
+                } else if bytes[i..].starts_with(b"*/") {
+                    depth -= 1;
+                    i += 2;
+                    if depth == 0 {
+                        break;
+                    }
+                } else {
+                    line += (bytes[i] == b'\n') as u32;
+                    i += 1;
+                }
+            }
+        } else if c.is_ascii_alphabetic() || c == b'_' {
+            let start = i;
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                i += 1;
+            }
+            tokens.push((Token::Ident(source[start..i].to_string()), line));
+        } else if c.is_ascii_digit()
+            || (c == b'.' && bytes.get(i + 1).is_some_and(u8::is_ascii_digit))
+        {
+            let (n, len) =
+                lex_number(&source[i..]).ok_or_else(|| format!("line {line}: invalid number"))?;
+            tokens.push((Token::Num(n), line));
+            i += len;
+        } else {
+            let punct = PUNCTS
+                .iter()
+                .find(|p| source[i..].starts_with(**p))
+                .ok_or_else(|| {
+                    let c = source[i..].chars().next().unwrap_or_default();
+                    format!("line {line}: unexpected character {c:?}")
+                })?;
+            tokens.push((Token::Punct(punct), line));
+            i += punct.len();
+        }
+    }
+    tokens.push((Token::Eof, line));
+    Ok(tokens)
+}
+
+/// Reads the number `s` starts with, returning it and how many bytes it took
+fn lex_number(s: &str) -> Option<(f32, usize)> {
+    let bytes = s.as_bytes();
+    if s.starts_with("0x") || s.starts_with("0X") {
+        let len = 2 + bytes[2..]
+            .iter()
+            .take_while(|b| b.is_ascii_hexdigit())
+            .count();
+        let n = u64::from_str_radix(&s[2..len], 16).ok()? as f32;
+        let suffix = matches!(bytes.get(len), Some(b'i' | b'u')) as usize;
+        return Some((n, len + suffix));
+    }
+    let mut len = bytes.iter().take_while(|b| b.is_ascii_digit()).count();
+    if bytes.get(len) == Some(&b'.') {
+        len += 1;
+        len += bytes[len..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count();
+    }
+    if matches!(bytes.get(len), Some(b'e' | b'E')) {
+        let mut exp = len + 1;
+        if matches!(bytes.get(exp), Some(b'+' | b'-')) {
+            exp += 1;
+        }
+        let digits = bytes[exp..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count();
+        if digits > 0 {
+            len = exp + digits;
+        }
+    }
+    let n = s[..len].parse().ok()?;
+    let suffix = matches!(bytes.get(len), Some(b'f' | b'h' | b'i' | b'u')) as usize;
+    Some((n, len + suffix))
+}
+
+struct Parser<'a> {
+    tokens: &'a [(Token, u32)],
+    pos: usize,
+    /// every function, by name, so that they can be called before they are defined
+    names: HashMap<String, usize>,
+    functions: Vec<Function>,
+    consts: HashMap<String, Value>,
+    /// the variables in scope in the current function, innermost last
+    scopes: Vec<Vec<(String, usize)>>,
+    locals: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> &'a Token {
+        &self.tokens[self.pos].0
+    }
+
+    fn next(&mut self) -> &'a Token {
+        let token = &self.tokens[self.pos].0;
+        if *token != Token::Eof {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn error<T>(&self, message: impl std::fmt::Display) -> Result<T, String> {
+        Err(format!("line {}: {message}", self.tokens[self.pos].1))
+    }
+
+    fn is(&self, punct: &str) -> bool {
+        matches!(self.peek(), Token::Punct(p) if *p == punct)
+    }
+
+    fn is_ident(&self, ident: &str) -> bool {
+        matches!(self.peek(), Token::Ident(i) if i == ident)
+    }
+
+    fn eat(&mut self, punct: &str) -> bool {
+        let is = self.is(punct);
+        if is {
+            self.pos += 1;
+        }
+        is
+    }
+
+    fn expect(&mut self, punct: &str) -> Result<(), String> {
+        if self.eat(punct) {
+            Ok(())
+        } else {
+            self.error(format!("expected `{punct}`"))
+        }
+    }
+
+    fn ident(&mut self) -> Result<&'a str, String> {
+        match self.peek() {
+            Token::Ident(ident) => {
+                self.pos += 1;
+                Ok(ident)
+            }
+            _ => self.error("expected a name"),
+        }
+    }
+
+    /// skips attributes like `@fragment` and `@location(0)`, which mean nothing to us
+    fn skip_attributes(&mut self) -> Result<(), String> {
+        while self.eat("@") {
+            self.ident()?;
+            if self.eat("(") {
+                let mut depth = 1;
+                while depth > 0 {
+                    match self.next() {
+                        Token::Punct("(") => depth += 1,
+                        Token::Punct(")") => depth -= 1,
+                        Token::Eof => return self.error("unclosed attribute"),
+                        _ => (),
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The shader's functions, and their indices by name
+    fn program(mut self) -> Result<(Vec<Function>, HashMap<String, usize>), String> {
+        for window in self.tokens.windows(2) {
+            if let [(Token::Ident(keyword), _), (Token::Ident(name), _)] = window {
+                if keyword == "fn" {
+                    let index = self.functions.len();
+                    self.names.insert(name.clone(), index);
+                    self.functions.push(Function::default());
+                }
+            }
+        }
+        loop {
+            self.skip_attributes()?;
+            match self.peek() {
+                Token::Eof => return Ok((self.functions, self.names)),
+                Token::Ident(keyword) if keyword == "fn" => self.function()?,
+                Token::Ident(keyword) if keyword == "const" || keyword == "override" => {
+                    self.pos += 1;
+                    let name = self.ident()?;
+                    if self.eat(":") {
+                        self.ty()?;
+                    }
+                    self.expect("=")?;
+                    let expr = self.expr()?;
+                    self.expect(";")?;
+                    // consts cannot call the shader's functions, which are not parsed yet
+                    let value = Evaluator::new(&[], &Uniforms::default())
+                        .eval(&expr, 0)
+                        .or_else(|e| self.error(e))?;
+                    self.consts.insert(name.to_string(), value);
+                }
+                Token::Ident(keyword) => {
+                    return self.error(format!("`{keyword}` is not supported without a gpu"))
+                }
+                _ => return self.error("expected a function or a constant"),
+            }
+        }
+    }
+
+    fn function(&mut self) -> Result<(), String> {
+        self.pos += 1;
+        let name = self.ident()?;
+        let index = self.names[name];
+        self.expect("(")?;
+        self.scopes = vec![Vec::new()];
+        self.locals = 0;
+        while !self.eat(")") {
+            self.skip_attributes()?;
+            let name = self.ident()?;
+            self.expect(":")?;
+            self.ty()?;
+            self.declare(name);
+            if !self.is(")") {
+                self.expect(",")?;
+            }
+        }
+        let params = self.locals;
+        if self.eat("->") {
+            self.skip_attributes()?;
+            self.ty()?;
+        }
+        let body = self.block()?;
+        self.functions[index] = Function {
+            params,
+            locals: self.locals,
+            body,
+        };
+        Ok(())
+    }
+
+    fn ty(&mut self) -> Result<Type, String> {
+        let name = self.ident()?;
+        let ty = match name {
+            "bool" => Type::Bool,
+            "f32" | "f16" | "i32" | "u32" => Type::Num,
+            "vec2" | "vec3" | "vec4" => {
+                if self.eat("<") {
+                    self.ty()?;
+                    self.expect(">")?;
+                }
+                Type::Vec(name.as_bytes()[3] - b'0')
+            }
+            _ => match vector_alias(name) {
+                Some(n) => Type::Vec(n),
+                None => return self.error(format!("`{name}` is not supported without a gpu")),
+            },
+        };
+        Ok(ty)
+    }
+
+    fn declare(&mut self, name: &str) -> usize {
+        let slot = self.locals;
+        self.locals += 1;
+        self.scopes
+            .last_mut()
+            .expect("functions have a scope")
+            .push((name.to_string(), slot));
+        slot
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt>, String> {
+        self.expect("{")?;
+        self.scopes.push(Vec::new());
+        let mut stmts = Vec::new();
+        while !self.eat("}") {
+            if let Some(stmt) = self.stmt()? {
+                stmts.push(stmt);
+            }
+        }
+        self.scopes.pop();
+        Ok(stmts)
+    }
+
+    fn stmt(&mut self) -> Result<Option<Stmt>, String> {
+        self.skip_attributes()?;
+        let keyword = match self.peek() {
+            Token::Ident(keyword) => keyword.as_str(),
+            Token::Punct(";") => {
+                self.pos += 1;
+                return Ok(None);
+            }
+            Token::Punct("{") => return Ok(Some(Stmt::Block(self.block()?))),
+            Token::Eof => return self.error("unclosed block"),
+            _ => "",
+        };
+        let stmt = match keyword {
+            "if" => {
+                self.pos += 1;
+                self.if_stmt()?
+            }
+            "for" => {
+                self.pos += 1;
+                self.expect("(")?;
+                self.scopes.push(Vec::new());
+                let init = if self.is(";") {
+                    None
+                } else {
+                    Some(self.simple_stmt()?)
+                };
+                self.expect(";")?;
+                let condition = if self.is(";") {
+                    None
+                } else {
+                    Some(self.expr()?)
+                };
+                self.expect(";")?;
+                let update = if self.is(")") {
+                    None
+                } else {
+                    Some(self.simple_stmt()?)
+                };
+                self.expect(")")?;
+                let body = self.block()?;
+                self.scopes.pop();
+                let looped = Stmt::Loop {
+                    condition,
+                    body,
+                    continuing: update.into_iter().collect(),
+                };
+                Stmt::Block(init.into_iter().chain([looped]).collect())
+            }
+            "while" => {
+                self.pos += 1;
+                let condition = Some(self.expr()?);
+                let body = self.block()?;
+                Stmt::Loop {
+                    condition,
+                    body,
+                    continuing: Vec::new(),
+                }
+            }
+            "loop" => {
+                self.pos += 1;
+                self.expect("{")?;
+                self.scopes.push(Vec::new());
+                let (mut body, mut continuing) = (Vec::new(), Vec::new());
+                while !self.eat("}") {
+                    if self.is_ident("continuing") {
+                        self.pos += 1;
+                        continuing = self.block()?;
+                    } else if let Some(stmt) = self.stmt()? {
+                        body.push(stmt);
+                    }
+                }
+                self.scopes.pop();
+                Stmt::Loop {
+                    condition: None,
+                    body,
+                    continuing,
+                }
+            }
+            "break" => {
+                self.pos += 1;
+                let stmt = if self.is_ident("if") {
+                    self.pos += 1;
+                    Stmt::If(self.expr()?, vec![Stmt::Break], Vec::new())
+                } else {
+                    Stmt::Break
+                };
+                self.expect(";")?;
+                stmt
+            }
+            "continue" => {
+                self.pos += 1;
+                self.expect(";")?;
+                Stmt::Continue
+            }
+            "return" => {
+                self.pos += 1;
+                let value = if self.is(";") {
+                    None
+                } else {
+                    Some(self.expr()?)
+                };
+                self.expect(";")?;
+                Stmt::Return(value)
+            }
+            _ => {
+                let stmt = self.simple_stmt()?;
+                self.expect(";")?;
+                stmt
+            }
+        };
+        Ok(Some(stmt))
+    }
+
+    fn if_stmt(&mut self) -> Result<Stmt, String> {
+        let condition = self.expr()?;
+        let then = self.block()?;
+        let otherwise = if self.is_ident("else") {
+            self.pos += 1;
+            if self.is_ident("if") {
+                self.pos += 1;
+                vec![self.if_stmt()?]
+            } else {
+                self.block()?
+            }
+        } else {
+            Vec::new()
+        };
+        Ok(Stmt::If(condition, then, otherwise))
+    }
+
+    /// a declaration, assignment, increment or call, without the `;` after it
+    fn simple_stmt(&mut self) -> Result<Stmt, String> {
+        if let Token::Ident(keyword) = self.peek() {
+            if matches!(keyword.as_str(), "let" | "var" | "const") {
+                self.pos += 1;
+                let name = self.ident()?;
+                let ty = if self.eat(":") {
+                    Some(self.ty()?)
+                } else {
+                    None
+                };
+                let value = if self.eat("=") {
+                    self.expr()?
+                } else {
+                    match ty {
+                        Some(ty) => Expr::Const(ty.zero()),
+                        None => return self.error(format!("`{name}` needs a type or a value")),
+                    }
+                };
+                // declared after its value, which may use a variable it shadows
+                return Ok(Stmt::Let(self.declare(name), value));
+            }
+            if keyword == "_" {
+                self.pos += 1;
+                self.expect("=")?;
+                return Ok(Stmt::Expr(self.expr()?));
+            }
+        }
+
+        let target = self.expr()?;
+        let op = match self.peek() {
+            Token::Punct("=") => None,
+            Token::Punct("+=") | Token::Punct("++") => Some(BinOp::Add),
+            Token::Punct("-=") | Token::Punct("--") => Some(BinOp::Sub),
+            Token::Punct("*=") => Some(BinOp::Mul),
+            Token::Punct("/=") => Some(BinOp::Div),
+            Token::Punct("%=") => Some(BinOp::Rem),
+            _ => return Ok(Stmt::Expr(target)),
+        };
+        let value = match self.next() {
+            Token::Punct("++") | Token::Punct("--") => Expr::Const(Value::Num(1.0)),
+            _ => self.expr()?,
+        };
+        let (slot, access) = match target {
+            Expr::Local(slot) => (slot, Access::Whole),
+            Expr::Swizzle(target, components, len) => match *target {
+                Expr::Local(slot) => (slot, Access::Swizzle(components, len)),
+                _ => return self.error("can only assign to variables"),
+            },
+            Expr::Index(target, index) => match *target {
+                Expr::Local(slot) => (slot, Access::Index(*index)),
+                _ => return self.error("can only assign to variables"),
+            },
+            _ => return self.error("can only assign to variables"),
+        };
+        Ok(Stmt::Assign(slot, access, op, value))
+    }
+
+    fn expr(&mut self) -> Result<Expr, String> {
+        let mut left = self.and()?;
+        while self.eat("||") {
+            left = Expr::Binary(BinOp::Or, Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut left = self.relational()?;
+        while self.eat("&&") {
+            left = Expr::Binary(BinOp::And, Box::new(left), Box::new(self.relational()?));
+        }
+        Ok(left)
+    }
+
+    fn relational(&mut self) -> Result<Expr, String> {
+        let left = self.additive()?;
+        let op = match self.peek() {
+            Token::Punct("==") => BinOp::Eq,
+            Token::Punct("!=") => BinOp::Ne,
+            Token::Punct("<") => BinOp::Lt,
+            Token::Punct("<=") => BinOp::Le,
+            Token::Punct(">") => BinOp::Gt,
+            Token::Punct(">=") => BinOp::Ge,
+            _ => return Ok(left),
+        };
+        self.pos += 1;
+        Ok(Expr::Binary(op, Box::new(left), Box::new(self.additive()?)))
+    }
+
+    fn additive(&mut self) -> Result<Expr, String> {
+        let mut left = self.multiplicative()?;
+        loop {
+            let op = match self.peek() {
+                Token::Punct("+") => BinOp::Add,
+                Token::Punct("-") => BinOp::Sub,
+                _ => return Ok(left),
+            };
+            self.pos += 1;
+            left = Expr::Binary(op, Box::new(left), Box::new(self.multiplicative()?));
+        }
+    }
+
+    fn multiplicative(&mut self) -> Result<Expr, String> {
+        let mut left = self.unary()?;
+        loop {
+            let op = match self.peek() {
+                Token::Punct("*") => BinOp::Mul,
+                Token::Punct("/") => BinOp::Div,
+                Token::Punct("%") => BinOp::Rem,
+                _ => return Ok(left),
+            };
+            self.pos += 1;
+            left = Expr::Binary(op, Box::new(left), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat("-") {
+            Ok(Expr::Neg(Box::new(self.unary()?)))
+        } else if self.eat("!") {
+            Ok(Expr::Not(Box::new(self.unary()?)))
+        } else {
+            self.postfix()
+        }
+    }
+
+    fn postfix(&mut self) -> Result<Expr, String> {
+        let mut expr = self.primary()?;
+        loop {
+            if self.eat(".") {
+                let name = self.ident()?;
+                let mut components = [0; 4];
+                if name.len() > 4 {
+                    return self.error(format!("invalid swizzle `{name}`"));
+                }
+                for (i, c) in name.bytes().enumerate() {
+                    components[i] = match c {
+                        b'x' | b'r' => 0,
+                        b'y' | b'g' => 1,
+                        b'z' | b'b' => 2,
+                        b'w' | b'a' => 3,
+                        _ => return self.error(format!("invalid swizzle `{name}`")),
+                    };
+                }
+                expr = Expr::Swizzle(Box::new(expr), components, name.len() as u8);
+            } else if self.eat("[") {
+                let index = self.expr()?;
+                self.expect("]")?;
+                expr = Expr::Index(Box::new(expr), Box::new(index));
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    fn args(&mut self) -> Result<Vec<Expr>, String> {
+        self.expect("(")?;
+        let mut args = Vec::new();
+        while !self.eat(")") {
+            args.push(self.expr()?);
+            if !self.is(")") {
+                self.expect(",")?;
+            }
+        }
+        Ok(args)
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        let name = match self.next() {
+            Token::Num(n) => return Ok(Expr::Const(Value::Num(*n))),
+            Token::Punct("(") => {
+                let expr = self.expr()?;
+                self.expect(")")?;
+                return Ok(expr);
+            }
+            Token::Ident(name) => name.as_str(),
+            _ => {
+                self.pos -= 1;
+                return self.error("expected an expression");
+            }
+        };
+        match name {
+            "true" => return Ok(Expr::Const(Value::Bool(true))),
+            "false" => return Ok(Expr::Const(Value::Bool(false))),
+            "swww" => {
+                self.expect(".")?;
+                let uniform = match self.ident()? {
+                    "time" => Uniform::Time,
+                    "ratio" => Uniform::Ratio,
+                    "frame" => Uniform::Frame,
+                    "resolution" => Uniform::Resolution,
+                    field => return self.error(format!("`swww` has no field `{field}`")),
+                };
+                return Ok(Expr::Uniform(uniform));
+            }
+            "f32" | "f16" => return Ok(Expr::Construct(1, self.args()?)),
+            "i32" | "u32" => {
+                let mut args = self.args()?;
+                return match args.len() {
+                    1 => Ok(Expr::Int(Box::new(args.remove(0)))),
+                    _ => self.error(format!("{name} takes 1 argument")),
+                };
+            }
+            "bool" => {
+                let mut args = self.args()?;
+                return match args.len() {
+                    1 => Ok(Expr::ToBool(Box::new(args.remove(0)))),
+                    _ => self.error("bool takes 1 argument"),
+                };
+            }
+            "vec2" | "vec3" | "vec4" => {
+                if self.eat("<") {
+                    self.ty()?;
+                    self.expect(">")?;
+                }
+                return Ok(Expr::Construct(name.as_bytes()[3] - b'0', self.args()?));
+            }
+            _ => (),
+        }
+        if let Some(n) = vector_alias(name) {
+            return Ok(Expr::Construct(n, self.args()?));
+        }
+        if self.is("(") {
+            if let Some(&index) = self.names.get(name) {
+                return Ok(Expr::Call(index, self.args()?));
+            }
+            if let Some((builtin, arity)) = Builtin::parse(name) {
+                let args = self.args()?;
+                if args.len() != arity {
+                    return self.error(format!("{name} takes {arity} arguments"));
+                }
+                return Ok(Expr::Builtin(builtin, args));
+            }
+            return self.error(format!("unknown function `{name}`"));
+        }
+        for scope in self.scopes.iter().rev() {
+            if let Some((_, slot)) = scope.iter().rev().find(|(n, _)| n == name) {
+                return Ok(Expr::Local(*slot));
+            }
+        }
+        match self.consts.get(name) {
+            Some(value) => Ok(Expr::Const(*value)),
+            None => {
+                self.pos -= 1;
+                self.error(format!("unknown name `{name}`"))
+            }
+        }
+    }
+}
+
+/// the size of `vec2f`, `vec3i` and the like
+fn vector_alias(name: &str) -> Option<u8> {
+    match name.as_bytes() {
+        [b'v', b'e', b'c', n @ b'2'..=b'4', b'f' | b'h' | b'i' | b'u'] => Some(n - b'0'),
+        _ => None,
+    }
+}
+
+enum Flow {
+    Normal,
+    Break,
+    Continue,
+    Return(Value),
+}
+
+/// Runs the shader's functions, keeping their variables on a stack that is reused from one pixel
+/// to the next
+struct Evaluator<'a> {
+    functions: &'a [Function],
+    uniforms: &'a Uniforms,
+    stack: Vec<Value>,
+    iterations: u32,
+    depth: usize,
+}
+
+impl<'a> Evaluator<'a> {
+    fn new(functions: &'a [Function], uniforms: &'a Uniforms) -> Self {
+        Self {
+            functions,
+            uniforms,
+            stack: Vec::new(),
+            iterations: 0,
+            depth: 0,
+        }
+    }
+
+    /// Calls function `index` with the arguments `args`, evaluated in the frame starting at `base`
+    fn call(&mut self, index: usize, args: &[Expr], base: usize) -> Result<Value, String> {
+        let function = self
+            .functions
+            .get(index)
+            .ok_or("constants cannot call functions")?;
+        if args.len() != function.params {
+            return Err(format!(
+                "a function takes {} arguments, but got {}",
+                function.params,
+                args.len()
+            ));
+        }
+        if self.depth >= MAX_DEPTH {
+            return Err("functions are nested too deeply".to_string());
+        }
+        let frame = self.stack.len();
+        for arg in args {
+            let value = self.eval(arg, base)?;
+            self.stack.push(value);
+        }
+        self.stack.resize(frame + function.locals, Value::Num(0.0));
+        self.depth += 1;
+        let flow = self.exec(&function.body, frame);
+        self.depth -= 1;
+        self.stack.truncate(frame);
+        match flow? {
+            Flow::Return(value) => Ok(value),
+            // only used as statements
+            _ => Ok(Value::Num(0.0)),
+        }
+    }
+
+    fn exec(&mut self, stmts: &[Stmt], base: usize) -> Result<Flow, String> {
+        for stmt in stmts {
+            match self.stmt(stmt, base)? {
+                Flow::Normal => (),
+                flow => return Ok(flow),
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    fn stmt(&mut self, stmt: &Stmt, base: usize) -> Result<Flow, String> {
+        match stmt {
+            Stmt::Let(slot, value) => self.stack[base + slot] = self.eval(value, base)?,
+            Stmt::Assign(slot, access, op, value) => {
+                let mut value = self.eval(value, base)?;
+                let index = match access {
+                    Access::Index(index) => Some(self.eval(index, base)?.num()? as usize),
+                    _ => None,
+                };
+                let old = self.stack[base + slot];
+                if let Some(op) = op {
+                    let current = match access {
+                        Access::Whole => old,
+                        Access::Swizzle(components, len) => swizzle(old, components, *len)?,
+                        Access::Index(_) => swizzle(old, &[index.unwrap_or(0) as u8; 4], 1)?,
+                    };
+                    value = binary(*op, current, value)?;
+                }
+                self.stack[base + slot] = match access {
+                    Access::Whole => value,
+                    Access::Swizzle(components, len) => assign(old, components, *len, value)?,
+                    Access::Index(_) => assign(old, &[index.unwrap_or(0) as u8; 4], 1, value)?,
+                };
+            }
+            Stmt::If(condition, then, otherwise) => {
+                return match self.eval(condition, base)?.bool()? {
+                    true => self.exec(then, base),
+                    false => self.exec(otherwise, base),
+                };
+            }
+            Stmt::Loop {
+                condition,
+                body,
+                continuing,
+            } => loop {
+                if let Some(condition) = condition {
+                    if !self.eval(condition, base)?.bool()? {
+                        break;
+                    }
+                }
+                self.iterations += 1;
+                if self.iterations > MAX_ITERATIONS {
+                    return Err("a loop ran for too long".to_string());
+                }
+                match self.exec(body, base)? {
+                    Flow::Break => break,
+                    Flow::Return(value) => return Ok(Flow::Return(value)),
+                    Flow::Normal | Flow::Continue => (),
+                }
+                match self.exec(continuing, base)? {
+                    Flow::Break => break,
+                    Flow::Return(value) => return Ok(Flow::Return(value)),
+                    Flow::Normal | Flow::Continue => (),
+                }
+            },
+            Stmt::Break => return Ok(Flow::Break),
+            Stmt::Continue => return Ok(Flow::Continue),
+            Stmt::Return(value) => {
+                let value = match value {
+                    Some(value) => self.eval(value, base)?,
+                    None => Value::Num(0.0),
+                };
+                return Ok(Flow::Return(value));
+            }
+            Stmt::Expr(expr) => {
+                self.eval(expr, base)?;
+            }
+            Stmt::Block(stmts) => return self.exec(stmts, base),
+        }
+        Ok(Flow::Normal)
+    }
+
+    fn eval(&mut self, expr: &Expr, base: usize) -> Result<Value, String> {
+        Ok(match expr {
+            Expr::Const(value) => *value,
+            Expr::Local(slot) => self.stack[base + slot],
+            Expr::Uniform(uniform) => match uniform {
+                Uniform::Time => Value::Num(self.uniforms.time),
+                Uniform::Ratio => Value::Num(self.uniforms.ratio),
+                Uniform::Frame => Value::Num(self.uniforms.frame),
+                Uniform::Resolution => {
+                    let [width, height] = self.uniforms.resolution;
+                    Value::Vec(2, [width, height, 0.0, 0.0])
+                }
+            },
+            Expr::Neg(expr) => map(self.eval(expr, base)?, |x| -x)?,
+            Expr::Not(expr) => Value::Bool(!self.eval(expr, base)?.bool()?),
+            Expr::Binary(BinOp::And, left, right) => {
+                Value::Bool(self.eval(left, base)?.bool()? && self.eval(right, base)?.bool()?)
+            }
+            Expr::Binary(BinOp::Or, left, right) => {
+                Value::Bool(self.eval(left, base)?.bool()? || self.eval(right, base)?.bool()?)
+            }
+            Expr::Binary(op, left, right) => {
+                let left = self.eval(left, base)?;
+                binary(*op, left, self.eval(right, base)?)?
+            }
+            Expr::Call(index, args) => self.call(*index, args, base)?,
+            Expr::Builtin(builtin, args) => {
+                let mut values = [Value::Num(0.0); 3];
+                for (value, arg) in values.iter_mut().zip(args) {
+                    *value = self.eval(arg, base)?;
+                }
+                builtin.call(&values[..args.len()])?
+            }
+            Expr::Construct(len, args) => {
+                let mut c = [0.0; 4];
+                let mut count = 0;
+                for arg in args {
+                    let (n, values) = match self.eval(arg, base)? {
+                        Value::Bool(b) => (1, [b as u8 as f32; 4]),
+                        value => value.components()?,
+                    };
+                    for &value in &values[..n as usize] {
+                        if count < 4 {
+                            c[count] = value;
+                        }
+                        count += 1;
+                    }
+                }
+                match count {
+                    // a number fills the whole vector
+                    1 => c = [c[0]; 4],
+                    n if n == *len as usize => (),
+                    n => return Err(format!("a vec{len} needs {len} components, not {n}")),
+                }
+                Value::from_components(*len, c)
+            }
+            Expr::Int(expr) => match self.eval(expr, base)? {
+                Value::Bool(b) => Value::Num(b as u8 as f32),
+                value => Value::Num(value.num()?.trunc()),
+            },
+            Expr::ToBool(expr) => match self.eval(expr, base)? {
+                Value::Bool(b) => Value::Bool(b),
+                value => Value::Bool(value.num()? != 0.0),
+            },
+            Expr::Swizzle(expr, components, len) => {
+                swizzle(self.eval(expr, base)?, components, *len)?
+            }
+            Expr::Index(expr, index) => {
+                let value = self.eval(expr, base)?;
+                let index = self.eval(index, base)?.num()?;
+                swizzle(value, &[index as u8; 4], 1)?
+            }
+        })
+    }
+}
+
+fn binary(op: BinOp, left: Value, right: Value) -> Result<Value, String> {
+    let compare = |f: fn(f32, f32) -> bool| match (left, right) {
+        (Value::Num(a), Value::Num(b)) => Ok(Value::Bool(f(a, b))),
+        _ => Err(format!(
+            "cannot compare a {} with a {}",
+            left.type_name(),
+            right.type_name()
+        )),
+    };
+    match op {
+        BinOp::Add => zip(left, right, |a, b| a + b),
+        BinOp::Sub => zip(left, right, |a, b| a - b),
+        BinOp::Mul => zip(left, right, |a, b| a * b),
+        BinOp::Div => zip(left, right, |a, b| a / b),
+        BinOp::Rem => zip(left, right, |a, b| a % b),
+        BinOp::Eq | BinOp::Ne if matches!((left, right), (Value::Bool(_), Value::Bool(_))) => {
+            Ok(Value::Bool((left == right) == (op == BinOp::Eq)))
+        }
+        BinOp::Eq => compare(|a, b| a == b),
+        BinOp::Ne => compare(|a, b| a != b),
+        BinOp::Lt => compare(|a, b| a < b),
+        BinOp::Le => compare(|a, b| a <= b),
+        BinOp::Gt => compare(|a, b| a > b),
+        BinOp::Ge => compare(|a, b| a >= b),
+        BinOp::And | BinOp::Or => Ok(Value::Bool(match op {
+            BinOp::And => left.bool()? && right.bool()?,
+            _ => left.bool()? || right.bool()?,
+        })),
+    }
+}
+
+fn swizzle(value: Value, components: &[u8; 4], len: u8) -> Result<Value, String> {
+    let (n, values) = match value {
+        Value::Vec(n, values) => (n, values),
+        _ => return Err(format!("cannot take components of a {}", value.type_name())),
+    };
+    let mut c = [0.0; 4];
+    for i in 0..len as usize {
+        // out of bounds indices are clamped, like on the gpu
+        c[i] = values[(components[i]).min(n - 1) as usize];
+    }
+    Ok(Value::from_components(len, c))
+}
+
+fn assign(target: Value, components: &[u8; 4], len: u8, value: Value) -> Result<Value, String> {
+    let (n, mut c) = match target {
+        Value::Vec(n, c) => (n, c),
+        _ => return Err(format!("cannot set components of a {}", target.type_name())),
+    };
+    let (m, values) = value.components()?;
+    if m != 1 && m != len {
+        return Err(format!("cannot set {len} components to a vec{m}"));
+    }
+    for i in 0..len as usize {
+        c[(components[i]).min(n - 1) as usize] = values[i];
+    }
+    Ok(Value::Vec(n, c))
+}
+
+/// A parsed shader, defining `fn wallpaper(uv: vec2<f32>) -> vec4<f32>`
+pub(super) struct Program {
+    functions: Vec<Function>,
+    entry: usize,
+}
+
+impl Program {
+    pub(super) fn new(source: &str) -> Result<Self, String> {
+        let tokens = lex(source)?;
+        let parser = Parser {
+            tokens: &tokens,
+            pos: 0,
+            names: HashMap::new(),
+            functions: Vec::new(),
+            consts: HashMap::new(),
+            scopes: Vec::new(),
+            locals: 0,
+        };
+        let (functions, names) = parser.program()?;
+        let entry = match names.get("wallpaper") {
+            Some(&entry) if functions[entry].params == 1 => entry,
+            Some(_) => return Err("`wallpaper` must take the uv coordinates".to_string()),
+            None => return Err("the shader has no `wallpaper` function".to_string()),
+        };
+        Ok(Self { functions, entry })
+    }
+
+    /// Draws the shader onto `canvas`, which is `width` pixels of `channels` bytes wide, in blue,
+    /// green, red order if `swap`. The rows are split between threads
+    pub(super) fn render(
+        &self,
+        canvas: &mut [u8],
+        width: usize,
+        channels: usize,
+        swap: bool,
+        uniforms: &Uniforms,
+    ) -> Result<(), String> {
+        let row = width * channels;
+        let height = canvas.len() / row;
+        let threads = thread::available_parallelism()
+            .map_or(1, |n| n.get())
+            .min(height)
+            .max(1);
+        let rows = height.div_ceil(threads);
+        thread::scope(|s| {
+            let handles: Vec<_> = canvas
+                .chunks_mut(rows * row)
+                .enumerate()
+                .map(|(i, chunk)| {
+                    s.spawn(move || {
+                        let mut evaluator = Evaluator::new(&self.functions, uniforms);
+                        for (y, pixels) in chunk.chunks_exact_mut(row).enumerate() {
+                            let v = ((i * rows + y) as f32 + 0.5) / height as f32;
+                            for (x, pixel) in pixels.chunks_exact_mut(channels).enumerate() {
+                                let u = (x as f32 + 0.5) / width as f32;
+                                let color = evaluator.pixel(self.entry, u, v)?;
+                                let color =
+                                    color.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8);
+                                pixel[..3].copy_from_slice(&color);
+                                if swap {
+                                    pixel.swap(0, 2);
+                                }
+                            }
+                        }
+                        Ok(())
+                    })
+                })
+                .collect();
+            handles.into_iter().try_for_each(|handle| {
+                handle
+                    .join()
+                    .unwrap_or(Err("the shader panicked".to_string()))
+            })
+        })
+    }
+}
+
+impl Evaluator<'_> {
+    /// the color of the pixel at `u`, `v`
+    fn pixel(&mut self, entry: usize, u: f32, v: f32) -> Result<[f32; 3], String> {
+        self.iterations = 0;
+        let uv = [Expr::Const(Value::Vec(2, [u, v, 0.0, 0.0]))];
+        match self.call(entry, &uv, 0)? {
+            Value::Vec(3 | 4, c) => Ok([c[0], c[1], c[2]]),
+            value => Err(format!(
+                "`wallpaper` must return a vec4<f32>, not a {}",
+                value.type_name()
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixel(source: &str, u: f32, v: f32) -> Result<[f32; 3], String> {
+        let program = Program::new(source)?;
+        let uniforms = Uniforms {
+            time: 2.0,
+            ratio: 2.0,
+            frame: 60.0,
+            resolution: [4.0, 2.0],
+        };
+        Evaluator::new(&program.functions, &uniforms).pixel(program.entry, u, v)
+    }
+
+    #[test]
+    fn shaders_should_compute_colors() {
+        let source = "
+            const HALF: f32 = 0.5;
+
+            // functions can be called before they are defined
+            @fragment
+            fn wallpaper(uv: vec2<f32>) -> vec4<f32> {
+                var color = vec3f(shade(uv.x), uv.yx);
+                color.b += HALF * f32(swww.time > 1.0);
+                return vec4<f32>(color, 1.0);
+            }
+
+            fn shade(x: f32) -> f32 {
+                return clamp(x * 2.0, 0.0, 1.0);
+            }
+        ";
+        assert_eq!(pixel(source, 0.25, 0.75), Ok([0.5, 0.75, 0.75]));
+    }
+
+    #[test]
+    fn loops_and_branches_should_work() {
+        let source = "
+            fn wallpaper(uv: vec2<f32>) -> vec4<f32> {
+                var sum = 0.0;
+                for (var i = 0; i < 10; i++) {
+                    if i % 2 == 0 { continue; } else if i > 7 { break; }
+                    sum += i; // 1 + 3 + 5 + 7
+                }
+                var n = 0;
+                loop {
+                    n += 1;
+                    continuing { break if n >= 4; }
+                }
+                while sum > 10.0 { sum -= 10.0; }
+                /* nested /* comments */ work */
+                return vec4(sum / 10.0, n / 10.0, select(0.0, 1.0, swww.frame == 60.0), 1.0);
+            }
+        ";
+        assert_eq!(pixel(source, 0.0, 0.0), Ok([0.6, 0.4, 1.0]));
+    }
+
+    #[test]
+    fn builtins_should_match_wgsl() {
+        let source = "
+            fn wallpaper(uv: vec2<f32>) -> vec4<f32> {
+                let v = normalize(vec2(3.0, 4.0));
+                return vec4(round(2.5) / 4.0, length(swww.resolution - vec2(1.0, -2.0)) / 10.0,
+                    smoothstep(0.0, 1.0, 0.5) * dot(v, vec2(1.0, 0.0)) / 0.6, 1.0);
+            }
+        ";
+        let [r, g, b] = pixel(source, 0.0, 0.0).unwrap();
+        assert_eq!(r, 0.5);
+        assert_eq!(g, 0.5);
+        assert!((b - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn rendering_should_follow_the_pixel_format() {
+        let program = Program::new(
+            "fn wallpaper(uv: vec2<f32>) -> vec4<f32> { return vec4(uv.x, 0.0, 1.0, 1.0); }",
+        )
+        .unwrap();
+        let mut canvas = [0; 2 * 3 * 4];
+        program
+            .render(&mut canvas, 2, 4, true, &Uniforms::default())
+            .unwrap();
+        // u is 0.25 and 0.75, at the center of the pixels
+        assert_eq!(canvas[..8], [255, 0, 64, 0, 255, 0, 191, 0]);
+        assert_eq!(canvas[..8], canvas[16..]);
+    }
+
+    #[test]
+    fn unsupported_shaders_should_be_refused() {
+        for source in [
+            "",
+            "fn main() {}",
+            "struct S { a: f32 } fn wallpaper(uv: vec2<f32>) -> vec4<f32> { return vec4(1.0); }",
+            "fn wallpaper(uv: vec2<f32>) -> vec4<f32> { return vec4(unknown); }",
+            "fn wallpaper(uv: vec2<f32>) -> vec4<f32> { let m: mat2x2<f32>; return vec4(1.0); }",
+            "fn wallpaper(uv: vec2<f32>) -> vec4<f32> { return vec4(1.0) }",
+            "/* é */ fn wallpaper(uv: vec2<f32>) -> vec4<f32> { return vec4(é); }",
+        ] {
+            assert!(Program::new(source).is_err(), "{source}");
+        }
+        for source in [
+            "fn wallpaper(uv: vec2<f32>) -> vec4<f32> { loop {} }",
+            "fn wallpaper(uv: vec2<f32>) -> vec4<f32> { return vec4(uv, 1.0); }",
+            "fn wallpaper(uv: vec2<f32>) -> vec4<f32> { return uv.x; }",
+            "fn wallpaper(uv: vec2<f32>) -> vec4<f32> { return wallpaper(uv); }",
+        ] {
+            assert!(pixel(source, 0.0, 0.0).is_err(), "{source}");
+        }
+    }
+}
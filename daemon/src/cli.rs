@@ -1,9 +1,32 @@
 use common::ipc::PixelFormat;
 
+use crate::animations::ReduceMotion;
+use crate::greeter;
+use crate::limits::Limits;
+use crate::output_order::OutputOrder;
+use crate::sleep;
+use crate::wayland::interfaces::zwlr_layer_shell_v1::layer;
+
 pub struct Cli {
     pub format: Option<PixelFormat>,
     pub quiet: bool,
     pub no_cache: bool,
+    pub reduce_motion: ReduceMotion,
+    pub output_order: OutputOrder,
+    pub accept_pointer: bool,
+    /// start the first transition on each output from a capture of what it showed
+    pub capture_first_frame: bool,
+    /// the zwlr_layer_shell_v1 layer to put the wallpapers in
+    pub layer: u32,
+    pub greeter: Option<greeter::Config>,
+    /// the file the greeter told us about its wallpaper in
+    pub handoff: Option<std::path::PathBuf>,
+    /// how we wait for the next frame
+    pub sleep: sleep::Strategy,
+    pub limits: Limits,
+    /// the DRM device to draw to directly, instead of going through a compositor
+    #[cfg_attr(not(feature = "drm"), allow(dead_code))]
+    pub drm: Option<std::path::PathBuf>,
 }
 
 impl Cli {
@@ -11,6 +34,17 @@ impl Cli {
         let mut quiet = false;
         let mut no_cache = false;
         let mut format = None;
+        let mut reduce_motion = ReduceMotion::Off;
+        let mut output_order = OutputOrder::Name;
+        let mut accept_pointer = false;
+        let mut capture_first_frame = false;
+        let mut layer = layer::BACKGROUND;
+        let mut greeter = None;
+        let mut handoff = None;
+        let mut sleep = sleep::Strategy::Hybrid(None);
+        let mut sleep_accuracy = None;
+        let mut limits = None;
+        let mut drm = None;
         let mut args = std::env::args();
         args.next(); // skip the first argument
 
@@ -28,6 +62,90 @@ impl Cli {
                 },
                 "-q" | "--quiet" => quiet = true,
                 "--no-cache" => no_cache = true,
+                "--accept-pointer" => accept_pointer = true,
+                "--capture-first-frame" => capture_first_frame = true,
+                "-l" | "--layer" => match args.next().as_deref() {
+                    Some("background") => layer = layer::BACKGROUND,
+                    Some("bottom") => layer = layer::BOTTOM,
+                    Some("top") => layer = layer::TOP,
+                    Some("overlay") => layer = layer::OVERLAY,
+                    _ => {
+                        eprintln!("`--layer` command line option must be one of: 'background', 'bottom', 'top' or 'overlay'");
+                        std::process::exit(-2);
+                    }
+                },
+                "--greeter" => match args.next() {
+                    Some(path) => match greeter::Config::load(path.as_ref()) {
+                        Ok(config) => greeter = Some(config),
+                        Err(e) => {
+                            eprintln!("`--greeter`: {e}");
+                            std::process::exit(-2);
+                        }
+                    },
+                    None => {
+                        eprintln!("`--greeter` command line option must be given a file");
+                        std::process::exit(-2);
+                    }
+                },
+                "--handoff" => match args.next() {
+                    Some(path) => handoff = Some(path.into()),
+                    None => {
+                        eprintln!("`--handoff` command line option must be given a file");
+                        std::process::exit(-2);
+                    }
+                },
+                "--limits" => match args.next() {
+                    Some(path) => limits = Some(std::path::PathBuf::from(path)),
+                    None => {
+                        eprintln!("`--limits` command line option must be given a file");
+                        std::process::exit(-2);
+                    }
+                },
+                "--drm" => match args.next() {
+                    Some(_) if cfg!(not(feature = "drm")) => {
+                        eprintln!("swww-daemon was built without the `drm` feature, so it cannot use `--drm`");
+                        std::process::exit(-2);
+                    }
+                    Some(device) => drm = Some(std::path::PathBuf::from(device)),
+                    None => {
+                        eprintln!("`--drm` requires a device, like /dev/dri/card0");
+                        std::process::exit(-2);
+                    }
+                },
+                "--sleep" => match args.next().as_deref().and_then(sleep::Strategy::parse) {
+                    Some(s) => sleep = s,
+                    None => {
+                        eprintln!(
+                            "`--sleep` command line option must be one of: 'sleep', 'hybrid' or 'spin'"
+                        );
+                        std::process::exit(-2);
+                    }
+                },
+                "--sleep-accuracy" => match args.next().and_then(|s| s.parse::<u64>().ok()) {
+                    Some(us) => sleep_accuracy = Some(std::time::Duration::from_micros(us)),
+                    None => {
+                        eprintln!("`--sleep-accuracy` command line option must be given a number of microseconds");
+                        std::process::exit(-2);
+                    }
+                },
+                "--reduce-motion" => match args.next().as_deref().and_then(ReduceMotion::parse) {
+                    Some(r) => reduce_motion = r,
+                    None => {
+                        eprintln!(
+                            "`--reduce-motion` command line option must be one of: 'off', 'fade' or 'instant'"
+                        );
+                        std::process::exit(-2);
+                    }
+                },
+                "--output-order" => match args.next().as_deref().and_then(OutputOrder::parse) {
+                    Some(o) => output_order = o,
+                    None => {
+                        eprintln!(
+                            "`--output-order` command line option must be one of: 'name', 'position' or 'registry'"
+                        );
+                        std::process::exit(-2);
+                    }
+                },
                 "-h" | "--help" => {
                     println!("swww-daemon");
                     println!();
@@ -49,6 +167,81 @@ impl Cli {
                     );
                     println!("          Useful if you always want to select which image 'swww' loads manually using 'swww img'");
                     println!();
+                    println!("  --reduce-motion <off|fade|instant>");
+                    println!("          replace every transition with a simple fade (or with no transition at");
+                    println!("          all, for 'instant'), and only show the first frame of animations.");
+                    println!("          Can be changed later with 'swww set-var reduce_motion <off|fade|instant>'");
+                    println!();
+                    println!("  --output-order <name|position|registry>");
+                    println!("          the order 'swww query' lists the outputs in, which is also the order");
+                    println!("          clients give out images to them. 'name' (the default) sorts them by");
+                    println!(
+                        "          name, 'position' left to right in the compositor's layout, and"
+                    );
+                    println!(
+                        "          'registry' keeps the order the compositor announced them in."
+                    );
+                    println!(
+                        "          Can be changed later with 'swww set-var output_order <ORDER>'"
+                    );
+                    println!();
+                    println!("  --accept-pointer");
+                    println!("          let the wallpaper receive pointer input, for interactive wallpapers.");
+                    println!("          By default, all pointer input goes through the wallpaper to whatever");
+                    println!("          is below it.");
+                    println!("          Clicks run the commands of hotspots set with 'swww set-var hotspot.<name>'");
+                    println!();
+                    println!("  --capture-first-frame");
+                    println!("          start the first transition on each output from a capture of what it");
+                    println!(
+                        "          showed before, windows included, instead of from black. Needs a"
+                    );
+                    println!("          compositor with wlr-screencopy");
+                    println!();
+                    println!("  -l|--layer <background|bottom|top|overlay>");
+                    println!(
+                        "          which layer to put the wallpapers in. Defaults to 'background'."
+                    );
+                    println!();
+                    println!("  --greeter <FILE>");
+                    println!("          run as the login screen's wallpaper daemon (e.g. for greetd), showing");
+                    println!("          the images from FILE instead of the cache. It also waits for the");
+                    println!("          compositor to start, and keeps the clients' cache in the directory");
+                    println!("          FILE sets, or else in $XDG_RUNTIME_DIR/swww-greeter.");
+                    println!("          See swww-daemon(1) for the file's format");
+                    println!();
+                    println!("  --handoff <FILE>");
+                    println!("          start from the wallpaper the login screen's daemon wrote to FILE,");
+                    println!(
+                        "          if it exists, and then fade into the images from the cache."
+                    );
+                    println!();
+                    println!("  --limits <FILE>");
+                    println!("          cap the fps of transitions, the resolution of animations, the size");
+                    println!("          of the cache and how many outputs can be animated at once, as set");
+                    println!("          in FILE. Defaults to /etc/swww/limits, if it exists.");
+                    println!("          See swww-daemon(1) for the file's format");
+                    println!();
+                    println!("  --drm <DEVICE>");
+                    println!("          draw straight to the screens of DEVICE (like /dev/dri/card0), for");
+                    println!("          kiosks and picture frames without a compositor. Only 'img', 'clear',");
+                    println!("          'query' and 'kill' work there, and animations only show their first");
+                    println!("          frame. Requires building with the 'drm' feature");
+                    println!();
+                    println!("  --sleep <sleep|hybrid|spin>");
+                    println!(
+                        "          how to wait for the next frame of transitions and animations."
+                    );
+                    println!(
+                        "          'hybrid' (the default) sleeps until shortly before it, and then"
+                    );
+                    println!("          spins. 'sleep' uses less cpu but can be late, 'spin' is the most");
+                    println!("          precise but keeps a cpu core busy");
+                    println!();
+                    println!("  --sleep-accuracy <MICROSECONDS>");
+                    println!("          how long before the next frame 'hybrid' stops sleeping. By default,");
+                    println!("          it is measured at startup");
+                    println!();
                     println!("  -q|--quiet    will only log errors");
                     println!("  -h|--help     print help");
                     println!("  -V|--version  print version");
@@ -66,10 +259,36 @@ impl Cli {
             }
         }
 
+        if let Some(accuracy) = sleep_accuracy {
+            if sleep != sleep::Strategy::Hybrid(None) {
+                eprintln!("`--sleep-accuracy` can only be used with `--sleep hybrid`");
+                std::process::exit(-2);
+            }
+            sleep = sleep::Strategy::Hybrid(Some(accuracy));
+        }
+
+        let limits = match Limits::load(limits.as_deref()) {
+            Ok(limits) => limits,
+            Err(e) => {
+                eprintln!("`--limits`: {e}");
+                std::process::exit(-2);
+            }
+        };
+
         Self {
             format,
             quiet,
             no_cache,
+            reduce_motion,
+            output_order,
+            accept_pointer,
+            capture_first_frame,
+            layer,
+            greeter,
+            handoff,
+            sleep,
+            limits,
+            drm,
         }
     }
 }
@@ -0,0 +1,535 @@
+//! Color filters applied to everything we draw: the tint, the accessibility filters, the dim and
+//! the tint's vignette, in that order.
+//!
+//! Filters are represented as lookup tables, so applying one costs a few table lookups per
+//! pixel, regardless of how complicated the filter itself is. The vignette depends on where the
+//! pixel is, so it picks one of its tables by the pixel's distance to the center.
+use std::{
+    collections::HashMap,
+    sync::{mpsc, Arc},
+    time::Duration,
+};
+
+use common::ipc::{ColorFilter, PixelFormat};
+
+use crate::wallpaper::Wallpaper;
+
+/// How many entries we use to convert linear light back into srgb
+const LINEAR_LEVELS: usize = 1 << 14;
+
+/// In how many steps the vignette darkens, from the center to the corners
+const VIGNETTE_LEVELS: usize = 256;
+
+/// Tables that apply a 3x3 color matrix in linear light
+struct MatrixTables {
+    /// contribution of the value of each input channel to each output channel, already in linear
+    /// light: `contrib[out][in][value]`
+    contrib: [[[f32; 256]; 3]; 3],
+    /// converts (quantized) linear light back into srgb, for each output channel. These already
+    /// include the dim
+    to_srgb: [Box<[u8]>; 3],
+}
+
+/// The lookup tables corresponding to a tint, an accessibility filter and a dim
+///
+/// The tables are already in the order the channels are laid out in memory, so the padding byte
+/// of 4-channel formats is the only one we leave alone
+pub(crate) struct Lut {
+    /// per channel tables. These already include the tint, and the dim if there is no `matrix`
+    tables: [[u8; 256]; 3],
+    /// color matrix to apply after the tables, for the filters that mix channels together
+    matrix: Option<Box<MatrixTables>>,
+    /// a table for each level of the vignette, indexed by the squared distance to the center
+    vignette: Option<Box<[[u8; 256]]>>,
+}
+
+impl Lut {
+    /// `tint` and `dim` are the color and strength we blend towards before and after the filter.
+    /// `vignette` is how much we darken the corners after that, 0 meaning not at all
+    pub(crate) fn new(
+        format: PixelFormat,
+        tint: Option<([u8; 3], u8)>,
+        filter: ColorFilter,
+        dim: Option<([u8; 3], u8)>,
+        vignette: u8,
+    ) -> Self {
+        let dim_channel = |channel: usize, value: u8| match dim {
+            Some((color, strength)) => tint_channel(value, color[channel], strength),
+            None => value,
+        };
+
+        let mut tables = [[0u8; 256]; 3];
+        for (channel, table) in tables.iter_mut().enumerate() {
+            for (value, entry) in table.iter_mut().enumerate() {
+                let mut value = value as u8;
+                if let Some((color, strength)) = tint {
+                    value = tint_channel(value, color[channel], strength);
+                }
+                *entry = match filter {
+                    ColorFilter::Invert => 255 - value,
+                    ColorFilter::HighContrast => {
+                        ((value as i16 - 128) * 2 + 128).clamp(0, 255) as u8
+                    }
+                    _ => value,
+                };
+            }
+        }
+
+        let mut matrix = color_matrix(filter).map(|m| {
+            let mut contrib = [[[0f32; 256]; 3]; 3];
+            for (out, row) in contrib.iter_mut().enumerate() {
+                for (input, table) in row.iter_mut().enumerate() {
+                    for (value, entry) in table.iter_mut().enumerate() {
+                        *entry = m[out][input] * srgb_to_linear(tables[input][value]);
+                    }
+                }
+            }
+            let to_srgb = [0, 1, 2].map(|channel| {
+                (0..LINEAR_LEVELS)
+                    .map(|i| linear_to_srgb(i as f32 / (LINEAR_LEVELS - 1) as f32))
+                    .map(|value| dim_channel(channel, value))
+                    .collect()
+            });
+            Box::new(MatrixTables { contrib, to_srgb })
+        });
+        if matrix.is_none() {
+            for (channel, table) in tables.iter_mut().enumerate() {
+                for entry in table.iter_mut() {
+                    *entry = dim_channel(channel, *entry);
+                }
+            }
+        }
+
+        if format.must_swap_r_and_b_channels() {
+            tables.swap(0, 2);
+            if let Some(matrix) = matrix.as_mut() {
+                matrix.contrib.swap(0, 2);
+                matrix.to_srgb.swap(0, 2);
+                for row in matrix.contrib.iter_mut() {
+                    row.swap(0, 2);
+                }
+            }
+        }
+
+        let vignette = (vignette > 0).then(|| {
+            let strength = vignette as f32 / 255.0;
+            let mut tables = vec![[0u8; 256]; VIGNETTE_LEVELS].into_boxed_slice();
+            for (level, table) in tables.iter_mut().enumerate() {
+                let factor = 1.0 - strength * level as f32 / (VIGNETTE_LEVELS - 1) as f32;
+                for (value, entry) in table.iter_mut().enumerate() {
+                    *entry = (value as f32 * factor).round() as u8;
+                }
+            }
+            tables
+        });
+        Self {
+            tables,
+            matrix,
+            vignette,
+        }
+    }
+
+    /// Writes `src` into `dst`, passing every pixel through the lut. `width` is how many pixels
+    /// each row has, for the vignette
+    pub(crate) fn apply(&self, src: &[u8], dst: &mut [u8], format: PixelFormat, width: usize) {
+        let channels = format.channels() as usize;
+        let pixels = src
+            .chunks_exact(channels)
+            .zip(dst.chunks_exact_mut(channels));
+        match &self.matrix {
+            None => {
+                for (src, dst) in pixels {
+                    dst[0] = self.tables[0][src[0] as usize];
+                    dst[1] = self.tables[1][src[1] as usize];
+                    dst[2] = self.tables[2][src[2] as usize];
+                    if channels == 4 {
+                        dst[3] = src[3];
+                    }
+                }
+            }
+            Some(matrix) => {
+                let max = (LINEAR_LEVELS - 1) as f32;
+                for (src, dst) in pixels {
+                    for (out, contrib) in matrix.contrib.iter().enumerate() {
+                        let linear = contrib[0][src[0] as usize]
+                            + contrib[1][src[1] as usize]
+                            + contrib[2][src[2] as usize];
+                        dst[out] = matrix.to_srgb[out][(linear.clamp(0.0, 1.0) * max) as usize];
+                    }
+                    if channels == 4 {
+                        dst[3] = src[3];
+                    }
+                }
+            }
+        }
+        if let Some(vignette) = &self.vignette {
+            apply_vignette(vignette, dst, channels, width);
+        }
+    }
+}
+
+/// Darkens `canvas` towards its edges, through the table of each pixel's level
+fn apply_vignette(tables: &[[u8; 256]], canvas: &mut [u8], channels: usize, width: usize) {
+    if width == 0 {
+        return;
+    }
+    let height = canvas.len() / channels / width;
+    // the squared distance to the center along each axis, so that adding both gives the level,
+    // from 0 in the center to the last one in the corners
+    let levels = |len: usize| -> Vec<usize> {
+        let half = (VIGNETTE_LEVELS - 1) as f32 / 2.0;
+        (0..len)
+            .map(|i| {
+                let distance = (i as f32 + 0.5) / len as f32 * 2.0 - 1.0;
+                (distance * distance * half).round() as usize
+            })
+            .collect()
+    };
+    let (columns, rows) = (levels(width), levels(height));
+    for (row, line) in rows.iter().zip(canvas.chunks_exact_mut(width * channels)) {
+        for (column, pixel) in columns.iter().zip(line.chunks_exact_mut(channels)) {
+            let table = &tables[(row + column).min(VIGNETTE_LEVELS - 1)];
+            pixel[0] = table[pixel[0] as usize];
+            pixel[1] = table[pixel[1] as usize];
+            pixel[2] = table[pixel[2] as usize];
+        }
+    }
+}
+
+fn tint_channel(value: u8, color: u8, strength: u8) -> u8 {
+    let s = strength as u16;
+    ((value as u16 * (255 - s) + color as u16 * s) / 255) as u8
+}
+
+/// Color blindness simulation matrices, from Machado, Oliveira and Fernandes (2009), "A
+/// Physiologically-based Model for Simulation of Color Vision Deficiency". Compensation matrices
+/// shift the colors a person cannot see into the ones they can (i.e. "daltonization")
+fn color_matrix(filter: ColorFilter) -> Option<[[f32; 3]; 3]> {
+    const PROTANOPIA: [[f32; 3]; 3] = [
+        [0.152286, 1.052583, -0.204868],
+        [0.114503, 0.786281, 0.099216],
+        [-0.003882, -0.048116, 1.051998],
+    ];
+    const DEUTERANOPIA: [[f32; 3]; 3] = [
+        [0.367322, 0.860646, -0.227968],
+        [0.280085, 0.672501, 0.047413],
+        [-0.011820, 0.042940, 0.968881],
+    ];
+    const TRITANOPIA: [[f32; 3]; 3] = [
+        [1.255528, -0.076749, -0.178779],
+        [-0.078411, 0.930809, 0.147602],
+        [0.004733, 0.691367, 0.303900],
+    ];
+    // how the error between the real and the simulated colors gets redistributed
+    const RED_GREEN_SHIFT: [[f32; 3]; 3] = [[0.0, 0.0, 0.0], [0.7, 1.0, 0.0], [0.7, 0.0, 1.0]];
+    const BLUE_YELLOW_SHIFT: [[f32; 3]; 3] = [[1.0, 0.0, 0.7], [0.0, 1.0, 0.7], [0.0, 0.0, 0.0]];
+
+    match filter {
+        ColorFilter::None | ColorFilter::Invert | ColorFilter::HighContrast => None,
+        ColorFilter::ProtanopiaSimulation => Some(PROTANOPIA),
+        ColorFilter::DeuteranopiaSimulation => Some(DEUTERANOPIA),
+        ColorFilter::TritanopiaSimulation => Some(TRITANOPIA),
+        ColorFilter::ProtanopiaCompensation => Some(compensation(PROTANOPIA, RED_GREEN_SHIFT)),
+        ColorFilter::DeuteranopiaCompensation => Some(compensation(DEUTERANOPIA, RED_GREEN_SHIFT)),
+        ColorFilter::TritanopiaCompensation => Some(compensation(TRITANOPIA, BLUE_YELLOW_SHIFT)),
+    }
+}
+
+/// computes `I + shift * (I - simulation)`
+fn compensation(simulation: [[f32; 3]; 3], shift: [[f32; 3]; 3]) -> [[f32; 3]; 3] {
+    let mut error = [[0f32; 3]; 3];
+    for (i, row) in error.iter_mut().enumerate() {
+        for (j, e) in row.iter_mut().enumerate() {
+            *e = (i == j) as u8 as f32 - simulation[i][j];
+        }
+    }
+    let mut out = [[0f32; 3]; 3];
+    for (i, row) in out.iter_mut().enumerate() {
+        for (j, o) in row.iter_mut().enumerate() {
+            *o = (i == j) as u8 as f32 + (0..3).map(|k| shift[i][k] * error[k][j]).sum::<f32>();
+        }
+    }
+    out
+}
+
+fn srgb_to_linear(value: u8) -> f32 {
+    let v = value as f32 / 255.0;
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(linear: f32) -> u8 {
+    let v = if linear <= 0.0031308 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    };
+    (v * 255.0).round().clamp(0.0, 255.0) as u8
+}
+
+/// A tint scheduled through the `tint.*` variables
+///
+/// * `tint.color`: the color to tint towards, in rrggbb format. Empty disables the tint;
+/// * `tint.strength`: how strong the tint gets, from 0 to 1. Defaults to 0.5;
+/// * `tint.vignette`: how dark the corners get, from 0 to 1. Defaults to 0, for no vignette. It
+///   works without a `tint.color` too;
+/// * `tint.start` and `tint.end`: unix timestamps (in seconds). If both are set, the tint (and
+///   the vignette) starts at 0 in `tint.start` and grows linearly until it reaches
+///   `tint.strength` (and `tint.vignette`) in `tint.end`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Tint {
+    pub color: Option<[u8; 3]>,
+    strength: f32,
+    vignette: f32,
+    schedule: Option<(f64, f64)>,
+}
+
+impl Tint {
+    pub(crate) fn from_vars(vars: &HashMap<String, String>) -> Result<Option<Self>, String> {
+        let color = match vars.get("tint.color").map(|s| s.trim()) {
+            None | Some("") => None,
+            Some(color) => Some(parse_color(color)?),
+        };
+
+        let fraction = |key: &str, default: f32| -> Result<f32, String> {
+            match vars.get(key) {
+                Some(s) => Ok(s
+                    .trim()
+                    .parse::<f32>()
+                    .map_err(|e| format!("invalid {key} {s:?}: {e}"))?
+                    .clamp(0.0, 1.0)),
+                None => Ok(default),
+            }
+        };
+        let strength = fraction("tint.strength", 0.5)?;
+        let vignette = fraction("tint.vignette", 0.0)?;
+        if color.is_none() && vignette == 0.0 {
+            return Ok(None);
+        }
+
+        let timestamp = |key: &str| -> Result<Option<f64>, String> {
+            match vars.get(key) {
+                Some(s) => s
+                    .trim()
+                    .parse::<f64>()
+                    .map(Some)
+                    .map_err(|e| format!("invalid {key} {s:?}: {e}")),
+                None => Ok(None),
+            }
+        };
+        let schedule = match (timestamp("tint.start")?, timestamp("tint.end")?) {
+            (Some(start), Some(end)) => Some((start, end)),
+            _ => None,
+        };
+
+        Ok(Some(Self {
+            color,
+            strength,
+            vignette,
+            schedule,
+        }))
+    }
+
+    /// How far along the schedule we are at `now` (in seconds since the unix epoch), from 0 to 1
+    fn progress(&self, now: f64) -> f32 {
+        let progress = match self.schedule {
+            None => 1.0,
+            Some((start, end)) if end <= start => (now >= end) as u8 as f64,
+            Some((start, end)) => ((now - start) / (end - start)).clamp(0.0, 1.0),
+        };
+        progress as f32
+    }
+
+    /// The tint's strength at `now` (in seconds since the unix epoch), quantized to a u8
+    pub(crate) fn strength_at(&self, now: f64) -> u8 {
+        (self.progress(now) * self.strength * 255.0).round() as u8
+    }
+
+    /// The vignette's strength at `now`, like `Tint::strength_at`
+    pub(crate) fn vignette_at(&self, now: f64) -> u8 {
+        (self.progress(now) * self.vignette * 255.0).round() as u8
+    }
+
+    /// How long until we should check the strength again, if it still has to change
+    pub(crate) fn next_update(&self, now: f64) -> Option<Duration> {
+        match self.schedule {
+            Some((_, end)) if now < end => Some(Duration::from_secs_f64((end - now).min(1.0))),
+            _ => None,
+        }
+    }
+}
+
+fn parse_color(hex: &str) -> Result<[u8; 3], String> {
+    let hex = hex.strip_prefix('#').unwrap_or(hex);
+    if hex.len() != 6 || !hex.is_ascii() {
+        return Err(format!(
+            "invalid tint.color {hex:?}: expected rrggbb format"
+        ));
+    }
+    let mut color = [0u8; 3];
+    for (i, c) in color.iter_mut().enumerate() {
+        *c = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16)
+            .map_err(|e| format!("invalid tint.color {hex:?}: {e}"))?;
+    }
+    Ok(color)
+}
+
+/// The wallpapers to redraw with new filters, with the lut of each one (`None` removes it)
+type Refilter = Vec<(Arc<Wallpaper>, Option<Arc<Lut>>)>;
+
+/// The thread redrawing the wallpapers whose filters changed, so the main loop does not wait for
+/// it. Changes are applied in the order they are sent
+pub(crate) struct Worker {
+    tx: mpsc::Sender<Refilter>,
+}
+
+impl Worker {
+    pub(crate) fn new() -> Self {
+        let (tx, rx) = mpsc::channel::<Refilter>();
+        std::thread::Builder::new()
+            .stack_size(1 << 15)
+            .name("filter".to_string())
+            .spawn(move || {
+                for wallpapers in rx {
+                    for (wallpaper, lut) in &wallpapers {
+                        wallpaper.set_filter(lut.clone());
+                    }
+                    let wallpapers: Vec<_> = wallpapers.into_iter().map(|(w, _)| w).collect();
+                    crate::wallpaper::attach_buffers_and_damange_surfaces(&wallpapers);
+                    crate::wallpaper::commit_wallpapers(&wallpapers);
+                }
+            })
+            .unwrap(); // builder only failed if the name contains null bytes
+        Self { tx }
+    }
+
+    pub(crate) fn refilter(&self, wallpapers: Refilter) {
+        // the thread only stops once we drop the sender
+        self.tx.send(wallpapers).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn tint_needs_a_color() {
+        assert_eq!(Tint::from_vars(&vars(&[])), Ok(None));
+        assert_eq!(Tint::from_vars(&vars(&[("tint.color", "")])), Ok(None));
+        assert!(Tint::from_vars(&vars(&[("tint.color", "red")])).is_err());
+    }
+
+    #[test]
+    fn tint_strength_follows_schedule() {
+        let tint = Tint::from_vars(&vars(&[
+            ("tint.color", "ff0000"),
+            ("tint.strength", "1"),
+            ("tint.start", "100"),
+            ("tint.end", "200"),
+        ]))
+        .unwrap()
+        .unwrap();
+        assert_eq!(tint.color, Some([255, 0, 0]));
+        assert_eq!(tint.strength_at(0.0), 0);
+        assert_eq!(tint.strength_at(150.0), 128);
+        assert_eq!(tint.strength_at(300.0), 255);
+        assert!(tint.next_update(150.0).is_some());
+        assert!(tint.next_update(300.0).is_none());
+    }
+
+    #[test]
+    fn tint_lut_respects_channel_order() {
+        let lut = Lut::new(
+            PixelFormat::Xrgb,
+            Some(([255, 0, 0], 255)),
+            ColorFilter::None,
+            None,
+            0,
+        );
+        let src = [10, 20, 30, 40];
+        let mut dst = [0; 4];
+        lut.apply(&src, &mut dst, PixelFormat::Xrgb, 1);
+        // Xrgb stores blue first
+        assert_eq!(dst, [0, 0, 255, 40]);
+    }
+
+    #[test]
+    fn color_matrices_keep_grays() {
+        for filter in [
+            ColorFilter::ProtanopiaSimulation,
+            ColorFilter::DeuteranopiaCompensation,
+            ColorFilter::TritanopiaCompensation,
+        ] {
+            let lut = Lut::new(PixelFormat::Bgr, None, filter, None, 0);
+            let src = [0, 0, 0, 128, 128, 128, 255, 255, 255];
+            let mut dst = [0; 9];
+            lut.apply(&src, &mut dst, PixelFormat::Bgr, 3);
+            for (a, b) in src.iter().zip(dst.iter()) {
+                assert!(a.abs_diff(*b) <= 2, "{filter:?}: {src:?} became {dst:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn dim_comes_after_the_filter() {
+        let src = [0, 100, 255];
+        for filter in [ColorFilter::Invert, ColorFilter::ProtanopiaSimulation] {
+            let lut = Lut::new(PixelFormat::Bgr, None, filter, Some(([0, 0, 0], 255)), 0);
+            let mut dst = [1; 3];
+            lut.apply(&src, &mut dst, PixelFormat::Bgr, 1);
+            assert_eq!(dst, [0, 0, 0], "{filter:?}");
+        }
+
+        let lut = Lut::new(
+            PixelFormat::Xrgb,
+            None,
+            ColorFilter::None,
+            Some(([0, 0, 255], 128)),
+            0,
+        );
+        let src = [0, 0, 0, 7];
+        let mut dst = [0; 4];
+        lut.apply(&src, &mut dst, PixelFormat::Xrgb, 1);
+        // Xrgb stores blue first
+        assert_eq!(dst, [128, 0, 0, 7]);
+    }
+
+    #[test]
+    fn vignette_works_without_a_color() {
+        let tint = Tint::from_vars(&vars(&[("tint.vignette", "0.5")]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(tint.color, None);
+        assert_eq!(tint.strength_at(0.0), 128);
+        assert_eq!(tint.vignette_at(0.0), 128);
+        assert!(Tint::from_vars(&vars(&[("tint.vignette", "dark")])).is_err());
+        assert_eq!(Tint::from_vars(&vars(&[("tint.vignette", "0")])), Ok(None));
+    }
+
+    #[test]
+    fn vignette_darkens_the_corners_only() {
+        let lut = Lut::new(PixelFormat::Xrgb, None, ColorFilter::None, None, 255);
+        let (width, height) = (5, 5);
+        let src = vec![200; width * height * 4];
+        let mut dst = vec![0; src.len()];
+        lut.apply(&src, &mut dst, PixelFormat::Xrgb, width);
+        let pixel = |x: usize, y: usize| &dst[(y * width + x) * 4..][..4];
+        assert_eq!(pixel(2, 2), [200, 200, 200, 200]);
+        assert!(pixel(0, 2)[0] < 200 && pixel(0, 2)[0] > pixel(0, 0)[0]);
+        assert!(pixel(0, 0)[0] < 100);
+        // the padding byte is left alone
+        assert_eq!(pixel(0, 0)[3], 200);
+        assert_eq!(pixel(0, 0), pixel(4, 4));
+    }
+}
@@ -0,0 +1,204 @@
+//! Greeter mode, for showing the wallpaper on the login screen (`swww-daemon --greeter <FILE>`).
+//!
+//! Greeters like regreet run as their own user, usually without a home directory, under a
+//! compositor that might still be starting up. So in this mode the daemon waits for the
+//! compositor to show up, never touches the usual cache, and instead of restoring the last
+//! wallpaper of each output, shows the one from its configuration file:
+//!
+//! ```text
+//! # every output, unless it has its own
+//! image = /usr/share/backgrounds/login.png
+//! image.DP-1 = /usr/share/backgrounds/left.png
+//! # passed on to `swww img --resize`
+//! resize = fit
+//! # where `swww` may write its cache. Defaults to `$XDG_RUNTIME_DIR/swww-greeter`
+//! cache-dir = /var/cache/swww-greeter
+//! # where we tell the session which wallpaper we showed
+//! handoff = /var/cache/swww-greeter/handoff
+//! ```
+//!
+//! The handoff file has the same format, minus `cache-dir` and `handoff`. When the user logs
+//! in, the session's daemon (`swww-daemon --handoff <FILE>`) reads it, shows the same images
+//! right away, and then fades into the user's own wallpapers, so that the login screen's
+//! wallpaper does not just vanish.
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use log::debug;
+
+/// how long we wait for the compositor's socket to accept us
+pub(crate) const COMPOSITOR_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct Config {
+    image: Option<PathBuf>,
+    /// by output name
+    output_images: HashMap<String, PathBuf>,
+    resize: Option<String>,
+    pub cache_dir: Option<PathBuf>,
+    pub handoff: Option<PathBuf>,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let config = std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+        Self::parse(&config).map_err(|e| format!("{}: {e}", path.display()))
+    }
+
+    fn parse(config: &str) -> Result<Self, String> {
+        let mut this = Self::default();
+        for (i, line) in config.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(format!("line {}: expected '<key> = <value>'", i + 1));
+            };
+            let (key, value) = (key.trim(), value.trim());
+            match key {
+                "image" => this.image = Some(value.into()),
+                "resize" => this.resize = Some(value.to_string()),
+                "cache-dir" => this.cache_dir = Some(value.into()),
+                "handoff" => this.handoff = Some(value.into()),
+                _ => match key.strip_prefix("image.") {
+                    Some(output) if !output.is_empty() => {
+                        this.output_images.insert(output.to_string(), value.into());
+                    }
+                    _ => return Err(format!("line {}: unknown key {key:?}", i + 1)),
+                },
+            }
+        }
+        Ok(this)
+    }
+
+    fn image(&self, output: &str) -> Option<&Path> {
+        self.output_images
+            .get(output)
+            .or(self.image.as_ref())
+            .map(PathBuf::as_path)
+    }
+
+    /// Shows the configured image on `output`, through `swww img`
+    pub fn show(&self, output: &str) -> std::io::Result<()> {
+        let Some(image) = self.image(output) else {
+            debug!("no greeter image for output {output}");
+            return Ok(());
+        };
+        img(output, image, "none", self.resize.as_deref())
+    }
+
+    /// Shows the greeter's image on `output`, like `show`, and then fades into the last image
+    /// `swww img` showed there, if we should restore it from the `cache`
+    pub fn hand_off(&self, output: &str, cache: bool) -> std::io::Result<()> {
+        // showing the greeter's image overwrites the cache, so we look at it first
+        let previous = if cache {
+            common::cache::get_previous_image_path(output)?
+        } else {
+            String::new()
+        };
+        self.show(output)?;
+        if previous.is_empty() {
+            return Ok(());
+        }
+        img(output, Path::new(&previous), "fade", None)
+    }
+
+    /// Tells the session which images we show, for `hand_off`. Its daemon belongs to another
+    /// user, so everyone may read the file
+    pub fn write_handoff(&self, path: &Path) -> std::io::Result<()> {
+        use std::fmt::Write;
+        use std::os::unix::fs::PermissionsExt;
+
+        let mut handoff = String::new();
+        if let Some(image) = &self.image {
+            let _ = writeln!(handoff, "image = {}", image.display());
+        }
+        let mut outputs: Vec<_> = self.output_images.iter().collect();
+        outputs.sort();
+        for (output, image) in outputs {
+            let _ = writeln!(handoff, "image.{output} = {}", image.display());
+        }
+        if let Some(resize) = &self.resize {
+            let _ = writeln!(handoff, "resize = {resize}");
+        }
+
+        // so the session never reads half a file
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, handoff)?;
+        std::fs::set_permissions(&tmp, std::fs::Permissions::from_mode(0o644))?;
+        std::fs::rename(tmp, path)
+    }
+}
+
+fn img(output: &str, image: &Path, transition: &str, resize: Option<&str>) -> std::io::Result<()> {
+    let mut command = std::process::Command::new("swww");
+    command.args([
+        "img",
+        &format!("--outputs={output}"),
+        &format!("--transition-type={transition}"),
+    ]);
+    if let Some(resize) = resize {
+        command.arg(format!("--resize={resize}"));
+    }
+    command.arg(image).spawn()?.wait()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_parse_config() {
+        let config = Config::parse(
+            "# login screen\n\
+             image = /usr/share/backgrounds/login.png\n\
+             image.DP-1 = /left one.png\n\
+             \n\
+             resize = fit\n\
+             cache-dir = /var/cache/swww-greeter\n",
+        )
+        .unwrap();
+        assert_eq!(config.image("DP-1"), Some(Path::new("/left one.png")));
+        assert_eq!(
+            config.image("HDMI-A-1"),
+            Some(Path::new("/usr/share/backgrounds/login.png"))
+        );
+        assert_eq!(config.resize.as_deref(), Some("fit"));
+        assert_eq!(
+            config.cache_dir.as_deref(),
+            Some(Path::new("/var/cache/swww-greeter"))
+        );
+
+        assert_eq!(Config::parse("").unwrap().image("DP-1"), None);
+        assert!(Config::parse("image /a.png").is_err());
+        assert!(Config::parse("images = /a.png").is_err());
+        assert!(Config::parse("image. = /a.png").is_err());
+    }
+
+    #[test]
+    fn handoff_should_keep_the_images() {
+        let config = Config::parse(
+            "image = /login.png\n\
+             image.DP-1 = /left one.png\n\
+             resize = fit\n\
+             cache-dir = /var/cache/swww-greeter\n",
+        )
+        .unwrap();
+        let path = std::env::temp_dir().join(format!("swww-handoff-{}", std::process::id()));
+        config.write_handoff(&path).unwrap();
+        let handoff = Config::load(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let handoff = handoff.unwrap();
+        assert_eq!(handoff.image("DP-1"), Some(Path::new("/left one.png")));
+        assert_eq!(handoff.image("HDMI-A-1"), Some(Path::new("/login.png")));
+        assert_eq!(handoff.resize.as_deref(), Some("fit"));
+        assert_eq!(handoff.cache_dir, None);
+    }
+}
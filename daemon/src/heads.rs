@@ -0,0 +1,159 @@
+//! What wlr-output-management tells us about the outputs.
+//!
+//! Compositors announce mode changes there before the `wl_output`s and our layer surfaces hear
+//! about them, so we use it (read-only) to get the wallpapers ready for their new size ahead of
+//! time (see `Wallpaper::prepare_mode`).
+use log::{debug, error};
+
+use crate::wayland::{
+    globals,
+    interfaces::{zwlr_output_head_v1, zwlr_output_mode_v1},
+    wire::WlFixed,
+    ObjectId, WlDynObj,
+};
+
+struct Head {
+    id: ObjectId,
+    name: Option<String>,
+    enabled: bool,
+    current_mode: Option<ObjectId>,
+    transform: u32,
+}
+
+struct Mode {
+    id: ObjectId,
+    size: (i32, i32),
+}
+
+#[derive(Default)]
+pub(crate) struct Heads {
+    /// version of our `zwlr_output_manager_v1`
+    version: u32,
+    heads: Vec<Head>,
+    modes: Vec<Mode>,
+}
+
+impl Heads {
+    pub fn new(version: u32) -> Self {
+        Self {
+            version,
+            ..Self::default()
+        }
+    }
+
+    pub fn new_head(&mut self, id: ObjectId) {
+        globals::object_insert_server(id, WlDynObj::OutputHead);
+        self.heads.push(Head {
+            id,
+            name: None,
+            enabled: false,
+            current_mode: None,
+            transform: 0,
+        });
+    }
+
+    /// The size in pixels and the `wl_output.transform` of each enabled output, by name
+    pub fn current_modes(&self) -> impl Iterator<Item = (&str, (i32, i32), u32)> {
+        self.heads
+            .iter()
+            .filter(|head| head.enabled)
+            .filter_map(|head| {
+                let mode = self
+                    .modes
+                    .iter()
+                    .find(|mode| Some(mode.id) == head.current_mode)?;
+                Some((head.name.as_deref()?, mode.size, head.transform))
+            })
+    }
+
+    fn head(&mut self, id: ObjectId) -> Option<&mut Head> {
+        self.heads.iter_mut().find(|head| head.id == id)
+    }
+}
+
+impl zwlr_output_head_v1::EvHandler for Heads {
+    fn name(&mut self, sender_id: ObjectId, name: &str) {
+        if let Some(head) = self.head(sender_id) {
+            head.name = Some(name.to_string());
+        }
+    }
+
+    fn description(&mut self, _: ObjectId, _: &str) {}
+
+    fn physical_size(&mut self, _: ObjectId, _: i32, _: i32) {}
+
+    fn mode(&mut self, _: ObjectId, mode: ObjectId) {
+        globals::object_insert_server(mode, WlDynObj::OutputMode);
+        self.modes.push(Mode {
+            id: mode,
+            size: (0, 0),
+        });
+    }
+
+    fn enabled(&mut self, sender_id: ObjectId, enabled: i32) {
+        if let Some(head) = self.head(sender_id) {
+            head.enabled = enabled != 0;
+            // the compositor only sends the current mode of enabled heads
+            if !head.enabled {
+                head.current_mode = None;
+            }
+        }
+    }
+
+    fn current_mode(&mut self, sender_id: ObjectId, mode: Option<ObjectId>) {
+        if let Some(head) = self.head(sender_id) {
+            head.current_mode = mode;
+        }
+    }
+
+    fn position(&mut self, _: ObjectId, _: i32, _: i32) {}
+
+    fn transform(&mut self, sender_id: ObjectId, transform: i32) {
+        if let Some(head) = self.head(sender_id) {
+            head.transform = transform as u32;
+        }
+    }
+
+    fn scale(&mut self, _: ObjectId, _: WlFixed) {}
+
+    fn finished(&mut self, sender_id: ObjectId) {
+        debug!("output head {sender_id:?} is gone");
+        self.heads.retain(|head| head.id != sender_id);
+        if self.version >= 3 {
+            if let Err(e) = zwlr_output_head_v1::req::release(sender_id) {
+                error!("failed to release zwlr_output_head_v1: {e}");
+            }
+        }
+        globals::object_remove(sender_id);
+    }
+
+    fn make(&mut self, _: ObjectId, _: &str) {}
+
+    fn model(&mut self, _: ObjectId, _: &str) {}
+
+    fn serial_number(&mut self, _: ObjectId, _: &str) {}
+
+    fn adaptive_sync(&mut self, _: ObjectId, _: u32) {}
+}
+
+impl zwlr_output_mode_v1::EvHandler for Heads {
+    fn size(&mut self, sender_id: ObjectId, width: i32, height: i32) {
+        if let Some(mode) = self.modes.iter_mut().find(|mode| mode.id == sender_id) {
+            mode.size = (width, height);
+        }
+    }
+
+    fn refresh(&mut self, _: ObjectId, _: i32) {}
+
+    fn preferred(&mut self, _: ObjectId) {}
+
+    fn finished(&mut self, sender_id: ObjectId) {
+        self.modes.retain(|mode| mode.id != sender_id);
+        if self.version >= 3 {
+            if let Err(e) = zwlr_output_mode_v1::req::release(sender_id) {
+                error!("failed to release zwlr_output_mode_v1: {e}");
+            }
+        }
+        globals::object_remove(sender_id);
+    }
+}
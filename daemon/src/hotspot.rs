@@ -0,0 +1,164 @@
+//! Clickable areas of the wallpaper, set through the `hotspot.*` variables.
+//!
+//! These only work when the daemon accepts pointer input (`--accept-pointer`), and are mostly
+//! useful on the `bottom` layer or above, since on the `background` layer any window on top of
+//! the wallpaper gets the clicks first.
+use std::collections::HashMap;
+
+use log::{debug, error};
+
+/// A rectangle that runs a command when clicked
+///
+/// `swww set-var hotspot.<name> "[<output>:]<x>,<y>,<width>,<height> <command>"`. The rectangle
+/// is in logical pixels, from the top left corner of the output. Without an output, the hotspot
+/// is on every output. An empty value removes the hotspot.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Hotspot {
+    output: Option<String>,
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+    command: String,
+}
+
+impl Hotspot {
+    fn parse(value: &str) -> Result<Self, String> {
+        let (area, command) = value
+            .trim()
+            .split_once(char::is_whitespace)
+            .ok_or_else(|| {
+                format!("expected '[<output>:]<x>,<y>,<width>,<height> <command>', got {value:?}")
+            })?;
+        let (output, rect) = match area.rsplit_once(':') {
+            Some((output, rect)) => (Some(output.to_string()), rect),
+            None => (None, area),
+        };
+
+        let rect = rect
+            .split(',')
+            .map(|n| n.parse::<u32>().map(f64::from))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("invalid rectangle {rect:?}: {e}"))?;
+        let &[x, y, width, height] = rect.as_slice() else {
+            return Err(format!(
+                "invalid rectangle {area:?}: expected 4 numbers, got {}",
+                rect.len()
+            ));
+        };
+
+        Ok(Self {
+            output,
+            x,
+            y,
+            width,
+            height,
+            command: command.trim().to_string(),
+        })
+    }
+
+    fn contains(&self, output: &str, x: f64, y: f64) -> bool {
+        self.output.as_deref().map_or(true, |o| o == output)
+            && (self.x..self.x + self.width).contains(&x)
+            && (self.y..self.y + self.height).contains(&y)
+    }
+
+    /// Runs the command through `sh`, without waiting for it
+    fn run(&self, output: &str) {
+        debug!("running hotspot command: {}", self.command);
+        let child = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(&self.command)
+            .env("SWWW_OUTPUT", output)
+            .stdin(std::process::Stdio::null())
+            .spawn();
+        match child {
+            // somebody has to reap it
+            Ok(mut child) => {
+                if let Err(e) = std::thread::Builder::new()
+                    .name("hotspot".to_string())
+                    .stack_size(1 << 14)
+                    .spawn(move || child.wait())
+                {
+                    error!("failed to wait for hotspot command: {e}");
+                }
+            }
+            Err(e) => error!("failed to run hotspot command {:?}: {e}", self.command),
+        }
+    }
+}
+
+/// Parses every `hotspot.*` variable, sorted by name. Invalid hotspots are logged and skipped
+pub(crate) fn from_vars(vars: &HashMap<String, String>) -> Vec<Hotspot> {
+    let mut hotspots: Vec<_> = vars
+        .iter()
+        .filter(|(key, value)| key.starts_with("hotspot.") && !value.trim().is_empty())
+        .filter_map(|(key, value)| match Hotspot::parse(value) {
+            Ok(hotspot) => Some((key, hotspot)),
+            Err(e) => {
+                error!("invalid {key}: {e}");
+                None
+            }
+        })
+        .collect();
+    hotspots.sort_unstable_by(|a, b| a.0.cmp(b.0));
+    hotspots.into_iter().map(|(_, hotspot)| hotspot).collect()
+}
+
+/// Runs the first hotspot under a click at `(x, y)` in `output`, returning whether there was one
+pub(crate) fn click(hotspots: &[Hotspot], output: &str, x: f64, y: f64) -> bool {
+    match hotspots.iter().find(|h| h.contains(output, x, y)) {
+        Some(hotspot) => {
+            hotspot.run(output);
+            true
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_parse_hotspots() {
+        let hotspot = Hotspot::parse("DP-1:10,20,30,40 notify-send 'hello world'").unwrap();
+        assert_eq!(hotspot.output.as_deref(), Some("DP-1"));
+        assert_eq!(
+            (hotspot.x, hotspot.y, hotspot.width, hotspot.height),
+            (10.0, 20.0, 30.0, 40.0)
+        );
+        assert_eq!(hotspot.command, "notify-send 'hello world'");
+
+        assert!(Hotspot::parse("10,20,30,40 foot").unwrap().output.is_none());
+        assert!(Hotspot::parse("10,20,30,40").is_err());
+        assert!(Hotspot::parse("10,20,30 foot").is_err());
+        assert!(Hotspot::parse("10,-20,30,40 foot").is_err());
+    }
+
+    #[test]
+    fn should_hit_test_in_name_order() {
+        let vars: HashMap<String, String> = [
+            ("hotspot.b", "0,0,100,100 b"),
+            ("hotspot.a", "DP-1:50,50,10,10 a"),
+            ("hotspot.c", ""),
+            ("tint.color", "ff0000"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let hotspots = from_vars(&vars);
+        assert_eq!(hotspots.len(), 2);
+
+        let hit = |output, x, y| {
+            hotspots
+                .iter()
+                .find(|h| h.contains(output, x, y))
+                .map(|h| h.command.as_str())
+        };
+        assert_eq!(hit("DP-1", 55.0, 55.0), Some("a"));
+        assert_eq!(hit("HDMI-A-1", 55.0, 55.0), Some("b"));
+        assert_eq!(hit("DP-1", 60.0, 60.0), Some("b"));
+        assert_eq!(hit("DP-1", 100.0, 0.0), None);
+    }
+}
@@ -0,0 +1,447 @@
+//! Draws straight to the screens through DRM/KMS, for kiosks and picture frames that run without
+//! a compositor (`swww-daemon --drm <DEVICE>`).
+//!
+//! Every connected connector gets its preferred mode and a dumb buffer, which we draw into with
+//! the same transition engine as the wallpapers (`swww_render::transition`). This does a lot less
+//! than the daemon does under wayland: there is no scaling, no cache to restore from, animations
+//! only show their first frame, and we only answer `img`, `clear`, `query`, `kill` and `ping`.
+use std::{
+    fs::File,
+    num::NonZeroI32,
+    os::fd::{AsFd, BorrowedFd},
+    path::Path,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use common::{
+    ipc::{
+        Answer, BgImg, BgInfo, ImageReq, ImgReq, IpcSocket, PixelFormat, RequestRecv, Scale,
+        Server, TransitionUsed,
+    },
+    mmap::MmappedStr,
+};
+use drm::{
+    buffer::{Buffer, DrmFourcc},
+    control::{
+        connector, crtc, dumbbuffer::DumbBuffer, Device as ControlDevice, Mode, ModeTypeFlags,
+        ResourceHandles,
+    },
+    Device,
+};
+use log::{debug, error, info};
+use rustix::{
+    event::{poll, PollFd, PollFlags},
+    fd::OwnedFd,
+};
+use swww_render::transition::{Target, Transition};
+
+use crate::{animations, cli::Cli};
+
+/// Dumb buffers are XRGB8888, which has the same layout in memory as wl_shm's xrgb8888
+const FORMAT: PixelFormat = PixelFormat::Xrgb;
+
+struct Card(File);
+
+impl AsFd for Card {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0.as_fd()
+    }
+}
+
+impl Device for Card {}
+impl ControlDevice for Card {}
+
+/// A connected connector, and what it shows
+struct Screen {
+    name: String,
+    dim: (u32, u32),
+    buffer: DumbBuffer,
+    /// what we draw into, copied to `buffer` for every frame. Dumb buffers can be slow to read
+    /// from, and their lines may be padded
+    canvas: Box<[u8]>,
+    img: BgImg,
+    /// bumped whenever something else is shown, which stops the transitions still running on us
+    generation: u64,
+}
+
+struct Screens {
+    card: Card,
+    screens: Box<[Mutex<Screen>]>,
+}
+
+impl Screens {
+    fn open(device: &Path) -> Result<Self, String> {
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .open(device)
+            .map_err(|e| format!("failed to open {}: {e}", device.display()))?;
+        let card = Card(file);
+        let resources = card
+            .resource_handles()
+            .map_err(|e| format!("failed to get the resources of {}: {e}", device.display()))?;
+
+        let mut used_crtcs = Vec::new();
+        let mut screens = Vec::new();
+        for &handle in resources.connectors() {
+            let info = match card.get_connector(handle, false) {
+                Ok(info) => info,
+                Err(e) => {
+                    error!("failed to get connector {handle:?}: {e}");
+                    continue;
+                }
+            };
+            if info.state() != connector::State::Connected {
+                continue;
+            }
+            let name = format!("{}-{}", info.interface().as_str(), info.interface_id());
+            let Some(&mode) = info
+                .modes()
+                .iter()
+                .find(|mode| mode.mode_type().contains(ModeTypeFlags::PREFERRED))
+                .or(info.modes().first())
+            else {
+                error!("{name} has no mode");
+                continue;
+            };
+            let Some(crtc) = find_crtc(&card, &resources, &info, &used_crtcs) else {
+                error!("{name} has no free crtc");
+                continue;
+            };
+            match Screen::new(&card, name, handle, crtc, mode) {
+                Ok(screen) => {
+                    info!("drawing to {} at {:?}", screen.name, screen.dim);
+                    used_crtcs.push(crtc);
+                    screens.push(Mutex::new(screen));
+                }
+                Err(e) => error!("{e}"),
+            }
+        }
+        if screens.is_empty() {
+            return Err(format!("{} has no screen we can draw to", device.display()));
+        }
+        Ok(Self {
+            card,
+            screens: screens.into(),
+        })
+    }
+
+    /// The indices of the screens called one of `names`, or all of them if there are no `names`
+    fn find(&self, names: &[MmappedStr]) -> Vec<usize> {
+        (0..self.screens.len())
+            .filter(|&i| {
+                let screen = self.screens[i].lock().unwrap();
+                names.is_empty() || names.iter().any(|name| name.str() == screen.name)
+            })
+            .collect()
+    }
+
+    fn info(&self) -> Box<[BgInfo]> {
+        self.screens
+            .iter()
+            .map(|screen| {
+                let screen = screen.lock().unwrap();
+                BgInfo {
+                    name: screen.name.clone(),
+                    dim: screen.dim,
+                    native_dim: screen.dim,
+                    scale_factor: Scale::Whole(NonZeroI32::new(1).unwrap()),
+                    img: screen.img.clone(),
+                    pixel_format: FORMAT,
+                    error: None,
+                }
+            })
+            .collect()
+    }
+
+    fn show(&self, screen: &mut Screen) {
+        let stride = screen.dim.0 as usize * FORMAT.channels() as usize;
+        let pitch = screen.buffer.pitch() as usize;
+        match self.card.map_dumb_buffer(&mut screen.buffer) {
+            Ok(mut mapping) => {
+                for (line, canvas) in mapping
+                    .as_mut()
+                    .chunks_exact_mut(pitch)
+                    .zip(screen.canvas.chunks_exact(stride))
+                {
+                    line[..stride].copy_from_slice(canvas);
+                }
+            }
+            Err(e) => error!("failed to map the buffer of {}: {e}", screen.name),
+        }
+    }
+}
+
+/// The crtc the connector is already using, or else the first free one it can use
+fn find_crtc(
+    card: &Card,
+    resources: &ResourceHandles,
+    info: &connector::Info,
+    used: &[crtc::Handle],
+) -> Option<crtc::Handle> {
+    let current = info
+        .current_encoder()
+        .and_then(|encoder| card.get_encoder(encoder).ok())
+        .and_then(|encoder| encoder.crtc())
+        .filter(|crtc| !used.contains(crtc));
+    current.or_else(|| {
+        info.encoders()
+            .iter()
+            .filter_map(|&encoder| card.get_encoder(encoder).ok())
+            .flat_map(|encoder| resources.filter_crtcs(encoder.possible_crtcs()))
+            .find(|crtc| !used.contains(crtc))
+    })
+}
+
+impl Screen {
+    fn new(
+        card: &Card,
+        name: String,
+        connector: connector::Handle,
+        crtc: crtc::Handle,
+        mode: Mode,
+    ) -> Result<Self, String> {
+        let (width, height) = mode.size();
+        let dim = (width as u32, height as u32);
+        let buffer = card
+            .create_dumb_buffer(dim, DrmFourcc::Xrgb8888, 32)
+            .map_err(|e| format!("failed to create a buffer for {name}: {e}"))?;
+        let framebuffer = card
+            .add_framebuffer(&buffer, 24, 32)
+            .map_err(|e| format!("failed to add a framebuffer for {name}: {e}"))?;
+        card.set_crtc(crtc, Some(framebuffer), (0, 0), &[connector], Some(mode))
+            .map_err(|e| format!("failed to set the mode of {name}: {e}"))?;
+        Ok(Self {
+            name,
+            dim,
+            buffer,
+            // dumb buffers start out black, like this
+            canvas: vec![0; dim.0 as usize * dim.1 as usize * FORMAT.channels() as usize].into(),
+            img: BgImg::Color([0, 0, 0]),
+            generation: 0,
+        })
+    }
+}
+
+pub(crate) fn run(device: &Path, listener: &OwnedFd, cli: &Cli) -> Result<(), String> {
+    let screens = Arc::new(Screens::open(device)?);
+    crate::notify_systemd();
+
+    let mut fds = [PollFd::new(listener, PollFlags::IN)];
+    while !crate::should_daemon_exit() {
+        if let Err(e) = poll(&mut fds, -1) {
+            match e {
+                rustix::io::Errno::INTR => continue,
+                _ => return Err(format!("failed to poll file descriptors: {e:?}")),
+            }
+        }
+        if fds[0].revents().is_empty() {
+            continue;
+        }
+        let stream = match rustix::net::accept(listener) {
+            Ok(stream) => IpcSocket::<Server>::new(stream),
+            Err(rustix::io::Errno::INTR | rustix::io::Errno::WOULDBLOCK) => continue,
+            Err(e) => return Err(format!("failed to accept incoming connection: {e}")),
+        };
+        let bytes = match stream.recv() {
+            Ok(bytes) => bytes,
+            Err(e) => return Err(format!("cannot read socket: {e}")),
+        };
+        let answer = handle_request(&screens, RequestRecv::receive(bytes), cli);
+        if let Err(e) = answer.send(&stream) {
+            error!("error sending answer to client: {e}");
+        }
+    }
+    Ok(())
+}
+
+fn handle_request(screens: &Arc<Screens>, request: RequestRecv, cli: &Cli) -> Answer {
+    match request {
+        RequestRecv::Ping => Answer::Ping(true),
+        RequestRecv::Query => Answer::Info(screens.info()),
+        RequestRecv::Kill => {
+            crate::exit_daemon();
+            Answer::Ok
+        }
+        RequestRecv::Clear(clear) => {
+            let pixel = [clear.color[2], clear.color[1], clear.color[0], 0xFF];
+            for i in screens.find(&clear.outputs) {
+                let mut screen = screens.screens[i].lock().unwrap();
+                screen.generation += 1;
+                screen.img = BgImg::Color(clear.color);
+                for chunk in screen.canvas.chunks_exact_mut(4) {
+                    chunk.copy_from_slice(&pixel);
+                }
+                screens.show(&mut screen);
+            }
+            Answer::Ok
+        }
+        RequestRecv::Img(ImageReq {
+            mut transitions,
+            imgs,
+            outputs,
+            animations,
+        }) => {
+            transitions.iter_mut().for_each(animations::pick_random);
+            if cli
+                .reduce_motion
+                .apply(&mut transitions, animations)
+                .is_some()
+            {
+                debug!("without a compositor, animations only show their first frame");
+            }
+            let mut used = Vec::new();
+            let mut runs = Vec::new();
+            for (i, (transition, names)) in transitions.iter_mut().zip(outputs.iter()).enumerate() {
+                let clamped = animations::sanitize(transition, cli.limits.max_fps);
+                let mut used_names = Vec::new();
+                for index in screens.find(names) {
+                    let mut screen = screens.screens[index].lock().unwrap();
+                    screen.generation += 1;
+                    screen.img = BgImg::Img(imgs[i].path.str().to_string());
+                    used_names.push(screen.name.clone());
+                    runs.push((i, index, screen.generation));
+                }
+                used.push(TransitionUsed {
+                    outputs: used_names.into(),
+                    transition_type: transition.transition_type,
+                    duration: transition.duration,
+                    fps: transition.fps,
+                    clamped: clamped.into(),
+                });
+            }
+
+            let screens = Arc::clone(screens);
+            thread::Builder::new()
+                .name("transition spawner".to_string())
+                .stack_size(1 << 15)
+                .spawn(move || {
+                    thread::scope(|s| {
+                        for (i, index, generation) in runs {
+                            let (screens, transition, img) = (&screens, &transitions[i], &imgs[i]);
+                            thread::Builder::new()
+                                .name("transition".to_string())
+                                .stack_size(1 << 17)
+                                .spawn_scoped(s, move || {
+                                    let target = Frames::new(
+                                        screens,
+                                        index,
+                                        generation,
+                                        img,
+                                        transition.fps,
+                                    );
+                                    let Some(target) = target else {
+                                        return;
+                                    };
+                                    let dim = img.dim;
+                                    let mask = img.mask.as_ref().map(|mask| mask.bytes());
+                                    Transition::new(target, dim, transition.params(), mask, None)
+                                        .execute(img.img.bytes());
+                                })
+                                .unwrap(); // builder only fails if name contains null bytes
+                        }
+                    });
+                })
+                .unwrap(); // builder only fails if name contains null bytes
+            Answer::Transitions(used.into())
+        }
+        RequestRecv::Batch(requests) => {
+            for request in requests.into_vec() {
+                handle_request(screens, request, cli);
+            }
+            Answer::Ok
+        }
+        RequestRecv::Capabilities => Answer::Capabilities(crate::capabilities()),
+        _ => Answer::Error(
+            "without a compositor, swww-daemon only supports img, clear, query, kill, ping and \
+             capabilities"
+                .to_string(),
+        ),
+    }
+}
+
+/// The transition's target: a screen, shown at the transition's fps, until something else is
+/// shown on it
+struct Frames<'a> {
+    screens: &'a Screens,
+    index: usize,
+    generation: u64,
+    fps: Duration,
+    start: Instant,
+    last_frame: Instant,
+    /// the canvas as it was before the transition
+    old: Option<Box<[u8]>>,
+}
+
+impl<'a> Frames<'a> {
+    fn new(
+        screens: &'a Screens,
+        index: usize,
+        generation: u64,
+        img: &ImgReq,
+        fps: u16,
+    ) -> Option<Self> {
+        let screen = screens.screens[index].lock().unwrap();
+        let len = screen.dim.0 as usize * screen.dim.1 as usize * FORMAT.channels() as usize;
+        if img.dim != screen.dim || img.format != FORMAT || img.img.bytes().len() != len {
+            error!(
+                "image for {} has the wrong size or format! Expect {:?}, actual {:?}",
+                screen.name, screen.dim, img.dim
+            );
+            return None;
+        }
+        Some(Self {
+            screens,
+            index,
+            generation,
+            fps: Duration::from_nanos(1_000_000_000 / fps as u64),
+            start: Instant::now(),
+            last_frame: Instant::now(),
+            old: None,
+        })
+    }
+
+    /// Our screen, unless something else is shown on it now
+    fn screen(&self) -> Option<std::sync::MutexGuard<'a, Screen>> {
+        let screen = self.screens.screens[self.index].lock().unwrap();
+        (screen.generation == self.generation).then_some(screen)
+    }
+}
+
+impl Target for Frames<'_> {
+    fn channels(&self) -> usize {
+        FORMAT.channels() as usize
+    }
+
+    fn bgr(&self) -> bool {
+        FORMAT.must_swap_r_and_b_channels()
+    }
+
+    fn now(&self) -> f64 {
+        self.start.elapsed().as_secs_f64()
+    }
+
+    fn draw(&mut self, draw: &mut dyn FnMut(&mut [u8])) {
+        if let Some(mut screen) = self.screen() {
+            draw(&mut screen.canvas);
+        }
+    }
+
+    fn redraw(&mut self, draw: &mut dyn FnMut(&mut [u8], &[u8])) {
+        if let Some(mut screen) = self.screen() {
+            let old = self.old.get_or_insert_with(|| screen.canvas.clone());
+            draw(&mut screen.canvas, old);
+        }
+    }
+
+    fn present(&mut self) {
+        let timeout = self.fps.saturating_sub(self.last_frame.elapsed());
+        crate::sleep::spin_sleep(timeout);
+        if let Some(mut screen) = self.screen() {
+            self.screens.show(&mut screen);
+        }
+        self.last_frame = Instant::now();
+    }
+}
@@ -0,0 +1,167 @@
+//! Limits on the resources clients can make us use (`swww-daemon --limits <FILE>`).
+//!
+//! On a machine shared by several users, a misbehaving script should not be able to make the
+//! daemon eat a whole cpu core. Administrators can cap what we accept in a file like:
+//!
+//! ```text
+//! # transitions asking for more are run at this fps instead
+//! max-fps = 60
+//! # animations bigger than this are refused
+//! max-animation-resolution = 1920x1080
+//! # oldest animation frames are removed from the cache past this size, in MiB
+//! max-cache-size = 512
+//! # outputs that can show an animation at the same time
+//! max-animations = 2
+//! ```
+//!
+//! Without `--limits`, we read `/etc/swww/limits` if it exists.
+use std::path::Path;
+
+/// where we look for the limits when `--limits` is not given
+const DEFAULT_PATH: &str = "/etc/swww/limits";
+
+/// Transitions faster than this only burn cpu, since no monitor can show them
+const MAX_FPS: u16 = 240;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Limits {
+    pub max_fps: u16,
+    pub max_animation_resolution: Option<(u32, u32)>,
+    /// in bytes
+    pub max_cache_size: Option<u64>,
+    pub max_animations: Option<usize>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_fps: MAX_FPS,
+            max_animation_resolution: None,
+            max_cache_size: None,
+            max_animations: None,
+        }
+    }
+}
+
+impl Limits {
+    /// Loads the limits from `path`, or from [`DEFAULT_PATH`] if it exists
+    pub fn load(path: Option<&Path>) -> Result<Self, String> {
+        let config = match path {
+            Some(path) => std::fs::read_to_string(path)
+                .map_err(|e| format!("failed to read {}: {e}", path.display()))?,
+            None => match std::fs::read_to_string(DEFAULT_PATH) {
+                Ok(config) => config,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+                Err(e) => return Err(format!("failed to read {DEFAULT_PATH}: {e}")),
+            },
+        };
+        let path = path.unwrap_or(Path::new(DEFAULT_PATH));
+        Self::parse(&config).map_err(|e| format!("{}: {e}", path.display()))
+    }
+
+    fn parse(config: &str) -> Result<Self, String> {
+        let mut this = Self::default();
+        for (i, line) in config.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(format!("line {}: expected '<key> = <value>'", i + 1));
+            };
+            let (key, value) = (key.trim(), value.trim());
+            let invalid = |expected: &str| format!("line {}: {key} must be {expected}", i + 1);
+            match key {
+                "max-fps" => {
+                    this.max_fps = value
+                        .parse()
+                        .ok()
+                        .filter(|fps| (1..=MAX_FPS).contains(fps))
+                        .ok_or_else(|| invalid(&format!("a number between 1 and {MAX_FPS}")))?
+                }
+                "max-animation-resolution" => {
+                    this.max_animation_resolution = Some(
+                        value
+                            .split_once('x')
+                            .and_then(|(w, h)| {
+                                Some((w.trim().parse().ok()?, h.trim().parse().ok()?))
+                            })
+                            .ok_or_else(|| invalid("<WIDTH>x<HEIGHT>"))?,
+                    )
+                }
+                "max-cache-size" => {
+                    let mib: u64 = value.parse().map_err(|_| invalid("a number of MiB"))?;
+                    this.max_cache_size = Some(mib * 1024 * 1024);
+                }
+                "max-animations" => {
+                    this.max_animations =
+                        Some(value.parse().map_err(|_| invalid("a number of outputs"))?)
+                }
+                _ => return Err(format!("line {}: unknown key {key:?}", i + 1)),
+            }
+        }
+        Ok(this)
+    }
+
+    /// Returns why an animation of `dim` is refused, if it is
+    pub fn check_animation(&self, dim: (u32, u32)) -> Result<(), String> {
+        match self.max_animation_resolution {
+            Some(max) if dim.0 > max.0 || dim.1 > max.1 => Err(format!(
+                "animations are limited to {}x{} by the administrator, but this one is {}x{}. \
+                 Try `swww img --max-anim-resolution {}x{}`",
+                max.0, max.1, dim.0, dim.1, max.0, max.1
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns why showing animations on `count` outputs at once is refused, if it is
+    pub fn check_animation_count(&self, count: usize) -> Result<(), String> {
+        match self.max_animations {
+            Some(max) if count > max => Err(format!(
+                "at most {max} outputs can show an animation at once, as set by the \
+                 administrator, but this request would make it {count}"
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_should_parse() {
+        let limits = Limits::parse(
+            "# shared machine\nmax-fps = 60\nmax-animation-resolution = 1920x1080\n\
+             max-cache-size=2\nmax-animations = 1\n",
+        )
+        .unwrap();
+        assert_eq!(
+            limits,
+            Limits {
+                max_fps: 60,
+                max_animation_resolution: Some((1920, 1080)),
+                max_cache_size: Some(2 * 1024 * 1024),
+                max_animations: Some(1),
+            }
+        );
+        assert_eq!(Limits::parse("").unwrap(), Limits::default());
+        assert!(Limits::parse("max-fps = 0").is_err());
+        assert!(Limits::parse("max-fps = 1000").is_err());
+        assert!(Limits::parse("max-animation-resolution = 1920").is_err());
+        assert!(Limits::parse("max-threads = 2").is_err());
+    }
+
+    #[test]
+    fn limits_should_refuse_too_much() {
+        let limits =
+            Limits::parse("max-animation-resolution = 100x100\nmax-animations = 2").unwrap();
+        assert!(limits.check_animation((100, 50)).is_ok());
+        assert!(limits.check_animation((101, 50)).is_err());
+        assert!(limits.check_animation_count(2).is_ok());
+        assert!(limits.check_animation_count(3).is_err());
+        assert!(Limits::default().check_animation_count(100).is_ok());
+    }
+}
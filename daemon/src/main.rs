@@ -2,8 +2,21 @@
 //! them fail there is no point in continuing. All of the initialization code, for example, is full
 //! of `expects`, **on purpose**, because we **want** to unwind and exit when they happen
 
+mod alloc_audit;
 mod animations;
 mod cli;
+mod filter;
+mod greeter;
+mod heads;
+mod hotspot;
+#[cfg(feature = "drm")]
+mod kms;
+mod limits;
+mod output_order;
+mod read_cache;
+mod screencopy;
+mod sleep;
+mod transition_defaults;
 mod wallpaper;
 #[allow(dead_code)]
 mod wayland;
@@ -13,13 +26,15 @@ use rustix::{
     fd::OwnedFd,
 };
 
-use wallpaper::Wallpaper;
+use filter::{Lut, Tint};
+use wallpaper::{Restore, Wallpaper};
 use wayland::{
     globals::{self, Initializer},
     ObjectId,
 };
 
 use std::{
+    collections::{HashMap, HashSet},
     fs,
     io::{IsTerminal, Write},
     num::{NonZeroI32, NonZeroU32},
@@ -28,12 +43,17 @@ use std::{
         atomic::{AtomicBool, Ordering},
         Arc,
     },
+    time::Duration,
 };
 
-use common::ipc::{Answer, BgInfo, ImageReq, IpcSocket, RequestRecv, RequestSend, Scale, Server};
+use common::ipc::{
+    Answer, BgImg, Capabilities, ColorFilter, Condition, DimReq, FilterReq, GetVarReq, IfReq,
+    ImageReq, IpcSocket, ListVarsReq, Outro, RequestRecv, RequestSend, Scale, Server, SetVarReq,
+    ShaderReq, StreamReq, TransitionUsed,
+};
 use common::mmap::MmappedStr;
 
-use animations::Animator;
+use animations::{Animator, ReduceMotion};
 
 // We need this because this might be set by signals, so we can't keep it in the daemon
 static EXIT: AtomicBool = AtomicBool::new(false);
@@ -53,12 +73,86 @@ extern "C" fn signal_handler(_s: libc::c_int) {
 struct Daemon {
     wallpapers: Vec<Arc<Wallpaper>>,
     animator: Animator,
-    use_cache: bool,
+    restore: Restore,
     fractional_scale_manager: Option<(ObjectId, NonZeroU32)>,
+    /// key-value store set by clients through `swww set-var`
+    vars: HashMap<String, String>,
+    /// tint configured through the `tint.*` variables
+    tint: Option<Tint>,
+    /// color and strength of the tint currently applied to the wallpapers
+    applied_tint: Option<([u8; 3], u8)>,
+    /// strength of the tint's vignette currently applied to the wallpapers
+    applied_vignette: u8,
+    /// accessibility filters set through `swww color-filter`, by output name
+    color_filters: HashMap<String, ColorFilter>,
+    /// accessibility filter for the outputs not in `color_filters`
+    default_color_filter: ColorFilter,
+    filter_worker: filter::Worker,
+    /// color and strength set through `swww dim`, by output name
+    dims: HashMap<String, ([u8; 3], u8)>,
+    /// dim for the outputs not in `dims`
+    default_dim: Option<([u8; 3], u8)>,
+    reduce_motion: ReduceMotion,
+    transition_defaults: transition_defaults::TransitionDefaults,
+    /// the order we list the outputs in
+    output_order: output_order::OutputOrder,
+    /// whether our surfaces take pointer input, instead of letting it through to what is below
+    accept_pointer: bool,
+    seats: Vec<Seat>,
+    /// clickable areas set through the `hotspot.*` variables
+    hotspots: Vec<hotspot::Hotspot>,
+    /// the zwlr_layer_shell_v1 layer of our surfaces
+    layer: u32,
+    /// how many times we had to rebuild the wallpaper of each output, by output name
+    rebuilds: HashMap<u32, u8>,
+    /// the size we ask for, in logical pixels, by output name, for outputs the compositor did not
+    /// configure when we let it pick the size
+    fallback_sizes: HashMap<u32, (u32, u32)>,
+    /// name and version of the `zxdg_output_manager_v1` global, and our object once bound, when the
+    /// first output shows up. We learn where the outputs are through it, and the names of those
+    /// older than version 4 of `wl_output`
+    xdg_output_manager: Option<(u32, u32, Option<ObjectId>)>,
+    /// name of the `zwlr_output_manager_v1` global and our object, once bound
+    output_manager: Option<(u32, ObjectId)>,
+    /// the outputs as wlr-output-management describes them
+    heads: heads::Heads,
+    /// whether the first transition on each output starts from a capture of what it showed
+    capture_first_frame: bool,
+    /// captures what the outputs show before the first transition on them
+    screencopy: screencopy::Screencopy,
+    /// what the administrator lets clients make us do
+    limits: limits::Limits,
+    /// names of the outputs showing an animation
+    animated: HashSet<String>,
+    /// threads running the outros of `swww clear --outro`
+    outros: Vec<std::thread::JoinHandle<()>>,
+    /// whether we were told to exit once `outros` finish
+    exiting: bool,
+    /// recent answers to `swww query` and pings, for clients polling them
+    read_cache: read_cache::ReadCache,
+}
+
+/// how many times we rebuild an output's wallpaper before giving up on it
+const MAX_REBUILDS: u8 = 3;
+
+/// how long the compositor has to configure a new wallpaper before we rebuild it
+const CONFIGURE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How many queued clients we answer per wakeup, so a flood of them cannot starve wayland events
+const MAX_CLIENTS_PER_WAKEUP: usize = 16;
+
+struct Seat {
+    /// name of the `wl_seat` global
+    name: u32,
+    version: u32,
+    wl_seat: ObjectId,
+    wl_pointer: Option<ObjectId>,
+    /// the surface the pointer is over, and where, if it is one of ours
+    focus: Option<(ObjectId, f64, f64)>,
 }
 
 impl Daemon {
-    fn new(initializer: &Initializer, no_cache: bool) -> Self {
+    fn new(initializer: &Initializer, cli: &cli::Cli, restore: Restore) -> Self {
         log::info!(
             "Selected wl_shm format: {:?}",
             wayland::globals::pixel_format()
@@ -70,56 +164,338 @@ impl Daemon {
         Self {
             wallpapers,
             animator: Animator::new(),
-            use_cache: !no_cache,
+            restore,
             fractional_scale_manager,
+            vars: HashMap::new(),
+            tint: None,
+            applied_tint: None,
+            applied_vignette: 0,
+            color_filters: HashMap::new(),
+            default_color_filter: ColorFilter::None,
+            filter_worker: filter::Worker::new(),
+            dims: HashMap::new(),
+            default_dim: None,
+            reduce_motion: cli.reduce_motion,
+            transition_defaults: transition_defaults::TransitionDefaults::default(),
+            output_order: cli.output_order,
+            accept_pointer: cli.accept_pointer,
+            seats: Vec::new(),
+            hotspots: Vec::new(),
+            layer: cli.layer,
+            rebuilds: HashMap::new(),
+            fallback_sizes: HashMap::new(),
+            xdg_output_manager: initializer
+                .xdg_output_manager()
+                .map(|(name, version)| (name, version, None)),
+            output_manager: None,
+            heads: heads::Heads::default(),
+            capture_first_frame: cli.capture_first_frame,
+            screencopy: screencopy::Screencopy::default(),
+            limits: cli.limits,
+            animated: HashSet::new(),
+            outros: Vec::new(),
+            exiting: false,
+            read_cache: read_cache::ReadCache::default(),
         }
     }
 
-    fn new_output(&mut self, output_name: u32) {
+    fn new_seat(&mut self, name: u32, version: u32) {
         use wayland::interfaces::*;
-        let output = globals::object_create(wayland::WlDynObj::Output);
-        wl_registry::req::bind(output_name, output, "wl_output", 4).unwrap();
+        // version 5 adds the release requests
+        let version = version.min(5);
+        let wl_seat = globals::object_create(wayland::WlDynObj::Seat);
+        if let Err(e) = wl_registry::req::bind(name, wl_seat, "wl_seat", version) {
+            error!("failed to bind wl_seat: {e}");
+            return;
+        }
+        debug!("New seat: {name}");
+        self.seats.push(Seat {
+            name,
+            version,
+            wl_seat,
+            wl_pointer: None,
+            focus: None,
+        });
+    }
 
-        let surface = globals::object_create(wayland::WlDynObj::Surface);
-        wl_compositor::req::create_surface(surface).unwrap();
+    /// binds `zwlr_output_manager_v1`, to hear about mode changes early
+    fn bind_output_manager(&mut self, name: u32, version: u32) {
+        use wayland::interfaces::*;
+        let id = globals::object_create(wayland::WlDynObj::OutputManager);
+        let version = version.min(4);
+        if let Err(e) = wl_registry::req::bind(name, id, "zwlr_output_manager_v1", version) {
+            error!("failed to bind zwlr_output_manager_v1: {e}");
+            return;
+        }
+        debug!("binding wlr-output-management to prepare for mode changes");
+        self.output_manager = Some((name, id));
+        self.heads = heads::Heads::new(version);
+    }
 
-        let region = globals::object_create(wayland::WlDynObj::Region);
-        wl_compositor::req::create_region(region).unwrap();
+    fn new_output(&mut self, output_name: u32, version: u32) {
+        // version 4 gives us the output's name, before that we need xdg-output for it
+        let xdg_output_manager = self.xdg_output_manager();
+        if version < 4 && xdg_output_manager.is_none() {
+            warn!(
+                "output {output_name} only supports version {version} of wl_output, and the \
+                 compositor does not tell its name through xdg-output either. We will name it \
+                 after its make and model"
+            );
+        }
+        match self.create_wallpaper(output_name, version, xdg_output_manager) {
+            Ok(wallpaper) => {
+                self.wallpapers.push(Arc::new(wallpaper));
+                self.read_cache.invalidate();
+            }
+            Err(e) => error!("failed to create the wallpaper for output {output_name}: {e}"),
+        }
+    }
 
-        wl_surface::req::set_input_region(surface, Some(region)).unwrap();
-        wl_region::req::destroy(region).unwrap();
+    /// binds `zxdg_output_manager_v1` if we have not yet. Returns `None` if the compositor does
+    /// not have it, or only has the first version, which does not send names
+    fn xdg_output_manager(&mut self) -> Option<ObjectId> {
+        use wayland::interfaces::*;
+        let (name, version, object) = self.xdg_output_manager.as_mut()?;
+        if *version < 2 {
+            return None;
+        }
+        if object.is_none() {
+            let id = globals::object_create(wayland::WlDynObj::XdgOutputManager);
+            let version = (*version).min(3);
+            if let Err(e) = wl_registry::req::bind(*name, id, "zxdg_output_manager_v1", version) {
+                error!("failed to bind zxdg_output_manager_v1: {e}");
+                return None;
+            }
+            info!("binding xdg-output to learn where the outputs are");
+            *object = Some(id);
+        }
+        *object
+    }
+
+    fn create_wallpaper(
+        &self,
+        output_name: u32,
+        version: u32,
+        xdg_output_manager: Option<ObjectId>,
+    ) -> Result<Wallpaper, String> {
+        // we allocate the buffers first, so that if it fails we have not created any object yet
+        let pool = wayland::bump_pool::BumpPool::new(256, 256)?;
+        let (output, xdg_output, surface, layer_surface, viewport, wp_fractional) = self
+            .create_wallpaper_objects(output_name, version, xdg_output_manager)
+            .map_err(|e| {
+                wallpaper::check_disconnect(e);
+                e.to_string()
+            })?;
+
+        debug!("New output: {output_name} (wl_output v{version})");
+        Ok(Wallpaper::new(
+            output,
+            output_name,
+            version,
+            xdg_output,
+            surface,
+            viewport,
+            wp_fractional,
+            layer_surface,
+            pool,
+            self.accept_pointer,
+            self.fallback_sizes.get(&output_name).copied(),
+        ))
+    }
+
+    /// returns the wl_output, zxdg_output_v1, wl_surface, zwlr_layer_surface_v1, wp_viewport and
+    /// wp_fractional_scale_v1 of a new wallpaper
+    #[allow(clippy::type_complexity)]
+    fn create_wallpaper_objects(
+        &self,
+        output_name: u32,
+        version: u32,
+        xdg_output_manager: Option<ObjectId>,
+    ) -> rustix::io::Result<(
+        ObjectId,
+        Option<ObjectId>,
+        ObjectId,
+        ObjectId,
+        ObjectId,
+        Option<ObjectId>,
+    )> {
+        use wayland::interfaces::*;
+        let output = globals::object_create(wayland::WlDynObj::Output);
+        wl_registry::req::bind(output_name, output, "wl_output", version.min(4))?;
+
+        let xdg_output = match xdg_output_manager {
+            Some(manager) => {
+                let xdg_output = globals::object_create(wayland::WlDynObj::XdgOutput);
+                zxdg_output_manager_v1::req::get_xdg_output(manager, xdg_output, output)?;
+                Some(xdg_output)
+            }
+            None => None,
+        };
+
+        let surface = globals::object_create(wayland::WlDynObj::Surface);
+        wl_compositor::req::create_surface(surface)?;
 
         let layer_surface = globals::object_create(wayland::WlDynObj::LayerSurface);
         zwlr_layer_shell_v1::req::get_layer_surface(
             layer_surface,
             surface,
             Some(output),
-            zwlr_layer_shell_v1::layer::BACKGROUND,
+            self.layer,
             "swww-daemon",
-        )
-        .unwrap();
+        )?;
 
         let viewport = globals::object_create(wayland::WlDynObj::Viewport);
-        wp_viewporter::req::get_viewport(viewport, surface).unwrap();
+        wp_viewporter::req::get_viewport(viewport, surface)?;
 
         let wp_fractional = if let Some((id, _)) = self.fractional_scale_manager.as_ref() {
             let fractional = globals::object_create(wayland::WlDynObj::FractionalScale);
-            wp_fractional_scale_manager_v1::req::get_fractional_scale(*id, fractional, surface)
-                .unwrap();
+            wp_fractional_scale_manager_v1::req::get_fractional_scale(*id, fractional, surface)?;
             Some(fractional)
         } else {
             None
         };
-
-        debug!("New output: {output_name}");
-        self.wallpapers.push(Arc::new(Wallpaper::new(
+        Ok((
             output,
-            output_name,
+            xdg_output,
             surface,
+            layer_surface,
             viewport,
             wp_fractional,
-            layer_surface,
-        )));
+        ))
+    }
+
+    /// Replaces the wallpapers whose requests failed with new ones, keeping the other outputs
+    /// untouched
+    fn rebuild_broken_wallpapers(&mut self) {
+        if !self.wallpapers.iter().any(|w| w.is_broken()) {
+            return;
+        }
+        let (broken, ok): (Vec<_>, Vec<_>) = self.wallpapers.drain(..).partition(|w| w.is_broken());
+        self.wallpapers = ok;
+        crate::wallpaper::stop_animations(&broken);
+        for wallpaper in broken {
+            // the animation threads might be waiting for a frame callback that will never come
+            wallpaper.frame_callback_completed();
+            let output_name = wallpaper.output_name();
+            let version = wallpaper.output_version();
+            drop(wallpaper);
+
+            let rebuilds = self.rebuilds.entry(output_name).or_default();
+            *rebuilds += 1;
+            if *rebuilds > MAX_REBUILDS {
+                error!("output {output_name} keeps failing. Giving up on it");
+                continue;
+            }
+            info!("rebuilding the wallpaper of output {output_name}");
+            self.new_output(output_name, version);
+        }
+    }
+
+    /// Marks the wallpapers the compositor is taking too long to configure as broken, so that we
+    /// rebuild them. If one was already rebuilt, its next one asks for the output's size instead
+    /// of letting the compositor pick it
+    fn check_configure_timeouts(&mut self) {
+        for wallpaper in self.wallpapers.iter() {
+            if !wallpaper.check_configure_timeout(CONFIGURE_TIMEOUT) {
+                continue;
+            }
+            let output_name = wallpaper.output_name();
+            let rebuilt = self.rebuilds.get(&output_name).is_some_and(|&n| n > 0);
+            match wallpaper.staged_size() {
+                Some(size) if rebuilt && !self.fallback_sizes.contains_key(&output_name) => {
+                    info!(
+                        "asking for a size of {}x{} for output {output_name} this time",
+                        size.0, size.1
+                    );
+                    self.fallback_sizes.insert(output_name, size);
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Recomputes the tint's and vignette's strengths, redrawing the wallpapers if they changed
+    fn update_tint(&mut self) {
+        let now = unix_time();
+        let applied = self
+            .tint
+            .and_then(|tint| Some((tint.color?, tint.strength_at(now))))
+            .filter(|(_, strength)| *strength > 0);
+        let vignette = self.tint.map_or(0, |tint| tint.vignette_at(now));
+        if applied == self.applied_tint && vignette == self.applied_vignette {
+            return;
+        }
+        self.applied_tint = applied;
+        self.applied_vignette = vignette;
+        self.apply_filters(self.wallpapers.clone());
+    }
+
+    fn color_filter(&self, wallpaper: &Wallpaper) -> ColorFilter {
+        wallpaper
+            .name()
+            .and_then(|name| self.color_filters.get(&name).copied())
+            .unwrap_or(self.default_color_filter)
+    }
+
+    fn dim(&self, wallpaper: &Wallpaper) -> Option<([u8; 3], u8)> {
+        wallpaper
+            .name()
+            .and_then(|name| self.dims.get(&name).copied())
+            .or(self.default_dim)
+            .filter(|(_, strength)| *strength > 0)
+    }
+
+    /// Whether we have to filter what we draw on `wallpaper` at all
+    fn is_filtered(&self, wallpaper: &Wallpaper) -> bool {
+        self.applied_tint.is_some()
+            || self.applied_vignette > 0
+            || self.color_filter(wallpaper) != ColorFilter::None
+            || self.dim(wallpaper).is_some()
+    }
+
+    /// Redraws `wallpapers` with the current tint, accessibility filters and dims
+    fn apply_filters(&self, wallpapers: Vec<Arc<Wallpaper>>) {
+        let wallpapers: Vec<_> = wallpapers
+            .into_iter()
+            .filter(|w| w.configured.load(std::sync::atomic::Ordering::Acquire))
+            .map(|w| {
+                let lut = if self.is_filtered(&w) {
+                    Some(Arc::new(Lut::new(
+                        globals::pixel_format(),
+                        self.applied_tint,
+                        self.color_filter(&w),
+                        self.dim(&w),
+                        self.applied_vignette,
+                    )))
+                } else {
+                    None
+                };
+                (w, lut)
+            })
+            .collect();
+        if wallpapers.is_empty() {
+            return;
+        }
+
+        self.filter_worker.refilter(wallpapers);
+    }
+
+    /// How long the main loop may sleep before we have to update the tint, in milliseconds
+    fn poll_timeout(&self) -> i32 {
+        if self.exiting {
+            // check on the outros regularly
+            return 10;
+        }
+        let tint = self.tint.and_then(|tint| tint.next_update(unix_time()));
+        let configure = self
+            .wallpapers
+            .iter()
+            .filter_map(|w| w.configure_deadline(CONFIGURE_TIMEOUT))
+            .min();
+        match tint.into_iter().chain(configure).min() {
+            Some(duration) => (duration.as_millis() as i32).max(1),
+            None => -1,
+        }
     }
 
     fn recv_socket_msg(&mut self, stream: IpcSocket<Server>) {
@@ -131,10 +507,42 @@ impl Daemon {
                 return;
             }
         };
-        let request = RequestRecv::receive(bytes);
-        let answer = match request {
+        let answer = self.handle_request(RequestRecv::receive(bytes));
+        if let Err(e) = answer.send(&stream) {
+            error!("error sending answer to client: {e}");
+        }
+    }
+
+    fn handle_request(&mut self, request: RequestRecv) -> Answer {
+        if !matches!(
+            request,
+            RequestRecv::Ping
+                | RequestRecv::Query
+                | RequestRecv::Capabilities
+                | RequestRecv::DebugSurfaces
+        ) {
+            self.read_cache.invalidate();
+        }
+        match request {
             RequestRecv::Clear(clear) => {
                 let wallpapers = self.find_wallpapers_by_names(&clear.outputs);
+                for wallpaper in &wallpapers {
+                    if let Some(name) = wallpaper.name() {
+                        self.animated.remove(&name);
+                    }
+                }
+                if clear.outro != Outro::None {
+                    let outro = self.animator.outro(
+                        wallpapers,
+                        clear.color,
+                        clear.outro,
+                        clear.outro_duration,
+                        self.limits.max_fps,
+                    );
+                    self.outros.retain(|outro| !outro.is_finished());
+                    self.outros.push(outro);
+                    return Answer::Ok;
+                }
                 std::thread::Builder::new()
                     .stack_size(1 << 15)
                     .name("clear".to_string())
@@ -142,6 +550,7 @@ impl Daemon {
                         crate::wallpaper::stop_animations(&wallpapers);
                         for wallpaper in &wallpapers {
                             wallpaper.set_img_info(common::ipc::BgImg::Color(clear.color));
+                            wallpaper.set_render_size(None);
                             wallpaper.clear(clear.color);
                         }
                         crate::wallpaper::attach_buffers_and_damange_surfaces(&wallpapers);
@@ -150,42 +559,333 @@ impl Daemon {
                     .unwrap(); // builder only failed if the name contains null bytes
                 Answer::Ok
             }
-            RequestRecv::Ping => Answer::Ping(
+            RequestRecv::Ping => {
+                let wallpapers = &self.wallpapers;
+                Answer::Ping(self.read_cache.ping(|| {
+                    wallpapers
+                        .iter()
+                        .all(|w| w.configured.load(std::sync::atomic::Ordering::Acquire))
+                }))
+            }
+            RequestRecv::Capabilities => Answer::Capabilities(capabilities()),
+            // like the buffer release handler, we are animating if an animation holds a wallpaper
+            RequestRecv::DebugSurfaces => Answer::Surfaces(
                 self.wallpapers
                     .iter()
-                    .all(|w| w.configured.load(std::sync::atomic::Ordering::Acquire)),
+                    .map(|w| w.get_surface_info(Arc::strong_count(w) != 1))
+                    .collect(),
             ),
+            RequestRecv::TransitionDefaults(req) => {
+                self.transition_defaults.update(req, &mut self.vars);
+                Answer::Ok
+            }
+            RequestRecv::Pause(paused) => {
+                animations::set_paused(paused);
+                Answer::Ok
+            }
             RequestRecv::Kill => {
-                exit_daemon();
+                // let `swww kill --outro` finish first
+                self.exiting = true;
+                self.exit_once_outros_finish();
                 Answer::Ok
             }
-            RequestRecv::Query => Answer::Info(self.wallpapers_info()),
+            RequestRecv::Query => {
+                let (wallpapers, order) = (&self.wallpapers, self.output_order);
+                Answer::Info(self.read_cache.info(|| {
+                    let mut outputs: Vec<_> = wallpapers
+                        .iter()
+                        .map(|w| {
+                            let (x, y, _, _) = w.get_layout();
+                            (w.get_bg_info(), (x, y))
+                        })
+                        .collect();
+                    outputs.sort_by(|(a, a_pos), (b, b_pos)| {
+                        order.compare((&a.name, *a_pos), (&b.name, *b_pos))
+                    });
+                    outputs.into_iter().map(|(info, _)| info).collect()
+                }))
+            }
             RequestRecv::Img(ImageReq {
-                transition,
+                mut transitions,
                 imgs,
                 outputs,
                 animations,
             }) => {
+                let default_masks = transitions
+                    .iter_mut()
+                    .map(|transition| self.transition_defaults.apply(transition))
+                    .collect();
+                transitions.iter_mut().for_each(animations::pick_random);
+                let animations = self.reduce_motion.apply(&mut transitions, animations);
+                let dims = imgs.iter().map(|img| img.dim);
+                if let Err(e) = self.check_limits(dims, &outputs, animations.is_some()) {
+                    error!("refusing image request: {e}");
+                    return Answer::Error(e);
+                }
+                let max_fps = self.limits.max_fps;
+                let used = transitions
+                    .iter_mut()
+                    .zip(outputs.iter())
+                    .map(|(transition, names)| {
+                        let clamped = animations::sanitize(transition, max_fps);
+                        TransitionUsed {
+                            outputs: names.iter().map(|name| name.str().to_string()).collect(),
+                            transition_type: transition.transition_type,
+                            duration: transition.duration,
+                            fps: transition.fps,
+                            clamped: clamped.into(),
+                        }
+                    })
+                    .collect();
                 let mut used_wallpapers = Vec::new();
                 for names in outputs.iter() {
                     let wallpapers = self.find_wallpapers_by_names(names);
                     crate::wallpaper::stop_animations(&wallpapers);
+                    // the transition thread waits for these
+                    self.screencopy.capture(&wallpapers);
                     used_wallpapers.push(wallpapers);
                 }
-                self.animator
-                    .transition(transition, imgs, animations, used_wallpapers)
+                self.animator.transition(
+                    transitions,
+                    imgs,
+                    default_masks,
+                    animations,
+                    used_wallpapers,
+                );
+                if let Some(max_size) = self.limits.max_cache_size {
+                    if let Err(e) = common::cache::trim(max_size) {
+                        error!("failed to trim the cache: {e}");
+                    }
+                }
+                Answer::Transitions(used)
+            }
+            RequestRecv::SetVar(SetVarReq { key, .. }) if key.str().starts_with("transition.") => {
+                Answer::Error(
+                    "transition defaults are set through `swww transition-defaults`".to_string(),
+                )
+            }
+            RequestRecv::SetVar(SetVarReq { key, value }) => {
+                debug!("setting variable {} = {}", key.str(), value.str());
+                self.vars
+                    .insert(key.str().to_string(), value.str().to_string());
+                if key.str().starts_with("tint.") {
+                    match Tint::from_vars(&self.vars) {
+                        Ok(tint) => self.tint = tint,
+                        Err(e) => error!("failed to set tint: {e}"),
+                    }
+                    self.update_tint();
+                } else if key.str().starts_with("hotspot.") {
+                    self.hotspots = hotspot::from_vars(&self.vars);
+                    if !self.accept_pointer && !self.hotspots.is_empty() {
+                        warn!("hotspots only work when swww-daemon runs with --accept-pointer");
+                    }
+                } else if key.str() == "reduce_motion" {
+                    match ReduceMotion::parse(value.str()) {
+                        Some(reduce_motion) => self.reduce_motion = reduce_motion,
+                        None => error!("reduce_motion must be one of: 'off', 'fade' or 'instant'"),
+                    }
+                } else if key.str() == "output_order" {
+                    match output_order::OutputOrder::parse(value.str()) {
+                        Some(order) => self.output_order = order,
+                        None => {
+                            error!("output_order must be one of: 'name', 'position' or 'registry'")
+                        }
+                    }
+                }
+                Answer::Ok
+            }
+            RequestRecv::GetVar(GetVarReq { key }) => {
+                Answer::Var(self.vars.get(key.str()).cloned())
+            }
+            RequestRecv::ListVars(ListVarsReq { prefix }) => {
+                let mut vars: Vec<_> = self
+                    .vars
+                    .iter()
+                    .filter(|(key, _)| key.starts_with(prefix.str()))
+                    .map(|(key, value)| (key.clone(), value.clone()))
+                    .collect();
+                vars.sort_unstable();
+                Answer::Vars(vars.into())
+            }
+            RequestRecv::Filter(FilterReq { filter, outputs }) => {
+                debug!("setting color filter {filter:?}");
+                if outputs.is_empty() {
+                    self.color_filters.clear();
+                    self.default_color_filter = filter;
+                } else {
+                    for output in outputs.iter() {
+                        self.color_filters.insert(output.str().to_string(), filter);
+                    }
+                }
+                self.apply_filters(self.find_wallpapers_by_names(&outputs));
+                Answer::Ok
+            }
+            RequestRecv::Dim(DimReq {
+                color,
+                strength,
+                outputs,
+            }) => {
+                debug!("dimming towards {color:?} with strength {strength}");
+                if outputs.is_empty() {
+                    self.dims.clear();
+                    self.default_dim = Some((color, strength));
+                } else {
+                    for output in outputs.iter() {
+                        self.dims
+                            .insert(output.str().to_string(), (color, strength));
+                    }
+                }
+                self.apply_filters(self.find_wallpapers_by_names(&outputs));
+                Answer::Ok
+            }
+            RequestRecv::Batch(requests) => {
+                debug!("handling a batch of {} requests", requests.len());
+                // `swww batch` does not report the answers of each request
+                for request in requests.into_vec() {
+                    self.handle_request(request);
+                }
+                Answer::Ok
+            }
+            RequestRecv::Stream(StreamReq { mut ring, outputs }) => {
+                if let Err(e) = ring.attach(globals::pixel_format()) {
+                    error!("refusing stream: {e}");
+                    return Answer::Error(e);
+                }
+                let wallpapers = self.find_wallpapers_by_names(&outputs);
+                if wallpapers.is_empty() {
+                    return Answer::Error("none of the stream's outputs exist".to_string());
+                }
+                let dim = ring.dim();
+                if let Some(wallpaper) = wallpapers.iter().find(|w| {
+                    let (width, height) = w.get_dimensions();
+                    dim.0 > width || dim.1 > height
+                }) {
+                    let e = format!(
+                        "the stream's frames are {}x{}, larger than output {}",
+                        dim.0,
+                        dim.1,
+                        wallpaper.name().unwrap_or_default()
+                    );
+                    error!("refusing stream: {e}");
+                    return Answer::Error(e);
+                }
+                if let Err(e) = self.check_limits([dim], std::slice::from_ref(&outputs), true) {
+                    error!("refusing stream: {e}");
+                    return Answer::Error(e);
+                }
+                crate::wallpaper::stop_animations(&wallpapers);
+                self.animator.stream(ring, wallpapers, self.limits.max_fps);
+                Answer::Ok
+            }
+            RequestRecv::Shader(ShaderReq {
+                source,
+                language,
+                fps,
+                downscale,
+                outputs,
+            }) => {
+                let wallpapers = self.find_wallpapers_by_names(&outputs);
+                if wallpapers.is_empty() {
+                    return Answer::Error("none of the shader's outputs exist".to_string());
+                }
+                let dims: Vec<_> = wallpapers.iter().map(|w| w.get_dimensions()).collect();
+                if let Err(e) = self.check_limits(dims, std::slice::from_ref(&outputs), true) {
+                    error!("refusing shader: {e}");
+                    return Answer::Error(e);
+                }
+                crate::wallpaper::stop_animations(&wallpapers);
+                let fps = fps.clamp(1, self.limits.max_fps);
+                match self
+                    .animator
+                    .shader(source.str(), language, fps, downscale, wallpapers)
+                {
+                    Ok(()) => Answer::Ok,
+                    Err(e) => {
+                        error!("shader failed: {e}");
+                        Answer::Error(e)
+                    }
+                }
+            }
+            RequestRecv::If(IfReq {
+                condition,
+                then,
+                otherwise,
+            }) => {
+                let holds = self.condition_holds(&condition);
+                debug!("condition {condition:?} is {holds}");
+                let requests = if holds { then } else { otherwise };
+                for request in requests.into_vec() {
+                    self.handle_request(request);
+                }
+                Answer::Ok
             }
-        };
-        if let Err(e) = answer.send(&stream) {
-            error!("error sending answer to client: {e}");
         }
     }
 
-    fn wallpapers_info(&self) -> Box<[BgInfo]> {
-        self.wallpapers
-            .iter()
-            .map(|wallpaper| wallpaper.get_bg_info())
-            .collect()
+    fn condition_holds(&self, condition: &Condition) -> bool {
+        match condition {
+            Condition::OutputExists(name) => self.wallpapers.iter().any(|w| w.has_name(name)),
+            Condition::CurrentImage {
+                output,
+                path,
+                equal,
+            } => {
+                let mut wallpapers = self
+                    .wallpapers
+                    .iter()
+                    .filter(|w| output.as_ref().map_or(true, |name| w.has_name(name)))
+                    .peekable();
+                // no outputs display the image if there are no outputs
+                let displayed = wallpapers.peek().is_some()
+                    && wallpapers
+                        .all(|w| matches!(w.get_bg_info().img, BgImg::Img(ref img) if img == path));
+                displayed == *equal
+            }
+            Condition::Var(name, value) => self.vars.get(name) == Some(value),
+        }
+    }
+
+    fn exit_once_outros_finish(&mut self) {
+        self.outros.retain(|outro| !outro.is_finished());
+        if self.outros.is_empty() {
+            exit_daemon();
+        }
+    }
+
+    /// Checks an image request against `self.limits`, and if it passes, remembers which outputs
+    /// will show an animation
+    fn check_limits(
+        &mut self,
+        dims: impl IntoIterator<Item = (u32, u32)>,
+        outputs: &[Box<[MmappedStr]>],
+        animated: bool,
+    ) -> Result<(), String> {
+        let mut animated_after = self.animated.clone();
+        for names in outputs {
+            for wallpaper in self.find_wallpapers_by_names(names) {
+                if let Some(name) = wallpaper.name() {
+                    animated_after.remove(&name);
+                    if animated {
+                        animated_after.insert(name);
+                    }
+                }
+            }
+        }
+        if animated {
+            for dim in dims {
+                self.limits.check_animation(dim)?;
+            }
+            // outputs that went away are not animated anymore
+            let count = self
+                .wallpapers
+                .iter()
+                .filter_map(|wallpaper| wallpaper.name())
+                .filter(|name| animated_after.contains(name))
+                .count();
+            self.limits.check_animation_count(count)?;
+        }
+        self.animated = animated_after;
+        Ok(())
     }
 
     fn find_wallpapers_by_names(&self, names: &[MmappedStr]) -> Vec<Arc<Wallpaper>> {
@@ -210,20 +910,185 @@ impl wayland::interfaces::wl_display::EvHandler for Daemon {
 }
 impl wayland::interfaces::wl_registry::EvHandler for Daemon {
     fn global(&mut self, name: u32, interface: &str, version: u32) {
-        if interface == "wl_output" {
-            if version < 4 {
-                error!("your compositor must support at least version 4 of wl_output");
-            } else {
-                self.new_output(name);
+        match interface {
+            "wl_output" => {
+                if version < 3 {
+                    error!("your compositor must support at least version 3 of wl_output");
+                } else {
+                    self.new_output(name, version);
+                }
             }
+            "zxdg_output_manager_v1" if self.xdg_output_manager.is_none() => {
+                self.xdg_output_manager = Some((name, version, None));
+            }
+            "zwlr_output_manager_v1" if self.output_manager.is_none() => {
+                self.bind_output_manager(name, version);
+            }
+            "zwlr_screencopy_manager_v1"
+                if self.capture_first_frame && !self.screencopy.is_bound() =>
+            {
+                self.screencopy.bind(name, version);
+            }
+            "wl_seat" => self.new_seat(name, version),
+            _ => (),
         }
     }
 
     fn global_remove(&mut self, name: u32) {
+        use wayland::interfaces::*;
         self.wallpapers.retain(|w| !w.has_output_name(name));
+        self.read_cache.invalidate();
+        if self
+            .xdg_output_manager
+            .is_some_and(|(manager, _, _)| manager == name)
+        {
+            self.xdg_output_manager = None;
+        }
+        if self
+            .output_manager
+            .is_some_and(|(manager, _)| manager == name)
+        {
+            self.output_manager = None;
+        }
+        self.screencopy.global_remove(name);
+        if let Some(i) = self.seats.iter().position(|seat| seat.name == name) {
+            let seat = self.seats.swap_remove(i);
+            if seat.version >= 5 {
+                if let Some(pointer) = seat.wl_pointer {
+                    if let Err(e) = wl_pointer::req::release(pointer) {
+                        error!("failed to release wl_pointer: {e}");
+                    }
+                }
+                if let Err(e) = wl_seat::req::release(seat.wl_seat) {
+                    error!("failed to release wl_seat: {e}");
+                }
+            }
+        }
     }
 }
 
+impl wayland::interfaces::wl_seat::EvHandler for Daemon {
+    fn capabilities(&mut self, sender_id: ObjectId, capabilities: u32) {
+        use wayland::interfaces::*;
+        let Some(seat) = self.seats.iter_mut().find(|seat| seat.wl_seat == sender_id) else {
+            return;
+        };
+        let has_pointer = capabilities & wl_seat::capability::POINTER != 0;
+        match seat.wl_pointer {
+            // we listen to the pointer even if we do not accept its input, so that we notice if
+            // the compositor gives us its focus anyway
+            None if has_pointer => {
+                let pointer = globals::object_create(wayland::WlDynObj::Pointer);
+                match wl_seat::req::get_pointer(seat.wl_seat, pointer) {
+                    Ok(()) => seat.wl_pointer = Some(pointer),
+                    Err(e) => error!("failed to get wl_pointer: {e}"),
+                }
+            }
+            Some(pointer) if !has_pointer => {
+                if seat.version >= 5 {
+                    if let Err(e) = wl_pointer::req::release(pointer) {
+                        error!("failed to release wl_pointer: {e}");
+                    }
+                }
+                seat.wl_pointer = None;
+                seat.focus = None;
+            }
+            _ => (),
+        }
+    }
+
+    fn name(&mut self, _sender_id: ObjectId, name: &str) {
+        debug!("Seat name: {name}");
+    }
+}
+
+impl wayland::interfaces::wl_pointer::EvHandler for Daemon {
+    fn enter(
+        &mut self,
+        sender_id: ObjectId,
+        _serial: u32,
+        surface: Option<ObjectId>,
+        surface_x: wayland::wire::WlFixed,
+        surface_y: wayland::wire::WlFixed,
+    ) {
+        let Some(surface) = surface else { return };
+        let Some(wallpaper) = self.wallpapers.iter().find(|w| w.has_surface(surface)) else {
+            return;
+        };
+        if !self.accept_pointer {
+            warn!(
+                "compositor gave pointer focus to output {} despite its empty input region. \
+                 Setting it again",
+                wallpaper.get_bg_info().name
+            );
+            wallpaper.reset_input_region();
+            return;
+        }
+        if let Some(seat) = self
+            .seats
+            .iter_mut()
+            .find(|seat| seat.wl_pointer == Some(sender_id))
+        {
+            seat.focus = Some((surface, f64::from(&surface_x), f64::from(&surface_y)));
+        }
+    }
+
+    fn leave(&mut self, sender_id: ObjectId, _serial: u32, _surface: Option<ObjectId>) {
+        if let Some(seat) = self
+            .seats
+            .iter_mut()
+            .find(|seat| seat.wl_pointer == Some(sender_id))
+        {
+            if let Some((surface, x, y)) = seat.focus.take() {
+                debug!("Pointer left surface {} at ({x}, {y})", surface.get());
+            }
+        }
+    }
+
+    fn motion(
+        &mut self,
+        sender_id: ObjectId,
+        _time: u32,
+        surface_x: wayland::wire::WlFixed,
+        surface_y: wayland::wire::WlFixed,
+    ) {
+        if let Some(seat) = self
+            .seats
+            .iter_mut()
+            .find(|seat| seat.wl_pointer == Some(sender_id))
+        {
+            if let Some((_, x, y)) = seat.focus.as_mut() {
+                *x = f64::from(&surface_x);
+                *y = f64::from(&surface_y);
+            }
+        }
+    }
+
+    fn button(&mut self, sender_id: ObjectId, _serial: u32, _time: u32, button: u32, state: u32) {
+        /// from linux/input-event-codes.h
+        const BTN_LEFT: u32 = 0x110;
+        if button != BTN_LEFT || state != wayland::interfaces::wl_pointer::button_state::PRESSED {
+            return;
+        }
+        let Some((surface, x, y)) = self
+            .seats
+            .iter()
+            .find(|seat| seat.wl_pointer == Some(sender_id))
+            .and_then(|seat| seat.focus)
+        else {
+            return;
+        };
+        if let Some(wallpaper) = self.wallpapers.iter().find(|w| w.has_surface(surface)) {
+            let output = wallpaper.get_bg_info().name;
+            if !hotspot::click(&self.hotspots, &output, x, y) {
+                debug!("click at ({x}, {y}) in {output} hit no hotspot");
+            }
+        }
+    }
+
+    fn axis(&mut self, _: ObjectId, _: u32, _: u32, _: wayland::wire::WlFixed) {}
+}
+
 impl wayland::interfaces::wl_shm::EvHandler for Daemon {
     fn format(&mut self, format: u32) {
         warn!(
@@ -236,17 +1101,19 @@ impl wayland::interfaces::wl_output::EvHandler for Daemon {
     fn geometry(
         &mut self,
         sender_id: ObjectId,
-        _x: i32,
-        _y: i32,
+        x: i32,
+        y: i32,
         _physical_width: i32,
         _physical_height: i32,
         _subpixel: i32,
-        _make: &str,
-        _model: &str,
+        make: &str,
+        model: &str,
         transform: i32,
     ) {
         for wallpaper in self.wallpapers.iter() {
             if wallpaper.has_output(sender_id) {
+                wallpaper.set_make_and_model(make, model);
+                wallpaper.set_position(x, y, false);
                 if transform as u32 > wayland::interfaces::wl_output::transform::FLIPPED_270 {
                     error!("received invalid transform value from compositor: {transform}")
                 } else {
@@ -267,9 +1134,14 @@ impl wayland::interfaces::wl_output::EvHandler for Daemon {
     }
 
     fn done(&mut self, sender_id: ObjectId) {
+        // the output's name, size or scale may have changed
+        self.read_cache.invalidate();
         for wallpaper in self.wallpapers.iter() {
             if wallpaper.has_output(sender_id) {
-                wallpaper.commit_surface_changes(self.use_cache);
+                wallpaper.commit_surface_changes(&self.restore);
+                if self.is_filtered(wallpaper) {
+                    self.apply_filters(vec![Arc::clone(wallpaper)]);
+                }
                 break;
             }
         }
@@ -305,6 +1177,39 @@ impl wayland::interfaces::wl_output::EvHandler for Daemon {
         }
     }
 }
+impl wayland::interfaces::zxdg_output_v1::EvHandler for Daemon {
+    fn logical_position(&mut self, sender_id: ObjectId, x: i32, y: i32) {
+        if let Some(wallpaper) = self.wallpapers.iter().find(|w| w.has_xdg_output(sender_id)) {
+            wallpaper.set_position(x, y, true);
+        }
+    }
+
+    fn logical_size(&mut self, _: ObjectId, _: i32, _: i32) {}
+
+    fn done(&mut self, sender_id: ObjectId) {
+        // before version 3, the name only gets applied with this, and not with `wl_output.done`
+        let output = self
+            .wallpapers
+            .iter()
+            .find(|w| w.has_xdg_output(sender_id))
+            .map(|w| w.output());
+        if let Some(output) = output {
+            wayland::interfaces::wl_output::EvHandler::done(self, output);
+        }
+    }
+
+    fn name(&mut self, sender_id: ObjectId, name: &str) {
+        if let Some(wallpaper) = self.wallpapers.iter().find(|w| w.has_xdg_output(sender_id)) {
+            wallpaper.set_name(name.to_string());
+        }
+    }
+
+    fn description(&mut self, sender_id: ObjectId, description: &str) {
+        if let Some(wallpaper) = self.wallpapers.iter().find(|w| w.has_xdg_output(sender_id)) {
+            wallpaper.set_desc(description.to_string());
+        }
+    }
+}
 impl wayland::interfaces::wl_surface::EvHandler for Daemon {
     fn enter(&mut self, _sender_id: ObjectId, output: ObjectId) {
         debug!("Output {}: Surface Enter", output.get());
@@ -326,8 +1231,39 @@ impl wayland::interfaces::wl_surface::EvHandler for Daemon {
         }
     }
 
-    fn preferred_buffer_transform(&mut self, _sender_id: ObjectId, _transform: u32) {
-        warn!("Received PreferredBufferTransform. We currently ignore those")
+    fn preferred_buffer_transform(&mut self, sender_id: ObjectId, transform: u32) {
+        let Some(wallpaper) = self.wallpapers.iter().find(|w| w.has_surface(sender_id)) else {
+            return;
+        };
+        if transform > wayland::interfaces::wl_output::transform::FLIPPED_270 {
+            error!("received invalid buffer transform value from compositor: {transform}");
+            return;
+        }
+        debug!(
+            "Output {}: preferred buffer transform {transform}",
+            wallpaper.output_name()
+        );
+        if !wallpaper
+            .configured
+            .load(std::sync::atomic::Ordering::Acquire)
+        {
+            // the first configure will draw with it
+            wallpaper.set_buffer_transform(transform);
+            return;
+        }
+
+        // attaching waits for the frame callback, which we must be free to dispatch
+        let wallpaper = Arc::clone(wallpaper);
+        std::thread::Builder::new()
+            .stack_size(1 << 15)
+            .name("buffer transform".to_string())
+            .spawn(move || {
+                wallpaper.set_buffer_transform(transform);
+                let wallpapers = [wallpaper];
+                crate::wallpaper::attach_buffers_and_damange_surfaces(&wallpapers);
+                crate::wallpaper::commit_wallpapers(&wallpapers);
+            })
+            .unwrap(); // builder only failed if the name contains null bytes
     }
 }
 
@@ -342,6 +1278,27 @@ impl wayland::interfaces::wl_buffer::EvHandler for Daemon {
     }
 }
 
+impl wayland::interfaces::zwlr_output_manager_v1::EvHandler for Daemon {
+    fn head(&mut self, _: ObjectId, head: ObjectId) {
+        self.heads.new_head(head);
+    }
+
+    fn done(&mut self, _: ObjectId, _: u32) {
+        for (name, (width, height), transform) in self.heads.current_modes() {
+            if let Some(wallpaper) = self.wallpapers.iter().find(|w| w.has_name(name)) {
+                wallpaper.prepare_mode(width, height, transform);
+            }
+        }
+    }
+
+    fn finished(&mut self, sender_id: ObjectId) {
+        debug!("wlr-output-management is gone");
+        self.output_manager = None;
+        self.heads = heads::Heads::default();
+        globals::object_remove(sender_id);
+    }
+}
+
 impl wayland::interfaces::wl_callback::EvHandler for Daemon {
     fn done(&mut self, sender_id: ObjectId, _callback_data: u32) {
         for wallpaper in self.wallpapers.iter() {
@@ -357,8 +1314,12 @@ impl wayland::interfaces::zwlr_layer_surface_v1::EvHandler for Daemon {
     fn configure(&mut self, sender_id: ObjectId, serial: u32, _width: u32, _height: u32) {
         for wallpaper in self.wallpapers.iter() {
             if wallpaper.has_layer_surface(sender_id) {
-                wayland::interfaces::zwlr_layer_surface_v1::req::ack_configure(sender_id, serial)
-                    .unwrap();
+                wallpaper.check(
+                    "zwlr_layer_surface_v1.ack_configure",
+                    wayland::interfaces::zwlr_layer_surface_v1::req::ack_configure(
+                        sender_id, serial,
+                    ),
+                );
                 break;
             }
         }
@@ -366,6 +1327,7 @@ impl wayland::interfaces::zwlr_layer_surface_v1::EvHandler for Daemon {
 
     fn closed(&mut self, sender_id: ObjectId) {
         self.wallpapers.retain(|w| !w.has_layer_surface(sender_id));
+        self.read_cache.invalidate();
     }
 }
 
@@ -376,7 +1338,7 @@ impl wayland::interfaces::wp_fractional_scale_v1::EvHandler for Daemon {
                 match NonZeroI32::new(scale as i32) {
                     Some(factor) => {
                         wallpaper.set_scale(Scale::Fractional(factor));
-                        wallpaper.commit_surface_changes(self.use_cache);
+                        wallpaper.commit_surface_changes(&self.restore);
                     }
                     None => error!("received scale factor of 0 from compositor"),
                 }
@@ -388,11 +1350,61 @@ impl wayland::interfaces::wp_fractional_scale_v1::EvHandler for Daemon {
 
 fn main() -> Result<(), String> {
     // first, get the command line arguments and make the logger
-    let cli = cli::Cli::new();
+    let mut cli = cli::Cli::new();
     make_logger(cli.quiet);
+    sleep::init(cli.sleep);
+
+    let restore = match cli.greeter.take() {
+        Some(config) => {
+            // the clients we spawn inherit this, so they keep their cache there too
+            let cache_dir = config.cache_dir.clone().unwrap_or_else(|| {
+                let mut dir =
+                    std::env::var_os("XDG_RUNTIME_DIR").map_or_else(std::env::temp_dir, Into::into);
+                dir.push("swww-greeter");
+                dir
+            });
+            info!("greeter mode: keeping the cache in {}", cache_dir.display());
+            std::env::set_var("SWWW_CACHE_DIR", cache_dir);
+            if let Some(handoff) = &config.handoff {
+                if let Err(e) = config.write_handoff(handoff) {
+                    warn!(
+                        "failed to write the handoff file {}: {e}",
+                        handoff.display()
+                    );
+                }
+            }
+            Restore::Greeter(Arc::new(config))
+        }
+        // the greeter might not have run, e.g. with autologin
+        None => match cli.handoff.as_deref().map(greeter::Config::load) {
+            Some(Ok(config)) => Restore::Handoff {
+                greeter: Arc::new(config),
+                cache: !cli.no_cache,
+            },
+            Some(Err(e)) => {
+                info!("not starting from the greeter's wallpaper: {e}");
+                if cli.no_cache {
+                    Restore::Nothing
+                } else {
+                    Restore::Cache
+                }
+            }
+            None if cli.no_cache => Restore::Nothing,
+            None => Restore::Cache,
+        },
+    };
 
-    // initialize the wayland connection, getting all the necessary globals
-    let initializer = wayland::globals::init(cli.format);
+    #[cfg(feature = "drm")]
+    if let Some(device) = cli.drm.take() {
+        let listener = SocketWrapper::new()?;
+        setup_signals();
+        return kms::run(&device, &listener.0, &cli);
+    }
+
+    // initialize the wayland connection, getting all the necessary globals. On the login screen,
+    // we might have been started before the compositor is ready for us
+    let wait = matches!(restore, Restore::Greeter(_)).then_some(greeter::COMPOSITOR_TIMEOUT);
+    let initializer = wayland::globals::init(cli.format, wait);
 
     // create the socket listener and setup the signal handlers
     // this will also return an error if there is an `swww-daemon` instance already
@@ -401,17 +1413,24 @@ fn main() -> Result<(), String> {
     setup_signals();
 
     // use the initializer to create the Daemon, then drop it to free up the memory
-    let mut daemon = Daemon::new(&initializer, cli.no_cache);
-    for &output_name in initializer.output_names() {
-        daemon.new_output(output_name);
+    let mut daemon = Daemon::new(&initializer, &cli, restore);
+    for &(output_name, version) in initializer.outputs() {
+        daemon.new_output(output_name, version);
     }
-    drop(initializer);
-
-    if let Ok(true) = sd_notify::booted() {
-        if let Err(e) = sd_notify::notify(true, &[sd_notify::NotifyState::Ready]) {
-            error!("Error sending status update to systemd: {e}");
-        }
+    for &(seat_name, version) in initializer.seats() {
+        daemon.new_seat(seat_name, version);
+    }
+    if let Some((name, version)) = initializer.output_manager() {
+        daemon.bind_output_manager(name, version);
     }
+    if let Some((name, version)) = initializer
+        .screencopy_manager()
+        .filter(|_| daemon.capture_first_frame)
+    {
+        daemon.screencopy.bind(name, version);
+    }
+    drop(initializer);
+    notify_systemd();
 
     let wayland_fd = wayland::globals::wayland_fd();
     let mut fds = [
@@ -423,13 +1442,20 @@ fn main() -> Result<(), String> {
     while !should_daemon_exit() {
         use wayland::{interfaces::*, wire, WlDynObj};
 
-        if let Err(e) = poll(&mut fds, -1) {
+        if let Err(e) = poll(&mut fds, daemon.poll_timeout()) {
             match e {
                 rustix::io::Errno::INTR => continue,
                 _ => return Err(format!("failed to poll file descriptors: {e:?}")),
             }
         }
 
+        if daemon.tint.is_some() {
+            daemon.update_tint();
+        }
+        if daemon.exiting {
+            daemon.exit_once_outros_finish();
+        }
+
         if !fds[0].revents().is_empty() {
             let (msg, payload) = match wire::WireMsg::recv() {
                 Ok((msg, payload)) => (msg, payload),
@@ -460,18 +1486,47 @@ fn main() -> Result<(), String> {
                         Some(WlDynObj::FractionalScale) => {
                             wp_fractional_scale_v1::event(&mut daemon, msg, payload)
                         }
+                        Some(WlDynObj::Seat) => wl_seat::event(&mut daemon, msg, payload),
+                        Some(WlDynObj::Pointer) => wl_pointer::event(&mut daemon, msg, payload),
+                        Some(WlDynObj::XdgOutputManager) => {
+                            error!("zxdg_output_manager_v1 has no events")
+                        }
+                        Some(WlDynObj::XdgOutput) => {
+                            zxdg_output_v1::event(&mut daemon, msg, payload)
+                        }
+                        Some(WlDynObj::OutputManager) => {
+                            zwlr_output_manager_v1::event(&mut daemon, msg, payload)
+                        }
+                        Some(WlDynObj::OutputHead) => {
+                            zwlr_output_head_v1::event(&mut daemon.heads, msg, payload)
+                        }
+                        Some(WlDynObj::OutputMode) => {
+                            zwlr_output_mode_v1::event(&mut daemon.heads, msg, payload)
+                        }
+                        Some(WlDynObj::ScreencopyManager) => {
+                            error!("zwlr_screencopy_manager_v1 has no events")
+                        }
+                        Some(WlDynObj::ScreencopyFrame) => {
+                            zwlr_screencopy_frame_v1::event(&mut daemon.screencopy, msg, payload)
+                        }
                         None => error!("Received event for deleted object ({other:?})"),
                     }
                 }
             }
         }
+        daemon.check_configure_timeouts();
+        daemon.rebuild_broken_wallpapers();
 
         if !fds[1].revents().is_empty() {
-            match rustix::net::accept(&listener.0) {
-                // TODO: abstract away explicit socket creation
-                Ok(stream) => daemon.recv_socket_msg(IpcSocket::new(stream)),
-                Err(rustix::io::Errno::INTR | rustix::io::Errno::WOULDBLOCK) => continue,
-                Err(e) => return Err(format!("failed to accept incoming connection: {e}")),
+            // answer every client already waiting, instead of polling again for each of them
+            for _ in 0..MAX_CLIENTS_PER_WAKEUP {
+                match rustix::net::accept(&listener.0) {
+                    // TODO: abstract away explicit socket creation
+                    Ok(stream) => daemon.recv_socket_msg(IpcSocket::new(stream)),
+                    Err(rustix::io::Errno::INTR) => continue,
+                    Err(rustix::io::Errno::WOULDBLOCK) => break,
+                    Err(e) => return Err(format!("failed to accept incoming connection: {e}")),
+                }
             }
         }
     }
@@ -496,6 +1551,14 @@ fn main() -> Result<(), String> {
     Ok(())
 }
 
+fn notify_systemd() {
+    if let Ok(true) = sd_notify::booted() {
+        if let Err(e) = sd_notify::notify(true, &[sd_notify::NotifyState::Ready]) {
+            error!("Error sending status update to systemd: {e}");
+        }
+    }
+}
+
 fn setup_signals() {
     // C data structure, expected to be zeroed out.
     let mut sigaction: libc::sigaction = unsafe { std::mem::zeroed() };
@@ -503,7 +1566,7 @@ fn setup_signals() {
 
     #[cfg(not(target_os = "aix"))]
     {
-        sigaction.sa_sigaction = signal_handler as usize;
+        sigaction.sa_sigaction = signal_handler as *const () as usize;
     }
     #[cfg(target_os = "aix")]
     {
@@ -650,18 +1713,28 @@ pub fn is_daemon_running() -> Result<bool, String> {
     }
 }
 
-/// copy-pasted from the `spin_sleep` crate on crates.io
-///
-/// This will sleep for an amount of time we can roughly expected the OS to still be precise enough
-/// for frame timing (125 us, currently).
-fn spin_sleep(duration: std::time::Duration) {
-    const ACCURACY: std::time::Duration = std::time::Duration::new(0, 125_000);
-    let start = std::time::Instant::now();
-    if duration > ACCURACY {
-        std::thread::sleep(duration - ACCURACY);
+/// The optional features we were built with, and the wayland interfaces we bound
+fn capabilities() -> Capabilities {
+    let features = [
+        ("wasm", cfg!(feature = "wasm")),
+        ("gpu", cfg!(feature = "gpu")),
+        ("drm", cfg!(feature = "drm")),
+        ("alloc-audit", cfg!(feature = "alloc-audit")),
+    ];
+    Capabilities {
+        features: features
+            .into_iter()
+            .filter(|(_, enabled)| *enabled)
+            .map(|(feature, _)| feature.to_string())
+            .collect(),
+        protocols: globals::bound_interfaces().into(),
     }
+}
 
-    while start.elapsed() < duration {
-        std::thread::yield_now();
-    }
+/// seconds since the unix epoch
+fn unix_time() -> f64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs_f64())
+        .unwrap_or(0.0)
 }
@@ -0,0 +1,99 @@
+//! The order we list the outputs in (`swww-daemon --output-order`).
+//!
+//! Clients give out images to the outputs in the order `swww query` lists them, which by default
+//! would be the order the compositor announced the outputs in. That changes between sessions, so
+//! we sort them instead: by name (with `DP-2` before `DP-10`), or left to right by their position
+//! in the compositor's layout.
+use std::cmp::Ordering;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum OutputOrder {
+    /// the order the compositor announced the outputs in
+    Registry,
+    Name,
+    /// left to right, and then top to bottom
+    Position,
+}
+
+impl OutputOrder {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "registry" => Some(Self::Registry),
+            "name" => Some(Self::Name),
+            "position" => Some(Self::Position),
+            _ => None,
+        }
+    }
+
+    /// Compares two outputs, given as their name and position in the layout. Ties are broken by
+    /// name, so the order is the same every time
+    pub fn compare(self, a: (&str, (i32, i32)), b: (&str, (i32, i32))) -> Ordering {
+        match self {
+            Self::Registry => Ordering::Equal,
+            Self::Name => natural_cmp(a.0, b.0),
+            Self::Position => a.1.cmp(&b.1).then_with(|| natural_cmp(a.0, b.0)),
+        }
+    }
+}
+
+/// Compares strings with the numbers in them compared by value
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a, b);
+    loop {
+        let digits = |s: &str| s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        match (a.chars().next(), b.chars().next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let (x, rest_a) = a.split_at(digits(a));
+                let (y, rest_b) = b.split_at(digits(b));
+                let (x, y) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+                let ord = x.len().cmp(&y.len()).then_with(|| x.cmp(y));
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+                (a, b) = (rest_a, rest_b);
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(&y);
+                }
+                (a, b) = (&a[x.len_utf8()..], &b[y.len_utf8()..]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(order: OutputOrder, mut outputs: Vec<(&str, (i32, i32))>) -> Vec<&str> {
+        outputs.sort_by(|a, b| order.compare(*a, *b));
+        outputs.into_iter().map(|(name, _)| name).collect()
+    }
+
+    #[test]
+    fn outputs_should_sort_deterministically() {
+        let outputs = vec![
+            ("HDMI-A-1", (0, 0)),
+            ("DP-10", (1920, 0)),
+            ("DP-2", (-1080, 0)),
+            ("eDP-1", (0, 1080)),
+        ];
+        assert_eq!(
+            sorted(OutputOrder::Name, outputs.clone()),
+            ["DP-2", "DP-10", "HDMI-A-1", "eDP-1"]
+        );
+        assert_eq!(
+            sorted(OutputOrder::Position, outputs.clone()),
+            ["DP-2", "HDMI-A-1", "eDP-1", "DP-10"]
+        );
+        assert_eq!(
+            sorted(OutputOrder::Registry, outputs),
+            ["HDMI-A-1", "DP-10", "DP-2", "eDP-1"]
+        );
+        assert_eq!(natural_cmp("DP-02", "DP-2"), Ordering::Equal);
+    }
+}
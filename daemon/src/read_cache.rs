@@ -0,0 +1,70 @@
+//! Answers to the requests that only read our state, reused for a little while.
+//!
+//! Status bars and monitoring scripts may poll `swww query` many times a second. Building its
+//! answer locks every wallpaper, competing with the animation threads for them, so polling can
+//! make animations stutter. Within `FRESHNESS`, we answer with what we said last time instead.
+//! Anything that changes our state must call `invalidate`, so clients never see an answer older
+//! than their own changes.
+use std::time::{Duration, Instant};
+
+use common::ipc::BgInfo;
+
+/// How long an answer stays good for
+const FRESHNESS: Duration = Duration::from_millis(100);
+
+#[derive(Default)]
+pub(crate) struct ReadCache {
+    info: Option<(Instant, Box<[BgInfo]>)>,
+    /// we only keep `Ping`s saying every wallpaper is configured: clients waiting for them to be
+    /// should not be kept waiting any longer than necessary
+    configured: Option<Instant>,
+}
+
+impl ReadCache {
+    /// The answer to `Query`, from `compute` unless we have a fresh one
+    pub(crate) fn info(&mut self, compute: impl FnOnce() -> Box<[BgInfo]>) -> Box<[BgInfo]> {
+        match &self.info {
+            Some((at, info)) if at.elapsed() < FRESHNESS => info.clone(),
+            _ => {
+                let info = compute();
+                self.info = Some((Instant::now(), info.clone()));
+                info
+            }
+        }
+    }
+
+    /// The answer to `Ping`, from `compute` unless we have a fresh one
+    pub(crate) fn ping(&mut self, compute: impl FnOnce() -> bool) -> bool {
+        if self.configured.is_some_and(|at| at.elapsed() < FRESHNESS) {
+            return true;
+        }
+        let configured = compute();
+        self.configured = configured.then(Instant::now);
+        configured
+    }
+
+    pub(crate) fn invalidate(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_only_reuse_fresh_and_configured_answers() {
+        let mut cache = ReadCache::default();
+        assert!(!cache.ping(|| false));
+        assert!(cache.ping(|| true), "a negative ping was reused");
+        assert!(cache.ping(|| false), "a fresh ping was not reused");
+
+        assert!(cache.info(|| Box::new([])).is_empty());
+        cache.invalidate();
+        assert!(!cache.ping(|| false), "the cache was not invalidated");
+
+        cache.ping(|| true);
+        std::thread::sleep(FRESHNESS);
+        assert!(!cache.ping(|| false), "a stale ping was reused");
+    }
+}
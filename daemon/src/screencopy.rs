@@ -0,0 +1,345 @@
+//! Captures of what the outputs show, through wlr-screencopy.
+//!
+//! Until the first `swww img`, our surfaces have no buffer, so the compositor shows whatever is
+//! below them: its own background, or the wallpaper of whatever ran before us. With
+//! `--capture-first-frame`, we capture it when that first request comes, so that its transition
+//! starts from what was on screen instead of from black. The capture has the windows on the output
+//! too, which go away with the transition, so we do not even bind wlr-screencopy without the flag.
+//!
+//! The main thread receives the capture, while the transition thread waits for it (see
+//! `Wallpaper::draw_capture`).
+use std::{
+    sync::{Arc, Condvar, Mutex},
+    time::Duration,
+};
+
+use common::{ipc::PixelFormat, mmap::Mmap};
+use log::{debug, error};
+
+use crate::{
+    wallpaper::Wallpaper,
+    wayland::{
+        globals,
+        interfaces::{
+            wl_buffer, wl_registry, wl_shm, wl_shm_pool, zwlr_screencopy_frame_v1,
+            zwlr_screencopy_manager_v1,
+        },
+        ObjectId, WlDynObj,
+    },
+};
+
+/// What the compositor copied for us, in one of the `wl_shm` formats of `SUPPORTED_FORMATS`
+pub(crate) struct Captured {
+    pixels: Box<[u8]>,
+    format: u32,
+    width: u32,
+    height: u32,
+    stride: u32,
+    y_invert: bool,
+}
+
+/// The formats we can read captures in: 4 bytes per pixel, in either channel order
+const SUPPORTED_FORMATS: [u32; 4] = [
+    wl_shm::format::XRGB8888,
+    wl_shm::format::ARGB8888,
+    wl_shm::format::XBGR8888,
+    wl_shm::format::ABGR8888,
+];
+
+impl Captured {
+    /// The capture in `format`, resized to `dim` (nearest neighbor is enough for something we are
+    /// about to transition away from). `None` if it is empty
+    pub(crate) fn to_canvas(&self, format: PixelFormat, dim: (u32, u32)) -> Option<Box<[u8]>> {
+        let stride = self.stride as usize;
+        if self.width == 0
+            || self.height == 0
+            || stride < self.width as usize * 4
+            || self.pixels.len() < stride * self.height as usize
+        {
+            return None;
+        }
+        // in memory, the `*RGB8888` formats are little endian, so blue comes first. Our canvases
+        // have blue first when we must swap the channels of the images we get
+        let captured_bgr = matches!(
+            self.format,
+            wl_shm::format::XRGB8888 | wl_shm::format::ARGB8888
+        );
+        let swap = captured_bgr != format.must_swap_r_and_b_channels();
+        let channels = format.channels() as usize;
+        let (width, height) = (dim.0 as usize, dim.1 as usize);
+        let mut canvas = vec![0xFF; width * height * channels];
+        for (y, row) in canvas.chunks_exact_mut(width * channels).enumerate() {
+            let mut src_y = y * self.height as usize / height;
+            if self.y_invert {
+                src_y = self.height as usize - 1 - src_y;
+            }
+            let src_row = &self.pixels[src_y * stride..];
+            for (x, pixel) in row.chunks_exact_mut(channels).enumerate() {
+                let src_x = x * self.width as usize / width;
+                let src = &src_row[src_x * 4..src_x * 4 + 3];
+                pixel[..3].copy_from_slice(src);
+                if swap {
+                    pixel.swap(0, 2);
+                }
+            }
+        }
+        Some(canvas.into_boxed_slice())
+    }
+}
+
+/// A capture the transition thread waits on, while the main thread receives it
+#[derive(Default)]
+pub(crate) struct Capture {
+    /// `None` until the compositor is done, then what it captured, if it could
+    result: Mutex<Option<Option<Captured>>>,
+    cvar: Condvar,
+}
+
+impl Capture {
+    fn finish(&self, captured: Option<Captured>) {
+        *self.result.lock().unwrap() = Some(captured);
+        self.cvar.notify_all();
+    }
+
+    /// Waits up to `timeout` for the compositor. `None` if it failed, or took too long
+    pub(crate) fn wait(&self, timeout: Duration) -> Option<Captured> {
+        let result = self.result.lock().unwrap();
+        let (mut result, _) = self
+            .cvar
+            .wait_timeout_while(result, timeout, |result| result.is_none())
+            .unwrap();
+        result.take().flatten()
+    }
+}
+
+/// A capture in progress
+struct Frame {
+    id: ObjectId,
+    capture: Arc<Capture>,
+    /// the format, width, height and stride of the `wl_shm` buffer we copy to, once the
+    /// compositor offers one we support
+    buffer: Option<(u32, u32, u32, u32)>,
+    /// the memory and `wl_buffer` the compositor copies to
+    shm: Option<(Mmap, ObjectId)>,
+    y_invert: bool,
+}
+
+impl Frame {
+    /// Gives the compositor a buffer to copy to
+    fn copy(&mut self) -> Result<(), String> {
+        let Some((format, width, height, stride)) = self.buffer else {
+            return Err("the compositor offered no buffer format we support".to_string());
+        };
+        let len = stride as usize * height as usize;
+        let mmap =
+            Mmap::try_create(len).map_err(|e| format!("failed to allocate {len} bytes: {e}"))?;
+        let pool = globals::object_create(WlDynObj::ShmPool);
+        let buffer = globals::object_create(WlDynObj::Buffer);
+        let request = || -> rustix::io::Result<()> {
+            wl_shm::req::create_pool(pool, &mmap.fd(), len as i32)?;
+            wl_shm_pool::req::create_buffer(
+                pool,
+                buffer,
+                0,
+                width as i32,
+                height as i32,
+                stride as i32,
+                format,
+            )?;
+            // the buffer keeps the pool alive
+            wl_shm_pool::req::destroy(pool)?;
+            zwlr_screencopy_frame_v1::req::copy(self.id, buffer)
+        };
+        let res = request();
+        self.shm = Some((mmap, buffer));
+        res.map_err(|e| format!("failed to request the copy: {e}"))
+    }
+
+    /// Hands what we captured to the transition thread, if anything, and destroys our objects
+    fn finish(self, ready: bool) {
+        let captured = match (&self.shm, self.buffer) {
+            (Some((mmap, _)), Some((format, width, height, stride))) if ready => Some(Captured {
+                pixels: mmap.slice().into(),
+                format,
+                width,
+                height,
+                stride,
+                y_invert: self.y_invert,
+            }),
+            _ => None,
+        };
+        self.capture.finish(captured);
+        if let Some((_, buffer)) = self.shm {
+            if let Err(e) = wl_buffer::req::destroy(buffer) {
+                error!("failed to destroy wl_buffer: {e}");
+            }
+        }
+        if let Err(e) = zwlr_screencopy_frame_v1::req::destroy(self.id) {
+            error!("failed to destroy zwlr_screencopy_frame_v1: {e}");
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Screencopy {
+    /// name of the `zwlr_screencopy_manager_v1` global, and our object and its version, once bound
+    manager: Option<(u32, ObjectId, u32)>,
+    frames: Vec<Frame>,
+}
+
+impl Screencopy {
+    pub fn is_bound(&self) -> bool {
+        self.manager.is_some()
+    }
+
+    pub fn bind(&mut self, name: u32, version: u32) {
+        let id = globals::object_create(WlDynObj::ScreencopyManager);
+        // version 3 tells us when it is done offering buffer formats
+        let version = version.min(3);
+        if let Err(e) = wl_registry::req::bind(name, id, "zwlr_screencopy_manager_v1", version) {
+            error!("failed to bind zwlr_screencopy_manager_v1: {e}");
+            return;
+        }
+        debug!("binding wlr-screencopy to start the first transitions from what is on screen");
+        self.manager = Some((name, id, version));
+    }
+
+    pub fn global_remove(&mut self, name: u32) {
+        if self.manager.is_some_and(|(manager, _, _)| manager == name) {
+            self.manager = None;
+        }
+    }
+
+    /// Starts capturing the outputs of the `wallpapers` that have not shown anything yet
+    pub fn capture(&mut self, wallpapers: &[Arc<Wallpaper>]) {
+        let Some((_, manager, _)) = self.manager else {
+            return;
+        };
+        for wallpaper in wallpapers.iter().filter(|w| w.wants_capture()) {
+            let id = globals::object_create(WlDynObj::ScreencopyFrame);
+            if let Err(e) =
+                zwlr_screencopy_manager_v1::req::capture_output(manager, id, 0, wallpaper.output())
+            {
+                error!("failed to capture output {}: {e}", wallpaper.output_name());
+                continue;
+            }
+            let capture = Arc::new(Capture::default());
+            wallpaper.set_capture(Arc::clone(&capture));
+            self.frames.push(Frame {
+                id,
+                capture,
+                buffer: None,
+                shm: None,
+                y_invert: false,
+            });
+        }
+    }
+
+    fn frame(&mut self, id: ObjectId) -> Option<&mut Frame> {
+        self.frames.iter_mut().find(|frame| frame.id == id)
+    }
+
+    fn finish(&mut self, id: ObjectId, ready: bool) {
+        if let Some(i) = self.frames.iter().position(|frame| frame.id == id) {
+            self.frames.swap_remove(i).finish(ready);
+        }
+    }
+
+    fn copy(&mut self, id: ObjectId) {
+        let Some(frame) = self.frame(id) else {
+            return;
+        };
+        if let Err(e) = frame.copy() {
+            error!("failed to capture output: {e}");
+            self.finish(id, false);
+        }
+    }
+}
+
+impl zwlr_screencopy_frame_v1::EvHandler for Screencopy {
+    fn buffer(&mut self, sender_id: ObjectId, format: u32, width: u32, height: u32, stride: u32) {
+        let version = self.manager.map_or(0, |(_, _, version)| version);
+        let Some(frame) = self.frame(sender_id) else {
+            return;
+        };
+        if frame.buffer.is_none() && SUPPORTED_FORMATS.contains(&format) {
+            frame.buffer = Some((format, width, height, stride));
+        }
+        // before version 3, this is the only buffer we are offered
+        if version < 3 {
+            self.copy(sender_id);
+        }
+    }
+
+    fn flags(&mut self, sender_id: ObjectId, flags: u32) {
+        if let Some(frame) = self.frame(sender_id) {
+            frame.y_invert = flags & zwlr_screencopy_frame_v1::flags::Y_INVERT != 0;
+        }
+    }
+
+    fn ready(&mut self, sender_id: ObjectId, _: u32, _: u32, _: u32) {
+        self.finish(sender_id, true);
+    }
+
+    fn failed(&mut self, sender_id: ObjectId) {
+        debug!("the compositor failed to capture an output");
+        self.finish(sender_id, false);
+    }
+
+    fn damage(&mut self, _: ObjectId, _: u32, _: u32, _: u32, _: u32) {}
+
+    fn linux_dmabuf(&mut self, _: ObjectId, _: u32, _: u32, _: u32) {}
+
+    fn buffer_done(&mut self, sender_id: ObjectId) {
+        self.copy(sender_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn captured(format: u32, y_invert: bool) -> Captured {
+        // 2x2, with a padding pixel at the end of each row
+        #[rustfmt::skip]
+        let pixels = [
+            1, 2, 3, 0, 4, 5, 6, 0, 0, 0, 0, 0,
+            7, 8, 9, 0, 10, 11, 12, 0, 0, 0, 0, 0,
+        ];
+        Captured {
+            pixels: pixels.into(),
+            format,
+            width: 2,
+            height: 2,
+            stride: 12,
+            y_invert,
+        }
+    }
+
+    #[test]
+    fn captures_should_keep_the_channel_order_of_the_same_format() {
+        let canvas = captured(wl_shm::format::XRGB8888, false).to_canvas(PixelFormat::Xrgb, (2, 2));
+        assert_eq!(
+            canvas.as_deref(),
+            Some(&[1, 2, 3, 0xFF, 4, 5, 6, 0xFF, 7, 8, 9, 0xFF, 10, 11, 12, 0xFF][..])
+        );
+    }
+
+    #[test]
+    fn captures_should_swap_channels_and_flip() {
+        let canvas = captured(wl_shm::format::XRGB8888, true).to_canvas(PixelFormat::Bgr, (2, 2));
+        assert_eq!(
+            canvas.as_deref(),
+            Some(&[9, 8, 7, 12, 11, 10, 3, 2, 1, 6, 5, 4][..])
+        );
+    }
+
+    #[test]
+    fn captures_should_be_resized() {
+        let canvas = captured(wl_shm::format::XBGR8888, false).to_canvas(PixelFormat::Xbgr, (4, 1));
+        assert_eq!(
+            canvas.as_deref(),
+            Some(&[1, 2, 3, 0xFF, 1, 2, 3, 0xFF, 4, 5, 6, 0xFF, 4, 5, 6, 0xFF][..])
+        );
+    }
+}
@@ -0,0 +1,117 @@
+//! How we wait for the next frame of transitions and animations.
+//!
+//! `thread::sleep` usually wakes up a little late, so by default we sleep until shortly before
+//! the deadline and spin for the rest, like the `spin_sleep` crate on crates.io. How long "shortly
+//! before" should be depends on the scheduler, so unless told otherwise we measure it at startup.
+
+use std::{
+    sync::OnceLock,
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::debug;
+
+/// what we spin for when we have not calibrated (yet)
+const DEFAULT_ACCURACY: Duration = Duration::from_micros(125);
+const MIN_ACCURACY: Duration = Duration::from_micros(10);
+const MAX_ACCURACY: Duration = Duration::from_millis(5);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Strategy {
+    /// only `thread::sleep`, which uses the least cpu but might miss the deadline
+    Sleep,
+    /// sleep until this long before the deadline, and spin for the rest. `None` means we measure
+    /// it when initializing
+    Hybrid(Option<Duration>),
+    /// spin the whole time, for the most precise timing
+    Spin,
+}
+
+static STRATEGY: OnceLock<Strategy> = OnceLock::new();
+
+impl Strategy {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "sleep" => Some(Self::Sleep),
+            "hybrid" => Some(Self::Hybrid(None)),
+            "spin" => Some(Self::Spin),
+            _ => None,
+        }
+    }
+}
+
+/// Sets how `spin_sleep` waits, calibrating it if needed. Only the first call does anything
+pub fn init(strategy: Strategy) {
+    let strategy = match strategy {
+        Strategy::Hybrid(None) => {
+            let accuracy = calibrate();
+            debug!("calibrated sleeping: spinning for the last {accuracy:?}");
+            Strategy::Hybrid(Some(accuracy))
+        }
+        strategy => strategy,
+    };
+    let _ = STRATEGY.set(strategy);
+}
+
+/// Measures how late `thread::sleep` wakes up, keeping the worst of a few tries, plus a margin for
+/// the outliers we did not see
+fn calibrate() -> Duration {
+    const TRIES: usize = 16;
+    const NAP: Duration = Duration::from_millis(1);
+    let overshoot = (0..TRIES)
+        .map(|_| {
+            let start = Instant::now();
+            thread::sleep(NAP);
+            start.elapsed().saturating_sub(NAP)
+        })
+        .max()
+        .unwrap_or(DEFAULT_ACCURACY);
+    (overshoot + overshoot / 4).clamp(MIN_ACCURACY, MAX_ACCURACY)
+}
+
+/// Sleeps for `duration`, the way `init` was told to
+pub fn spin_sleep(duration: Duration) {
+    let strategy = STRATEGY
+        .get()
+        .copied()
+        .unwrap_or(Strategy::Hybrid(Some(DEFAULT_ACCURACY)));
+    sleep_with(strategy, duration);
+}
+
+fn sleep_with(strategy: Strategy, duration: Duration) {
+    let start = Instant::now();
+    let accuracy = match strategy {
+        Strategy::Sleep => return thread::sleep(duration),
+        Strategy::Hybrid(accuracy) => accuracy.unwrap_or(DEFAULT_ACCURACY),
+        Strategy::Spin => Duration::ZERO,
+    };
+    if duration > accuracy {
+        thread::sleep(duration - accuracy);
+    }
+
+    while start.elapsed() < duration {
+        thread::yield_now();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_strategy_should_sleep_long_enough() {
+        let duration = Duration::from_millis(2);
+        for strategy in [
+            Strategy::Sleep,
+            Strategy::Hybrid(None),
+            Strategy::Hybrid(Some(Duration::from_millis(1))),
+            Strategy::Spin,
+        ] {
+            let start = Instant::now();
+            sleep_with(strategy, duration);
+            assert!(start.elapsed() >= duration, "{strategy:?}");
+        }
+        assert!((MIN_ACCURACY..=MAX_ACCURACY).contains(&calibrate()));
+    }
+}
@@ -0,0 +1,115 @@
+//! The transition defaults set through `swww transition-defaults`.
+//!
+//! Clients mark the parts of each transition the user did not set (see `Transition::defaulted`),
+//! and we fill them in with the defaults, so `swww img` does not have to ask us for them every
+//! time. The values are also kept as the `transition.<name>` variables, for
+//! `swww transition-defaults show`.
+use std::{borrow::Cow, collections::HashMap, sync::Arc};
+
+use common::ipc::{transition_default_bit, ImgReq, Transition, TransitionDefaultsReq};
+use log::debug;
+
+/// The grayscale mask of the `mask` default, which the 'luma' transition follows
+pub(crate) struct Mask {
+    dim: (u32, u32),
+    bytes: Box<[u8]>,
+}
+
+impl Mask {
+    /// Scaled and cropped to `dim`, the way clients resize the masks they send us
+    fn resize(&self, dim: (u32, u32)) -> Box<[u8]> {
+        let mut mask = vec![0; dim.0 as usize * dim.1 as usize].into_boxed_slice();
+        swww_render::compose::resample_cropped(
+            &self.bytes,
+            (self.dim.0 as usize, self.dim.1 as usize),
+            &mut mask,
+            (dim.0 as usize, dim.1 as usize),
+            1,
+        );
+        mask
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct TransitionDefaults {
+    /// holds the value of every default in `set`
+    transition: Option<Transition>,
+    /// the defaults that are set, as bits of `Transition::defaulted`
+    set: u64,
+    mask: Option<Arc<Mask>>,
+}
+
+impl TransitionDefaults {
+    /// Changes the defaults in `req`, also storing them in `vars`
+    pub(crate) fn update(
+        &mut self,
+        req: TransitionDefaultsReq,
+        vars: &mut HashMap<String, String>,
+    ) {
+        let TransitionDefaultsReq {
+            values,
+            transition,
+            mask,
+        } = req;
+        for (name, value) in values.iter() {
+            let (name, value) = (name.str(), value.str());
+            debug!("setting transition default {name} = {value}");
+            vars.insert(format!("transition.{name}"), value.to_string());
+            let bit = transition_default_bit(name);
+            if value.is_empty() {
+                self.set &= !bit;
+            } else {
+                self.set |= bit;
+                if let Some(defaults) = &mut self.transition {
+                    defaults.copy_default(&transition, name);
+                }
+            }
+            if name == "mask" {
+                self.mask = mask.as_ref().map(|(dim, bytes)| {
+                    Arc::new(Mask {
+                        dim: *dim,
+                        bytes: bytes.bytes().into(),
+                    })
+                });
+            }
+        }
+        self.transition.get_or_insert(*transition);
+    }
+
+    /// Fills in the defaults `transition` did not set. Returns the `mask` default, if it should
+    /// follow it instead of its image's mask
+    pub(crate) fn apply(&self, transition: &mut Transition) -> Option<Arc<Mask>> {
+        if let Some(defaults) = &self.transition {
+            transition.apply_defaults(defaults, self.set);
+        }
+        let use_mask = transition.defaulted & self.set & transition_default_bit("mask") != 0;
+        self.mask.clone().filter(|_| use_mask)
+    }
+}
+
+/// The mask the 'luma' transition of `img` follows: `default` (see `TransitionDefaults::apply`),
+/// the one the client sent, or the brightness of the image itself. Other transitions need none
+pub(crate) fn luma_mask<'a>(
+    img: &'a ImgReq,
+    transition: &Transition,
+    default: Option<&Mask>,
+) -> Option<Cow<'a, [u8]>> {
+    if !matches!(
+        transition.transition_type,
+        common::ipc::TransitionType::Luma
+    ) {
+        return None;
+    }
+    Some(match (default, &img.mask) {
+        (Some(default), _) => Cow::Owned(default.resize(img.dim).into()),
+        (None, Some(mask)) => Cow::Borrowed(mask.bytes()),
+        (None, None) => Cow::Owned(
+            swww_render::compose::luma_mask(
+                img.img.bytes(),
+                img.format.channels() as usize,
+                img.format.must_swap_r_and_b_channels(),
+            )
+            .into(),
+        ),
+    })
+}
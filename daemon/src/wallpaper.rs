@@ -1,19 +1,24 @@
-use common::ipc::{BgImg, BgInfo, Scale};
+use common::ipc::{BgImg, BgInfo, Scale, SurfaceInfo};
 use log::{debug, error, warn};
 
 use std::{
+    cell::RefCell,
     num::NonZeroI32,
     sync::{
         atomic::{AtomicBool, AtomicUsize, Ordering},
         Arc, Condvar, Mutex, RwLock,
     },
+    time::{Duration, Instant},
 };
 
+use crate::filter::Lut;
+use crate::screencopy::Capture;
 use crate::wayland::{
     bump_pool::BumpPool,
     globals,
     interfaces::{
-        wl_output, wl_surface, wp_fractional_scale_v1, wp_viewport, zwlr_layer_surface_v1,
+        wl_compositor, wl_output, wl_region, wl_surface, wp_fractional_scale_v1, wp_viewport,
+        zwlr_layer_surface_v1, zxdg_output_v1,
     },
     ObjectId, WlDynObj,
 };
@@ -35,23 +40,125 @@ struct FrameCallbackHandler {
 }
 
 impl FrameCallbackHandler {
-    fn new(surface: ObjectId) -> Self {
+    fn new(surface: ObjectId) -> (Self, rustix::io::Result<()>) {
         let callback = globals::object_create(WlDynObj::Callback);
-        wl_surface::req::frame(surface, callback).unwrap();
-        FrameCallbackHandler {
+        let res = wl_surface::req::frame(surface, callback);
+        let handler = FrameCallbackHandler {
             cvar: Condvar::new(),
             done: Mutex::new(true), // we do not have to wait for the first frame
             callback: Mutex::new(callback),
-        }
+        };
+        (handler, res)
     }
 
-    fn request_frame_callback(&self, surface: ObjectId) {
+    fn request_frame_callback(&self, surface: ObjectId) -> rustix::io::Result<()> {
         let callback = globals::object_create(WlDynObj::Callback);
-        wl_surface::req::frame(surface, callback).unwrap();
+        wl_surface::req::frame(surface, callback)?;
         *self.callback.lock().unwrap() = callback;
+        Ok(())
+    }
+}
+
+/// A filter that is currently being applied to a wallpaper
+struct Filter {
+    lut: Arc<Lut>,
+    /// the canvas contents before filtering. All drawing happens here, and the result is then
+    /// passed through the lut onto the real canvas
+    source: Box<[u8]>,
+    /// the size of the source, in pixels
+    dim: (i32, i32),
+}
+
+impl Filter {
+    /// Resamples the source to `dim`, in case the canvas was resized since we last drew to it.
+    /// The canvas itself is no use then, since it was already filtered
+    fn fit(&mut self, dim: (i32, i32)) {
+        if self.dim == dim {
+            return;
+        }
+        let channels = globals::pixel_format().channels() as usize;
+        let mut source = vec![0; dim.0 as usize * dim.1 as usize * channels];
+        swww_render::compose::resample(
+            &self.source,
+            (self.dim.0 as usize, self.dim.1 as usize),
+            &mut source,
+            (dim.0 as usize, dim.1 as usize),
+            channels,
+        );
+        self.source = source.into();
+        self.dim = dim;
+    }
+}
+
+/// What we show, already scaled to the size the output is about to change to (see
+/// `Wallpaper::prepare_mode`)
+struct Prescaled {
+    /// the canvas dimensions it was scaled to, untransformed
+    dim: (i32, i32),
+    canvas: Box<[u8]>,
+}
+
+/// Set when we render with a `wl_surface.set_buffer_transform`, usually because the output is
+/// rotated and the compositor would rather not rotate our buffers itself
+struct BufferTransform {
+    transform: u32,
+    /// the canvas contents before transforming. All drawing happens here (after the filter, if
+    /// there is one), and the result is then transformed onto the real canvas
+    source: Box<[u8]>,
+}
+
+impl BufferTransform {
+    /// `dim` is the size of the source, in pixels
+    fn apply(&self, dim: (i32, i32), canvas: &mut [u8]) {
+        let channels = globals::pixel_format().channels() as usize;
+        let dim = (dim.0 as usize, dim.1 as usize);
+        // the pool is resized right after our dimensions change, so they might not match for a
+        // moment
+        if dim.0 * dim.1 * channels != canvas.len() || self.source.len() != canvas.len() {
+            return;
+        }
+        swww_render::compose::transform(
+            &self.source,
+            dim,
+            canvas,
+            channels,
+            (self.transform % 4) as u8,
+            self.transform >= wl_output::transform::FLIPPED,
+        );
+    }
+}
+
+/// Calls `f` with what we draw to before the buffer transform (or the canvas itself, if there
+/// is none), then transforms it onto the canvas
+fn with_untransformed<T>(
+    transform: &mut Option<BufferTransform>,
+    canvas: &mut [u8],
+    dim: (i32, i32),
+    f: impl FnOnce(&mut [u8]) -> T,
+) -> T {
+    match transform.as_mut() {
+        None => f(canvas),
+        Some(transform) => {
+            // the pool was resized
+            if transform.source.len() != canvas.len() {
+                transform.source = (&*canvas).into();
+            }
+            let ret = f(&mut transform.source);
+            transform.apply(dim, canvas);
+            ret
+        }
     }
 }
 
+/// whether a `wl_output.transform` rotates by 90 or 270 degrees, swapping width and height
+fn swaps_dimensions(transform: u32) -> bool {
+    use wl_output::transform;
+    matches!(
+        transform,
+        transform::_90 | transform::_270 | transform::FLIPPED_90 | transform::FLIPPED_270
+    )
+}
+
 /// Owns all the necessary information for drawing.
 #[derive(Clone, Debug)]
 struct WallpaperInner {
@@ -59,8 +166,53 @@ struct WallpaperInner {
     desc: Option<String>,
     width: NonZeroI32,
     height: NonZeroI32,
+    /// the output's current mode, in pixels. For rotated outputs, it is only rotated along with
+    /// `width` and `height` in `commit_surface_changes`
+    mode: Option<(NonZeroI32, NonZeroI32)>,
     scale_factor: Scale,
     transform: u32,
+    /// the transform we render with, from `wl_surface.preferred_buffer_transform`. It is applied
+    /// right away, without going through `commit_surface_changes`
+    buffer_transform: u32,
+    /// when set, we render at this size (in pixels) and let the compositor upscale it
+    render_size: Option<(NonZeroI32, NonZeroI32)>,
+    /// where the output is in the compositor's layout, in logical pixels
+    position: (i32, i32),
+}
+
+impl WallpaperInner {
+    /// the output's resolution, in pixels
+    ///
+    /// We use the mode when we have it, because with fractional scaling, multiplying the logical
+    /// size by the scale might be off by a pixel, and compositors can only scan out buffers that
+    /// match the mode exactly
+    fn native_dim(&self) -> (i32, i32) {
+        match self.mode {
+            Some((width, height)) => (width.get(), height.get()),
+            None => self
+                .scale_factor
+                .mul_dim(self.width.get(), self.height.get()),
+        }
+    }
+
+    /// the size of what we draw, in pixels
+    fn canvas_dim(&self) -> (i32, i32) {
+        match self.render_size {
+            Some((width, height)) => (width.get(), height.get()),
+            None => self.native_dim(),
+        }
+    }
+
+    /// the size of our buffers, in pixels. Same as `canvas_dim`, unless the buffer transform
+    /// rotates them
+    fn buffer_dim(&self) -> (i32, i32) {
+        let (width, height) = self.canvas_dim();
+        if swaps_dimensions(self.buffer_transform) {
+            (height, width)
+        } else {
+            (width, height)
+        }
+    }
 }
 
 impl Default for WallpaperInner {
@@ -70,15 +222,37 @@ impl Default for WallpaperInner {
             desc: None,
             width: unsafe { NonZeroI32::new_unchecked(4) },
             height: unsafe { NonZeroI32::new_unchecked(4) },
+            mode: None,
             scale_factor: Scale::Whole(unsafe { NonZeroI32::new_unchecked(1) }),
             transform: wl_output::transform::NORMAL,
+            buffer_transform: wl_output::transform::NORMAL,
+            render_size: None,
+            position: (0, 0),
         }
     }
 }
 
+/// What we show on an output once we know its name, before any client tells us what to
+pub(crate) enum Restore {
+    Nothing,
+    /// the last image `swww img` showed on it
+    Cache,
+    /// the image from the greeter configuration
+    Greeter(Arc<crate::greeter::Config>),
+    /// the image the greeter showed, and then the last image `swww img` showed, if `cache`
+    Handoff {
+        greeter: Arc<crate::greeter::Config>,
+        cache: bool,
+    },
+}
+
 pub(super) struct Wallpaper {
     output: ObjectId,
     output_name: u32,
+    output_version: u32,
+    /// tells us where the output is in the compositor's layout, and the names of outputs older
+    /// than version 4 of `wl_output`
+    xdg_output: Option<ObjectId>,
     wl_surface: ObjectId,
     wp_viewport: ObjectId,
     #[allow(unused)]
@@ -90,44 +264,84 @@ pub(super) struct Wallpaper {
 
     animation_state: AnimationState,
     pub configured: AtomicBool,
+    /// set when a request for one of our objects failed. The daemon then replaces us with a new
+    /// wallpaper for the same output
+    broken: AtomicBool,
+    /// when we created the layer surface, to tell when the compositor is taking too long to
+    /// configure it (see `Wallpaper::check_configure_timeout`)
+    created: Instant,
 
     frame_callback_handler: FrameCallbackHandler,
     img: Mutex<BgImg>,
+    filter: Mutex<Option<Filter>>,
+    buffer_transform: Mutex<Option<BufferTransform>>,
     pool: Mutex<BumpPool>,
+    /// why we cannot draw to this output, if we cannot. Cleared when the output changes size
+    error: Mutex<Option<String>>,
+    /// so that we can show what we showed as soon as the output changes size
+    prescaled: Mutex<Option<Prescaled>>,
+    /// whether we ever attached a buffer. Until then, the compositor shows what is below us
+    shown: AtomicBool,
+    /// what the output showed before we drew anything, while it is being captured (see
+    /// `screencopy`)
+    capture: Mutex<Option<Arc<Capture>>>,
 }
 
 impl Wallpaper {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
         output: ObjectId,
         output_name: u32,
+        output_version: u32,
+        xdg_output: Option<ObjectId>,
         wl_surface: ObjectId,
         wp_viewport: ObjectId,
         wp_fractional: Option<ObjectId>,
         layer_surface: ObjectId,
+        pool: BumpPool,
+        accept_pointer: bool,
+        size: Option<(u32, u32)>,
     ) -> Self {
         let inner = RwLock::default();
         let inner_staging = Mutex::default();
 
         // Configure the layer surface
-        zwlr_layer_surface_v1::req::set_anchor(layer_surface, 15).unwrap();
-        zwlr_layer_surface_v1::req::set_exclusive_zone(layer_surface, -1).unwrap();
-        zwlr_layer_surface_v1::req::set_margin(layer_surface, 0, 0, 0, 0).unwrap();
-        zwlr_layer_surface_v1::req::set_keyboard_interactivity(
-            layer_surface,
-            zwlr_layer_surface_v1::keyboard_interactivity::NONE,
-        )
-        .unwrap();
-        wl_surface::req::set_buffer_scale(wl_surface, 1).unwrap();
-
-        let frame_callback_handler = FrameCallbackHandler::new(wl_surface);
-        // commit so that the compositor send the initial configuration
-        wl_surface::req::commit(wl_surface).unwrap();
+        let (frame_callback_handler, res) = FrameCallbackHandler::new(wl_surface);
+        let configure = || -> rustix::io::Result<()> {
+            res?;
+            zwlr_layer_surface_v1::req::set_anchor(layer_surface, 15)?;
+            // by default, the anchors let the compositor pick the size
+            if let Some((width, height)) = size {
+                zwlr_layer_surface_v1::req::set_size(layer_surface, width, height)?;
+            }
+            zwlr_layer_surface_v1::req::set_exclusive_zone(layer_surface, -1)?;
+            zwlr_layer_surface_v1::req::set_margin(layer_surface, 0, 0, 0, 0)?;
+            zwlr_layer_surface_v1::req::set_keyboard_interactivity(
+                layer_surface,
+                zwlr_layer_surface_v1::keyboard_interactivity::NONE,
+            )?;
+            wl_surface::req::set_buffer_scale(wl_surface, 1)?;
+            if !accept_pointer {
+                set_empty_input_region(wl_surface)?;
+            }
 
-        let pool = Mutex::new(BumpPool::new(256, 256));
+            // commit so that the compositor send the initial configuration
+            wl_surface::req::commit(wl_surface)
+        };
+        let broken = match configure() {
+            Ok(()) => false,
+            Err(e) => {
+                error!("failed to configure the surface of output {output_name}: {e}");
+                check_disconnect(e);
+                true
+            }
+        };
 
         Self {
             output,
             output_name,
+            output_version,
+            xdg_output,
             wl_surface,
             wp_viewport,
             wp_fractional,
@@ -138,9 +352,17 @@ impl Wallpaper {
                 id: AtomicUsize::new(0),
             },
             configured: AtomicBool::new(false),
+            broken: AtomicBool::new(broken),
+            created: Instant::now(),
             frame_callback_handler,
             img: Mutex::new(BgImg::Color([0, 0, 0])),
-            pool,
+            filter: Mutex::new(None),
+            buffer_transform: Mutex::new(None),
+            pool: Mutex::new(pool),
+            error: Mutex::new(None),
+            prescaled: Mutex::new(None),
+            shown: AtomicBool::new(false),
+            capture: Mutex::new(None),
         }
     }
 
@@ -149,9 +371,30 @@ impl Wallpaper {
         BgInfo {
             name: inner.name.clone().unwrap_or("?".to_string()),
             dim: (inner.width.get() as u32, inner.height.get() as u32),
+            native_dim: {
+                let (width, height) = inner.native_dim();
+                (width as u32, height as u32)
+            },
             scale_factor: inner.scale_factor,
             img: self.img.lock().unwrap().clone(),
             pixel_format: globals::pixel_format(),
+            error: self.error.lock().unwrap().clone(),
+        }
+    }
+
+    /// What our surface is waiting on. `animating` is whether an animation holds us
+    pub fn get_surface_info(&self, animating: bool) -> SurfaceInfo {
+        let name = self.name().unwrap_or("?".to_string());
+        let pool = self.pool.lock().unwrap();
+        SurfaceInfo {
+            name,
+            configured: self.configured.load(Ordering::Acquire),
+            draw_ready: *self.frame_callback_handler.done.lock().unwrap(),
+            frame_callback: self.frame_callback_handler.callback.lock().unwrap().get(),
+            animating,
+            buffers: pool.buffer_states(),
+            committed: pool.get_commitable_buffer().map(|b| b.get()),
+            error: self.error.lock().unwrap().clone(),
         }
     }
 
@@ -160,6 +403,18 @@ impl Wallpaper {
         self.inner_staging.lock().unwrap().name = Some(name);
     }
 
+    /// Outputs older than version 4 of `wl_output` that have no xdg-output either never tell us
+    /// their name, so we name them after their make and model instead. Identical monitors end up
+    /// with the same name, and are picked together by `--outputs`
+    pub fn set_make_and_model(&self, make: &str, model: &str) {
+        if self.output_version >= 4 || self.xdg_output.is_some() {
+            return;
+        }
+        if let Some(name) = name_from_make_and_model(make, model) {
+            self.set_name(name);
+        }
+    }
+
     pub fn set_desc(&self, desc: String) {
         debug!("Output {} description: {desc}", self.output_name);
         self.inner_staging.lock().unwrap().desc = Some(desc)
@@ -167,6 +422,10 @@ impl Wallpaper {
 
     pub fn set_dimensions(&self, width: i32, height: i32) {
         let mut lock = self.inner_staging.lock().unwrap();
+        match (NonZeroI32::new(width), NonZeroI32::new(height)) {
+            (Some(width), Some(height)) => lock.mode = Some((width, height)),
+            _ => error!("received mode with a width or height of 0: {width}x{height}"),
+        }
         let (width, height) = lock.scale_factor.div_dim(width, height);
 
         match NonZeroI32::new(width) {
@@ -190,6 +449,14 @@ impl Wallpaper {
         }
     }
 
+    /// `wl_output.geometry` also tells us the position, but we prefer xdg-output's when we have
+    /// it, which is in logical pixels even on older compositors
+    pub fn set_position(&self, x: i32, y: i32, from_xdg_output: bool) {
+        if from_xdg_output || self.xdg_output.is_none() {
+            self.inner_staging.lock().unwrap().position = (x, y);
+        }
+    }
+
     pub fn set_transform(&self, transform: u32) {
         self.inner_staging.lock().unwrap().transform = transform;
     }
@@ -227,80 +494,453 @@ impl Wallpaper {
         }
     }
 
-    pub fn commit_surface_changes(&self, use_cache: bool) {
-        use wl_output::transform;
+    pub fn commit_surface_changes(&self, restore: &Restore) {
         let mut inner = self.inner.write().unwrap();
         let staging = self.inner_staging.lock().unwrap();
 
-        if inner.name != staging.name && use_cache {
+        if inner.name != staging.name {
             let name = staging.name.clone().unwrap_or("".to_string());
-            std::thread::Builder::new()
-                .name("cache loader".to_string())
-                .stack_size(1 << 14)
-                .spawn(move || {
-                    if let Err(e) = common::cache::load(&name) {
-                        warn!("failed to load cache: {e}");
-                    }
-                })
-                .unwrap(); // builder only fails if `name` contains null bytes
-        }
-
-        let (width, height) = if matches!(
-            staging.transform,
-            transform::_90 | transform::_270 | transform::FLIPPED_90 | transform::FLIPPED_270
-        ) {
-            (staging.height, staging.width)
-        } else {
-            (staging.width, staging.height)
-        };
-
-        if staging.scale_factor != inner.scale_factor || staging.transform != inner.transform {
-            match staging.scale_factor {
-                Scale::Whole(i) => {
-                    // unset destination
-                    wp_viewport::req::set_destination(self.wp_viewport, -1, -1).unwrap();
-                    wl_surface::req::set_buffer_scale(self.wl_surface, i.get()).unwrap();
+            match restore {
+                Restore::Nothing => (),
+                Restore::Cache => {
+                    std::thread::Builder::new()
+                        .name("cache loader".to_string())
+                        .stack_size(1 << 14)
+                        .spawn(move || {
+                            if let Err(e) = common::cache::load(&name) {
+                                warn!("failed to load cache: {e}");
+                            }
+                        })
+                        .unwrap(); // builder only fails if `name` contains null bytes
+                }
+                Restore::Greeter(config) => {
+                    let config = Arc::clone(config);
+                    std::thread::Builder::new()
+                        .name("greeter loader".to_string())
+                        .stack_size(1 << 14)
+                        .spawn(move || {
+                            if let Err(e) = config.show(&name) {
+                                warn!("failed to show the greeter image: {e}");
+                            }
+                        })
+                        .unwrap(); // builder only fails if `name` contains null bytes
                 }
-                Scale::Fractional(_) => {
-                    wl_surface::req::set_buffer_scale(self.wl_surface, 1).unwrap();
-                    wp_viewport::req::set_destination(self.wp_viewport, width.get(), height.get())
-                        .unwrap();
+                &Restore::Handoff { ref greeter, cache } => {
+                    let greeter = Arc::clone(greeter);
+                    std::thread::Builder::new()
+                        .name("handoff loader".to_string())
+                        .stack_size(1 << 14)
+                        .spawn(move || {
+                            if let Err(e) = greeter.hand_off(&name, cache) {
+                                warn!("failed to hand the greeter's image off: {e}");
+                            }
+                        })
+                        .unwrap(); // builder only fails if `name` contains null bytes
                 }
             }
         }
 
+        let ((width, height), mode) = if swaps_dimensions(staging.transform) {
+            (
+                (staging.height, staging.width),
+                staging.mode.map(|(w, h)| (h, w)),
+            )
+        } else {
+            ((staging.width, staging.height), staging.mode)
+        };
+
+        let viewport_changed =
+            staging.scale_factor != inner.scale_factor || staging.transform != inner.transform;
         inner.scale_factor = staging.scale_factor;
         inner.transform = staging.transform;
         inner.name.clone_from(&staging.name);
         inner.desc.clone_from(&staging.desc);
-        if (inner.width, inner.height) == (width, height) {
+        inner.position = staging.position;
+        if (inner.width, inner.height, inner.mode) == (width, height, mode) {
+            if viewport_changed {
+                self.set_viewport(&inner);
+                self.log_scanout(&inner);
+            }
             return;
         }
         self.stop_animations();
         inner.width = width;
         inner.height = height;
+        inner.mode = mode;
+        inner.render_size = None;
+        // the viewport also depends on whether the mode is a whole multiple of the logical size
+        self.set_viewport(&inner);
+        self.log_scanout(&inner);
 
-        let scale_factor = staging.scale_factor;
+        let buffer_dim = inner.buffer_dim();
         drop(inner);
         drop(staging);
 
-        zwlr_layer_surface_v1::req::set_size(
-            self.layer_surface,
-            width.get() as u32,
-            height.get() as u32,
-        )
-        .unwrap();
+        self.check(
+            "zwlr_layer_surface_v1.set_size",
+            zwlr_layer_surface_v1::req::set_size(
+                self.layer_surface,
+                width.get() as u32,
+                height.get() as u32,
+            ),
+        );
+        // we never draw anything transparent, so the compositor does not have to draw what is
+        // below us
+        self.check(
+            "wl_surface.set_opaque_region",
+            set_opaque_region(self.wl_surface, width.get(), height.get()),
+        );
 
-        let (w, h) = scale_factor.mul_dim(width.get(), height.get());
-        self.pool.lock().unwrap().resize(w, h);
+        self.pool.lock().unwrap().resize(buffer_dim.0, buffer_dim.1);
+        // maybe we can allocate buffers for the new size
+        *self.error.lock().unwrap() = None;
+
+        // show what we showed before right away, instead of letting the compositor stretch it
+        let prescaled = self.prescaled.lock().unwrap().take();
+        if let Some(prescaled) = prescaled {
+            let canvas_dim = self.inner.read().unwrap().canvas_dim();
+            if prescaled.dim == canvas_dim {
+                self.canvas_change(|canvas| canvas.copy_from_slice(&prescaled.canvas));
+                if let Some(buffer) = self.pool.lock().unwrap().get_commitable_buffer() {
+                    self.check(
+                        "wl_surface.attach",
+                        wl_surface::req::attach(self.wl_surface, Some(buffer), 0, 0),
+                    );
+                    self.check(
+                        "wl_surface.damage_buffer",
+                        wl_surface::req::damage_buffer(
+                            self.wl_surface,
+                            0,
+                            0,
+                            buffer_dim.0,
+                            buffer_dim.1,
+                        ),
+                    );
+                }
+            }
+        }
 
-        self.frame_callback_handler
-            .request_frame_callback(self.wl_surface);
-        wl_surface::req::commit(self.wl_surface).unwrap();
+        self.check(
+            "wl_surface.frame",
+            self.frame_callback_handler
+                .request_frame_callback(self.wl_surface),
+        );
+        self.check(
+            "wl_surface.commit",
+            wl_surface::req::commit(self.wl_surface),
+        );
         self.configured
             .store(true, std::sync::atomic::Ordering::Release);
     }
 
+    /// sets the buffer scale and viewport destination, according to the scale factor and whether
+    /// we are rendering at a reduced size
+    fn set_viewport(&self, inner: &WallpaperInner) {
+        let whole_multiple = inner.render_size.is_none()
+            && inner.native_dim()
+                == inner
+                    .scale_factor
+                    .mul_dim(inner.width.get(), inner.height.get());
+        let res = match inner.scale_factor {
+            Scale::Whole(i) if whole_multiple => {
+                // unset destination
+                wp_viewport::req::set_destination(self.wp_viewport, -1, -1)
+                    .and_then(|()| wl_surface::req::set_buffer_scale(self.wl_surface, i.get()))
+            }
+            _ => wl_surface::req::set_buffer_scale(self.wl_surface, 1).and_then(|()| {
+                wp_viewport::req::set_destination(
+                    self.wp_viewport,
+                    inner.width.get(),
+                    inner.height.get(),
+                )
+            }),
+        };
+        self.check("wp_viewport.set_destination", res);
+    }
+
+    /// Logs whether the compositor could put our buffers on screen directly ("direct scanout"),
+    /// as far as we can tell: it is up to the compositor in the end
+    fn log_scanout(&self, inner: &WallpaperInner) {
+        if !log::log_enabled!(log::Level::Debug) {
+            return;
+        }
+        let buffer = inner.buffer_dim();
+        let mode = inner.native_dim();
+        // the mode is already rotated, but the buffers are not if we use the buffer transform
+        let mode = if swaps_dimensions(inner.buffer_transform) {
+            (mode.1, mode.0)
+        } else {
+            mode
+        };
+        let mut problems = Vec::new();
+        if inner.mode.is_none() {
+            problems.push("unknown mode".to_string());
+        }
+        if buffer != mode {
+            problems.push(format!(
+                "{}x{} buffers for a {}x{} mode",
+                buffer.0, buffer.1, mode.0, mode.1
+            ));
+        }
+        if inner.buffer_transform != inner.transform {
+            problems.push(format!(
+                "buffer transform {} for an output transform {}",
+                inner.buffer_transform, inner.transform
+            ));
+        }
+        if problems.is_empty() {
+            debug!(
+                "Output {}: {}x{} buffers, suitable for direct scanout",
+                self.output_name, buffer.0, buffer.1
+            );
+        } else {
+            debug!(
+                "Output {}: not suitable for direct scanout: {}",
+                self.output_name,
+                problems.join(", ")
+            );
+        }
+    }
+
+    /// Renders everything at `size` (in pixels) instead of at the output's resolution, letting
+    /// the compositor upscale it. `None` goes back to the output's resolution
+    ///
+    /// The current content is rescaled to the new size, but nothing is committed
+    pub(super) fn set_render_size(&self, size: Option<(u32, u32)>) {
+        let mut inner = self.inner.write().unwrap();
+        let native = inner.native_dim();
+        let size = size
+            .filter(|&(w, h)| (w as i32, h as i32) != native)
+            .and_then(|(w, h)| Some((NonZeroI32::new(w as i32)?, NonZeroI32::new(h as i32)?)));
+        if inner.render_size == size {
+            return;
+        }
+        let old_dim = inner.canvas_dim();
+        inner.render_size = size;
+        let new_dim = inner.canvas_dim();
+        let buffer_dim = inner.buffer_dim();
+        self.set_viewport(&inner);
+        self.log_scanout(&inner);
+        drop(inner);
+
+        let channels = globals::pixel_format().channels() as usize;
+        let mut filter = self.filter.lock().unwrap();
+        let mut transform = self.buffer_transform.lock().unwrap();
+        let mut pool = self.pool.lock().unwrap();
+        let canvas = match pool.get_drawable() {
+            Ok(canvas) => canvas,
+            Err(e) => return self.set_error(e),
+        };
+        let new = with_untransformed(&mut transform, canvas, old_dim, |canvas| {
+            let old = match filter.as_mut() {
+                Some(filter) => {
+                    filter.fit(old_dim);
+                    &filter.source
+                }
+                None => &*canvas,
+            };
+            let mut new = vec![0; new_dim.0 as usize * new_dim.1 as usize * channels];
+            swww_render::compose::resample(
+                old,
+                (old_dim.0 as usize, old_dim.1 as usize),
+                &mut new,
+                (new_dim.0 as usize, new_dim.1 as usize),
+                channels,
+            );
+            new
+        });
+
+        pool.resize(buffer_dim.0, buffer_dim.1);
+        let canvas = match pool.get_drawable() {
+            Ok(canvas) => canvas,
+            Err(e) => return self.set_error(e),
+        };
+        with_untransformed(&mut transform, canvas, new_dim, |canvas| {
+            match filter.as_mut() {
+                Some(filter) => {
+                    filter
+                        .lut
+                        .apply(&new, canvas, globals::pixel_format(), new_dim.0 as usize);
+                    filter.source = new.into();
+                    filter.dim = new_dim;
+                }
+                None => canvas.copy_from_slice(&new),
+            }
+        });
+    }
+
+    /// Gets ready for the output to change to a mode of `width`x`height` pixels, with `transform`
+    /// (a `wl_output.transform`), before it tells us it did: we allocate the memory for the new
+    /// buffers and scale what we show to the new size
+    pub(super) fn prepare_mode(&self, width: i32, height: i32, transform: u32) {
+        // we learn our first size from the layer surface, like always
+        if !self.configured.load(Ordering::Acquire) {
+            return;
+        }
+        let new_dim = if swaps_dimensions(transform) {
+            (height, width)
+        } else {
+            (width, height)
+        };
+        let (old_dim, buffer_dim) = {
+            let inner = self.inner.read().unwrap();
+            if inner.native_dim() == new_dim || width <= 0 || height <= 0 {
+                return;
+            }
+            let buffer_dim = if swaps_dimensions(inner.buffer_transform) {
+                (new_dim.1, new_dim.0)
+            } else {
+                new_dim
+            };
+            (inner.canvas_dim(), buffer_dim)
+        };
+        debug!(
+            "Output {} is changing to {}x{}, preparing for it",
+            self.output_name, new_dim.0, new_dim.1
+        );
+
+        if let Err(e) = self
+            .pool
+            .lock()
+            .unwrap()
+            .reserve(buffer_dim.0, buffer_dim.1)
+        {
+            warn!("failed to allocate buffers for the next mode: {e}");
+            return;
+        }
+
+        let channels = globals::pixel_format().channels() as usize;
+        let prescaled = self.canvas_change_if_different(|canvas| {
+            let mut new = vec![0; new_dim.0 as usize * new_dim.1 as usize * channels];
+            swww_render::compose::resample(
+                canvas,
+                (old_dim.0 as usize, old_dim.1 as usize),
+                &mut new,
+                (new_dim.0 as usize, new_dim.1 as usize),
+                channels,
+            );
+            *self.prescaled.lock().unwrap() = Some(Prescaled {
+                dim: new_dim,
+                canvas: new.into(),
+            });
+            Ok(false)
+        });
+        if prescaled.is_none() {
+            *self.prescaled.lock().unwrap() = None;
+        }
+    }
+
+    /// Renders with `transform` (a `wl_output.transform`), which the compositor told us it
+    /// prefers for our surface. For rotated outputs, this lets it use our buffers as they are,
+    /// instead of rotating them every frame
+    ///
+    /// The current content is transformed accordingly, but nothing is committed
+    pub(super) fn set_buffer_transform(&self, transform: u32) {
+        let mut inner = self.inner.write().unwrap();
+        if inner.buffer_transform == transform {
+            return;
+        }
+        inner.buffer_transform = transform;
+        let canvas_dim = inner.canvas_dim();
+        let buffer_dim = inner.buffer_dim();
+        self.log_scanout(&inner);
+        drop(inner);
+
+        self.check(
+            "wl_surface.set_buffer_transform",
+            wl_surface::req::set_buffer_transform(self.wl_surface, transform as i32),
+        );
+
+        let mut buffer_transform = self.buffer_transform.lock().unwrap();
+        let mut pool = self.pool.lock().unwrap();
+        let canvas = match pool.get_drawable() {
+            Ok(canvas) => canvas,
+            Err(e) => return self.set_error(e),
+        };
+        // what we have drawn so far, before any transform
+        let source: Box<[u8]> = match buffer_transform.take() {
+            Some(old) if old.source.len() == canvas.len() => old.source,
+            _ => (&*canvas).into(),
+        };
+
+        pool.resize(buffer_dim.0, buffer_dim.1);
+        let canvas = match pool.get_drawable() {
+            Ok(canvas) => canvas,
+            Err(e) => return self.set_error(e),
+        };
+        if transform == wl_output::transform::NORMAL {
+            if source.len() == canvas.len() {
+                canvas.copy_from_slice(&source);
+            }
+        } else {
+            let transform = BufferTransform { transform, source };
+            transform.apply(canvas_dim, canvas);
+            *buffer_transform = Some(transform);
+        }
+    }
+
+    pub(super) fn name(&self) -> Option<String> {
+        self.inner.read().unwrap().name.clone()
+    }
+
+    /// Sets the input region back to empty, for compositors that gave us pointer focus anyway
+    pub(super) fn reset_input_region(&self) {
+        let res = set_empty_input_region(self.wl_surface)
+            .and_then(|()| wl_surface::req::commit(self.wl_surface));
+        self.check("wl_surface.set_input_region", res);
+    }
+
+    /// Marks the wallpaper as broken if `res` is an error, so that the daemon rebuilds it
+    pub(super) fn check(&self, request: &str, res: rustix::io::Result<()>) {
+        if let Err(e) = res {
+            error!(
+                "{request} failed for output {}: {e}",
+                self.name().as_deref().unwrap_or("?")
+            );
+            check_disconnect(e);
+            self.broken.store(true, Ordering::Release);
+        }
+    }
+
+    pub(super) fn is_broken(&self) -> bool {
+        self.broken.load(Ordering::Acquire)
+    }
+
+    /// How long we still wait for the compositor to configure us, or `None` if it did
+    pub(super) fn configure_deadline(&self, timeout: Duration) -> Option<Duration> {
+        if self.configured.load(Ordering::Acquire) {
+            return None;
+        }
+        Some(timeout.saturating_sub(self.created.elapsed()))
+    }
+
+    /// Marks the wallpaper as broken if the compositor did not configure it within `timeout`, so
+    /// that the daemon rebuilds it. Returns whether it did
+    pub(super) fn check_configure_timeout(&self, timeout: Duration) -> bool {
+        if self.configure_deadline(timeout) != Some(Duration::ZERO) || self.is_broken() {
+            return false;
+        }
+        warn!(
+            "output {} was not configured {}s after we created its surface",
+            self.name().as_deref().unwrap_or("?"),
+            timeout.as_secs()
+        );
+        self.broken.store(true, Ordering::Release);
+        true
+    }
+
+    /// The output's size in logical pixels, if it told us, even if we did not apply it yet
+    pub(super) fn staged_size(&self) -> Option<(u32, u32)> {
+        let staging = self.inner_staging.lock().unwrap();
+        staging
+            .mode
+            .map(|_| (staging.width.get() as u32, staging.height.get() as u32))
+    }
+
+    pub(super) fn output_name(&self) -> u32 {
+        self.output_name
+    }
+
     pub(super) fn has_name(&self, name: &str) -> bool {
         match self.inner.read().unwrap().name.as_ref() {
             Some(n) => n == name,
@@ -308,10 +948,22 @@ impl Wallpaper {
         }
     }
 
+    pub(super) fn output_version(&self) -> u32 {
+        self.output_version
+    }
+
+    pub(super) fn output(&self) -> ObjectId {
+        self.output
+    }
+
     pub(super) fn has_output(&self, output: ObjectId) -> bool {
         self.output == output
     }
 
+    pub(super) fn has_xdg_output(&self, xdg_output: ObjectId) -> bool {
+        self.xdg_output == Some(xdg_output)
+    }
+
     pub(super) fn has_output_name(&self, name: u32) -> bool {
         self.output_name == name
     }
@@ -351,18 +1003,107 @@ impl Wallpaper {
     }
 
     pub(super) fn get_dimensions(&self) -> (u32, u32) {
-        let inner = self.inner.read().unwrap();
-        let dim = inner
-            .scale_factor
-            .mul_dim(inner.width.get(), inner.height.get());
+        let dim = self.inner.read().unwrap().native_dim();
         (dim.0 as u32, dim.1 as u32)
     }
 
-    pub(super) fn canvas_change<F, T>(&self, f: F) -> T
+    /// Where the output is in the compositor's layout, as (x, y, width, height) in logical pixels
+    pub(super) fn get_layout(&self) -> (i32, i32, i32, i32) {
+        let inner = self.inner.read().unwrap();
+        let (x, y) = inner.position;
+        (x, y, inner.width.get(), inner.height.get())
+    }
+
+    /// Calls `f` with the canvas, returning its result, or `None` if we could not get a canvas
+    /// (see `Wallpaper::error`)
+    pub(super) fn canvas_change<F, T>(&self, f: F) -> Option<T>
     where
         F: FnOnce(&mut [u8]) -> T,
     {
-        f(self.pool.lock().unwrap().get_drawable())
+        let dim = self.inner.read().unwrap().canvas_dim();
+        let mut filter = self.filter.lock().unwrap();
+        let mut transform = self.buffer_transform.lock().unwrap();
+        let mut pool = self.pool.lock().unwrap();
+        let canvas = match pool.get_drawable() {
+            Ok(canvas) => canvas,
+            Err(e) => {
+                self.set_error(e);
+                return None;
+            }
+        };
+        Some(with_untransformed(
+            &mut transform,
+            canvas,
+            dim,
+            |canvas| match filter.as_mut() {
+                None => f(canvas),
+                Some(filter) => {
+                    filter.fit(dim);
+                    let ret = f(&mut filter.source);
+                    filter.lut.apply(
+                        &filter.source,
+                        canvas,
+                        globals::pixel_format(),
+                        dim.0 as usize,
+                    );
+                    ret
+                }
+            },
+        ))
+    }
+
+    /// Like `canvas_change`, for when `f` tells whether it actually changed the canvas. If it did
+    /// not, we keep the buffer we already committed, so the wallpaper has nothing new to commit
+    pub(super) fn canvas_change_if_different<F>(&self, f: F) -> Option<Result<bool, String>>
+    where
+        F: FnOnce(&mut [u8]) -> Result<bool, String>,
+    {
+        let changed = self.canvas_change(f)?;
+        if let Ok(false) = changed {
+            self.pool.lock().unwrap().unget_drawable();
+        }
+        Some(changed)
+    }
+
+    fn set_error(&self, e: String) {
+        let mut error = self.error.lock().unwrap();
+        if error.as_ref() != Some(&e) {
+            error!(
+                "cannot draw to output {}: {e}",
+                self.name().as_deref().unwrap_or("?")
+            );
+            *error = Some(e);
+        }
+    }
+
+    /// Sets (or unsets) the filter applied to everything we draw, and redraws the canvas with it
+    ///
+    /// Note this does not commit the new canvas to the surface
+    pub(super) fn set_filter(&self, lut: Option<Arc<Lut>>) {
+        let dim = self.inner.read().unwrap().canvas_dim();
+        let mut filter = self.filter.lock().unwrap();
+        let mut transform = self.buffer_transform.lock().unwrap();
+        let mut pool = self.pool.lock().unwrap();
+        let canvas = match pool.get_drawable() {
+            Ok(canvas) => canvas,
+            Err(e) => return self.set_error(e),
+        };
+        with_untransformed(&mut transform, canvas, dim, |canvas| {
+            let source = match filter.take() {
+                Some(mut old) => {
+                    old.fit(dim);
+                    old.source
+                }
+                None => (&*canvas).into(),
+            };
+            match lut {
+                Some(lut) => {
+                    lut.apply(&source, canvas, globals::pixel_format(), dim.0 as usize);
+                    *filter = Some(Filter { lut, source, dim });
+                }
+                None => canvas.copy_from_slice(&source),
+            }
+        });
     }
 
     pub(super) fn create_animation_token(&self) -> AnimationToken {
@@ -379,12 +1120,45 @@ impl Wallpaper {
         self.animation_state.id.fetch_add(1, Ordering::AcqRel);
     }
 
+    /// Whether we should capture what the output shows before the first transition, because we
+    /// have not drawn anything on it yet. We do not rotate captures, so not for rotated outputs
+    pub(super) fn wants_capture(&self) -> bool {
+        self.configured.load(Ordering::Acquire)
+            && !self.shown.load(Ordering::Acquire)
+            && self.inner.read().unwrap().transform == wl_output::transform::NORMAL
+            && self.capture.lock().unwrap().is_none()
+    }
+
+    pub(super) fn set_capture(&self, capture: Arc<Capture>) {
+        *self.capture.lock().unwrap() = Some(capture);
+    }
+
+    /// Draws what the output showed before us, if we are capturing it, waiting up to `timeout`
+    /// for the compositor. Note this does not commit the new canvas to the surface
+    pub(super) fn draw_capture(&self, timeout: Duration) {
+        let Some(capture) = self.capture.lock().unwrap().take() else {
+            return;
+        };
+        let Some(captured) = capture.wait(timeout) else {
+            debug!(
+                "output {}: no capture of the screen, starting from black",
+                self.name().as_deref().unwrap_or("?")
+            );
+            return;
+        };
+        let (width, height) = self.inner.read().unwrap().canvas_dim();
+        let format = globals::pixel_format();
+        if let Some(pixels) = captured.to_canvas(format, (width as u32, height as u32)) {
+            self.canvas_change(|canvas| canvas.copy_from_slice(&pixels));
+        }
+    }
+
     pub(super) fn clear(&self, color: [u8; 3]) {
         self.canvas_change(|canvas| {
             for pixel in canvas.chunks_exact_mut(globals::pixel_format().channels().into()) {
                 pixel[0..3].copy_from_slice(&color);
             }
-        })
+        });
     }
 
     pub(super) fn set_img_info(&self, img_info: BgImg) {
@@ -397,6 +1171,11 @@ impl Wallpaper {
     }
 }
 
+thread_local! {
+    /// the requests we send every frame, kept so that animating does not allocate for them
+    static FRAME_MSG: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
 /// stops all animations for the passed wallpapers
 pub(crate) fn stop_animations(wallpapers: &[Arc<Wallpaper>]) {
     wallpapers
@@ -424,44 +1203,85 @@ pub(crate) fn attach_buffers_and_damange_surfaces(wallpapers: &[Arc<Wallpaper>])
         12, 0,                  // msg length
         0, 0, 0, 0,             // wl_callback object id (to be filled)
     ];
-    let msg: Box<[u8]> = wallpapers
-        .iter()
-        .flat_map(|wallpaper| {
-            let mut done = wallpaper.frame_callback_handler.done.lock().unwrap();
-            while !*done {
-                //debug!("waiting for frame callback");
-                done = wallpaper.frame_callback_handler.cvar.wait(done).unwrap();
-            }
-            *done = false;
-            drop(done);
+    FRAME_MSG.with_borrow_mut(|requests| {
+        requests.clear();
+        requests.extend(
+            wallpapers
+                .iter()
+                .filter_map(|wallpaper| {
+                    // wallpapers we could not draw to have nothing to attach
+                    let buf = wallpaper.pool.lock().unwrap().get_commitable_buffer()?;
+                    wallpaper.shown.store(true, Ordering::Release);
+                    let mut done = wallpaper.frame_callback_handler.done.lock().unwrap();
+                    while !*done {
+                        //debug!("waiting for frame callback");
+                        done = wallpaper.frame_callback_handler.cvar.wait(done).unwrap();
+                    }
+                    *done = false;
+                    drop(done);
 
-            let mut msg = MSG;
+                    let mut msg = MSG;
 
-            let buf = wallpaper.pool.lock().unwrap().get_commitable_buffer();
-            let inner = wallpaper.inner.read().unwrap();
-            let (width, height) = inner
-                .scale_factor
-                .mul_dim(inner.width.get(), inner.height.get());
-            drop(inner);
+                    let (width, height) = wallpaper.inner.read().unwrap().buffer_dim();
 
-            // attach
-            msg[0..4].copy_from_slice(&wallpaper.wl_surface.get().to_ne_bytes());
-            msg[8..12].copy_from_slice(&buf.get().to_ne_bytes());
-
-            //damage buffer
-            msg[20..24].copy_from_slice(&wallpaper.wl_surface.get().to_ne_bytes());
-            msg[36..40].copy_from_slice(&width.to_ne_bytes());
-            msg[40..44].copy_from_slice(&height.to_ne_bytes());
-
-            // frame callback
-            let callback = globals::object_create(WlDynObj::Callback);
-            *wallpaper.frame_callback_handler.callback.lock().unwrap() = callback;
-            msg[44..48].copy_from_slice(&wallpaper.wl_surface.get().to_ne_bytes());
-            msg[52..56].copy_from_slice(&callback.get().to_ne_bytes());
-            msg
-        })
-        .collect();
-    unsafe { crate::wayland::wire::send_unchecked(msg.as_ref(), &[]).unwrap() }
+                    // attach
+                    msg[0..4].copy_from_slice(&wallpaper.wl_surface.get().to_ne_bytes());
+                    msg[8..12].copy_from_slice(&buf.get().to_ne_bytes());
+
+                    //damage buffer
+                    msg[20..24].copy_from_slice(&wallpaper.wl_surface.get().to_ne_bytes());
+                    msg[36..40].copy_from_slice(&width.to_ne_bytes());
+                    msg[40..44].copy_from_slice(&height.to_ne_bytes());
+
+                    // frame callback
+                    let callback = globals::object_create(WlDynObj::Callback);
+                    *wallpaper.frame_callback_handler.callback.lock().unwrap() = callback;
+                    msg[44..48].copy_from_slice(&wallpaper.wl_surface.get().to_ne_bytes());
+                    msg[52..56].copy_from_slice(&callback.get().to_ne_bytes());
+                    Some(msg)
+                })
+                .flatten(),
+        );
+        let res = unsafe { crate::wayland::wire::send_unchecked(requests, &[]) };
+        for wallpaper in wallpapers {
+            wallpaper.check("wl_surface.attach", res);
+        }
+    })
+}
+
+/// makes the surface transparent to pointer and touch input. Takes effect on the next commit
+fn set_empty_input_region(wl_surface: ObjectId) -> rustix::io::Result<()> {
+    let region = globals::object_create(WlDynObj::Region);
+    wl_compositor::req::create_region(region)?;
+    wl_surface::req::set_input_region(wl_surface, Some(region))?;
+    wl_region::req::destroy(region)
+}
+
+/// Takes effect on the next commit
+fn set_opaque_region(wl_surface: ObjectId, width: i32, height: i32) -> rustix::io::Result<()> {
+    let region = globals::object_create(WlDynObj::Region);
+    wl_compositor::req::create_region(region)?;
+    wl_region::req::add(region, 0, 0, width, height)?;
+    wl_surface::req::set_opaque_region(wl_surface, Some(region))?;
+    wl_region::req::destroy(region)
+}
+
+/// If the compositor closed the connection, there is nothing left to rebuild, so we exit
+pub(crate) fn check_disconnect(e: rustix::io::Errno) {
+    if matches!(e, rustix::io::Errno::PIPE | rustix::io::Errno::CONNRESET) {
+        error!("lost the connection to the compositor");
+        crate::exit_daemon();
+    }
+}
+
+/// `--outputs` is a comma separated list, so names cannot have commas
+fn name_from_make_and_model(make: &str, model: &str) -> Option<String> {
+    let name = format!("{make} {model}")
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    (!name.is_empty()).then_some(name)
 }
 
 /// commits multiple wallpapers at once with a single message through the socket
@@ -473,21 +1293,32 @@ pub(crate) fn commit_wallpapers(wallpapers: &[Arc<Wallpaper>]) {
         6, 0,       // commit opcode
         8, 0,       // msg length
     ];
-    let msg: Box<[u8]> = wallpapers
-        .iter()
-        .flat_map(|wallpaper| {
+    FRAME_MSG.with_borrow_mut(|requests| {
+        requests.clear();
+        requests.extend(wallpapers.iter().flat_map(|wallpaper| {
             let mut msg = MSG;
             msg[0..4].copy_from_slice(&wallpaper.wl_surface.get().to_ne_bytes());
             msg
-        })
-        .collect();
-    unsafe { crate::wayland::wire::send_unchecked(msg.as_ref(), &[]).unwrap() }
+        }));
+        let res = unsafe { crate::wayland::wire::send_unchecked(requests, &[]) };
+        for wallpaper in wallpapers {
+            wallpaper.check("wl_surface.commit", res);
+        }
+    })
 }
 
 impl Drop for Wallpaper {
     fn drop(&mut self) {
         // note we shouldn't panic in a drop implementation
 
+        if let Some(xdg_output) = self.xdg_output {
+            if let Err(e) = zxdg_output_v1::req::destroy(xdg_output) {
+                error!("error destroying zxdg_output_v1: {e:?}");
+            }
+        }
+        if let Err(e) = wl_output::req::release(self.output) {
+            error!("error releasing wl_output: {e:?}");
+        }
         if let Err(e) = wp_viewport::req::destroy(self.wp_viewport) {
             error!("error destroying wp_viewport: {e:?}");
         }
@@ -516,3 +1347,21 @@ impl Drop for Wallpaper {
 
 unsafe impl Sync for Wallpaper {}
 unsafe impl Send for Wallpaper {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_from_make_and_model_should_work_with_outputs() {
+        assert_eq!(
+            name_from_make_and_model("Dell Inc.", "DELL U2720Q").as_deref(),
+            Some("Dell Inc. DELL U2720Q")
+        );
+        assert_eq!(
+            name_from_make_and_model(" Foo,Bar ", "  27\"\t").as_deref(),
+            Some("Foo Bar 27\"")
+        );
+        assert_eq!(name_from_make_and_model("", " "), None);
+    }
+}
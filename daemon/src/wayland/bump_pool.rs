@@ -35,18 +35,18 @@ impl Buffer {
         height: i32,
         stride: i32,
         format: u32,
-    ) -> Self {
+    ) -> Result<Self, String> {
         let released = ReleaseFlag(AtomicBool::new(true));
 
         let object_id = globals::object_create(super::WlDynObj::Buffer);
         super::interfaces::wl_shm_pool::req::create_buffer(
             pool_id, object_id, offset, width, height, stride, format,
         )
-        .expect("WlShmPool failed to create buffer");
-        Self {
+        .map_err(|e| format!("WlShmPool failed to create buffer: {e}"))?;
+        Ok(Self {
             object_id,
             released,
-        }
+        })
     }
 
     fn destroy(self) {
@@ -70,27 +70,31 @@ pub(crate) struct BumpPool {
     width: i32,
     height: i32,
     last_used_buffer: usize,
+    /// the last used buffer before the last call to `get_drawable`
+    previous_buffer: usize,
 }
 
 impl BumpPool {
     /// We assume `width` and `height` have already been multiplied by their scale factor
-    pub(crate) fn new(width: i32, height: i32) -> Self {
+    pub(crate) fn new(width: i32, height: i32) -> Result<Self, String> {
         let len =
             width as usize * height as usize * super::globals::pixel_format().channels() as usize;
-        let mmap = Mmap::create(len);
+        let mmap =
+            Mmap::try_create(len).map_err(|e| format!("failed to allocate {len} bytes: {e}"))?;
         let pool_id = globals::object_create(super::WlDynObj::ShmPool);
         super::interfaces::wl_shm::req::create_pool(pool_id, &mmap.fd(), len as i32)
-            .expect("failed to create WlShmPool object");
+            .map_err(|e| format!("failed to create WlShmPool object: {e}"))?;
         let buffers = Vec::with_capacity(2);
 
-        Self {
+        Ok(Self {
             pool_id,
             mmap,
             buffers,
             width,
             height,
             last_used_buffer: 0,
-        }
+            previous_buffer: 0,
+        })
     }
 
     /// Releases a buffer, if we have it
@@ -131,20 +135,22 @@ impl BumpPool {
     }
 
     /// resizes the pool and creates a new WlBuffer at the next free offset
-    fn grow(&mut self) {
+    fn grow(&mut self) -> Result<(), String> {
         let len = self.buffer_len();
         let new_len = self.occupied_bytes() + len;
 
-        // we unmap the shared memory file descriptor when animations are done, so here we must
-        // ensure the bytes are actually mmaped
-        self.mmap.ensure_mapped();
-
         if new_len > self.mmap.len() {
             if new_len > i32::MAX as usize {
-                panic!("Buffers have grown too big. We cannot allocate any more.")
+                return Err(format!(
+                    "buffers of {}x{} are too big. We cannot allocate any more",
+                    self.width, self.height
+                ));
+            }
+            if let Err(e) = self.mmap.try_remap(new_len) {
+                return Err(format!("failed to allocate {new_len} bytes: {e}"));
             }
-            self.mmap.remap(new_len);
-            super::interfaces::wl_shm_pool::req::resize(self.pool_id, new_len as i32).unwrap();
+            super::interfaces::wl_shm_pool::req::resize(self.pool_id, new_len as i32)
+                .map_err(|e| format!("failed to resize WlShmPool: {e}"))?;
         }
 
         let new_buffer_index = self.buffers.len();
@@ -155,19 +161,26 @@ impl BumpPool {
             self.height,
             self.width * super::globals::pixel_format().channels() as i32,
             super::globals::wl_shm_format(),
-        ));
+        )?);
 
         log::info!(
             "BumpPool with: {} buffers. Size: {}Kb",
             self.buffers.len(),
             self.mmap.len() / 1024
         );
+        Ok(())
     }
 
     /// Returns a drawable surface. If we can't find a free buffer, we request more memory
     ///
     /// This function automatically handles copying the previous buffer over onto the new one
-    pub(crate) fn get_drawable(&mut self) -> &mut [u8] {
+    pub(crate) fn get_drawable(&mut self) -> Result<&mut [u8], String> {
+        // we unmap the shared memory file descriptor when animations are done (or when growing
+        // it failed), so here we must ensure the bytes are actually mmaped
+        self.mmap
+            .try_ensure_mapped()
+            .map_err(|e| format!("failed to map {} bytes: {e}", self.mmap.len()))?;
+
         let (i, buf) = match self
             .buffers
             .iter()
@@ -176,7 +189,7 @@ impl BumpPool {
         {
             Some((i, buf)) => (i, buf),
             None => {
-                self.grow();
+                self.grow()?;
                 (self.buffers.len() - 1, self.buffers.last().unwrap())
             }
         };
@@ -185,6 +198,7 @@ impl BumpPool {
         let offset = self.buffer_offset(i);
         buf.released.unset_released();
 
+        self.previous_buffer = self.last_used_buffer;
         if self.last_used_buffer != i {
             let last_offset = self.buffer_offset(self.last_used_buffer);
             self.mmap
@@ -193,12 +207,50 @@ impl BumpPool {
             self.last_used_buffer = i;
         }
 
-        &mut self.mmap.slice_mut()[offset..offset + len]
+        Ok(&mut self.mmap.slice_mut()[offset..offset + len])
+    }
+
+    /// Gives back the buffer the last call to `get_drawable` returned, when we did not change it
+    /// after all, so that it is free again and we keep committing the previous one
+    pub(crate) fn unget_drawable(&mut self) {
+        if let Some(buf) = self.buffers.get(self.last_used_buffer) {
+            buf.released.set_released();
+        }
+        self.last_used_buffer = self.previous_buffer;
+    }
+
+    /// gets the last buffer we've drawn to, if we have drawn to any since the last resize
+    pub(crate) fn get_commitable_buffer(&self) -> Option<ObjectId> {
+        self.buffers.get(self.last_used_buffer).map(|b| b.object_id)
+    }
+
+    /// The object id of each buffer, and whether the compositor released it
+    pub(crate) fn buffer_states(&self) -> Box<[(u32, bool)]> {
+        self.buffers
+            .iter()
+            .map(|b| (b.object_id.get(), b.released.is_released()))
+            .collect()
     }
 
-    /// gets the last buffer we've drawn to
-    pub(crate) fn get_commitable_buffer(&self) -> ObjectId {
-        self.buffers[self.last_used_buffer].object_id
+    /// Grows the shared memory so that it fits two buffers of `width`x`height`, so that we do not
+    /// have to when we resize to them
+    pub(crate) fn reserve(&mut self, width: i32, height: i32) -> Result<(), String> {
+        let len =
+            2 * width as usize * height as usize * globals::pixel_format().channels() as usize;
+        if len <= self.mmap.len() {
+            return Ok(());
+        }
+        if len > i32::MAX as usize {
+            return Err(format!("buffers of {width}x{height} are too big"));
+        }
+        self.mmap
+            .try_ensure_mapped()
+            .map_err(|e| format!("failed to map {} bytes: {e}", self.mmap.len()))?;
+        self.mmap
+            .try_remap(len)
+            .map_err(|e| format!("failed to allocate {len} bytes: {e}"))?;
+        super::interfaces::wl_shm_pool::req::resize(self.pool_id, len as i32)
+            .map_err(|e| format!("failed to resize WlShmPool: {e}"))
     }
 
     /// We assume `width` and `height` have already been multiplied by their scale factor
@@ -206,6 +258,7 @@ impl BumpPool {
         self.width = width;
         self.height = height;
         self.last_used_buffer = 0;
+        self.previous_buffer = 0;
         for buffer in self.buffers.drain(..) {
             buffer.destroy();
         }
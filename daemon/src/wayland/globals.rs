@@ -25,6 +25,7 @@ use std::{
     num::NonZeroU32,
     path::PathBuf,
     sync::{atomic::AtomicBool, Mutex},
+    time::{Duration, Instant},
 };
 
 // all of these objects must always exist for `swww-daemon` to work correctly, so we turn them into
@@ -48,18 +49,23 @@ const REQUIRED_GLOBALS: [&str; 4] = [
 ];
 /// Minimal version necessary for `REQUIRED_GLOBALS`
 const VERSIONS: [u32; 4] = [4, 1, 1, 3];
+/// Version we bind `REQUIRED_GLOBALS` at, if the compositor supports it. wl_compositor version 6
+/// gives us `wl_surface.preferred_buffer_scale` and `wl_surface.preferred_buffer_transform`
+const MAX_VERSIONS: [u32; 4] = [6, 1, 1, 3];
 
 static mut WAYLAND_FD: OwnedFd = unsafe { std::mem::zeroed() };
 static mut FRACTIONAL_SCALE_SUPPORT: bool = false;
 static mut PIXEL_FORMAT: PixelFormat = PixelFormat::Xrgb;
-static mut OBJECT_MANAGER: Mutex<ObjectManager> = Mutex::new(ObjectManager::new());
+static OBJECT_MANAGER: Mutex<ObjectManager> = Mutex::new(ObjectManager::new());
+/// every interface we bound, with the version we last bound it at
+static BOUND: Mutex<Vec<(String, u32)>> = Mutex::new(Vec::new());
 
 static INITIALIZED: AtomicBool = AtomicBool::new(false);
 
 #[must_use]
 pub fn wayland_fd() -> BorrowedFd<'static> {
     debug_assert!(INITIALIZED.load(std::sync::atomic::Ordering::Relaxed));
-    unsafe { WAYLAND_FD.as_fd() }
+    unsafe { (*std::ptr::addr_of!(WAYLAND_FD)).as_fd() }
 }
 
 #[must_use]
@@ -70,20 +76,43 @@ pub fn fractional_scale_support() -> bool {
 #[must_use]
 pub fn object_type_get(object_id: ObjectId) -> Option<WlDynObj> {
     debug_assert!(INITIALIZED.load(std::sync::atomic::Ordering::Relaxed));
-    unsafe { OBJECT_MANAGER.lock() }.unwrap().get(object_id)
+    OBJECT_MANAGER.lock().unwrap().get(object_id)
 }
 
 #[must_use]
 pub fn object_create(object_type: WlDynObj) -> ObjectId {
     debug_assert!(INITIALIZED.load(std::sync::atomic::Ordering::Relaxed));
-    unsafe { OBJECT_MANAGER.lock() }
+    OBJECT_MANAGER.lock().unwrap().create(object_type)
+}
+
+/// registers an object the compositor created for us through an event
+pub fn object_insert_server(object_id: ObjectId, object_type: WlDynObj) {
+    debug_assert!(INITIALIZED.load(std::sync::atomic::Ordering::Relaxed));
+    OBJECT_MANAGER
+        .lock()
         .unwrap()
-        .create(object_type)
+        .insert_server(object_id, object_type)
 }
 
 pub fn object_remove(object_id: ObjectId) {
     debug_assert!(INITIALIZED.load(std::sync::atomic::Ordering::Relaxed));
-    unsafe { OBJECT_MANAGER.lock() }.unwrap().remove(object_id)
+    OBJECT_MANAGER.lock().unwrap().remove(object_id)
+}
+
+pub fn record_bound(interface: &str, version: u32) {
+    let mut bound = BOUND.lock().unwrap();
+    match bound.iter_mut().find(|(name, _)| name == interface) {
+        Some((_, v)) => *v = version,
+        None => bound.push((interface.to_string(), version)),
+    }
+}
+
+/// the interfaces we bound so far, with their versions, sorted by name
+#[must_use]
+pub fn bound_interfaces() -> Vec<(String, u32)> {
+    let mut bound = BOUND.lock().unwrap().clone();
+    bound.sort_unstable();
+    bound
 }
 
 #[must_use]
@@ -104,7 +133,10 @@ pub fn wl_shm_format() -> u32 {
 }
 
 /// Note that this function assumes the logger has already been set up
-pub fn init(pixel_format: Option<PixelFormat>) -> Initializer {
+///
+/// With `wait`, we keep trying to connect to the compositor for that long, for when we are
+/// started before it is ready
+pub fn init(pixel_format: Option<PixelFormat>, wait: Option<Duration>) -> Initializer {
     // if we have initialized already, return imediatelly with an empty Initializer
     let mut initializer = Initializer::new(pixel_format);
     if INITIALIZED.load(std::sync::atomic::Ordering::SeqCst) {
@@ -116,7 +148,7 @@ pub fn init(pixel_format: Option<PixelFormat>) -> Initializer {
     //   * the object manager
     // we optionally initialize the pixel_format, if necessary
     unsafe {
-        WAYLAND_FD = connect();
+        WAYLAND_FD = connect(wait.unwrap_or_default());
         if let Some(format) = pixel_format {
             info!("Forced usage of wl_shm format: {:?}", format);
             PIXEL_FORMAT = format;
@@ -162,7 +194,7 @@ pub fn init(pixel_format: Option<PixelFormat>) -> Initializer {
     for (i, name) in initializer.global_names.into_iter().enumerate() {
         let id = IDS[i];
         let interface = REQUIRED_GLOBALS[i];
-        let version = VERSIONS[i];
+        let version = initializer.global_versions[i].min(MAX_VERSIONS[i]);
         super::interfaces::wl_registry::req::bind(name, id, interface, version).unwrap();
     }
 
@@ -204,7 +236,7 @@ pub fn init(pixel_format: Option<PixelFormat>) -> Initializer {
 }
 
 /// copy-pasted from `wayland-client.rs`
-fn connect() -> OwnedFd {
+fn connect(wait: Duration) -> OwnedFd {
     if let Ok(txt) = std::env::var("WAYLAND_SOCKET") {
         // We should connect to the provided WAYLAND_SOCKET
         let fd = txt
@@ -247,16 +279,34 @@ fn connect() -> OwnedFd {
             socket_path
         };
 
-        std::os::unix::net::UnixStream::connect(socket_path)
-            .expect("failed to connect to socket")
-            .into()
+        let start = Instant::now();
+        loop {
+            match std::os::unix::net::UnixStream::connect(&socket_path) {
+                Ok(stream) => break stream.into(),
+                Err(e) if start.elapsed() < wait => {
+                    debug!("compositor not ready yet ({e}), retrying");
+                    std::thread::sleep(Duration::from_millis(100));
+                }
+                Err(e) => panic!("failed to connect to socket: {e}"),
+            }
+        }
     }
 }
 
 /// Helper struct to do all the initialization in this file
 pub struct Initializer {
     global_names: [u32; REQUIRED_GLOBALS.len()],
-    output_names: Vec<u32>,
+    global_versions: [u32; REQUIRED_GLOBALS.len()],
+    /// name and version of every `wl_output` global
+    outputs: Vec<(u32, u32)>,
+    /// name and version of every `wl_seat` global
+    seats: Vec<(u32, u32)>,
+    /// name and version of the `zxdg_output_manager_v1` global, if there is one
+    xdg_output_manager: Option<(u32, u32)>,
+    /// name and version of the `zwlr_output_manager_v1` global, if there is one
+    output_manager: Option<(u32, u32)>,
+    /// name and version of the `zwlr_screencopy_manager_v1` global, if there is one
+    screencopy_manager: Option<(u32, u32)>,
     fractional_scale: Option<(ObjectId, NonZeroU32)>,
     forced_shm_format: bool,
     should_exit: bool,
@@ -266,7 +316,12 @@ impl Initializer {
     fn new(cli_format: Option<PixelFormat>) -> Self {
         Self {
             global_names: [0; REQUIRED_GLOBALS.len()],
-            output_names: Vec::new(),
+            global_versions: [0; REQUIRED_GLOBALS.len()],
+            outputs: Vec::new(),
+            seats: Vec::new(),
+            xdg_output_manager: None,
+            output_manager: None,
+            screencopy_manager: None,
             fractional_scale: None,
             forced_shm_format: cli_format.is_some(),
             should_exit: false,
@@ -281,8 +336,24 @@ impl Initializer {
         }
     }
 
-    pub fn output_names(&self) -> &[u32] {
-        &self.output_names
+    pub fn outputs(&self) -> &[(u32, u32)] {
+        &self.outputs
+    }
+
+    pub fn seats(&self) -> &[(u32, u32)] {
+        &self.seats
+    }
+
+    pub fn xdg_output_manager(&self) -> Option<(u32, u32)> {
+        self.xdg_output_manager
+    }
+
+    pub fn output_manager(&self) -> Option<(u32, u32)> {
+        self.output_manager
+    }
+
+    pub fn screencopy_manager(&self) -> Option<(u32, u32)> {
+        self.screencopy_manager
     }
 
     pub fn fractional_scale(&self) -> Option<&(ObjectId, NonZeroU32)> {
@@ -326,12 +397,16 @@ impl super::interfaces::wl_registry::EvHandler for Initializer {
                 ));
             }
             "wl_output" => {
-                if version < 4 {
-                    error!("wl_output implementation must have at least version 4 for swww-daemon")
+                if version < 3 {
+                    error!("wl_output implementation must have at least version 3 for swww-daemon")
                 } else {
-                    self.output_names.push(name);
+                    self.outputs.push((name, version));
                 }
             }
+            "zxdg_output_manager_v1" => self.xdg_output_manager = Some((name, version)),
+            "zwlr_output_manager_v1" => self.output_manager = Some((name, version)),
+            "zwlr_screencopy_manager_v1" => self.screencopy_manager = Some((name, version)),
+            "wl_seat" => self.seats.push((name, version)),
             _ => {
                 for (i, global) in REQUIRED_GLOBALS.iter().enumerate() {
                     if *global == interface {
@@ -342,6 +417,7 @@ impl super::interfaces::wl_registry::EvHandler for Initializer {
                             );
                         }
                         self.global_names[i] = name;
+                        self.global_versions[i] = version;
                         break;
                     }
                 }
@@ -46,6 +46,15 @@ pub mod wl_display {
                     Some(super::super::WlDynObj::Callback) => "wl_callback",
                     Some(super::super::WlDynObj::Viewport) => "wl_viewport",
                     Some(super::super::WlDynObj::FractionalScale) => "wp_fractional_scale_v1",
+                    Some(super::super::WlDynObj::Seat) => "wl_seat",
+                    Some(super::super::WlDynObj::Pointer) => "wl_pointer",
+                    Some(super::super::WlDynObj::XdgOutputManager) => "zxdg_output_manager_v1",
+                    Some(super::super::WlDynObj::XdgOutput) => "zxdg_output_v1",
+                    Some(super::super::WlDynObj::OutputManager) => "zwlr_output_manager_v1",
+                    Some(super::super::WlDynObj::OutputHead) => "zwlr_output_head_v1",
+                    Some(super::super::WlDynObj::OutputMode) => "zwlr_output_mode_v1",
+                    Some(super::super::WlDynObj::ScreencopyManager) => "zwlr_screencopy_manager_v1",
+                    Some(super::super::WlDynObj::ScreencopyFrame) => "zwlr_screencopy_frame_v1",
                     None => "???",
                 },
             };
@@ -206,7 +215,9 @@ pub mod wl_registry {
             let mut wire_msg_builder = WireMsgBuilder::new(globals::WL_REGISTRY, 0);
             wire_msg_builder.add_u32(name);
             wire_msg_builder.add_new_unspecified_id(id, id_interface, id_version);
-            wire_msg_builder.send()
+            wire_msg_builder.send()?;
+            globals::record_bound(id_interface, id_version);
+            Ok(())
         }
     }
 }
@@ -1486,6 +1497,230 @@ pub mod wl_output {
         pub const PREFERRED: u32 = 2u32;
     }
 }
+///group of input devices
+///
+///A seat is a group of keyboards, pointer and touch devices. This
+///object is published as a global during start up, or when such a
+///device is hot plugged.  A seat typically has a pointer and
+///maintains a keyboard focus and a pointer focus.
+pub mod wl_seat {
+    use super::*;
+
+    pub trait EvHandler {
+        ///seat capabilities changed
+        ///
+        ///This is emitted whenever a seat gains or loses the pointer,
+        ///keyboard or touch capabilities.  The argument is a capability
+        ///enum containing the complete set of capabilities this seat has.
+        ///
+        ///When the pointer capability is added, a client may create a
+        ///wl_pointer object using the wl_seat.get_pointer request. This object
+        ///will receive pointer events until the capability is removed in the
+        ///future.
+        ///
+        ///When the pointer capability is removed, a client should destroy the
+        ///wl_pointer objects associated with the seat where the capability was
+        ///removed, using the wl_pointer.release request. No further pointer
+        ///events will be received on these objects.
+        fn capabilities(&mut self, sender_id: ObjectId, capabilities: u32);
+        ///unique identifier for this seat
+        ///
+        ///In a multi-seat configuration the seat name can be used by clients to
+        ///help identify which physical devices the seat represents.
+        ///
+        ///The seat name is a UTF-8 string with no convention defined for its
+        ///contents. Each name is unique among all wl_seat globals. The name is
+        ///only guaranteed to be unique for the current compositor instance.
+        fn name(&mut self, sender_id: ObjectId, name: &str);
+    }
+
+    pub fn event<T: EvHandler>(state: &mut T, mut wire_msg: WireMsg, payload: WaylandPayload) {
+        match wire_msg.op() {
+            0 => {
+                let capabilities = wire_msg.next_u32(&payload);
+                state.capabilities(wire_msg.sender_id(), capabilities);
+            }
+            1 => {
+                let name = wire_msg.next_string(&payload);
+                state.name(wire_msg.sender_id(), name);
+            }
+            e => log::error!("unrecognized event opcode: {e} for interface wl_seat"),
+        }
+    }
+
+    ///Requests for this interface
+    pub mod req {
+        use super::*;
+        ///return pointer object
+        ///
+        ///The ID provided will be initialized to the wl_pointer interface
+        ///for this seat.
+        ///
+        ///This request only takes effect if the seat has the pointer
+        ///capability, or has had the pointer capability in the past.
+        ///It is a protocol violation to issue this request on a seat that has
+        ///never had the pointer capability. The missing_capability error will
+        ///be sent in this case.
+        pub fn get_pointer(sender_id: ObjectId, id: ObjectId) -> rustix::io::Result<()> {
+            let mut wire_msg_builder = WireMsgBuilder::new(sender_id, 0u16);
+            wire_msg_builder.add_new_specified_id(id);
+            wire_msg_builder.send()
+        }
+        ///release the seat object
+        ///
+        ///Using this request a client can tell the server that it is not going to
+        ///use the seat object anymore.
+        ///
+        ///THIS IS A DESTRUCTOR
+        pub fn release(sender_id: ObjectId) -> rustix::io::Result<()> {
+            let wire_msg_builder = WireMsgBuilder::new(sender_id, 3u16);
+            wire_msg_builder.send()
+        }
+    }
+    ///seat capability bitmask
+    ///
+    ///This is a bitmask of capabilities this seat has; if a member is
+    ///set, then it is present on the seat.
+    pub mod capability {
+        ///the seat has pointer devices
+        pub const POINTER: u32 = 1u32;
+        ///the seat has one or more keyboards
+        pub const KEYBOARD: u32 = 2u32;
+        ///the seat has touch devices
+        pub const TOUCH: u32 = 4u32;
+    }
+}
+///pointer input device
+///
+///The wl_pointer interface represents one or more input devices,
+///such as mice, which control the pointer location and pointer_focus
+///of a seat.
+///
+///The wl_pointer interface generates motion, enter and leave
+///events for the surfaces that the pointer is located over,
+///and button and axis events for button presses, button releases
+///and scrolling.
+pub mod wl_pointer {
+    use super::*;
+
+    pub trait EvHandler {
+        ///enter event
+        ///
+        ///Notification that this seat's pointer is focused on a certain
+        ///surface.
+        ///
+        ///When a seat's focus enters a surface, the pointer image
+        ///is undefined and a client should respond to this event by setting
+        ///an appropriate pointer image with the set_cursor request.
+        fn enter(
+            &mut self,
+            sender_id: ObjectId,
+            serial: u32,
+            surface: Option<ObjectId>,
+            surface_x: WlFixed,
+            surface_y: WlFixed,
+        );
+        ///leave event
+        ///
+        ///Notification that this seat's pointer is no longer focused on
+        ///a certain surface.
+        ///
+        ///The leave notification is sent before the enter notification
+        ///for the new focus.
+        fn leave(&mut self, sender_id: ObjectId, serial: u32, surface: Option<ObjectId>);
+        ///pointer motion event
+        ///
+        ///Notification of pointer location change. The arguments
+        ///surface_x and surface_y are the location relative to the
+        ///focused surface.
+        fn motion(
+            &mut self,
+            sender_id: ObjectId,
+            time: u32,
+            surface_x: WlFixed,
+            surface_y: WlFixed,
+        );
+        ///pointer button event
+        ///
+        ///Mouse button click and release notifications.
+        ///
+        ///The location of the click is given by the last motion or
+        ///enter event.
+        fn button(&mut self, sender_id: ObjectId, serial: u32, time: u32, button: u32, state: u32);
+        ///axis event
+        ///
+        ///Scroll and other axis notifications.
+        fn axis(&mut self, sender_id: ObjectId, time: u32, axis: u32, value: WlFixed);
+    }
+
+    pub fn event<T: EvHandler>(state: &mut T, mut wire_msg: WireMsg, payload: WaylandPayload) {
+        match wire_msg.op() {
+            0 => {
+                let serial = wire_msg.next_u32(&payload);
+                let surface = wire_msg.next_object(&payload);
+                let surface_x = wire_msg.next_fixed(&payload);
+                let surface_y = wire_msg.next_fixed(&payload);
+                state.enter(wire_msg.sender_id(), serial, surface, surface_x, surface_y);
+            }
+            1 => {
+                let serial = wire_msg.next_u32(&payload);
+                let surface = wire_msg.next_object(&payload);
+                state.leave(wire_msg.sender_id(), serial, surface);
+            }
+            2 => {
+                let time = wire_msg.next_u32(&payload);
+                let surface_x = wire_msg.next_fixed(&payload);
+                let surface_y = wire_msg.next_fixed(&payload);
+                state.motion(wire_msg.sender_id(), time, surface_x, surface_y);
+            }
+            3 => {
+                let serial = wire_msg.next_u32(&payload);
+                let time = wire_msg.next_u32(&payload);
+                let button = wire_msg.next_u32(&payload);
+                let button_state = wire_msg.next_u32(&payload);
+                state.button(wire_msg.sender_id(), serial, time, button, button_state);
+            }
+            4 => {
+                let time = wire_msg.next_u32(&payload);
+                let axis = wire_msg.next_u32(&payload);
+                let value = wire_msg.next_fixed(&payload);
+                state.axis(wire_msg.sender_id(), time, axis, value);
+            }
+            // frame, axis_source, axis_stop and axis_discrete (version 5) only add precision to
+            // the events above, which we do not need
+            5..=8 => (),
+            e => log::error!("unrecognized event opcode: {e} for interface wl_pointer"),
+        }
+    }
+
+    ///Requests for this interface
+    pub mod req {
+        use super::*;
+        ///release the pointer object
+        ///
+        ///Using this request a client can tell the server that it is not going to
+        ///use the pointer object anymore.
+        ///
+        ///This request destroys the pointer proxy object, so clients must not call
+        ///wl_pointer_destroy() after using this request.
+        ///
+        ///THIS IS A DESTRUCTOR
+        pub fn release(sender_id: ObjectId) -> rustix::io::Result<()> {
+            let wire_msg_builder = WireMsgBuilder::new(sender_id, 1u16);
+            wire_msg_builder.send()
+        }
+    }
+    ///physical button state
+    ///
+    ///Describes the physical state of a button that produced the button
+    ///event.
+    pub mod button_state {
+        ///the button is not pressed
+        pub const RELEASED: u32 = 0u32;
+        ///the button is pressed
+        pub const PRESSED: u32 = 1u32;
+    }
+}
 ///region interface
 ///
 ///A region object describes an area.
@@ -1629,6 +1864,7 @@ pub mod wp_viewporter {
 /// 1. buffer_transform (wl_surface.set_buffer_transform)
 /// 2. buffer_scale (wl_surface.set_buffer_scale)
 /// 3. crop and scale (wp_viewport.set*)
+///
 ///This means, that the source rectangle coordinates of crop and scale
 ///are given in the coordinates after the buffer transform and scale,
 ///i.e. in the coordinates that would be the surface-local coordinates
@@ -2263,3 +2499,530 @@ pub mod zwlr_layer_surface_v1 {
         pub const RIGHT: u32 = 8u32;
     }
 }
+///manage xdg_output objects
+///
+///A global factory interface for xdg_output objects.
+///
+///We only use it to learn the names of outputs that are older than version 4 of wl_output.
+pub mod zxdg_output_manager_v1 {
+    use super::*;
+
+    ///Events for this interface
+    pub mod ev {}
+    ///Requests for this interface
+    pub mod req {
+        use super::*;
+        ///destroy the xdg_output_manager object
+        ///
+        ///Using this request a client can tell the server that it is not
+        ///going to use the xdg_output_manager object anymore.
+        ///
+        ///Any objects already created through this instance are not affected.
+        ///
+        ///THIS IS A DESTRUCTOR
+        pub fn destroy(sender_id: ObjectId) -> rustix::io::Result<()> {
+            let wire_msg_builder = WireMsgBuilder::new(sender_id, 0);
+            wire_msg_builder.send()
+        }
+        ///create an xdg output from a wl_output
+        ///
+        ///This creates a new xdg_output object for the given wl_output.
+        pub fn get_xdg_output(
+            sender_id: ObjectId,
+            id: ObjectId,
+            output: ObjectId,
+        ) -> rustix::io::Result<()> {
+            let mut wire_msg_builder = WireMsgBuilder::new(sender_id, 1);
+            wire_msg_builder.add_new_specified_id(id);
+            wire_msg_builder.add_object(Some(output));
+            wire_msg_builder.send()
+        }
+    }
+}
+///compositor logical output region
+///
+///An xdg_output describes part of the compositor geometry.
+///
+///This typically corresponds to a monitor that displays part of the
+///compositor space.
+pub mod zxdg_output_v1 {
+    use super::*;
+
+    pub trait EvHandler {
+        ///position of the output within the global compositor space
+        fn logical_position(&mut self, sender_id: ObjectId, x: i32, y: i32);
+        ///size of the output in the global compositor space
+        fn logical_size(&mut self, sender_id: ObjectId, width: i32, height: i32);
+        ///all information about the output have been sent
+        ///
+        ///This event is sent after all other properties of an xdg_output
+        ///have been sent. For objects version 3 or above, it is deprecated: the
+        ///compositor sends wl_output.done instead.
+        fn done(&mut self, sender_id: ObjectId);
+        ///name of this output
+        ///
+        ///Many compositors will assign names to their outputs, show them to the
+        ///user, allow them to be configured by name, etc. Only sent once, after
+        ///the xdg_output is created. Since version 2.
+        fn name(&mut self, sender_id: ObjectId, name: &str);
+        ///human-readable description of this output
+        ///
+        ///Since version 2.
+        fn description(&mut self, sender_id: ObjectId, description: &str);
+    }
+
+    pub fn event<T: EvHandler>(state: &mut T, mut wire_msg: WireMsg, payload: WaylandPayload) {
+        match wire_msg.op() {
+            0 => {
+                let x = wire_msg.next_i32(&payload);
+                let y = wire_msg.next_i32(&payload);
+                state.logical_position(wire_msg.sender_id(), x, y);
+            }
+            1 => {
+                let width = wire_msg.next_i32(&payload);
+                let height = wire_msg.next_i32(&payload);
+                state.logical_size(wire_msg.sender_id(), width, height);
+            }
+            2 => {
+                state.done(wire_msg.sender_id());
+            }
+            3 => {
+                let name = wire_msg.next_string(&payload);
+                state.name(wire_msg.sender_id(), name);
+            }
+            4 => {
+                let description = wire_msg.next_string(&payload);
+                state.description(wire_msg.sender_id(), description);
+            }
+            e => log::error!("unrecognized event opcode: {e} for interface zxdg_output_v1"),
+        }
+    }
+
+    ///Requests for this interface
+    pub mod req {
+        use super::*;
+        ///destroy the xdg_output object
+        ///
+        ///Using this request a client can tell the server that it is not
+        ///going to use the xdg_output object anymore.
+        ///
+        ///THIS IS A DESTRUCTOR
+        pub fn destroy(sender_id: ObjectId) -> rustix::io::Result<()> {
+            let wire_msg_builder = WireMsgBuilder::new(sender_id, 0);
+            wire_msg_builder.send()
+        }
+    }
+}
+///list and configure wlr_output_management
+///
+///This interface is a manager that allows reading and writing the current output device
+///configuration.
+///
+///We only read it, to learn about mode changes before the outputs themselves tell us about them.
+pub mod zwlr_output_manager_v1 {
+    use super::*;
+
+    pub trait EvHandler {
+        ///introduce a new head
+        ///
+        ///This event introduces a new head. This happens whenever a new head appears (e.g. a
+        ///monitor is plugged in) or after the output manager is bound.
+        fn head(&mut self, sender_id: ObjectId, head: ObjectId);
+        ///sent all information about current configuration
+        ///
+        ///This event is sent after all information has been sent after binding to the output
+        ///manager object and after any subsequent changes. This applies to child head and mode
+        ///objects as well. In other words, this event is sent whenever a head or mode is
+        ///created or destroyed and whenever one of their properties has been changed.
+        fn done(&mut self, sender_id: ObjectId, serial: u32);
+        ///the compositor has finished with the manager
+        ///
+        ///This event indicates that the compositor is done sending manager events. The
+        ///compositor will destroy the object immediately after sending this event.
+        fn finished(&mut self, sender_id: ObjectId);
+    }
+
+    pub fn event<T: EvHandler>(state: &mut T, mut wire_msg: WireMsg, payload: WaylandPayload) {
+        match wire_msg.op() {
+            0 => {
+                let head = wire_msg.next_new_specified_id(&payload);
+                state.head(wire_msg.sender_id(), head);
+            }
+            1 => {
+                let serial = wire_msg.next_u32(&payload);
+                state.done(wire_msg.sender_id(), serial);
+            }
+            2 => {
+                state.finished(wire_msg.sender_id());
+            }
+            e => log::error!("unrecognized event opcode: {e} for interface zwlr_output_manager_v1"),
+        }
+    }
+
+    ///Requests for this interface
+    pub mod req {
+        use super::*;
+        ///stop sending events
+        ///
+        ///Indicates the client no longer wishes to receive events for output configuration
+        ///changes. However the compositor may emit further events, until the finished event is
+        ///emitted.
+        pub fn stop(sender_id: ObjectId) -> rustix::io::Result<()> {
+            let wire_msg_builder = WireMsgBuilder::new(sender_id, 1);
+            wire_msg_builder.send()
+        }
+    }
+}
+///output device
+///
+///A head is an output device. The difference between a wl_output object and a head is that
+///heads are advertised even if they are turned off. A head object only advertises properties
+///and cannot be used directly to change them.
+pub mod zwlr_output_head_v1 {
+    use super::*;
+
+    pub trait EvHandler {
+        ///head name
+        ///
+        ///This event describes the head name. It is the same as `wl_output.name`.
+        fn name(&mut self, sender_id: ObjectId, name: &str);
+        ///head description
+        fn description(&mut self, sender_id: ObjectId, description: &str);
+        ///head physical size, in millimeters
+        fn physical_size(&mut self, sender_id: ObjectId, width: i32, height: i32);
+        ///introduce a mode
+        ///
+        ///This event introduces a mode for this head. It is sent once per supported mode.
+        fn mode(&mut self, sender_id: ObjectId, mode: ObjectId);
+        ///head is enabled or disabled
+        fn enabled(&mut self, sender_id: ObjectId, enabled: i32);
+        ///current mode
+        ///
+        ///This event describes the mode currently in use for this head. It is only sent if the
+        ///output is enabled.
+        fn current_mode(&mut self, sender_id: ObjectId, mode: Option<ObjectId>);
+        ///current position, in the global compositor space
+        fn position(&mut self, sender_id: ObjectId, x: i32, y: i32);
+        ///current transformation, as a `wl_output.transform`
+        fn transform(&mut self, sender_id: ObjectId, transform: i32);
+        ///current scale
+        fn scale(&mut self, sender_id: ObjectId, scale: WlFixed);
+        ///the head has disappeared
+        ///
+        ///This event indicates that the head is no longer available. The head object becomes
+        ///inert. Clients should send a destroy request and release any resources associated with
+        ///it.
+        fn finished(&mut self, sender_id: ObjectId);
+        ///head manufacturer. Since version 2.
+        fn make(&mut self, sender_id: ObjectId, make: &str);
+        ///head model. Since version 2.
+        fn model(&mut self, sender_id: ObjectId, model: &str);
+        ///head serial number. Since version 2.
+        fn serial_number(&mut self, sender_id: ObjectId, serial_number: &str);
+        ///current adaptive sync state. Since version 4.
+        fn adaptive_sync(&mut self, sender_id: ObjectId, state: u32);
+    }
+
+    pub fn event<T: EvHandler>(state: &mut T, mut wire_msg: WireMsg, payload: WaylandPayload) {
+        match wire_msg.op() {
+            0 => {
+                let name = wire_msg.next_string(&payload);
+                state.name(wire_msg.sender_id(), name);
+            }
+            1 => {
+                let description = wire_msg.next_string(&payload);
+                state.description(wire_msg.sender_id(), description);
+            }
+            2 => {
+                let width = wire_msg.next_i32(&payload);
+                let height = wire_msg.next_i32(&payload);
+                state.physical_size(wire_msg.sender_id(), width, height);
+            }
+            3 => {
+                let mode = wire_msg.next_new_specified_id(&payload);
+                state.mode(wire_msg.sender_id(), mode);
+            }
+            4 => {
+                let enabled = wire_msg.next_i32(&payload);
+                state.enabled(wire_msg.sender_id(), enabled);
+            }
+            5 => {
+                let mode = wire_msg.next_object(&payload);
+                state.current_mode(wire_msg.sender_id(), mode);
+            }
+            6 => {
+                let x = wire_msg.next_i32(&payload);
+                let y = wire_msg.next_i32(&payload);
+                state.position(wire_msg.sender_id(), x, y);
+            }
+            7 => {
+                let transform = wire_msg.next_i32(&payload);
+                state.transform(wire_msg.sender_id(), transform);
+            }
+            8 => {
+                let scale = wire_msg.next_fixed(&payload);
+                state.scale(wire_msg.sender_id(), scale);
+            }
+            9 => {
+                state.finished(wire_msg.sender_id());
+            }
+            10 => {
+                let make = wire_msg.next_string(&payload);
+                state.make(wire_msg.sender_id(), make);
+            }
+            11 => {
+                let model = wire_msg.next_string(&payload);
+                state.model(wire_msg.sender_id(), model);
+            }
+            12 => {
+                let serial_number = wire_msg.next_string(&payload);
+                state.serial_number(wire_msg.sender_id(), serial_number);
+            }
+            13 => {
+                let adaptive_sync = wire_msg.next_u32(&payload);
+                state.adaptive_sync(wire_msg.sender_id(), adaptive_sync);
+            }
+            e => log::error!("unrecognized event opcode: {e} for interface zwlr_output_head_v1"),
+        }
+    }
+
+    ///Requests for this interface
+    pub mod req {
+        use super::*;
+        ///destroy the head object
+        ///
+        ///This request indicates that the client will no longer use this head object. Since
+        ///version 3.
+        ///
+        ///THIS IS A DESTRUCTOR
+        pub fn release(sender_id: ObjectId) -> rustix::io::Result<()> {
+            let wire_msg_builder = WireMsgBuilder::new(sender_id, 0);
+            wire_msg_builder.send()
+        }
+    }
+}
+///output mode
+///
+///This object describes an output mode.
+pub mod zwlr_output_mode_v1 {
+    use super::*;
+
+    pub trait EvHandler {
+        ///mode size, in physical hardware units of the output device
+        fn size(&mut self, sender_id: ObjectId, width: i32, height: i32);
+        ///mode refresh rate, in mHz
+        fn refresh(&mut self, sender_id: ObjectId, refresh: i32);
+        ///mode is preferred
+        fn preferred(&mut self, sender_id: ObjectId);
+        ///the mode has disappeared
+        ///
+        ///This event indicates that the mode is no longer available. The mode object becomes
+        ///inert. Clients should send a destroy request and release any resources associated with
+        ///it.
+        fn finished(&mut self, sender_id: ObjectId);
+    }
+
+    pub fn event<T: EvHandler>(state: &mut T, mut wire_msg: WireMsg, payload: WaylandPayload) {
+        match wire_msg.op() {
+            0 => {
+                let width = wire_msg.next_i32(&payload);
+                let height = wire_msg.next_i32(&payload);
+                state.size(wire_msg.sender_id(), width, height);
+            }
+            1 => {
+                let refresh = wire_msg.next_i32(&payload);
+                state.refresh(wire_msg.sender_id(), refresh);
+            }
+            2 => {
+                state.preferred(wire_msg.sender_id());
+            }
+            3 => {
+                state.finished(wire_msg.sender_id());
+            }
+            e => log::error!("unrecognized event opcode: {e} for interface zwlr_output_mode_v1"),
+        }
+    }
+
+    ///Requests for this interface
+    pub mod req {
+        use super::*;
+        ///destroy the mode object
+        ///
+        ///This request indicates that the client will no longer use this mode object. Since
+        ///version 3.
+        ///
+        ///THIS IS A DESTRUCTOR
+        pub fn release(sender_id: ObjectId) -> rustix::io::Result<()> {
+            let wire_msg_builder = WireMsgBuilder::new(sender_id, 0);
+            wire_msg_builder.send()
+        }
+    }
+}
+///manager to inform clients and begin capturing
+///
+///This object is a manager which offers requests to start capturing from a source.
+///
+///We only capture what an output shows before we draw anything on it, so that the first
+///transition starts from it.
+pub mod zwlr_screencopy_manager_v1 {
+    use super::*;
+
+    ///Events for this interface
+    pub mod ev {}
+    ///Requests for this interface
+    pub mod req {
+        use super::*;
+        ///capture an output
+        ///
+        ///Capture the next frame of an entire output.
+        pub fn capture_output(
+            sender_id: ObjectId,
+            frame: ObjectId,
+            overlay_cursor: i32,
+            output: ObjectId,
+        ) -> rustix::io::Result<()> {
+            let mut wire_msg_builder = WireMsgBuilder::new(sender_id, 0);
+            wire_msg_builder.add_new_specified_id(frame);
+            wire_msg_builder.add_i32(overlay_cursor);
+            wire_msg_builder.add_object(Some(output));
+            wire_msg_builder.send()
+        }
+        ///destroy the manager
+        ///
+        ///All objects created by the manager will still remain valid, until their appropriate
+        ///destroy request has been called.
+        ///
+        ///THIS IS A DESTRUCTOR
+        pub fn destroy(sender_id: ObjectId) -> rustix::io::Result<()> {
+            let wire_msg_builder = WireMsgBuilder::new(sender_id, 2);
+            wire_msg_builder.send()
+        }
+    }
+}
+///a frame ready for copy
+///
+///This object represents a single frame.
+///
+///When created, a series of buffer events will be sent, each representing a supported buffer
+///type. The "buffer_done" event is sent afterwards to indicate that all supported buffer types
+///have been enumerated. The client will then be able to send a "copy" request. If the capture is
+///successful, the compositor will send a "flags" event followed by a "ready" event. Otherwise, a
+///"failed" event will be sent.
+pub mod zwlr_screencopy_frame_v1 {
+    use super::*;
+
+    pub trait EvHandler {
+        ///wl_shm buffer information
+        ///
+        ///Provides information about wl_shm buffer parameters that need to be used for this
+        ///frame. This event is sent once after the frame is created if wl_shm buffers are
+        ///supported.
+        fn buffer(
+            &mut self,
+            sender_id: ObjectId,
+            format: u32,
+            width: u32,
+            height: u32,
+            stride: u32,
+        );
+        ///frame flags
+        ///
+        ///Provides flags about the frame. This event is sent once before the "ready" event.
+        fn flags(&mut self, sender_id: ObjectId, flags: u32);
+        ///indicates frame is available for reading
+        ///
+        ///Called as soon as the frame is copied, indicating it is available for reading. The
+        ///timestamp is the time the frame was presented.
+        fn ready(&mut self, sender_id: ObjectId, tv_sec_hi: u32, tv_sec_lo: u32, tv_nsec: u32);
+        ///frame copy failed
+        ///
+        ///This event indicates that the attempted frame copy has failed.
+        fn failed(&mut self, sender_id: ObjectId);
+        ///carries the coordinates of the damaged region. Since version 2.
+        fn damage(&mut self, sender_id: ObjectId, x: u32, y: u32, width: u32, height: u32);
+        ///linux-dmabuf buffer information. Since version 3.
+        fn linux_dmabuf(&mut self, sender_id: ObjectId, format: u32, width: u32, height: u32);
+        ///all buffer types reported
+        ///
+        ///This event is sent once after all buffer events have been sent. The client should
+        ///proceed to create a buffer of one of the supported types, and send a "copy" request.
+        ///Since version 3.
+        fn buffer_done(&mut self, sender_id: ObjectId);
+    }
+
+    pub fn event<T: EvHandler>(state: &mut T, mut wire_msg: WireMsg, payload: WaylandPayload) {
+        match wire_msg.op() {
+            0 => {
+                let format = wire_msg.next_u32(&payload);
+                let width = wire_msg.next_u32(&payload);
+                let height = wire_msg.next_u32(&payload);
+                let stride = wire_msg.next_u32(&payload);
+                state.buffer(wire_msg.sender_id(), format, width, height, stride);
+            }
+            1 => {
+                let flags = wire_msg.next_u32(&payload);
+                state.flags(wire_msg.sender_id(), flags);
+            }
+            2 => {
+                let tv_sec_hi = wire_msg.next_u32(&payload);
+                let tv_sec_lo = wire_msg.next_u32(&payload);
+                let tv_nsec = wire_msg.next_u32(&payload);
+                state.ready(wire_msg.sender_id(), tv_sec_hi, tv_sec_lo, tv_nsec);
+            }
+            3 => {
+                state.failed(wire_msg.sender_id());
+            }
+            4 => {
+                let x = wire_msg.next_u32(&payload);
+                let y = wire_msg.next_u32(&payload);
+                let width = wire_msg.next_u32(&payload);
+                let height = wire_msg.next_u32(&payload);
+                state.damage(wire_msg.sender_id(), x, y, width, height);
+            }
+            5 => {
+                let format = wire_msg.next_u32(&payload);
+                let width = wire_msg.next_u32(&payload);
+                let height = wire_msg.next_u32(&payload);
+                state.linux_dmabuf(wire_msg.sender_id(), format, width, height);
+            }
+            6 => {
+                state.buffer_done(wire_msg.sender_id());
+            }
+            e => {
+                log::error!("unrecognized event opcode: {e} for interface zwlr_screencopy_frame_v1")
+            }
+        }
+    }
+
+    ///Requests for this interface
+    pub mod req {
+        use super::*;
+        ///copy the frame
+        ///
+        ///Copy the frame to the supplied buffer. The buffer must have the correct size, see
+        ///zwlr_screencopy_frame_v1.buffer and zwlr_screencopy_frame_v1.linux_dmabuf. The buffer
+        ///needs to have a supported format.
+        ///
+        ///If the frame is successfully copied, "flags" and "ready" events are sent. Otherwise, a
+        ///"failed" event is sent.
+        pub fn copy(sender_id: ObjectId, buffer: ObjectId) -> rustix::io::Result<()> {
+            let mut wire_msg_builder = WireMsgBuilder::new(sender_id, 0);
+            wire_msg_builder.add_object(Some(buffer));
+            wire_msg_builder.send()
+        }
+        ///delete this object, used or not
+        ///
+        ///Destroys the frame. This request can be sent at any time by the client.
+        ///
+        ///THIS IS A DESTRUCTOR
+        pub fn destroy(sender_id: ObjectId) -> rustix::io::Result<()> {
+            let wire_msg_builder = WireMsgBuilder::new(sender_id, 1);
+            wire_msg_builder.send()
+        }
+    }
+
+    pub mod flags {
+        ///contents are y-inverted
+        pub const Y_INVERT: u32 = 1u32;
+    }
+}
@@ -7,15 +7,15 @@
 //!
 //! Our own implementation can make several improvements:
 //!   * we make all the globals that always in our program exist `const`s, so that they can be
-//!   accessed anywhere within the code
+//!     accessed anywhere within the code
 //!   * we make the wayland file descriptor a global variable, so it can be accessed anywhere
-//!   within the code
-//!   * we don't buffer the wayland socket connection, instead just sending the message all at once
-//!   every time. This, combined with the two points above, mean we can make request from multiple
-//!   threads without having to keep passing weak references to a Backend struct (like how it
-//!   happens with `wayland-client.rs`).
-//!   * we have a much simpler (from what I can tell), object id manager implementation. That we've
-//!   also made global, so it can be called anywhere.
+//!     within the code
+//!   * we don't buffer the wayland socket connection, instead just sending the message all at
+//!     once every time. This, combined with the two points above, mean we can make request from
+//!     multiple threads without having to keep passing weak references to a Backend struct (like
+//!     how it happens with `wayland-client.rs`).
+//!   * we have a much simpler (from what I can tell), object id manager implementation. That
+//!     we've also made global, so it can be called anywhere.
 //!
 //! Furthermore, this also prevents any changes to `wayland-client.rs` from affecting us. We are
 //! now completely independent from them.
@@ -57,6 +57,15 @@ pub enum WlDynObj {
     Callback,
     Viewport,
     FractionalScale,
+    Seat,
+    Pointer,
+    XdgOutputManager,
+    XdgOutput,
+    OutputManager,
+    OutputHead,
+    OutputMode,
+    ScreencopyManager,
+    ScreencopyFrame,
 }
 
 /// Object Manager for creating, removing, and maintaining Wayland Objects
@@ -67,16 +76,23 @@ pub struct ObjectManager {
     objects: Vec<Option<WlDynObj>>,
     /// the next id we ought to generate
     next: u32,
+    /// the objects the compositor created, like the heads of wlr-output-management. Their ids
+    /// start at `SERVER_ID_START`
+    server_objects: Vec<Option<WlDynObj>>,
 }
 
 impl ObjectManager {
     /// Ids 1-6, inclusive, are all already taken by the globals in `globals.rs`
     const BASE_OFFSET: u32 = 7;
 
+    /// The ids of the objects the compositor creates start here
+    const SERVER_ID_START: u32 = 0xff000000;
+
     pub const fn new() -> Self {
         Self {
             objects: Vec::new(),
             next: 0,
+            server_objects: Vec::new(),
         }
     }
 
@@ -87,6 +103,9 @@ impl ObjectManager {
     ///   * 'None' if the object was already deleted
     #[must_use]
     pub fn get(&self, object_id: ObjectId) -> Option<WlDynObj> {
+        if let Some(pos) = object_id.get().checked_sub(Self::SERVER_ID_START) {
+            return self.server_objects.get(pos as usize).copied().flatten();
+        }
         let offset = Self::BASE_OFFSET + globals::fractional_scale_support() as u32;
         let pos = object_id.get() - offset;
         self.objects[pos as usize]
@@ -117,11 +136,31 @@ impl ObjectManager {
         }
     }
 
+    /// registers an object the compositor created through an event, like the heads of
+    /// wlr-output-management
+    pub fn insert_server(&mut self, object_id: ObjectId, object: WlDynObj) {
+        let Some(pos) = object_id.get().checked_sub(Self::SERVER_ID_START) else {
+            log::error!("the compositor created an object with a client id: {object_id:?}");
+            return;
+        };
+        let pos = pos as usize;
+        if pos >= self.server_objects.len() {
+            self.server_objects.resize(pos + 1, None);
+        }
+        self.server_objects[pos] = Some(object);
+    }
+
     /// removes the wayland object.
     ///
     /// Removing the same element twice currently works just fine and does not panic,
     /// but that may change in the future
     pub fn remove(&mut self, object_id: ObjectId) {
+        if let Some(pos) = object_id.get().checked_sub(Self::SERVER_ID_START) {
+            if let Some(object) = self.server_objects.get_mut(pos as usize) {
+                *object = None;
+            }
+            return;
+        }
         let offset = Self::BASE_OFFSET + globals::fractional_scale_support() as u32;
         let pos = object_id.get() - offset;
         self.objects[pos as usize] = None;
@@ -166,4 +205,22 @@ mod tests {
         let id7 = manager.create(WlDynObj::Region);
         assert_eq!(id7, id2);
     }
+
+    #[test]
+    fn server_object_ids() {
+        let mut manager = ObjectManager::new();
+        let head = obj_from_u32(ObjectManager::SERVER_ID_START + 2);
+        manager.insert_server(head, WlDynObj::OutputHead);
+        assert!(matches!(manager.get(head), Some(WlDynObj::OutputHead)));
+        assert!(manager
+            .get(obj_from_u32(ObjectManager::SERVER_ID_START))
+            .is_none());
+
+        // they do not take the ids we create
+        let id = manager.create(WlDynObj::Region);
+        assert_eq!(id, obj_from_u32(ObjectManager::BASE_OFFSET));
+
+        manager.remove(head);
+        assert!(manager.get(head).is_none());
+    }
 }
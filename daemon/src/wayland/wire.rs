@@ -260,7 +260,12 @@ pub unsafe fn send_unchecked(msg: &[u8], fds: &[BorrowedFd]) -> rustix::io::Resu
     let mut control = net::SendAncillaryBuffer::new(&mut control_buf);
     let msg = net::SendAncillaryMessage::ScmRights(fds);
     control.push(msg);
-    net::sendmsg(wayland_fd(), &[iov], &mut control, net::SendFlags::NOSIGNAL).map(|_| ())
+    // a signal must not make us drop the message, since then the compositor would not know about
+    // the objects it creates
+    rustix::io::retry_on_intr(|| {
+        net::sendmsg(wayland_fd(), &[iov], &mut control, net::SendFlags::NOSIGNAL)
+    })
+    .map(|_| ())
 }
 
 impl<'a> WlSlice<'a> {
@@ -296,7 +301,6 @@ impl<'a, 'b> From<&'b [u8]> for WlSlice<'a>
 where
     'b: 'a,
 {
-    #[must_use]
     fn from(bytes: &'b [u8]) -> Self {
         Self(bytes)
     }
@@ -337,35 +341,30 @@ impl<'a, 'b> From<&'b str> for WlStr<'a>
 where
     'b: 'a,
 {
-    #[must_use]
     fn from(s: &'b str) -> Self {
         Self(s)
     }
 }
 
 impl From<i32> for WlFixed {
-    #[must_use]
     fn from(value: i32) -> Self {
         Self(value * 256)
     }
 }
 
 impl From<u32> for WlFixed {
-    #[must_use]
     fn from(value: u32) -> Self {
         Self(value as i32 * 256)
     }
 }
 
 impl From<&WlFixed> for i32 {
-    #[must_use]
     fn from(val: &WlFixed) -> Self {
         val.0 / 256
     }
 }
 
 impl From<f64> for WlFixed {
-    #[must_use]
     fn from(value: f64) -> Self {
         let d = value + (3i64 << (51 - 8)) as f64;
         Self(d.to_bits() as i32)
@@ -373,7 +372,6 @@ impl From<f64> for WlFixed {
 }
 
 impl From<&WlFixed> for f64 {
-    #[must_use]
     fn from(val: &WlFixed) -> Self {
         let i = ((1023i64 + 44i64) << 52) + (1i64 << 51) + val.0 as i64;
         let d = f64::from_bits(i as u64);
@@ -0,0 +1,131 @@
+//! Conversions from and to the sRGB bytes we draw, for blending colors the way we perceive them
+//!
+//! Blending the bytes directly (like `transitions::fade` does) darkens and desaturates the colors
+//! in between, because sRGB is not linear. These conversions go through lookup tables, since they
+//! run for every pixel of every frame.
+
+/// Entries of the table from linear light back to sRGB. Dark colors need the precision
+const LINEAR_STEPS: usize = 1 << 14;
+
+/// Entries of the cube root table, used for OKLab
+const CBRT_STEPS: usize = 1 << 16;
+
+/// Lookup tables for converting between sRGB bytes, linear light and OKLab
+pub struct Luts {
+    to_linear: [f32; 256],
+    from_linear: Box<[u8]>,
+    cbrt: Box<[f32]>,
+}
+
+impl Luts {
+    pub fn new() -> Self {
+        let mut to_linear = [0.0; 256];
+        for (byte, linear) in to_linear.iter_mut().enumerate() {
+            let c = byte as f32 / 255.0;
+            *linear = if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            };
+        }
+        let from_linear = (0..LINEAR_STEPS)
+            .map(|i| {
+                let l = i as f32 / (LINEAR_STEPS - 1) as f32;
+                let c = if l <= 0.0031308 {
+                    l * 12.92
+                } else {
+                    1.055 * l.powf(1.0 / 2.4) - 0.055
+                };
+                (c * 255.0).round() as u8
+            })
+            .collect();
+        let cbrt = (0..CBRT_STEPS)
+            .map(|i| (i as f32 / (CBRT_STEPS - 1) as f32).cbrt())
+            .collect();
+        Self {
+            to_linear,
+            from_linear,
+            cbrt,
+        }
+    }
+
+    /// From an sRGB byte to linear light, between 0 and 1
+    #[inline(always)]
+    pub fn srgb_to_linear(&self, byte: u8) -> f32 {
+        self.to_linear[byte as usize]
+    }
+
+    /// From linear light, between 0 and 1 (it is clamped otherwise), to an sRGB byte
+    #[inline(always)]
+    pub fn linear_to_srgb(&self, linear: f32) -> u8 {
+        let i = (linear.clamp(0.0, 1.0) * (LINEAR_STEPS - 1) as f32 + 0.5) as usize;
+        self.from_linear[i]
+    }
+
+    #[inline(always)]
+    fn cbrt(&self, x: f32) -> f32 {
+        self.cbrt[(x.clamp(0.0, 1.0) * (CBRT_STEPS - 1) as f32 + 0.5) as usize]
+    }
+
+    /// From sRGB bytes to OKLab (https://bottosson.github.io/posts/oklab/)
+    #[inline(always)]
+    pub fn srgb_to_oklab(&self, [r, g, b]: [u8; 3]) -> [f32; 3] {
+        let (r, g, b) = (
+            self.srgb_to_linear(r),
+            self.srgb_to_linear(g),
+            self.srgb_to_linear(b),
+        );
+        let l = self.cbrt(0.41222146 * r + 0.53633255 * g + 0.05144599 * b);
+        let m = self.cbrt(0.2119035 * r + 0.6806995 * g + 0.10739696 * b);
+        let s = self.cbrt(0.08830246 * r + 0.28171885 * g + 0.6299787 * b);
+        [
+            0.21045426 * l + 0.7936178 * m - 0.00407205 * s,
+            1.9779985 * l - 2.4285922 * m + 0.4505937 * s,
+            0.02590404 * l + 0.78277177 * m - 0.80867577 * s,
+        ]
+    }
+
+    /// From OKLab to sRGB bytes. Colors outside of sRGB are clamped
+    #[inline(always)]
+    pub fn oklab_to_srgb(&self, [l, a, b]: [f32; 3]) -> [u8; 3] {
+        let l_ = (l + 0.39633778 * a + 0.21580376 * b).powi(3);
+        let m_ = (l - 0.10556135 * a - 0.06385417 * b).powi(3);
+        let s_ = (l - 0.08948418 * a - 1.2914855 * b).powi(3);
+        [
+            self.linear_to_srgb(4.0767417 * l_ - 3.3077116 * m_ + 0.23096994 * s_),
+            self.linear_to_srgb(-1.268438 * l_ + 2.6097574 * m_ - 0.3413194 * s_),
+            self.linear_to_srgb(-0.00419609 * l_ - 0.7034186 * m_ + 1.7076147 * s_),
+        ]
+    }
+}
+
+impl Default for Luts {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conversions_should_round_trip() {
+        let luts = Luts::new();
+        for byte in 0..=255 {
+            assert_eq!(luts.linear_to_srgb(luts.srgb_to_linear(byte)), byte);
+        }
+        for color in [
+            [0, 0, 0],
+            [255, 255, 255],
+            [255, 0, 0],
+            [0, 128, 255],
+            [7, 200, 31],
+        ] {
+            let back = luts.oklab_to_srgb(luts.srgb_to_oklab(color));
+            for (a, b) in color.iter().zip(back) {
+                assert!(a.abs_diff(b) <= 1, "{color:?} came back as {back:?}");
+            }
+        }
+    }
+}
@@ -76,7 +76,7 @@ unsafe fn count_different(s1: &[u8], s2: &[u8], mut i: usize) -> usize {
 ///
 /// cur.len() must be equal to goal.len()
 #[inline(always)]
-pub(super) unsafe fn pack_bytes(cur: &[u8], goal: &[u8], v: &mut Vec<u8>) {
+pub unsafe fn pack_bytes(cur: &[u8], goal: &[u8], v: &mut Vec<u8>) {
     // use the most efficient implementation available:
     #[cfg(not(test))] // when testing, we want to use the specific implementation
     {
@@ -142,7 +142,11 @@ mod tests {
             for x in &mut b[..i] {
                 *x = 1;
             }
-            assert_eq!(unsafe { count_different(&a, &b, 0) }, (i + 2) / 3, "i: {i}");
+            assert_eq!(
+                unsafe { count_different(&a, &b, 0) },
+                i.div_ceil(3),
+                "i: {i}"
+            );
         }
     }
 }
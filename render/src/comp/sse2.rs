@@ -57,7 +57,7 @@ unsafe fn count_different(s1: &[u8], s2: &[u8], mut i: usize) -> usize {
         let mask = (mask & (mask >> 1) & (mask >> 2)) & 0b001001001001001;
         if mask != 0 {
             let tz = mask.trailing_zeros() as usize;
-            diff += (tz + 2) / 3;
+            diff += tz.div_ceil(3);
             return diff;
         }
         diff += 5;
@@ -117,7 +117,7 @@ pub(super) unsafe fn pack_bytes(cur: &[u8], goal: &[u8], v: &mut Vec<u8>) {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::compression::unpack_bytes_4channels;
+    use crate::decomp::unpack_bytes_4channels;
 
     #[test]
     fn count_equal_test() {
@@ -139,7 +139,11 @@ mod tests {
             for x in &mut b[..i] {
                 *x = 1;
             }
-            assert_eq!(unsafe { count_different(&a, &b, 0) }, (i + 2) / 3, "i: {i}");
+            assert_eq!(
+                unsafe { count_different(&a, &b, 0) },
+                i.div_ceil(3),
+                "i: {i}"
+            );
         }
     }
 
@@ -163,7 +167,7 @@ mod tests {
         unsafe { pack_bytes(&frame1, &frame2, &mut compressed) };
 
         let mut buf = buf_from(&frame1);
-        unpack_bytes_4channels(&mut buf, &compressed);
+        unsafe { unpack_bytes_4channels(&mut buf, &compressed) }
         for i in 0..2 {
             for j in 0..3 {
                 assert_eq!(
@@ -202,7 +206,7 @@ mod tests {
 
             let mut buf = buf_from(original.last().unwrap());
             for i in 0..20 {
-                unpack_bytes_4channels(&mut buf, &compressed[i]);
+                unsafe { unpack_bytes_4channels(&mut buf, &compressed[i]) }
                 let mut j = 0;
                 let mut l = 0;
                 while j < 3000 {
@@ -258,7 +262,7 @@ mod tests {
 
             let mut buf = buf_from(original.last().unwrap());
             for i in 0..20 {
-                unpack_bytes_4channels(&mut buf, &compressed[i]);
+                unsafe { unpack_bytes_4channels(&mut buf, &compressed[i]) }
                 let mut j = 0;
                 let mut l = 0;
                 while j < 3000 {
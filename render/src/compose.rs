@@ -0,0 +1,220 @@
+//! Helpers for composing the canvas
+
+/// Moves `old` by `step` towards `new`
+#[inline(always)]
+pub fn change_byte(step: u8, old: &mut u8, new: &u8) {
+    if old.abs_diff(*new) < step {
+        *old = *new;
+    } else if *old > *new {
+        *old -= step;
+    } else {
+        *old += step;
+    }
+}
+
+/// Nearest neighbor rescaling of `src`, with dimensions `src_dim`, into `dst`, with dimensions
+/// `dst_dim`
+///
+/// # Panics
+///
+/// If the slices are smaller than their dimensions say they are
+pub fn resample(
+    src: &[u8],
+    src_dim: (usize, usize),
+    dst: &mut [u8],
+    dst_dim: (usize, usize),
+    channels: usize,
+) {
+    let (src_w, src_h) = src_dim;
+    let (dst_w, dst_h) = dst_dim;
+    assert!(src.len() >= src_w * src_h * channels);
+    for (y, row) in dst
+        .chunks_exact_mut(dst_w * channels)
+        .take(dst_h)
+        .enumerate()
+    {
+        let src_row = &src[(y * src_h / dst_h) * src_w * channels..];
+        for (x, pixel) in row.chunks_exact_mut(channels).enumerate() {
+            let i = (x * src_w / dst_w) * channels;
+            pixel.copy_from_slice(&src_row[i..i + channels]);
+        }
+    }
+}
+
+/// Like `resample`, but keeping the aspect ratio of `src`: it is scaled to cover `dst`, and what
+/// does not fit is cropped equally from both sides, like `swww img --resize crop` does
+///
+/// # Panics
+///
+/// If the slices are smaller than their dimensions say they are
+pub fn resample_cropped(
+    src: &[u8],
+    src_dim: (usize, usize),
+    dst: &mut [u8],
+    dst_dim: (usize, usize),
+    channels: usize,
+) {
+    let (src_w, src_h) = src_dim;
+    let (dst_w, dst_h) = dst_dim;
+    assert!(src.len() >= src_w * src_h * channels);
+    // the part of `src` we keep
+    let (crop_w, crop_h) = if src_w * dst_h > src_h * dst_w {
+        (src_h * dst_w / dst_h, src_h)
+    } else {
+        (src_w, src_w * dst_h / dst_w)
+    };
+    let (crop_x, crop_y) = ((src_w - crop_w) / 2, (src_h - crop_h) / 2);
+    for (y, row) in dst
+        .chunks_exact_mut(dst_w * channels)
+        .take(dst_h)
+        .enumerate()
+    {
+        let src_row = &src[(crop_y + y * crop_h / dst_h) * src_w * channels..];
+        for (x, pixel) in row.chunks_exact_mut(channels).enumerate() {
+            let i = (crop_x + x * crop_w / dst_w) * channels;
+            pixel.copy_from_slice(&src_row[i..i + channels]);
+        }
+    }
+}
+
+/// The brightness of each pixel of `img`, which has `channels` bytes per pixel, starting with
+/// their blue byte if `bgr` is set. It is what the 'luma' transition follows when it is not given
+/// a mask
+#[must_use]
+pub fn luma_mask(img: &[u8], channels: usize, bgr: bool) -> Box<[u8]> {
+    // Rec. 709 weights, scaled so that they add up to 256
+    let (r, b) = if bgr { (2, 0) } else { (0, 2) };
+    img.chunks_exact(channels)
+        .map(|pixel| {
+            let luma = 54 * pixel[r] as u32 + 183 * pixel[1] as u32 + 19 * pixel[b] as u32;
+            (luma >> 8) as u8
+        })
+        .collect()
+}
+
+/// Copies `src`, with dimensions `src_dim`, into `dst`, mirrored around the vertical axis if
+/// `flipped` is set, then rotated `quarter_turns` times by 90 degrees counter-clockwise. This is
+/// what a `wl_output.transform` does to a buffer. When `quarter_turns` is odd, `dst`'s width is
+/// `src`'s height, and vice versa.
+///
+/// # Panics
+///
+/// If the slices do not have exactly `src_dim.0 * src_dim.1 * channels` bytes
+pub fn transform(
+    src: &[u8],
+    src_dim: (usize, usize),
+    dst: &mut [u8],
+    channels: usize,
+    quarter_turns: u8,
+    flipped: bool,
+) {
+    let (w, h) = src_dim;
+    assert_eq!(
+        src.len(),
+        w * h * channels,
+        "src does not match its dimensions"
+    );
+    assert_eq!(src.len(), dst.len(), "src and dst have different sizes");
+    let dst_w = if quarter_turns % 2 == 0 { w } else { h };
+
+    // where each pixel of `src` ends up in `dst`
+    let position = |x: usize, y: usize| match (quarter_turns % 4, flipped) {
+        (0, false) => (x, y),
+        (0, true) => (w - 1 - x, y),
+        (1, false) => (y, w - 1 - x),
+        (1, true) => (y, x),
+        (2, false) => (w - 1 - x, h - 1 - y),
+        (2, true) => (x, h - 1 - y),
+        (3, false) => (h - 1 - y, x),
+        (_, _) => (h - 1 - y, w - 1 - x),
+    };
+
+    for (y, row) in src.chunks_exact(w * channels).enumerate() {
+        for (x, pixel) in row.chunks_exact(channels).enumerate() {
+            let (dst_x, dst_y) = position(x, y);
+            let i = (dst_y * dst_w + dst_x) * channels;
+            dst[i..i + channels].copy_from_slice(pixel);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn change_byte_should_never_overshoot() {
+        for step in 1..=255 {
+            for (old, new) in [(0, 255), (255, 0), (10, 12), (12, 10), (7, 7)] {
+                let mut byte = old;
+                for _ in 0..256 {
+                    change_byte(step, &mut byte, &new);
+                }
+                assert_eq!(byte, new, "step: {step}, old: {old}, new: {new}");
+            }
+        }
+    }
+
+    #[test]
+    fn resample_roundtrip() {
+        let src: Vec<u8> = (0..4 * 2 * 3).collect();
+        let mut big = vec![0; 8 * 4 * 3];
+        resample(&src, (4, 2), &mut big, (8, 4), 3);
+        let mut small = vec![0; src.len()];
+        resample(&big, (8, 4), &mut small, (4, 2), 3);
+        assert_eq!(src, small);
+    }
+
+    #[test]
+    fn resample_cropped_should_keep_the_middle() {
+        // 1 2 3 4
+        // 5 6 7 8
+        let src = [1, 2, 3, 4, 5, 6, 7, 8];
+        let mut dst = [0; 4];
+        resample_cropped(&src, (4, 2), &mut dst, (2, 2), 1);
+        assert_eq!(dst, [2, 3, 6, 7]);
+        let mut dst = [0; 16];
+        resample_cropped(&src, (4, 2), &mut dst, (4, 4), 1);
+        assert_eq!(dst, [2, 2, 3, 3, 2, 2, 3, 3, 6, 6, 7, 7, 6, 6, 7, 7]);
+    }
+
+    #[test]
+    fn transform_should_rotate_counter_clockwise() {
+        // 1 2 3
+        // 4 5 6
+        let src = [1, 2, 3, 4, 5, 6];
+        let mut dst = [0; 6];
+        transform(&src, (3, 2), &mut dst, 1, 1, false);
+        assert_eq!(dst, [3, 6, 2, 5, 1, 4]);
+        transform(&src, (3, 2), &mut dst, 1, 0, true);
+        assert_eq!(dst, [3, 2, 1, 6, 5, 4]);
+    }
+
+    #[test]
+    fn transforms_should_have_inverses() {
+        let src: Vec<u8> = (0..5 * 3 * 4).collect();
+        for quarter_turns in 0..4 {
+            for flipped in [false, true] {
+                let mut dst = vec![0; src.len()];
+                transform(&src, (5, 3), &mut dst, 4, quarter_turns, flipped);
+                let dst_dim = if quarter_turns % 2 == 0 {
+                    (5, 3)
+                } else {
+                    (3, 5)
+                };
+                // flips are their own inverse
+                let inverse = if flipped {
+                    quarter_turns
+                } else {
+                    (4 - quarter_turns) % 4
+                };
+                let mut back = vec![0; src.len()];
+                transform(&dst, dst_dim, &mut back, 4, inverse, flipped);
+                assert_eq!(
+                    src, back,
+                    "quarter turns: {quarter_turns}, flipped: {flipped}"
+                );
+            }
+        }
+    }
+}
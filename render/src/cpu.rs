@@ -15,6 +15,7 @@ macro_rules! decl_feature {
     ($feature:ident, $function:ident) => {
         static mut $feature: bool = false;
         #[inline(always)]
+        #[cfg_attr(test, allow(dead_code))] // tests call the specific implementations directly
         pub fn $function() -> bool {
             // SAFETY: we ensure this is false by default, and only changes ONCE, if someone calls
             // this module's init() function
@@ -24,7 +25,9 @@ macro_rules! decl_feature {
 }
 
 static ONCE_INIT: Once = Once::new();
-pub(super) fn init() {
+/// Must be called before using the functions in this crate, so they can pick the most efficient
+/// implementation available
+pub fn init() {
     // SAFETY: features::init will modify some static mut variables. It is safe because we are
     // wrapping them in a Once call
     ONCE_INIT.call_once(|| unsafe { features::init() });
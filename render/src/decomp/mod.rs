@@ -0,0 +1,325 @@
+//! This modules contains all the decompression functions, including the specialized ones using
+//! architecture-dependent instructions
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub(super) mod ssse3;
+
+/// Checks whether unpacking `diff` onto a frame with `pixels` pixels stays in bounds, both when
+/// reading `diff` and when writing to the frame.
+///
+/// Everything `comp::pack_bytes` produces for a frame of that size passes this check, so it is
+/// only needed for diffs that come from somewhere we do not trust, like the cache.
+pub fn is_well_formed(diff: &[u8], pixels: usize) -> bool {
+    if diff.len() < 3 || diff[diff.len() - 1] | diff[diff.len() - 2] != 0 {
+        return false;
+    }
+
+    let len = diff.len() - 3;
+    let mut diff_idx = 0;
+    let mut pix_idx = 0;
+    while diff_idx < len {
+        let Some(equals) = read_count(diff, &mut diff_idx) else {
+            return false;
+        };
+        pix_idx += equals;
+        let Some(to_cpy) = read_count(diff, &mut diff_idx) else {
+            return false;
+        };
+        // the 4 channels implementations read one byte past the last pixel
+        if diff_idx + to_cpy * 3 + 1 >= diff.len() || pix_idx + to_cpy > pixels {
+            return false;
+        }
+        diff_idx += to_cpy * 3;
+        pix_idx += to_cpy + 1;
+    }
+    true
+}
+
+/// Checks whether unpacking `diff` onto `buf`, whose pixels are `channels` bytes long, would
+/// change any of them. `diff` must be well formed for `buf` (see `is_well_formed`).
+///
+/// This only looks at the pixels `diff` copies, so it is much cheaper than unpacking onto a copy
+/// of `buf` and comparing the two. It lets us skip frames that do not change what is on screen,
+/// like when an animation is played on top of something else than its previous frame.
+pub fn changes(buf: &[u8], diff: &[u8], channels: usize) -> bool {
+    let len = diff.len().saturating_sub(3);
+    let mut diff_idx = 0;
+    let mut pix_idx = 0;
+    while diff_idx < len {
+        let (Some(equals), Some(to_cpy)) = (
+            read_count(diff, &mut diff_idx),
+            read_count(diff, &mut diff_idx),
+        ) else {
+            return true;
+        };
+        pix_idx += equals;
+        for pixel in 0..to_cpy {
+            let new = diff.get(diff_idx + pixel * 3..diff_idx + pixel * 3 + 3);
+            let old = buf.get((pix_idx + pixel) * channels..(pix_idx + pixel) * channels + 3);
+            if new.is_none() || new != old {
+                return true;
+            }
+        }
+        diff_idx += to_cpy * 3;
+        pix_idx += to_cpy + 1;
+    }
+    false
+}
+
+/// reads one of the counts `comp::pack_bytes` stores, which are split into bytes of at most 255
+fn read_count(diff: &[u8], diff_idx: &mut usize) -> Option<usize> {
+    let mut count = 0;
+    loop {
+        let byte = *diff.get(*diff_idx)?;
+        *diff_idx += 1;
+        count += byte as usize;
+        if byte != u8::MAX {
+            return Some(count);
+        }
+    }
+}
+
+/// # Safety
+///
+/// diff must be a slice produced by `comp::pack_bytes`, and buf must have the EXACT size of the
+/// frame it was produced from (with 4 channels per pixel instead of 3)
+#[inline(always)]
+pub unsafe fn unpack_bytes_4channels(buf: &mut [u8], diff: &[u8]) {
+    assert!(
+        diff[diff.len() - 1] | diff[diff.len() - 2] == 0,
+        "Poorly formed BitPack"
+    );
+    // use the most efficient implementation available:
+    #[cfg(not(test))] // when testing, we want to use the specific implementation
+    {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        if super::cpu::features::ssse3() {
+            return unsafe { ssse3::unpack_bytes_4channels(buf, diff) };
+        }
+    }
+
+    // The final bytes are just padding to prevent us from going out of bounds
+    let len = diff.len() - 3;
+    let buf_ptr = buf.as_mut_ptr();
+    let diff_ptr = diff.as_ptr();
+
+    let mut diff_idx = 0;
+    let mut pix_idx = 0;
+    while diff_idx < len {
+        while unsafe { diff_ptr.add(diff_idx).read() } == u8::MAX {
+            pix_idx += u8::MAX as usize;
+            diff_idx += 1;
+        }
+        pix_idx += unsafe { diff_ptr.add(diff_idx).read() } as usize;
+        diff_idx += 1;
+
+        let mut to_cpy = 0;
+        while unsafe { diff_ptr.add(diff_idx).read() } == u8::MAX {
+            to_cpy += u8::MAX as usize;
+            diff_idx += 1;
+        }
+        to_cpy += unsafe { diff_ptr.add(diff_idx).read() } as usize;
+        diff_idx += 1;
+
+        assert!(
+            diff_idx + to_cpy * 3 + 1 < diff.len(),
+            "copying: {}, diff.len(): {}",
+            diff_idx + to_cpy * 3 + 1,
+            diff.len()
+        );
+        for _ in 0..to_cpy {
+            unsafe {
+                std::ptr::copy_nonoverlapping(diff_ptr.add(diff_idx), buf_ptr.add(pix_idx * 4), 4)
+            }
+            diff_idx += 3;
+            pix_idx += 1;
+        }
+        pix_idx += 1;
+    }
+}
+
+/// # Safety
+///
+/// diff must be a slice produced by `comp::pack_bytes`, and buf must have the EXACT size of the
+/// frame it was produced from
+#[inline(always)]
+pub unsafe fn unpack_bytes_3channels(buf: &mut [u8], diff: &[u8]) {
+    assert!(
+        diff[diff.len() - 1] | diff[diff.len() - 2] == 0,
+        "Poorly formed BitPack"
+    );
+    // The final bytes are just padding to prevent us from going out of bounds
+    let len = diff.len() - 3;
+    let buf_ptr = buf.as_mut_ptr();
+    let diff_ptr = diff.as_ptr();
+
+    let mut diff_idx = 0;
+    let mut pix_idx = 0;
+    while diff_idx < len {
+        while unsafe { diff_ptr.add(diff_idx).read() } == u8::MAX {
+            pix_idx += u8::MAX as usize;
+            diff_idx += 1;
+        }
+        pix_idx += unsafe { diff_ptr.add(diff_idx).read() } as usize;
+        diff_idx += 1;
+
+        let mut to_cpy = 0;
+        while unsafe { diff_ptr.add(diff_idx).read() } == u8::MAX {
+            to_cpy += u8::MAX as usize;
+            diff_idx += 1;
+        }
+        to_cpy += unsafe { diff_ptr.add(diff_idx).read() } as usize;
+        diff_idx += 1;
+
+        assert!(
+            diff_idx + to_cpy * 3 <= diff.len(),
+            "diff_idx: {diff_idx}, to_copy: {to_cpy}, diff.len(): {}",
+            diff.len()
+        );
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                diff_ptr.add(diff_idx),
+                buf_ptr.add(pix_idx * 3),
+                to_cpy * 3,
+            );
+        }
+        diff_idx += to_cpy * 3;
+        pix_idx += to_cpy + 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[should_panic]
+    fn ub_unpack_bytes4_poorly_formed() {
+        let mut bytes = vec![u8::MAX; 9];
+        let diff = vec![u8::MAX; 18];
+        unsafe { unpack_bytes_4channels(&mut bytes, &diff) };
+    }
+
+    #[test]
+    #[should_panic]
+    fn ub_unpack_bytes3_poorly_formed() {
+        let mut bytes = vec![u8::MAX; 9];
+        let diff = vec![u8::MAX; 18];
+        unsafe { unpack_bytes_3channels(&mut bytes, &diff) };
+    }
+
+    #[test]
+    #[should_panic]
+    fn ub_unpack_bytes4_poorly_formed2() {
+        let mut bytes = vec![u8::MAX; 9];
+        let mut diff = vec![u8::MAX; 18];
+        diff[8] = 0;
+        diff[7] = 0;
+        unsafe { unpack_bytes_4channels(&mut bytes, &diff) };
+    }
+
+    #[test]
+    #[should_panic]
+    fn ub_unpack_bytes3_poorly_formed2() {
+        let mut bytes = vec![u8::MAX; 9];
+        let mut diff = vec![u8::MAX; 18];
+        diff[8] = 0;
+        diff[7] = 0;
+        unsafe { unpack_bytes_3channels(&mut bytes, &diff) };
+    }
+
+    #[test]
+    #[should_panic]
+    fn ub_unpack_bytes4_poorly_formed3() {
+        let mut bytes = vec![u8::MAX; 9];
+        let mut diff = vec![u8::MAX; 18];
+        diff[8] = 0;
+        diff[7] = 0;
+        diff[2] = 0;
+        unsafe { unpack_bytes_4channels(&mut bytes, &diff) };
+    }
+
+    #[test]
+    #[should_panic]
+    fn ub_unpack_bytes3_poorly_formed3() {
+        let mut bytes = vec![u8::MAX; 9];
+        let mut diff = vec![u8::MAX; 18];
+        diff[8] = 0;
+        diff[7] = 0;
+        diff[2] = 0;
+        unsafe { unpack_bytes_3channels(&mut bytes, &diff) };
+    }
+
+    mod proptests {
+        use super::*;
+        use crate::comp::pack_bytes;
+        use proptest::prelude::*;
+
+        fn frames() -> impl Strategy<Value = (Vec<u8>, Vec<u8>)> {
+            (1usize..600).prop_flat_map(|pixels| {
+                (
+                    proptest::collection::vec(any::<u8>(), pixels * 3),
+                    proptest::collection::vec(any::<u8>(), pixels * 3),
+                )
+            })
+        }
+
+        /// random bytes, biased towards small counts and the trailing padding so that some of
+        /// them end up being well formed
+        fn diffs() -> impl Strategy<Value = Vec<u8>> {
+            let byte = prop_oneof![0u8..4, any::<u8>()];
+            proptest::collection::vec(byte, 0..256).prop_map(|mut diff| {
+                diff.extend([0, 0]);
+                diff
+            })
+        }
+
+        proptest! {
+            #[test]
+            fn packed_bytes_should_be_well_formed((prev, cur) in frames()) {
+                let mut diff = Vec::new();
+                unsafe { pack_bytes(&prev, &cur, &mut diff) };
+                prop_assert!(diff.is_empty() || is_well_formed(&diff, cur.len() / 3));
+            }
+
+            #[test]
+            fn diffs_should_only_change_different_frames((prev, cur) in frames()) {
+                let mut diff = Vec::new();
+                unsafe { pack_bytes(&prev, &cur, &mut diff) };
+                if !diff.is_empty() {
+                    prop_assert!(changes(&prev, &diff, 3));
+                    prop_assert!(!changes(&cur, &diff, 3));
+
+                    let mut buf: Vec<u8> = cur.chunks_exact(3).flat_map(|p| [p[0], p[1], p[2], 0]).collect();
+                    prop_assert!(!changes(&buf, &diff, 4));
+                    unsafe { unpack_bytes_4channels(&mut buf, &diff) };
+                    prop_assert!(!changes(&buf, &diff, 4));
+                }
+            }
+
+            #[test]
+            fn well_formed_diffs_should_unpack_in_bounds(
+                diff in diffs(),
+                pixels in 0usize..128,
+            ) {
+                // we unpack into the start of a bigger buffer, and check the rest is untouched
+                const GUARD: usize = 64;
+                if is_well_formed(&diff, pixels) {
+                    let mut buf = vec![0; pixels * 3 + GUARD];
+                    unsafe { unpack_bytes_3channels(&mut buf[..pixels * 3], &diff) };
+                    prop_assert!(buf[pixels * 3..].iter().all(|&b| b == 0));
+
+                    let mut buf = vec![0; pixels * 4 + GUARD];
+                    unsafe { unpack_bytes_4channels(&mut buf[..pixels * 4], &diff) };
+                    prop_assert!(buf[pixels * 4..].iter().all(|&b| b == 0));
+
+                    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+                    if is_x86_feature_detected!("ssse3") {
+                        unsafe { ssse3::unpack_bytes_4channels(&mut buf[..pixels * 4], &diff) };
+                        prop_assert!(buf[pixels * 4..].iter().all(|&b| b == 0));
+                    }
+                }
+            }
+        }
+    }
+}
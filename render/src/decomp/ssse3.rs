@@ -57,7 +57,7 @@ pub(super) unsafe fn unpack_bytes_4channels(buf: &mut [u8], diff: &[u8]) {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::compression::pack_bytes;
+    use crate::comp::pack_bytes;
 
     fn buf_from(slice: &[u8]) -> Vec<u8> {
         let mut v = Vec::new();
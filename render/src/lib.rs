@@ -0,0 +1,21 @@
+//! # swww-render
+//!
+//! The parts of swww that actually compute pixels: the frame differences we compress animations
+//! with, the transitions and the engine running them, and the helpers used to compose the final
+//! canvas.
+//!
+//! Nothing in here knows about wayland, sockets, threads or clocks. Every function works on plain
+//! byte slices (`width * height * channels` bytes, row by row), and animated things take their
+//! progress as an argument instead of measuring time (the transition engine asks the `Target` it
+//! draws onto). This makes them easy to test, benchmark and reuse outside of the daemon.
+//!
+//! We only use `std` for floating point math and cpu feature detection, so this crate can be made
+//! `no_std` if we ever need it.
+
+pub mod color;
+pub mod comp;
+pub mod compose;
+pub mod cpu;
+pub mod decomp;
+pub mod transition;
+pub mod transitions;
@@ -0,0 +1,888 @@
+//! The transition engine
+//!
+//! This runs a transition from start to finish, moving its shape (from [`crate::transitions`])
+//! along its easing curve, and drawing every frame onto a [`Target`]. The daemon's target are the
+//! wallpapers of its outputs, paced by the clock. `swww preview-transition` draws onto an
+//! in-memory canvas instead, saving every frame to a file.
+//!
+//! Plugin transitions only exist in the daemon, so they have no [`Kind`] here. The daemon runs
+//! them itself, through [`Transition::redraw_from_old`].
+//!
+//! A transition can also [`Span`] several canvases, drawing each as a part of a larger one. Only
+//! the transitions with a position or a direction (wipe, grow, outer, ripple, clock and spiral)
+//! flow from one canvas to the next, the others run on every canvas on their own.
+
+use keyframe::{
+    functions::BezierCurve, keyframes, mint::Vector2, AnimationSequence, EasingFunction, Keyframe,
+};
+
+use crate::transitions::{self as shapes, BlendMode, Colorspace};
+
+/// What a transition draws onto
+pub trait Target {
+    /// Bytes per pixel of the canvases
+    fn channels(&self) -> usize;
+
+    /// Whether the canvases' pixels start with their blue byte instead of their red one
+    fn bgr(&self) -> bool;
+
+    /// The time, in seconds. Only the difference between two calls matters
+    fn now(&self) -> f64;
+
+    /// Calls `draw` with every canvas still in the transition
+    fn draw(&mut self, draw: &mut dyn FnMut(&mut [u8]));
+
+    /// Like `draw`, but also passes a copy of each canvas as it was the first time this was
+    /// called
+    fn redraw(&mut self, draw: &mut dyn FnMut(&mut [u8], &[u8]));
+
+    /// Shows what was drawn since the last call, once it is time for a new frame
+    fn present(&mut self);
+}
+
+impl<T: Target> Target for &mut T {
+    fn channels(&self) -> usize {
+        (**self).channels()
+    }
+
+    fn bgr(&self) -> bool {
+        (**self).bgr()
+    }
+
+    fn now(&self) -> f64 {
+        (**self).now()
+    }
+
+    fn draw(&mut self, draw: &mut dyn FnMut(&mut [u8])) {
+        (**self).draw(draw)
+    }
+
+    fn redraw(&mut self, draw: &mut dyn FnMut(&mut [u8], &[u8])) {
+        (**self).redraw(draw)
+    }
+
+    fn present(&mut self) {
+        (**self).present()
+    }
+}
+
+/// Where a canvas is within the larger one a transition spans, in the canvas' pixels
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Span {
+    /// the canvas' top left corner
+    pub origin: (i32, i32),
+    /// of the larger canvas
+    pub dimensions: (u32, u32),
+}
+
+#[derive(Clone, PartialEq)]
+pub enum Coord {
+    Pixel(f32),
+    Percent(f32),
+}
+
+#[derive(Clone, PartialEq)]
+pub struct Position {
+    pub x: Coord,
+    pub y: Coord,
+}
+
+impl Position {
+    #[must_use]
+    pub fn new(x: Coord, y: Coord) -> Self {
+        Self { x, y }
+    }
+
+    #[must_use]
+    pub fn to_pixel(&self, dim: (u32, u32), invert_y: bool) -> (f32, f32) {
+        let x = match self.x {
+            Coord::Pixel(x) => x,
+            Coord::Percent(x) => x * dim.0 as f32,
+        };
+
+        let y = match self.y {
+            Coord::Pixel(y) => {
+                if invert_y {
+                    y
+                } else {
+                    dim.1 as f32 - y
+                }
+            }
+            Coord::Percent(y) => {
+                if invert_y {
+                    y * dim.1 as f32
+                } else {
+                    (1.0 - y) * dim.1 as f32
+                }
+            }
+        };
+
+        (x, y)
+    }
+
+    #[must_use]
+    pub fn to_percent(&self, dim: (u32, u32)) -> (f32, f32) {
+        let x = match self.x {
+            Coord::Pixel(x) => x / dim.0 as f32,
+            Coord::Percent(x) => x,
+        };
+
+        let y = match self.y {
+            Coord::Pixel(y) => y / dim.1 as f32,
+            Coord::Percent(y) => y,
+        };
+
+        (x, y)
+    }
+}
+
+/// A keyframe of `Params::curve`, as (time, value, bezier). Times and values go from 0 to 1 over
+/// the transition, and the bezier eases from the keyframe to the next one
+pub type CurveKeyframe = (f32, f32, (f32, f32, f32, f32));
+
+/// The shapes a transition can take
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    None,
+    Simple,
+    Fade,
+    Wipe,
+    Grow,
+    Outer,
+    Wave,
+    Ripple,
+    Pixelate,
+    Blinds,
+    Dissolve,
+    Clock,
+    Cube,
+    Push,
+    ZoomBlur,
+    PixelSort,
+    Halftone,
+    DoubleExposure,
+    Sketch,
+    Shatter,
+    Spiral,
+    Checkerboard,
+    Luma,
+}
+
+/// Everything a transition needs besides its target and images. Each kind of transition only
+/// reads the fields it is named after (see the `Transition` request of swww's IPC for their units)
+#[derive(Clone)]
+pub struct Params {
+    pub kind: Kind,
+    /// in seconds
+    pub duration: f32,
+    /// how far `simple` moves each byte towards the new image every frame
+    pub step: u8,
+    /// in degrees
+    pub angle: f64,
+    pub pos: Position,
+    /// replaces `pos` for the `Grow` transition, unless it is empty
+    pub origins: Box<[Position]>,
+    pub bezier: (f32, f32, f32, f32),
+    /// stiffness, damping and mass of the spring that replaces the `bezier` curve, if any
+    pub spring: Option<(f32, f32, f32)>,
+    /// replaces both the `bezier` curve and the `spring`, unless it is empty
+    pub curve: Box<[CurveKeyframe]>,
+    pub wave: (f32, f32),
+    pub ripple: (f32, f32),
+    pub block_size: u16,
+    pub slats: (u16, bool),
+    pub seed: u32,
+    pub clock: (bool, f32),
+    pub cube: (bool, f32),
+    pub shatter: (u16, f32),
+    pub spiral: (f32, f32),
+    pub tile_size: u16,
+    pub softness: f32,
+    pub feather: f32,
+    pub colorspace: Colorspace,
+    pub pixel_sort: (f32, bool),
+    pub halftone: (u8, bool),
+    pub exposure: (BlendMode, f32, f32),
+    pub edge_threshold: f32,
+    pub invert_y: bool,
+}
+
+pub struct Transition<'a, T: Target> {
+    target: T,
+    dimensions: (u32, u32),
+    span: Option<Span>,
+    kind: Kind,
+    duration: f32,
+    step: u8,
+    angle: f64,
+    pos: Position,
+    /// replaces `pos` for the `Grow` transition, unless it is empty
+    origins: Box<[Position]>,
+    easing: Easing,
+    /// replaces `easing`, unless it is empty (see `Params::curve`)
+    curve: Box<[(f32, f32, BezierCurve)]>,
+    wave: (f32, f32),
+    ripple: (f32, f32),
+    block_size: u16,
+    slats: (u16, bool),
+    seed: u32,
+    clock: (bool, f32),
+    cube: (bool, f32),
+    shatter: (u16, f32),
+    spiral: (f32, f32),
+    tile_size: u16,
+    mask: Option<&'a [u8]>,
+    softness: f32,
+    feather: f32,
+    colorspace: Colorspace,
+    pixel_sort: (f32, bool),
+    halftone: (u8, bool),
+    exposure: (BlendMode, f32, f32),
+    edge_threshold: f32,
+    invert_y: bool,
+}
+
+impl<'a, T: Target> Transition<'a, T> {
+    /// `mask` is the grayscale mask of the 'luma' transition, with `dimensions`. With a `span`,
+    /// the transition's position and direction are those of the larger canvas
+    pub fn new(
+        target: T,
+        dimensions: (u32, u32),
+        params: Params,
+        mask: Option<&'a [u8]>,
+        span: Option<Span>,
+    ) -> Self {
+        let Params {
+            kind,
+            duration,
+            step,
+            angle,
+            pos,
+            origins,
+            bezier,
+            spring,
+            curve,
+            wave,
+            ripple,
+            block_size,
+            slats,
+            seed,
+            clock,
+            cube,
+            shatter,
+            spiral,
+            tile_size,
+            softness,
+            feather,
+            colorspace,
+            pixel_sort,
+            halftone,
+            exposure,
+            edge_threshold,
+            invert_y,
+        } = params;
+        Transition {
+            target,
+            dimensions,
+            span,
+            kind,
+            duration,
+            step,
+            angle,
+            pos,
+            origins,
+            easing: match spring {
+                Some((stiffness, damping, mass)) => Easing::Spring(Spring {
+                    stiffness: stiffness as f64,
+                    damping: damping as f64,
+                    mass: mass as f64,
+                    duration: duration as f64,
+                }),
+                None => Easing::Bezier(bezier_curve(bezier)),
+            },
+            curve: curve
+                .iter()
+                .map(|&(time, value, bezier)| (time, value, bezier_curve(bezier)))
+                .collect(),
+            wave,
+            ripple,
+            block_size,
+            slats,
+            seed,
+            clock,
+            cube,
+            shatter,
+            spiral,
+            tile_size,
+            mask,
+            softness,
+            feather,
+            colorspace,
+            pixel_sort,
+            halftone,
+            exposure,
+            edge_threshold,
+            invert_y,
+        }
+    }
+
+    /// Runs the transition until every canvas is equal to `new_img`
+    pub fn execute(mut self, new_img: &[u8]) {
+        match self.kind {
+            Kind::None => self.none(new_img),
+            Kind::Simple => self.simple(new_img),
+            Kind::Wipe => self.wipe(new_img),
+            Kind::Grow => self.grow(new_img),
+            Kind::Outer => self.outer(new_img),
+            Kind::Wave => self.wave(new_img),
+            Kind::Fade => self.fade(new_img),
+            Kind::Ripple => self.ripple(new_img),
+            Kind::Pixelate => self.pixelate(new_img),
+            Kind::Blinds => self.blinds(new_img),
+            Kind::Dissolve => self.dissolve(new_img),
+            Kind::Clock => self.clock(new_img),
+            Kind::Cube => self.cube(new_img),
+            Kind::Push => self.push(new_img),
+            Kind::ZoomBlur => self.zoom_blur(new_img),
+            Kind::PixelSort => self.pixel_sort(new_img),
+            Kind::Halftone => self.halftone(new_img),
+            Kind::DoubleExposure => self.double_exposure(new_img),
+            Kind::Sketch => self.sketch(new_img),
+            Kind::Shatter => self.shatter(new_img),
+            Kind::Spiral => self.spiral(new_img),
+            Kind::Checkerboard => self.checkerboard(new_img),
+            Kind::Luma => self.luma(new_img),
+        }
+    }
+
+    /// The dimensions of the canvas the transition's shapes are made for
+    fn shape_dimensions(&self) -> (u32, u32) {
+        self.span.map_or(self.dimensions, |span| span.dimensions)
+    }
+
+    /// Crops `shape` to our canvas, when it spans a larger one
+    fn crop<S>(&self, shape: S, crop: fn(S, (i32, i32), (u32, u32)) -> S) -> S {
+        match self.span {
+            Some(span) => crop(shape, span.origin, self.dimensions),
+            None => shape,
+        }
+    }
+
+    fn easing_seq(&self, start: f32, end: f32) -> (AnimationSequence<f32>, f64) {
+        if self.curve.is_empty() {
+            return (
+                keyframes![(start, 0.0, self.easing), (end, self.duration, self.easing)],
+                self.target.now(),
+            );
+        }
+        // the curve goes from 0 to 1, in both time and value
+        let keyframes: Vec<Keyframe<f32>> = self
+            .curve
+            .iter()
+            .map(|&(time, value, bezier)| {
+                Keyframe::new(start + (end - start) * value, time * self.duration, bezier)
+            })
+            .collect();
+        (AnimationSequence::from(keyframes), self.target.now())
+    }
+
+    /// Draws the frames of a transition whose shape moves from `start` to `end`, along the
+    /// easing curve, then finishes it with `simple`
+    fn animate(&mut self, new_img: &[u8], start: f32, end: f32, draw: impl Fn(&mut [u8], f32)) {
+        let (mut seq, begin) = self.easing_seq(start, end);
+
+        let mut value = start;
+        while self.target.now() - begin < seq.duration() {
+            self.target.draw(&mut |canvas| draw(canvas, value));
+            self.target.present();
+
+            value = seq.now();
+            seq.advance_to(self.target.now() - begin);
+        }
+        self.step = 4 + self.step / 4;
+        self.simple(new_img)
+    }
+
+    fn none(&mut self, new: &[u8]) {
+        self.target.draw(&mut |canvas| canvas.copy_from_slice(new));
+        self.target.present();
+    }
+
+    fn simple(&mut self, new_img: &[u8]) {
+        let step = self.step;
+        let mut done = false;
+        while !done {
+            done = true;
+            self.target
+                .draw(&mut |canvas| done = shapes::simple(canvas, new_img, step));
+            self.target.present();
+        }
+    }
+
+    fn fade(&mut self, new_img: &[u8]) {
+        let fade = shapes::Fade::new(self.colorspace, self.target.channels(), self.target.bgr());
+        let mut step = 0;
+        let (mut seq, begin) = self.easing_seq(0.0, 1.0);
+
+        while self.target.now() - begin < seq.duration() {
+            self.target
+                .draw(&mut |canvas| fade.draw(canvas, new_img, step));
+            self.target.present();
+            // springs overshoot, but there is nothing past the new image to fade to
+            step = (256.0 * seq.now().clamp(0.0, 1.0) as f64).trunc() as u16;
+            seq.advance_to(self.target.now() - begin);
+        }
+        self.step = 4 + self.step / 4;
+        self.simple(new_img)
+    }
+
+    fn wave(&mut self, new_img: &[u8]) {
+        let channels = self.target.channels();
+        let wave = shapes::Wave::new(self.dimensions, channels, self.angle, self.wave);
+        let step = self.step;
+        self.animate(
+            new_img,
+            wave.start() as f32,
+            wave.end() as f32,
+            |canvas, offset| wave.draw(canvas, new_img, offset as f64, step),
+        )
+    }
+
+    fn wipe(&mut self, new_img: &[u8]) {
+        let channels = self.target.channels();
+        let wipe =
+            shapes::Wipe::new(self.shape_dimensions(), channels, self.angle).feather(self.feather);
+        let wipe = self.crop(wipe, shapes::Wipe::crop);
+        let step = self.step;
+        self.animate(
+            new_img,
+            wipe.start() as f32,
+            wipe.end() as f32,
+            |canvas, offset| wipe.draw(canvas, new_img, offset as f64, step),
+        )
+    }
+
+    fn grow(&mut self, new_img: &[u8]) {
+        let channels = self.target.channels();
+        let dimensions = self.shape_dimensions();
+        let centers: Vec<_> = if self.origins.is_empty() {
+            vec![self.pos.to_pixel(dimensions, self.invert_y)]
+        } else {
+            self.origins
+                .iter()
+                .map(|origin| origin.to_pixel(dimensions, self.invert_y))
+                .collect()
+        };
+        let grow = shapes::Grow::from_points(dimensions, channels, &centers).feather(self.feather);
+        let grow = self.crop(grow, shapes::Grow::crop);
+        let step = self.step;
+        self.animate(new_img, 0.0, grow.end(), |canvas, radius| {
+            grow.draw(canvas, new_img, radius, step)
+        })
+    }
+
+    fn outer(&mut self, new_img: &[u8]) {
+        let channels = self.target.channels();
+        let center = self.pos.to_pixel(self.shape_dimensions(), self.invert_y);
+        let outer =
+            shapes::Outer::new(self.shape_dimensions(), channels, center).feather(self.feather);
+        let outer = self.crop(outer, shapes::Outer::crop);
+        let step = self.step;
+        self.animate(new_img, outer.start(), outer.end(), |canvas, radius| {
+            outer.draw(canvas, new_img, radius, step)
+        })
+    }
+
+    fn ripple(&mut self, new_img: &[u8]) {
+        let channels = self.target.channels();
+        let center = self.pos.to_pixel(self.shape_dimensions(), self.invert_y);
+        let ripple = shapes::Ripple::new(self.shape_dimensions(), channels, center, self.ripple);
+        let ripple = self.crop(ripple, shapes::Ripple::crop);
+        let step = self.step;
+        self.animate(new_img, ripple.start(), ripple.end(), |canvas, radius| {
+            ripple.draw(canvas, new_img, radius, step)
+        })
+    }
+
+    fn blinds(&mut self, new_img: &[u8]) {
+        let channels = self.target.channels();
+        let (slats, vertical) = self.slats;
+        let blinds = shapes::Blinds::new(self.dimensions, channels, slats, vertical);
+        let step = self.step;
+        self.animate(new_img, 0.0, blinds.end(), |canvas, open| {
+            blinds.draw(canvas, new_img, open, step)
+        })
+    }
+
+    fn clock(&mut self, new_img: &[u8]) {
+        let channels = self.target.channels();
+        let dimensions = self.shape_dimensions();
+        let center = self.pos.to_pixel(dimensions, self.invert_y);
+        let (clockwise, soft_edge) = self.clock;
+        let clock = shapes::Clock::new(dimensions, channels, center, clockwise, soft_edge);
+        let clock = self.crop(clock, shapes::Clock::crop);
+        let step = self.step;
+        self.animate(new_img, 0.0, clock.end(), |canvas, angle| {
+            clock.draw(canvas, new_img, angle, step)
+        })
+    }
+
+    fn spiral(&mut self, new_img: &[u8]) {
+        let channels = self.target.channels();
+        let dimensions = self.shape_dimensions();
+        let center = self.pos.to_pixel(dimensions, self.invert_y);
+        let (turns, thickness) = self.spiral;
+        let spiral = shapes::Spiral::new(dimensions, channels, center, turns, thickness);
+        let spiral = self.crop(spiral, shapes::Spiral::crop);
+        let step = self.step;
+        self.animate(new_img, 0.0, spiral.end(), |canvas, angle| {
+            spiral.draw(canvas, new_img, angle, step)
+        })
+    }
+
+    fn checkerboard(&mut self, new_img: &[u8]) {
+        let channels = self.target.channels();
+        let checkerboard =
+            shapes::Checkerboard::new(self.dimensions, channels, self.tile_size, self.angle);
+        let step = self.step;
+        self.animate(new_img, 0.0, checkerboard.end(), |canvas, progress| {
+            checkerboard.draw(canvas, new_img, progress, step)
+        })
+    }
+
+    fn luma(&mut self, new_img: &[u8]) {
+        let (width, height) = self.dimensions;
+        let Some(mask) = self
+            .mask
+            .filter(|mask| mask.len() == width as usize * height as usize)
+        else {
+            return self.simple(new_img);
+        };
+        let channels = self.target.channels();
+        let luma = shapes::Luma::new(self.dimensions, channels, mask, self.softness);
+        let step = self.step;
+        self.animate(new_img, 0.0, luma.end(), |canvas, threshold| {
+            luma.draw(canvas, new_img, threshold, step)
+        })
+    }
+
+    fn dissolve(&mut self, new_img: &[u8]) {
+        let channels = self.target.channels();
+        let dissolve = shapes::Dissolve::new(self.dimensions, channels, self.seed);
+        let step = self.step;
+        self.animate(new_img, 0.0, 1.0, |canvas, progress| {
+            dissolve.draw(canvas, new_img, progress, step)
+        })
+    }
+
+    fn halftone(&mut self, new_img: &[u8]) {
+        let channels = self.target.channels();
+        let (size, blue_noise) = self.halftone;
+        let halftone = shapes::Halftone::new(self.dimensions, channels, size, blue_noise);
+        let step = self.step;
+        self.animate(new_img, 0.0, 1.0, |canvas, progress| {
+            halftone.draw(canvas, new_img, progress, step)
+        })
+    }
+
+    fn sketch(&mut self, new_img: &[u8]) {
+        let channels = self.target.channels();
+        let sketch = shapes::Sketch::new(self.dimensions, channels, new_img, self.edge_threshold);
+        let step = self.step;
+        self.animate(new_img, 0.0, 1.0, |canvas, progress| {
+            sketch.draw(canvas, new_img, progress, step)
+        })
+    }
+
+    fn pixelate(&mut self, new_img: &[u8]) {
+        let channels = self.target.channels();
+        let pixelate = shapes::Pixelate::new(self.dimensions, channels, self.block_size);
+        self.redraw_from_old(new_img, |canvas, old, progress| {
+            pixelate.draw(canvas, old, new_img, progress);
+            true
+        })
+    }
+
+    fn cube(&mut self, new_img: &[u8]) {
+        let channels = self.target.channels();
+        let (vertical_axis, fov) = self.cube;
+        let cube = shapes::Cube::new(self.dimensions, channels, vertical_axis, fov);
+        self.redraw_from_old(new_img, |canvas, old, progress| {
+            cube.draw(canvas, old, new_img, progress);
+            true
+        })
+    }
+
+    fn push(&mut self, new_img: &[u8]) {
+        let channels = self.target.channels();
+        let push = shapes::Push::new(self.dimensions, channels, self.angle);
+        self.redraw_from_old(new_img, |canvas, old, progress| {
+            push.draw(canvas, old, new_img, progress);
+            true
+        })
+    }
+
+    fn zoom_blur(&mut self, new_img: &[u8]) {
+        let channels = self.target.channels();
+        let center = self.pos.to_pixel(self.dimensions, self.invert_y);
+        let mut zoom = shapes::ZoomBlur::new(self.dimensions, channels, center);
+        self.redraw_from_old(new_img, |canvas, old, progress| {
+            zoom.draw(canvas, old, new_img, progress);
+            true
+        })
+    }
+
+    fn pixel_sort(&mut self, new_img: &[u8]) {
+        let channels = self.target.channels();
+        let (threshold, vertical) = self.pixel_sort;
+        let mut sort = shapes::PixelSort::new(self.dimensions, channels, threshold, vertical);
+        self.redraw_from_old(new_img, |canvas, old, progress| {
+            sort.draw(canvas, old, new_img, progress);
+            true
+        })
+    }
+
+    fn double_exposure(&mut self, new_img: &[u8]) {
+        let (mode, strength, length) = self.exposure;
+        let exposure = shapes::DoubleExposure::new(mode, strength, length);
+        self.redraw_from_old(new_img, |canvas, old, progress| {
+            exposure.draw(canvas, old, new_img, progress);
+            true
+        })
+    }
+
+    fn shatter(&mut self, new_img: &[u8]) {
+        let channels = self.target.channels();
+        let center = self.pos.to_pixel(self.dimensions, self.invert_y);
+        let (shards, gravity) = self.shatter;
+        let shatter = shapes::Shatter::new(
+            self.dimensions,
+            channels,
+            center,
+            shards,
+            gravity,
+            self.duration,
+            self.seed,
+        );
+        self.redraw_from_old(new_img, |canvas, old, progress| {
+            shatter.draw(canvas, old, new_img, progress);
+            true
+        })
+    }
+
+    /// For the transitions that draw every frame from the old image, given how far along the
+    /// easing curve they are. Once `draw` returns false, we skip straight to `simple`
+    pub fn redraw_from_old(
+        &mut self,
+        new_img: &[u8],
+        mut draw: impl FnMut(&mut [u8], &[u8], f32) -> bool,
+    ) {
+        let (mut seq, begin) = self.easing_seq(0.0, 1.0);
+
+        let mut progress = 0.0;
+        let mut stopped = false;
+        while !stopped && self.target.now() - begin < seq.duration() {
+            self.target.redraw(&mut |canvas, old| {
+                stopped = stopped || !draw(canvas, old, progress);
+            });
+            self.target.present();
+
+            progress = seq.now();
+            seq.advance_to(self.target.now() - begin);
+        }
+        self.step = 4 + self.step / 4;
+        self.simple(new_img)
+    }
+}
+
+fn bezier_curve((x1, y1, x2, y2): (f32, f32, f32, f32)) -> BezierCurve {
+    BezierCurve::from(Vector2 { x: x1, y: y1 }, Vector2 { x: x2, y: y2 })
+}
+
+#[derive(Clone, Copy)]
+enum Easing {
+    Bezier(BezierCurve),
+    Spring(Spring),
+}
+
+impl EasingFunction for Easing {
+    fn y(&self, x: f64) -> f64 {
+        match self {
+            Self::Bezier(bezier) => bezier.y(x),
+            Self::Spring(spring) => spring.y(x),
+        }
+    }
+}
+
+/// A damped spring pulling the transition from 0 to 1, starting at rest. Unlike the bezier
+/// curves, it moves in real time instead of being stretched over the transition's duration, so
+/// it may overshoot 1, and it may not have settled by the time the transition ends
+#[derive(Clone, Copy)]
+struct Spring {
+    stiffness: f64,
+    damping: f64,
+    mass: f64,
+    /// of the transition, in seconds
+    duration: f64,
+}
+
+impl Spring {
+    /// where the spring is `t` seconds after it was released
+    fn position(&self, t: f64) -> f64 {
+        let omega = (self.stiffness / self.mass).sqrt();
+        let zeta = self.damping / (2.0 * (self.stiffness * self.mass).sqrt());
+        // how far we still are from 1
+        let displacement = if zeta < 1.0 {
+            let omega_d = omega * (1.0 - zeta * zeta).sqrt();
+            (-zeta * omega * t).exp()
+                * ((omega_d * t).cos() + zeta * omega / omega_d * (omega_d * t).sin())
+        } else if zeta > 1.0 {
+            let root = (zeta * zeta - 1.0).sqrt();
+            let (r1, r2) = (-omega * (zeta - root), -omega * (zeta + root));
+            (r2 * (r1 * t).exp() - r1 * (r2 * t).exp()) / (r2 - r1)
+        } else {
+            (-omega * t).exp() * (1.0 + omega * t)
+        };
+        1.0 - displacement
+    }
+}
+
+impl EasingFunction for Spring {
+    fn y(&self, x: f64) -> f64 {
+        self.position(x * self.duration)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spring(damping: f64) -> Spring {
+        Spring {
+            stiffness: 170.0,
+            damping,
+            mass: 1.0,
+            duration: 3.0,
+        }
+    }
+
+    #[test]
+    fn springs_should_start_at_rest_and_settle_at_the_end() {
+        for damping in [0.0, 12.0, 26.0, 2.0 * 170f64.sqrt(), 60.0] {
+            let spring = spring(damping);
+            assert!(spring.y(0.0).abs() < 1e-9, "{damping}");
+            assert!(spring.y(1e-4).abs() < 1e-3, "{damping}");
+            if damping > 0.0 {
+                assert!((spring.y(1.0) - 1.0).abs() < 1e-3, "{damping}");
+            }
+        }
+    }
+
+    #[test]
+    fn only_underdamped_springs_should_overshoot() {
+        let samples = |spring: Spring| (0..=1000).map(move |i| spring.y(i as f64 / 1000.0));
+        assert!(samples(spring(12.0)).any(|y| y > 1.1));
+        for damping in [2.0 * 170f64.sqrt(), 60.0] {
+            assert!(
+                samples(spring(damping)).all(|y| y <= 1.0 + 1e-9),
+                "{damping}"
+            );
+        }
+    }
+
+    /// draws on a single canvas, one frame every 1/30th of a second
+    struct Canvas {
+        canvas: Vec<u8>,
+        old: Option<Vec<u8>>,
+        frames: u32,
+    }
+
+    impl Target for Canvas {
+        fn channels(&self) -> usize {
+            3
+        }
+
+        fn bgr(&self) -> bool {
+            false
+        }
+
+        fn now(&self) -> f64 {
+            self.frames as f64 / 30.0
+        }
+
+        fn draw(&mut self, draw: &mut dyn FnMut(&mut [u8])) {
+            draw(&mut self.canvas)
+        }
+
+        fn redraw(&mut self, draw: &mut dyn FnMut(&mut [u8], &[u8])) {
+            let old = self.old.get_or_insert_with(|| self.canvas.clone());
+            draw(&mut self.canvas, old)
+        }
+
+        fn present(&mut self) {
+            self.frames += 1;
+        }
+    }
+
+    #[test]
+    fn transitions_should_end_on_the_new_image() {
+        let new = [200, 100, 50].repeat(16 * 9);
+        for kind in [
+            Kind::None,
+            Kind::Simple,
+            Kind::Fade,
+            Kind::Wipe,
+            Kind::Grow,
+            Kind::Cube,
+            Kind::Push,
+            Kind::ZoomBlur,
+            Kind::PixelSort,
+            Kind::Halftone,
+            Kind::DoubleExposure,
+            Kind::Sketch,
+        ] {
+            let mut canvas = Canvas {
+                canvas: vec![0; new.len()],
+                old: None,
+                frames: 0,
+            };
+            let params = Params {
+                kind,
+                duration: 1.0,
+                step: 90,
+                angle: 45.0,
+                pos: Position::new(Coord::Percent(0.5), Coord::Percent(0.5)),
+                origins: Box::new([
+                    Position::new(Coord::Percent(0.0), Coord::Percent(0.0)),
+                    Position::new(Coord::Pixel(16.0), Coord::Pixel(9.0)),
+                ]),
+                bezier: (0.54, 0.0, 0.34, 0.99),
+                spring: None,
+                curve: Box::new([]),
+                wave: (20.0, 20.0),
+                ripple: (20.0, 40.0),
+                block_size: 8,
+                slats: (4, false),
+                seed: 0,
+                clock: (true, 0.0),
+                cube: (true, 60.0),
+                shatter: (8, 100.0),
+                spiral: (3.0, 4.0),
+                tile_size: 4,
+                softness: 0.0,
+                feather: 0.0,
+                colorspace: Colorspace::Oklab,
+                pixel_sort: (0.25, false),
+                halftone: (4, true),
+                exposure: (BlendMode::Multiply, 1.0, 0.5),
+                edge_threshold: 0.2,
+                invert_y: false,
+            };
+            Transition::new(&mut canvas, (16, 9), params, None, None).execute(&new);
+            assert_eq!(canvas.canvas, new, "{kind:?}");
+            if kind != Kind::None {
+                assert!(canvas.frames > 1, "{kind:?}");
+            }
+        }
+    }
+}
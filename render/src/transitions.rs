@@ -0,0 +1,3024 @@
+//! The shapes of our transitions
+//!
+//! Each transition draws one of its frames at a time: the caller decides how far along it is
+//! (usually by running the transition's bezier curve against the clock) and passes that in.
+//! Pixels inside the transition's shape are moved `step` closer to the new image, through
+//! `compose::change_byte`. Once the shape covers the whole screen, callers should keep calling
+//! `simple` until the canvas is equal to the new image.
+//!
+//! All functions take the `canvas` being drawn and the `new` image, both with exactly
+//! `width * height * channels` bytes.
+//!
+//! Some shapes can also be `crop`ped: created for a larger canvas, then drawn only where a smaller
+//! one sits within it. This is how a transition flows across several outputs, as if they were a
+//! single screen.
+
+use crate::{color::Luts, compose::change_byte};
+
+#[inline(always)]
+fn assert_dimensions(canvas: &[u8], new: &[u8], width: usize, height: usize, channels: usize) {
+    assert_eq!(
+        canvas.len(),
+        new.len(),
+        "canvas and image have different sizes"
+    );
+    assert_eq!(
+        canvas.len(),
+        width * height * channels,
+        "canvas does not have the expected dimensions"
+    );
+}
+
+/// Moves the bytes of `canvas` in `span` `step` closer to `new`. This is how transitions that
+/// draw whole runs of a line at once (like `Wipe` and `Blinds`) blend them
+#[inline(always)]
+fn change_span(canvas: &mut [u8], new: &[u8], span: std::ops::Range<usize>, step: u8) {
+    for (old, new) in canvas[span.clone()].iter_mut().zip(&new[span]) {
+        change_byte(step, old, new);
+    }
+}
+
+/// The step of a pixel `depth` pixels behind the edge of a shape whose edge is `feather` pixels
+/// wide: the closer to the edge, the slower it moves towards the new image
+#[inline(always)]
+fn feathered(step: u8, depth: f32, feather: f32) -> u8 {
+    if depth >= feather {
+        step
+    } else {
+        ((step as f32 * depth.max(0.0) / feather) as u8).max(1)
+    }
+}
+
+/// Moves every byte of `canvas` `step` closer to `new`. Returns whether they are now equal
+pub fn simple(canvas: &mut [u8], new: &[u8], step: u8) -> bool {
+    assert_eq!(
+        canvas.len(),
+        new.len(),
+        "canvas and image have different sizes"
+    );
+    for (old, new) in canvas.iter_mut().zip(new) {
+        change_byte(step, old, new);
+    }
+    canvas == new
+}
+
+/// Blends `new` into `canvas`, with a weight of `step` out of 256
+pub fn fade(canvas: &mut [u8], new: &[u8], step: u16) {
+    assert_eq!(
+        canvas.len(),
+        new.len(),
+        "canvas and image have different sizes"
+    );
+    for (old, new) in canvas.iter_mut().zip(new) {
+        let x = *old as u16 * (256 - step);
+        let y = *new as u16 * step;
+        *old = ((x + y) >> 8) as u8;
+    }
+}
+
+/// The color space `Fade` blends colors in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Colorspace {
+    /// the bytes themselves, like `fade` does
+    Srgb,
+    /// linear light, which keeps the colors in between as bright as the ones we fade from and to
+    Linear,
+    /// OKLab, which also keeps their saturation, so fades between saturated colors are not muddy
+    Oklab,
+}
+
+/// Like `fade`, but blending colors in any `Colorspace`
+pub struct Fade {
+    colorspace: Colorspace,
+    channels: usize,
+    /// where red, green and blue are within a pixel
+    rgb: [usize; 3],
+    luts: Option<Luts>,
+}
+
+impl Fade {
+    /// `bgr` is whether pixels start with their blue byte instead of their red one. Bytes after
+    /// the third one in each pixel are padding
+    pub fn new(colorspace: Colorspace, channels: usize, bgr: bool) -> Self {
+        Self {
+            colorspace,
+            channels,
+            rgb: if bgr { [2, 1, 0] } else { [0, 1, 2] },
+            luts: (colorspace != Colorspace::Srgb).then(Luts::new),
+        }
+    }
+
+    /// Blends `new` into `canvas`, with a weight of `step` out of 256
+    pub fn draw(&self, canvas: &mut [u8], new: &[u8], step: u16) {
+        let luts = match &self.luts {
+            Some(luts) => luts,
+            None => return fade(canvas, new, step),
+        };
+        assert_eq!(
+            canvas.len(),
+            new.len(),
+            "canvas and image have different sizes"
+        );
+        let t = step as f32 / 256.0;
+        match self.colorspace {
+            Colorspace::Linear => {
+                for (old, new) in canvas.iter_mut().zip(new) {
+                    if *old != *new {
+                        let (from, to) = (luts.srgb_to_linear(*old), luts.srgb_to_linear(*new));
+                        *old = luts.linear_to_srgb(from + (to - from) * t);
+                    }
+                }
+            }
+            _ => {
+                let [r, g, b] = self.rgb;
+                let pixels = canvas.chunks_exact_mut(self.channels);
+                for (old, new) in pixels.zip(new.chunks_exact(self.channels)) {
+                    if old == new {
+                        continue;
+                    }
+                    let from = luts.srgb_to_oklab([old[r], old[g], old[b]]);
+                    let to = luts.srgb_to_oklab([new[r], new[g], new[b]]);
+                    let mixed = std::array::from_fn(|i| from[i] + (to[i] - from[i]) * t);
+                    [old[r], old[g], old[b]] = luts.oklab_to_srgb(mixed);
+                    old[3..].copy_from_slice(&new[3..]);
+                }
+            }
+        }
+    }
+}
+
+/// A straight line crossing the screen at an angle
+pub struct Wipe {
+    width: usize,
+    height: usize,
+    channels: usize,
+    center: (f64, f64),
+    circle_radius: f64,
+    a: f64,
+    b: f64,
+    start: f64,
+    end: f64,
+    feather: f32,
+}
+
+impl Wipe {
+    /// `angle` is in degrees
+    pub fn new(dimensions: (u32, u32), channels: usize, angle: f64) -> Self {
+        let (width, height) = dimensions;
+        let center = ((width / 2) as f64, (height / 2) as f64);
+        let screen_diag = ((width as f64).powi(2) + (height as f64).powi(2)).sqrt();
+
+        let circle_radius = screen_diag / 2.0;
+        let angle = angle.to_radians();
+        let start = {
+            let (x, y) = angle.sin_cos();
+            (x.abs() * width as f64 + y.abs() * height as f64) * 2.0
+        };
+
+        Self {
+            width: width as usize,
+            height: height as usize,
+            channels,
+            center,
+            circle_radius,
+            a: circle_radius * angle.cos(),
+            b: circle_radius * angle.sin(),
+            start,
+            end: circle_radius.powi(2) * 2.0,
+            feather: 0.0,
+        }
+    }
+
+    /// Blends the line's edge over `feather` pixels behind it, instead of changing pixels as soon
+    /// as the line reaches them
+    pub fn feather(self, feather: f32) -> Self {
+        let feather = feather.max(0.0);
+        Self {
+            feather,
+            end: self.end + feather as f64 * self.circle_radius,
+            ..self
+        }
+    }
+
+    /// the line's offset at the beginning of the transition
+    pub fn start(&self) -> f64 {
+        self.start
+    }
+
+    /// the line's offset when it has crossed the whole screen
+    pub fn end(&self) -> f64 {
+        self.end
+    }
+
+    /// Only draws the part of the screen at `origin`, with `dimensions`
+    pub fn crop(self, origin: (i32, i32), dimensions: (u32, u32)) -> Self {
+        // lines are counted from the bottom of the screen
+        let bottom = self.height as f64 - (origin.1 as f64 + dimensions.1 as f64);
+        Self {
+            width: dimensions.0 as usize,
+            height: dimensions.1 as usize,
+            center: (self.center.0 - origin.0 as f64, self.center.1 - bottom),
+            ..self
+        }
+    }
+
+    pub fn draw(&self, canvas: &mut [u8], new: &[u8], offset: f64, step: u8) {
+        let Self {
+            width,
+            height,
+            channels,
+            center,
+            circle_radius,
+            a,
+            b,
+            feather,
+            ..
+        } = *self;
+        assert_dimensions(canvas, new, width, height, channels);
+        let stride = width * channels;
+
+        // line formula: (x-h)*a + (y-k)*b + C = r^2
+        // https://www.desmos.com/calculator/vpvzk12yar
+        for line in 0..height {
+            let y = ((height - line) as f64 - center.1) * b;
+            let edge = |offset: f64| (circle_radius.powi(2) - y - offset) / a + center.0;
+            let x = edge(offset);
+            // pixels past this column are at least `feather` pixels behind the line
+            let solid = edge(offset - feather as f64 * circle_radius).min(width as f64) as usize;
+            let edge = x.min(width as f64) as usize;
+            let (solid, feathered_cols) = if a.is_sign_negative() {
+                (0..solid, solid..edge)
+            } else {
+                (solid..width, edge..solid)
+            };
+            let begin = line * stride;
+            change_span(
+                canvas,
+                new,
+                begin + solid.start * channels..begin + solid.end * channels,
+                step,
+            );
+            for col in feathered_cols {
+                let depth = ((col as f64 - x) * a / circle_radius) as f32;
+                let i = begin + col * channels;
+                change_span(
+                    canvas,
+                    new,
+                    i..i + channels,
+                    feathered(step, depth, feather),
+                );
+            }
+        }
+    }
+}
+
+/// Like `Wipe`, but the line is a wave
+pub struct Wave {
+    width: usize,
+    height: usize,
+    channels: usize,
+    center: (f64, f64),
+    sin: f64,
+    cos: f64,
+    scale: (f64, f64),
+    circle_radius: f64,
+    a: f64,
+    b: f64,
+    start: f64,
+    end: f64,
+}
+
+impl Wave {
+    /// `angle` is in degrees, and `wave` is the width and height of each wave
+    pub fn new(dimensions: (u32, u32), channels: usize, angle: f64, wave: (f32, f32)) -> Self {
+        let (width, height) = dimensions;
+        let center = ((width / 2) as f64, (height / 2) as f64);
+        let screen_diag = ((width as f64).powi(2) + (height as f64).powi(2)).sqrt();
+
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let circle_radius = screen_diag / 2.0;
+
+        Self {
+            width: width as usize,
+            height: height as usize,
+            channels,
+            center,
+            sin,
+            cos,
+            scale: (wave.0 as f64, wave.1 as f64),
+            circle_radius,
+            a: circle_radius * cos,
+            b: circle_radius * sin,
+            start: (sin.abs() * width as f64 + cos.abs() * height as f64) * 2.0,
+            end: circle_radius.powi(2) * 2.0,
+        }
+    }
+
+    /// the wave's offset at the beginning of the transition
+    pub fn start(&self) -> f64 {
+        self.start
+    }
+
+    /// the wave's offset when it has crossed the whole screen
+    pub fn end(&self) -> f64 {
+        self.end
+    }
+
+    pub fn draw(&self, canvas: &mut [u8], new: &[u8], offset: f64, step: u8) {
+        let Self {
+            width,
+            height,
+            channels,
+            center,
+            sin,
+            cos,
+            scale: (scale_x, scale_y),
+            circle_radius,
+            a,
+            b,
+            ..
+        } = *self;
+        assert_dimensions(canvas, new, width, height, channels);
+        let stride = width * channels;
+
+        // graph: https://www.desmos.com/calculator/wunde042es
+        //
+        // checks if a pixel is to the left or right of the line
+        let is_low = |x: f64, y: f64| {
+            let x = x - center.0;
+            let y = y - center.1;
+
+            let lhs = y * sin - x * cos;
+
+            let f = ((x * sin + y * cos) / scale_x).sin() * scale_y;
+            let rhs = f - circle_radius + offset / circle_radius;
+            lhs <= rhs
+        };
+
+        // divide in 3 sections: the one we know will not be drawn to, the one we know
+        // WILL be drawn to, and the one we need to do a more expensive check on.
+        // We do this by creating 2 lines: the first tangential to the wave's peaks,
+        // the second to its valeys. In-between is where we have to do the more
+        // expensive checks
+        for line in 0..height {
+            let y = ((height - line) as f64 - center.1 - scale_y * sin) * b;
+            let x = (circle_radius.powi(2) - y - offset) / a + center.0 + scale_y * cos;
+            let x = x.min(width as f64);
+            let (col_begin, col_end) = if a.is_sign_negative() {
+                (0usize, x as usize * channels)
+            } else {
+                (x as usize * channels, stride)
+            };
+            for col in col_begin..col_end {
+                // SAFETY: x <= width, so col < stride, and we've checked the slices' sizes above
+                let old = unsafe { canvas.get_unchecked_mut(line * stride + col) };
+                let new = unsafe { new.get_unchecked(line * stride + col) };
+                change_byte(step, old, new);
+            }
+            let old_x = x;
+            let y = ((height - line) as f64 - center.1 + scale_y * sin) * b;
+            let x = (circle_radius.powi(2) - y - offset) / a + center.0 - scale_y * cos;
+            let x = x.min(width as f64);
+            let (col_begin, col_end) = if old_x < x {
+                (old_x as usize, x as usize)
+            } else {
+                (x as usize, old_x as usize)
+            };
+            for col in col_begin..col_end {
+                if is_low(col as f64, line as f64) {
+                    let i = line * stride + col * channels;
+                    for j in 0..channels {
+                        // SAFETY: both x and old_x are <= width, so col < width
+                        let old = unsafe { canvas.get_unchecked_mut(i + j) };
+                        let new = unsafe { new.get_unchecked(i + j) };
+                        change_byte(step, old, new);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// distance from `center` to the farthest corner of the screen
+fn farthest_corner(dimensions: (u32, u32), center: (f32, f32)) -> f32 {
+    let (width, height) = (dimensions.0 as f32, dimensions.1 as f32);
+    let (mut x, mut y) = center;
+    if x < width / 2.0 {
+        x = width - 1.0 - x;
+    }
+    if y < height / 2.0 {
+        y = height - 1.0 - y;
+    }
+    f32::sqrt(x.powi(2) + y.powi(2))
+}
+
+/// Slats that open to reveal the new image, like venetian blinds
+pub struct Blinds {
+    width: usize,
+    height: usize,
+    channels: usize,
+    vertical: bool,
+    slat_size: usize,
+}
+
+impl Blinds {
+    /// There are `slats` horizontal slats (stacked from top to bottom), or vertical ones if
+    /// `vertical` is set
+    pub fn new(dimensions: (u32, u32), channels: usize, slats: u16, vertical: bool) -> Self {
+        let (width, height) = (dimensions.0 as usize, dimensions.1 as usize);
+        let extent = if vertical { width } else { height };
+        let slats = (slats as usize).clamp(1, extent.max(1));
+        Self {
+            width,
+            height,
+            channels,
+            vertical,
+            slat_size: extent.div_ceil(slats).max(1),
+        }
+    }
+
+    /// how far open the slats are once they reveal the whole screen, in pixels
+    pub fn end(&self) -> f32 {
+        self.slat_size as f32
+    }
+
+    /// `open` is how many pixels of each slat have been revealed, from its top (or left) edge
+    pub fn draw(&self, canvas: &mut [u8], new: &[u8], open: f32, step: u8) {
+        let Self {
+            width,
+            height,
+            channels,
+            vertical,
+            slat_size,
+        } = *self;
+        assert_dimensions(canvas, new, width, height, channels);
+        let stride = width * channels;
+        let open = (open.max(0.0) as usize).min(slat_size);
+        if open == 0 {
+            return;
+        }
+
+        for line in 0..height {
+            let begin = line * stride;
+            if !vertical {
+                if line % slat_size < open {
+                    change_span(canvas, new, begin..begin + stride, step);
+                }
+                continue;
+            }
+            for slat in (0..width).step_by(slat_size) {
+                let end = width.min(slat + open);
+                change_span(
+                    canvas,
+                    new,
+                    begin + slat * channels..begin + end * channels,
+                    step,
+                );
+            }
+        }
+    }
+}
+
+/// Square tiles flipping to the new image in a wave crossing the screen at an angle: first every
+/// other tile, like the dark squares of a checkerboard, and then the rest
+pub struct Checkerboard {
+    width: usize,
+    height: usize,
+    channels: usize,
+    tile_size: usize,
+    columns: usize,
+    /// when each tile flips, row by row
+    flips: Box<[f32]>,
+}
+
+impl Checkerboard {
+    /// `tile_size` is in pixels, and `angle` is in degrees, the same way as `Wipe`'s
+    pub fn new(dimensions: (u32, u32), channels: usize, tile_size: u16, angle: f64) -> Self {
+        let (width, height) = (dimensions.0 as usize, dimensions.1 as usize);
+        let tile_size = (tile_size as usize).max(1);
+        let columns = width.div_ceil(tile_size);
+        let rows = height.div_ceil(tile_size);
+
+        // how far along the wave's direction each tile's center is
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let distance = |row: usize, col: usize| {
+            let x = (col as f64 + 0.5) * tile_size as f64;
+            let y = (row as f64 + 0.5) * tile_size as f64;
+            (y * sin - x * cos) as f32
+        };
+        let mut flips: Box<[f32]> = (0..rows * columns)
+            .map(|i| distance(i / columns, i % columns))
+            .collect();
+        let first = flips.iter().copied().fold(f32::INFINITY, f32::min);
+        let last = flips.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        let span = last - first;
+        for (i, flip) in flips.iter_mut().enumerate() {
+            let wave = if span > 0.0 {
+                (*flip - first) / span
+            } else {
+                0.0
+            };
+            *flip = wave + ((i / columns + i % columns) % 2) as f32;
+        }
+
+        Self {
+            width,
+            height,
+            channels,
+            tile_size,
+            columns,
+            flips,
+        }
+    }
+
+    /// the progress at which the second wave has crossed the whole screen
+    pub fn end(&self) -> f32 {
+        2.0
+    }
+
+    /// `progress` goes from 0 to 1 while the first wave crosses the screen, and from 1 to 2 for
+    /// the second one
+    pub fn draw(&self, canvas: &mut [u8], new: &[u8], progress: f32, step: u8) {
+        let Self {
+            width,
+            height,
+            channels,
+            tile_size,
+            columns,
+            ..
+        } = *self;
+        assert_dimensions(canvas, new, width, height, channels);
+        if progress <= 0.0 {
+            return;
+        }
+        let stride = width * channels;
+
+        for line in 0..height {
+            let flips = &self.flips[line / tile_size * columns..][..columns];
+            let begin = line * stride;
+            for (col, &flip) in flips.iter().enumerate() {
+                if progress < flip {
+                    continue;
+                }
+                let start = col * tile_size;
+                let end = width.min(start + tile_size);
+                change_span(
+                    canvas,
+                    new,
+                    begin + start * channels..begin + end * channels,
+                    step,
+                );
+            }
+        }
+    }
+}
+
+/// Circles growing from one or more points, merging as they meet
+pub struct Grow {
+    width: usize,
+    height: usize,
+    channels: usize,
+    centers: Vec<(isize, isize)>,
+    end: f32,
+    feather: f32,
+}
+
+impl Grow {
+    /// `center` is in pixels
+    pub fn new(dimensions: (u32, u32), channels: usize, center: (f32, f32)) -> Self {
+        Self::from_points(dimensions, channels, &[center])
+    }
+
+    /// A circle grows from each of `centers`, which are in pixels
+    pub fn from_points(dimensions: (u32, u32), channels: usize, centers: &[(f32, f32)]) -> Self {
+        assert!(!centers.is_empty(), "grow needs at least one center");
+        let farthest = centers
+            .iter()
+            .map(|&center| farthest_corner(dimensions, center))
+            .fold(f32::INFINITY, f32::min);
+        let end = if centers.len() == 1 {
+            farthest
+        } else {
+            farthest.min(farthest_from_all(dimensions, centers))
+        };
+        Self {
+            width: dimensions.0 as usize,
+            height: dimensions.1 as usize,
+            channels,
+            centers: centers
+                .iter()
+                .map(|&(x, y)| (x as isize, y as isize))
+                .collect(),
+            end,
+            feather: 0.0,
+        }
+    }
+
+    /// Blends the circle's edge over `feather` pixels inside it, instead of changing pixels as
+    /// soon as the circle reaches them
+    pub fn feather(self, feather: f32) -> Self {
+        let feather = feather.max(0.0);
+        Self {
+            feather,
+            end: self.end + feather,
+            ..self
+        }
+    }
+
+    /// the circles' radius once they cover the whole screen
+    pub fn end(&self) -> f32 {
+        self.end
+    }
+
+    /// Only draws the part of the screen at `origin`, with `dimensions`
+    pub fn crop(self, origin: (i32, i32), dimensions: (u32, u32)) -> Self {
+        Self {
+            width: dimensions.0 as usize,
+            height: dimensions.1 as usize,
+            centers: self
+                .centers
+                .iter()
+                .map(|&(x, y)| (x - origin.0 as isize, y - origin.1 as isize))
+                .collect(),
+            ..self
+        }
+    }
+
+    pub fn draw(&self, canvas: &mut [u8], new: &[u8], radius: f32, step: u8) {
+        let Self {
+            width,
+            height,
+            channels,
+            ref centers,
+            feather,
+            ..
+        } = *self;
+        assert_dimensions(canvas, new, width, height, channels);
+        let stride = width * channels;
+        // the centers may be outside of the screen when we are cropped
+        let clamp = |x: isize, max: usize| x.clamp(0, max as isize) as usize;
+        let lines = |center_y: isize| {
+            clamp(center_y - radius as isize, height)..clamp(center_y + radius as isize, height)
+        };
+
+        let line_begin = centers.iter().map(|c| lines(c.1).start).min().unwrap_or(0);
+        let line_end = centers.iter().map(|c| lines(c.1).end).max().unwrap_or(0);
+        // pixels within this radius are at least `feather` pixels inside a circle
+        let solid = (radius - feather).max(0.0);
+
+        // the columns each circle covers on a line, and the ones where it is solid
+        let mut edges = Vec::with_capacity(centers.len());
+        let mut solids = Vec::with_capacity(centers.len());
+        for line in line_begin..line_end {
+            edges.clear();
+            solids.clear();
+            // to plot half a circle with radius r, we do sqrt(r^2 - x^2)
+            for &(center_x, center_y) in centers {
+                if !lines(center_y).contains(&line) {
+                    continue;
+                }
+                let dy = center_y as f32 - line as f32;
+                let offset = (radius.powi(2) - dy.powi(2)).sqrt() as isize;
+                let solid_offset = (solid.powi(2) - dy.powi(2)).sqrt() as isize;
+                edges.push(clamp(center_x - offset, width)..clamp(center_x + offset, width));
+                solids.push(
+                    clamp(center_x - solid_offset, width)..clamp(center_x + solid_offset, width),
+                );
+            }
+            merge_ranges(&mut edges);
+            merge_ranges(&mut solids);
+
+            // how far inside the nearest circle's edge a pixel is
+            let depth = |col: usize| {
+                centers
+                    .iter()
+                    .map(|&(x, y)| radius - (x as f32 - col as f32).hypot(y as f32 - line as f32))
+                    .fold(f32::NEG_INFINITY, f32::max)
+            };
+            let feather_span = |canvas: &mut [u8], cols: std::ops::Range<usize>| {
+                for col in cols {
+                    let i = line * stride + col * channels;
+                    change_span(
+                        canvas,
+                        new,
+                        i..i + channels,
+                        feathered(step, depth(col), feather),
+                    );
+                }
+            };
+
+            // every solid span is inside one of the edge spans, since both are merged
+            let mut solids = solids.iter().peekable();
+            for edge in &edges {
+                let mut col = edge.start;
+                while let Some(solid) = solids.next_if(|solid| solid.end <= edge.end) {
+                    feather_span(canvas, col..solid.start);
+                    for col in solid.start * channels..solid.end * channels {
+                        // SAFETY: line < height and col < stride, and we've checked the slices'
+                        // sizes
+                        let old = unsafe { canvas.get_unchecked_mut(line * stride + col) };
+                        let new = unsafe { new.get_unchecked(line * stride + col) };
+                        change_byte(step, old, new);
+                    }
+                    col = solid.end;
+                }
+                feather_span(canvas, col..edge.end);
+            }
+        }
+    }
+}
+
+/// Sorts `ranges` and joins the ones that overlap, dropping the empty ones
+fn merge_ranges(ranges: &mut Vec<std::ops::Range<usize>>) {
+    ranges.retain(|range| !range.is_empty());
+    ranges.sort_unstable_by_key(|range| range.start);
+    let mut merged = 0;
+    for i in 0..ranges.len() {
+        if merged > 0 && ranges[i].start <= ranges[merged - 1].end {
+            ranges[merged - 1].end = ranges[merged - 1].end.max(ranges[i].end);
+        } else {
+            ranges[merged] = ranges[i].clone();
+            merged += 1;
+        }
+    }
+    ranges.truncate(merged);
+}
+
+/// How far from its nearest center the point of the screen farthest from all of `centers` is, or
+/// a little more. This is the radius at which the circles growing from them cover the screen
+fn farthest_from_all(dimensions: (u32, u32), centers: &[(f32, f32)]) -> f32 {
+    // we sample a grid instead of every pixel: no pixel is more than `SPACING` pixels away from
+    // a sample, so it is at most that much farther from the centers
+    const SPACING: u32 = 8;
+    let samples = |len: u32| {
+        (0..len)
+            .step_by(SPACING as usize)
+            .chain([len.saturating_sub(1)])
+    };
+    let mut farthest = 0f32;
+    for y in samples(dimensions.1) {
+        for x in samples(dimensions.0) {
+            let nearest = centers
+                .iter()
+                .map(|&(cx, cy)| (cx - x as f32).hypot(cy - y as f32))
+                .fold(f32::INFINITY, f32::min);
+            farthest = farthest.max(nearest);
+        }
+    }
+    // plus a pixel, because the circles' spans are truncated to whole pixels
+    farthest + SPACING as f32 + 1.0
+}
+
+/// A circle shrinking into a point
+pub struct Outer {
+    width: usize,
+    height: usize,
+    channels: usize,
+    center: (isize, isize),
+    start: f32,
+    feather: f32,
+}
+
+impl Outer {
+    /// `center` is in pixels
+    pub fn new(dimensions: (u32, u32), channels: usize, center: (f32, f32)) -> Self {
+        Self {
+            width: dimensions.0 as usize,
+            height: dimensions.1 as usize,
+            channels,
+            center: (center.0 as isize, center.1 as isize),
+            start: farthest_corner(dimensions, center),
+            feather: 0.0,
+        }
+    }
+
+    /// Blends the circle's edge over `feather` pixels outside of it, instead of changing pixels
+    /// as soon as the circle reaches them
+    pub fn feather(self, feather: f32) -> Self {
+        Self {
+            feather: feather.max(0.0),
+            ..self
+        }
+    }
+
+    /// the circle's radius at the beginning of the transition
+    pub fn start(&self) -> f32 {
+        self.start
+    }
+
+    /// the circle's radius once the whole screen is outside of it, feathered edge included. This
+    /// is negative when the edge is feathered
+    pub fn end(&self) -> f32 {
+        -self.feather
+    }
+
+    /// Only draws the part of the screen at `origin`, with `dimensions`
+    pub fn crop(self, origin: (i32, i32), dimensions: (u32, u32)) -> Self {
+        Self {
+            width: dimensions.0 as usize,
+            height: dimensions.1 as usize,
+            center: (
+                self.center.0 - origin.0 as isize,
+                self.center.1 - origin.1 as isize,
+            ),
+            ..self
+        }
+    }
+
+    pub fn draw(&self, canvas: &mut [u8], new: &[u8], radius: f32, step: u8) {
+        let Self {
+            width,
+            height,
+            channels,
+            center: (center_x, center_y),
+            feather,
+            ..
+        } = *self;
+        assert_dimensions(canvas, new, width, height, channels);
+        let stride = width * channels;
+        // the center may be outside of the screen when we are cropped
+        let clamp = |x: isize| x.clamp(0, width as isize) as usize;
+        // pixels outside of this radius are at least `feather` pixels outside of the circle
+        let solid = (radius + feather).max(0.0);
+        let hole = radius.max(0.0);
+
+        // to plot half a circle with radius r, we do sqrt(r^2 - x^2)
+        for line in 0..height {
+            let dy = center_y as f32 - line as f32;
+            let offset = (hole.powi(2) - dy.powi(2)).sqrt() as isize;
+            let solid_offset = (solid.powi(2) - dy.powi(2)).sqrt() as isize;
+            let col_begin = clamp(center_x - solid_offset) * channels;
+            let col_end = clamp(center_x + solid_offset) * channels;
+            for col in (0..col_begin).chain(col_end..stride) {
+                // SAFETY: line < height and col < stride, and we've checked the slices' sizes
+                let old = unsafe { canvas.get_unchecked_mut(line * stride + col) };
+                let new = unsafe { new.get_unchecked(line * stride + col) };
+                change_byte(step, old, new);
+            }
+
+            let left = clamp(center_x - solid_offset)..clamp(center_x - offset);
+            let right = clamp(center_x + offset)..clamp(center_x + solid_offset);
+            for col in left.chain(right) {
+                let depth = (center_x as f32 - col as f32).hypot(dy) - radius;
+                let i = line * stride + col * channels;
+                change_span(
+                    canvas,
+                    new,
+                    i..i + channels,
+                    feathered(step, depth, feather),
+                );
+            }
+        }
+    }
+}
+
+/// A circle growing from a point, with its edge rippling like a wave in a pond
+pub struct Ripple {
+    width: usize,
+    height: usize,
+    channels: usize,
+    center: (f32, f32),
+    amplitude: f32,
+    frequency: f32,
+    end: f32,
+}
+
+impl Ripple {
+    /// `center` is in pixels, and `ripple` is the amplitude and wavelength of the ripples
+    pub fn new(
+        dimensions: (u32, u32),
+        channels: usize,
+        center: (f32, f32),
+        ripple: (f32, f32),
+    ) -> Self {
+        let amplitude = ripple.0.abs();
+        Self {
+            width: dimensions.0 as usize,
+            height: dimensions.1 as usize,
+            channels,
+            center,
+            amplitude,
+            frequency: std::f32::consts::TAU / ripple.1.max(1.0),
+            end: farthest_corner(dimensions, center) + amplitude,
+        }
+    }
+
+    /// the circle's radius at the beginning of the transition, where it draws nothing
+    pub fn start(&self) -> f32 {
+        -self.amplitude
+    }
+
+    /// the circle's radius once it covers the whole screen
+    pub fn end(&self) -> f32 {
+        self.end
+    }
+
+    /// Only draws the part of the screen at `origin`, with `dimensions`
+    pub fn crop(self, origin: (i32, i32), dimensions: (u32, u32)) -> Self {
+        Self {
+            width: dimensions.0 as usize,
+            height: dimensions.1 as usize,
+            center: (
+                self.center.0 - origin.0 as f32,
+                self.center.1 - origin.1 as f32,
+            ),
+            ..self
+        }
+    }
+
+    pub fn draw(&self, canvas: &mut [u8], new: &[u8], radius: f32, step: u8) {
+        let Self {
+            width,
+            height,
+            channels,
+            center: (center_x, center_y),
+            amplitude,
+            frequency,
+            ..
+        } = *self;
+        assert_dimensions(canvas, new, width, height, channels);
+        let stride = width * channels;
+
+        // nothing outside of a circle with the ripples' peaks can be drawn to
+        let outer = radius + amplitude;
+        if outer <= 0.0 {
+            return;
+        }
+        let line_begin = (center_y - outer).max(0.0) as usize;
+        let line_end = height.min((center_y + outer).max(0.0) as usize + 1);
+        let col_begin = (center_x - outer).max(0.0) as usize;
+        let col_end = width.min((center_x + outer).max(0.0) as usize + 1);
+
+        for line in line_begin..line_end {
+            let dy = line as f32 - center_y;
+            for col in col_begin..col_end {
+                let dx = col as f32 - center_x;
+                let dist = (dx.powi(2) + dy.powi(2)).sqrt();
+                if dist <= radius + amplitude * (dist * frequency).sin() {
+                    let i = line * stride + col * channels;
+                    for j in 0..channels {
+                        // SAFETY: line < height and col < width, and we've checked the slices'
+                        // sizes
+                        let old = unsafe { canvas.get_unchecked_mut(i + j) };
+                        let new = unsafe { new.get_unchecked(i + j) };
+                        change_byte(step, old, new);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A hand sweeping around a point, starting at 12 o'clock, with the new image behind it
+pub struct Clock {
+    width: usize,
+    height: usize,
+    channels: usize,
+    center: (f32, f32),
+    clockwise: bool,
+    soft_edge: f32,
+}
+
+impl Clock {
+    /// `center` is in pixels, and `soft_edge` is how wide the blurry part behind the hand is, in
+    /// degrees
+    pub fn new(
+        dimensions: (u32, u32),
+        channels: usize,
+        center: (f32, f32),
+        clockwise: bool,
+        soft_edge: f32,
+    ) -> Self {
+        Self {
+            width: dimensions.0 as usize,
+            height: dimensions.1 as usize,
+            channels,
+            center,
+            clockwise,
+            soft_edge: soft_edge.max(0.0).to_radians(),
+        }
+    }
+
+    /// the hand's angle once it covers the whole screen, soft edge included, in radians
+    pub fn end(&self) -> f32 {
+        std::f32::consts::TAU + self.soft_edge
+    }
+
+    /// Only draws the part of the screen at `origin`, with `dimensions`
+    pub fn crop(self, origin: (i32, i32), dimensions: (u32, u32)) -> Self {
+        Self {
+            width: dimensions.0 as usize,
+            height: dimensions.1 as usize,
+            center: (
+                self.center.0 - origin.0 as f32,
+                self.center.1 - origin.1 as f32,
+            ),
+            ..self
+        }
+    }
+
+    /// `angle` is how far the hand went, in radians. Pixels within the soft edge behind it move
+    /// towards the new image slower the closer they are to the hand
+    pub fn draw(&self, canvas: &mut [u8], new: &[u8], angle: f32, step: u8) {
+        let Self {
+            width,
+            height,
+            channels,
+            center: (center_x, center_y),
+            clockwise,
+            soft_edge,
+        } = *self;
+        assert_dimensions(canvas, new, width, height, channels);
+        if angle <= 0.0 {
+            return;
+        }
+        let stride = width * channels;
+
+        for line in 0..height {
+            let dy = line as f32 + 0.5 - center_y;
+            for col in 0..width {
+                let dx = col as f32 + 0.5 - center_x;
+                // measured from 12 o'clock, in the direction the hand moves
+                let dx = if clockwise { dx } else { -dx };
+                let mut pixel = dx.atan2(-dy);
+                if pixel < 0.0 {
+                    pixel += std::f32::consts::TAU;
+                }
+                let behind = angle - pixel;
+                if behind < 0.0 {
+                    continue;
+                }
+                let step = if behind >= soft_edge {
+                    step
+                } else {
+                    ((step as f32 * behind / soft_edge) as u8).max(1)
+                };
+                let i = line * stride + col * channels;
+                change_span(canvas, new, i..i + channels, step);
+            }
+        }
+    }
+}
+
+/// A thick line tracing an Archimedean spiral out of a point, starting at 12 o'clock and going
+/// clockwise. The gaps between its turns close behind it
+pub struct Spiral {
+    width: usize,
+    height: usize,
+    channels: usize,
+    center: (f32, f32),
+    /// distance between two turns of the spiral, in pixels
+    pitch: f32,
+    /// half the line's thickness, in pixels
+    half_thickness: f32,
+    end: f32,
+}
+
+impl Spiral {
+    /// `center` is in pixels. `turns` is how many times the line goes around before reaching the
+    /// farthest corner of the screen, and `thickness` is its width, in pixels. Lines at least as
+    /// thick as the distance between two turns leave no gaps
+    pub fn new(
+        dimensions: (u32, u32),
+        channels: usize,
+        center: (f32, f32),
+        turns: f32,
+        thickness: f32,
+    ) -> Self {
+        let radius = farthest_corner(dimensions, center);
+        let pitch = (radius / turns.max(0.01)).max(1.0);
+        Self {
+            width: dimensions.0 as usize,
+            height: dimensions.1 as usize,
+            channels,
+            center,
+            pitch,
+            half_thickness: thickness.max(0.0) / 2.0,
+            // once the line reaches the farthest corner, the last gap takes another turn to close
+            end: (radius / pitch + 1.0) * std::f32::consts::TAU,
+        }
+    }
+
+    /// how far the line must go around once the spiral covers the whole screen, in radians
+    pub fn end(&self) -> f32 {
+        self.end
+    }
+
+    /// Only draws the part of the screen at `origin`, with `dimensions`
+    pub fn crop(self, origin: (i32, i32), dimensions: (u32, u32)) -> Self {
+        Self {
+            width: dimensions.0 as usize,
+            height: dimensions.1 as usize,
+            center: (
+                self.center.0 - origin.0 as f32,
+                self.center.1 - origin.1 as f32,
+            ),
+            ..self
+        }
+    }
+
+    /// `angle` is how far the tip of the line went around, in radians
+    pub fn draw(&self, canvas: &mut [u8], new: &[u8], angle: f32, step: u8) {
+        use std::f32::consts::TAU;
+
+        let Self {
+            width,
+            height,
+            channels,
+            center: (center_x, center_y),
+            pitch,
+            half_thickness,
+            ..
+        } = *self;
+        assert_dimensions(canvas, new, width, height, channels);
+        if angle <= 0.0 {
+            return;
+        }
+        let stride = width * channels;
+        // the part of a turn the line does not cover, which closes as the next turn goes by
+        let gap = pitch - 2.0 * half_thickness;
+
+        for line in 0..height {
+            let dy = line as f32 + 0.5 - center_y;
+            for col in 0..width {
+                let dx = col as f32 + 0.5 - center_x;
+                let mut pixel = dx.atan2(-dy) / TAU;
+                if pixel < 0.0 {
+                    pixel += 1.0;
+                }
+                // in turns, from the center. The line passes this pixel's direction every turn,
+                // `pixel` turns in, so the turn just inside it was at `turn`
+                let radius = dx.hypot(dy) / pitch;
+                let turn = (radius - pixel).floor() + pixel;
+                let distance = (radius - turn) * pitch;
+                let behind = if distance <= half_thickness {
+                    0.0
+                } else if gap <= 0.0 {
+                    1.0
+                } else {
+                    ((distance - half_thickness) / gap).min(1.0)
+                };
+                if angle >= (turn + behind) * TAU {
+                    let i = line * stride + col * channels;
+                    change_span(canvas, new, i..i + channels, step);
+                }
+            }
+        }
+    }
+}
+
+/// Pixels switching to the new image one by one, in a pseudo-random order, like a film dissolve
+pub struct Dissolve {
+    width: usize,
+    height: usize,
+    channels: usize,
+    seed: u32,
+}
+
+/// a well mixed 32 bit hash, from https://nullprogram.com/blog/2018/07/31/
+fn hash(mut x: u32) -> u32 {
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7feb352d);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x846ca68b);
+    x ^= x >> 16;
+    x
+}
+
+impl Dissolve {
+    /// `seed` decides the order the pixels switch in
+    pub fn new(dimensions: (u32, u32), channels: usize, seed: u32) -> Self {
+        Self {
+            width: dimensions.0 as usize,
+            height: dimensions.1 as usize,
+            channels,
+            seed: hash(seed),
+        }
+    }
+
+    /// `progress` goes from 0 to 1: pixels start moving towards the new image once `progress`
+    /// goes past their threshold, and at 1 every pixel is moving
+    pub fn draw(&self, canvas: &mut [u8], new: &[u8], progress: f32, step: u8) {
+        let Self {
+            width,
+            height,
+            channels,
+            seed,
+        } = *self;
+        assert_dimensions(canvas, new, width, height, channels);
+        let limit = (progress.clamp(0.0, 1.0) * 65536.0) as u32;
+
+        let pixels = canvas
+            .chunks_exact_mut(channels)
+            .zip(new.chunks_exact(channels));
+        for (i, (old, new)) in pixels.enumerate() {
+            let threshold = hash(i as u32 ^ seed) >> 16;
+            if threshold < limit {
+                for (old, new) in old.iter_mut().zip(new) {
+                    change_byte(step, old, new);
+                }
+            }
+        }
+    }
+}
+
+/// Pixels switching to the new image in the order of a threshold matrix tiled over the screen,
+/// which draws halftone-like patterns on the way
+pub struct Halftone {
+    width: usize,
+    height: usize,
+    channels: usize,
+    size: usize,
+    /// when each cell of the matrix switches, from 0 to 65536
+    thresholds: Vec<u32>,
+}
+
+impl Halftone {
+    /// The matrix is `size` by `size` (a power of two, clamped between 2 and 64). It is an ordered
+    /// Bayer matrix, which makes regular crosshatch patterns, or, if `blue_noise` is set, blue
+    /// noise, which looks like an even grain instead
+    pub fn new(dimensions: (u32, u32), channels: usize, size: u8, blue_noise: bool) -> Self {
+        let size = (size.clamp(2, 64) as usize).next_power_of_two();
+        let ranks = if blue_noise {
+            blue_noise_matrix(size)
+        } else {
+            bayer_matrix(size)
+        };
+        let cells = (size * size) as u32;
+        Self {
+            width: dimensions.0 as usize,
+            height: dimensions.1 as usize,
+            channels,
+            size,
+            thresholds: ranks.iter().map(|&rank| rank * 65536 / cells).collect(),
+        }
+    }
+
+    /// `progress` goes from 0 to 1: pixels start moving towards the new image once `progress`
+    /// goes past their cell's threshold, and at 1 every pixel is moving
+    pub fn draw(&self, canvas: &mut [u8], new: &[u8], progress: f32, step: u8) {
+        let Self {
+            width,
+            height,
+            channels,
+            size,
+            ..
+        } = *self;
+        assert_dimensions(canvas, new, width, height, channels);
+        let limit = (progress.clamp(0.0, 1.0) * 65536.0) as u32;
+
+        for line in 0..height {
+            let row = &self.thresholds[(line % size) * size..(line % size + 1) * size];
+            let begin = line * width * channels;
+            for col in 0..width {
+                if row[col % size] < limit {
+                    let i = begin + col * channels;
+                    for (old, new) in canvas[i..i + channels]
+                        .iter_mut()
+                        .zip(&new[i..i + channels])
+                    {
+                        change_byte(step, old, new);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The ordered dithering matrix of `size` by `size` (a power of two), as ranks from 0 up
+fn bayer_matrix(size: usize) -> Vec<u32> {
+    let mut matrix = vec![0];
+    let mut n = 1;
+    while n < size {
+        // four copies of the previous matrix, offset in the order of the 2x2 matrix
+        let mut next = vec![0; 4 * n * n];
+        for y in 0..2 * n {
+            for x in 0..2 * n {
+                let offset = [[0, 2], [3, 1]][y / n][x / n];
+                next[y * 2 * n + x] = 4 * matrix[(y % n) * n + x % n] + offset;
+            }
+        }
+        matrix = next;
+        n *= 2;
+    }
+    matrix
+}
+
+/// A `size` by `size` blue noise matrix, as ranks from 0 up, made with Ulichney's
+/// void-and-cluster method: every rank goes to the cell farthest from all the cells ranked
+/// before it, so that the cells switched at any point are spread out evenly, without a pattern
+fn blue_noise_matrix(size: usize) -> Vec<u32> {
+    const SIGMA: f32 = 1.5;
+    let cells = size * size;
+    // how much a cell weighs on another, by their distance along each axis, wrapping around
+    let kernel: Vec<f32> = (0..cells)
+        .map(|i| {
+            let wrapped = |d: usize| d.min(size - d) as f32;
+            let (dx, dy) = (wrapped(i % size), wrapped(i / size));
+            (-(dx * dx + dy * dy) / (2.0 * SIGMA * SIGMA)).exp()
+        })
+        .collect();
+    let mut energy = vec![0.0f32; cells];
+    let mut set = vec![false; cells];
+    let toggle = |energy: &mut [f32], set: &mut [bool], cell: usize, on: bool| {
+        set[cell] = on;
+        let sign = if on { 1.0 } else { -1.0 };
+        let (cx, cy) = (cell % size, cell / size);
+        for (i, e) in energy.iter_mut().enumerate() {
+            let dx = (i % size + size - cx) % size;
+            let dy = (i / size + size - cy) % size;
+            *e += sign * kernel[dy * size + dx];
+        }
+    };
+    // the tightest cluster, among set cells, and the largest void, among the others
+    let tightest = |energy: &[f32], set: &[bool]| {
+        (0..cells)
+            .filter(|&i| set[i])
+            .max_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+    };
+    let largest_void = |energy: &[f32], set: &[bool]| {
+        (0..cells)
+            .filter(|&i| !set[i])
+            .min_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+    };
+
+    // a tenth of the cells, at random, spread out by moving the tightest cluster to the largest
+    // void until that changes nothing
+    let initial = (cells / 10).max(1);
+    let mut placed = 0;
+    let mut x = 0;
+    while placed < initial {
+        let cell = hash(x) as usize % cells;
+        x += 1;
+        if !set[cell] {
+            toggle(&mut energy, &mut set, cell, true);
+            placed += 1;
+        }
+    }
+    for _ in 0..cells {
+        let cluster = tightest(&energy, &set).unwrap();
+        toggle(&mut energy, &mut set, cluster, false);
+        let void = largest_void(&energy, &set).unwrap();
+        toggle(&mut energy, &mut set, void, true);
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut ranks = vec![0; cells];
+    // the initial cells are ranked from their tightest cluster down
+    let (mut energy_down, mut set_down) = (energy.clone(), set.clone());
+    for rank in (0..initial).rev() {
+        let cluster = tightest(&energy_down, &set_down).unwrap();
+        toggle(&mut energy_down, &mut set_down, cluster, false);
+        ranks[cluster] = rank as u32;
+    }
+    // and the rest into the largest void, up
+    for rank in initial..cells {
+        let void = largest_void(&energy, &set).unwrap();
+        toggle(&mut energy, &mut set, void, true);
+        ranks[void] = rank as u32;
+    }
+    ranks
+}
+
+/// Pixels switching to the new image along its edges first, strongest first, and then filling in
+/// away from them, like a sketch being painted in
+pub struct Sketch {
+    width: usize,
+    height: usize,
+    channels: usize,
+    /// when each pixel starts moving towards the new image, from 0 (at the start) to `u16::MAX`
+    thresholds: Box<[u16]>,
+}
+
+impl Sketch {
+    /// How much of the transition drawing the edges takes, before the rest fills in
+    const EDGES: f32 = 0.4;
+
+    /// The edges are found once, in `new`, with a Sobel filter over its brightness. Those at least
+    /// `threshold` (from 0 to 1) times as strong as the strongest one are drawn first
+    pub fn new(dimensions: (u32, u32), channels: usize, new: &[u8], threshold: f32) -> Self {
+        let (width, height) = (dimensions.0 as usize, dimensions.1 as usize);
+        assert_eq!(
+            new.len(),
+            width * height * channels,
+            "the image has the wrong size"
+        );
+        let strength = sobel(width, height, channels, new);
+        let strongest = strength.iter().copied().max().unwrap_or(0);
+        let cutoff = ((threshold.clamp(0.0, 1.0) * strongest as f32) as u32).max(1);
+        let is_edge = |s: u32| s >= cutoff;
+
+        // how far each pixel is from the nearest edge, with a 3-4 chamfer distance transform: a
+        // pass down and right, and one back up and left
+        const FAR: u32 = u32::MAX / 2;
+        let mut distance: Vec<u32> = strength
+            .iter()
+            .map(|&s| if is_edge(s) { 0 } else { FAR })
+            .collect();
+        let mut relax = |i: usize, neighbor: Option<usize>, cost: u32| {
+            if let Some(neighbor) = neighbor {
+                distance[i] = distance[i].min(distance[neighbor] + cost);
+            }
+        };
+        for y in 0..height {
+            for x in 0..width {
+                let i = y * width + x;
+                relax(i, (x > 0).then(|| i - 1), 3);
+                relax(i, (y > 0).then(|| i - width), 3);
+                relax(i, (x > 0 && y > 0).then(|| i - width - 1), 4);
+                relax(i, (x + 1 < width && y > 0).then(|| i - width + 1), 4);
+            }
+        }
+        for y in (0..height).rev() {
+            for x in (0..width).rev() {
+                let i = y * width + x;
+                relax(i, (x + 1 < width).then(|| i + 1), 3);
+                relax(i, (y + 1 < height).then(|| i + width), 3);
+                relax(
+                    i,
+                    (x + 1 < width && y + 1 < height).then(|| i + width + 1),
+                    4,
+                );
+                relax(i, (x > 0 && y + 1 < height).then(|| i + width - 1), 4);
+            }
+        }
+        let farthest = distance.iter().copied().filter(|&d| d < FAR).max();
+
+        let thresholds = strength
+            .iter()
+            .zip(&distance)
+            .map(|(&s, &d)| {
+                let start = if is_edge(s) {
+                    let above = (s - cutoff) as f32 / (strongest - cutoff).max(1) as f32;
+                    Self::EDGES * (1.0 - above)
+                } else {
+                    // without any edges, everything fills in at once
+                    let away = farthest.map_or(0.0, |far| d.min(far) as f32 / far.max(1) as f32);
+                    Self::EDGES + (1.0 - Self::EDGES) * away
+                };
+                (start * u16::MAX as f32) as u16
+            })
+            .collect();
+        Self {
+            width,
+            height,
+            channels,
+            thresholds,
+        }
+    }
+
+    /// `progress` goes from 0 to 1: pixels start moving towards the new image once `progress`
+    /// goes past their threshold, and at 1 every pixel is moving
+    pub fn draw(&self, canvas: &mut [u8], new: &[u8], progress: f32, step: u8) {
+        let Self {
+            width,
+            height,
+            channels,
+            ..
+        } = *self;
+        assert_dimensions(canvas, new, width, height, channels);
+        let limit = (progress.clamp(0.0, 1.0) * 65536.0) as u32;
+        for (pixel, &threshold) in self.thresholds.iter().enumerate() {
+            if (threshold as u32) < limit {
+                let i = pixel * channels;
+                change_span(canvas, new, i..i + channels, step);
+            }
+        }
+    }
+}
+
+/// How strong an edge each pixel of `img` is on, as the sum of the absolute horizontal and
+/// vertical Sobel gradients of its brightness. Pixels past the borders repeat the ones on them
+fn sobel(width: usize, height: usize, channels: usize, img: &[u8]) -> Vec<u32> {
+    // the channels are either rgb or bgr, and green weighs the most in both
+    let brightness: Vec<i32> = img
+        .chunks_exact(channels)
+        .map(|pixel| pixel[0] as i32 + 2 * pixel[1] as i32 + pixel[2] as i32)
+        .collect();
+    let at = |x: usize, y: usize| brightness[y * width + x];
+    let mut strength = Vec::with_capacity(width * height);
+    for y in 0..height {
+        let (up, down) = (y.saturating_sub(1), (y + 1).min(height - 1));
+        for x in 0..width {
+            let (left, right) = (x.saturating_sub(1), (x + 1).min(width - 1));
+            let gx = at(right, up) + 2 * at(right, y) + at(right, down)
+                - at(left, up)
+                - 2 * at(left, y)
+                - at(left, down);
+            let gy = at(left, down) + 2 * at(x, down) + at(right, down)
+                - at(left, up)
+                - 2 * at(x, up)
+                - at(right, up);
+            strength.push(gx.unsigned_abs() + gy.unsigned_abs());
+        }
+    }
+    strength
+}
+
+/// Pixels switching to the new image in the order of a grayscale mask's brightness, darkest
+/// first, like a luma wipe
+pub struct Luma<'a> {
+    width: usize,
+    height: usize,
+    channels: usize,
+    mask: &'a [u8],
+    softness: f32,
+}
+
+impl<'a> Luma<'a> {
+    /// `mask` has a byte per pixel. `softness`, from 0 to 1, is how much darker than the
+    /// threshold pixels must be before they move at full speed, as a fraction of the whole range
+    pub fn new(dimensions: (u32, u32), channels: usize, mask: &'a [u8], softness: f32) -> Self {
+        let (width, height) = (dimensions.0 as usize, dimensions.1 as usize);
+        assert_eq!(mask.len(), width * height, "the mask has the wrong size");
+        Self {
+            width,
+            height,
+            channels,
+            mask,
+            softness: softness.clamp(0.0, 1.0),
+        }
+    }
+
+    /// the threshold once every pixel moves at full speed
+    pub fn end(&self) -> f32 {
+        1.0 + self.softness
+    }
+
+    /// `threshold` goes from 0 to `end()`: pixels start moving towards the new image once it goes
+    /// past their brightness in the mask, slower the closer they are to it
+    pub fn draw(&self, canvas: &mut [u8], new: &[u8], threshold: f32, step: u8) {
+        let Self {
+            width,
+            height,
+            channels,
+            mask,
+            softness,
+        } = *self;
+        assert_dimensions(canvas, new, width, height, channels);
+
+        let pixels = canvas
+            .chunks_exact_mut(channels)
+            .zip(new.chunks_exact(channels));
+        for ((old, new), &brightness) in pixels.zip(mask) {
+            // dividing by 256 means even the brightest pixels are past the threshold at the end
+            let behind = threshold - brightness as f32 / 256.0;
+            if behind <= 0.0 {
+                continue;
+            }
+            let step = if behind >= softness {
+                step
+            } else {
+                ((step as f32 * behind / softness) as u8).max(1)
+            };
+            for (old, new) in old.iter_mut().zip(new) {
+                change_byte(step, old, new);
+            }
+        }
+    }
+}
+
+/// A mosaic of the old image that gets finer and finer as it turns into the new one
+pub struct Pixelate {
+    width: usize,
+    height: usize,
+    channels: usize,
+    block_size: f32,
+}
+
+impl Pixelate {
+    /// `block_size` is the size of the mosaic's blocks at the beginning of the transition, in
+    /// pixels
+    pub fn new(dimensions: (u32, u32), channels: usize, block_size: u16) -> Self {
+        Self {
+            width: dimensions.0 as usize,
+            height: dimensions.1 as usize,
+            channels,
+            block_size: block_size.max(1) as f32,
+        }
+    }
+
+    /// Unlike the other transitions, this one redraws the whole canvas every frame, from the
+    /// `old` image (which must have the same size as `new`).
+    ///
+    /// `progress` goes from 0 to 1. The blocks shrink geometrically, so halving their size
+    /// always takes the same time, while their colors blend from `old` into `new`. At 1, the
+    /// canvas is exactly `new`.
+    pub fn draw(&self, canvas: &mut [u8], old: &[u8], new: &[u8], progress: f32) {
+        let Self {
+            width,
+            height,
+            channels,
+            block_size,
+        } = *self;
+        assert_dimensions(canvas, new, width, height, channels);
+        assert_eq!(
+            old.len(),
+            new.len(),
+            "old and new images have different sizes"
+        );
+        let stride = width * channels;
+
+        let progress = progress.clamp(0.0, 1.0);
+        let block = (block_size.powf(1.0 - progress).round() as usize).max(1);
+        let weight = (256.0 * progress) as u16;
+
+        let mut color = [0u8; 4];
+        for line in (0..height).step_by(block) {
+            let lines = line..height.min(line + block);
+            let sample_line = (line + block / 2).min(height - 1);
+            for col in (0..width).step_by(block) {
+                let cols = col * channels..width.min(col + block) * channels;
+                let sample = sample_line * stride + (col + block / 2).min(width - 1) * channels;
+                for (c, color) in color.iter_mut().take(channels).enumerate() {
+                    let x = old[sample + c] as u16 * (256 - weight);
+                    let y = new[sample + c] as u16 * weight;
+                    *color = ((x as u32 + y as u32) >> 8) as u8;
+                }
+                for line in lines.clone() {
+                    let row = &mut canvas[line * stride..(line + 1) * stride];
+                    for pixel in row[cols.clone()].chunks_exact_mut(channels) {
+                        pixel.copy_from_slice(&color[..channels]);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The old image turning away as a face of a cube, with the new image on the next face. The
+/// faces are projected in perspective, and darken as they turn away from the screen
+pub struct Cube {
+    width: usize,
+    height: usize,
+    channels: usize,
+    vertical_axis: bool,
+    /// from the eye to the screen, in pixels
+    distance: f32,
+}
+
+impl Cube {
+    /// With a `vertical_axis`, the cube turns from left to right, otherwise from top to bottom.
+    /// `fov` is the camera's field of view along the direction it turns, in degrees: the wider,
+    /// the stronger the perspective
+    pub fn new(dimensions: (u32, u32), channels: usize, vertical_axis: bool, fov: f32) -> Self {
+        let along = if vertical_axis {
+            dimensions.0
+        } else {
+            dimensions.1
+        };
+        let fov = fov.clamp(1.0, 179.0).to_radians();
+        Self {
+            width: dimensions.0 as usize,
+            height: dimensions.1 as usize,
+            channels,
+            vertical_axis,
+            distance: along as f32 / 2.0 / (fov / 2.0).tan(),
+        }
+    }
+
+    /// Like `Pixelate`, this redraws the whole canvas every frame, from the `old` image (which
+    /// must have the same size as `new`). Whatever the faces do not cover is black.
+    ///
+    /// `progress` goes from 0 to 1, turning the cube by a quarter. At 1, the canvas is exactly
+    /// `new`.
+    pub fn draw(&self, canvas: &mut [u8], old: &[u8], new: &[u8], progress: f32) {
+        let Self {
+            width,
+            height,
+            channels,
+            vertical_axis,
+            distance,
+        } = *self;
+        assert_dimensions(canvas, new, width, height, channels);
+        assert_eq!(
+            old.len(),
+            new.len(),
+            "old and new images have different sizes"
+        );
+        let stride = width * channels;
+
+        // `along` is the direction the cube turns in, `across` is its axis
+        let (along, across) = if vertical_axis {
+            (width, height)
+        } else {
+            (height, width)
+        };
+        let half = along as f32 / 2.0;
+        let turn = progress.clamp(0.0, 1.0) * std::f32::consts::FRAC_PI_2;
+        let faces = [(old, turn), (new, turn - std::f32::consts::FRAC_PI_2)];
+
+        // The eye is `distance` in front of the screen, and the cube's axis is `half` behind it,
+        // so an unturned face lies exactly on the screen. The ray through each position `along`
+        // the screen hits at most one face: we keep which, where on it, how much farther than
+        // the screen (which scales the positions `across` it) and how lit it is
+        let hits: Vec<Option<(usize, usize, f32, u16)>> = (0..along)
+            .map(|a| {
+                let x = a as f32 + 0.5 - half;
+                faces.iter().enumerate().find_map(|(face, &(_, angle))| {
+                    let (sin, cos) = angle.sin_cos();
+                    let depth = distance + half - half * cos;
+                    // negative when the face looks away from the eye
+                    let facing = cos * depth - half * sin * sin;
+                    let det = cos * distance - x * sin;
+                    if facing <= 0.0 || det <= 0.0 {
+                        return None;
+                    }
+                    let u = ((x * depth - distance * half * sin) / det + half).floor();
+                    if !(0.0..along as f32).contains(&u) {
+                        return None;
+                    }
+                    let light = (64.0 + 192.0 * cos) as u16;
+                    Some((face, u as usize, facing / det, light))
+                })
+            })
+            .collect();
+
+        let middle = across as f32 / 2.0;
+        for line in 0..height {
+            for col in 0..width {
+                let (a, b) = if vertical_axis {
+                    (col, line)
+                } else {
+                    (line, col)
+                };
+                let i = line * stride + col * channels;
+                let pixel = &mut canvas[i..i + channels];
+                let sample = hits[a].and_then(|(face, u, scale, light)| {
+                    let v = ((b as f32 + 0.5 - middle) * scale + middle).floor();
+                    if !(0.0..across as f32).contains(&v) {
+                        return None;
+                    }
+                    let (col, line) = if vertical_axis {
+                        (u, v as usize)
+                    } else {
+                        (v as usize, u)
+                    };
+                    let j = line * stride + col * channels;
+                    Some((&faces[face].0[j..j + channels], light))
+                });
+                match sample {
+                    Some((src, light)) => {
+                        for (dst, src) in pixel.iter_mut().zip(src) {
+                            *dst = ((*src as u16 * light) >> 8) as u8;
+                        }
+                    }
+                    None => pixel.fill(0),
+                }
+            }
+        }
+    }
+}
+
+/// The new image sliding in and pushing the old one out, both moving together
+pub struct Push {
+    width: usize,
+    height: usize,
+    channels: usize,
+    /// the direction the images move in, in pixels of the screen, where y grows downwards
+    motion: (f32, f32),
+    /// how far the images move, which is as far as it takes the old one to leave the screen
+    distance: f32,
+}
+
+impl Push {
+    /// `angle` is in degrees, like `Wipe`'s: at 0 the new image comes in from the right, and at
+    /// 90 from the top. At angles that are not a multiple of 90, the images move diagonally, and
+    /// the corners neither covers are black
+    pub fn new(dimensions: (u32, u32), channels: usize, angle: f64) -> Self {
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let motion = (-cos as f32, sin as f32);
+        let across = |size: u32, motion: f32| {
+            if motion.abs() < 1e-6 {
+                f32::INFINITY
+            } else {
+                size as f32 / motion.abs()
+            }
+        };
+        Self {
+            width: dimensions.0 as usize,
+            height: dimensions.1 as usize,
+            channels,
+            motion,
+            distance: across(dimensions.0, motion.0).min(across(dimensions.1, motion.1)),
+        }
+    }
+
+    /// Like `Cube`, this redraws the whole canvas every frame, from the `old` image (which must
+    /// have the same size as `new`).
+    ///
+    /// `progress` goes from 0 to 1. At 1, the canvas is exactly `new`.
+    pub fn draw(&self, canvas: &mut [u8], old: &[u8], new: &[u8], progress: f32) {
+        let Self {
+            width,
+            height,
+            channels,
+            motion,
+            distance,
+        } = *self;
+        assert_dimensions(canvas, new, width, height, channels);
+        assert_eq!(
+            old.len(),
+            new.len(),
+            "old and new images have different sizes"
+        );
+        let stride = width * channels;
+
+        // where each image's top left corner is on the screen
+        let moved = progress.clamp(0.0, 1.0) * distance;
+        let shift = |along: f32| {
+            (
+                (motion.0 * along).round() as isize,
+                (motion.1 * along).round() as isize,
+            )
+        };
+        let images = [(old, shift(moved)), (new, shift(moved - distance))];
+
+        for line in 0..height {
+            let row = &mut canvas[line * stride..(line + 1) * stride];
+            row.fill(0);
+            // the new image goes last, so it is on top where they meet
+            for &(image, (x, y)) in &images {
+                let src_line = line as isize - y;
+                if !(0..height as isize).contains(&src_line) {
+                    continue;
+                }
+                let begin = x.clamp(0, width as isize) as usize;
+                let end = (width as isize + x).clamp(0, width as isize) as usize;
+                if begin >= end {
+                    continue;
+                }
+                let src = src_line as usize * stride + (begin as isize - x) as usize * channels;
+                row[begin * channels..end * channels]
+                    .copy_from_slice(&image[src..src + (end - begin) * channels]);
+            }
+        }
+    }
+}
+
+/// How many passes `ZoomBlur`'s blur takes. Each doubles its samples, so 4 of them make 16
+const ZOOM_BLUR_PASSES: u32 = 4;
+/// How long `ZoomBlur`'s streaks get halfway through, as a fraction of the distance to the center
+const ZOOM_BLUR_LENGTH: f32 = 0.3;
+/// How much larger the old image gets by the end of `ZoomBlur`
+const ZOOM_BLUR_ZOOM: f32 = 0.5;
+
+/// The old image rushing towards the viewer from a point, blurred along the rays coming out of
+/// it, while the new image fades in through the blur
+pub struct ZoomBlur {
+    width: usize,
+    height: usize,
+    channels: usize,
+    center: (f32, f32),
+    /// what the blur passes read from
+    scratch: Vec<u8>,
+    /// the column and line each pixel samples, scaled towards the center
+    cols: Vec<usize>,
+    lines: Vec<usize>,
+}
+
+impl ZoomBlur {
+    /// `center` is the point the image zooms from, in pixels
+    pub fn new(dimensions: (u32, u32), channels: usize, center: (f32, f32)) -> Self {
+        let (width, height) = (dimensions.0 as usize, dimensions.1 as usize);
+        Self {
+            width,
+            height,
+            channels,
+            center,
+            scratch: vec![0; width * height * channels],
+            cols: vec![0; width],
+            lines: vec![0; height],
+        }
+    }
+
+    /// Like `Cube`, this redraws the whole canvas every frame, from the `old` image (which must
+    /// have the same size as `new`).
+    ///
+    /// `progress` goes from 0 to 1. The old image grows and blends into the new one, and the blur
+    /// is strongest halfway through. At 1, the canvas is exactly `new`.
+    pub fn draw(&mut self, canvas: &mut [u8], old: &[u8], new: &[u8], progress: f32) {
+        let (width, height, channels) = (self.width, self.height, self.channels);
+        assert_dimensions(canvas, new, width, height, channels);
+        assert_eq!(
+            old.len(),
+            new.len(),
+            "old and new images have different sizes"
+        );
+        let progress = progress.clamp(0.0, 1.0);
+
+        // the old image, zoomed in, blended into the new one along a smoothstep
+        self.scale_towards_center(1.0 / (1.0 + ZOOM_BLUR_ZOOM * progress));
+        let weight = (256.0 * progress * progress * (3.0 - 2.0 * progress)) as u32;
+        for (line, &src_line) in self.lines.iter().enumerate() {
+            for (col, &src_col) in self.cols.iter().enumerate() {
+                let i = (line * width + col) * channels;
+                let o = (src_line * width + src_col) * channels;
+                for c in 0..channels {
+                    let x = old[o + c] as u32 * (256 - weight);
+                    let y = new[i + c] as u32 * weight;
+                    canvas[i + c] = ((x + y) >> 8) as u8;
+                }
+            }
+        }
+
+        let length = ZOOM_BLUR_LENGTH * (std::f32::consts::PI * progress).sin();
+        if length * (width.max(height) as f32) < 1.0 {
+            return;
+        }
+        // each pass averages the canvas with itself scaled towards the center, by factors that
+        // double from one pass to the next, so that the samples spread evenly along the rays
+        let factor = (1.0 - length).powf(1.0 / (1 << ZOOM_BLUR_PASSES) as f32);
+        for pass in 0..ZOOM_BLUR_PASSES {
+            self.scale_towards_center(factor.powi(1 << pass));
+            self.scratch.copy_from_slice(canvas);
+            // rounding down and up in turns, so that the passes do not drift brighter or darker
+            let round = (pass & 1) as u16;
+            for (line, &src_line) in self.lines.iter().enumerate() {
+                for (col, &src_col) in self.cols.iter().enumerate() {
+                    let i = (line * width + col) * channels;
+                    let o = (src_line * width + src_col) * channels;
+                    for c in 0..channels {
+                        let sum = self.scratch[i + c] as u16 + self.scratch[o + c] as u16;
+                        canvas[i + c] = ((sum + round) >> 1) as u8;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Makes every pixel sample the one `scale` times as far from the center
+    fn scale_towards_center(&mut self, scale: f32) {
+        let scaled = |i: usize, center: f32, len: usize| {
+            let x = center + (i as f32 + 0.5 - center) * scale;
+            (x.max(0.0) as usize).min(len - 1)
+        };
+        for (col, src) in self.cols.iter_mut().enumerate() {
+            *src = scaled(col, self.center.0, self.width);
+        }
+        for (line, src) in self.lines.iter_mut().enumerate() {
+            *src = scaled(line, self.center.1, self.height);
+        }
+    }
+}
+
+/// When `PixelSort` is done sorting and its lines start snapping to the new image
+const PIXEL_SORT_SNAP: f32 = 0.8;
+/// How far apart `PixelSort`'s lines start sorting, as a fraction of the time they take
+const PIXEL_SORT_STAGGER: f32 = 0.5;
+
+/// The glitch art "pixel sort": the bright runs of each row (or column) of the old image get
+/// sorted by brightness, one line after the other, before every line snaps to the new image
+pub struct PixelSort {
+    width: usize,
+    height: usize,
+    channels: usize,
+    /// the brightness runs start at, as the sum of the pixel's channels
+    threshold: u16,
+    vertical: bool,
+    /// the old image, with its runs sorted. Empty until the first `draw`
+    sorted: Vec<u8>,
+}
+
+impl PixelSort {
+    /// Only runs of pixels at least `threshold` bright (from 0 to 1) are sorted, along rows, or
+    /// along columns if `vertical` is set
+    pub fn new(dimensions: (u32, u32), channels: usize, threshold: f32, vertical: bool) -> Self {
+        Self {
+            width: dimensions.0 as usize,
+            height: dimensions.1 as usize,
+            channels,
+            threshold: (threshold.clamp(0.0, 1.0) * 765.0).round() as u16,
+            vertical,
+            sorted: Vec::new(),
+        }
+    }
+
+    /// Like `Cube`, this redraws the whole canvas every frame, from the `old` image (which must
+    /// have the same size as `new`, and stay the same through the transition).
+    ///
+    /// `progress` goes from 0 to 1. At 1, the canvas is exactly `new`.
+    pub fn draw(&mut self, canvas: &mut [u8], old: &[u8], new: &[u8], progress: f32) {
+        let (width, height, channels) = (self.width, self.height, self.channels);
+        assert_dimensions(canvas, new, width, height, channels);
+        assert_eq!(
+            old.len(),
+            new.len(),
+            "old and new images have different sizes"
+        );
+        if self.sorted.is_empty() {
+            self.sort(old);
+        }
+        let progress = progress.clamp(0.0, 1.0);
+
+        let (lines, length) = if self.vertical {
+            (width, height)
+        } else {
+            (height, width)
+        };
+        // how many pixels of each line are sorted, from its start, and whether it snapped
+        let fronts: Vec<(usize, bool)> = (0..lines)
+            .map(|line| {
+                let delay = hash(line as u32) as f32 / u32::MAX as f32;
+                let sorted = progress / PIXEL_SORT_SNAP * (1.0 + PIXEL_SORT_STAGGER)
+                    - delay * PIXEL_SORT_STAGGER;
+                let front = (sorted.clamp(0.0, 1.0) * length as f32).round() as usize;
+                let snapped = progress >= PIXEL_SORT_SNAP + delay * (1.0 - PIXEL_SORT_SNAP);
+                (front, snapped)
+            })
+            .collect();
+
+        for y in 0..height {
+            for x in 0..width {
+                let (line, along) = if self.vertical { (x, y) } else { (y, x) };
+                let (front, snapped) = fronts[line];
+                let src = if snapped {
+                    new
+                } else if along < front {
+                    &self.sorted
+                } else {
+                    old
+                };
+                let i = (y * width + x) * channels;
+                canvas[i..i + channels].copy_from_slice(&src[i..i + channels]);
+            }
+        }
+    }
+
+    /// Fills `sorted` with `old`, each of its runs sorted from darkest to brightest
+    fn sort(&mut self, old: &[u8]) {
+        let (width, height, channels) = (self.width, self.height, self.channels);
+        self.sorted = old.to_vec();
+        let brightness = |i: usize| old[i..i + 3].iter().map(|&c| c as u16).sum::<u16>();
+        let (lines, length, step, line_step) = if self.vertical {
+            (width, height, width * channels, channels)
+        } else {
+            (height, width, channels, width * channels)
+        };
+
+        let mut run = Vec::with_capacity(length);
+        for line in 0..lines {
+            let start = line * line_step;
+            let mut along = 0;
+            while along < length {
+                if brightness(start + along * step) < self.threshold {
+                    along += 1;
+                    continue;
+                }
+                run.clear();
+                let begin = along;
+                while along < length && brightness(start + along * step) >= self.threshold {
+                    let i = start + along * step;
+                    run.push((brightness(i), i));
+                    along += 1;
+                }
+                run.sort_by_key(|&(brightness, _)| brightness);
+                for (k, &(_, src)) in run.iter().enumerate() {
+                    let dst = start + (begin + k) * step;
+                    self.sorted[dst..dst + channels].copy_from_slice(&old[src..src + channels]);
+                }
+            }
+        }
+    }
+}
+
+/// How `DoubleExposure` overlays the images
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlendMode {
+    /// the inverse of multiplying their inverses, which lightens, like two exposures of a film
+    Screen,
+    /// multiplying them, which darkens, like two slides stacked on a light table
+    Multiply,
+}
+
+/// A crossfade that overlays both images with a `BlendMode` on its way, like a double exposure
+pub struct DoubleExposure {
+    mode: BlendMode,
+    /// how much of the overlay shows halfway through, from 0 to 1
+    strength: f32,
+    /// for how much of the transition the overlay shows, from 0 to 1, centered halfway through
+    length: f32,
+}
+
+impl DoubleExposure {
+    pub fn new(mode: BlendMode, strength: f32, length: f32) -> Self {
+        Self {
+            mode,
+            strength: strength.clamp(0.0, 1.0),
+            length: length.clamp(0.0, 1.0),
+        }
+    }
+
+    /// How much of the overlay shows at `progress`: a smooth bump, as tall as `strength` and as
+    /// wide as `length`
+    pub fn overlay(&self, progress: f32) -> f32 {
+        if self.length <= 0.0 {
+            return 0.0;
+        }
+        let x = 1.0 - (progress - 0.5).abs() / (self.length / 2.0);
+        if x <= 0.0 {
+            return 0.0;
+        }
+        self.strength * x * x * (3.0 - 2.0 * x)
+    }
+
+    /// Like `Cube`, this redraws the whole canvas every frame, from the `old` image (which must
+    /// have the same size as `new`).
+    ///
+    /// `progress` goes from 0 to 1. At 1, the canvas is exactly `new`.
+    pub fn draw(&self, canvas: &mut [u8], old: &[u8], new: &[u8], progress: f32) {
+        assert_eq!(
+            canvas.len(),
+            new.len(),
+            "canvas and image have different sizes"
+        );
+        assert_eq!(
+            old.len(),
+            new.len(),
+            "old and new images have different sizes"
+        );
+        let progress = progress.clamp(0.0, 1.0);
+        let fade = (progress * 256.0) as u32;
+        let overlay = (self.overlay(progress) * 256.0) as u32;
+
+        for ((dst, &a), &b) in canvas.iter_mut().zip(old).zip(new) {
+            let (a, b) = (a as u32, b as u32);
+            let faded = (a * (256 - fade) + b * fade) >> 8;
+            let blended = match self.mode {
+                BlendMode::Screen => 255 - ((255 - a) * (255 - b) + 127) / 255,
+                BlendMode::Multiply => (a * b + 127) / 255,
+            };
+            *dst = ((faded * (256 - overlay) + blended * overlay) >> 8) as u8;
+        }
+    }
+}
+
+/// The old image breaking into triangular shards that fly away from a point and fall,
+/// revealing the new image behind them
+pub struct Shatter {
+    width: usize,
+    height: usize,
+    channels: usize,
+    shards: Vec<Shard>,
+    /// in pixels per second squared
+    gravity: f32,
+    /// in seconds
+    duration: f32,
+}
+
+struct Shard {
+    vertices: [(f32, f32); 3],
+    centroid: (f32, f32),
+    /// in pixels per second
+    velocity: (f32, f32),
+    /// in radians per second
+    spin: f32,
+}
+
+impl Shatter {
+    /// `center` is where the image breaks from, in pixels, `shards` roughly how many pieces it
+    /// breaks into, and `gravity` how fast they fall, in pixels per second squared. `duration`,
+    /// in seconds, is only used to move the shards in real time. `seed` decides the shards'
+    /// shapes and how they fly
+    pub fn new(
+        dimensions: (u32, u32),
+        channels: usize,
+        center: (f32, f32),
+        shards: u16,
+        gravity: f32,
+        duration: f32,
+        seed: u32,
+    ) -> Self {
+        let (width, height) = (dimensions.0 as f32, dimensions.1 as f32);
+        let mut state = hash(seed);
+        let mut random = || {
+            state = hash(state.wrapping_add(0x9e37_79b9));
+            state as f32 / u32::MAX as f32
+        };
+
+        // a grid of jittered points, every cell of it split into two shards. The points on the
+        // border stay there, so that the shards always cover the whole screen
+        let cells = (shards.max(2) / 2) as f32;
+        let cols = (cells * width / height).sqrt().round().max(1.0) as usize;
+        let rows = (cells / cols as f32).ceil().max(1.0) as usize;
+        let (cell_w, cell_h) = (width / cols as f32, height / rows as f32);
+        let mut points = Vec::with_capacity((cols + 1) * (rows + 1));
+        for row in 0..=rows {
+            for col in 0..=cols {
+                let mut x = col as f32 * cell_w;
+                let mut y = row as f32 * cell_h;
+                if col != 0 && col != cols {
+                    x += (random() - 0.5) * 0.8 * cell_w;
+                }
+                if row != 0 && row != rows {
+                    y += (random() - 0.5) * 0.8 * cell_h;
+                }
+                points.push((x, y));
+            }
+        }
+
+        let diagonal = width.hypot(height);
+        let mut shards = Vec::with_capacity(cols * rows * 2);
+        for row in 0..rows {
+            for col in 0..cols {
+                let i = row * (cols + 1) + col;
+                let [a, b, c, d] = [
+                    points[i],
+                    points[i + 1],
+                    points[i + cols + 1],
+                    points[i + cols + 2],
+                ];
+                let triangles = if random() < 0.5 {
+                    [[a, b, d], [a, d, c]]
+                } else {
+                    [[a, b, c], [b, d, c]]
+                };
+                for vertices in triangles {
+                    let centroid = (
+                        (vertices[0].0 + vertices[1].0 + vertices[2].0) / 3.0,
+                        (vertices[0].1 + vertices[1].1 + vertices[2].1) / 3.0,
+                    );
+                    let (dx, dy) = (centroid.0 - center.0, centroid.1 - center.1);
+                    let distance = dx.hypot(dy).max(1.0);
+                    let speed = diagonal * (0.1 + 0.3 * random());
+                    shards.push(Shard {
+                        vertices,
+                        centroid,
+                        velocity: (
+                            dx / distance * speed,
+                            dy / distance * speed - diagonal * 0.2 * random(),
+                        ),
+                        spin: (random() - 0.5) * 4.0,
+                    });
+                }
+            }
+        }
+
+        Self {
+            width: dimensions.0 as usize,
+            height: dimensions.1 as usize,
+            channels,
+            shards,
+            gravity,
+            duration: duration.max(0.0),
+        }
+    }
+
+    /// Like `Pixelate`, this redraws the whole canvas every frame, from the `old` image (which
+    /// must have the same size as `new`).
+    ///
+    /// `progress` goes from 0 to 1, and the shards fade away over its last quarter, so that at 1
+    /// the canvas is exactly `new`, even if some of them are still on the screen.
+    pub fn draw(&self, canvas: &mut [u8], old: &[u8], new: &[u8], progress: f32) {
+        let Self {
+            width,
+            height,
+            channels,
+            ref shards,
+            gravity,
+            duration,
+        } = *self;
+        assert_dimensions(canvas, new, width, height, channels);
+        assert_eq!(
+            old.len(),
+            new.len(),
+            "old and new images have different sizes"
+        );
+        let stride = width * channels;
+
+        canvas.copy_from_slice(new);
+        let progress = progress.clamp(0.0, 1.0);
+        if progress >= 1.0 {
+            return;
+        }
+        let opacity = ((1.0 - progress) * 4.0 * 256.0).min(256.0) as u16;
+        let t = progress * duration;
+
+        for shard in shards {
+            let (sin, cos) = (shard.spin * t).sin_cos();
+            let offset = (
+                shard.velocity.0 * t,
+                shard.velocity.1 * t + gravity * t * t / 2.0,
+            );
+            let (cx, cy) = shard.centroid;
+            let place = |(x, y): (f32, f32)| {
+                let (x, y) = (x - cx, y - cy);
+                (
+                    x * cos - y * sin + cx + offset.0,
+                    x * sin + y * cos + cy + offset.1,
+                )
+            };
+            let [a, b, c] = shard.vertices.map(place);
+
+            let left = a.0.min(b.0).min(c.0).floor().max(0.0) as usize;
+            let right = (a.0.max(b.0).max(c.0).ceil().max(0.0) as usize).min(width);
+            let top = a.1.min(b.1).min(c.1).floor().max(0.0) as usize;
+            let bottom = (a.1.max(b.1).max(c.1).ceil().max(0.0) as usize).min(height);
+            if left >= right || top >= bottom {
+                continue;
+            }
+
+            // edge functions, all positive inside the triangle whatever its winding. Pixels right
+            // on an edge belong to both shards sharing it, so there are no gaps between them
+            let area = (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0);
+            if area.abs() < f32::EPSILON {
+                continue;
+            }
+            let sign = area.signum();
+            let edge = |(x0, y0): (f32, f32), (x1, y1): (f32, f32), (x, y): (f32, f32)| {
+                sign * ((x1 - x0) * (y - y0) - (y1 - y0) * (x - x0)) >= -1e-3
+            };
+
+            for line in top..bottom {
+                for col in left..right {
+                    let p = (col as f32 + 0.5, line as f32 + 0.5);
+                    if !(edge(a, b, p) && edge(b, c, p) && edge(c, a, p)) {
+                        continue;
+                    }
+                    // back to where this pixel was in the old image
+                    let (x, y) = (p.0 - cx - offset.0, p.1 - cy - offset.1);
+                    let src_x = (x * cos + y * sin + cx)
+                        .floor()
+                        .clamp(0.0, (width - 1) as f32);
+                    let src_y = (y * cos - x * sin + cy)
+                        .floor()
+                        .clamp(0.0, (height - 1) as f32);
+                    let src = src_y as usize * stride + src_x as usize * channels;
+                    let dst = line * stride + col * channels;
+                    for c in 0..channels {
+                        let x = old[src + c] as u16 * opacity;
+                        let y = canvas[dst + c] as u16 * (256 - opacity);
+                        canvas[dst + c] = ((x as u32 + y as u32) >> 8) as u8;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIM: (u32, u32) = (64, 48);
+    const CHANNELS: usize = 3;
+    const LEN: usize = DIM.0 as usize * DIM.1 as usize * CHANNELS;
+
+    /// two images that differ in almost every byte, and a blank canvas to draw them on
+    fn images() -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+        let old = (0..LEN).map(|i| (i * 7) as u8).collect();
+        let new = (0..LEN).map(|i| (i * 13) as u8).collect();
+        (old, new, vec![0u8; LEN])
+    }
+
+    /// the first channel of the pixel at `x`, `y`
+    fn pixel(canvas: &[u8], x: usize, y: usize) -> u8 {
+        canvas[(y * DIM.0 as usize + x) * CHANNELS]
+    }
+
+    #[test]
+    fn shapes_should_cover_the_screen_at_their_end() {
+        let new = vec![255u8; LEN];
+        for angle in [0.0, 45.0, 90.0, 135.0, 180.0, 270.0] {
+            let mut canvas = vec![0u8; LEN];
+            let wipe = Wipe::new(DIM, CHANNELS, angle);
+            wipe.draw(&mut canvas, &new, wipe.end(), 255);
+            assert_eq!(canvas, new, "wipe with angle {angle}");
+        }
+
+        let center = (10.0, 40.0);
+        let mut canvas = vec![0u8; LEN];
+        let grow = Grow::new(DIM, CHANNELS, center);
+        grow.draw(&mut canvas, &new, grow.end() + 1.0, 255);
+        assert_eq!(canvas, new, "grow");
+
+        let mut canvas = vec![0u8; LEN];
+        Outer::new(DIM, CHANNELS, center).draw(&mut canvas, &new, 0.0, 255);
+        assert_eq!(canvas, new, "outer");
+
+        for (slats, vertical) in [(1, false), (7, false), (10, true), (1000, true)] {
+            let mut canvas = vec![0u8; LEN];
+            let blinds = Blinds::new(DIM, CHANNELS, slats, vertical);
+            blinds.draw(&mut canvas, &new, blinds.end(), 255);
+            assert_eq!(
+                canvas, new,
+                "blinds with {slats} slats, vertical: {vertical}"
+            );
+        }
+
+        let mask: Vec<u8> = (0..LEN / CHANNELS).map(|i| (i * 7) as u8).collect();
+        for softness in [0.0, 0.3, 1.0] {
+            let mut canvas = vec![0u8; LEN];
+            let luma = Luma::new(DIM, CHANNELS, &mask, softness);
+            luma.draw(&mut canvas, &new, luma.end(), 255);
+            assert_eq!(canvas, new, "luma with softness {softness}");
+        }
+
+        for (tile_size, angle) in [(1, 0.0), (10, 45.0), (16, 270.0), (1000, 90.0)] {
+            let mut canvas = vec![0u8; LEN];
+            let checkerboard = Checkerboard::new(DIM, CHANNELS, tile_size, angle);
+            checkerboard.draw(&mut canvas, &new, checkerboard.end(), 255);
+            assert_eq!(
+                canvas, new,
+                "checkerboard with {tile_size} pixel tiles, angle {angle}"
+            );
+        }
+
+        for (clockwise, soft_edge) in [(true, 0.0), (false, 30.0)] {
+            let mut canvas = vec![0u8; LEN];
+            let clock = Clock::new(DIM, CHANNELS, center, clockwise, soft_edge);
+            clock.draw(&mut canvas, &new, clock.end(), 255);
+            assert_eq!(canvas, new, "clock, clockwise: {clockwise}");
+        }
+
+        for ripple in [(20.0, 20.0), (5.0, 60.0), (0.0, 10.0)] {
+            let mut canvas = vec![0u8; LEN];
+            let ripple_t = Ripple::new(DIM, CHANNELS, center, ripple);
+            ripple_t.draw(&mut canvas, &new, ripple_t.end(), 255);
+            assert_eq!(canvas, new, "ripple {ripple:?}");
+        }
+
+        for (turns, thickness) in [(5.0, 30.0), (0.2, 0.0), (20.0, 1.0), (3.0, 200.0)] {
+            let mut canvas = vec![0u8; LEN];
+            let spiral = Spiral::new(DIM, CHANNELS, center, turns, thickness);
+            spiral.draw(&mut canvas, &new, spiral.end(), 255);
+            assert_eq!(canvas, new, "spiral with {turns} turns, {thickness} thick");
+        }
+    }
+
+    #[test]
+    fn cropped_shapes_should_draw_their_part_of_the_screen() {
+        let new = vec![255u8; LEN];
+        // a screen on the left, and a smaller one on its right, a bit lower
+        let parts = [((0, 0), (40, 48)), ((40, 8), (24, 32))];
+        let part = |canvas: &[u8], (x, y): (i32, i32), (width, height): (u32, u32)| {
+            let stride = DIM.0 as usize * CHANNELS;
+            (y as usize..y as usize + height as usize)
+                .flat_map(|line| {
+                    let begin = line * stride + x as usize * CHANNELS;
+                    canvas[begin..begin + width as usize * CHANNELS].to_vec()
+                })
+                .collect::<Vec<u8>>()
+        };
+        type Crop = Option<((i32, i32), (u32, u32))>;
+        let check = |name: &str, draw: &dyn Fn(Crop, &mut [u8])| {
+            let mut whole = vec![0u8; LEN];
+            draw(None, &mut whole);
+            for (origin, dim) in parts {
+                let mut canvas = vec![0u8; dim.0 as usize * dim.1 as usize * CHANNELS];
+                draw(Some((origin, dim)), &mut canvas);
+                assert_eq!(canvas, part(&whole, origin, dim), "{name} at {origin:?}");
+            }
+        };
+
+        let center = (50.0, 10.0);
+        check("wipe", &|crop, canvas| {
+            let wipe = Wipe::new(DIM, CHANNELS, 30.0);
+            let wipe = match crop {
+                Some((origin, dim)) => wipe.crop(origin, dim),
+                None => wipe,
+            };
+            wipe.draw(canvas, &new[..canvas.len()], 1500.0, 255);
+        });
+        check("grow", &|crop, canvas| {
+            let grow = Grow::new(DIM, CHANNELS, center);
+            let grow = match crop {
+                Some((origin, dim)) => grow.crop(origin, dim),
+                None => grow,
+            };
+            grow.draw(canvas, &new[..canvas.len()], 30.0, 255);
+        });
+        check("outer", &|crop, canvas| {
+            let outer = Outer::new(DIM, CHANNELS, center);
+            let outer = match crop {
+                Some((origin, dim)) => outer.crop(origin, dim),
+                None => outer,
+            };
+            outer.draw(canvas, &new[..canvas.len()], 30.0, 255);
+        });
+        check("ripple", &|crop, canvas| {
+            let ripple = Ripple::new(DIM, CHANNELS, center, (4.0, 10.0));
+            let ripple = match crop {
+                Some((origin, dim)) => ripple.crop(origin, dim),
+                None => ripple,
+            };
+            ripple.draw(canvas, &new[..canvas.len()], 30.0, 255);
+        });
+        check("clock", &|crop, canvas| {
+            let clock = Clock::new(DIM, CHANNELS, center, true, 20.0);
+            let clock = match crop {
+                Some((origin, dim)) => clock.crop(origin, dim),
+                None => clock,
+            };
+            clock.draw(canvas, &new[..canvas.len()], 4.0, 255);
+        });
+        check("spiral", &|crop, canvas| {
+            let spiral = Spiral::new(DIM, CHANNELS, center, 3.0, 4.0);
+            let spiral = match crop {
+                Some((origin, dim)) => spiral.crop(origin, dim),
+                None => spiral,
+            };
+            spiral.draw(canvas, &new[..canvas.len()], 10.0, 255);
+        });
+    }
+
+    #[test]
+    fn feathered_edges_should_blend_in_and_still_cover_the_screen() {
+        let new = vec![255u8; LEN];
+        let center = (32.0, 24.0);
+
+        // a 20 pixels circle, whose last 8 pixels blend in
+        let grow = Grow::new(DIM, CHANNELS, center).feather(8.0);
+        let mut canvas = vec![0u8; LEN];
+        grow.draw(&mut canvas, &new, 20.0, 255);
+        assert_eq!(pixel(&canvas, 32, 24), 255);
+        assert!((100..160).contains(&pixel(&canvas, 48, 24)));
+        assert_eq!(pixel(&canvas, 53, 24), 0);
+        let mut canvas = vec![0u8; LEN];
+        grow.draw(&mut canvas, &new, grow.end() + 1.0, 255);
+        assert_eq!(canvas, new, "feathered grow");
+
+        let outer = Outer::new(DIM, CHANNELS, center).feather(8.0);
+        let mut canvas = vec![0u8; LEN];
+        outer.draw(&mut canvas, &new, 12.0, 255);
+        assert_eq!(pixel(&canvas, 32, 24), 0);
+        assert!((100..160).contains(&pixel(&canvas, 48, 24)));
+        assert_eq!(pixel(&canvas, 60, 24), 255);
+        let mut canvas = vec![0u8; LEN];
+        outer.draw(&mut canvas, &new, outer.end(), 255);
+        assert_eq!(canvas, new, "feathered outer");
+
+        for angle in [0.0, 45.0, 90.0, 180.0, 270.0] {
+            let wipe = Wipe::new(DIM, CHANNELS, angle).feather(8.0);
+            let mut canvas = vec![0u8; LEN];
+            wipe.draw(&mut canvas, &new, wipe.end(), 255);
+            assert_eq!(canvas, new, "feathered wipe with angle {angle}");
+        }
+        // halfway through a horizontal wipe, the columns right behind the line are in between
+        let wipe = Wipe::new(DIM, CHANNELS, 0.0).feather(8.0);
+        let halfway = Wipe::new(DIM, CHANNELS, 0.0);
+        let mut hard = vec![0u8; LEN];
+        halfway.draw(
+            &mut hard,
+            &new,
+            (halfway.start() + halfway.end()) / 2.0,
+            255,
+        );
+        let mut soft = vec![0u8; LEN];
+        wipe.draw(
+            &mut soft,
+            &new,
+            (halfway.start() + halfway.end()) / 2.0,
+            255,
+        );
+        let row = |canvas: &[u8]| {
+            (0..DIM.0 as usize)
+                .map(|x| pixel(canvas, x, 0))
+                .collect::<Vec<_>>()
+        };
+        let (hard, soft) = (row(&hard), row(&soft));
+        let edge = hard
+            .iter()
+            .position(|&p| p == 255)
+            .expect("the wipe is halfway");
+        assert!(soft[..edge].iter().all(|&p| p == 0));
+        assert!(soft[edge..edge + 8].windows(2).all(|w| w[0] <= w[1]));
+        assert!(soft[edge] < 255 && soft[edge + 8] == 255);
+    }
+
+    #[test]
+    fn grow_circles_should_merge_and_cover_the_screen_sooner() {
+        let new = vec![255u8; LEN];
+        let corners = [(0.0, 0.0), (63.0, 0.0), (0.0, 47.0), (63.0, 47.0)];
+
+        let grow = Grow::from_points(DIM, CHANNELS, &corners);
+        assert!(grow.end() < Grow::new(DIM, CHANNELS, corners[0]).end());
+        let mut canvas = vec![0u8; LEN];
+        grow.draw(&mut canvas, &new, grow.end(), 255);
+        assert_eq!(canvas, new, "grow from the corners");
+
+        // every corner has its own circle, and the middle is still untouched
+        let mut canvas = vec![0u8; LEN];
+        grow.draw(&mut canvas, &new, 10.0, 255);
+        for (x, y) in [(2, 2), (61, 2), (2, 45), (61, 45)] {
+            assert_eq!(pixel(&canvas, x, y), 255, "corner at {x}x{y}");
+        }
+        assert_eq!(pixel(&canvas, 32, 24), 0);
+
+        // where two feathered circles overlap, the deeper one decides how far along a pixel is
+        let feathered =
+            Grow::from_points(DIM, CHANNELS, &[(20.0, 24.0), (30.0, 24.0)]).feather(8.0);
+        let single = Grow::new(DIM, CHANNELS, (30.0, 24.0)).feather(8.0);
+        let (mut both, mut one) = (vec![0u8; LEN], vec![0u8; LEN]);
+        feathered.draw(&mut both, &new, 12.0, 255);
+        single.draw(&mut one, &new, 12.0, 255);
+        for x in 26..DIM.0 as usize {
+            assert_eq!(pixel(&both, x, 24), pixel(&one, x, 24), "column {x}");
+        }
+        assert_eq!(pixel(&both, 20, 24), 255);
+    }
+
+    #[test]
+    fn ranges_should_merge_when_they_overlap() {
+        let mut ranges = vec![8..12, 0..3, 2..5, 5..6, 7..7, 10..11];
+        merge_ranges(&mut ranges);
+        assert_eq!(ranges, [0..6, 8..12]);
+    }
+
+    #[test]
+    fn perceptual_fades_should_keep_colors_bright() {
+        // halfway from red to green, with pixels stored as bgr
+        let (red, green) = ([0u8, 0, 255].repeat(4), [0u8, 255, 0].repeat(4));
+        let brightness = |colorspace| {
+            let mut canvas = red.clone();
+            Fade::new(colorspace, CHANNELS, true).draw(&mut canvas, &green, 128);
+            assert_eq!(canvas[0], 0, "{colorspace:?} added blue");
+            canvas[1] as u32 + canvas[2] as u32
+        };
+        let srgb = brightness(Colorspace::Srgb);
+        assert!(brightness(Colorspace::Linear) > srgb + 60);
+        assert!(brightness(Colorspace::Oklab) > srgb + 30);
+
+        for colorspace in [Colorspace::Srgb, Colorspace::Linear, Colorspace::Oklab] {
+            let mut canvas = red.clone();
+            Fade::new(colorspace, CHANNELS, true).draw(&mut canvas, &green, 256);
+            assert_eq!(canvas, green, "{colorspace:?}");
+        }
+    }
+
+    #[test]
+    fn circles_should_not_draw_at_their_start() {
+        let new = vec![255u8; LEN];
+        let mut canvas = vec![0u8; LEN];
+        Grow::new(DIM, CHANNELS, (10.0, 40.0)).draw(&mut canvas, &new, 0.0, 255);
+        let outer = Outer::new(DIM, CHANNELS, (10.0, 40.0));
+        outer.draw(&mut canvas, &new, outer.start() + 1.0, 255);
+        let ripple = Ripple::new(DIM, CHANNELS, (10.0, 40.0), (20.0, 20.0));
+        ripple.draw(&mut canvas, &new, ripple.start(), 255);
+        assert!(canvas.iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn clock_should_sweep_in_its_direction() {
+        let new = vec![255u8; LEN];
+        let center = (DIM.0 as f32 / 2.0, DIM.1 as f32 / 2.0);
+
+        // a quarter turn reveals the top right corner clockwise, and the top left one otherwise
+        let mut canvas = vec![0u8; LEN];
+        Clock::new(DIM, CHANNELS, center, true, 0.0).draw(&mut canvas, &new, 1.6, 255);
+        assert_eq!(pixel(&canvas, DIM.0 as usize - 1, 0), 255);
+        assert_eq!(pixel(&canvas, 0, 0), 0);
+
+        let mut canvas = vec![0u8; LEN];
+        Clock::new(DIM, CHANNELS, center, false, 0.0).draw(&mut canvas, &new, 1.6, 255);
+        assert_eq!(pixel(&canvas, 0, 0), 255);
+        assert_eq!(pixel(&canvas, DIM.0 as usize - 1, 0), 0);
+    }
+
+    #[test]
+    fn checkerboard_should_flip_every_other_tile_first() {
+        let new = vec![255u8; LEN];
+        // 16 pixel tiles, in a wave going from right to left
+        let checkerboard = Checkerboard::new(DIM, CHANNELS, 16, 0.0);
+
+        // the first wave only flips the first tile of each pair, and has yet to reach the left
+        let mut canvas = vec![0u8; LEN];
+        checkerboard.draw(&mut canvas, &new, 0.9, 255);
+        assert_eq!(pixel(&canvas, 16, 16), 255);
+        assert_eq!(pixel(&canvas, 16, 0), 0);
+        assert_eq!(pixel(&canvas, 63, 47), 0);
+        assert_eq!(pixel(&canvas, 0, 0), 0);
+
+        // and the second wave starts from the right too
+        checkerboard.draw(&mut canvas, &new, 1.1, 255);
+        assert_eq!(pixel(&canvas, 63, 47), 255);
+        assert_eq!(pixel(&canvas, 0, 16), 0);
+
+        let mut canvas = vec![0u8; LEN];
+        checkerboard.draw(&mut canvas, &new, 0.0, 255);
+        assert!(canvas.iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn spiral_should_leave_gaps_between_its_turns() {
+        let new = vec![255u8; LEN];
+        let center = (DIM.0 as f32 / 2.0, DIM.1 as f32 / 2.0);
+        // 4 turns out to the corners, so a turn every 10 pixels, 2 pixels thick
+        let spiral = Spiral::new(DIM, CHANNELS, center, 4.0, 2.0);
+
+        // two turns and a quarter in, the line just went past 3 o'clock, 22.5 pixels out
+        let mut canvas = vec![0u8; LEN];
+        spiral.draw(&mut canvas, &new, 2.26 * std::f32::consts::TAU, 255);
+        assert_eq!(pixel(&canvas, 32 + 22, 24), 255);
+        // the gap inside that closed, but most of the one outside is still open
+        assert_eq!(pixel(&canvas, 32 + 17, 24), 255);
+        assert_eq!(pixel(&canvas, 32 + 28, 24), 0);
+        // and it has yet to get to 9 o'clock
+        assert_eq!(pixel(&canvas, 3, 24), 0);
+
+        let mut canvas = vec![0u8; LEN];
+        spiral.draw(&mut canvas, &new, 0.0, 255);
+        assert!(canvas.iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn luma_should_follow_the_mask() {
+        let new = vec![255u8; LEN];
+        // darker on the left
+        let mask: Vec<u8> = (0..LEN / CHANNELS)
+            .map(|i| (i % DIM.0 as usize * 255 / (DIM.0 as usize - 1)) as u8)
+            .collect();
+
+        let mut canvas = vec![0u8; LEN];
+        Luma::new(DIM, CHANNELS, &mask, 0.0).draw(&mut canvas, &new, 0.5, 255);
+        assert_eq!(pixel(&canvas, 10, 5), 255);
+        assert_eq!(pixel(&canvas, 40, 5), 0);
+
+        // soft pixels just past the threshold only move a little
+        let mut canvas = vec![0u8; LEN];
+        Luma::new(DIM, CHANNELS, &mask, 0.5).draw(&mut canvas, &new, 0.5, 200);
+        assert_eq!(pixel(&canvas, 0, 5), 200);
+        assert!((1..100).contains(&pixel(&canvas, 25, 5)));
+        assert_eq!(pixel(&canvas, 40, 5), 0);
+
+        let mut canvas = vec![0u8; LEN];
+        Luma::new(DIM, CHANNELS, &mask, 0.5).draw(&mut canvas, &new, 0.0, 255);
+        assert!(canvas.iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn dissolve_should_depend_on_the_seed() {
+        let new = vec![255u8; LEN];
+        let mut a = vec![0u8; LEN];
+        let mut b = vec![0u8; LEN];
+        Dissolve::new(DIM, CHANNELS, 1).draw(&mut a, &new, 0.5, 255);
+        Dissolve::new(DIM, CHANNELS, 2).draw(&mut b, &new, 0.5, 255);
+        assert_ne!(a, b);
+        // roughly half the pixels are done
+        let done = a.iter().filter(|&&b| b == 255).count();
+        assert!(
+            (LEN * 2 / 5..LEN * 3 / 5).contains(&done),
+            "{done} of {LEN}"
+        );
+
+        Dissolve::new(DIM, CHANNELS, 1).draw(&mut a, &new, 0.0, 255);
+        assert!(a.iter().filter(|&&b| b == 255).count() == done);
+        Dissolve::new(DIM, CHANNELS, 1).draw(&mut a, &new, 1.0, 255);
+        assert_eq!(a, new);
+    }
+
+    #[test]
+    fn halftone_matrices_should_rank_every_cell_once() {
+        #[rustfmt::skip]
+        let bayer = [
+             0,  8,  2, 10,
+            12,  4, 14,  6,
+             3, 11,  1,  9,
+            15,  7, 13,  5,
+        ];
+        assert_eq!(bayer_matrix(4), bayer);
+        for size in [2, 8, 16] {
+            let mut ranks = blue_noise_matrix(size);
+            ranks.sort_unstable();
+            assert!(ranks.iter().copied().eq(0..(size * size) as u32), "{size}");
+        }
+
+        // the first quarter of the blue noise cells are spread out: far fewer of them touch than
+        // in white noise, where a sixteenth of the pairs of neighbors would
+        let size = 16;
+        let ranks = blue_noise_matrix(size);
+        let early = |x: usize, y: usize| ranks[(y % size) * size + x % size] < 64;
+        let mut touching = 0;
+        for y in 0..size {
+            for x in 0..size {
+                touching += (early(x, y) && early(x + 1, y)) as usize;
+                touching += (early(x, y) && early(x, y + 1)) as usize;
+            }
+        }
+        assert!(touching < 2 * size * size / 32, "{touching}");
+    }
+
+    #[test]
+    fn halftone_should_switch_in_the_order_of_its_matrix() {
+        let new = [255; 4 * 4 * 3];
+        let mut canvas = [0; 4 * 4 * 3];
+        let halftone = Halftone::new((4, 4), 3, 2, false);
+        // the first of the 4 cells of the 2x2 Bayer matrix, top left of each block
+        halftone.draw(&mut canvas, &new, 0.25, u8::MAX);
+        for (i, pixel) in canvas.chunks_exact(3).enumerate() {
+            let first = i % 2 == 0 && (i / 4) % 2 == 0;
+            assert_eq!(pixel[0], if first { 255 } else { 0 }, "{i}");
+        }
+        halftone.draw(&mut canvas, &new, 1.0, u8::MAX);
+        assert_eq!(canvas, new);
+    }
+
+    #[test]
+    fn sketch_should_draw_edges_before_filling_in() {
+        // dark on the left half, bright on the right one, so the only edge is down the middle
+        let new: Vec<u8> = (0..LEN)
+            .map(|i| {
+                if (i / CHANNELS) % DIM.0 as usize >= 32 {
+                    250
+                } else {
+                    10
+                }
+            })
+            .collect();
+        let sketch = Sketch::new(DIM, CHANNELS, &new, 0.5);
+
+        let mut canvas = vec![0u8; LEN];
+        sketch.draw(&mut canvas, &new, Sketch::EDGES, 255);
+        for y in [0, 24, 47] {
+            assert_eq!(pixel(&canvas, 31, y), 10);
+            assert_eq!(pixel(&canvas, 32, y), 250);
+            assert_eq!(pixel(&canvas, 2, y), 0);
+            assert_eq!(pixel(&canvas, 61, y), 0);
+        }
+        // then it fills in away from the edge, reaching the sides of the screen last
+        sketch.draw(&mut canvas, &new, 0.7, 255);
+        assert_eq!(pixel(&canvas, 40, 24), 250);
+        assert_eq!(pixel(&canvas, 63, 24), 0);
+        sketch.draw(&mut canvas, &new, 1.0, 255);
+        assert_eq!(canvas, new);
+
+        // without edges, everything switches at once
+        let flat = vec![128u8; LEN];
+        let mut canvas = vec![0u8; LEN];
+        Sketch::new(DIM, CHANNELS, &flat, 0.5).draw(&mut canvas, &flat, 0.5, 255);
+        assert_eq!(canvas, flat);
+    }
+
+    #[test]
+    fn pixelate_should_go_from_old_to_new() {
+        let (old, new, mut canvas) = images();
+
+        Pixelate::new(DIM, CHANNELS, 1).draw(&mut canvas, &old, &new, 0.0);
+        assert!(
+            canvas == old,
+            "pixelate with blocks of 1 pixel should start on old"
+        );
+
+        let pixelate = Pixelate::new(DIM, CHANNELS, 16);
+        pixelate.draw(&mut canvas, &old, &new, 0.0);
+        let stride = DIM.0 as usize * CHANNELS;
+        assert_eq!(
+            canvas[..3],
+            canvas[15 * stride + 15 * 3..15 * stride + 16 * 3]
+        );
+        assert_ne!(canvas[..3], canvas[16 * 3..17 * 3]);
+
+        pixelate.draw(&mut canvas, &old, &new, 1.0);
+        assert!(canvas == new, "pixelate should end on new");
+    }
+
+    #[test]
+    fn shatter_should_go_from_old_to_new() {
+        let (old, new, mut canvas) = images();
+        let center = (DIM.0 as f32 / 2.0, DIM.1 as f32 / 2.0);
+
+        for (shards, seed) in [(1, 0), (40, 1), (1000, 2)] {
+            let shatter = Shatter::new(DIM, CHANNELS, center, shards, 200.0, 1.0, seed);
+            shatter.draw(&mut canvas, &old, &new, 0.0);
+            assert!(
+                canvas == old,
+                "shatter with {shards} shards should start on old"
+            );
+            shatter.draw(&mut canvas, &old, &new, 0.2);
+            assert!(canvas != old && canvas != new);
+            shatter.draw(&mut canvas, &old, &new, 1.0);
+            assert!(
+                canvas == new,
+                "shatter with {shards} shards should end on new"
+            );
+        }
+    }
+
+    #[test]
+    fn cube_should_turn_from_old_to_new() {
+        let (old, new, mut canvas) = images();
+
+        for vertical_axis in [true, false] {
+            let cube = Cube::new(DIM, CHANNELS, vertical_axis, 60.0);
+            cube.draw(&mut canvas, &old, &new, 0.0);
+            assert!(canvas == old, "cube should start on old");
+            cube.draw(&mut canvas, &old, &new, 1.0);
+            assert!(canvas == new, "cube should end on new");
+        }
+
+        // halfway, the new face comes in from the left (or the top), and the old one leaves
+        // through the right (or the bottom)
+        let old = vec![100u8; LEN];
+        let new = vec![200u8; LEN];
+        let (w, h) = (DIM.0 as usize, DIM.1 as usize);
+        Cube::new(DIM, CHANNELS, true, 60.0).draw(&mut canvas, &old, &new, 0.5);
+        assert!(pixel(&canvas, w / 4, h / 2) > pixel(&canvas, w * 3 / 4, h / 2));
+        Cube::new(DIM, CHANNELS, false, 60.0).draw(&mut canvas, &old, &new, 0.5);
+        assert!(pixel(&canvas, w / 2, h / 4) > pixel(&canvas, w / 2, h * 3 / 4));
+        assert!(pixel(&canvas, w / 2, h * 3 / 4) > 0);
+    }
+
+    #[test]
+    fn zoom_blur_should_blend_without_blurring_the_center() {
+        let (old, new, mut canvas) = images();
+        let mut zoom = ZoomBlur::new(DIM, CHANNELS, (20.5, 30.5));
+        zoom.draw(&mut canvas, &old, &new, 0.0);
+        assert!(canvas == old, "zoom blur should start on old");
+        zoom.draw(&mut canvas, &old, &new, 1.0);
+        assert!(canvas == new, "zoom blur should end on new");
+
+        // the rays all start at the center, so it only blends
+        zoom.draw(&mut canvas, &old, &new, 0.5);
+        let i = (30 * DIM.0 as usize + 20) * CHANNELS;
+        for c in 0..CHANNELS {
+            let blended = (old[i + c] as u32 + new[i + c] as u32) / 2;
+            assert!(canvas[i + c].abs_diff(blended as u8) <= 2, "channel {c}");
+        }
+    }
+
+    #[test]
+    fn pixel_sort_should_sort_bright_runs_and_end_on_new() {
+        // one row: a dark pixel splitting two bright runs, each out of order
+        let old = [
+            200, 200, 200, 90, 90, 90, 0, 0, 0, 250, 250, 250, 100, 100, 100,
+        ];
+        let new = [7; 15];
+        let mut canvas = [0; 15];
+        let mut sort = PixelSort::new((5, 1), 3, 0.3, false);
+        sort.draw(&mut canvas, &old, &new, 0.0);
+        assert_eq!(canvas, old);
+        sort.draw(&mut canvas, &old, &new, 0.75);
+        let sorted = [
+            90, 90, 90, 200, 200, 200, 0, 0, 0, 100, 100, 100, 250, 250, 250,
+        ];
+        assert_eq!(canvas, sorted);
+        sort.draw(&mut canvas, &old, &new, 1.0);
+        assert_eq!(canvas, new);
+
+        // the same pixels, as a column
+        let mut canvas = [0; 15];
+        let mut sort = PixelSort::new((1, 5), 3, 0.3, true);
+        sort.draw(&mut canvas, &old, &new, 0.75);
+        assert_eq!(canvas, sorted);
+    }
+
+    #[test]
+    fn double_exposure_should_blend_halfway_and_end_on_new() {
+        let old = [100, 0, 255];
+        let new = [200, 255, 255];
+        let mut canvas = [0; 3];
+        let screen = DoubleExposure::new(BlendMode::Screen, 1.0, 0.5);
+        screen.draw(&mut canvas, &old, &new, 0.0);
+        assert_eq!(canvas, old);
+        screen.draw(&mut canvas, &old, &new, 0.5);
+        assert_eq!(canvas, [222, 255, 255]);
+        // the overlay is over a quarter of the way before the end
+        assert_eq!(screen.overlay(0.75), 0.0);
+        screen.draw(&mut canvas, &old, &new, 1.0);
+        assert_eq!(canvas, new);
+
+        let multiply = DoubleExposure::new(BlendMode::Multiply, 1.0, 0.5);
+        multiply.draw(&mut canvas, &old, &new, 0.5);
+        assert_eq!(canvas, [78, 0, 255]);
+        assert!(multiply.overlay(0.4) > 0.0 && multiply.overlay(0.4) < 1.0);
+    }
+
+    #[test]
+    fn push_should_move_both_images() {
+        let (old, new, mut canvas) = images();
+        for angle in [0.0, 90.0, 135.0, 270.0] {
+            let push = Push::new(DIM, CHANNELS, angle);
+            push.draw(&mut canvas, &old, &new, 0.0);
+            assert!(canvas == old, "push should start on old");
+            push.draw(&mut canvas, &old, &new, 1.0);
+            assert!(canvas == new, "push should end on new");
+        }
+
+        // at 0, the new image comes in from the right, pushing the old one to the left
+        let (w, stride) = (DIM.0 as usize, DIM.0 as usize * CHANNELS);
+        Push::new(DIM, CHANNELS, 0.0).draw(&mut canvas, &old, &new, 0.25);
+        let moved = (w as f32 * 0.25).round() as usize;
+        let line = 3 * stride;
+        assert_eq!(
+            canvas[line..line + (w - moved) * CHANNELS],
+            old[line + moved * CHANNELS..line + stride]
+        );
+        assert_eq!(
+            canvas[line + (w - moved) * CHANNELS..line + stride],
+            new[line..line + moved * CHANNELS]
+        );
+    }
+
+    #[test]
+    fn simple_should_finish() {
+        let new: Vec<u8> = (0..LEN).map(|i| i as u8).collect();
+        let mut canvas = vec![0u8; LEN];
+        let mut frames = 0;
+        while !simple(&mut canvas, &new, 2) {
+            frames += 1;
+            assert!(frames < 128, "simple transition did not finish");
+        }
+    }
+}
@@ -0,0 +1,474 @@
+//! Golden tests for the transitions.
+//!
+//! Every case renders a transition between two fixed images, at a fixed resolution, through a
+//! fixed sequence of progress values (the same way the daemon does it, frame after frame), and
+//! hashes every frame. The result is compared against the hashes in `GOLDEN`, so any change to
+//! what the transitions look like makes this test fail, even if it is subtle.
+//!
+//! If a change is intentional, regenerate the table with:
+//!
+//! ```sh
+//! SWWW_BLESS=1 cargo test -p swww-render --test golden -- --nocapture
+//! ```
+//!
+//! and paste the output over `GOLDEN`. Note the hashes depend on the platform's floating point
+//! functions (`sin`, `cos`, `sqrt`), which are not guaranteed to match bit for bit everywhere.
+use swww_render::transitions::{
+    fade, simple, BlendMode, Blinds, Checkerboard, Clock, Cube, Dissolve, DoubleExposure, Grow,
+    Halftone, Luma, Outer, PixelSort, Pixelate, Push, Ripple, Shatter, Sketch, Spiral, Wave, Wipe,
+    ZoomBlur,
+};
+
+const GOLDEN: &[(&str, u64)] = &[
+    ("simple_64x48x3", 0xee07aaa1341bc0d0),
+    ("fade_64x48x3", 0x6dbad2e1ab1a9e61),
+    ("wipe_0_64x48x3", 0xd0a97ee9ed6e4b33),
+    ("wipe_45_64x48x3", 0x32f6c17bb69adce1),
+    ("wipe_90_64x48x3", 0xd014db0ffebdf6dc),
+    ("wipe_200_64x48x3", 0x903e0714d942479a),
+    ("wave_45_64x48x3", 0xab7dd3e7f49f394f),
+    ("wave_135_64x48x3", 0xcf604209354b28c2),
+    ("grow_50%_50%_64x48x3", 0x539d3cd912cbc756),
+    ("outer_50%_50%_64x48x3", 0x7a31181e7ba74939),
+    ("grow_10%_90%_64x48x3", 0xf62bc00ae2feaf09),
+    ("outer_10%_90%_64x48x3", 0x1931da108358d9bf),
+    ("grow_100%_0%_64x48x3", 0xe97130764f40b94d),
+    ("outer_100%_0%_64x48x3", 0x654decfeb4e2fa50),
+    ("grow_corners_64x48x3", 0x32aa41ee6cb4c528),
+    ("ripple_50%_50%_64x48x3", 0xc5e8df0733a5fb8f),
+    ("ripple_10%_90%_64x48x3", 0xd4e2e9e577b39df3),
+    ("blinds_6_horizontal_64x48x3", 0xc13c78c7261c77e2),
+    ("blinds_5_vertical_64x48x3", 0x429a7b01c6d17749),
+    ("clock_50%_50%_cw_64x48x3", 0x7ebd8564f884b57e),
+    ("clock_10%_90%_ccw_64x48x3", 0xa50f0c39594d95f2),
+    ("dissolve_0_64x48x3", 0xf34ebf5a8917fb79),
+    ("dissolve_deadbeef_64x48x3", 0xa208a4586c9af3a1),
+    ("halftone_bayer_4_64x48x3", 0xb4a1d705ab1f05e3),
+    ("halftone_bluenoise_16_64x48x3", 0xbaf2f7b44d064f70),
+    ("sketch_0.02_64x48x3", 0xb91e6f7769be4316),
+    ("sketch_0.5_64x48x3", 0x778e49a91919c665),
+    ("pixelate_1_64x48x3", 0x39b87599400d95b3),
+    ("pixelate_16_64x48x3", 0xd8d88c678040a3b2),
+    ("pixelate_100_64x48x3", 0xab8bad30ae89c788),
+    ("cube_vertical_60_64x48x3", 0xcc38d5208c8e5158),
+    ("cube_horizontal_120_64x48x3", 0x98d582cc5b025789),
+    ("push_0_64x48x3", 0x6bebddd3d70642a7),
+    ("push_90_64x48x3", 0x93e9e481e341f3e5),
+    ("push_200_64x48x3", 0xb58baeba98244f05),
+    ("zoomblur_0.5_0.5_64x48x3", 0xff7a9dc59927353c),
+    ("zoomblur_0.2_0.9_64x48x3", 0xa2e528c69bde164f),
+    ("pixelsort_0.25_horizontal_64x48x3", 0xc4ecd73b206abd4a),
+    ("pixelsort_0.6_vertical_64x48x3", 0x3d044dca14ba67b7),
+    ("doubleexposure_screen_1_0.6_64x48x3", 0xcdc68f7ec6758351),
+    ("doubleexposure_multiply_0.7_1_64x48x3", 0xd427f79f56af1139),
+    ("shatter_40_2000_0_64x48x3", 0xd75382cdaf35a882),
+    ("shatter_300_-500_deadbeef_64x48x3", 0x36b8ea72cb19ddfd),
+    ("spiral_50%_50%_5_30_64x48x3", 0x7014f7993f456144),
+    ("spiral_10%_90%_2.5_4_64x48x3", 0xe71ef2cd6bd54290),
+    ("checkerboard_8_45_64x48x3", 0x4b4a71c57b47dc3b),
+    ("checkerboard_20_270_64x48x3", 0x61dbe80c5796d8c0),
+    ("luma_diagonal_0_64x48x3", 0x4fe386634d0b6906),
+    ("luma_rings_0.3_64x48x3", 0xc51fadda74e37073),
+    ("simple_37x53x4", 0x2b8cba1157afe815),
+    ("fade_37x53x4", 0x1ceca1689e3a76ea),
+    ("wipe_0_37x53x4", 0x164746b415c10813),
+    ("wipe_45_37x53x4", 0xe05a2980aa989a9b),
+    ("wipe_90_37x53x4", 0xd0eaf842a08fdb30),
+    ("wipe_200_37x53x4", 0x97f3c825e49a9837),
+    ("wave_45_37x53x4", 0x6ea9b657b240c2a4),
+    ("wave_135_37x53x4", 0x005c0df862e57c8f),
+    ("grow_50%_50%_37x53x4", 0xb19c9f6503d3f8f5),
+    ("outer_50%_50%_37x53x4", 0xe4a835f63fe16268),
+    ("grow_10%_90%_37x53x4", 0xe23247301262e71c),
+    ("outer_10%_90%_37x53x4", 0x4eaeb1eab155094b),
+    ("grow_100%_0%_37x53x4", 0x44d22c8bc9d53ec5),
+    ("outer_100%_0%_37x53x4", 0x7e2708ad872b0400),
+    ("grow_corners_37x53x4", 0x8519f4dce4b40404),
+    ("ripple_50%_50%_37x53x4", 0xb332db9918acb515),
+    ("ripple_10%_90%_37x53x4", 0x44c7473baea525d5),
+    ("blinds_6_horizontal_37x53x4", 0x2ea7e5e9280ac3f1),
+    ("blinds_5_vertical_37x53x4", 0x184b99c4bb72e386),
+    ("clock_50%_50%_cw_37x53x4", 0x1a9aa042a87d7e63),
+    ("clock_10%_90%_ccw_37x53x4", 0xc8aaee43a1ae19a4),
+    ("dissolve_0_37x53x4", 0x000137c1e73a1a6a),
+    ("dissolve_deadbeef_37x53x4", 0x7aeac3b7889a4d07),
+    ("halftone_bayer_4_37x53x4", 0x2022d290fff1615a),
+    ("halftone_bluenoise_16_37x53x4", 0x767d2103c0bf641b),
+    ("sketch_0.02_37x53x4", 0xecd22280514d3c03),
+    ("sketch_0.5_37x53x4", 0x00c63b9bd7a0c381),
+    ("pixelate_1_37x53x4", 0xa31f0c06150795a0),
+    ("pixelate_16_37x53x4", 0x7ee78e42fc8b6195),
+    ("pixelate_100_37x53x4", 0x0b1ffb9a75d38c1c),
+    ("cube_vertical_60_37x53x4", 0x4689eaacb8248284),
+    ("cube_horizontal_120_37x53x4", 0xee6e5269b16c0797),
+    ("push_0_37x53x4", 0xda176b4987c9df6c),
+    ("push_90_37x53x4", 0xae96bf36bcf8fee2),
+    ("push_200_37x53x4", 0xb66c5e33e39ff8fe),
+    ("zoomblur_0.5_0.5_37x53x4", 0xecf909c86e6dc7b6),
+    ("zoomblur_0.2_0.9_37x53x4", 0xef3b730e04daf747),
+    ("pixelsort_0.25_horizontal_37x53x4", 0x872fc6b97860147b),
+    ("pixelsort_0.6_vertical_37x53x4", 0xdb32bad63e6ff10c),
+    ("doubleexposure_screen_1_0.6_37x53x4", 0x7facc20fec8d15e3),
+    ("doubleexposure_multiply_0.7_1_37x53x4", 0x1bcd9b9c041c5d2c),
+    ("shatter_40_2000_0_37x53x4", 0x6114abbdf3e2e559),
+    ("shatter_300_-500_deadbeef_37x53x4", 0xfc727ce2c790bf4f),
+    ("spiral_50%_50%_5_30_37x53x4", 0x9f98e89d3f659a1e),
+    ("spiral_10%_90%_2.5_4_37x53x4", 0x6e32a36bc7a9ccbe),
+    ("checkerboard_8_45_37x53x4", 0x3d0d2f642c8715f9),
+    ("checkerboard_20_270_37x53x4", 0x5463e0d5a4f3cc2f),
+    ("luma_diagonal_0_37x53x4", 0x1a103c936212e7a1),
+    ("luma_rings_0.3_37x53x4", 0x15a2cf105cc94b18),
+];
+
+/// number of frames we render for each transition, not counting the final `simple` ones
+const FRAMES: usize = 8;
+const STEP: u8 = 30;
+
+struct Images {
+    old: Vec<u8>,
+    new: Vec<u8>,
+}
+
+impl Images {
+    /// two deterministic images with a bit of everything: gradients, hard edges and noise
+    fn new(dim: (u32, u32), channels: usize) -> Self {
+        let (w, h) = (dim.0 as usize, dim.1 as usize);
+        let mut old = Vec::with_capacity(w * h * channels);
+        let mut new = Vec::with_capacity(w * h * channels);
+        let mut state: u32 = 0x9e3779b9;
+        for y in 0..h {
+            for x in 0..w {
+                // xorshift32
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                for c in 0..channels {
+                    old.push(((x * 255 / w) as u8).wrapping_add(c as u8 * 85));
+                    let checker = if (x / 8 + y / 8) % 2 == 0 { 200 } else { 40 };
+                    new.push(checker ^ ((state >> (c * 8)) as u8 & 0x0f));
+                }
+            }
+        }
+        Self { old, new }
+    }
+}
+
+/// FNV-1a, so the hashes do not depend on the rust version
+fn hash(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// the values a transition goes through, from `start` to `end`, one per frame
+fn progress(start: f64, end: f64) -> impl Iterator<Item = f64> {
+    (0..=FRAMES).map(move |i| start + (end - start) * i as f64 / FRAMES as f64)
+}
+
+/// runs `draw` once per progress value, then `simple` until the canvas reaches the new image,
+/// hashing every frame
+fn render(
+    images: &Images,
+    (start, end): (f64, f64),
+    mut draw: impl FnMut(&mut [u8], &[u8], f64),
+) -> u64 {
+    let mut canvas = images.old.clone();
+    let mut h = 0xcbf29ce484222325;
+    for p in progress(start, end) {
+        draw(&mut canvas, &images.new, p);
+        h = hash(h, &canvas);
+    }
+    let mut frames = 0;
+    while !simple(&mut canvas, &images.new, STEP / 4 + 4) {
+        h = hash(h, &canvas);
+        frames += 1;
+        assert!(frames < 256, "transition never finished");
+    }
+    assert_eq!(
+        canvas, images.new,
+        "transition did not end in the new image"
+    );
+    h
+}
+
+fn cases() -> Vec<(String, u64)> {
+    let mut cases = Vec::new();
+    for (dim, channels) in [((64, 48), 3), ((37, 53), 4)] {
+        let images = Images::new(dim, channels);
+        let suffix = format!("{}x{}x{channels}", dim.0, dim.1);
+
+        let h = render(&images, (0.0, 0.0), |canvas, new, _| {
+            simple(canvas, new, STEP);
+        });
+        cases.push((format!("simple_{suffix}"), h));
+
+        let h = render(&images, (0.0, 256.0), |canvas, new, p| {
+            fade(canvas, new, p.min(255.0) as u16)
+        });
+        cases.push((format!("fade_{suffix}"), h));
+
+        for angle in [0.0, 45.0, 90.0, 200.0] {
+            let wipe = Wipe::new(dim, channels, angle);
+            let h = render(&images, (wipe.start(), wipe.end()), |canvas, new, p| {
+                wipe.draw(canvas, new, p, STEP)
+            });
+            cases.push((format!("wipe_{angle}_{suffix}"), h));
+        }
+
+        for (angle, wave) in [(45.0, (20.0, 20.0)), (135.0, (5.0, 10.0))] {
+            let wave = Wave::new(dim, channels, angle, wave);
+            let h = render(&images, (wave.start(), wave.end()), |canvas, new, p| {
+                wave.draw(canvas, new, p, STEP)
+            });
+            cases.push((format!("wave_{angle}_{suffix}"), h));
+        }
+
+        for (x, y) in [(50, 50), (10, 90), (100, 0)] {
+            let center = (
+                x as f32 / 100.0 * dim.0 as f32,
+                y as f32 / 100.0 * dim.1 as f32,
+            );
+            let grow = Grow::new(dim, channels, center);
+            let h = render(&images, (0.0, grow.end() as f64), |canvas, new, p| {
+                grow.draw(canvas, new, p as f32, STEP)
+            });
+            cases.push((format!("grow_{x}%_{y}%_{suffix}"), h));
+
+            let outer = Outer::new(dim, channels, center);
+            let h = render(&images, (outer.start() as f64, 0.0), |canvas, new, p| {
+                outer.draw(canvas, new, p as f32, STEP)
+            });
+            cases.push((format!("outer_{x}%_{y}%_{suffix}"), h));
+        }
+
+        let corners = [
+            (0.0, 0.0),
+            (dim.0 as f32, 0.0),
+            (0.0, dim.1 as f32),
+            (dim.0 as f32, dim.1 as f32),
+        ];
+        let grow = Grow::from_points(dim, channels, &corners).feather(4.0);
+        let h = render(&images, (0.0, grow.end() as f64), |canvas, new, p| {
+            grow.draw(canvas, new, p as f32, STEP)
+        });
+        cases.push((format!("grow_corners_{suffix}"), h));
+
+        for ((x, y), ripple) in [((50, 50), (20.0, 20.0)), ((10, 90), (4.0, 12.0))] {
+            let center = (
+                x as f32 / 100.0 * dim.0 as f32,
+                y as f32 / 100.0 * dim.1 as f32,
+            );
+            let ripple = Ripple::new(dim, channels, center, ripple);
+            let h = render(
+                &images,
+                (ripple.start() as f64, ripple.end() as f64),
+                |canvas, new, p| ripple.draw(canvas, new, p as f32, STEP),
+            );
+            cases.push((format!("ripple_{x}%_{y}%_{suffix}"), h));
+        }
+
+        for (slats, vertical) in [(6, false), (5, true)] {
+            let blinds = Blinds::new(dim, channels, slats, vertical);
+            let h = render(&images, (0.0, blinds.end() as f64), |canvas, new, p| {
+                blinds.draw(canvas, new, p as f32, STEP)
+            });
+            let direction = if vertical { "vertical" } else { "horizontal" };
+            cases.push((format!("blinds_{slats}_{direction}_{suffix}"), h));
+        }
+
+        for ((x, y), clockwise, soft_edge) in [((50, 50), true, 0.0), ((10, 90), false, 30.0)] {
+            let center = (
+                x as f32 / 100.0 * dim.0 as f32,
+                y as f32 / 100.0 * dim.1 as f32,
+            );
+            let clock = Clock::new(dim, channels, center, clockwise, soft_edge);
+            let h = render(&images, (0.0, clock.end() as f64), |canvas, new, p| {
+                clock.draw(canvas, new, p as f32, STEP)
+            });
+            let direction = if clockwise { "cw" } else { "ccw" };
+            cases.push((format!("clock_{x}%_{y}%_{direction}_{suffix}"), h));
+        }
+
+        for seed in [0, 0xdeadbeef] {
+            let dissolve = Dissolve::new(dim, channels, seed);
+            let h = render(&images, (0.0, 1.0), |canvas, new, p| {
+                dissolve.draw(canvas, new, p as f32, STEP)
+            });
+            cases.push((format!("dissolve_{seed:x}_{suffix}"), h));
+        }
+
+        for (size, blue_noise) in [(4, false), (16, true)] {
+            let halftone = Halftone::new(dim, channels, size, blue_noise);
+            let h = render(&images, (0.0, 1.0), |canvas, new, p| {
+                halftone.draw(canvas, new, p as f32, STEP)
+            });
+            let matrix = if blue_noise { "bluenoise" } else { "bayer" };
+            cases.push((format!("halftone_{matrix}_{size}_{suffix}"), h));
+        }
+
+        for threshold in [0.02, 0.5] {
+            let sketch = Sketch::new(dim, channels, &images.new, threshold);
+            let h = render(&images, (0.0, 1.0), |canvas, new, p| {
+                sketch.draw(canvas, new, p as f32, STEP)
+            });
+            cases.push((format!("sketch_{threshold}_{suffix}"), h));
+        }
+
+        for block_size in [1, 16, 100] {
+            let pixelate = Pixelate::new(dim, channels, block_size);
+            let h = render(&images, (0.0, 1.0), |canvas, new, p| {
+                pixelate.draw(canvas, &images.old, new, p as f32)
+            });
+            cases.push((format!("pixelate_{block_size}_{suffix}"), h));
+        }
+
+        for (vertical_axis, fov) in [(true, 60.0), (false, 120.0)] {
+            let cube = Cube::new(dim, channels, vertical_axis, fov);
+            let h = render(&images, (0.0, 1.0), |canvas, new, p| {
+                cube.draw(canvas, &images.old, new, p as f32)
+            });
+            let axis = if vertical_axis {
+                "vertical"
+            } else {
+                "horizontal"
+            };
+            cases.push((format!("cube_{axis}_{fov}_{suffix}"), h));
+        }
+
+        for angle in [0.0, 90.0, 200.0] {
+            let push = Push::new(dim, channels, angle);
+            let h = render(&images, (0.0, 1.0), |canvas, new, p| {
+                push.draw(canvas, &images.old, new, p as f32)
+            });
+            cases.push((format!("push_{angle}_{suffix}"), h));
+        }
+
+        for (x, y) in [(0.5, 0.5), (0.2, 0.9)] {
+            let center = (dim.0 as f32 * x, dim.1 as f32 * y);
+            let mut zoom = ZoomBlur::new(dim, channels, center);
+            let h = render(&images, (0.0, 1.0), |canvas, new, p| {
+                zoom.draw(canvas, &images.old, new, p as f32)
+            });
+            cases.push((format!("zoomblur_{x}_{y}_{suffix}"), h));
+        }
+
+        for (threshold, vertical) in [(0.25, false), (0.6, true)] {
+            let mut sort = PixelSort::new(dim, channels, threshold, vertical);
+            let h = render(&images, (0.0, 1.0), |canvas, new, p| {
+                sort.draw(canvas, &images.old, new, p as f32)
+            });
+            let direction = if vertical { "vertical" } else { "horizontal" };
+            cases.push((format!("pixelsort_{threshold}_{direction}_{suffix}"), h));
+        }
+
+        for (mode, strength, length) in [
+            (BlendMode::Screen, 1.0, 0.6),
+            (BlendMode::Multiply, 0.7, 1.0),
+        ] {
+            let exposure = DoubleExposure::new(mode, strength, length);
+            let h = render(&images, (0.0, 1.0), |canvas, new, p| {
+                exposure.draw(canvas, &images.old, new, p as f32)
+            });
+            let mode = format!("{mode:?}").to_lowercase();
+            cases.push((
+                format!("doubleexposure_{mode}_{strength}_{length}_{suffix}"),
+                h,
+            ));
+        }
+
+        for (shards, gravity, seed) in [(40, 2000.0, 0), (300, -500.0, 0xdeadbeef)] {
+            let center = (dim.0 as f32 * 0.3, dim.1 as f32 * 0.6);
+            let shatter = Shatter::new(dim, channels, center, shards, gravity, 1.0, seed);
+            let h = render(&images, (0.0, 1.0), |canvas, new, p| {
+                shatter.draw(canvas, &images.old, new, p as f32)
+            });
+            cases.push((format!("shatter_{shards}_{gravity}_{seed:x}_{suffix}"), h));
+        }
+
+        for ((x, y), turns, thickness) in [((50, 50), 5.0, 30.0), ((10, 90), 2.5, 4.0)] {
+            let center = (
+                x as f32 / 100.0 * dim.0 as f32,
+                y as f32 / 100.0 * dim.1 as f32,
+            );
+            let spiral = Spiral::new(dim, channels, center, turns, thickness);
+            let h = render(&images, (0.0, spiral.end() as f64), |canvas, new, p| {
+                spiral.draw(canvas, new, p as f32, STEP)
+            });
+            cases.push((format!("spiral_{x}%_{y}%_{turns}_{thickness}_{suffix}"), h));
+        }
+
+        for (tile_size, angle) in [(8, 45.0), (20, 270.0)] {
+            let checkerboard = Checkerboard::new(dim, channels, tile_size, angle);
+            let h = render(
+                &images,
+                (0.0, checkerboard.end() as f64),
+                |canvas, new, p| checkerboard.draw(canvas, new, p as f32, STEP),
+            );
+            cases.push((format!("checkerboard_{tile_size}_{angle}_{suffix}"), h));
+        }
+
+        // a diagonal gradient, and rings around the middle of the top edge
+        let (w, h) = (dim.0 as usize, dim.1 as usize);
+        let masks: [(&str, Vec<u8>); 2] = [
+            (
+                "diagonal",
+                (0..w * h)
+                    .map(|i| ((i % w + i / w) * 255 / (w + h - 2)) as u8)
+                    .collect(),
+            ),
+            (
+                "rings",
+                (0..w * h)
+                    .map(|i| {
+                        let distance = ((i % w) as f32 - w as f32 / 2.0).hypot((i / w) as f32);
+                        (distance * 20.0) as u32 as u8
+                    })
+                    .collect(),
+            ),
+        ];
+        for ((name, mask), softness) in masks.iter().zip([0.0, 0.3]) {
+            let luma = Luma::new(dim, channels, mask, softness);
+            let h = render(&images, (0.0, luma.end() as f64), |canvas, new, p| {
+                luma.draw(canvas, new, p as f32, STEP)
+            });
+            cases.push((format!("luma_{name}_{softness}_{suffix}"), h));
+        }
+    }
+    cases
+}
+
+#[test]
+fn transitions_should_match_golden_hashes() {
+    let actual = cases();
+    let table: String = actual
+        .iter()
+        .map(|(name, h)| format!("    (\"{name}\", {h:#018x}),\n"))
+        .collect();
+
+    if std::env::var_os("SWWW_BLESS").is_some() {
+        println!("const GOLDEN: &[(&str, u64)] = &[\n{table}];");
+        return;
+    }
+
+    let mut mismatches = Vec::new();
+    for (name, h) in &actual {
+        match GOLDEN.iter().find(|(n, _)| n == name) {
+            Some((_, expected)) if expected == h => (),
+            Some((_, expected)) => {
+                mismatches.push(format!("{name}: expected {expected:#018x}, got {h:#018x}"))
+            }
+            None => mismatches.push(format!("{name}: missing from GOLDEN")),
+        }
+    }
+    for (name, _) in GOLDEN {
+        if !actual.iter().any(|(n, _)| n == name) {
+            mismatches.push(format!("{name}: no longer rendered"));
+        }
+    }
+
+    assert!(
+        mismatches.is_empty(),
+        "transitions changed:\n{}\n\nif this is intentional, update GOLDEN to:\n{table}",
+        mismatches.join("\n")
+    );
+}